@@ -0,0 +1,258 @@
+// Copyright 2016 Bruno Medeiros
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>.
+// This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! A small language server for a toy language, exercising the main parts of the crate:
+//! the document store, diagnostics publishing on change, completion, hover, formatting and progress.
+//!
+//! A toy document is a list of definitions `name = value`, one per line.
+//! Blank lines and lines starting with `#` are ignored.
+//!
+//! Run it with `cargo run --example toy_server`, speaking LSP on stdin and stdout.
+
+extern crate rust_lsp;
+extern crate serde_json;
+extern crate url;
+
+use std::io;
+use std::time::Duration;
+
+use rust_lsp::ls_types::*;
+use rust_lsp::lsp::*;
+use rust_lsp::lsp_completion::CompletionItemBuilder;
+use rust_lsp::lsp_completion::LSCompletionList;
+use rust_lsp::lsp_diagnostics::DiagnosticBuilder;
+use rust_lsp::lsp_diagnostics::DiagnosticsPublisher;
+use rust_lsp::lsp_diagnostics::LSDiagnostic;
+use rust_lsp::lsp_documents::TextDocuments;
+use rust_lsp::lsp_initialize::InitializeResultBuilder;
+use rust_lsp::lsp_markup::MarkupContent;
+use rust_lsp::lsp_markup::MarkupTarget;
+use rust_lsp::lsp_methods as methods;
+use rust_lsp::lsp_output::NotificationSender;
+use rust_lsp::lsp_output::split_endpoint;
+use rust_lsp::lsp_transport::LSPBufferedMessageReader;
+use rust_lsp::lsp_transport::LSPBufferedMessageWriter;
+use rust_lsp::jsonrpc::json_util::JsonObject;
+use serde_json::Value;
+use url::Url;
+
+/* ----------------- Toy language ----------------- */
+
+/// A definition `name = value`, at given line.
+struct Definition<'a> {
+    line: u64,
+    name: &'a str,
+    value: &'a str,
+}
+
+/// The definitions of given document, and the errors of the lines that are not definitions.
+fn parse(text: &str) -> (Vec<Definition>, Vec<LSDiagnostic>) {
+    let mut definitions = vec![];
+    let mut errors = vec![];
+    for (line_ix, line) in text.lines().enumerate() {
+        let line_ix = line_ix as u64;
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        let range = Range {
+            start : Position { line : line_ix, character : 0 },
+            end : Position { line : line_ix, character : line.chars().count() as u64 },
+        };
+        let mut parts = trimmed.splitn(2, '=');
+        let name = parts.next().unwrap_or("").trim();
+        match parts.next() {
+            Some(value) if is_identifier(name) => {
+                definitions.push(Definition { line : line_ix, name : name, value : value.trim() });
+            }
+            Some(_) => {
+                errors.push(DiagnosticBuilder::error(range, &format!("`{}` is not a valid name.", name))
+                    .source("toy").build());
+            }
+            None => {
+                errors.push(DiagnosticBuilder::error(range, "Expected a definition `name = value`.")
+                    .source("toy").code("expected-definition").build());
+            }
+        }
+    }
+    (definitions, errors)
+}
+
+fn is_identifier(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|ch| ch.is_alphanumeric() || ch == '_')
+}
+
+/// The identifier at given position of given text, if any.
+fn word_at(text: &str, position: &Position) -> Option<String> {
+    let line = match text.lines().nth(position.line as usize) {
+        Some(line) => line,
+        None => return None,
+    };
+    let chars : Vec<char> = line.chars().collect();
+    let column = (position.character as usize).min(chars.len());
+    let is_word_char = |ch: &char| ch.is_alphanumeric() || *ch == '_';
+    let start = chars[..column].iter().rposition(|ch| !is_word_char(ch)).map_or(0, |ix| ix + 1);
+    let end = chars[column..].iter().position(|ch| !is_word_char(ch)).map_or(chars.len(), |ix| column + ix);
+    if start == end {
+        return None;
+    }
+    Some(chars[start..end].iter().collect())
+}
+
+/* ----------------- Server ----------------- */
+
+struct ToyServer {
+    context: LSServerContext,
+    documents: TextDocuments,
+    diagnostics: DiagnosticsPublisher,
+    notifications: NotificationSender,
+    next_progress: u64,
+}
+
+impl ToyServer {
+
+    fn new(context: LSServerContext) -> ToyServer {
+        let notifications = context.notification_sender().expect("The context must have an output channel.");
+        let publish_notifications = notifications.clone();
+        let diagnostics = DiagnosticsPublisher::with_sender(Duration::from_millis(200), move |params| {
+            let _ = publish_notifications.publish_diagnostics(&params);
+        });
+        ToyServer {
+            context : context, documents : TextDocuments::new(), diagnostics : diagnostics,
+            notifications : notifications, next_progress : 0,
+        }
+    }
+
+    fn analyze(&self, uri: &Url) {
+        if let Some(document) = self.documents.get(uri) {
+            let (_, errors) = parse(document.text());
+            self.diagnostics.publish(uri.clone(), errors);
+        }
+    }
+
+    /// Report the progress of a full analysis of the open documents.
+    fn analyze_all(&mut self) {
+        self.next_progress += 1;
+        let token = Value::String(format!("toy-analysis-{}", self.next_progress));
+        let _ = self.notifications.progress(&token, &progress_value("begin", "Analyzing documents"));
+
+        let uris : Vec<Url> = self.documents.iter().map(|document| document.uri.clone()).collect();
+        for uri in &uris {
+            self.analyze(uri);
+        }
+        self.diagnostics.flush();
+
+        let _ = self.notifications.progress(&token, &progress_value("end", "Analyzed documents"));
+    }
+
+}
+
+fn progress_value(kind: &str, message: &str) -> Value {
+    let mut value = JsonObject::new();
+    value.insert("kind".to_string(), Value::String(kind.to_string()));
+    value.insert("title".to_string(), Value::String("Toy".to_string()));
+    value.insert("message".to_string(), Value::String(message.to_string()));
+    Value::Object(value)
+}
+
+impl LanguageServerHandling for ToyServer {
+
+    fn server_capabilities(&self) -> ServerCapabilities {
+        InitializeResultBuilder::new()
+            .text_document_sync(TextDocumentSyncKind::Incremental)
+            .hover()
+            .completion(CompletionOptions::default())
+            .formatting()
+            .build().capabilities
+    }
+
+    fn did_open_text_document(&mut self, params: DidOpenTextDocumentParams) {
+        let uri = params.text_document.uri.clone();
+        self.documents.did_open(params);
+        self.analyze(&uri);
+    }
+
+    fn did_change_text_document(&mut self, params: DidChangeTextDocumentParams) {
+        let uri = params.text_document.uri.clone();
+        if let Err(error) = self.documents.did_change(params) {
+            let _ = self.context.log_error(&format!("Failed to apply change: {}", error));
+        }
+        self.analyze(&uri);
+    }
+
+    fn did_save_text_document(&mut self, params: DidSaveTextDocumentParams) {
+        let _ = self.documents.did_save(params);
+        self.analyze_all();
+    }
+
+    fn did_close_text_document(&mut self, params: DidCloseTextDocumentParams) {
+        self.diagnostics.clear(&params.text_document.uri);
+        self.documents.did_close(params);
+    }
+
+    fn completion(&mut self, params: TextDocumentPositionParams, completable: LSMethodCompletable<methods::Completion>) {
+        let items = match self.documents.get(&params.text_document.uri) {
+            Some(document) => parse(document.text()).0.iter().map(|definition| {
+                CompletionItemBuilder::new(definition.name)
+                    .kind(CompletionItemKind::Variable)
+                    .detail(definition.value)
+                    .build()
+            }).collect(),
+            None => vec![],
+        };
+        completable.complete(Ok(LSCompletionList::new(items)))
+    }
+
+    fn hover(&mut self, params: TextDocumentPositionParams, completable: LSMethodCompletable<methods::HoverRequest>) {
+        let mut markup = MarkupContent::plain_text("");
+        if let Some(document) = self.documents.get(&params.text_document.uri) {
+            if let Some(word) = word_at(document.text(), &params.position) {
+                let (definitions, _) = parse(document.text());
+                if let Some(definition) = definitions.iter().find(|definition| definition.name == word) {
+                    markup = self.context.markup_builder(MarkupTarget::Hover)
+                        .code_block("toy", &format!("{} = {}", definition.name, definition.value))
+                        .text(&format!("Defined at line {}.", definition.line + 1))
+                        .build();
+                }
+            }
+        }
+        let hover = LSHover::from(markup).into_supported(self.context.client_capabilities().as_ref());
+        completable.complete(Ok(hover))
+    }
+
+    fn formatting(&mut self, params: DocumentFormattingParams, completable: LSMethodCompletable<methods::Formatting>) {
+        let edits = match self.documents.get(&params.text_document.uri) {
+            Some(document) => {
+                let text = document.text();
+                let formatted : Vec<String> = text.lines().map(|line| {
+                    match parse(line).0.first() {
+                        Some(definition) => format!("{} = {}", definition.name, definition.value),
+                        None => line.trim_right().to_string(),
+                    }
+                }).collect();
+                let end = Position { line : text.lines().count() as u64 + 1, character : 0 };
+                vec![TextEdit {
+                    range : Range { start : Position { line : 0, character : 0 }, end : end },
+                    new_text : formatted.join("\n") + "\n",
+                }]
+            }
+            None => vec![],
+        };
+        completable.complete(Ok(edits))
+    }
+
+}
+
+fn main() {
+    let (_sender, dispatcher) = split_endpoint(|| LSPBufferedMessageWriter::new(io::stdout()));
+    let context = dispatcher.server_context();
+    let server = ToyServer::new(context.clone());
+
+    let stdin = io::stdin();
+    let exit_code = dispatcher.run_server(&mut LSPBufferedMessageReader::new(stdin.lock()), context, server);
+    std::process::exit(exit_code);
+}
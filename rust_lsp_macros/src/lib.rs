@@ -0,0 +1,148 @@
+// Copyright 2016 Bruno Medeiros
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>.
+// This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Procedural macros of `rust_lsp`, re-exported by it with the `derive` feature.
+//!
+//! `#[lsp_routes]` annotates an inherent impl of a server, whose handler methods are annotated
+//! with the LSP method they handle. It implements `LanguageServerHandling` with the `lsp_server!`
+//! macro of `rust_lsp`, which routes the methods to the handlers, and advertises the capabilities:
+//!
+//! ```ignore
+//! #[macro_use] extern crate rust_lsp;
+//!
+//! #[lsp_routes]
+//! impl MyServer {
+//!     #[lsp(method = "textDocument/hover")]
+//!     fn my_hover(&mut self, params: TextDocumentPositionParams, completable: LSMethodCompletable<HoverRequest>) {
+//!         ...
+//!     }
+//! }
+//! ```
+
+extern crate proc_macro;
+extern crate proc_macro2;
+#[macro_use]
+extern crate quote;
+#[macro_use]
+extern crate syn;
+
+use proc_macro::TokenStream;
+use proc_macro2::Ident;
+use syn::Attribute;
+use syn::ImplItem;
+use syn::ItemImpl;
+use syn::Lit;
+use syn::Meta;
+use syn::NestedMeta;
+
+/// Implement `LanguageServerHandling` for the type of the annotated impl, with its methods
+/// annotated with `#[lsp(method = "...")]` as the handlers of those LSP methods.
+#[proc_macro_attribute]
+pub fn lsp_routes(args: TokenStream, input: TokenStream) -> TokenStream {
+    if !args.is_empty() {
+        let args = proc_macro2::TokenStream::from(args);
+        return syn::Error::new_spanned(args, "`lsp_routes` takes no arguments").to_compile_error().into();
+    }
+    let mut item = parse_macro_input!(input as ItemImpl);
+
+    let mut trait_methods = vec![];
+    let mut handlers = vec![];
+    let mut errors = vec![];
+    for impl_item in &mut item.items {
+        let method = match *impl_item {
+            ImplItem::Method(ref mut method) => method,
+            _ => continue,
+        };
+        let (lsp_attrs, other_attrs) : (Vec<Attribute>, Vec<Attribute>) =
+            method.attrs.drain(..).partition(|attr| attr.path.is_ident("lsp"));
+        method.attrs = other_attrs;
+        for attr in lsp_attrs {
+            match route_of(&attr) {
+                Ok(trait_method) => {
+                    trait_methods.push(trait_method);
+                    handlers.push(method.sig.ident.clone());
+                }
+                Err(error) => errors.push(error.to_compile_error()),
+            }
+        }
+    }
+
+    let self_ty = &item.self_ty;
+    let output = quote! {
+        #item
+
+        lsp_server! {
+            impl LanguageServerHandling for #self_ty {
+                #( #trait_methods => #handlers, )*
+            }
+        }
+
+        #( #errors )*
+    };
+    output.into()
+}
+
+/// The `LanguageServerHandling` method for the LSP method of given `#[lsp(method = "...")]` attribute.
+fn route_of(attr: &Attribute) -> Result<Ident, syn::Error> {
+    let lsp_method = try!(lsp_method_of(attr));
+    match trait_method_of(&lsp_method) {
+        Some(trait_method) => Ok(Ident::new(trait_method, proc_macro2::Span::call_site())),
+        None => Err(syn::Error::new_spanned(attr, format!("LSP method `{}` is not supported by `lsp_server!`", lsp_method))),
+    }
+}
+
+fn lsp_method_of(attr: &Attribute) -> Result<String, syn::Error> {
+    if let Meta::List(list) = try!(attr.parse_meta()) {
+        if list.nested.len() == 1 {
+            if let NestedMeta::Meta(Meta::NameValue(ref name_value)) = list.nested[0] {
+                if let Lit::Str(ref method) = name_value.lit {
+                    if name_value.path.is_ident("method") {
+                        return Ok(method.value());
+                    }
+                }
+            }
+        }
+    }
+    Err(syn::Error::new_spanned(attr, "expected `#[lsp(method = \"...\")]`"))
+}
+
+/// The methods that `lsp_server!` can route, as (LSP method, `LanguageServerHandling` method).
+const ROUTES: &'static [(&'static str, &'static str)] = &[
+    ("initialize", "initialize"),
+    ("shutdown", "shutdown"),
+    ("exit", "exit"),
+    ("workspace/didChangeConfiguration", "workspace_change_configuration"),
+    ("workspace/didChangeWatchedFiles", "did_change_watched_files"),
+    ("workspace/symbol", "workspace_symbols"),
+    ("textDocument/didOpen", "did_open_text_document"),
+    ("textDocument/didChange", "did_change_text_document"),
+    ("textDocument/didClose", "did_close_text_document"),
+    ("textDocument/didSave", "did_save_text_document"),
+    ("textDocument/completion", "completion"),
+    ("completionItem/resolve", "resolve_completion_item"),
+    ("textDocument/hover", "hover"),
+    ("textDocument/signatureHelp", "signature_help"),
+    ("textDocument/definition", "goto_definition"),
+    ("textDocument/typeDefinition", "goto_type_definition"),
+    ("textDocument/implementation", "goto_implementation"),
+    ("textDocument/references", "references"),
+    ("textDocument/documentHighlight", "document_highlight"),
+    ("textDocument/documentSymbol", "document_symbols"),
+    ("textDocument/codeAction", "code_action"),
+    ("textDocument/codeLens", "code_lens"),
+    ("codeLens/resolve", "code_lens_resolve"),
+    ("textDocument/documentLink", "document_link"),
+    ("documentLink/resolve", "document_link_resolve"),
+    ("textDocument/formatting", "formatting"),
+    ("textDocument/rangeFormatting", "range_formatting"),
+    ("textDocument/onTypeFormatting", "on_type_formatting"),
+    ("textDocument/rename", "rename"),
+];
+
+fn trait_method_of(lsp_method: &str) -> Option<&'static str> {
+    ROUTES.iter().find(|route| route.0 == lsp_method).map(|route| route.1)
+}
@@ -17,6 +17,7 @@ https://github.com/RustDT/RustLSP/blob/master/src/server_tests.rs
 
 extern crate serde_json;
 extern crate serde;
+extern crate url;
 
 pub extern crate rustdt_util as util;
 pub extern crate jsonrpc;
@@ -24,7 +25,65 @@ pub extern crate languageserver_types as ls_types;
 
 #[macro_use] extern crate log;
 
+#[cfg(feature = "async-tokio")] extern crate bytes;
+#[cfg(feature = "async-tokio")] extern crate futures;
+#[cfg(feature = "async-tokio")] extern crate tokio;
+#[cfg(feature = "lsp-types-interop")] extern crate lsp_types;
+#[cfg(feature = "lsp-types-interop")] extern crate serde1;
+#[cfg(feature = "lsp-types-interop")] extern crate serde1_json;
+#[cfg(feature = "derive")] extern crate rust_lsp_macros;
+
+#[cfg(feature = "derive")] pub use rust_lsp_macros::lsp_routes;
+
+#[macro_use] mod lsp_macros;
+
 pub mod lsp_transport;
+pub mod lsp_adapters;
+pub mod lsp_codec;
+pub mod lsp_raw;
+pub mod lsp_json_util;
+pub mod lsp_output;
+pub mod lsp_capabilities;
+pub mod lsp_errors;
+pub mod lsp_initialize;
+pub mod lsp_methods;
+pub mod lsp_enums;
+pub mod lsp_protocol;
+pub mod lsp_cancellation;
+pub mod lsp_scheduler;
+pub mod lsp_position;
+pub mod lsp_documents;
+pub mod lsp_diagnostics;
+pub mod lsp_markup;
+pub mod lsp_completion;
+pub mod lsp_symbols;
+pub mod lsp_definition;
+pub mod lsp_uri;
+pub mod lsp_workspace;
+pub mod lsp_workspace_edit;
+pub mod lsp_config;
+#[cfg(feature = "lsp-types-interop")]
+pub mod lsp_interop;
+pub mod lsp_inline;
+pub mod lsp_http_bridge;
+pub mod lsp_launcher;
+pub mod lsp_client;
+pub mod lsp_proxy;
+pub mod lsp_dispatch;
+pub mod lsp_router;
+pub mod lsp_multiplex;
+#[cfg(feature = "async-tokio")]
+pub mod lsp_async;
+pub mod lsp_testing;
+pub mod lsp_transcript;
+pub mod lsp_logging;
+pub mod lsp_metrics;
+pub mod lsp_trace;
+pub mod lsp_watchdog;
+pub mod lsp_correlation;
+pub mod lsp_conformance;
+pub mod lsp_openrpc;
+pub mod lsp_schema;
 pub mod lsp;
 
 #[cfg(test)]
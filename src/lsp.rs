@@ -7,22 +7,72 @@
 
 
 use std::io;
+use std::sync::Arc;
+use std::sync::Mutex;
 
 use util::core::*;
 
 use jsonrpc::*;
 pub use jsonrpc::service_util::MessageReader;
 pub use jsonrpc::service_util::MessageWriter;
+pub use lsp_transport::RequestInfo;
 
 use jsonrpc::output_agent::OutputAgent;
 
+use jsonrpc::jsonrpc_common::Id;
 use jsonrpc::method_types::MethodError;
 use jsonrpc::jsonrpc_request::RequestParams;
 
-use lsp_transport::LSPMessageWriter;
-use lsp_transport::LSPMessageReader;
+use lsp_transport::LSPBufferedMessageWriter;
+use lsp_transport::LSPBufferedMessageReader;
+use lsp_transport::RecoveringMessageReader;
+use lsp_transport::parse_jsonrpc_request_bytes;
+use lsp_capabilities::LSClientCapabilities;
+use lsp_methods::LSRequest;
+use lsp_methods::LSNotification;
+use lsp_errors::*;
+use lsp_markup::MarkupBuilder;
+use lsp_markup::MarkupKind;
+use lsp_markup::MarkupTarget;
+use lsp_metrics::MetricsRecorder;
+use lsp_cancellation::RequestCancellation;
+use lsp_output::NotificationSender;
+use lsp_output::OutputSender;
+use lsp_protocol::CallHierarchyIncomingCallsParams;
+use lsp_protocol::CallHierarchyOutgoingCallsParams;
+use lsp_protocol::ConfigurationParams;
+use lsp_protocol::DocumentDiagnosticParams;
+use lsp_protocol::FoldingRangeParams;
+use lsp_protocol::InlayHintParams;
+use lsp_protocol::SelectionRangeParams;
+use lsp_protocol::SemanticTokensParams;
+use lsp_protocol::SemanticTokensRangeParams;
+use lsp_protocol::ShowDocumentParams;
+use lsp_schema::ParamsValidator;
+use lsp_schema::error_InvalidParams;
+use lsp_scheduler::BackgroundTasks;
+use lsp_scheduler::DEFAULT_BACKGROUND_WORKERS;
+use lsp_scheduler::document_uri_of_params;
+use lsp_trace::TraceValue;
+use lsp_trace::Tracer;
+use lsp_watchdog::SlowRequestDetector;
+pub use lsp_initialize::LSInitializeResult;
+pub use lsp_initialize::InitializeResultBuilder;
+pub use lsp_completion::LSCompletionItem;
+pub use lsp_completion::LSCompletionList;
+pub use lsp_definition::LSDefinitionResponse;
+pub use lsp_protocol::LSHover;
+pub use lsp_protocol::LSSignatureHelp;
+pub use lsp_diagnostics::LSPublishDiagnosticsParams;
+pub use lsp_symbols::LSDocumentSymbolResponse;
+pub use lsp_workspace_edit::LSWorkspaceEdit;
 use ls_types::*;
+use serde;
+use serde_json;
 use serde_json::Value;
+use url::Url;
+
+pub use lsp_methods as methods;
 
 /* -----------------  ----------------- */
 
@@ -42,7 +92,7 @@ impl LSPEndpoint {
         OUT_PROV : FnOnce() -> OUT + Send + 'static
     {
         Self::create_lsp_output(|| {
-            LSPMessageWriter(output_stream_provider())
+            LSPBufferedMessageWriter::new(output_stream_provider())
         })
     }
     
@@ -61,240 +111,924 @@ impl LSPEndpoint {
     /* -----------------  ----------------- */
     
     pub fn run_server_from_input<SERVER>(
-        input: &mut io::BufRead, endpoint: Endpoint, lsp_server_handler: SERVER, 
-    ) 
+        input: &mut io::BufRead, context: LSServerContext, lsp_server_handler: SERVER, 
+    ) -> i32
     where 
         SERVER : LanguageServerHandling + 'static,
     {
-        Self::run_server(&mut LSPMessageReader(input), endpoint, lsp_server_handler)
+        Self::run_server(&mut LSPBufferedMessageReader::new(input), context, lsp_server_handler)
     }
     
     /// Run the message read loop on the server, for given msg_reader.
     /// msg_reader must be a LSPMessageReader or compatible.
+    /// 
+    /// The loop terminates on the `exit` notification (or end of input), after which pending output
+    /// is written out. Returns the process exit code the spec requires: 
+    /// 0 if `shutdown` was received before, 1 otherwise.
     pub fn run_server<SERVER, MR>(
-        mut msg_reader: &mut MR, endpoint: Endpoint, lsp_server_handler: SERVER
-    ) 
+        mut msg_reader: &mut MR, context: LSServerContext, lsp_server_handler: SERVER
+    ) -> i32
     where 
         SERVER : LanguageServerHandling + 'static,
         MR : MessageReader,
     {
-        Self::run_endpoint_loop(msg_reader, endpoint, new(ServerRequestHandler(lsp_server_handler)))
+        let server_handler = new(ServerRequestHandler { context : context.clone(), ls : lsp_server_handler });
+        Self::run_server_with_handler(msg_reader, context, server_handler)
+    }
+    
+    /// Run the message read loop on the server, as `run_server` does, with given request handler, 
+    /// which dispatches the messages to the server, such as the handlers of `lsp_dispatch`.
+    pub fn run_server_with_handler<MR>(
+        mut msg_reader: &mut MR, context: LSServerContext, server_handler: Box<LSRequestHandler>
+    ) -> i32
+    where 
+        MR : MessageReader,
+    {
+        let mut endpoint = context.endpoint.clone();
+        let mut msg_reader = RecoveringMessageReader::new(msg_reader);
+        if let Some(output) = context.output_sender() {
+            msg_reader = msg_reader.with_error_responses(Box::new(output.clone()));
+        }
+        let _ = Self::run_endpoint_loop(&mut msg_reader, endpoint.clone(), server_handler);
+        
+        // In case the input ended without `shutdown`
+        context.background_tasks().shutdown();
+        context.set_state(ServerState::Exited);
+        endpoint.shutdown_and_join();
+        context.exit_code()
     }
     
     pub fn run_client_from_input<CLIENT>(
         input: &mut io::BufRead, endpoint: Endpoint, lsp_client_handler: CLIENT,
-    ) 
+    ) -> Result<(), LSError>
     where 
         CLIENT : LanguageClientHandling + 'static,
     {
         let cl_handler = new(ClientRequestHandler(lsp_client_handler));
-        Self::run_endpoint_loop(&mut LSPMessageReader(input), endpoint, cl_handler)
+        Self::run_endpoint_loop(&mut LSPBufferedMessageReader::new(input), endpoint, cl_handler)
     }
     
+    /// Run the message read loop for given request handler, until the endpoint is shut down 
+    /// or the input fails. The end of the input is reported as a `LSError::Shutdown`.
+    /// The messages that fail to be read with a recoverable error are skipped.
     pub fn run_endpoint_loop<MR>(
-        mut msg_reader: &mut MR, endpoint: Endpoint, request_handler: Box<RequestHandler>
-    ) 
+        mut msg_reader: &mut MR, endpoint: Endpoint, request_handler: Box<LSRequestHandler>
+    ) -> Result<(), LSError>
     where 
         MR : MessageReader,
     {
         info!("Starting LSP Endpoint");
         
-        let endpoint = EndpointHandler::create(endpoint, request_handler);
-        
-        let result = endpoint.run_message_read_loop(msg_reader);
+        let mut msg_reader = RecoveringMessageReader::new(msg_reader);
+        let result = Self::read_messages(&mut msg_reader, endpoint, request_handler);
         
-        if let Err(error) = result {
-            error!("Error handling the incoming stream: {}", error);
+        match result {
+            Err(ref error) if error.is_shutdown() => {
+                info!("LSP Endpoint input ended: {}", error);
+            }
+            Err(ref error) => {
+                error!("Error handling the incoming stream: {}", error);
+            }
+            Ok(_) => {}
+        }
+        result
+    }
+    
+    /// Give each request or notification read to the request handler, with its `RequestInfo`, and the other
+    /// messages, such as the responses to the requests of this endpoint, to the endpoint.
+    fn read_messages<MR>(
+        msg_reader: &mut MR, endpoint: Endpoint, mut request_handler: Box<LSRequestHandler>
+    ) -> Result<(), LSError>
+    where 
+        MR : MessageReader,
+    {
+        let mut endpoint_handler = EndpointHandler::create(endpoint.clone(), Box::new(InvalidRequestHandler));
+        loop {
+            let message = try!(msg_reader.read_next().map_err(LSError::from_gerror));
+            let request = parse_jsonrpc_request_bytes(message.as_bytes()).ok();
+            match request.map(|request| (request_completable_id(&request.id), request)) {
+                Some((Some(completable_id), request)) => {
+                    let completable = ResponseCompletable::new(completable_id, endpoint.output_agent.clone());
+                    let params = request_params_of_value(request.params);
+                    let request_info = match request.id {
+                        Some(id) => RequestInfo::request(id),
+                        None => RequestInfo::notification(),
+                    };
+                    request_handler.handle_ls_request(request_info, &request.method, params, completable);
+                }
+                _ => endpoint_handler.handle_incoming_message(&message),
+            }
+            if endpoint.is_shutdown() {
+                return Ok(());
+            }
         }
     }
     
 }
 
+/// The id of the completable of a request with given id: `Some(None)` for a notification,
+/// `None` if the endpoint can't answer the id.
+fn request_completable_id(id: &Option<Value>) -> Option<Option<Id>> {
+    match *id {
+        None => Some(None),
+        Some(ref id) => serde_json::from_value(id.clone()).ok().map(Some),
+    }
+}
+
+/// The params of a request, as the endpoint gives them to its request handler.
+fn request_params_of_value(params: Value) -> RequestParams {
+    match params {
+        Value::Object(object) => RequestParams::Object(object),
+        Value::Array(array) => RequestParams::Array(array),
+        _ => RequestParams::None,
+    }
+}
+
+/// Answers the messages that the read loop could not parse as a request, but the endpoint did.
+struct InvalidRequestHandler;
+
+impl RequestHandler for InvalidRequestHandler {
+    fn handle_request(&mut self, _: &str, _: RequestParams, completable: ResponseCompletable) {
+        completable.complete_with_error(jsonrpc_common::error_JSON_RPC_InvalidRequest())
+    }
+}
+
+/// The lifecycle state of a server connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ServerState {
+    /// Before the `initialize` request.
+    Uninitialized,
+    /// Handling the `initialize` request.
+    Initializing,
+    /// After `initialize`, from the first message the client sends once it has the result.
+    Running,
+    /// After the `shutdown` request.
+    ShuttingDown,
+    /// After the `exit` notification, or the end of the input.
+    Exited,
+}
+
+/// A callback for the transitions of the server state, called with the previous and the new state.
+pub type StateChangeCallback = Box<FnMut(ServerState, ServerState) + Send>;
+
+/// State of an LSP server connection that is maintained by the library.
+/// Clones share the same state, so a server implementation can keep a clone
+/// of the context given to `LSPEndpoint::run_server`.
+#[derive(Clone)]
+pub struct LSServerContext {
+    pub endpoint: Endpoint,
+    output: Option<OutputSender>,
+    metrics: Option<MetricsRecorder>,
+    tracer: Option<Tracer>,
+    params_validator: Option<ParamsValidator>,
+    background_tasks: BackgroundTasks,
+    cancellation: RequestCancellation,
+    client_capabilities: Arc<Mutex<Option<LSClientCapabilities>>>,
+    shutdown_received: Arc<Mutex<bool>>,
+    state: Arc<Mutex<ServerState>>,
+    state_callbacks: Arc<Mutex<Vec<StateChangeCallback>>>,
+}
+
+impl LSServerContext {
+    
+    pub fn new(endpoint: Endpoint) -> LSServerContext {
+        LSServerContext { 
+            endpoint : endpoint, 
+            output : None,
+            metrics : None,
+            tracer : None,
+            params_validator : None,
+            background_tasks : BackgroundTasks::new(DEFAULT_BACKGROUND_WORKERS),
+            cancellation : RequestCancellation::new(),
+            client_capabilities : newArcMutex(None),
+            shutdown_received : newArcMutex(false),
+            state : newArcMutex(ServerState::Uninitialized),
+            state_callbacks : newArcMutex(vec![]),
+        }
+    }
+    
+    /// Create a context for an endpoint created with `lsp_output::create_endpoint_with_channel`,
+    /// with a sender of that channel, so that notifications don't go through the endpoint.
+    pub fn new_with_output(endpoint: Endpoint, output: OutputSender) -> LSServerContext {
+        let mut context = LSServerContext::new(endpoint);
+        context.output = Some(output);
+        context
+    }
+    
+    /// Record the metrics of the requests dispatched to the server with given recorder.
+    pub fn with_metrics(mut self, recorder: MetricsRecorder) -> LSServerContext {
+        self.metrics = Some(recorder);
+        self
+    }
+    
+    /// Trace the messages to the client with given tracer, which must wrap the reader and writer
+    /// of the endpoint.
+    pub fn with_tracer(mut self, tracer: Tracer) -> LSServerContext {
+        tracer.attach(self.endpoint.clone());
+        self.tracer = Some(tracer);
+        self
+    }
+    
+    /// Report the slow requests with given detector, which must wrap the reader and writer
+    /// of the endpoint.
+    pub fn with_slow_request_detector(self, detector: SlowRequestDetector) -> LSServerContext {
+        detector.attach(self.endpoint.clone());
+        self
+    }
+    
+    /// Validate the params of incoming messages with given validator, before dispatching them.
+    pub fn with_params_validator(mut self, validator: ParamsValidator) -> LSServerContext {
+        self.params_validator = Some(validator);
+        self
+    }
+    
+    /// Run the background tasks of the server with given tasks, instead of the default ones,
+    /// such as to use a different number of workers.
+    pub fn with_background_tasks(mut self, background_tasks: BackgroundTasks) -> LSServerContext {
+        self.background_tasks = background_tasks;
+        self
+    }
+    
+    /// The background tasks of the server. The tasks of a document are cancelled when it changes
+    /// or is closed, and all tasks are cancelled and waited for on `shutdown`.
+    pub fn background_tasks(&self) -> &BackgroundTasks {
+        &self.background_tasks
+    }
+    
+    /// Track the requests to cancel with given tracker, instead of a new one, such as to cancel them
+    /// from elsewhere.
+    pub fn with_request_cancellation(mut self, cancellation: RequestCancellation) -> LSServerContext {
+        self.cancellation = cancellation;
+        self
+    }
+    
+    /// The tracker of the requests being dispatched, which `$/cancelRequest` cancels.
+    pub fn request_cancellation(&self) -> &RequestCancellation {
+        &self.cancellation
+    }
+    
+    /// Begin the handling of given message, in the thread that reads it, before it is dispatched
+    /// (see `ServerRequestHandler::dispatch_begun_request`), which may be later, in another thread.
+    /// A change or close of a document cancels the requests of that document, and a request is tracked
+    /// with its token right away, so that it can be cancelled while it waits to be dispatched.
+    pub fn begin_request(&self, mut request: RequestInfo, method_name: &str, params: &RequestParams) 
+        -> RequestInfo 
+    {
+        let document_uri = document_uri_of_params(params);
+        if method_name == methods::TEXT_DOCUMENT_DID_CHANGE || method_name == methods::TEXT_DOCUMENT_DID_CLOSE {
+            if let Some(uri) = document_uri {
+                self.background_tasks.cancel_document(uri);
+                self.cancellation.cancel_document(uri);
+            }
+        }
+        request.cancellation = self.cancellation.begin_request(request.id.as_ref(), document_uri);
+        request
+    }
+    
+    /// The trace level the client set, `Off` if there is no tracer.
+    pub fn trace_value(&self) -> TraceValue {
+        self.tracer.as_ref().map_or(TraceValue::Off, Tracer::trace_value)
+    }
+    
+    /// The sender for the output channel of the endpoint, if there is one.
+    pub fn output_sender(&self) -> Option<&OutputSender> {
+        self.output.as_ref()
+    }
+    
+    /// A notification sender for worker threads, if the endpoint has an output channel.
+    pub fn notification_sender(&self) -> Option<NotificationSender> {
+        self.output.clone().map(NotificationSender::new)
+    }
+    
+    /// Send a notification for LSP method `NOTIF` to the client, through the output sender
+    /// if there is one, otherwise through the endpoint.
+    pub fn send_notification<NOTIF>(&self, params: NOTIF::Params) -> LSSendResult<()>
+    where 
+        NOTIF : LSNotification,
+    {
+        match self.output {
+            Some(ref output) => output.send_lsp_notification::<NOTIF>(&params),
+            None => send_lsp_notification::<NOTIF>(&mut self.endpoint.clone(), params),
+        }
+    }
+    
+    /// Send a `window/showMessage` notification of given type.
+    pub fn show_message(&self, typ: MessageType, message: &str) -> LSSendResult<()> {
+        let params = ShowMessageParams { typ : typ, message : message.to_string() };
+        self.send_notification::<methods::ShowMessage>(params)
+    }
+    
+    pub fn show_error(&self, message: &str) -> LSSendResult<()> {
+        self.show_message(MessageType::Error, message)
+    }
+    
+    pub fn show_warning(&self, message: &str) -> LSSendResult<()> {
+        self.show_message(MessageType::Warning, message)
+    }
+    
+    pub fn show_info(&self, message: &str) -> LSSendResult<()> {
+        self.show_message(MessageType::Info, message)
+    }
+    
+    /// Send a `window/logMessage` notification of given type.
+    pub fn log_message(&self, typ: MessageType, message: &str) -> LSSendResult<()> {
+        let params = LogMessageParams { typ : typ, message : message.to_string() };
+        self.send_notification::<methods::LogMessage>(params)
+    }
+    
+    pub fn log_error(&self, message: &str) -> LSSendResult<()> {
+        self.log_message(MessageType::Error, message)
+    }
+    
+    pub fn log_warning(&self, message: &str) -> LSSendResult<()> {
+        self.log_message(MessageType::Warning, message)
+    }
+    
+    pub fn log_info(&self, message: &str) -> LSSendResult<()> {
+        self.log_message(MessageType::Info, message)
+    }
+    
+    /// Whether the `initialize` request has been received.
+    pub fn is_initialize_received(&self) -> bool {
+        self.client_capabilities.lock().unwrap().is_some()
+    }
+    
+    /// Whether the `shutdown` request has been received.
+    pub fn is_shutdown_received(&self) -> bool {
+        *self.shutdown_received.lock().unwrap()
+    }
+    
+    /// The lifecycle state of the connection.
+    pub fn state(&self) -> ServerState {
+        *self.state.lock().unwrap()
+    }
+    
+    /// Call given callback on each transition of the lifecycle state, such as to set up resources
+    /// once `Running`, and release them on `ShuttingDown`. It is called in the thread that dispatches
+    /// the messages, after the state changed, and before the message that caused it is handled.
+    pub fn on_state_change<CALLBACK>(&self, callback: CALLBACK) 
+    where 
+        CALLBACK : FnMut(ServerState, ServerState) + Send + 'static,
+    {
+        self.state_callbacks.lock().unwrap().push(Box::new(callback));
+    }
+    
+    fn set_state(&self, new_state: ServerState) {
+        let old_state = {
+            let mut state = self.state.lock().unwrap();
+            if *state == new_state {
+                return;
+            }
+            ::std::mem::replace(&mut *state, new_state)
+        };
+        for callback in self.state_callbacks.lock().unwrap().iter_mut() {
+            callback(old_state, new_state);
+        }
+    }
+    
+    /// The process exit code to use after `exit`: 0 if `shutdown` was received before, 1 otherwise.
+    pub fn exit_code(&self) -> i32 {
+        if self.is_shutdown_received() { 0 } else { 1 }
+    }
+    
+    /// The capabilities sent by the client on `initialize`, 
+    /// or None if the server has not been initialized yet.
+    pub fn client_capabilities(&self) -> Option<LSClientCapabilities> {
+        self.client_capabilities.lock().unwrap().clone()
+    }
+    
+    pub fn set_client_capabilities(&self, client_capabilities: LSClientCapabilities) {
+        *self.client_capabilities.lock().unwrap() = Some(client_capabilities);
+    }
+    
+    /// A builder of markup for given target, in markdown if the client supports it for the target,
+    /// in plain text otherwise (or before `initialize`).
+    pub fn markup_builder(&self, target: MarkupTarget) -> MarkupBuilder {
+        match *self.client_capabilities.lock().unwrap() {
+            Some(ref capabilities) => MarkupBuilder::for_client(capabilities, target),
+            None => MarkupBuilder::new(MarkupKind::PlainText),
+        }
+    }
+    
+    /// Whether the connected client can understand given method (see `LSClientCapabilities::supports_method`).
+    /// Before `initialize`, all methods are considered supported.
+    pub fn client_supports_method(&self, method: &str) -> bool {
+        match *self.client_capabilities.lock().unwrap() {
+            Some(ref client_capabilities) => client_capabilities.supports_method(method),
+            None => true,
+        }
+    }
+    
+    /// Convert given response of the definition-family request `method` to the form the connected
+    /// client supports: `LocationLink`s are converted to plain `Location`s unless the client
+    /// declared `linkSupport` for that request.
+    pub fn definition_response(&self, method: &str, response: LSDefinitionResponse) -> LSDefinitionResponse {
+        response.into_supported(method, self.client_capabilities.lock().unwrap().as_ref())
+    }
+    
+    /// Remove from given server capabilities the features the connected client declared it does not support,
+    /// so they are not advertised in the `initialize` result.
+    pub fn restrict_server_capabilities(&self, capabilities: &mut ServerCapabilities) {
+        let supports = |method| self.client_supports_method(method);
+        if !supports(methods::TEXT_DOCUMENT_HOVER) {
+            capabilities.hover_provider = None;
+        }
+        if !supports(methods::TEXT_DOCUMENT_COMPLETION) {
+            capabilities.completion_provider = None;
+        }
+        if !supports(methods::TEXT_DOCUMENT_SIGNATURE_HELP) {
+            capabilities.signature_help_provider = None;
+        }
+        if !supports(methods::TEXT_DOCUMENT_DEFINITION) {
+            capabilities.definition_provider = None;
+        }
+        if !supports(methods::TEXT_DOCUMENT_REFERENCES) {
+            capabilities.references_provider = None;
+        }
+        if !supports(methods::TEXT_DOCUMENT_DOCUMENT_HIGHLIGHT) {
+            capabilities.document_highlight_provider = None;
+        }
+        if !supports(methods::TEXT_DOCUMENT_DOCUMENT_SYMBOL) {
+            capabilities.document_symbol_provider = None;
+        }
+        if !supports(methods::WORKSPACE_SYMBOL) {
+            capabilities.workspace_symbol_provider = None;
+        }
+        if !supports(methods::TEXT_DOCUMENT_CODE_ACTION) {
+            capabilities.code_action_provider = None;
+        }
+        if !supports(methods::TEXT_DOCUMENT_CODE_LENS) {
+            capabilities.code_lens_provider = None;
+        }
+        if !supports(methods::TEXT_DOCUMENT_DOCUMENT_LINK) {
+            capabilities.document_link_provider = None;
+        }
+        if !supports(methods::TEXT_DOCUMENT_FORMATTING) {
+            capabilities.document_formatting_provider = None;
+        }
+        if !supports(methods::TEXT_DOCUMENT_RANGE_FORMATTING) {
+            capabilities.document_range_formatting_provider = None;
+        }
+        if !supports(methods::TEXT_DOCUMENT_ON_TYPE_FORMATTING) {
+            capabilities.document_on_type_formatting_provider = None;
+        }
+        if !supports(methods::TEXT_DOCUMENT_RENAME) {
+            capabilities.rename_provider = None;
+        }
+    }
+    
+}
+
+pub use lsp_methods::LSErrorData;
+
 pub type LSResult<RET, ERR_DATA> = Result<RET, MethodError<ERR_DATA>>;
-pub type LSCompletable<RET> = MethodCompletable<RET, ()>;
+pub type LSCompletable<RET> = MethodCompletable<RET, LSErrorData>;
 
-/// Trait for the handling of LSP server requests
+/// The completable for LSP request method `REQ`, with the result and error data types of that method.
+pub type LSMethodCompletable<REQ> = MethodCompletable<<REQ as LSRequest>::Result, <REQ as LSRequest>::ErrorData>;
+/// The future result of a LSP request method `REQ` sent to the other endpoint.
+pub type LSRequestFuture<REQ> = RequestFuture<<REQ as LSRequest>::Result, <REQ as LSRequest>::ErrorData>;
+/// The result of sending a message to the other endpoint.
+pub type LSSendResult<T> = Result<T, LSError>;
+
+/// The error for a method that the server does not provide.
+pub fn error_not_supported<DATA : Default>() -> MethodError<DATA> {
+    MethodError { code : -32601, message : "Method not supported by the server.".to_string(), data : DATA::default() }
+}
+
+/// Trait for the handling of LSP server requests.
+/// 
+/// Every method has a default implementation: requests other than `initialize` and `shutdown`
+/// fail with `error_not_supported()`, and notifications are ignored. 
+/// So an implementation only needs to override the methods it actually provides.
+/// 
+/// A long-running request can get its cancellation token from its `RequestInfo`, in `before_dispatch`,
+/// and stop once it is cancelled.
+#[allow(unused_variables)]
 pub trait LanguageServerHandling {
     
-    fn initialize(&mut self, params: InitializeParams, completable: MethodCompletable<InitializeResult, InitializeError>);
-    fn shutdown(&mut self, params: (), completable: LSCompletable<()>);
-    fn exit(&mut self, params: ());
-    fn workspace_change_configuration(&mut self, params: DidChangeConfigurationParams);
-    fn did_open_text_document(&mut self, params: DidOpenTextDocumentParams);
-    fn did_change_text_document(&mut self, params: DidChangeTextDocumentParams);
-    fn did_close_text_document(&mut self, params: DidCloseTextDocumentParams);
-    fn did_save_text_document(&mut self, params: DidSaveTextDocumentParams);
-    fn did_change_watched_files(&mut self, params: DidChangeWatchedFilesParams);
-    
-    fn completion(&mut self, params: TextDocumentPositionParams, completable: LSCompletable<CompletionList>);
-    fn resolve_completion_item(&mut self, params: CompletionItem, completable: LSCompletable<CompletionItem>);
-    fn hover(&mut self, params: TextDocumentPositionParams, completable: LSCompletable<Hover>);
-    fn signature_help(&mut self, params: TextDocumentPositionParams, completable: LSCompletable<SignatureHelp>);
-    fn goto_definition(&mut self, params: TextDocumentPositionParams, completable: LSCompletable<Vec<Location>>);
-    fn references(&mut self, params: ReferenceParams, completable: LSCompletable<Vec<Location>>);
-    fn document_highlight(&mut self, params: TextDocumentPositionParams, completable: LSCompletable<Vec<DocumentHighlight>>);
-    fn document_symbols(&mut self, params: DocumentSymbolParams, completable: LSCompletable<Vec<SymbolInformation>>);
-    fn workspace_symbols(&mut self, params: WorkspaceSymbolParams, completable: LSCompletable<Vec<SymbolInformation>>);
-    fn code_action(&mut self, params: CodeActionParams, completable: LSCompletable<Vec<Command>>);
-    fn code_lens(&mut self, params: CodeLensParams, completable: LSCompletable<Vec<CodeLens>>);
-    fn code_lens_resolve(&mut self, params: CodeLens, completable: LSCompletable<CodeLens>);
-    fn document_link(&mut self, params: DocumentLinkParams, completable: LSCompletable<Vec<DocumentLink>>);
-    fn document_link_resolve(&mut self, params: DocumentLink, completable: LSCompletable<DocumentLink>);
-    fn formatting(&mut self, params: DocumentFormattingParams, completable: LSCompletable<Vec<TextEdit>>);
-    fn range_formatting(&mut self, params: DocumentRangeFormattingParams, completable: LSCompletable<Vec<TextEdit>>);
-    fn on_type_formatting(&mut self, params: DocumentOnTypeFormattingParams, completable: LSCompletable<Vec<TextEdit>>);
-    fn rename(&mut self, params: RenameParams, completable: LSCompletable<WorkspaceEdit>);
+    /// Called with each request or notification, before it is dispatched to its handler method.
+    /// A handler that needs the id or the cancellation token of its request, such as to complete it
+    /// in another thread, keeps them from here.
+    fn before_dispatch(&mut self, request: &RequestInfo) {}
     
-    #[allow(unused_variables)]
-    fn handle_other_method(&mut self, method_name: &str, params: RequestParams, completable: ResponseCompletable) {
-        completable.complete_with_error(jsonrpc_common::error_JSON_RPC_MethodNotFound()); 
+    fn initialize(&mut self, params: InitializeParams, completable: LSMethodCompletable<methods::Initialize>) {
+        let result = InitializeResultBuilder::new().capabilities(self.server_capabilities()).build();
+        completable.complete(Ok(result))
+    }
+    fn shutdown(&mut self, params: (), completable: LSMethodCompletable<methods::Shutdown>) {
+        completable.complete(Ok(()))
+    }
+    fn exit(&mut self, params: ()) {}
+    fn workspace_change_configuration(&mut self, params: DidChangeConfigurationParams) {}
+    fn did_open_text_document(&mut self, params: DidOpenTextDocumentParams) {}
+    fn did_change_text_document(&mut self, params: DidChangeTextDocumentParams) {}
+    fn did_close_text_document(&mut self, params: DidCloseTextDocumentParams) {}
+    fn did_save_text_document(&mut self, params: DidSaveTextDocumentParams) {}
+    fn did_change_watched_files(&mut self, params: DidChangeWatchedFilesParams) {}
+    
+    fn completion(&mut self, params: TextDocumentPositionParams, completable: LSMethodCompletable<methods::Completion>) {
+        completable.complete(Err(error_not_supported()))
+    }
+    fn resolve_completion_item(&mut self, params: LSCompletionItem, completable: LSMethodCompletable<methods::ResolveCompletionItem>) {
+        completable.complete(Err(error_not_supported()))
+    }
+    fn hover(&mut self, params: TextDocumentPositionParams, completable: LSMethodCompletable<methods::HoverRequest>) {
+        completable.complete(Err(error_not_supported()))
+    }
+    fn signature_help(&mut self, params: TextDocumentPositionParams, completable: LSMethodCompletable<methods::SignatureHelpRequest>) {
+        completable.complete(Err(error_not_supported()))
+    }
+    fn goto_definition(&mut self, params: TextDocumentPositionParams, completable: LSMethodCompletable<methods::GotoDefinition>) {
+        completable.complete(Err(error_not_supported()))
+    }
+    fn goto_type_definition(&mut self, params: TextDocumentPositionParams, completable: LSMethodCompletable<methods::GotoTypeDefinition>) {
+        completable.complete(Err(error_not_supported()))
+    }
+    fn goto_implementation(&mut self, params: TextDocumentPositionParams, completable: LSMethodCompletable<methods::GotoImplementation>) {
+        completable.complete(Err(error_not_supported()))
+    }
+    fn references(&mut self, params: ReferenceParams, completable: LSMethodCompletable<methods::References>) {
+        completable.complete(Err(error_not_supported()))
+    }
+    fn document_highlight(&mut self, params: TextDocumentPositionParams, completable: LSMethodCompletable<methods::DocumentHighlightRequest>) {
+        completable.complete(Err(error_not_supported()))
+    }
+    fn document_symbols(&mut self, params: DocumentSymbolParams, completable: LSMethodCompletable<methods::DocumentSymbols>) {
+        completable.complete(Err(error_not_supported()))
+    }
+    fn workspace_symbols(&mut self, params: WorkspaceSymbolParams, completable: LSMethodCompletable<methods::WorkspaceSymbol>) {
+        completable.complete(Err(error_not_supported()))
+    }
+    fn code_action(&mut self, params: CodeActionParams, completable: LSMethodCompletable<methods::CodeActionRequest>) {
+        completable.complete(Err(error_not_supported()))
+    }
+    fn code_lens(&mut self, params: CodeLensParams, completable: LSMethodCompletable<methods::CodeLensRequest>) {
+        completable.complete(Err(error_not_supported()))
+    }
+    fn code_lens_resolve(&mut self, params: CodeLens, completable: LSMethodCompletable<methods::CodeLensResolve>) {
+        completable.complete(Err(error_not_supported()))
+    }
+    fn document_link(&mut self, params: DocumentLinkParams, completable: LSMethodCompletable<methods::DocumentLinkRequest>) {
+        completable.complete(Err(error_not_supported()))
+    }
+    fn document_link_resolve(&mut self, params: DocumentLink, completable: LSMethodCompletable<methods::DocumentLinkResolve>) {
+        completable.complete(Err(error_not_supported()))
+    }
+    fn formatting(&mut self, params: DocumentFormattingParams, completable: LSMethodCompletable<methods::Formatting>) {
+        completable.complete(Err(error_not_supported()))
+    }
+    fn range_formatting(&mut self, params: DocumentRangeFormattingParams, completable: LSMethodCompletable<methods::RangeFormatting>) {
+        completable.complete(Err(error_not_supported()))
+    }
+    fn on_type_formatting(&mut self, params: DocumentOnTypeFormattingParams, completable: LSMethodCompletable<methods::OnTypeFormatting>) {
+        completable.complete(Err(error_not_supported()))
+    }
+    fn rename(&mut self, params: RenameParams, completable: LSMethodCompletable<methods::Rename>) {
+        completable.complete(Err(error_not_supported()))
+    }
+    fn folding_range(&mut self, params: FoldingRangeParams, completable: LSMethodCompletable<methods::FoldingRangeRequest>) {
+        completable.complete(Err(error_not_supported()))
+    }
+    fn selection_range(&mut self, params: SelectionRangeParams, completable: LSMethodCompletable<methods::SelectionRangeRequest>) {
+        completable.complete(Err(error_not_supported()))
+    }
+    fn prepare_call_hierarchy(&mut self, params: TextDocumentPositionParams, completable: LSMethodCompletable<methods::PrepareCallHierarchy>) {
+        completable.complete(Err(error_not_supported()))
+    }
+    fn call_hierarchy_incoming_calls(&mut self, params: CallHierarchyIncomingCallsParams, completable: LSMethodCompletable<methods::CallHierarchyIncomingCalls>) {
+        completable.complete(Err(error_not_supported()))
+    }
+    fn call_hierarchy_outgoing_calls(&mut self, params: CallHierarchyOutgoingCallsParams, completable: LSMethodCompletable<methods::CallHierarchyOutgoingCalls>) {
+        completable.complete(Err(error_not_supported()))
+    }
+    fn semantic_tokens_full(&mut self, params: SemanticTokensParams, completable: LSMethodCompletable<methods::SemanticTokensFull>) {
+        completable.complete(Err(error_not_supported()))
+    }
+    fn semantic_tokens_range(&mut self, params: SemanticTokensRangeParams, completable: LSMethodCompletable<methods::SemanticTokensRange>) {
+        completable.complete(Err(error_not_supported()))
+    }
+    fn linked_editing_range(&mut self, params: TextDocumentPositionParams, completable: LSMethodCompletable<methods::LinkedEditingRange>) {
+        completable.complete(Err(error_not_supported()))
+    }
+    fn inlay_hint(&mut self, params: InlayHintParams, completable: LSMethodCompletable<methods::InlayHintRequest>) {
+        completable.complete(Err(error_not_supported()))
+    }
+    fn document_diagnostic(&mut self, params: DocumentDiagnosticParams, completable: LSMethodCompletable<methods::DocumentDiagnostic>) {
+        completable.complete(Err(error_not_supported()))
+    }
+    
+    fn handle_other_method(&mut self, request: &RequestInfo, method_name: &str, params: RequestParams,
+        completable: ResponseCompletable)
+    {
+        handle_unknown_method(request, method_name, completable)
+    }
+    
+    /// The capabilities the default `initialize` advertises.
+    fn server_capabilities(&self) -> ServerCapabilities {
+        ServerCapabilities::default()
     }
 }
 
 
-pub struct ServerRequestHandler<LS : ?Sized>(pub LS);
+/// A handler of the messages of the read loop of `LSPEndpoint::run_endpoint_loop`, which is given
+/// the `RequestInfo` of each, such as whether it is a notification, unlike a `RequestHandler`.
+pub trait LSRequestHandler {
+    fn handle_ls_request(&mut self, request: RequestInfo, method_name: &str, params: RequestParams, 
+        completable: ResponseCompletable);
+}
+
+impl<RH : RequestHandler + ?Sized> LSRequestHandler for RH {
+    fn handle_ls_request(&mut self, _: RequestInfo, method_name: &str, params: RequestParams, 
+        completable: ResponseCompletable)
+    {
+        self.handle_request(method_name, params, completable)
+    }
+}
 
-impl<LS : LanguageServerHandling + ?Sized> RequestHandler for ServerRequestHandler<LS> {
+pub struct ServerRequestHandler<LS : ?Sized> {
+    pub context: LSServerContext,
+    pub ls: LS,
+}
+
+impl<LS : LanguageServerHandling + ?Sized> LSRequestHandler for ServerRequestHandler<LS> {
     
-    fn handle_request(
-        &mut self, method_name: &str, params: RequestParams, completable: ResponseCompletable
+    fn handle_ls_request(
+        &mut self, request: RequestInfo, method_name: &str, params: RequestParams, completable: ResponseCompletable
     ) {
+        let request = self.context.begin_request(request, method_name, &params);
+        self.dispatch_begun_request(request, method_name, params, completable)
+    }
+    
+}
+
+impl<LS : LanguageServerHandling + ?Sized> ServerRequestHandler<LS> {
+    
+    /// Dispatch given message to the server, once `LSServerContext::begin_request` began its handling.
+    pub fn dispatch_begun_request(
+        &mut self, request: RequestInfo, method_name: &str, params: RequestParams, completable: ResponseCompletable
+    ) {
+        let dispatch = self.context.metrics.as_ref().map(|recorder| recorder.begin_dispatch(method_name));
+        self.ls.before_dispatch(&request);
+        self.dispatch_request(&request, method_name, params, completable);
+        if let Some(dispatch) = dispatch {
+            dispatch.end();
+        }
+    }
+    
+    fn dispatch_request(
+        &mut self, request: &RequestInfo, method_name: &str, params: RequestParams, completable: ResponseCompletable
+    ) {
+        // The client sends nothing else until it gets the result of `initialize`
+        if self.context.state() == ServerState::Initializing {
+            self.context.set_state(ServerState::Running);
+        }
+        if !self.context.is_initialize_received() 
+            && method_name != methods::INITIALIZE && method_name != methods::EXIT 
+        {
+            if request.is_notification() {
+                // Notifications are dropped, not answered
+                info!("Dropping notification `{}` received before `initialize`.", method_name);
+                return;
+            }
+            return completable.complete_with_error(error_LSP_ServerNotInitialized());
+        }
+        if self.context.is_shutdown_received() && method_name != methods::EXIT {
+            if request.is_notification() {
+                info!("Dropping notification `{}` received after `shutdown`.", method_name);
+                return;
+            }
+            // Only `exit` is valid after `shutdown`
+            return completable.complete_with_error(jsonrpc_common::error_JSON_RPC_InvalidRequest());
+        }
+        if !self.context.client_supports_method(method_name) {
+            if request.is_notification() {
+                return;
+            }
+            // The client declared it doesn't support the method
+            return completable.complete_with_error(jsonrpc_common::error_JSON_RPC_MethodNotFound());
+        }
+        if let Some(ref validator) = self.context.params_validator {
+            if let Err(error) = validator.validate_params(method_name, &params) {
+                if request.is_notification() {
+                    error!("Invalid params for notification `{}`: {}", method_name, error);
+                    return;
+                }
+                return completable.complete_with_error(error_InvalidParams(&error));
+            }
+        }
+        
         match method_name {
-            REQUEST__Initialize => {
+            methods::INITIALIZE => {
                 completable.handle_request_with(params, 
-                    |params, completable| self.0.initialize(params, completable)
+                    |params: InitializeParams, completable| {
+                        self.context.set_state(ServerState::Initializing);
+                        let client_capabilities = LSClientCapabilities(params.capabilities.clone());
+                        self.context.set_client_capabilities(client_capabilities);
+                        self.ls.initialize(params, completable)
+                    }
                 ) 
             }
-            REQUEST__Shutdown => {
+            methods::SHUTDOWN => {
+                *self.context.shutdown_received.lock().unwrap() = true;
+                self.context.set_state(ServerState::ShuttingDown);
+                self.context.background_tasks.shutdown();
                 completable.handle_request_with(params, 
-                    |params, completable| self.0.shutdown(params, completable)
+                    |params, completable| self.ls.shutdown(params, completable)
                 ) 
             }
-            NOTIFICATION__Exit => { 
-                completable.handle_notification_with(params, 
-                    |params| self.0.exit(params)) 
+            methods::EXIT => { 
+                handle_lsp_notification::<methods::Exit, _>(params, completable, 
+                    |params| self.ls.exit(params));
+                // Terminate the read loop
+                self.context.endpoint.request_shutdown();
+            }
+            methods::CANCEL_REQUEST => {
+                // A notification has no response
+                drop(completable);
+                self.context.cancellation.cancel_request_of_params(&params);
+            }
+            methods::WORKSPACE_DID_CHANGE_CONFIGURATION => {
+                handle_lsp_notification::<methods::DidChangeConfiguration, _>(params, completable, 
+                    |params| self.ls.workspace_change_configuration(params)
+                ) 
             }
-            NOTIFICATION__WorkspaceChangeConfiguration => {
-                completable.handle_notification_with(params, 
-                    |params| self.0.workspace_change_configuration(params)
+            methods::TEXT_DOCUMENT_DID_OPEN => {
+                handle_lsp_notification::<methods::DidOpenTextDocument, _>(params, completable, 
+                    |params| self.ls.did_open_text_document(params)
                 ) 
             }
-            NOTIFICATION__DidOpenTextDocument => {
-                completable.handle_notification_with(params, 
-                    |params| self.0.did_open_text_document(params)
+            methods::TEXT_DOCUMENT_DID_CHANGE => {
+                handle_lsp_notification::<methods::DidChangeTextDocument, _>(params, completable, 
+                    |params| self.ls.did_change_text_document(params)
                 ) 
             }
-            NOTIFICATION__DidChangeTextDocument => {
-                completable.handle_notification_with(params, 
-                    |params| self.0.did_change_text_document(params)
+            methods::TEXT_DOCUMENT_DID_CLOSE => {
+                handle_lsp_notification::<methods::DidCloseTextDocument, _>(params, completable, 
+                    |params| self.ls.did_close_text_document(params)
                 ) 
             }
-            NOTIFICATION__DidCloseTextDocument => {
-                completable.handle_notification_with(params, 
-                    |params| self.0.did_close_text_document(params)
+            methods::TEXT_DOCUMENT_DID_SAVE => {
+                handle_lsp_notification::<methods::DidSaveTextDocument, _>(params, completable, 
+                    |params| self.ls.did_save_text_document(params)
                 ) 
             }
-            NOTIFICATION__DidSaveTextDocument => {
-                completable.handle_notification_with(params, 
-                    |params| self.0.did_save_text_document(params)
+            methods::WORKSPACE_DID_CHANGE_WATCHED_FILES => {
+                handle_lsp_notification::<methods::DidChangeWatchedFiles, _>(params, completable, 
+                    |params| self.ls.did_change_watched_files(params)) 
+            }
+            methods::TEXT_DOCUMENT_COMPLETION => {
+                completable.handle_request_with(params, 
+                    |params, completable| self.ls.completion(params, completable)
                 ) 
             }
-            NOTIFICATION__DidChangeWatchedFiles => {
-                completable.handle_notification_with(params, 
-                    |params| self.0.did_change_watched_files(params)) 
+            methods::COMPLETION_ITEM_RESOLVE => {
+                completable.handle_request_with(params, 
+                    |params, completable| self.ls.resolve_completion_item(params, completable)
+                ) 
             }
-            REQUEST__Completion => {
+            methods::TEXT_DOCUMENT_HOVER => {
                 completable.handle_request_with(params, 
-                    |params, completable| self.0.completion(params, completable)
+                    |params, completable| self.ls.hover(params, completable)
                 ) 
             }
-            REQUEST__ResolveCompletionItem => {
+            methods::TEXT_DOCUMENT_SIGNATURE_HELP => {
                 completable.handle_request_with(params, 
-                    |params, completable| self.0.resolve_completion_item(params, completable)
+                    |params, completable| self.ls.signature_help(params, completable)
                 ) 
             }
-            REQUEST__Hover => {
+            methods::TEXT_DOCUMENT_DEFINITION => {
                 completable.handle_request_with(params, 
-                    |params, completable| self.0.hover(params, completable)
+                    |params, completable| self.ls.goto_definition(params, completable)
                 ) 
             }
-            REQUEST__SignatureHelp => {
+            methods::TEXT_DOCUMENT_TYPE_DEFINITION => {
                 completable.handle_request_with(params, 
-                    |params, completable| self.0.signature_help(params, completable)
+                    |params, completable| self.ls.goto_type_definition(params, completable)
                 ) 
             }
-            REQUEST__GotoDefinition => {
+            methods::TEXT_DOCUMENT_IMPLEMENTATION => {
                 completable.handle_request_with(params, 
-                    |params, completable| self.0.goto_definition(params, completable)
+                    |params, completable| self.ls.goto_implementation(params, completable)
                 ) 
             }
-            REQUEST__References => {
+            methods::TEXT_DOCUMENT_REFERENCES => {
                 completable.handle_request_with(params, 
-                    |params, completable| self.0.references(params, completable)
+                    |params, completable| self.ls.references(params, completable)
                 ) 
             }
-            REQUEST__DocumentHighlight => {
+            methods::TEXT_DOCUMENT_DOCUMENT_HIGHLIGHT => {
                 completable.handle_request_with(params, 
-                    |params, completable| self.0.document_highlight(params, completable)
+                    |params, completable| self.ls.document_highlight(params, completable)
                 ) 
             }
-            REQUEST__DocumentSymbols => {
+            methods::TEXT_DOCUMENT_DOCUMENT_SYMBOL => {
                 completable.handle_request_with(params, 
-                    |params, completable| self.0.document_symbols(params, completable)
+                    |params, completable| self.ls.document_symbols(params, completable)
                 ) 
             }
-            REQUEST__WorkspaceSymbols => {
+            methods::WORKSPACE_SYMBOL => {
                 completable.handle_request_with(params, 
-                    |params, completable| self.0.workspace_symbols(params, completable)
+                    |params, completable| self.ls.workspace_symbols(params, completable)
                 ) 
             }
-            REQUEST__CodeAction => {
+            methods::TEXT_DOCUMENT_CODE_ACTION => {
                 completable.handle_request_with(params, 
-                    |params, completable| self.0.code_action(params, completable)
+                    |params, completable| self.ls.code_action(params, completable)
                 ) 
             }
-            REQUEST__CodeLens => {
+            methods::TEXT_DOCUMENT_CODE_LENS => {
                 completable.handle_request_with(params, 
-                    |params, completable| self.0.code_lens(params, completable)
+                    |params, completable| self.ls.code_lens(params, completable)
                 ) 
             }
-            REQUEST__CodeLensResolve => {
+            methods::CODE_LENS_RESOLVE => {
                 completable.handle_request_with(params, 
-                    |params, completable| self.0.code_lens_resolve(params, completable)
+                    |params, completable| self.ls.code_lens_resolve(params, completable)
                 ) 
             }
-            REQUEST__DocumentLink => {
+            methods::TEXT_DOCUMENT_DOCUMENT_LINK => {
                 completable.handle_request_with(params, 
-                    |params, completable| self.0.document_link(params, completable)
+                    |params, completable| self.ls.document_link(params, completable)
                 ) 
             }            
-            REQUEST__DocumentLinkResolve => {
+            methods::DOCUMENT_LINK_RESOLVE => {
                 completable.handle_request_with(params, 
-                    |params, completable| self.0.document_link_resolve(params, completable)
+                    |params, completable| self.ls.document_link_resolve(params, completable)
                 ) 
             }            
-            REQUEST__Formatting => {
+            methods::TEXT_DOCUMENT_FORMATTING => {
+                completable.handle_request_with(params, 
+                    |params, completable| self.ls.formatting(params, completable)
+                ) 
+            }
+            methods::TEXT_DOCUMENT_RANGE_FORMATTING => {
+                completable.handle_request_with(params, 
+                    |params, completable| self.ls.range_formatting(params, completable)
+                ) 
+            }
+            methods::TEXT_DOCUMENT_ON_TYPE_FORMATTING => {
+                completable.handle_request_with(params, 
+                    |params, completable| self.ls.on_type_formatting(params, completable)
+                ) 
+            }
+            methods::TEXT_DOCUMENT_RENAME => {
+                completable.handle_request_with(params, 
+                    |params, completable| self.ls.rename(params, completable)
+                ) 
+            }
+            methods::TEXT_DOCUMENT_FOLDING_RANGE => {
+                completable.handle_request_with(params, 
+                    |params, completable| self.ls.folding_range(params, completable)
+                ) 
+            }
+            methods::TEXT_DOCUMENT_SELECTION_RANGE => {
+                completable.handle_request_with(params, 
+                    |params, completable| self.ls.selection_range(params, completable)
+                ) 
+            }
+            methods::TEXT_DOCUMENT_PREPARE_CALL_HIERARCHY => {
+                completable.handle_request_with(params, 
+                    |params, completable| self.ls.prepare_call_hierarchy(params, completable)
+                ) 
+            }
+            methods::CALL_HIERARCHY_INCOMING_CALLS => {
+                completable.handle_request_with(params, 
+                    |params, completable| self.ls.call_hierarchy_incoming_calls(params, completable)
+                ) 
+            }
+            methods::CALL_HIERARCHY_OUTGOING_CALLS => {
                 completable.handle_request_with(params, 
-                    |params, completable| self.0.formatting(params, completable)
+                    |params, completable| self.ls.call_hierarchy_outgoing_calls(params, completable)
                 ) 
             }
-            REQUEST__RangeFormatting => {
+            methods::TEXT_DOCUMENT_SEMANTIC_TOKENS_FULL => {
                 completable.handle_request_with(params, 
-                    |params, completable| self.0.range_formatting(params, completable)
+                    |params, completable| self.ls.semantic_tokens_full(params, completable)
                 ) 
             }
-            REQUEST__OnTypeFormatting => {
+            methods::TEXT_DOCUMENT_SEMANTIC_TOKENS_RANGE => {
                 completable.handle_request_with(params, 
-                    |params, completable| self.0.on_type_formatting(params, completable)
+                    |params, completable| self.ls.semantic_tokens_range(params, completable)
                 ) 
             }
-            REQUEST__Rename => {
+            methods::TEXT_DOCUMENT_LINKED_EDITING_RANGE => {
                 completable.handle_request_with(params, 
-                    |params, completable| self.0.rename(params, completable)
+                    |params, completable| self.ls.linked_editing_range(params, completable)
+                ) 
+            }
+            methods::TEXT_DOCUMENT_INLAY_HINT => {
+                completable.handle_request_with(params, 
+                    |params, completable| self.ls.inlay_hint(params, completable)
+                ) 
+            }
+            methods::TEXT_DOCUMENT_DIAGNOSTIC => {
+                completable.handle_request_with(params, 
+                    |params, completable| self.ls.document_diagnostic(params, completable)
                 ) 
             }
             _ => {
-                self.0.handle_other_method(method_name, params, completable);
+                self.ls.handle_other_method(request, method_name, params, completable);
             }
         };
         
@@ -303,25 +1037,150 @@ impl<LS : LanguageServerHandling + ?Sized> RequestHandler for ServerRequestHandl
 }
 
 
+/// Handle a method that the handler doesn't know, as the spec requires: a request is answered
+/// with MethodNotFound, and a notification is ignored. Only notifications without the `$/` prefix
+/// of optional protocol notifications are logged.
+pub fn handle_unknown_method(request: &RequestInfo, method_name: &str, completable: ResponseCompletable) {
+    if !request.is_notification() {
+        return completable.complete_with_error(jsonrpc_common::error_JSON_RPC_MethodNotFound());
+    }
+    // A notification has no response
+    drop(completable);
+    if !method_name.starts_with("$/") {
+        info!("Ignoring unknown notification `{}`.", method_name);
+    }
+}
+
+/// Send a request for LSP method `REQ`, with its typed params and result.
+pub fn send_lsp_request<REQ>(endpoint: &mut Endpoint, params: REQ::Params) 
+    -> LSSendResult<LSRequestFuture<REQ>>
+where 
+    REQ : LSRequest,
+{
+    let params = try!(check_send(endpoint, &params));
+    endpoint.send_request(REQ::METHOD, params).map_err(LSError::from_gerror)
+}
+
+/// Send a notification for LSP method `NOTIF`, with its typed params.
+pub fn send_lsp_notification<NOTIF>(endpoint: &mut Endpoint, params: NOTIF::Params) 
+    -> LSSendResult<()>
+where 
+    NOTIF : LSNotification,
+{
+    let params = try!(check_send(endpoint, &params));
+    endpoint.send_notification(NOTIF::METHOD, params).map_err(LSError::from_gerror)
+}
+
+/// Handle a notification for LSP method `NOTIF` with given handler. Unlike 
+/// `ResponseCompletable::handle_notification_with`, params that fail to deserialize are logged, 
+/// not answered: a notification has no id, so the error response would have `id: null`.
+pub fn handle_lsp_notification<NOTIF, HANDLER>(
+    params: RequestParams, completable: ResponseCompletable, handler: HANDLER
+)
+where 
+    NOTIF : LSNotification,
+    HANDLER : FnOnce(NOTIF::Params),
+{
+    // A notification has no response
+    drop(completable);
+    match deserialize_request_params::<NOTIF::Params>(params) {
+        Ok(params) => handler(params),
+        Err(error) => error!("Invalid params for notification `{}`: {}", NOTIF::METHOD, error),
+    }
+}
+
+/// Deserialize given request params to their type for the method.
+pub fn deserialize_request_params<PARAMS>(params: RequestParams) -> Result<PARAMS, LSError>
+where 
+    PARAMS : serde::Deserialize,
+{
+    let params = match params {
+        RequestParams::Object(object) => Value::Object(object),
+        RequestParams::Array(array) => Value::Array(array),
+        RequestParams::None => Value::Null,
+    };
+    serde_json::from_value(params).map_err(|error| {
+        LSError::Parse("Failed to deserialize params:".to_string(), Some(error.into()))
+    })
+}
+
+/// Check that a message can be sent: the endpoint is not shut down and the params serialize.
+/// Returns the serialized params, so that the endpoint won't fail later serializing them.
+fn check_send<PARAMS>(endpoint: &Endpoint, params: &PARAMS) -> LSSendResult<Value>
+where 
+    PARAMS : serde::Serialize,
+{
+    if endpoint.is_shutdown() {
+        return Err(LSError::Shutdown("Cannot send message, endpoint is shut down.".to_string()));
+    }
+    serialize_value(params).map_err(|error| {
+        LSError::Parse("Failed to serialize params:".to_string(), Some(error.into()))
+    })
+}
+
+/// Serialize given value straight to a `Value`: unlike `serde_json::to_value`, this reports errors.
+fn serialize_value<VALUE>(value: &VALUE) -> Result<Value, serde_json::Error>
+where 
+    VALUE : serde::Serialize,
+{
+    let mut serializer = serde_json::value::Serializer::new();
+    try!(value.serialize(&mut serializer));
+    Ok(serializer.unwrap())
+}
+
+/// Complete given request with given result. In debug builds, if the result fails to serialize, 
+/// the request is completed with an `InternalError` instead, as the endpoint would panic serializing it.
+/// Release builds skip that check, so that the result is serialized only once, by the endpoint.
+pub fn complete_lsp_request<RET, ERR_DATA>(completable: MethodCompletable<RET, ERR_DATA>, 
+    result: LSResult<RET, ERR_DATA>)
+where 
+    RET : serde::Serialize,
+    ERR_DATA : serde::Serialize + Default,
+{
+    if cfg!(debug_assertions) {
+        let serialized = match result {
+            Ok(ref result) => serialize_value(result),
+            Err(ref error) => serialize_value(&error.data),
+        };
+        if let Err(error) = serialized {
+            error!("Failed to serialize the result of a request: {}", error);
+            let error = method_error_InternalError(format!("Failed to serialize result: {}", error));
+            return completable.complete(Err(error));
+        }
+    }
+    completable.complete(result)
+}
+
+
 pub trait LspClientRpc {
     
     fn show_message(&mut self, params: ShowMessageParams) 
-        -> GResult<()>;
+        -> LSSendResult<()>;
     
     fn show_message_request(&mut self, params: ShowMessageRequestParams) 
-        -> GResult<RequestFuture<MessageActionItem, ()>>;
+        -> LSSendResult<LSRequestFuture<methods::ShowMessageRequest>>;
     
     fn log_message(&mut self, params: LogMessageParams) 
-        -> GResult<()>;
+        -> LSSendResult<()>;
     
     fn telemetry_event(&mut self, params: Value) 
-        -> GResult<()>;
+        -> LSSendResult<()>;
     
-    fn publish_diagnostics(&mut self, params: PublishDiagnosticsParams) 
-        -> GResult<()>;
+    fn publish_diagnostics(&mut self, params: LSPublishDiagnosticsParams) 
+        -> LSSendResult<()>;
 
 }
 
+/// The options of `LspClientRpc_::show_document`. None of them are set by default.
+#[derive(Debug, Clone, Default)]
+pub struct ShowDocumentOptions {
+    /// Show the resource in an external program, such as a browser.
+    pub external: Option<bool>,
+    pub take_focus: Option<bool>,
+    /// The range to select, if the resource is a text document.
+    pub selection: Option<Range>,
+}
+
 pub struct LspClientRpc_<'a> {
     pub endpoint: &'a mut Endpoint,    
 }
@@ -333,33 +1192,92 @@ pub fn client_rpc_handle(endpoint : &mut Endpoint) -> LspClientRpc_ {
 impl<'a> LspClientRpc for LspClientRpc_<'a> {
     
     fn show_message(&mut self, params: ShowMessageParams) 
-        -> GResult<()> 
+        -> LSSendResult<()> 
     {
-        self.endpoint.send_notification(NOTIFICATION__ShowMessage, params)
+        send_lsp_notification::<methods::ShowMessage>(self.endpoint, params)
     }
     
     fn show_message_request(&mut self, params: ShowMessageRequestParams) 
-        -> GResult<RequestFuture<MessageActionItem, ()>> 
+        -> LSSendResult<LSRequestFuture<methods::ShowMessageRequest>> 
     {
-        self.endpoint.send_request(REQUEST__ShowMessageRequest, params)
+        send_lsp_request::<methods::ShowMessageRequest>(self.endpoint, params)
     }
     
     fn log_message(&mut self, params: LogMessageParams) 
-        -> GResult<()> 
+        -> LSSendResult<()> 
     {
-        self.endpoint.send_notification(NOTIFICATION__LogMessage, params)
+        send_lsp_notification::<methods::LogMessage>(self.endpoint, params)
     }
     
     fn telemetry_event(&mut self, params: Value) 
-        -> GResult<()> 
+        -> LSSendResult<()> 
+    {
+        send_lsp_notification::<methods::TelemetryEvent>(self.endpoint, params)
+    }
+    
+    fn publish_diagnostics(&mut self, params: LSPublishDiagnosticsParams) 
+        -> LSSendResult<()> 
     {
-        self.endpoint.send_notification(NOTIFICATION__TelemetryEvent, params)
+        send_lsp_notification::<methods::PublishDiagnostics>(self.endpoint, params)
+    }
+    
+}
+
+impl<'a> LspClientRpc_<'a> {
+    
+    /// Send given event to the client, in a `telemetry/event` notification.
+    pub fn telemetry<EVENT>(&mut self, event: &EVENT) -> LSSendResult<()>
+    where 
+        EVENT : serde::Serialize,
+    {
+        let event = try!(check_send(self.endpoint, event));
+        self.telemetry_event(event)
+    }
+    
+    /// Ask the client to show given document or URL, with `window/showDocument`.
+    /// The result tells whether it was shown. Only clients that `supports_show_document` answer it.
+    pub fn show_document(&mut self, uri: Url, options: ShowDocumentOptions)
+        -> LSSendResult<LSRequestFuture<methods::ShowDocument>>
+    {
+        let params = ShowDocumentParams {
+            uri : uri, external : options.external, take_focus : options.take_focus, selection : options.selection,
+        };
+        send_lsp_request::<methods::ShowDocument>(self.endpoint, params)
+    }
+    
+    /// Ask the client to refresh the code lenses of all documents, with `workspace/codeLens/refresh`.
+    pub fn code_lens_refresh(&mut self) -> LSSendResult<LSRequestFuture<methods::CodeLensRefresh>> {
+        send_lsp_request::<methods::CodeLensRefresh>(self.endpoint, ())
+    }
+    
+    pub fn semantic_tokens_refresh(&mut self) -> LSSendResult<LSRequestFuture<methods::SemanticTokensRefresh>> {
+        send_lsp_request::<methods::SemanticTokensRefresh>(self.endpoint, ())
+    }
+    
+    pub fn inlay_hint_refresh(&mut self) -> LSSendResult<LSRequestFuture<methods::InlayHintRefresh>> {
+        send_lsp_request::<methods::InlayHintRefresh>(self.endpoint, ())
+    }
+    
+    /// Ask the client to pull the diagnostics again, with `workspace/diagnostic/refresh`.
+    pub fn diagnostic_refresh(&mut self) -> LSSendResult<LSRequestFuture<methods::DiagnosticRefresh>> {
+        send_lsp_request::<methods::DiagnosticRefresh>(self.endpoint, ())
     }
     
-    fn publish_diagnostics(&mut self, params: PublishDiagnosticsParams) 
-        -> GResult<()> 
+    /// Send refresh request `REQ`, and wait for the (empty) response of the client. 
+    /// Only clients that `supports_refresh` should be sent these requests.
+    /// 
+    /// The response is read by the message read loop, so this must not be called
+    /// from the thread that dispatches the messages, such as from a handler.
+    pub fn refresh_and_wait<REQ>(&mut self) -> Result<(), LSError>
+    where
+        REQ : LSRequest<Params = (), Result = ()>,
     {
-        self.endpoint.send_notification(NOTIFICATION__PublishDiagnostics, params)
+        let future = try!(send_lsp_request::<REQ>(self.endpoint, ()));
+        match future.wait() {
+            Ok(Ok(())) => Ok(()),
+            Ok(Err(error)) => Err(LSError::Protocol(format!("Request `{}` failed: {}", REQ::METHOD, error.message))),
+            Err(error) => Err(LSError::Shutdown(format!("Request `{}` got no response: {:?}", REQ::METHOD, error))),
+        }
     }
     
 }
@@ -369,79 +1287,121 @@ impl<'a> LspClientRpc for LspClientRpc_<'a> {
 pub trait LSPServerRpc {
     
     fn initialize(&mut self, params: InitializeParams)
-        -> GResult<RequestFuture<InitializeResult, InitializeError>>;
+        -> LSSendResult<LSRequestFuture<methods::Initialize>>;
         
     fn shutdown(&mut self)
-        -> GResult<RequestFuture<(), ()>>;
+        -> LSSendResult<LSRequestFuture<methods::Shutdown>>;
         
     fn exit(&mut self)
-        -> GResult<()>;
+        -> LSSendResult<()>;
         
     fn workspace_change_configuration(&mut self, params: DidChangeConfigurationParams)
-        -> GResult<()>;
+        -> LSSendResult<()>;
         
     fn did_open_text_document(&mut self, params: DidOpenTextDocumentParams)
-        -> GResult<()>;
+        -> LSSendResult<()>;
         
     fn did_change_text_document(&mut self, params: DidChangeTextDocumentParams)
-        -> GResult<()>;
+        -> LSSendResult<()>;
         
     fn did_close_text_document(&mut self, params: DidCloseTextDocumentParams)
-        -> GResult<()>;
+        -> LSSendResult<()>;
         
     fn did_save_text_document(&mut self, params: DidSaveTextDocumentParams)
-        -> GResult<()>;
+        -> LSSendResult<()>;
         
     fn did_change_watched_files(&mut self, params: DidChangeWatchedFilesParams)
-        -> GResult<()>;
+        -> LSSendResult<()>;
         
     fn completion(&mut self, params: TextDocumentPositionParams)
-        -> GResult<RequestFuture<CompletionList, ()>>;
+        -> LSSendResult<LSRequestFuture<methods::Completion>>;
         
-    fn resolve_completion_item(&mut self, params: CompletionItem)
-        -> GResult<RequestFuture<CompletionItem, ()>>;
+    fn resolve_completion_item(&mut self, params: LSCompletionItem)
+        -> LSSendResult<LSRequestFuture<methods::ResolveCompletionItem>>;
         
     fn hover(&mut self, params: TextDocumentPositionParams)
-        -> GResult<RequestFuture<Hover, ()>>;
+        -> LSSendResult<LSRequestFuture<methods::HoverRequest>>;
         
     fn signature_help(&mut self, params: TextDocumentPositionParams)
-        -> GResult<RequestFuture<SignatureHelp, ()>>;
+        -> LSSendResult<LSRequestFuture<methods::SignatureHelpRequest>>;
         
     fn goto_definition(&mut self, params: TextDocumentPositionParams)
-        -> GResult<RequestFuture<Vec<Location>, ()>>;
+        -> LSSendResult<LSRequestFuture<methods::GotoDefinition>>;
+        
+    fn goto_type_definition(&mut self, params: TextDocumentPositionParams)
+        -> LSSendResult<LSRequestFuture<methods::GotoTypeDefinition>>;
+        
+    fn goto_implementation(&mut self, params: TextDocumentPositionParams)
+        -> LSSendResult<LSRequestFuture<methods::GotoImplementation>>;
         
     fn references(&mut self, params: ReferenceParams)
-        -> GResult<RequestFuture<Vec<Location>, ()>>;
+        -> LSSendResult<LSRequestFuture<methods::References>>;
         
     fn document_highlight(&mut self, params: TextDocumentPositionParams)
-        -> GResult<RequestFuture<Vec<DocumentHighlight>, ()>>;
+        -> LSSendResult<LSRequestFuture<methods::DocumentHighlightRequest>>;
         
     fn document_symbols(&mut self, params: DocumentSymbolParams)
-        -> GResult<RequestFuture<Vec<SymbolInformation>, ()>>;
+        -> LSSendResult<LSRequestFuture<methods::DocumentSymbols>>;
         
     fn workspace_symbols(&mut self, params: WorkspaceSymbolParams)
-        -> GResult<RequestFuture<Vec<SymbolInformation>, ()>>;
+        -> LSSendResult<LSRequestFuture<methods::WorkspaceSymbol>>;
         
     fn code_action(&mut self, params: CodeActionParams)
-        -> GResult<RequestFuture<Vec<Command>, ()>>;
+        -> LSSendResult<LSRequestFuture<methods::CodeActionRequest>>;
         
     fn code_lens(&mut self, params: CodeLensParams)
-        -> GResult<RequestFuture<Vec<CodeLens>, ()>>;
+        -> LSSendResult<LSRequestFuture<methods::CodeLensRequest>>;
         
     fn code_lens_resolve(&mut self, params: CodeLens)
-        -> GResult<RequestFuture<CodeLens, ()>>;
+        -> LSSendResult<LSRequestFuture<methods::CodeLensResolve>>;
         
     fn formatting(&mut self, params: DocumentFormattingParams)
-        -> GResult<RequestFuture<Vec<TextEdit>, ()>>;
+        -> LSSendResult<LSRequestFuture<methods::Formatting>>;
         
     fn range_formatting(&mut self, params: DocumentRangeFormattingParams)
-        -> GResult<RequestFuture<Vec<TextEdit>, ()>>;
+        -> LSSendResult<LSRequestFuture<methods::RangeFormatting>>;
         
     fn on_type_formatting(&mut self, params: DocumentOnTypeFormattingParams)
-        -> GResult<RequestFuture<Vec<TextEdit>, ()>>;
+        -> LSSendResult<LSRequestFuture<methods::OnTypeFormatting>>;
         
     fn rename(&mut self, params: RenameParams)
-        -> GResult<RequestFuture<WorkspaceEdit, ()>>;
+        -> LSSendResult<LSRequestFuture<methods::Rename>>;
+        
+    fn document_link(&mut self, params: DocumentLinkParams)
+        -> LSSendResult<LSRequestFuture<methods::DocumentLinkRequest>>;
+        
+    fn document_link_resolve(&mut self, params: DocumentLink)
+        -> LSSendResult<LSRequestFuture<methods::DocumentLinkResolve>>;
+        
+    fn folding_range(&mut self, params: FoldingRangeParams)
+        -> LSSendResult<LSRequestFuture<methods::FoldingRangeRequest>>;
+        
+    fn selection_range(&mut self, params: SelectionRangeParams)
+        -> LSSendResult<LSRequestFuture<methods::SelectionRangeRequest>>;
+        
+    fn prepare_call_hierarchy(&mut self, params: TextDocumentPositionParams)
+        -> LSSendResult<LSRequestFuture<methods::PrepareCallHierarchy>>;
+        
+    fn call_hierarchy_incoming_calls(&mut self, params: CallHierarchyIncomingCallsParams)
+        -> LSSendResult<LSRequestFuture<methods::CallHierarchyIncomingCalls>>;
+        
+    fn call_hierarchy_outgoing_calls(&mut self, params: CallHierarchyOutgoingCallsParams)
+        -> LSSendResult<LSRequestFuture<methods::CallHierarchyOutgoingCalls>>;
+        
+    fn semantic_tokens_full(&mut self, params: SemanticTokensParams)
+        -> LSSendResult<LSRequestFuture<methods::SemanticTokensFull>>;
+        
+    fn semantic_tokens_range(&mut self, params: SemanticTokensRangeParams)
+        -> LSSendResult<LSRequestFuture<methods::SemanticTokensRange>>;
+        
+    fn linked_editing_range(&mut self, params: TextDocumentPositionParams)
+        -> LSSendResult<LSRequestFuture<methods::LinkedEditingRange>>;
+        
+    fn inlay_hint(&mut self, params: InlayHintParams)
+        -> LSSendResult<LSRequestFuture<methods::InlayHintRequest>>;
+        
+    fn document_diagnostic(&mut self, params: DocumentDiagnosticParams)
+        -> LSSendResult<LSRequestFuture<methods::DocumentDiagnostic>>;
     
 }
 
@@ -457,153 +1417,237 @@ pub fn server_rpc_handle(endpoint : &mut Endpoint) -> LspServerRpc_ {
 impl<'a> LSPServerRpc for LspServerRpc_<'a> {
     
     fn initialize(&mut self, params: InitializeParams)
-        -> GResult<RequestFuture<InitializeResult, InitializeError>> 
+        -> LSSendResult<LSRequestFuture<methods::Initialize>> 
     {
-        self.endpoint.send_request(REQUEST__Initialize, params)
+        send_lsp_request::<methods::Initialize>(self.endpoint, params)
     }
     
     fn shutdown(&mut self)
-        -> GResult<RequestFuture<(), ()>>
+        -> LSSendResult<LSRequestFuture<methods::Shutdown>>
     {
-        self.endpoint.send_request(REQUEST__Shutdown, ())
+        send_lsp_request::<methods::Shutdown>(self.endpoint, ())
     }
     
     fn exit(&mut self)
-        -> GResult<()>
+        -> LSSendResult<()>
     {
-        self.endpoint.send_notification(NOTIFICATION__Exit, ())
+        send_lsp_notification::<methods::Exit>(self.endpoint, ())
     }
     
     fn workspace_change_configuration(&mut self, params: DidChangeConfigurationParams)
-        -> GResult<()>
+        -> LSSendResult<()>
     {
-         self.endpoint.send_notification(NOTIFICATION__WorkspaceChangeConfiguration, params)
+         send_lsp_notification::<methods::DidChangeConfiguration>(self.endpoint, params)
     }
     
     fn did_open_text_document(&mut self, params: DidOpenTextDocumentParams)
-        -> GResult<()>
+        -> LSSendResult<()>
     {
-        self.endpoint.send_notification(NOTIFICATION__DidOpenTextDocument, params)
+        send_lsp_notification::<methods::DidOpenTextDocument>(self.endpoint, params)
     }
     
     fn did_change_text_document(&mut self, params: DidChangeTextDocumentParams)
-        -> GResult<()>
+        -> LSSendResult<()>
     {
-        self.endpoint.send_notification(NOTIFICATION__DidChangeTextDocument, params)
+        send_lsp_notification::<methods::DidChangeTextDocument>(self.endpoint, params)
     }
     
     fn did_close_text_document(&mut self, params: DidCloseTextDocumentParams)
-        -> GResult<()>
+        -> LSSendResult<()>
     {
-        self.endpoint.send_notification(NOTIFICATION__DidCloseTextDocument, params)
+        send_lsp_notification::<methods::DidCloseTextDocument>(self.endpoint, params)
     }
     
     fn did_save_text_document(&mut self, params: DidSaveTextDocumentParams)
-        -> GResult<()>
+        -> LSSendResult<()>
     {
-        self.endpoint.send_notification(NOTIFICATION__DidSaveTextDocument, params)
+        send_lsp_notification::<methods::DidSaveTextDocument>(self.endpoint, params)
     }
     
     fn did_change_watched_files(&mut self, params: DidChangeWatchedFilesParams)
-        -> GResult<()>
+        -> LSSendResult<()>
     {
-        self.endpoint.send_notification(NOTIFICATION__DidChangeWatchedFiles, params)
+        send_lsp_notification::<methods::DidChangeWatchedFiles>(self.endpoint, params)
     }
     
     fn completion(&mut self, params: TextDocumentPositionParams)
-        -> GResult<RequestFuture<CompletionList, ()>>
+        -> LSSendResult<LSRequestFuture<methods::Completion>>
     {
-        self.endpoint.send_request(REQUEST__Completion, params)
+        send_lsp_request::<methods::Completion>(self.endpoint, params)
     }
     
-    fn resolve_completion_item(&mut self, params: CompletionItem)
-        -> GResult<RequestFuture<CompletionItem, ()>>
+    fn resolve_completion_item(&mut self, params: LSCompletionItem)
+        -> LSSendResult<LSRequestFuture<methods::ResolveCompletionItem>>
     {
-        self.endpoint.send_request(REQUEST__ResolveCompletionItem, params)
+        send_lsp_request::<methods::ResolveCompletionItem>(self.endpoint, params)
     }
     
     fn hover(&mut self, params: TextDocumentPositionParams)
-        -> GResult<RequestFuture<Hover, ()>>
+        -> LSSendResult<LSRequestFuture<methods::HoverRequest>>
     {
-        self.endpoint.send_request(REQUEST__Hover, params)
+        send_lsp_request::<methods::HoverRequest>(self.endpoint, params)
     }
     
     fn signature_help(&mut self, params: TextDocumentPositionParams)
-        -> GResult<RequestFuture<SignatureHelp, ()>>
+        -> LSSendResult<LSRequestFuture<methods::SignatureHelpRequest>>
     {
-        self.endpoint.send_request(REQUEST__SignatureHelp, params)
+        send_lsp_request::<methods::SignatureHelpRequest>(self.endpoint, params)
     }
     
     fn goto_definition(&mut self, params: TextDocumentPositionParams)
-        -> GResult<RequestFuture<Vec<Location>, ()>>
+        -> LSSendResult<LSRequestFuture<methods::GotoDefinition>>
     {
-        self.endpoint.send_request(REQUEST__GotoDefinition, params)
+        send_lsp_request::<methods::GotoDefinition>(self.endpoint, params)
+    }
+    
+    fn goto_type_definition(&mut self, params: TextDocumentPositionParams)
+        -> LSSendResult<LSRequestFuture<methods::GotoTypeDefinition>>
+    {
+        send_lsp_request::<methods::GotoTypeDefinition>(self.endpoint, params)
+    }
+    
+    fn goto_implementation(&mut self, params: TextDocumentPositionParams)
+        -> LSSendResult<LSRequestFuture<methods::GotoImplementation>>
+    {
+        send_lsp_request::<methods::GotoImplementation>(self.endpoint, params)
     }
     
     fn references(&mut self, params: ReferenceParams)
-        -> GResult<RequestFuture<Vec<Location>, ()>>
+        -> LSSendResult<LSRequestFuture<methods::References>>
     {
-        self.endpoint.send_request(REQUEST__References, params)
+        send_lsp_request::<methods::References>(self.endpoint, params)
     }
     
     fn document_highlight(&mut self, params: TextDocumentPositionParams)
-        -> GResult<RequestFuture<Vec<DocumentHighlight>, ()>>
+        -> LSSendResult<LSRequestFuture<methods::DocumentHighlightRequest>>
     {
-        self.endpoint.send_request(REQUEST__DocumentHighlight, params)
+        send_lsp_request::<methods::DocumentHighlightRequest>(self.endpoint, params)
     }
     
     fn document_symbols(&mut self, params: DocumentSymbolParams)
-        -> GResult<RequestFuture<Vec<SymbolInformation>, ()>>
+        -> LSSendResult<LSRequestFuture<methods::DocumentSymbols>>
     {
-        self.endpoint.send_request(REQUEST__DocumentSymbols, params)
+        send_lsp_request::<methods::DocumentSymbols>(self.endpoint, params)
     }
     
     fn workspace_symbols(&mut self, params: WorkspaceSymbolParams)
-        -> GResult<RequestFuture<Vec<SymbolInformation>, ()>>
+        -> LSSendResult<LSRequestFuture<methods::WorkspaceSymbol>>
     {
-        self.endpoint.send_request(REQUEST__WorkspaceSymbols, params)
+        send_lsp_request::<methods::WorkspaceSymbol>(self.endpoint, params)
     }
     
     fn code_action(&mut self, params: CodeActionParams)
-        -> GResult<RequestFuture<Vec<Command>, ()>>
+        -> LSSendResult<LSRequestFuture<methods::CodeActionRequest>>
     {
-        self.endpoint.send_request(REQUEST__CodeAction, params)
+        send_lsp_request::<methods::CodeActionRequest>(self.endpoint, params)
     }
     
     fn code_lens(&mut self, params: CodeLensParams)
-        -> GResult<RequestFuture<Vec<CodeLens>, ()>>
+        -> LSSendResult<LSRequestFuture<methods::CodeLensRequest>>
     {
-        self.endpoint.send_request(REQUEST__CodeLens, params)
+        send_lsp_request::<methods::CodeLensRequest>(self.endpoint, params)
     }
     
     fn code_lens_resolve(&mut self, params: CodeLens)
-        -> GResult<RequestFuture<CodeLens, ()>>
+        -> LSSendResult<LSRequestFuture<methods::CodeLensResolve>>
     {
-        self.endpoint.send_request(REQUEST__CodeLensResolve, params)
+        send_lsp_request::<methods::CodeLensResolve>(self.endpoint, params)
     }
     
     fn formatting(&mut self, params: DocumentFormattingParams)
-        -> GResult<RequestFuture<Vec<TextEdit>, ()>>
+        -> LSSendResult<LSRequestFuture<methods::Formatting>>
     {
-        self.endpoint.send_request(REQUEST__Formatting, params)
+        send_lsp_request::<methods::Formatting>(self.endpoint, params)
     }
     
     fn range_formatting(&mut self, params: DocumentRangeFormattingParams)
-        -> GResult<RequestFuture<Vec<TextEdit>, ()>>
+        -> LSSendResult<LSRequestFuture<methods::RangeFormatting>>
     {
-        self.endpoint.send_request(REQUEST__RangeFormatting, params)
+        send_lsp_request::<methods::RangeFormatting>(self.endpoint, params)
     }
     
     fn on_type_formatting(&mut self, params: DocumentOnTypeFormattingParams)
-        -> GResult<RequestFuture<Vec<TextEdit>, ()>>
+        -> LSSendResult<LSRequestFuture<methods::OnTypeFormatting>>
     {
-        self.endpoint.send_request(REQUEST__OnTypeFormatting, params)
+        send_lsp_request::<methods::OnTypeFormatting>(self.endpoint, params)
     }
     
     fn rename(&mut self, params: RenameParams)
-        -> GResult<RequestFuture<WorkspaceEdit, ()>>
+        -> LSSendResult<LSRequestFuture<methods::Rename>>
+    {
+        send_lsp_request::<methods::Rename>(self.endpoint, params)
+    }
+    
+    fn document_link(&mut self, params: DocumentLinkParams)
+        -> LSSendResult<LSRequestFuture<methods::DocumentLinkRequest>>
+    {
+        send_lsp_request::<methods::DocumentLinkRequest>(self.endpoint, params)
+    }
+    
+    fn document_link_resolve(&mut self, params: DocumentLink)
+        -> LSSendResult<LSRequestFuture<methods::DocumentLinkResolve>>
+    {
+        send_lsp_request::<methods::DocumentLinkResolve>(self.endpoint, params)
+    }
+    
+    fn folding_range(&mut self, params: FoldingRangeParams)
+        -> LSSendResult<LSRequestFuture<methods::FoldingRangeRequest>>
+    {
+        send_lsp_request::<methods::FoldingRangeRequest>(self.endpoint, params)
+    }
+    
+    fn selection_range(&mut self, params: SelectionRangeParams)
+        -> LSSendResult<LSRequestFuture<methods::SelectionRangeRequest>>
+    {
+        send_lsp_request::<methods::SelectionRangeRequest>(self.endpoint, params)
+    }
+    
+    fn prepare_call_hierarchy(&mut self, params: TextDocumentPositionParams)
+        -> LSSendResult<LSRequestFuture<methods::PrepareCallHierarchy>>
+    {
+        send_lsp_request::<methods::PrepareCallHierarchy>(self.endpoint, params)
+    }
+    
+    fn call_hierarchy_incoming_calls(&mut self, params: CallHierarchyIncomingCallsParams)
+        -> LSSendResult<LSRequestFuture<methods::CallHierarchyIncomingCalls>>
+    {
+        send_lsp_request::<methods::CallHierarchyIncomingCalls>(self.endpoint, params)
+    }
+    
+    fn call_hierarchy_outgoing_calls(&mut self, params: CallHierarchyOutgoingCallsParams)
+        -> LSSendResult<LSRequestFuture<methods::CallHierarchyOutgoingCalls>>
+    {
+        send_lsp_request::<methods::CallHierarchyOutgoingCalls>(self.endpoint, params)
+    }
+    
+    fn semantic_tokens_full(&mut self, params: SemanticTokensParams)
+        -> LSSendResult<LSRequestFuture<methods::SemanticTokensFull>>
+    {
+        send_lsp_request::<methods::SemanticTokensFull>(self.endpoint, params)
+    }
+    
+    fn semantic_tokens_range(&mut self, params: SemanticTokensRangeParams)
+        -> LSSendResult<LSRequestFuture<methods::SemanticTokensRange>>
+    {
+        send_lsp_request::<methods::SemanticTokensRange>(self.endpoint, params)
+    }
+    
+    fn linked_editing_range(&mut self, params: TextDocumentPositionParams)
+        -> LSSendResult<LSRequestFuture<methods::LinkedEditingRange>>
+    {
+        send_lsp_request::<methods::LinkedEditingRange>(self.endpoint, params)
+    }
+    
+    fn inlay_hint(&mut self, params: InlayHintParams)
+        -> LSSendResult<LSRequestFuture<methods::InlayHintRequest>>
+    {
+        send_lsp_request::<methods::InlayHintRequest>(self.endpoint, params)
+    }
+    
+    fn document_diagnostic(&mut self, params: DocumentDiagnosticParams)
+        -> LSSendResult<LSRequestFuture<methods::DocumentDiagnostic>>
     {
-        self.endpoint.send_request(REQUEST__Rename, params)
+        send_lsp_request::<methods::DocumentDiagnostic>(self.endpoint, params)
     }
     
 }
@@ -611,59 +1655,117 @@ impl<'a> LSPServerRpc for LspServerRpc_<'a> {
 
 /// Trait for the handling of LSP client requests.
 /// (An LSP server can act as a JSON-RPC Client and request to the LSP client)
+/// 
+/// The requests from `window/showDocument` on have a default implementation, 
+/// which fails with `error_not_supported()`.
 pub trait LanguageClientHandling {
     
     fn show_message(&mut self, params: ShowMessageParams);
     
     fn show_message_request(&mut self, params: ShowMessageRequestParams, 
-        completable: LSCompletable<MessageActionItem>);
+        completable: LSMethodCompletable<methods::ShowMessageRequest>);
     
     fn log_message(&mut self, params: LogMessageParams);
     
     fn telemetry_event(&mut self, params: Value);
     
-    fn publish_diagnostics(&mut self, params: PublishDiagnosticsParams);
-	
+    fn publish_diagnostics(&mut self, params: LSPublishDiagnosticsParams);
+    
     #[allow(unused_variables)]
-    fn handle_other_method(&mut self, method_name: &str, params: RequestParams, completable: ResponseCompletable) {
-        completable.complete_with_error(jsonrpc_common::error_JSON_RPC_MethodNotFound()); 
+    fn show_document(&mut self, params: ShowDocumentParams, completable: LSMethodCompletable<methods::ShowDocument>) {
+        completable.complete(Err(error_not_supported()))
+    }
+    #[allow(unused_variables)]
+    fn workspace_configuration(&mut self, params: ConfigurationParams, 
+        completable: LSMethodCompletable<methods::WorkspaceConfiguration>) 
+    {
+        completable.complete(Err(error_not_supported()))
+    }
+    fn code_lens_refresh(&mut self, completable: LSMethodCompletable<methods::CodeLensRefresh>) {
+        completable.complete(Err(error_not_supported()))
+    }
+    fn semantic_tokens_refresh(&mut self, completable: LSMethodCompletable<methods::SemanticTokensRefresh>) {
+        completable.complete(Err(error_not_supported()))
+    }
+    fn inlay_hint_refresh(&mut self, completable: LSMethodCompletable<methods::InlayHintRefresh>) {
+        completable.complete(Err(error_not_supported()))
+    }
+    fn diagnostic_refresh(&mut self, completable: LSMethodCompletable<methods::DiagnosticRefresh>) {
+        completable.complete(Err(error_not_supported()))
+    }
+    
+    #[allow(unused_variables)]
+    fn handle_other_method(&mut self, request: &RequestInfo, method_name: &str, params: RequestParams,
+        completable: ResponseCompletable)
+    {
+        handle_unknown_method(request, method_name, completable)
     }
     
 }
 
 pub struct ClientRequestHandler<LS : ?Sized>(pub LS);
 
-impl<LS : LanguageClientHandling + ?Sized> RequestHandler for ClientRequestHandler<LS> {
+impl<LS : LanguageClientHandling + ?Sized> LSRequestHandler for ClientRequestHandler<LS> {
 
-    fn handle_request(
-        &mut self, method_name: &str, params: RequestParams, completable: ResponseCompletable
+    fn handle_ls_request(
+        &mut self, request: RequestInfo, method_name: &str, params: RequestParams, completable: ResponseCompletable
     ) {
         match method_name {
-            NOTIFICATION__ShowMessage => {
-                completable.handle_notification_with(params, 
+            methods::WINDOW_SHOW_MESSAGE => {
+                handle_lsp_notification::<methods::ShowMessage, _>(params, completable, 
                     |params| self.0.show_message(params)) 
             }
-            REQUEST__ShowMessageRequest => {
+            methods::WINDOW_SHOW_MESSAGE_REQUEST => {
                 completable.handle_request_with(params, 
                     |params, completable| self.0.show_message_request(params, completable)
                 )
             }
-            NOTIFICATION__LogMessage => { 
-                completable.handle_notification_with(params, 
+            methods::WINDOW_LOG_MESSAGE => { 
+                handle_lsp_notification::<methods::LogMessage, _>(params, completable, 
                     |params| self.0.log_message(params)) 
             }
-            NOTIFICATION__TelemetryEvent => {
-                completable.handle_notification_with(params, 
+            methods::TELEMETRY_EVENT => {
+                handle_lsp_notification::<methods::TelemetryEvent, _>(params, completable, 
                     |params| self.0.telemetry_event(params)
                 ) 
             }
-            NOTIFICATION__PublishDiagnostics => {
-                completable.handle_notification_with(params, 
+            methods::TEXT_DOCUMENT_PUBLISH_DIAGNOSTICS => {
+                handle_lsp_notification::<methods::PublishDiagnostics, _>(params, completable, 
                     |params| self.0.publish_diagnostics(params)
                 ) 
             }
+            methods::WINDOW_SHOW_DOCUMENT => {
+                completable.handle_request_with(params, 
+                    |params, completable| self.0.show_document(params, completable)
+                )
+            }
+            methods::WORKSPACE_CONFIGURATION => {
+                completable.handle_request_with(params, 
+                    |params, completable| self.0.workspace_configuration(params, completable)
+                )
+            }
+            methods::WORKSPACE_CODE_LENS_REFRESH => {
+                completable.handle_request_with(params, 
+                    |_: (), completable| self.0.code_lens_refresh(completable)
+                )
+            }
+            methods::WORKSPACE_SEMANTIC_TOKENS_REFRESH => {
+                completable.handle_request_with(params, 
+                    |_: (), completable| self.0.semantic_tokens_refresh(completable)
+                )
+            }
+            methods::WORKSPACE_INLAY_HINT_REFRESH => {
+                completable.handle_request_with(params, 
+                    |_: (), completable| self.0.inlay_hint_refresh(completable)
+                )
+            }
+            methods::WORKSPACE_DIAGNOSTIC_REFRESH => {
+                completable.handle_request_with(params, 
+                    |_: (), completable| self.0.diagnostic_refresh(completable)
+                )
+            }
             _ => {
-                self.0.handle_other_method(method_name, params, completable);
+                self.0.handle_other_method(&request, method_name, params, completable);
             }
         }
     }
@@ -0,0 +1,212 @@
+// Copyright 2016 Bruno Medeiros
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>.
+// This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Adapters between the message readers of an endpoint and the standard iterators and channels.
+//! A `MessageReader` can be created from an iterator of messages, or from the receiver of a channel,
+//! and a `MessageReader` (such as an `LSPBufferedMessageReader` of a `BufRead` of framed messages)
+//! can be iterated. The incoming notifications of an endpoint can also be consumed with an iterator:
+//!
+//! ```ignore
+//! let (queue, notifications) = notification_queue();
+//! thread::spawn(move || LSPEndpoint::run_endpoint_loop(&mut reader, endpoint, Box::new(queue)));
+//! for notification in notifications {
+//!     println!("{}: {}", notification.method, notification.params);
+//! }
+//! ```
+
+use std::fmt;
+use std::sync::mpsc;
+use std::time::Duration;
+
+use util::core::*;
+
+use jsonrpc::*;
+use jsonrpc::jsonrpc_request::RequestParams;
+use serde_json::Value;
+
+use lsp::*;
+use lsp_errors::LSError;
+
+/* ----------------- Message readers ----------------- */
+
+/// Reads the messages of an iterator. The input ends when the iterator does.
+pub struct IteratorMessageReader<ITER>(pub ITER);
+
+impl<ITER, ERR> MessageReader for IteratorMessageReader<ITER>
+where
+    ITER : Iterator<Item = Result<String, ERR>>,
+    ERR : fmt::Display,
+{
+    fn read_next(&mut self) -> GResult<String> {
+        match self.0.next() {
+            Some(Ok(message)) => Ok(message),
+            Some(Err(error)) => Err(LSError::Protocol(error.to_string()).into()),
+            None => Err(LSError::Shutdown("End of stream reached.".to_string()).into()),
+        }
+    }
+}
+
+/// Reads the messages received from a channel. The input ends when all the senders are dropped.
+pub struct ChannelMessageReader(pub mpsc::Receiver<String>);
+
+impl MessageReader for ChannelMessageReader {
+    fn read_next(&mut self) -> GResult<String> {
+        self.0.recv().map_err(|_| LSError::Shutdown("End of stream reached.".to_string()).into())
+    }
+}
+
+/// Iterates the messages of a `MessageReader`, until its input ends.
+/// A read error other than the end of the input is returned, and ends the iteration.
+pub struct MessageIter<MR : MessageReader> {
+    reader: MR,
+    ended: bool,
+}
+
+impl<MR : MessageReader> MessageIter<MR> {
+    pub fn new(reader: MR) -> MessageIter<MR> {
+        MessageIter { reader : reader, ended : false }
+    }
+}
+
+impl<MR : MessageReader> Iterator for MessageIter<MR> {
+    type Item = Result<String, LSError>;
+
+    fn next(&mut self) -> Option<Result<String, LSError>> {
+        if self.ended {
+            return None;
+        }
+        match self.reader.read_next() {
+            Ok(message) => Some(Ok(message)),
+            Err(error) => {
+                self.ended = true;
+                let error = LSError::from_gerror(error);
+                if error.is_shutdown() { None } else { Some(Err(error)) }
+            }
+        }
+    }
+}
+
+/* ----------------- Notifications ----------------- */
+
+/// A notification received by an endpoint.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Notification {
+    pub method: String,
+    pub params: Value,
+}
+
+/// A request handler that queues the notifications it receives, for the `Notifications` iterator.
+/// Requests are answered with MethodNotFound.
+pub struct NotificationQueue(mpsc::Sender<Notification>);
+
+/// Iterates the notifications received by a `NotificationQueue`, until it is dropped,
+/// which is when the read loop of its endpoint ends.
+pub struct Notifications(mpsc::Receiver<Notification>);
+
+/// Create a request handler that queues the notifications, and the iterator of them.
+pub fn notification_queue() -> (NotificationQueue, Notifications) {
+    let (sender, receiver) = mpsc::channel();
+    (NotificationQueue(sender), Notifications(receiver))
+}
+
+impl LSRequestHandler for NotificationQueue {
+
+    fn handle_ls_request(&mut self, request: RequestInfo, method_name: &str, params: RequestParams,
+        completable: ResponseCompletable)
+    {
+        if !request.is_notification() {
+            return handle_unknown_method(&request, method_name, completable);
+        }
+        // A notification has no response
+        drop(completable);
+        let params = deserialize_request_params::<Value>(params).unwrap_or(Value::Null);
+        let _ = self.0.send(Notification { method : method_name.to_string(), params : params });
+    }
+
+}
+
+impl Notifications {
+
+    /// The next notification, waiting up to given timeout for it.
+    pub fn next_timeout(&mut self, timeout: Duration) -> Option<Notification> {
+        self.0.recv_timeout(timeout).ok()
+    }
+
+    /// The next notification, if one was already received.
+    pub fn try_next(&mut self) -> Option<Notification> {
+        self.0.try_recv().ok()
+    }
+
+}
+
+impl Iterator for Notifications {
+    type Item = Notification;
+
+    fn next(&mut self) -> Option<Notification> {
+        self.0.recv().ok()
+    }
+}
+
+
+#[test]
+fn message_readers__test() {
+    use lsp_transport::LSPBufferedMessageReader;
+
+    let messages = vec![Ok("1".to_string()), Ok("2".to_string())];
+    let mut reader = IteratorMessageReader(messages.into_iter().map(|message : Result<String, String>| message));
+    assert_eq!(reader.read_next().unwrap(), "1");
+    assert_eq!(reader.read_next().unwrap(), "2");
+    assert!(LSError::from_gerror(reader.read_next().unwrap_err()).is_shutdown());
+
+    let (sender, receiver) = mpsc::channel();
+    sender.send("1".to_string()).unwrap();
+    drop(sender);
+    let messages : Vec<_> = MessageIter::new(ChannelMessageReader(receiver)).collect();
+    assert_eq!(messages.len(), 1);
+
+    let input = "Content-Length: 1\r\n\r\n1Content-Length: 1\r\n\r\n2Content-Length: x\r\n\r\n3";
+    let mut messages = MessageIter::new(LSPBufferedMessageReader::new(input.as_bytes()));
+    assert_eq!(messages.next().unwrap().unwrap(), "1");
+    assert_eq!(messages.next().unwrap().unwrap(), "2");
+    assert!(messages.next().unwrap().is_err());
+    assert!(messages.next().is_none());
+}
+
+#[test]
+fn notification_queue__test() {
+    use std::io;
+    use std::thread;
+    use jsonrpc::json_util::JsonObject;
+    use lsp_testing::pipe;
+    use lsp_transport::JsonRpcMessage;
+    use lsp_transport::LSPBufferedMessageReader;
+    use lsp_transport::write_jsonrpc_message;
+
+    let (mut writer, reader) = pipe();
+    let (queue, mut notifications) = notification_queue();
+    let reader_thread = thread::spawn(move || {
+        let mut endpoint = LSPEndpoint::create_lsp_output_with_output_stream(|| io::sink());
+        let input = &mut LSPBufferedMessageReader::new(reader);
+        let _ = LSPEndpoint::run_endpoint_loop(input, endpoint.clone(), Box::new(queue));
+        endpoint.shutdown_and_join();
+    });
+
+    let mut params = JsonObject::new();
+    params.insert("value".to_string(), Value::U64(1));
+    let params = Value::Object(params);
+    write_jsonrpc_message(&JsonRpcMessage { id : None, method : "custom/first", params : &params }, &mut writer).unwrap();
+    write_jsonrpc_message(&JsonRpcMessage { id : Some(1), method : "custom/request", params : &params }, &mut writer)
+        .unwrap();
+    write_jsonrpc_message(&JsonRpcMessage { id : None, method : "custom/second", params : &params }, &mut writer).unwrap();
+
+    let first = notifications.next_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!(first, Notification { method : "custom/first".to_string(), params : params.clone() });
+    drop(writer);
+    reader_thread.join().unwrap();
+    assert_eq!(notifications.try_next().map(|notification| notification.method), Some("custom/second".to_string()));
+    assert_eq!(notifications.try_next(), None);
+}
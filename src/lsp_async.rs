@@ -0,0 +1,232 @@
+// Copyright 2016 Bruno Medeiros
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>.
+// This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! An asynchronous front-end driven by tokio, enabled with the `async-tokio` feature.
+//!
+//! The transport runs on tokio, over `AsyncRead`/`AsyncWrite` streams, while the messages are
+//! handled by the same `jsonrpc` endpoint as the blocking implementation. The handlers of a
+//! server run on a dedicated thread, so they can block; a handler can also complete its request
+//! with a future, with `complete_with_future`.
+//!
+//! ```ignore
+//! let (endpoint, output) = AsyncEndpoint::start(tokio::io::stdout());
+//! let server = AsyncEndpoint::run_server(tokio::io::stdin(), endpoint, |context| MyServer::new(context));
+//! tokio::run(output.join(server).map(|_| ()).map_err(|error| error!("{}", error)));
+//! ```
+
+use std::sync::mpsc;
+use std::thread;
+
+use util::core::*;
+
+use bytes::BytesMut;
+use futures;
+use futures::Future;
+use futures::Stream;
+use futures::sync::mpsc as futures_mpsc;
+use futures::sync::oneshot;
+use tokio;
+use tokio::codec::Decoder;
+use tokio::codec::Encoder;
+use tokio::codec::FramedRead;
+use tokio::codec::FramedWrite;
+use tokio::io::AsyncRead;
+use tokio::io::AsyncWrite;
+
+use jsonrpc::*;
+
+use lsp::*;
+use lsp_adapters::ChannelMessageReader;
+use lsp_errors::LSError;
+use lsp_methods::LSNotification;
+use lsp_methods::LSRequest;
+use lsp_transport::parse_transport_message_bytes;
+use lsp_transport::write_transport_message;
+
+pub type LSFuture<T> = Box<Future<Item = T, Error = LSError> + Send>;
+
+/* ----------------- Codec ----------------- */
+
+/// The LSP base protocol framing (`Content-Length` header and content) as a tokio codec.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LSPCodec;
+
+impl Decoder for LSPCodec {
+    type Item = String;
+    type Error = LSError;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<String>, LSError> {
+        match parse_transport_message_bytes(&src[..]) {
+            Ok((message, consumed)) => {
+                src.split_to(consumed);
+                Ok(Some(message))
+            }
+            // The message is not complete yet
+            Err(ref error) if error.is_shutdown() => Ok(None),
+            Err(error) => Err(error),
+        }
+    }
+}
+
+impl Encoder for LSPCodec {
+    type Item = String;
+    type Error = LSError;
+
+    fn encode(&mut self, message: String, dst: &mut BytesMut) -> Result<(), LSError> {
+        let mut bytes = vec![];
+        try!(write_transport_message(&message, &mut bytes));
+        dst.extend_from_slice(&bytes);
+        Ok(())
+    }
+}
+
+/* ----------------- Endpoint ----------------- */
+
+/// Queues the messages of the endpoint, for the output future to write.
+struct QueueMessageWriter(futures_mpsc::UnboundedSender<String>);
+
+impl MessageWriter for QueueMessageWriter {
+    fn write_message(&mut self, msg: &str) -> GResult<()> {
+        self.0.unbounded_send(msg.to_string())
+            .map_err(|_| LSError::Shutdown("Output stream is closed.".to_string()).into())
+    }
+}
+
+/// A LSP endpoint whose output is written asynchronously.
+#[derive(Clone)]
+pub struct AsyncEndpoint {
+    pub endpoint: Endpoint,
+}
+
+impl AsyncEndpoint {
+
+    /// Create an endpoint writing to given output. Returns it with the future that writes
+    /// the output, which must be spawned. The future completes when the endpoint is shut down.
+    pub fn start<OUT>(output: OUT) -> (AsyncEndpoint, LSFuture<()>)
+    where
+        OUT : AsyncWrite + Send + 'static,
+    {
+        let (sender, receiver) = futures_mpsc::unbounded();
+        let endpoint = LSPEndpoint::create_lsp_output(move || QueueMessageWriter(sender));
+
+        let sink = FramedWrite::new(output, LSPCodec);
+        let output_future = receiver
+            .map_err(|()| LSError::Shutdown("Output queue failed.".to_string()))
+            .forward(sink)
+            .map(|_| ());
+        (AsyncEndpoint { endpoint : endpoint }, Box::new(output_future))
+    }
+
+    /// Run the server created by `create_server` for given input.
+    /// The handlers run on a dedicated thread. Returns a future of the exit code,
+    /// as `LSPEndpoint::run_server`, which must be spawned.
+    pub fn run_server<IN, SERVER, CREATE>(input: IN, endpoint: AsyncEndpoint, create_server: CREATE) -> LSFuture<i32>
+    where
+        IN : AsyncRead + Send + 'static,
+        SERVER : LanguageServerHandling + 'static,
+        CREATE : FnOnce(LSServerContext) -> SERVER + Send + 'static,
+    {
+        let (sender, receiver) = mpsc::channel();
+        let (exit_sender, exit_receiver) = oneshot::channel();
+
+        thread::spawn(move || {
+            let context = LSServerContext::new(endpoint.endpoint);
+            let server = create_server(context.clone());
+            let exit_code = LSPEndpoint::run_server(&mut ChannelMessageReader(receiver), context, server);
+            let _ = exit_sender.send(exit_code);
+        });
+
+        let input_future = FramedRead::new(input, LSPCodec)
+            .for_each(move |message| {
+                // Fails once the server has exited, in which case the rest of the input is ignored
+                let _ = sender.send(message);
+                Ok(())
+            });
+        let exit_future = exit_receiver
+            .map_err(|_| LSError::Shutdown("Server thread terminated abnormally.".to_string()));
+
+        // The input may not end after `exit`, so the exit code completes the future
+        Box::new(input_future.select2(exit_future).then(|result| {
+            use futures::future::Either;
+            let future : LSFuture<i32> = match result {
+                Ok(Either::A((_, exit_future))) => Box::new(exit_future),
+                Ok(Either::B((exit_code, _))) => Box::new(futures::future::ok(exit_code)),
+                Err(Either::A((error, _))) => Box::new(futures::future::err(error)),
+                Err(Either::B((error, _))) => Box::new(futures::future::err(error)),
+            };
+            future
+        }))
+    }
+
+    /// Send a request for LSP method `REQ`, returning a future of its result.
+    pub fn send_request<REQ>(&mut self, params: REQ::Params) -> LSFuture<REQ::Result>
+    where
+        REQ : LSRequest + 'static,
+        REQ::Result : Send + 'static,
+    {
+        let request_future = match send_lsp_request::<REQ>(&mut self.endpoint, params) {
+            Ok(request_future) => request_future,
+            Err(error) => return Box::new(futures::future::err(error)),
+        };
+        Box::new(request_future.then(|result| {
+            match result {
+                Ok(Ok(result)) => Ok(result),
+                Ok(Err(error)) => {
+                    Err(LSError::Handler(format!("Request `{}` failed: {:?}", REQ::METHOD, error).into()))
+                }
+                Err(error) => {
+                    Err(LSError::Shutdown(format!("Request `{}` got no response: {:?}", REQ::METHOD, error)))
+                }
+            }
+        }))
+    }
+
+    pub fn send_notification<NOTIF>(&mut self, params: NOTIF::Params) -> LSSendResult<()>
+    where
+        NOTIF : LSNotification,
+    {
+        send_lsp_notification::<NOTIF>(&mut self.endpoint, params)
+    }
+
+}
+
+/// Complete given request with the result of given future, once it resolves.
+/// The future is spawned on the current tokio executor.
+pub fn complete_with_future<RET, ERR_DATA, FUTURE>(completable: MethodCompletable<RET, ERR_DATA>, future: FUTURE)
+where
+    RET : 'static,
+    ERR_DATA : 'static,
+    MethodCompletable<RET, ERR_DATA> : Send,
+    FUTURE : Future<Item = RET, Error = MethodError<ERR_DATA>> + Send + 'static,
+{
+    tokio::spawn(future.then(move |result| {
+        completable.complete(result);
+        Ok(())
+    }));
+}
+
+
+#[test]
+fn lsp_codec__test() {
+    let mut codec = LSPCodec;
+    let mut buffer = BytesMut::new();
+
+    codec.encode("12345".to_string(), &mut buffer).unwrap();
+    let encoded = buffer.clone();
+    buffer.extend_from_slice(&encoded[..10]);
+
+    assert_eq!(codec.decode(&mut buffer).unwrap(), Some("12345".to_string()));
+    assert_eq!(codec.decode(&mut buffer).unwrap(), None);
+    assert_eq!(buffer.len(), 10);
+
+    buffer.extend_from_slice(&encoded[10..]);
+    assert_eq!(codec.decode(&mut buffer).unwrap(), Some("12345".to_string()));
+    assert!(buffer.is_empty());
+
+    buffer.extend_from_slice(b"Content-Length: x\r\n\r\n");
+    assert!(codec.decode(&mut buffer).is_err());
+}
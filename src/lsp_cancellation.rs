@@ -0,0 +1,150 @@
+// Copyright 2016 Bruno Medeiros
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>.
+// This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Cooperative cancellation of requests. The dispatch layer gives each request a `CancellationToken`,
+//! which is cancelled when `$/cancelRequest` arrives for it, or when its document changes.
+//! A handler gets the token from the `RequestInfo` of its request (see `LanguageServerHandling::before_dispatch`),
+//! and polls it to return early, such as with `error_LSP_RequestCancelled`.
+//!
+//! The dispatch layer tracks each request by the id that the read loop gives it, in its `RequestInfo`.
+
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::MutexGuard;
+use std::sync::Weak;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+
+use jsonrpc::jsonrpc_request::RequestParams;
+use serde_json::Value;
+
+/// A flag set when the work it was given to should stop. Clones share the same flag.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+
+    pub fn new() -> CancellationToken {
+        CancellationToken::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+
+}
+
+struct TrackedRequest {
+    /// `None` for a notification.
+    id: Option<Value>,
+    document_uri: Option<String>,
+    /// The request is no longer tracked once all clones of its token are dropped.
+    token: Weak<AtomicBool>,
+}
+
+impl TrackedRequest {
+    fn cancel(&self) {
+        if let Some(flag) = self.token.upgrade() {
+            flag.store(true, Ordering::SeqCst);
+        }
+    }
+}
+
+/// Tracks the tokens of the requests being handled. Clones share the same state.
+#[derive(Clone, Default)]
+pub struct RequestCancellation {
+    requests: Arc<Mutex<Vec<TrackedRequest>>>,
+}
+
+impl RequestCancellation {
+
+    pub fn new() -> RequestCancellation {
+        RequestCancellation::default()
+    }
+
+    fn lock_requests(&self) -> MutexGuard<Vec<TrackedRequest>> {
+        match self.requests.lock() {
+            Ok(requests) => requests,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    /// Called by the dispatch layer when it starts handling a message, with its id (`None` for a notification)
+    /// and the document of its params.
+    pub fn begin_request(&self, id: Option<&Value>, document_uri: Option<&str>) -> CancellationToken {
+        let token = CancellationToken::new();
+        let mut requests = self.lock_requests();
+        requests.retain(|request| request.token.upgrade().is_some());
+        requests.push(TrackedRequest {
+            id : id.cloned(),
+            document_uri : document_uri.map(String::from),
+            token : Arc::downgrade(&token.0),
+        });
+        token
+    }
+
+    /// Cancel the request of given id.
+    pub fn cancel_request(&self, id: &Value) {
+        for request in self.lock_requests().iter() {
+            if request.id.as_ref() == Some(id) {
+                request.cancel();
+            }
+        }
+    }
+
+    /// Cancel the request that given params of `$/cancelRequest` are for.
+    pub fn cancel_request_of_params(&self, params: &RequestParams) {
+        if let RequestParams::Object(ref params) = *params {
+            if let Some(id) = params.get("id") {
+                self.cancel_request(id);
+            }
+        }
+    }
+
+    /// Cancel the requests for given document, such as when it changes.
+    pub fn cancel_document(&self, document_uri: &str) {
+        for request in self.lock_requests().iter() {
+            if request.document_uri.as_ref().map(String::as_str) == Some(document_uri) {
+                request.cancel();
+            }
+        }
+    }
+
+}
+
+#[test]
+fn request_cancellation__test() {
+    use serde_json;
+
+    let cancellation = RequestCancellation::new();
+
+    let hover = cancellation.begin_request(Some(&Value::U64(1)), Some("file:///a"));
+    let completion = cancellation.begin_request(Some(&Value::String("2".to_string())), Some("file:///b"));
+    let symbols = cancellation.begin_request(Some(&Value::U64(3)), None);
+
+    // Ids of different types are different ids
+    cancellation.cancel_request(&Value::U64(2));
+    assert!(!completion.is_cancelled());
+    let params = RequestParams::Object(serde_json::from_str(r#"{ "id": "2" }"#).unwrap());
+    cancellation.cancel_request_of_params(&params);
+    assert!(completion.is_cancelled());
+    assert!(!hover.is_cancelled());
+
+    cancellation.cancel_document("file:///a");
+    assert!(hover.is_cancelled());
+    assert!(!symbols.is_cancelled());
+
+    // Finished requests are no longer tracked
+    drop(hover);
+    drop(completion);
+    cancellation.begin_request(None, None);
+    assert_eq!(cancellation.lock_requests().len(), 2);
+}
\ No newline at end of file
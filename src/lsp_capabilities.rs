@@ -0,0 +1,582 @@
+// Copyright 2016 Bruno Medeiros
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>.
+// This file may not be copied, modified, or distributed
+// except according to those terms.
+
+
+use ls_types::ClientCapabilities;
+use ls_types::TextDocumentSyncKind;
+use serde_json::Value;
+
+use jsonrpc::json_util::JsonObject;
+
+use lsp_methods::*;
+
+/* ----------------- Client capabilities ----------------- */
+
+/// The encoding used to count characters in a `Position`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PositionEncoding {
+    UTF8,
+    UTF16,
+    UTF32,
+}
+
+impl PositionEncoding {
+
+    pub fn from_str(name: &str) -> Option<PositionEncoding> {
+        match name {
+            "utf-8" => Some(PositionEncoding::UTF8),
+            "utf-16" => Some(PositionEncoding::UTF16),
+            "utf-32" => Some(PositionEncoding::UTF32),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            PositionEncoding::UTF8 => "utf-8",
+            PositionEncoding::UTF16 => "utf-16",
+            PositionEncoding::UTF32 => "utf-32",
+        }
+    }
+
+}
+
+/// The `ClientCapabilities` sent by the client on `initialize`, with query helpers.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LSClientCapabilities(pub ClientCapabilities);
+
+impl LSClientCapabilities {
+
+    /// Get the value at given path of object keys, if present.
+    pub fn get_path(&self, path: &[&str]) -> Option<&Value> {
+        value_at_path(&self.0, path)
+    }
+
+    /// Get the boolean at given path. Missing or non-boolean values are `false`.
+    pub fn get_flag(&self, path: &[&str]) -> bool {
+        match self.get_path(path) {
+            Some(&Value::Bool(flag)) => flag,
+            _ => false,
+        }
+    }
+
+    /// Get the strings of the array at given path. Non-string elements are ignored.
+    pub fn get_string_array(&self, path: &[&str]) -> Vec<&str> {
+        match self.get_path(path) {
+            Some(&Value::Array(ref array)) => {
+                array.iter().filter_map(|element| element.as_str()).collect()
+            }
+            _ => vec![],
+        }
+    }
+
+    pub fn supports_snippets(&self) -> bool {
+        self.get_flag(&["textDocument", "completion", "completionItem", "snippetSupport"])
+    }
+
+    /// Get the integers of the array at given path. Non-integer elements are ignored.
+    pub fn get_integer_array(&self, path: &[&str]) -> Vec<i64> {
+        match self.get_path(path) {
+            Some(&Value::Array(ref array)) => {
+                array.iter().filter_map(|element| element.as_i64()).collect()
+            }
+            _ => vec![],
+        }
+    }
+
+    /// Whether the client supports given completion item tag value.
+    pub fn supports_completion_item_tag(&self, tag: i32) -> bool {
+        self.get_integer_array(&["textDocument", "completion", "completionItem", "tagSupport", "valueSet"])
+            .contains(&(tag as i64))
+    }
+
+    /// Whether the client supports given insert text mode value, in completion items.
+    pub fn supports_insert_text_mode(&self, mode: i32) -> bool {
+        self.get_integer_array(&["textDocument", "completion", "completionItem", "insertTextModeSupport", "valueSet"])
+            .contains(&(mode as i64))
+    }
+
+    /// Whether the client supports an `InsertReplaceEdit` as the text edit of completion items.
+    pub fn supports_insert_replace_edit(&self) -> bool {
+        self.get_flag(&["textDocument", "completion", "completionItem", "insertReplaceSupport"])
+    }
+
+    pub fn supports_completion_label_details(&self) -> bool {
+        self.get_flag(&["textDocument", "completion", "completionItem", "labelDetailsSupport"])
+    }
+
+    /// Whether the client supports given property (such as `commitCharacters`) in the item defaults
+    /// of a `CompletionList`.
+    pub fn supports_completion_item_default(&self, property: &str) -> bool {
+        self.get_string_array(&["textDocument", "completion", "completionList", "itemDefaults"]).contains(&property)
+    }
+
+    pub fn supports_markdown_hover(&self) -> bool {
+        self.get_string_array(&["textDocument", "hover", "contentFormat"]).contains(&"markdown")
+    }
+
+    /// Whether the client supports the `documentChanges` form of `WorkspaceEdit`.
+    pub fn supports_document_changes(&self) -> bool {
+        self.get_flag(&["workspace", "workspaceEdit", "documentChanges"])
+    }
+
+    /// Whether the client supports given file operation kind (`create`, `rename` or `delete`)
+    /// in workspace edits.
+    pub fn supports_resource_operation(&self, kind: &str) -> bool {
+        self.get_string_array(&["workspace", "workspaceEdit", "resourceOperations"]).contains(&kind)
+    }
+
+    /// Whether the client answers `workspace/configuration` requests.
+    pub fn supports_configuration(&self) -> bool {
+        self.get_flag(&["workspace", "configuration"])
+    }
+
+    /// Whether the client answers `window/showDocument` requests.
+    pub fn supports_show_document(&self) -> bool {
+        self.get_flag(&["window", "showDocument", "support"])
+    }
+
+    /// Whether the client supports the refresh request of given workspace capability
+    /// (`codeLens`, `semanticTokens`, `inlayHint` or `diagnostics`).
+    pub fn supports_refresh(&self, capability: &str) -> bool {
+        self.get_flag(&["workspace", capability, "refreshSupport"])
+    }
+
+    /// Whether the client supports change annotations in workspace edits.
+    pub fn supports_change_annotations(&self) -> bool {
+        self.get_path(&["workspace", "workspaceEdit", "changeAnnotationSupport"]).map_or(false, Value::is_object)
+    }
+
+    /// Whether the client supports hierarchical `DocumentSymbol`s as the result of `documentSymbol`.
+    pub fn supports_hierarchical_document_symbols(&self) -> bool {
+        self.get_flag(&["textDocument", "documentSymbol", "hierarchicalDocumentSymbolSupport"])
+    }
+
+    /// Whether the client supports `LocationLink`s as the result of given definition-family request
+    /// capability (`definition`, `typeDefinition` or `implementation`).
+    pub fn supports_location_links(&self, capability: &str) -> bool {
+        self.get_flag(&["textDocument", capability, "linkSupport"])
+    }
+
+    pub fn supports_diagnostic_related_information(&self) -> bool {
+        self.get_flag(&["textDocument", "publishDiagnostics", "relatedInformation"])
+    }
+
+    /// Whether the client supports given diagnostic tag value.
+    pub fn supports_diagnostic_tag(&self, tag: i32) -> bool {
+        self.get_integer_array(&["textDocument", "publishDiagnostics", "tagSupport", "valueSet"])
+            .contains(&(tag as i64))
+    }
+
+    pub fn supports_diagnostic_code_description(&self) -> bool {
+        self.get_flag(&["textDocument", "publishDiagnostics", "codeDescriptionSupport"])
+    }
+
+    /// Whether the client preserves the `data` of diagnostics, for the code action requests.
+    pub fn supports_diagnostic_data(&self) -> bool {
+        self.get_flag(&["textDocument", "publishDiagnostics", "dataSupport"])
+    }
+
+    pub fn supports_dynamic_registration(&self, capability: &str) -> bool {
+        self.get_flag(&["textDocument", capability, "dynamicRegistration"])
+    }
+
+    /// Whether the client sends given save notification (`didSave`, `willSave` or `willSaveWaitUntil`).
+    pub fn supports_save_notification(&self, capability: &str) -> bool {
+        self.get_flag(&["textDocument", "synchronization", capability])
+    }
+
+    /// Whether the client understands given method. Clients of any protocol version may leave out
+    /// the capabilities they have no options for, so a method is unsupported only if the client
+    /// explicitly set its capability to `false`. Methods without a capability, such as `initialized`,
+    /// are always supported.
+    pub fn supports_method(&self, method: &str) -> bool {
+        match client_capability_of(method) {
+            Some(path) => self.get_path(path) != Some(&Value::Bool(false)),
+            None => true,
+        }
+    }
+    
+    /// The position encoding for the server to use: the first one listed by the client that is known,
+    /// otherwise UTF-16, which is the protocol default. The server must advertise it in its `initialize`
+    /// result, with `InitializeResultBuilder::position_encoding`, or the client keeps using UTF-16.
+    pub fn position_encoding(&self) -> PositionEncoding {
+        self.get_string_array(&["general", "positionEncodings"]).into_iter()
+            .filter_map(PositionEncoding::from_str)
+            .next()
+            .unwrap_or(PositionEncoding::UTF16)
+    }
+
+}
+
+/// The path of the capability of the client for given method.
+fn client_capability_of(method: &str) -> Option<&'static [&'static str]> {
+    let path : &'static [&'static str] = match method {
+        TEXT_DOCUMENT_COMPLETION | COMPLETION_ITEM_RESOLVE => &["textDocument", "completion"],
+        TEXT_DOCUMENT_HOVER => &["textDocument", "hover"],
+        TEXT_DOCUMENT_SIGNATURE_HELP => &["textDocument", "signatureHelp"],
+        TEXT_DOCUMENT_DEFINITION => &["textDocument", "definition"],
+        TEXT_DOCUMENT_TYPE_DEFINITION => &["textDocument", "typeDefinition"],
+        TEXT_DOCUMENT_IMPLEMENTATION => &["textDocument", "implementation"],
+        TEXT_DOCUMENT_REFERENCES => &["textDocument", "references"],
+        TEXT_DOCUMENT_DOCUMENT_HIGHLIGHT => &["textDocument", "documentHighlight"],
+        TEXT_DOCUMENT_DOCUMENT_SYMBOL => &["textDocument", "documentSymbol"],
+        TEXT_DOCUMENT_CODE_ACTION => &["textDocument", "codeAction"],
+        TEXT_DOCUMENT_CODE_LENS | CODE_LENS_RESOLVE => &["textDocument", "codeLens"],
+        TEXT_DOCUMENT_DOCUMENT_LINK | DOCUMENT_LINK_RESOLVE => &["textDocument", "documentLink"],
+        TEXT_DOCUMENT_FORMATTING => &["textDocument", "formatting"],
+        TEXT_DOCUMENT_RANGE_FORMATTING => &["textDocument", "rangeFormatting"],
+        TEXT_DOCUMENT_ON_TYPE_FORMATTING => &["textDocument", "onTypeFormatting"],
+        TEXT_DOCUMENT_RENAME => &["textDocument", "rename"],
+        TEXT_DOCUMENT_FOLDING_RANGE => &["textDocument", "foldingRange"],
+        TEXT_DOCUMENT_SELECTION_RANGE => &["textDocument", "selectionRange"],
+        TEXT_DOCUMENT_PREPARE_CALL_HIERARCHY |
+        CALL_HIERARCHY_INCOMING_CALLS |
+        CALL_HIERARCHY_OUTGOING_CALLS => &["textDocument", "callHierarchy"],
+        TEXT_DOCUMENT_SEMANTIC_TOKENS_FULL |
+        TEXT_DOCUMENT_SEMANTIC_TOKENS_RANGE => &["textDocument", "semanticTokens"],
+        TEXT_DOCUMENT_LINKED_EDITING_RANGE => &["textDocument", "linkedEditingRange"],
+        TEXT_DOCUMENT_INLAY_HINT => &["textDocument", "inlayHint"],
+        TEXT_DOCUMENT_DIAGNOSTIC => &["textDocument", "diagnostic"],
+        WORKSPACE_SYMBOL => &["workspace", "symbol"],
+        WORKSPACE_EXECUTE_COMMAND => &["workspace", "executeCommand"],
+        _ => return None,
+    };
+    Some(path)
+}
+
+fn value_at_path<'a>(mut value: &'a Value, path: &[&str]) -> Option<&'a Value> {
+    for key in path {
+        value = match *value {
+            Value::Object(ref obj) => {
+                match obj.get(*key) {
+                    Some(child) => child,
+                    None => return None,
+                }
+            }
+            _ => return None,
+        }
+    }
+    Some(value)
+}
+
+/// Builder for the `ClientCapabilities` a client sends on `initialize`.
+/// Each capability is the one queried by the `LSClientCapabilities` method of the same name.
+#[derive(Debug, Clone, Default)]
+pub struct ClientCapabilitiesBuilder {
+    capabilities: JsonObject,
+}
+
+impl ClientCapabilitiesBuilder {
+
+    pub fn new() -> ClientCapabilitiesBuilder {
+        ClientCapabilitiesBuilder::default()
+    }
+
+    /// Set the value at given path of object keys, creating the objects along it.
+    pub fn set(mut self, path: &[&str], value: Value) -> Self {
+        insert_at_path(&mut self.capabilities, path, value);
+        self
+    }
+
+    pub fn flag(self, path: &[&str]) -> Self {
+        self.set(path, Value::Bool(true))
+    }
+
+    fn string_array(self, path: &[&str], strings: &[&str]) -> Self {
+        let strings = strings.iter().map(|string| Value::String(string.to_string())).collect();
+        self.set(path, Value::Array(strings))
+    }
+
+    fn integer_array(self, path: &[&str], integers: &[i32]) -> Self {
+        let integers = integers.iter().map(|&integer| Value::I64(integer as i64)).collect();
+        self.set(path, Value::Array(integers))
+    }
+
+    pub fn snippets(self) -> Self {
+        self.flag(&["textDocument", "completion", "completionItem", "snippetSupport"])
+    }
+
+    pub fn completion_item_tags(self, tags: &[i32]) -> Self {
+        self.integer_array(&["textDocument", "completion", "completionItem", "tagSupport", "valueSet"], tags)
+    }
+
+    pub fn insert_replace_edit(self) -> Self {
+        self.flag(&["textDocument", "completion", "completionItem", "insertReplaceSupport"])
+    }
+
+    pub fn markdown_hover(self) -> Self {
+        self.string_array(&["textDocument", "hover", "contentFormat"], &["markdown", "plaintext"])
+    }
+
+    pub fn document_changes(self) -> Self {
+        self.flag(&["workspace", "workspaceEdit", "documentChanges"])
+    }
+
+    /// The file operation kinds (`create`, `rename` or `delete`) supported in workspace edits.
+    pub fn resource_operations(self, kinds: &[&str]) -> Self {
+        self.string_array(&["workspace", "workspaceEdit", "resourceOperations"], kinds)
+    }
+
+    pub fn configuration(self) -> Self {
+        self.flag(&["workspace", "configuration"])
+    }
+
+    pub fn show_document(self) -> Self {
+        self.flag(&["window", "showDocument", "support"])
+    }
+
+    /// The refresh request of given workspace capability (`codeLens`, `semanticTokens`, `inlayHint`
+    /// or `diagnostics`).
+    pub fn refresh(self, capability: &str) -> Self {
+        self.flag(&["workspace", capability, "refreshSupport"])
+    }
+
+    pub fn hierarchical_document_symbols(self) -> Self {
+        self.flag(&["textDocument", "documentSymbol", "hierarchicalDocumentSymbolSupport"])
+    }
+
+    /// `LocationLink`s as the result of given definition-family request capability.
+    pub fn location_links(self, capability: &str) -> Self {
+        self.flag(&["textDocument", capability, "linkSupport"])
+    }
+
+    pub fn diagnostic_related_information(self) -> Self {
+        self.flag(&["textDocument", "publishDiagnostics", "relatedInformation"])
+    }
+
+    pub fn diagnostic_tags(self, tags: &[i32]) -> Self {
+        self.integer_array(&["textDocument", "publishDiagnostics", "tagSupport", "valueSet"], tags)
+    }
+
+    pub fn dynamic_registration(self, capability: &str) -> Self {
+        self.flag(&["textDocument", capability, "dynamicRegistration"])
+    }
+
+    pub fn save_notification(self, capability: &str) -> Self {
+        self.flag(&["textDocument", "synchronization", capability])
+    }
+
+    /// The position encodings, in order of preference.
+    pub fn position_encodings(self, encodings: &[PositionEncoding]) -> Self {
+        let encodings : Vec<&str> = encodings.iter().map(PositionEncoding::as_str).collect();
+        self.string_array(&["general", "positionEncodings"], &encodings)
+    }
+
+    pub fn build(self) -> ClientCapabilities {
+        Value::Object(self.capabilities)
+    }
+
+}
+
+fn insert_at_path(obj: &mut JsonObject, path: &[&str], value: Value) {
+    let (key, rest) = match path.split_first() {
+        Some((key, rest)) => (key.to_string(), rest),
+        None => return,
+    };
+    if rest.is_empty() {
+        obj.insert(key, value);
+        return;
+    }
+    let child = obj.entry(key).or_insert_with(|| Value::Object(JsonObject::new()));
+    if !child.is_object() {
+        *child = Value::Object(JsonObject::new());
+    }
+    if let Value::Object(ref mut child) = *child {
+        insert_at_path(child, rest, value);
+    }
+}
+
+/* ----------------- Server capabilities ----------------- */
+
+/// The `ServerCapabilities` of the `initialize` result, as JSON: `ls_types::ServerCapabilities`
+/// lacks the capabilities of the methods newer than it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LSServerCapabilities(pub Value);
+
+impl LSServerCapabilities {
+
+    /// Get the value at given path of object keys, if present.
+    pub fn get_path(&self, path: &[&str]) -> Option<&Value> {
+        value_at_path(&self.0, path)
+    }
+
+    /// Whether the server provides given method, according to its capabilities.
+    /// The methods without a capability, such as `shutdown`, are always provided.
+    pub fn supports_method(&self, method: &str) -> bool {
+        match method {
+            INITIALIZE | INITIALIZED | SHUTDOWN | EXIT | CANCEL_REQUEST | SET_TRACE |
+            WORKSPACE_DID_CHANGE_CONFIGURATION | WORKSPACE_DID_CHANGE_WATCHED_FILES => true,
+            TEXT_DOCUMENT_DID_OPEN | TEXT_DOCUMENT_DID_CHANGE | TEXT_DOCUMENT_DID_CLOSE | TEXT_DOCUMENT_DID_SAVE => {
+                self.text_document_sync_kind() != TextDocumentSyncKind::None
+            }
+            _ => match server_capability_of(method) {
+                Some(path) => is_provided(self.get_path(path)),
+                None => false,
+            },
+        }
+    }
+
+    /// The kind of `didChange` notifications the server expects. `None` if it doesn't synchronize documents.
+    pub fn text_document_sync_kind(&self) -> TextDocumentSyncKind {
+        let kind = match self.get_path(&["textDocumentSync"]) {
+            Some(&Value::Object(ref options)) => options.get("change").and_then(Value::as_u64),
+            Some(kind) => kind.as_u64(),
+            None => None,
+        };
+        match kind {
+            Some(1) => TextDocumentSyncKind::Full,
+            Some(2) => TextDocumentSyncKind::Incremental,
+            _ => TextDocumentSyncKind::None,
+        }
+    }
+
+}
+
+/// The path of the capability of the server that tells whether it provides given method.
+fn server_capability_of(method: &str) -> Option<&'static [&'static str]> {
+    let path : &'static [&'static str] = match method {
+        TEXT_DOCUMENT_COMPLETION => &["completionProvider"],
+        COMPLETION_ITEM_RESOLVE => &["completionProvider", "resolveProvider"],
+        TEXT_DOCUMENT_HOVER => &["hoverProvider"],
+        TEXT_DOCUMENT_SIGNATURE_HELP => &["signatureHelpProvider"],
+        TEXT_DOCUMENT_DEFINITION => &["definitionProvider"],
+        TEXT_DOCUMENT_TYPE_DEFINITION => &["typeDefinitionProvider"],
+        TEXT_DOCUMENT_IMPLEMENTATION => &["implementationProvider"],
+        TEXT_DOCUMENT_REFERENCES => &["referencesProvider"],
+        TEXT_DOCUMENT_DOCUMENT_HIGHLIGHT => &["documentHighlightProvider"],
+        TEXT_DOCUMENT_DOCUMENT_SYMBOL => &["documentSymbolProvider"],
+        WORKSPACE_SYMBOL => &["workspaceSymbolProvider"],
+        TEXT_DOCUMENT_CODE_ACTION => &["codeActionProvider"],
+        TEXT_DOCUMENT_CODE_LENS => &["codeLensProvider"],
+        CODE_LENS_RESOLVE => &["codeLensProvider", "resolveProvider"],
+        TEXT_DOCUMENT_DOCUMENT_LINK => &["documentLinkProvider"],
+        DOCUMENT_LINK_RESOLVE => &["documentLinkProvider", "resolveProvider"],
+        TEXT_DOCUMENT_FORMATTING => &["documentFormattingProvider"],
+        TEXT_DOCUMENT_RANGE_FORMATTING => &["documentRangeFormattingProvider"],
+        TEXT_DOCUMENT_ON_TYPE_FORMATTING => &["documentOnTypeFormattingProvider"],
+        TEXT_DOCUMENT_RENAME => &["renameProvider"],
+        TEXT_DOCUMENT_FOLDING_RANGE => &["foldingRangeProvider"],
+        TEXT_DOCUMENT_SELECTION_RANGE => &["selectionRangeProvider"],
+        TEXT_DOCUMENT_PREPARE_CALL_HIERARCHY |
+        CALL_HIERARCHY_INCOMING_CALLS |
+        CALL_HIERARCHY_OUTGOING_CALLS => &["callHierarchyProvider"],
+        TEXT_DOCUMENT_SEMANTIC_TOKENS_FULL => &["semanticTokensProvider", "full"],
+        TEXT_DOCUMENT_SEMANTIC_TOKENS_RANGE => &["semanticTokensProvider", "range"],
+        TEXT_DOCUMENT_LINKED_EDITING_RANGE => &["linkedEditingRangeProvider"],
+        TEXT_DOCUMENT_INLAY_HINT => &["inlayHintProvider"],
+        TEXT_DOCUMENT_DIAGNOSTIC => &["diagnosticProvider"],
+        _ => return None,
+    };
+    Some(path)
+}
+
+/// A provider capability is either a flag, or the options of the provider.
+fn is_provided(capability: Option<&Value>) -> bool {
+    match capability {
+        None | Some(&Value::Null) | Some(&Value::Bool(false)) => false,
+        Some(_) => true,
+    }
+}
+
+
+#[test]
+fn client_capabilities__test() {
+    use serde_json;
+
+    let caps = LSClientCapabilities(Value::Null);
+    assert_eq!(caps.supports_snippets(), false);
+    assert_eq!(caps.supports_markdown_hover(), false);
+    assert_eq!(caps.position_encoding(), PositionEncoding::UTF16);
+    assert_eq!(caps.supports_method(TEXT_DOCUMENT_DOCUMENT_LINK), true);
+    assert_eq!(caps.supports_method(INITIALIZED), true);
+
+    let json = r#"{
+        "general": { "positionEncodings": ["utf-7", "utf-8"] },
+        "textDocument": {
+            "completion": { "completionItem": { "snippetSupport": true } },
+            "hover": { "contentFormat": ["markdown", "plaintext"] }
+        }
+    }"#;
+    let caps = LSClientCapabilities(serde_json::from_str(json).unwrap());
+    assert_eq!(caps.supports_snippets(), true);
+    assert_eq!(caps.supports_markdown_hover(), true);
+    assert_eq!(caps.supports_dynamic_registration("hover"), false);
+    assert_eq!(caps.supports_location_links("definition"), false);
+    assert_eq!(caps.position_encoding(), PositionEncoding::UTF8);
+    assert_eq!(caps.supports_method(TEXT_DOCUMENT_DOCUMENT_LINK), true);
+
+    let json = r#"{ "textDocument": { "typeDefinition": { "linkSupport": true } } }"#;
+    let caps = LSClientCapabilities(serde_json::from_str(json).unwrap());
+    assert_eq!(caps.supports_location_links("typeDefinition"), true);
+    assert_eq!(caps.supports_location_links("definition"), false);
+    assert_eq!(caps.supports_method(TEXT_DOCUMENT_IMPLEMENTATION), true);
+
+    // Only a capability explicitly set to false makes its methods unsupported
+    let json = r#"{
+        "textDocument": { "documentLink": false, "hover": {} },
+        "workspace": { "executeCommand": false }
+    }"#;
+    let caps = LSClientCapabilities(serde_json::from_str(json).unwrap());
+    assert_eq!(caps.supports_method(TEXT_DOCUMENT_DOCUMENT_LINK), false);
+    assert_eq!(caps.supports_method(DOCUMENT_LINK_RESOLVE), false);
+    assert_eq!(caps.supports_method(WORKSPACE_EXECUTE_COMMAND), false);
+    assert_eq!(caps.supports_method(TEXT_DOCUMENT_HOVER), true);
+    assert_eq!(caps.supports_method(TEXT_DOCUMENT_RENAME), true);
+}
+
+#[test]
+fn client_capabilities_builder__test() {
+    let caps = LSClientCapabilities(ClientCapabilitiesBuilder::new()
+        .snippets()
+        .markdown_hover()
+        .location_links("definition")
+        .dynamic_registration("hover")
+        .save_notification("didSave")
+        .diagnostic_tags(&[1, 2])
+        .position_encodings(&[PositionEncoding::UTF8, PositionEncoding::UTF16])
+        .set(&["textDocument", "hover", "x"], Value::Bool(true))
+        .build());
+
+    assert_eq!(caps.supports_snippets(), true);
+    assert_eq!(caps.supports_markdown_hover(), true);
+    assert_eq!(caps.supports_location_links("definition"), true);
+    assert_eq!(caps.supports_dynamic_registration("hover"), true);
+    assert_eq!(caps.supports_save_notification("didSave"), true);
+    assert_eq!(caps.supports_save_notification("willSave"), false);
+    assert_eq!(caps.supports_diagnostic_tag(2), true);
+    assert_eq!(caps.supports_show_document(), false);
+    assert_eq!(caps.position_encoding(), PositionEncoding::UTF8);
+    assert_eq!(caps.get_flag(&["textDocument", "hover", "x"]), true);
+}
+
+#[test]
+fn server_capabilities__test() {
+    use serde_json;
+
+    let json = r#"{
+        "textDocumentSync": { "openClose": true, "change": 2 },
+        "hoverProvider": true, "definitionProvider": false, "renameProvider": { "prepareProvider": true },
+        "completionProvider": { "triggerCharacters": ["."] },
+        "semanticTokensProvider": { "full": { "delta": true } }
+    }"#;
+    let caps = LSServerCapabilities(serde_json::from_str(json).unwrap());
+    assert_eq!(caps.text_document_sync_kind(), TextDocumentSyncKind::Incremental);
+    assert_eq!(caps.supports_method(TEXT_DOCUMENT_DID_OPEN), true);
+    assert_eq!(caps.supports_method(SHUTDOWN), true);
+    assert_eq!(caps.supports_method(TEXT_DOCUMENT_HOVER), true);
+    assert_eq!(caps.supports_method(TEXT_DOCUMENT_DEFINITION), false);
+    assert_eq!(caps.supports_method(TEXT_DOCUMENT_RENAME), true);
+    assert_eq!(caps.supports_method(TEXT_DOCUMENT_COMPLETION), true);
+    assert_eq!(caps.supports_method(COMPLETION_ITEM_RESOLVE), false);
+    assert_eq!(caps.supports_method(TEXT_DOCUMENT_SEMANTIC_TOKENS_FULL), true);
+    assert_eq!(caps.supports_method(TEXT_DOCUMENT_SEMANTIC_TOKENS_RANGE), false);
+    assert_eq!(caps.supports_method("custom/method"), false);
+
+    let caps = LSServerCapabilities(serde_json::from_str(r#"{ "textDocumentSync": 0 }"#).unwrap());
+    assert_eq!(caps.supports_method(TEXT_DOCUMENT_DID_CHANGE), false);
+}
@@ -0,0 +1,455 @@
+// Copyright 2016 Bruno Medeiros
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>.
+// This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! A client connection to an external language server, running as a child process
+//! that speaks LSP on its stdin and stdout. For test harnesses, and tools that use other servers.
+//!
+//! ```ignore
+//! let mut connection = ClientConnection::spawn(Command::new("rls")).unwrap();
+//! connection.initialize(Value::Object(JsonObject::new())).unwrap();
+//! let hover = connection.server_rpc().hover(params).unwrap().wait();
+//! let status = connection.shutdown(Duration::from_secs(5)).unwrap();
+//! ```
+//!
+//! A `LanguageClientConnection` is the same for a server on any input and output, such as a socket:
+//! the mirror image of running a `LanguageServerHandling` with `LSPEndpoint::run_server`.
+//! The requests and notifications from the server are handled by a `LanguageClientHandling`.
+//!
+//! ```ignore
+//! let mut connection = LanguageClientConnection::from_streams(stream.try_clone()?, stream, MyClient::new());
+//! let locations = connection.server_rpc().references(params).unwrap().wait();
+//! connection.shutdown().unwrap();
+//! ```
+
+use std::io;
+use std::io::BufReader;
+use std::process;
+use std::process::Command;
+use std::process::Stdio;
+use std::thread;
+use std::time::Duration;
+use std::time::Instant;
+
+use util::core::*;
+
+use jsonrpc::*;
+use jsonrpc::json_util::JsonObject;
+use ls_types::*;
+use serde_json::Value;
+
+use lsp::*;
+use lsp_capabilities::LSServerCapabilities;
+use lsp_documents::DocumentSyncClient;
+use lsp_errors::LSError;
+use lsp_methods::LSNotification;
+use lsp_methods::LSRequest;
+use lsp_testing::ClientMessages;
+use lsp_testing::MockLanguageClient;
+
+/// The interval to check if the server process exited, in milliseconds.
+const EXIT_POLL_MILLIS: u64 = 20;
+
+/// A connection to a language server child process. The process is killed when this is dropped,
+/// unless it exited through `shutdown` before.
+pub struct ClientConnection {
+    /// The client endpoint, connected to the server.
+    pub endpoint: Endpoint,
+    /// The messages received from the server, if the client is the default `MockLanguageClient`.
+    pub client_messages: ClientMessages,
+    initialize_result: Option<LSInitializeResult>,
+    child: process::Child,
+    reader_thread: Option<thread::JoinHandle<()>>,
+}
+
+impl ClientConnection {
+
+    /// Launch the server with given command, with its stdin and stdout connected to this client.
+    /// Messages from the server are recorded in `client_messages`.
+    pub fn spawn(command: Command) -> Result<ClientConnection, LSError> {
+        let client = MockLanguageClient::new();
+        let mut connection = try!(Self::spawn_with_client(command, client.clone()));
+        connection.client_messages = client.messages;
+        Ok(connection)
+    }
+
+    /// Launch the server with given command, with the messages from the server
+    /// to the client handled by `client`.
+    pub fn spawn_with_client<CLIENT>(mut command: Command, client: CLIENT) -> Result<ClientConnection, LSError>
+    where
+        CLIENT : LanguageClientHandling + Send + 'static,
+    {
+        let mut child = try!(command.stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().map_err(LSError::Transport));
+        let (stdin, stdout) = match (child.stdin.take(), child.stdout.take()) {
+            (Some(stdin), Some(stdout)) => (stdin, stdout),
+            _ => {
+                let _ = child.kill();
+                return Err(LSError::Shutdown("The server process has no stdio.".to_string()));
+            }
+        };
+
+        let endpoint = LSPEndpoint::create_lsp_output_with_output_stream(move || stdin);
+        let client_endpoint = endpoint.clone();
+        let reader_thread = thread::spawn(move || {
+            let _ = LSPEndpoint::run_client_from_input(&mut BufReader::new(stdout), client_endpoint, client);
+        });
+
+        Ok(ClientConnection {
+            endpoint : endpoint, client_messages : ClientMessages::default(), initialize_result : None,
+            child : child, reader_thread : Some(reader_thread),
+        })
+    }
+
+    /// The process id of the server.
+    pub fn process_id(&self) -> u32 {
+        self.child.id()
+    }
+
+    /// The result of `initialize`, once it succeeded.
+    pub fn initialize_result(&self) -> Option<&LSInitializeResult> {
+        self.initialize_result.as_ref()
+    }
+
+    /// A handle to send the requests and notifications of the server methods.
+    pub fn server_rpc(&mut self) -> LspServerRpc_ {
+        server_rpc_handle(&mut self.endpoint)
+    }
+
+    /// Send a request, without waiting for the result.
+    pub fn send_request<REQ>(&mut self, params: REQ::Params) -> LSSendResult<LSRequestFuture<REQ>>
+    where
+        REQ : LSRequest,
+    {
+        send_lsp_request::<REQ>(&mut self.endpoint, params)
+    }
+
+    /// Send a request and wait for its result. A response error is returned as an error.
+    pub fn request<REQ>(&mut self, params: REQ::Params) -> GResult<REQ::Result>
+    where
+        REQ : LSRequest,
+    {
+        let future = try!(self.send_request::<REQ>(params));
+        match future.wait() {
+            Ok(Ok(result)) => Ok(result),
+            Ok(Err(error)) => Err(format!("Request `{}` failed: {:?}", REQ::METHOD, error).into()),
+            Err(error) => Err(format!("Request `{}` got no response: {:?}", REQ::METHOD, error).into()),
+        }
+    }
+
+    pub fn notify<NOTIF>(&mut self, params: NOTIF::Params) -> GResult<()>
+    where
+        NOTIF : LSNotification,
+    {
+        try!(send_lsp_notification::<NOTIF>(&mut self.endpoint, params));
+        Ok(())
+    }
+
+    /// Send the `initialize` request with given client capabilities, wait for the result,
+    /// then send the `initialized` notification.
+    pub fn initialize(&mut self, capabilities: Value) -> GResult<LSInitializeResult> {
+        let params = InitializeParams {
+            process_id : Some(process::id() as u64),
+            root_path : None,
+            initialization_options : None,
+            capabilities : capabilities,
+        };
+        self.initialize_with(params)
+    }
+
+    pub fn initialize_with(&mut self, params: InitializeParams) -> GResult<LSInitializeResult> {
+        let result = try!(self.request::<methods::Initialize>(params));
+        try!(self.notify::<methods::Initialized>(Value::Object(JsonObject::new())));
+        self.initialize_result = Some(result.clone());
+        Ok(result)
+    }
+
+    /// Send `shutdown` and `exit`, and wait for the server process to exit, up to given timeout.
+    /// If it doesn't exit by then, it is killed, and an error is returned.
+    pub fn shutdown(mut self, timeout: Duration) -> GResult<process::ExitStatus> {
+        let shutdown_result = self.request::<methods::Shutdown>(());
+        let _ = self.notify::<methods::Exit>(());
+        // Closes the stdin of the server
+        self.endpoint.shutdown_and_join();
+
+        let deadline = Instant::now() + timeout;
+        while try!(self.child.try_wait()).is_none() {
+            if Instant::now() >= deadline {
+                let _ = self.child.kill();
+                let _ = self.child.wait();
+                self.join_reader();
+                return Err(format!("The server did not exit within {:?}.", timeout).into());
+            }
+            thread::sleep(Duration::from_millis(EXIT_POLL_MILLIS));
+        }
+        let status = try!(self.child.wait());
+        self.join_reader();
+        if let Err(error) = shutdown_result {
+            warn!("{}", error);
+        }
+        Ok(status)
+    }
+
+    fn join_reader(&mut self) {
+        if let Some(reader_thread) = self.reader_thread.take() {
+            let _ = reader_thread.join();
+        }
+    }
+
+}
+
+impl Drop for ClientConnection {
+    fn drop(&mut self) {
+        if self.reader_thread.is_some() {
+            let _ = self.child.kill();
+            let _ = self.child.wait();
+            self.endpoint.shutdown_and_join();
+            self.join_reader();
+        }
+    }
+}
+
+/* ----------------- LanguageClientConnection ----------------- */
+
+/// A connection of a language client to a server, over given input and output.
+/// The messages from the server are read in a thread of the connection, until its input ends.
+pub struct LanguageClientConnection {
+    /// The client endpoint, connected to the server.
+    pub endpoint: Endpoint,
+    initialize_result: Option<LSInitializeResult>,
+    server_capabilities: Option<LSServerCapabilities>,
+    reader_thread: Option<thread::JoinHandle<()>>,
+}
+
+impl LanguageClientConnection {
+
+    /// Connect to a server through given message reader and writer,
+    /// with the messages from the server to the client handled by `client`.
+    pub fn new<MR, MW, CLIENT>(msg_reader: MR, msg_writer: MW, client: CLIENT) -> LanguageClientConnection
+    where
+        MR : MessageReader + Send + 'static,
+        MW : MessageWriter + Send + 'static,
+        CLIENT : LanguageClientHandling + Send + 'static,
+    {
+        let endpoint = LSPEndpoint::create_lsp_output(move || msg_writer);
+        let client_endpoint = endpoint.clone();
+        let reader_thread = thread::spawn(move || {
+            let mut msg_reader = msg_reader;
+            let _ = LSPEndpoint::run_endpoint_loop(&mut msg_reader, client_endpoint,
+                Box::new(ClientRequestHandler(client)));
+        });
+        LanguageClientConnection::with_reader_thread(endpoint, reader_thread)
+    }
+
+    /// Connect to a server over given streams, of messages with the LSP base protocol framing.
+    pub fn from_streams<IN, OUT, CLIENT>(input: IN, output: OUT, client: CLIENT) -> LanguageClientConnection
+    where
+        IN : io::Read + Send + 'static,
+        OUT : io::Write + Send + 'static,
+        CLIENT : LanguageClientHandling + Send + 'static,
+    {
+        let endpoint = LSPEndpoint::create_lsp_output_with_output_stream(move || output);
+        let client_endpoint = endpoint.clone();
+        let reader_thread = thread::spawn(move || {
+            let _ = LSPEndpoint::run_client_from_input(&mut BufReader::new(input), client_endpoint, client);
+        });
+        LanguageClientConnection::with_reader_thread(endpoint, reader_thread)
+    }
+
+    fn with_reader_thread(endpoint: Endpoint, reader_thread: thread::JoinHandle<()>) -> LanguageClientConnection {
+        LanguageClientConnection {
+            endpoint : endpoint, initialize_result : None, server_capabilities : None,
+            reader_thread : Some(reader_thread),
+        }
+    }
+
+    /// The result of `initialize`, once it succeeded.
+    pub fn initialize_result(&self) -> Option<&LSInitializeResult> {
+        self.initialize_result.as_ref()
+    }
+
+    /// The capabilities of the server, once `initialize` succeeded.
+    pub fn server_capabilities(&self) -> Option<&LSServerCapabilities> {
+        self.server_capabilities.as_ref()
+    }
+
+    /// Whether the server provides given method, according to its capabilities.
+    /// Nothing is provided before `initialize`.
+    pub fn server_supports(&self, method: &str) -> bool {
+        self.server_capabilities.as_ref().map_or(false, |capabilities| capabilities.supports_method(method))
+    }
+
+    /// A `DocumentSyncClient` to send the buffers of the client to the server, once `initialize` succeeded.
+    pub fn document_sync(&self) -> Option<DocumentSyncClient> {
+        self.server_capabilities.as_ref()
+            .map(|capabilities| DocumentSyncClient::negotiated(self.endpoint.clone(), capabilities))
+    }
+
+    /// Send the `initialize` request with given client capabilities (see `ClientCapabilitiesBuilder`),
+    /// wait for the result, then send the `initialized` notification.
+    pub fn initialize(&mut self, capabilities: ClientCapabilities) -> GResult<LSInitializeResult> {
+        let params = InitializeParams {
+            process_id : Some(process::id() as u64),
+            root_path : None,
+            initialization_options : None,
+            capabilities : capabilities,
+        };
+        self.initialize_with(params)
+    }
+
+    pub fn initialize_with(&mut self, params: InitializeParams) -> GResult<LSInitializeResult> {
+        // The result is read as JSON, to keep the capabilities that `ServerCapabilities` lacks
+        let future : RequestFuture<Value, Value> = 
+            try!(self.endpoint.send_request(methods::INITIALIZE, params).map_err(LSError::from_gerror));
+        let result = match future.wait() {
+            Ok(Ok(result)) => result,
+            Ok(Err(error)) => return Err(format!("Request `initialize` failed: {:?}", error).into()),
+            Err(error) => return Err(format!("Request `initialize` got no response: {:?}", error).into()),
+        };
+        let capabilities = result.find("capabilities").cloned().unwrap_or(Value::Null);
+        let initialize_result = try!(LSInitializeResult::from_json(result));
+        try!(self.notify::<methods::Initialized>(Value::Object(JsonObject::new())));
+
+        self.initialize_result = Some(initialize_result.clone());
+        self.server_capabilities = Some(LSServerCapabilities(capabilities));
+        Ok(initialize_result)
+    }
+
+    /// A handle to send the requests and notifications of the server methods, 
+    /// with their params and result types.
+    pub fn server_rpc(&mut self) -> LspServerRpc_ {
+        server_rpc_handle(&mut self.endpoint)
+    }
+
+    /// Send a request, without waiting for the result.
+    pub fn send_request<REQ>(&mut self, params: REQ::Params) -> LSSendResult<LSRequestFuture<REQ>>
+    where
+        REQ : LSRequest,
+    {
+        send_lsp_request::<REQ>(&mut self.endpoint, params)
+    }
+
+    /// Send a request and wait for its result. A response error is returned as an error.
+    pub fn request<REQ>(&mut self, params: REQ::Params) -> GResult<REQ::Result>
+    where
+        REQ : LSRequest,
+    {
+        let future = try!(self.send_request::<REQ>(params));
+        match future.wait() {
+            Ok(Ok(result)) => Ok(result),
+            Ok(Err(error)) => Err(format!("Request `{}` failed: {:?}", REQ::METHOD, error).into()),
+            Err(error) => Err(format!("Request `{}` got no response: {:?}", REQ::METHOD, error).into()),
+        }
+    }
+
+    pub fn notify<NOTIF>(&mut self, params: NOTIF::Params) -> LSSendResult<()>
+    where
+        NOTIF : LSNotification,
+    {
+        send_lsp_notification::<NOTIF>(&mut self.endpoint, params)
+    }
+
+    /// Send `shutdown` and wait for its response, then send `exit`, and close the connection.
+    pub fn shutdown(mut self) -> GResult<()> {
+        let result = self.request::<methods::Shutdown>(());
+        let _ = self.notify::<methods::Exit>(());
+        self.close();
+        result
+    }
+
+    /// Close the output to the server, and wait for the input from it to end.
+    pub fn close(mut self) {
+        self.endpoint.shutdown_and_join();
+        self.join_reader();
+    }
+
+    fn join_reader(&mut self) {
+        if let Some(reader_thread) = self.reader_thread.take() {
+            let _ = reader_thread.join();
+        }
+    }
+
+}
+
+impl Drop for LanguageClientConnection {
+    fn drop(&mut self) {
+        if self.reader_thread.is_some() {
+            self.endpoint.shutdown_and_join();
+        }
+    }
+}
+
+
+#[test]
+fn client_connection_spawn__test() {
+    assert!(ClientConnection::spawn(Command::new("/nonexistent/language-server")).is_err());
+}
+
+#[cfg(unix)]
+#[test]
+fn client_connection_echo__test() {
+    // `cat` echoes the requests back: the client answers them as unknown methods,
+    // and those answers are echoed back as the responses
+    let mut connection = ClientConnection::spawn(Command::new("cat")).unwrap();
+    assert!(connection.initialize(Value::Object(JsonObject::new())).is_err());
+    assert!(connection.initialize_result().is_none());
+
+    let status = connection.shutdown(Duration::from_secs(5)).unwrap();
+    assert!(status.success());
+}
+
+#[test]
+fn language_client_connection__test() {
+    use url::Url;
+    use lsp_capabilities::ClientCapabilitiesBuilder;
+    use lsp_protocol::FoldingRangeParams;
+    use lsp_testing::pipe;
+
+    struct TestServer;
+
+    impl LanguageServerHandling for TestServer {
+        fn server_capabilities(&self) -> ServerCapabilities {
+            InitializeResultBuilder::new().references().build().capabilities
+        }
+        fn references(&mut self, params: ReferenceParams, completable: LSMethodCompletable<methods::References>) {
+            let position = Position { line : 0, character : 0 };
+            let range = Range { start : position, end : position };
+            completable.complete(Ok(vec![Location { uri : params.text_document.uri, range : range }]))
+        }
+    }
+
+    let (client_writer, server_reader) = pipe();
+    let (server_writer, client_reader) = pipe();
+    let server_thread = thread::spawn(move || {
+        let endpoint = LSPEndpoint::create_lsp_output_with_output_stream(|| server_writer);
+        let context = LSServerContext::new(endpoint);
+        LSPEndpoint::run_server_from_input(&mut BufReader::new(server_reader), context, TestServer)
+    });
+
+    let client = MockLanguageClient::new();
+    let mut connection = LanguageClientConnection::from_streams(client_reader, client_writer, client.clone());
+    assert!(!connection.server_supports(methods::TEXT_DOCUMENT_REFERENCES));
+    let capabilities = ClientCapabilitiesBuilder::new().markdown_hover().build();
+    connection.initialize(capabilities).unwrap();
+    assert!(connection.server_supports(methods::TEXT_DOCUMENT_REFERENCES));
+    assert!(!connection.server_supports(methods::TEXT_DOCUMENT_FOLDING_RANGE));
+    let params = ReferenceParams {
+        text_document : TextDocumentIdentifier { uri : Url::parse("file:///doc.rs").unwrap() },
+        position : Position { line : 0, character : 0 },
+        context : ReferenceContext { include_declaration : true },
+    };
+    let locations = connection.server_rpc().references(params).unwrap().wait().unwrap().unwrap();
+    assert_eq!(locations.len(), 1);
+    assert_eq!(locations[0].uri.as_str(), "file:///doc.rs");
+    // Not provided by the server
+    let params = FoldingRangeParams {
+        text_document : TextDocumentIdentifier { uri : Url::parse("file:///doc.rs").unwrap() },
+    };
+    assert!(connection.server_rpc().folding_range(params).unwrap().wait().unwrap().is_err());
+
+    connection.shutdown().unwrap();
+    assert_eq!(server_thread.join().unwrap(), 0);
+}
+
@@ -0,0 +1,110 @@
+// Copyright 2016 Bruno Medeiros
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>.
+// This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Pluggable encodings of the message content, for JSON-RPC deployments other than LSP,
+//! such as MessagePack or CBOR. LSP itself always uses `JsonCodec`.
+//!
+//! The endpoint works with JSON text, so a codec converts between that text and the encoded
+//! content of the transport messages (which keep the `Content-Length` framing).
+
+use std::io;
+
+use util::core::*;
+
+use jsonrpc::*;
+
+use lsp::LSPEndpoint;
+use lsp_errors::LSError;
+use lsp_transport::parse_transport_message_content;
+use lsp_transport::write_transport_message_content;
+
+/// An encoding of the message content.
+pub trait MessageCodec : Send {
+    /// Encode given JSON message.
+    fn encode(&self, message: &str) -> Result<Vec<u8>, LSError>;
+    /// Decode given content to a JSON message.
+    fn decode(&self, content: Vec<u8>) -> Result<String, LSError>;
+}
+
+/// The JSON encoding, in UTF-8, which is the one LSP uses.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct JsonCodec;
+
+impl MessageCodec for JsonCodec {
+    fn encode(&self, message: &str) -> Result<Vec<u8>, LSError> {
+        Ok(message.as_bytes().to_vec())
+    }
+
+    fn decode(&self, content: Vec<u8>) -> Result<String, LSError> {
+        String::from_utf8(content).map_err(|error| {
+            LSError::Parse("Message content is not valid UTF-8:".to_string(), Some(error.into()))
+        })
+    }
+}
+
+/// A message reader of given codec.
+pub struct CodecMessageReader<T : io::BufRead, CODEC : MessageCodec>(pub T, pub CODEC);
+
+impl<T : io::BufRead, CODEC : MessageCodec> MessageReader for CodecMessageReader<T, CODEC> {
+    fn read_next(&mut self) -> GResult<String> {
+        let content = try!(parse_transport_message_content(&mut self.0));
+        self.1.decode(content).map_err(GError::from)
+    }
+}
+
+/// A message writer of given codec.
+pub struct CodecMessageWriter<T : io::Write, CODEC : MessageCodec>(pub T, pub CODEC);
+
+impl<T : io::Write, CODEC : MessageCodec> MessageWriter for CodecMessageWriter<T, CODEC> {
+    fn write_message(&mut self, msg: &str) -> GResult<()> {
+        let content = try!(self.1.encode(msg));
+        write_transport_message_content(&content, &mut self.0).map_err(GError::from)
+    }
+}
+
+/// Create an Endpoint with given output stream provider, encoding the messages with given codec.
+pub fn create_output_with_codec<OUT, OUT_PROV, CODEC>(output_stream_provider: OUT_PROV, codec: CODEC) -> Endpoint
+where
+    OUT : io::Write + 'static,
+    OUT_PROV : FnOnce() -> OUT + Send + 'static,
+    CODEC : MessageCodec + 'static,
+{
+    LSPEndpoint::create_lsp_output(move || CodecMessageWriter(output_stream_provider(), codec))
+}
+
+
+#[cfg(test)]
+struct Utf16Codec;
+
+#[cfg(test)]
+impl MessageCodec for Utf16Codec {
+    fn encode(&self, message: &str) -> Result<Vec<u8>, LSError> {
+        Ok(message.encode_utf16().flat_map(|unit| vec![unit as u8, (unit >> 8) as u8]).collect())
+    }
+
+    fn decode(&self, content: Vec<u8>) -> Result<String, LSError> {
+        let units : Vec<u16> = content.chunks(2).map(|pair| {
+            pair[0] as u16 | (*pair.get(1).unwrap_or(&0) as u16) << 8
+        }).collect();
+        String::from_utf16(&units).map_err(|error| LSError::Parse(error.to_string(), None))
+    }
+}
+
+#[test]
+fn codec_message_reader_writer__test() {
+    let mut writer = CodecMessageWriter(vec![], Utf16Codec);
+    writer.write_message("{ \"a\": \"\u{4E2D}\" }").unwrap();
+    assert!(writer.0.starts_with(b"Content-Length: 24\r\n\r\n{\0"));
+
+    let mut reader = CodecMessageReader(&writer.0[..], Utf16Codec);
+    assert_eq!(reader.read_next().unwrap(), "{ \"a\": \"\u{4E2D}\" }");
+    assert!(reader.read_next().is_err());
+
+    let mut writer = CodecMessageWriter(vec![], JsonCodec);
+    writer.write_message("{}").unwrap();
+    assert_eq!(writer.0, b"Content-Length: 2\r\n\r\n{}".to_vec());
+}
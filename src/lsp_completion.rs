@@ -0,0 +1,607 @@
+// Copyright 2016 Bruno Medeiros
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>.
+// This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Completion items, with `MarkupContent` documentation, and builders for them.
+
+use serde;
+use serde::de::Error as DeError;
+use serde_json;
+use serde_json::Value;
+
+use jsonrpc::json_util::JsonObject;
+use ls_types::*;
+
+use lsp_capabilities::LSClientCapabilities;
+use lsp_enums::CompletionItemTag;
+use lsp_enums::InsertTextMode;
+use lsp_markup::LSDocumentation;
+use lsp_markup::MarkupContent;
+use lsp_markup::MarkupTarget;
+use lsp_protocol::CompletionEditRange;
+use lsp_protocol::CompletionItemDefaults;
+use lsp_protocol::CompletionItemLabelDetails;
+use lsp_protocol::InsertReplaceEdit;
+use lsp_protocol::insert_field;
+use lsp_protocol::take_field;
+
+/* ----------------- LSCompletionItem ----------------- */
+
+/// A completion item. The `documentation` of the `ls_types` item is not used,
+/// in favor of the one that can be `MarkupContent`. The fields newer than `ls_types` are added here.
+/// If `insert_replace_edit` is set, it is the `textEdit` of the item, instead of the `ls_types` one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LSCompletionItem {
+    pub item: CompletionItem,
+    pub documentation: Option<LSDocumentation>,
+    pub tags: Vec<CompletionItemTag>,
+    pub label_details: Option<CompletionItemLabelDetails>,
+    pub insert_text_mode: Option<InsertTextMode>,
+    /// Characters that accept the item when typed while it is selected, and are then inserted.
+    pub commit_characters: Option<Vec<String>>,
+    pub insert_replace_edit: Option<InsertReplaceEdit>,
+}
+
+impl From<CompletionItem> for LSCompletionItem {
+    fn from(mut item: CompletionItem) -> LSCompletionItem {
+        let documentation = item.documentation.take().map(LSDocumentation::String);
+        LSCompletionItem {
+            item : item, documentation : documentation, tags : vec![], label_details : None,
+            insert_text_mode : None, commit_characters : None, insert_replace_edit : None,
+        }
+    }
+}
+
+impl LSCompletionItem {
+
+    /// Convert to the form supported by a client with given capabilities (`None` if unknown):
+    /// the documentation is converted, an `InsertReplaceEdit` becomes a `TextEdit` of its insert range,
+    /// and the tags, label details and insert text mode the client doesn't support are dropped.
+    pub fn into_supported(mut self, client_capabilities: Option<&LSClientCapabilities>) -> LSCompletionItem {
+        self.documentation = self.documentation.map(|documentation| {
+            documentation.into_supported(MarkupTarget::CompletionDocumentation, client_capabilities)
+        });
+        self.tags.retain(|tag| client_capabilities.map_or(false, |caps| caps.supports_completion_item_tag(tag.to_i32())));
+        if !client_capabilities.map_or(false, LSClientCapabilities::supports_completion_label_details) {
+            self.label_details = None;
+        }
+        if let Some(mode) = self.insert_text_mode {
+            if !client_capabilities.map_or(false, |caps| caps.supports_insert_text_mode(mode.to_i32())) {
+                self.insert_text_mode = None;
+            }
+        }
+        if !client_capabilities.map_or(false, LSClientCapabilities::supports_insert_replace_edit) {
+            if let Some(edit) = self.insert_replace_edit.take() {
+                self.item.text_edit = Some(edit.to_insert_edit());
+            }
+        }
+        self
+    }
+
+    pub fn to_json(&self) -> Value {
+        let mut obj = match serde_json::to_value(&self.item) {
+            Value::Object(obj) => obj,
+            _ => JsonObject::new(),
+        };
+        obj.remove("documentation");
+        if let Some(ref documentation) = self.documentation {
+            obj.insert("documentation".to_string(), documentation.to_json());
+        }
+        if !self.tags.is_empty() {
+            insert_field(&mut obj, "tags", &self.tags);
+        }
+        insert_field(&mut obj, "labelDetails", &self.label_details);
+        insert_field(&mut obj, "insertTextMode", &self.insert_text_mode);
+        insert_field(&mut obj, "commitCharacters", &self.commit_characters);
+        if let Some(ref edit) = self.insert_replace_edit {
+            insert_field(&mut obj, "textEdit", edit);
+        }
+        Value::Object(obj)
+    }
+
+    pub fn from_json(value: Value) -> Result<LSCompletionItem, String> {
+        let mut obj = match value {
+            Value::Object(obj) => obj,
+            _ => return Err("CompletionItem is not an object.".to_string()),
+        };
+
+        let documentation = match obj.remove("documentation") {
+            Some(Value::Null) | None => None,
+            Some(documentation) => Some(try!(LSDocumentation::from_json(documentation))),
+        };
+        let tags : Option<Vec<CompletionItemTag>> = try!(take_field(&mut obj, "tags", "CompletionItem"));
+        let label_details = try!(take_field(&mut obj, "labelDetails", "CompletionItem"));
+        let insert_text_mode = try!(take_field(&mut obj, "insertTextMode", "CompletionItem"));
+        let commit_characters = try!(take_field(&mut obj, "commitCharacters", "CompletionItem"));
+        let insert_replace_edit = match obj.get("textEdit").and_then(|text_edit| text_edit.find("insert")) {
+            Some(_) => Some(try!(take_field(&mut obj, "textEdit", "CompletionItem"))),
+            None => None,
+        };
+        let item = try!(serde_json::from_value(Value::Object(obj)).map_err(|err| err.to_string()));
+        Ok(LSCompletionItem {
+            item : item, documentation : documentation, tags : tags.unwrap_or_default(), label_details : label_details,
+            insert_text_mode : insert_text_mode, commit_characters : commit_characters,
+            insert_replace_edit : insert_replace_edit,
+        })
+    }
+
+}
+
+impl serde::Serialize for LSCompletionItem {
+    fn serialize<S>(&self, serializer: &mut S) -> Result<(), S::Error>
+    where
+        S : serde::Serializer,
+    {
+        self.to_json().serialize(serializer)
+    }
+}
+
+impl serde::Deserialize for LSCompletionItem {
+    fn deserialize<D>(deserializer: &mut D) -> Result<LSCompletionItem, D::Error>
+    where
+        D : serde::Deserializer,
+    {
+        let value = try!(Value::deserialize(deserializer));
+        LSCompletionItem::from_json(value).map_err(D::Error::custom)
+    }
+}
+
+/* ----------------- LSCompletionList ----------------- */
+
+/// The result of `textDocument/completion`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LSCompletionList {
+    /// Whether the list is not complete, and further typing should recompute it.
+    pub is_incomplete: bool,
+    /// The values of the items that don't have their own. Only clients listing a property in
+    /// `textDocument.completion.completionList.itemDefaults` support it here.
+    pub item_defaults: Option<CompletionItemDefaults>,
+    pub items: Vec<LSCompletionItem>,
+}
+
+impl LSCompletionList {
+
+    pub fn new(items: Vec<LSCompletionItem>) -> LSCompletionList {
+        LSCompletionList { is_incomplete : false, item_defaults : None, items : items }
+    }
+
+    pub fn incomplete(items: Vec<LSCompletionItem>) -> LSCompletionList {
+        LSCompletionList { is_incomplete : true, item_defaults : None, items : items }
+    }
+
+    /// Move the commit characters, insert text mode and data that all items have in common
+    /// to the item defaults, if a client with given capabilities supports them there.
+    /// This shrinks the payload of large completion lists.
+    pub fn extract_item_defaults(mut self, client_capabilities: Option<&LSClientCapabilities>) -> LSCompletionList {
+        let supports = |property: &str| {
+            client_capabilities.map_or(false, |caps| caps.supports_completion_item_default(property))
+        };
+        let mut defaults = self.item_defaults.take().unwrap_or_default();
+
+        if defaults.commit_characters.is_none() && supports("commitCharacters") {
+            if let Some(common) = common_value(&self.items, |item| item.commit_characters.clone()) {
+                for item in &mut self.items {
+                    item.commit_characters = None;
+                }
+                defaults.commit_characters = Some(common);
+            }
+        }
+        if defaults.insert_text_mode.is_none() && supports("insertTextMode") {
+            if let Some(common) = common_value(&self.items, |item| item.insert_text_mode) {
+                for item in &mut self.items {
+                    item.insert_text_mode = None;
+                }
+                defaults.insert_text_mode = Some(common);
+            }
+        }
+        if defaults.data.is_none() && supports("data") {
+            if let Some(common) = common_value(&self.items, |item| item.item.data.clone()) {
+                for item in &mut self.items {
+                    item.item.data = None;
+                }
+                defaults.data = Some(common);
+            }
+        }
+
+        if defaults != CompletionItemDefaults::default() {
+            self.item_defaults = Some(defaults);
+        }
+        self
+    }
+
+    /// Convert to the form supported by a client with given capabilities (`None` if unknown):
+    /// the items are converted, and the item defaults the client doesn't support
+    /// are applied to the items that don't have their own.
+    pub fn into_supported(self, client_capabilities: Option<&LSClientCapabilities>) -> LSCompletionList {
+        let mut items = self.items;
+        let mut item_defaults = self.item_defaults;
+        if let Some(ref mut defaults) = item_defaults {
+            let supports = |property: &str| {
+                client_capabilities.map_or(false, |caps| caps.supports_completion_item_default(property))
+            };
+            if !supports("commitCharacters") {
+                if let Some(commit_characters) = defaults.commit_characters.take() {
+                    for item in items.iter_mut().filter(|item| item.commit_characters.is_none()) {
+                        item.commit_characters = Some(commit_characters.clone());
+                    }
+                }
+            }
+            if !supports("editRange") {
+                if let Some(edit_range) = defaults.edit_range.take() {
+                    let items_without_edit = items.iter_mut().filter(|item| {
+                        item.item.text_edit.is_none() && item.insert_replace_edit.is_none()
+                    });
+                    for item in items_without_edit {
+                        let new_text = item.item.insert_text.take().unwrap_or_else(|| item.item.label.clone());
+                        match edit_range {
+                            CompletionEditRange::Range(ref range) => {
+                                item.item.text_edit = Some(TextEdit { range : range.clone(), new_text : new_text });
+                            }
+                            CompletionEditRange::InsertReplace { ref insert, ref replace } => {
+                                item.insert_replace_edit = Some(InsertReplaceEdit {
+                                    new_text : new_text, insert : insert.clone(), replace : replace.clone()
+                                });
+                            }
+                        }
+                    }
+                }
+            }
+            if !supports("insertTextFormat") {
+                if let Some(format) = defaults.insert_text_format.take() {
+                    items = items.into_iter().map(|item| apply_json_default(item, "insertTextFormat", format.to_json()))
+                        .collect();
+                }
+            }
+            if !supports("insertTextMode") {
+                if let Some(mode) = defaults.insert_text_mode.take() {
+                    for item in items.iter_mut().filter(|item| item.insert_text_mode.is_none()) {
+                        item.insert_text_mode = Some(mode);
+                    }
+                }
+            }
+            if !supports("data") {
+                if let Some(data) = defaults.data.take() {
+                    for item in items.iter_mut().filter(|item| item.item.data.is_none()) {
+                        item.item.data = Some(data.clone());
+                    }
+                }
+            }
+        }
+        if item_defaults == Some(CompletionItemDefaults::default()) {
+            item_defaults = None;
+        }
+        let items = items.into_iter().map(|item| item.into_supported(client_capabilities)).collect();
+        LSCompletionList { is_incomplete : self.is_incomplete, item_defaults : item_defaults, items : items }
+    }
+
+    pub fn to_json(&self) -> Value {
+        let mut obj = JsonObject::new();
+        obj.insert("isIncomplete".to_string(), Value::Bool(self.is_incomplete));
+        insert_field(&mut obj, "itemDefaults", &self.item_defaults);
+        obj.insert("items".to_string(), Value::Array(self.items.iter().map(LSCompletionItem::to_json).collect()));
+        Value::Object(obj)
+    }
+
+    /// Parse a `CompletionList`, or a plain array of items, which the spec also allows.
+    pub fn from_json(value: Value) -> Result<LSCompletionList, String> {
+        let (is_incomplete, item_defaults, items) = match value {
+            Value::Array(items) => (false, None, items),
+            Value::Object(mut obj) => {
+                let is_incomplete = obj.get("isIncomplete").and_then(Value::as_bool).unwrap_or(false);
+                let item_defaults = try!(take_field(&mut obj, "itemDefaults", "CompletionList"));
+                match obj.remove("items") {
+                    Some(Value::Array(items)) => (is_incomplete, item_defaults, items),
+                    _ => return Err("CompletionList: missing `items`.".to_string()),
+                }
+            }
+            Value::Null => (false, None, vec![]),
+            _ => return Err("CompletionList is not an object or array.".to_string()),
+        };
+        let mut result = vec![];
+        for item in items {
+            result.push(try!(LSCompletionItem::from_json(item)));
+        }
+        Ok(LSCompletionList { is_incomplete : is_incomplete, item_defaults : item_defaults, items : result })
+    }
+
+}
+
+/// The value of given property, if all given items have the same one.
+fn common_value<T, PROPERTY>(items: &[LSCompletionItem], property: PROPERTY) -> Option<T>
+where
+    T : PartialEq,
+    PROPERTY : Fn(&LSCompletionItem) -> Option<T>,
+{
+    let first = match items.first().and_then(|item| property(item)) {
+        Some(first) => first,
+        None => return None,
+    };
+    if items[1..].iter().all(|item| property(item).as_ref() == Some(&first)) { Some(first) } else { None }
+}
+
+/// Set given property of the JSON form of given item, unless it has it.
+/// (For the properties `ls_types` may not have)
+fn apply_json_default(item: LSCompletionItem, property: &str, value: Value) -> LSCompletionItem {
+    let mut json = item.to_json();
+    if let Value::Object(ref mut obj) = json {
+        if obj.get(property).map_or(true, Value::is_null) {
+            obj.insert(property.to_string(), value);
+        }
+    }
+    LSCompletionItem::from_json(json).unwrap_or(item)
+}
+
+impl serde::Serialize for LSCompletionList {
+    fn serialize<S>(&self, serializer: &mut S) -> Result<(), S::Error>
+    where
+        S : serde::Serializer,
+    {
+        self.to_json().serialize(serializer)
+    }
+}
+
+impl serde::Deserialize for LSCompletionList {
+    fn deserialize<D>(deserializer: &mut D) -> Result<LSCompletionList, D::Error>
+    where
+        D : serde::Deserializer,
+    {
+        let value = try!(Value::deserialize(deserializer));
+        LSCompletionList::from_json(value).map_err(D::Error::custom)
+    }
+}
+
+impl From<Vec<LSCompletionItem>> for LSCompletionList {
+    fn from(items: Vec<LSCompletionItem>) -> LSCompletionList {
+        LSCompletionList::new(items)
+    }
+}
+
+/* ----------------- Builder ----------------- */
+
+/// Builder for `LSCompletionItem`.
+#[derive(Debug, Clone)]
+pub struct CompletionItemBuilder {
+    item: LSCompletionItem,
+}
+
+impl CompletionItemBuilder {
+
+    pub fn new(label: &str) -> CompletionItemBuilder {
+        let mut item = CompletionItem::default();
+        item.label = label.to_string();
+        CompletionItemBuilder { item : LSCompletionItem::from(item) }
+    }
+
+    pub fn kind(mut self, kind: CompletionItemKind) -> Self {
+        self.item.item.kind = Some(kind);
+        self
+    }
+
+    pub fn detail(mut self, detail: &str) -> Self {
+        self.item.item.detail = Some(detail.to_string());
+        self
+    }
+
+    pub fn documentation(mut self, documentation: MarkupContent) -> Self {
+        self.item.documentation = Some(LSDocumentation::Markup(documentation));
+        self
+    }
+
+    pub fn plain_text_documentation(self, documentation: &str) -> Self {
+        self.documentation(MarkupContent::plain_text(documentation))
+    }
+
+    pub fn markdown_documentation(self, documentation: &str) -> Self {
+        self.documentation(MarkupContent::markdown(documentation))
+    }
+
+    /// The text to sort the item by, instead of the label.
+    pub fn sort_text(mut self, sort_text: &str) -> Self {
+        self.item.item.sort_text = Some(sort_text.to_string());
+        self
+    }
+
+    /// The text to filter the item by, instead of the label.
+    pub fn filter_text(mut self, filter_text: &str) -> Self {
+        self.item.item.filter_text = Some(filter_text.to_string());
+        self
+    }
+
+    /// Insert given text, instead of the label. Replaces any text edit.
+    pub fn insert_text(mut self, insert_text: &str) -> Self {
+        self.item.item.insert_text = Some(insert_text.to_string());
+        self.item.item.text_edit = None;
+        self.item.insert_replace_edit = None;
+        self
+    }
+
+    /// Apply given edit, instead of inserting text. Replaces any insert text.
+    pub fn text_edit(mut self, text_edit: TextEdit) -> Self {
+        self.item.item.text_edit = Some(text_edit);
+        self.item.item.insert_text = None;
+        self.item.insert_replace_edit = None;
+        self
+    }
+
+    /// Apply an edit of given text to the insert range or the replace range, as the client chooses.
+    /// Replaces any insert text or text edit.
+    pub fn insert_replace_edit(mut self, new_text: &str, insert: Range, replace: Range) -> Self {
+        self.item.insert_replace_edit = Some(InsertReplaceEdit {
+            new_text : new_text.to_string(), insert : insert, replace : replace
+        });
+        self.item.item.text_edit = None;
+        self.item.item.insert_text = None;
+        self
+    }
+
+    /// Add an edit to apply elsewhere in the document, such as adding an import.
+    pub fn additional_text_edit(mut self, text_edit: TextEdit) -> Self {
+        self.item.item.additional_text_edits.get_or_insert_with(Vec::new).push(text_edit);
+        self
+    }
+
+    pub fn command(mut self, command: Command) -> Self {
+        self.item.item.command = Some(command);
+        self
+    }
+
+    /// Data preserved between the completion request and the resolve request.
+    pub fn data(mut self, data: Value) -> Self {
+        self.item.item.data = Some(data);
+        self
+    }
+
+    /// Mark the item as deprecated, with the `Deprecated` tag.
+    pub fn deprecated(mut self) -> Self {
+        if !self.item.tags.contains(&CompletionItemTag::Deprecated) {
+            self.item.tags.push(CompletionItemTag::Deprecated);
+        }
+        self
+    }
+
+    /// Details shown next to the label, such as a signature, and a less prominent description.
+    pub fn label_details(mut self, detail: Option<&str>, description: Option<&str>) -> Self {
+        self.item.label_details = Some(CompletionItemLabelDetails {
+            detail : detail.map(String::from),
+            description : description.map(String::from),
+        });
+        self
+    }
+
+    pub fn insert_text_mode(mut self, insert_text_mode: InsertTextMode) -> Self {
+        self.item.insert_text_mode = Some(insert_text_mode);
+        self
+    }
+
+    /// Characters that accept the item when typed while it is selected, and are then inserted.
+    pub fn commit_characters(mut self, commit_characters: &[&str]) -> Self {
+        self.item.commit_characters = Some(commit_characters.iter().map(|ch| ch.to_string()).collect());
+        self
+    }
+
+    pub fn build(self) -> LSCompletionItem {
+        self.item
+    }
+
+}
+
+
+#[test]
+fn completion_item_builder__test() {
+    let position = Position { line : 0, character : 2 };
+    let edit = TextEdit { range : Range { start : position, end : position }, new_text : "foo()".to_string() };
+    let item = CompletionItemBuilder::new("foo")
+        .kind(CompletionItemKind::Function)
+        .detail("fn foo()")
+        .markdown_documentation("Does *foo*.")
+        .sort_text("0_foo")
+        .insert_text("foo")
+        .text_edit(edit.clone())
+        .build();
+
+    assert_eq!(item.item.label, "foo");
+    assert_eq!(item.item.text_edit, Some(edit));
+    assert_eq!(item.item.insert_text, None);
+
+    let json = item.to_json();
+    assert_eq!(json.lookup("documentation.kind"), Some(&Value::String("markdown".to_string())));
+    assert_eq!(json.lookup("sortText"), Some(&Value::String("0_foo".to_string())));
+
+    let list = LSCompletionList::incomplete(vec![item.clone()]);
+    let list2 : LSCompletionList = serde_json::from_str(&serde_json::to_string(&list).unwrap()).unwrap();
+    assert_eq!(list2, list);
+
+    // Legacy forms: an array of items, with string documentation
+    let list3 = LSCompletionList::from_json(serde_json::from_str(
+        r#"[{ "label": "bar", "documentation": "Does bar." }]"#).unwrap()).unwrap();
+    assert_eq!(list3.is_incomplete, false);
+    assert_eq!(list3.items[0].documentation, Some(LSDocumentation::String("Does bar.".to_string())));
+    assert_eq!(list3.items[0].to_json().lookup("documentation"), Some(&Value::String("Does bar.".to_string())));
+
+    let legacy_list = list.into_supported(None);
+    assert_eq!(legacy_list.items[0].documentation, Some(LSDocumentation::String("Does foo.".to_string())));
+}
+
+#[test]
+fn completion_item_defaults__test() {
+    let items : Vec<LSCompletionItem> = ["foo", "bar"].iter().map(|label| {
+        CompletionItemBuilder::new(label)
+            .deprecated()
+            .label_details(Some("()"), None)
+            .insert_text_mode(InsertTextMode::AsIs)
+            .commit_characters(&["."])
+            .data(Value::Bool(true))
+            .build()
+    }).collect();
+    let list = LSCompletionList::new(items.clone());
+    assert_eq!(LSCompletionList::from_json(list.to_json()).unwrap(), list);
+
+    let json = r#"{ "textDocument": { "completion": {
+        "completionItem": { "tagSupport": { "valueSet": [1] }, "labelDetailsSupport": true },
+        "completionList": { "itemDefaults": ["commitCharacters", "data"] }
+    } } }"#;
+    let caps = LSClientCapabilities(serde_json::from_str(json).unwrap());
+
+    let compact = list.clone().extract_item_defaults(Some(&caps));
+    let defaults = compact.item_defaults.clone().unwrap();
+    assert_eq!(defaults.commit_characters, Some(vec![".".to_string()]));
+    assert_eq!(defaults.data, Some(Value::Bool(true)));
+    assert_eq!(defaults.insert_text_mode, None);
+    assert_eq!(compact.items[0].commit_characters, None);
+    assert_eq!(compact.items[0].insert_text_mode, Some(InsertTextMode::AsIs));
+    assert_eq!(LSCompletionList::from_json(compact.to_json()).unwrap(), compact);
+
+    let supported = compact.clone().into_supported(Some(&caps));
+    assert_eq!(supported.item_defaults, compact.item_defaults);
+    assert_eq!(supported.items[0].tags, vec![CompletionItemTag::Deprecated]);
+    assert!(supported.items[0].label_details.is_some());
+    assert_eq!(supported.items[0].insert_text_mode, None);
+
+    // Without item defaults support, they are applied back to the items
+    let legacy = compact.into_supported(None);
+    assert_eq!(legacy.item_defaults, None);
+    assert_eq!(legacy.items[1].commit_characters, Some(vec![".".to_string()]));
+    assert_eq!(legacy.items[1].item.data, Some(Value::Bool(true)));
+    assert_eq!(legacy.items[1].tags, vec![]);
+    assert_eq!(legacy.items[1].label_details, None);
+}
+
+#[test]
+fn insert_replace_edit__test() {
+    let range = |start: u64, end: u64| Range {
+        start : Position { line : 0, character : start }, end : Position { line : 0, character : end },
+    };
+    let item = CompletionItemBuilder::new("foo")
+        .insert_text("foo")
+        .insert_replace_edit("foo", range(0, 2), range(0, 5))
+        .build();
+    assert_eq!(item.item.insert_text, None);
+
+    let json = item.to_json();
+    assert_eq!(json.lookup("textEdit.insert.end.character"), Some(&Value::U64(2)));
+    assert_eq!(json.lookup("textEdit.replace.end.character"), Some(&Value::U64(5)));
+    assert_eq!(LSCompletionItem::from_json(json).unwrap(), item);
+
+    let json = r#"{ "textDocument": { "completion": { "completionItem": { "insertReplaceSupport": true } } } }"#;
+    let caps = LSClientCapabilities(serde_json::from_str(json).unwrap());
+    assert_eq!(item.clone().into_supported(Some(&caps)), item);
+
+    // Without support, the insert range is used
+    let legacy = item.into_supported(None);
+    assert_eq!(legacy.insert_replace_edit, None);
+    assert_eq!(legacy.item.text_edit, Some(TextEdit { range : range(0, 2), new_text : "foo".to_string() }));
+    assert_eq!(legacy.to_json().lookup("textEdit.range.end.character"), Some(&Value::U64(2)));
+
+    // An insert-replace edit range of the item defaults
+    let mut list = LSCompletionList::new(vec![CompletionItemBuilder::new("bar").build()]);
+    list.item_defaults = Some(CompletionItemDefaults {
+        edit_range : Some(CompletionEditRange::InsertReplace { insert : range(0, 1), replace : range(0, 3) }),
+        .. CompletionItemDefaults::default()
+    });
+    let supported = list.into_supported(Some(&caps));
+    assert_eq!(supported.item_defaults, None);
+    assert_eq!(supported.items[0].insert_replace_edit.as_ref().map(|edit| edit.new_text.as_str()), Some("bar"));
+}
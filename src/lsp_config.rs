@@ -0,0 +1,207 @@
+// Copyright 2016 Bruno Medeiros
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>.
+// This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Typed server configuration. A `ConfigurationManager` extracts the section of the server
+//! from the settings of `workspace/didChangeConfiguration`, or from the result of a `workspace/configuration`
+//! request, merges it over the defaults, and deserializes it into the configuration type of the server.
+//!
+//! ```ignore
+//! let mut config = ConfigurationManager::<ToyConfig>::new(Some("toy"));
+//! config.on_change(|_, new_config| info!("Configuration changed: {:?}", new_config));
+//!
+//! fn workspace_change_configuration(&mut self, params: DidChangeConfigurationParams) {
+//!     if let Err(error) = self.config.did_change_configuration(params) {
+//!         let _ = self.context.show_error(&format!("Invalid settings: {}", error));
+//!     }
+//! }
+//! ```
+
+use serde;
+use serde_json;
+use serde_json::Value;
+use url::Url;
+
+use ls_types::DidChangeConfigurationParams;
+
+use lsp::LSRequestFuture;
+use lsp::LSSendResult;
+use lsp::LSServerContext;
+use lsp::send_lsp_request;
+use lsp_errors::LSError;
+use lsp_methods as methods;
+use lsp_protocol::ConfigurationItem;
+use lsp_protocol::ConfigurationParams;
+
+/// A callback for a change of the effective configuration, called with the previous and the new one.
+pub type ConfigurationChangeCallback<CONFIG> = Box<FnMut(&CONFIG, &CONFIG) + Send>;
+
+/// The effective configuration of a server, of type `CONFIG`.
+pub struct ConfigurationManager<CONFIG> {
+    section: Option<String>,
+    defaults: Value,
+    config: CONFIG,
+    change_callbacks: Vec<ConfigurationChangeCallback<CONFIG>>,
+}
+
+impl<CONFIG> ConfigurationManager<CONFIG>
+where
+    CONFIG : serde::Serialize + serde::Deserialize + Clone + PartialEq,
+{
+
+    /// A manager for given settings section, such as `"toy"` or `"toy.formatting"`, or for all the settings
+    /// if None. The defaults are those of `CONFIG::default()`.
+    pub fn new(section: Option<&str>) -> ConfigurationManager<CONFIG>
+    where
+        CONFIG : Default,
+    {
+        Self::with_defaults(section, CONFIG::default())
+    }
+
+    /// A manager for given settings section, with given defaults for the settings the client doesn't set.
+    pub fn with_defaults(section: Option<&str>, defaults: CONFIG) -> ConfigurationManager<CONFIG> {
+        ConfigurationManager {
+            section : section.map(str::to_string),
+            defaults : serde_json::to_value(&defaults),
+            config : defaults,
+            change_callbacks : vec![],
+        }
+    }
+
+    pub fn section(&self) -> Option<&str> {
+        self.section.as_ref().map(String::as_str)
+    }
+
+    /// The effective configuration.
+    pub fn config(&self) -> &CONFIG {
+        &self.config
+    }
+
+    /// Call given callback when the effective configuration changes.
+    pub fn on_change<CALLBACK>(&mut self, callback: CALLBACK)
+    where
+        CALLBACK : FnMut(&CONFIG, &CONFIG) + Send + 'static,
+    {
+        self.change_callbacks.push(Box::new(callback));
+    }
+
+    /// Update the configuration from the settings of `workspace/didChangeConfiguration`.
+    /// Returns whether the effective configuration changed.
+    ///
+    /// Clients that support `workspace/configuration` may send `null` settings instead,
+    /// in which case the configuration is not changed, and should be requested with `request`.
+    pub fn did_change_configuration(&mut self, params: DidChangeConfigurationParams) -> Result<bool, LSError> {
+        if params.settings.is_null() {
+            return Ok(false);
+        }
+        let mut settings = params.settings;
+        if let Some(ref section) = self.section {
+            for name in section.split('.') {
+                settings = match settings {
+                    Value::Object(mut obj) => obj.remove(name).unwrap_or(Value::Null),
+                    _ => Value::Null,
+                };
+            }
+        }
+        self.update(settings)
+    }
+
+    /// The params to request the section of this manager, for given scope, with `workspace/configuration`.
+    pub fn configuration_params(&self, scope_uri: Option<Url>) -> ConfigurationParams {
+        ConfigurationParams { items : vec![ConfigurationItem { scope_uri : scope_uri, section : self.section.clone() }] }
+    }
+
+    /// Request the section of this manager from the client. The response must be waited for
+    /// outside of the thread that dispatches the messages, which is the one that reads it,
+    /// and applied with `did_receive_configuration`.
+    pub fn request(&self, context: &LSServerContext) -> LSSendResult<LSRequestFuture<methods::WorkspaceConfiguration>> {
+        send_lsp_request::<methods::WorkspaceConfiguration>(&mut context.endpoint.clone(), self.configuration_params(None))
+    }
+
+    /// Update the configuration from the result of a `workspace/configuration` request for the section.
+    /// Returns whether the effective configuration changed.
+    pub fn did_receive_configuration(&mut self, result: Vec<Value>) -> Result<bool, LSError> {
+        self.update(result.into_iter().next().unwrap_or(Value::Null))
+    }
+
+    /// Update the configuration from given settings of the section, merged over the defaults.
+    /// Returns whether the effective configuration changed.
+    pub fn update(&mut self, settings: Value) -> Result<bool, LSError> {
+        let mut merged = self.defaults.clone();
+        merge_settings(&mut merged, settings);
+        let config : CONFIG = try!(serde_json::from_value(merged).map_err(|error| {
+            LSError::Parse("Invalid settings:".to_string(), Some(error.into()))
+        }));
+        if config == self.config {
+            return Ok(false);
+        }
+        let old_config = ::std::mem::replace(&mut self.config, config);
+        for callback in &mut self.change_callbacks {
+            callback(&old_config, &self.config);
+        }
+        Ok(true)
+    }
+
+}
+
+/// Merge given settings over given defaults: objects are merged recursively,
+/// and a `null` setting keeps the default.
+pub fn merge_settings(defaults: &mut Value, settings: Value) {
+    match settings {
+        Value::Null => {}
+        Value::Object(settings) => {
+            if let Value::Object(ref mut defaults) = *defaults {
+                for (name, setting) in settings {
+                    if let Some(default) = defaults.get_mut(&name) {
+                        merge_settings(default, setting);
+                        continue;
+                    }
+                    defaults.insert(name, setting);
+                }
+                return;
+            }
+            *defaults = Value::Object(settings);
+        }
+        settings => *defaults = settings,
+    }
+}
+
+
+#[test]
+fn configuration_manager__test() {
+    use std::sync::Arc;
+    use std::sync::Mutex;
+    use jsonrpc::json_util::JsonObject;
+
+    let defaults : JsonObject = serde_json::from_str(r#"{ "lint": { "enabled": true, "level": "warn" }, "width": 80 }"#)
+        .unwrap();
+    let mut manager = ConfigurationManager::with_defaults(Some("toy.format"), defaults.clone());
+    let changes = Arc::new(Mutex::new(0));
+    let counted = changes.clone();
+    manager.on_change(move |_, _| *counted.lock().unwrap() += 1);
+
+    let params = |settings: &str| DidChangeConfigurationParams { settings : serde_json::from_str(settings).unwrap() };
+    assert_eq!(manager.did_change_configuration(params("null")).unwrap(), false);
+    assert_eq!(manager.did_change_configuration(params(r#"{ "other": {} }"#)).unwrap(), false);
+    assert_eq!(manager.config(), &defaults);
+
+    let settings = r#"{ "toy": { "format": { "lint": { "level": "error" }, "width": null } } }"#;
+    assert_eq!(manager.did_change_configuration(params(settings)).unwrap(), true);
+    let expected : JsonObject = serde_json::from_str(r#"{ "lint": { "enabled": true, "level": "error" }, "width": 80 }"#)
+        .unwrap();
+    assert_eq!(manager.config(), &expected);
+    assert_eq!(manager.did_change_configuration(params(settings)).unwrap(), false);
+    assert_eq!(*changes.lock().unwrap(), 1);
+
+    // The section is requested, and its settings received, without the enclosing sections
+    let params = manager.configuration_params(None);
+    assert_eq!(params.items[0].section, Some("toy.format".to_string()));
+    assert_eq!(manager.did_receive_configuration(vec![serde_json::from_str(r#"{ "width": 100 }"#).unwrap()]).unwrap(), true);
+    assert_eq!(manager.config().get("width"), Some(&Value::U64(100)));
+    assert_eq!(manager.config().get("lint"), defaults.get("lint"));
+    assert!(manager.did_receive_configuration(vec![Value::String("invalid".to_string())]).is_err());
+    assert_eq!(*changes.lock().unwrap(), 2);
+}
@@ -0,0 +1,338 @@
+// Copyright 2016 Bruno Medeiros
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>.
+// This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! A protocol conformance suite for `LanguageServerHandling` implementations.
+//! It drives the server through raw messages, so it can also send malformed ones,
+//! and reports the spec rules the server violates.
+//!
+//! ```ignore
+//! #[test]
+//! fn conformance() {
+//!     check_conformance(|context| MyServer::new(context)).assert_conformant();
+//! }
+//! ```
+
+use std::fmt;
+use std::io::Write;
+use std::sync::Arc;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use jsonrpc::json_util::JsonObject;
+use serde_json;
+use serde_json::Value;
+
+use lsp::*;
+use lsp_errors::*;
+use lsp_testing::pipe;
+use lsp_testing::PipeWriter;
+use lsp_transport::parse_jsonrpc_message_bytes;
+use lsp_transport::parse_transport_message_content;
+use lsp_transport::write_jsonrpc_message;
+use lsp_transport::JsonRpcMessage;
+use lsp_transport::write_transport_message;
+
+/* ----------------- Raw connection ----------------- */
+
+/// A connection to a server running in a background thread, exchanging raw JSON messages.
+pub struct RawConnection {
+    writer: Option<PipeWriter>,
+    messages: mpsc::Receiver<Value>,
+    server_thread: Option<thread::JoinHandle<i32>>,
+    /// How long to wait for a message from the server.
+    pub timeout: Duration,
+}
+
+impl RawConnection {
+
+    pub fn start<SERVER, CREATE>(create_server: CREATE) -> RawConnection
+    where
+        SERVER : LanguageServerHandling + 'static,
+        CREATE : FnOnce(LSServerContext) -> SERVER + Send + 'static,
+    {
+        let (client_writer, mut server_reader) = pipe();
+        let (server_writer, mut client_reader) = pipe();
+
+        let server_thread = thread::spawn(move || {
+            let endpoint = LSPEndpoint::create_lsp_output_with_output_stream(|| server_writer);
+            let context = LSServerContext::new(endpoint);
+            let server = create_server(context.clone());
+            LSPEndpoint::run_server_from_input(&mut server_reader, context, server)
+        });
+
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            while let Ok(content) = parse_transport_message_content(&mut client_reader) {
+                match parse_jsonrpc_message_bytes(&content) {
+                    Ok(message) => {
+                        if sender.send(message).is_err() {
+                            break;
+                        }
+                    }
+                    Err(error) => warn!("Server sent invalid message: {} {}", error, String::from_utf8_lossy(&content)),
+                }
+            }
+        });
+
+        RawConnection {
+            writer : Some(client_writer), messages : receiver, server_thread : Some(server_thread),
+            timeout : Duration::from_secs(5),
+        }
+    }
+
+    /// Send given bytes as the content of a message.
+    pub fn send_raw(&mut self, content: &str) {
+        if let Some(ref mut writer) = self.writer {
+            let _ = write_transport_message(content, writer);
+        }
+    }
+
+    /// Send given bytes as they are, without the message header.
+    pub fn send_bytes(&mut self, bytes: &[u8]) {
+        if let Some(ref mut writer) = self.writer {
+            let _ = writer.write_all(bytes);
+        }
+    }
+
+    pub fn send_request(&mut self, id: u64, method: &str, params: Value) {
+        self.send_message(&JsonRpcMessage { id : Some(id), method : method, params : &params });
+    }
+
+    pub fn send_notification(&mut self, method: &str, params: Value) {
+        self.send_message(&JsonRpcMessage { id : None, method : method, params : &params });
+    }
+
+    fn send_message(&mut self, message: &JsonRpcMessage<Value>) {
+        if let Some(ref mut writer) = self.writer {
+            let _ = write_jsonrpc_message(message, writer);
+        }
+    }
+
+    /// Receive the next message from the server, if any arrives before the timeout.
+    pub fn receive(&mut self) -> Option<Value> {
+        self.messages.recv_timeout(self.timeout).ok()
+    }
+
+    /// Receive the response with given id, skipping messages initiated by the server.
+    pub fn receive_response(&mut self, id: u64) -> Option<Value> {
+        while let Some(message) = self.receive() {
+            if message.find("method").is_none() && message.find("id").and_then(Value::as_u64) == Some(id) {
+                return Some(message);
+            }
+        }
+        None
+    }
+
+    /// Receive any response, skipping messages initiated by the server.
+    pub fn receive_any_response(&mut self) -> Option<Value> {
+        while let Some(message) = self.receive() {
+            if message.find("method").is_none() {
+                return Some(message);
+            }
+        }
+        None
+    }
+
+    /// Close the input of the server, and wait for it to terminate. Returns its exit code,
+    /// or `None` if it panicked.
+    pub fn close(mut self) -> Option<i32> {
+        self.writer = None;
+        self.server_thread.take().and_then(|server_thread| server_thread.join().ok())
+    }
+
+}
+
+impl Drop for RawConnection {
+    fn drop(&mut self) {
+        self.writer = None;
+        if let Some(server_thread) = self.server_thread.take() {
+            let _ = server_thread.join();
+        }
+    }
+}
+
+/// The error code of given response, if it is an error response.
+pub fn response_error_code(response: &Value) -> Option<i64> {
+    response.lookup("error.code").and_then(Value::as_i64)
+}
+
+/* ----------------- Conformance suite ----------------- */
+
+/// A spec rule that a server violated.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConformanceViolation {
+    pub rule: &'static str,
+    pub details: String,
+}
+
+/// The result of `check_conformance`.
+#[derive(Debug, Clone, Default)]
+pub struct ConformanceReport {
+    /// The rules that were checked.
+    pub checked: Vec<&'static str>,
+    pub violations: Vec<ConformanceViolation>,
+}
+
+impl ConformanceReport {
+
+    pub fn is_conformant(&self) -> bool {
+        self.violations.is_empty()
+    }
+
+    /// Panic with the list of violations, if there are any.
+    pub fn assert_conformant(&self) {
+        if !self.is_conformant() {
+            panic!("Server is not conformant to the LSP spec:\n{}", self);
+        }
+    }
+
+    fn check(&mut self, rule: &'static str, result: Result<(), String>) {
+        self.checked.push(rule);
+        if let Err(details) = result {
+            self.violations.push(ConformanceViolation { rule : rule, details : details });
+        }
+    }
+
+}
+
+impl fmt::Display for ConformanceReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for violation in &self.violations {
+            try!(writeln!(f, "[{}] {}", violation.rule, violation.details));
+        }
+        write!(f, "{} rules checked, {} violated.", self.checked.len(), self.violations.len())
+    }
+}
+
+pub const RULE__ServerNotInitialized: &'static str = "request-before-initialize-fails-with-ServerNotInitialized";
+pub const RULE__InitializeResult: &'static str = "initialize-returns-capabilities";
+pub const RULE__MalformedMessage: &'static str = "malformed-message-does-not-stop-server";
+pub const RULE__UnknownMethod: &'static str = "unknown-request-fails-with-MethodNotFound";
+pub const RULE__Cancellation: &'static str = "cancelled-request-still-gets-response";
+pub const RULE__ShutdownResult: &'static str = "shutdown-returns-null";
+pub const RULE__ExitAfterShutdown: &'static str = "exit-after-shutdown-exits-with-0";
+pub const RULE__ExitWithoutShutdown: &'static str = "exit-without-shutdown-exits-with-1";
+
+fn empty_object() -> Value {
+    Value::Object(JsonObject::new())
+}
+
+fn initialize_params() -> Value {
+    serde_json::from_str(r#"{ "processId": null, "rootPath": null, "capabilities": {} }"#).unwrap_or(Value::Null)
+}
+
+fn position_params() -> Value {
+    serde_json::from_str(r#"{
+        "textDocument": { "uri": "file:///conformance.txt" }, "position": { "line": 0, "character": 0 }
+    }"#).unwrap_or(Value::Null)
+}
+
+fn expect_response(response: Option<Value>, what: &str) -> Result<Value, String> {
+    response.ok_or_else(|| format!("No response to {}.", what))
+}
+
+/// Run the conformance suite against the server created by `create_server`, which is called
+/// once per connection the suite makes.
+pub fn check_conformance<SERVER, CREATE>(create_server: CREATE) -> ConformanceReport
+where
+    SERVER : LanguageServerHandling + 'static,
+    CREATE : Fn(LSServerContext) -> SERVER + Send + Sync + 'static,
+{
+    let create_server = Arc::new(create_server);
+    let start = || {
+        let create_server = create_server.clone();
+        RawConnection::start(move |context| (*create_server)(context))
+    };
+    let mut report = ConformanceReport::default();
+
+    // Full lifecycle
+    let mut connection = start();
+
+    connection.send_request(1, methods::TEXT_DOCUMENT_HOVER, position_params());
+    let response = connection.receive_response(1);
+    report.check(RULE__ServerNotInitialized, expect_response(response, "request before initialize").and_then(|response| {
+        match response_error_code(&response) {
+            Some(ERROR_CODE__ServerNotInitialized) => Ok(()),
+            _ => Err(format!("Expected error {}, got: {}", ERROR_CODE__ServerNotInitialized, response)),
+        }
+    }));
+
+    connection.send_request(2, methods::INITIALIZE, initialize_params());
+    let response = connection.receive_response(2);
+    report.check(RULE__InitializeResult, expect_response(response, "initialize").and_then(|response| {
+        match response.lookup("result.capabilities") {
+            Some(&Value::Object(_)) => Ok(()),
+            _ => Err(format!("Expected result with capabilities, got: {}", response)),
+        }
+    }));
+    connection.send_notification(methods::INITIALIZED, empty_object());
+
+    connection.send_raw("{ this is not JSON");
+    connection.send_request(3, "conformance/unknownMethod", Value::Null);
+    // Receive the response of the malformed message, if any
+    let mut response = connection.receive_any_response();
+    if response.as_ref().and_then(|response| response.find("id")) == Some(&Value::Null) {
+        response = connection.receive_response(3);
+    }
+    report.check(RULE__MalformedMessage, expect_response(response.clone(), "request after malformed message").map(|_| ()));
+    report.check(RULE__UnknownMethod, expect_response(response, "unknown request").and_then(|response| {
+        match response_error_code(&response) {
+            Some(-32601) => Ok(()),
+            _ => Err(format!("Expected error -32601, got: {}", response)),
+        }
+    }));
+
+    connection.send_request(4, methods::TEXT_DOCUMENT_HOVER, position_params());
+    connection.send_notification(methods::CANCEL_REQUEST, serde_json::from_str(r#"{ "id": 4 }"#).unwrap_or(Value::Null));
+    let response = connection.receive_response(4);
+    report.check(RULE__Cancellation, expect_response(response, "cancelled request").map(|_| ()));
+
+    connection.send_request(5, methods::SHUTDOWN, Value::Null);
+    let response = connection.receive_response(5);
+    report.check(RULE__ShutdownResult, expect_response(response, "shutdown").and_then(|response| {
+        match (response.find("result"), response.find("error")) {
+            (Some(&Value::Null), None) => Ok(()),
+            _ => Err(format!("Expected null result, got: {}", response)),
+        }
+    }));
+
+    connection.send_notification(methods::EXIT, Value::Null);
+    let exit_code = connection.close();
+    report.check(RULE__ExitAfterShutdown, match exit_code {
+        Some(0) => Ok(()),
+        _ => Err(format!("Expected exit code 0, got: {:?}", exit_code)),
+    });
+
+    // Exit without shutdown
+    let mut connection = start();
+    connection.send_request(1, methods::INITIALIZE, initialize_params());
+    let _ = connection.receive_response(1);
+    connection.send_notification(methods::EXIT, Value::Null);
+    let exit_code = connection.close();
+    report.check(RULE__ExitWithoutShutdown, match exit_code {
+        Some(1) => Ok(()),
+        _ => Err(format!("Expected exit code 1, got: {:?}", exit_code)),
+    });
+
+    report
+}
+
+
+#[cfg(test)]
+struct DefaultServer;
+
+#[cfg(test)]
+impl LanguageServerHandling for DefaultServer {
+}
+
+#[test]
+fn check_conformance__test() {
+    let report = check_conformance(|_| DefaultServer);
+    assert_eq!(report.checked.len(), 8);
+    report.assert_conformant();
+}
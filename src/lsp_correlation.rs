@@ -0,0 +1,167 @@
+// Copyright 2016 Bruno Medeiros
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>.
+// This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Correlation of the responses received with the requests sent, to observe the responses
+//! whose id matches no pending request, which the endpoint would otherwise swallow silently.
+//!
+//! A `ResponseCorrelation` wraps the `MessageWriter` and `MessageReader` of an endpoint:
+//!
+//! ```ignore
+//! let correlation = ResponseCorrelation::new().on_unmatched_response(|response| proxy.forward(response));
+//! let endpoint = LSPEndpoint::create_lsp_output(move || correlation.writer(LSPMessageWriter(server_input)));
+//! let client_handler = new(ClientRequestHandler(client));
+//! LSPEndpoint::run_endpoint_loop(&mut correlation.reader(LSPMessageReader(server_output)), endpoint, client_handler);
+//! ```
+
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::MutexGuard;
+
+use util::core::*;
+
+use jsonrpc::*;
+use serde_json;
+use serde_json::Value;
+
+pub type UnmatchedResponseCallback = Box<Fn(&Value) + Send>;
+
+#[derive(Default)]
+struct CorrelationState {
+    /// The ids of the requests sent without a response yet, as JSON.
+    pending: HashSet<String>,
+    callback: Option<UnmatchedResponseCallback>,
+}
+
+/// Tracks the requests sent, and calls a callback with each response received that matches
+/// none of them (by default, a warning is logged). Clones share the same state.
+#[derive(Clone, Default)]
+pub struct ResponseCorrelation {
+    state: Arc<Mutex<CorrelationState>>,
+}
+
+impl ResponseCorrelation {
+
+    pub fn new() -> ResponseCorrelation {
+        ResponseCorrelation::default()
+    }
+
+    /// Call given callback with each unmatched response, instead of logging a warning.
+    pub fn on_unmatched_response<CALLBACK>(self, callback: CALLBACK) -> ResponseCorrelation
+    where
+        CALLBACK : Fn(&Value) + Send + 'static,
+    {
+        self.lock_state().callback = Some(Box::new(callback));
+        self
+    }
+
+    fn lock_state(&self) -> MutexGuard<CorrelationState> {
+        match self.state.lock() {
+            Ok(state) => state,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    /// The number of requests sent without a response yet.
+    pub fn pending_count(&self) -> usize {
+        self.lock_state().pending.len()
+    }
+
+    /// Wrap given writer, to know the requests sent.
+    pub fn writer<MW : MessageWriter>(&self, msg_writer: MW) -> CorrelationMessageWriter<MW> {
+        CorrelationMessageWriter { msg_writer : msg_writer, correlation : self.clone() }
+    }
+
+    /// Wrap given reader, to match the responses received.
+    pub fn reader<MR : MessageReader>(&self, msg_reader: MR) -> CorrelationMessageReader<MR> {
+        CorrelationMessageReader { msg_reader : msg_reader, correlation : self.clone() }
+    }
+
+    fn message_written(&self, message: &str) {
+        let message = match serde_json::from_str::<Value>(message) {
+            Ok(message) => message,
+            Err(_) => return,
+        };
+        if let (Some(_), Some(id)) = (message.find("method"), message.find("id")) {
+            self.lock_state().pending.insert(id.to_string());
+        }
+    }
+
+    fn message_read(&self, message: &str) {
+        let message = match serde_json::from_str::<Value>(message) {
+            Ok(message) => message,
+            Err(_) => return,
+        };
+        if message.find("method").is_some() {
+            return;
+        }
+        let id = match message.find("id") {
+            Some(id) => id,
+            None => return,
+        };
+        let state = &mut *self.lock_state();
+        if state.pending.remove(&id.to_string()) {
+            return;
+        }
+        match state.callback {
+            Some(ref callback) => callback(&message),
+            None => warn!("Received a response with id {}, which matches no pending request.", id),
+        }
+    }
+
+}
+
+pub struct CorrelationMessageWriter<MW : MessageWriter> {
+    msg_writer: MW,
+    correlation: ResponseCorrelation,
+}
+
+impl<MW : MessageWriter> MessageWriter for CorrelationMessageWriter<MW> {
+    fn write_message(&mut self, msg: &str) -> GResult<()> {
+        // Registered before writing, in case the response arrives before this returns
+        self.correlation.message_written(msg);
+        self.msg_writer.write_message(msg)
+    }
+}
+
+pub struct CorrelationMessageReader<MR : MessageReader> {
+    msg_reader: MR,
+    correlation: ResponseCorrelation,
+}
+
+impl<MR : MessageReader> MessageReader for CorrelationMessageReader<MR> {
+    fn read_next(&mut self) -> GResult<String> {
+        let message = try!(self.msg_reader.read_next());
+        self.correlation.message_read(&message);
+        Ok(message)
+    }
+}
+
+
+#[test]
+fn response_correlation__test() {
+    use std::sync::mpsc;
+
+    let (sender, receiver) = mpsc::channel();
+    let correlation = ResponseCorrelation::new().on_unmatched_response(move |response| {
+        let _ = sender.send(response.find("id").cloned());
+    });
+
+    correlation.message_written(r#"{"jsonrpc":"2.0","id":1,"method":"textDocument/hover","params":{}}"#);
+    correlation.message_written(r#"{"jsonrpc":"2.0","method":"exit"}"#);
+    correlation.message_written(r#"{"jsonrpc":"2.0","id":7,"result":null}"#);
+    assert_eq!(correlation.pending_count(), 1);
+
+    correlation.message_read(r#"{"jsonrpc":"2.0","id":1,"result":null}"#);
+    correlation.message_read(r#"{"jsonrpc":"2.0","id":1,"result":null}"#);
+    correlation.message_read(r#"{"jsonrpc":"2.0","id":"2","error":{"code":-32601,"message":""}}"#);
+    correlation.message_read(r#"{"jsonrpc":"2.0","id":3,"method":"workspace/applyEdit","params":{}}"#);
+    assert_eq!(correlation.pending_count(), 0);
+
+    let unmatched : Vec<Option<Value>> = receiver.try_iter().collect();
+    assert_eq!(unmatched, vec![Some(Value::U64(1)), Some(Value::String("2".to_string()))]);
+}
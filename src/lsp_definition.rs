@@ -0,0 +1,163 @@
+// Copyright 2016 Bruno Medeiros
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>.
+// This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! The result of the definition-family requests (`textDocument/definition`, `textDocument/typeDefinition`
+//! and `textDocument/implementation`), either plain `Location`s or `LocationLink`s.
+
+use serde;
+use serde::de::Error as DeError;
+use serde_json;
+use serde_json::Value;
+
+use ls_types::*;
+
+use lsp_capabilities::LSClientCapabilities;
+use lsp_methods;
+use lsp_protocol::LocationLink;
+
+/// The result of a definition-family request. Only clients declaring `linkSupport`
+/// for the request support the `Links` form.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LSDefinitionResponse {
+    Locations(Vec<Location>),
+    Links(Vec<LocationLink>),
+}
+
+impl From<Vec<Location>> for LSDefinitionResponse {
+    fn from(locations: Vec<Location>) -> LSDefinitionResponse {
+        LSDefinitionResponse::Locations(locations)
+    }
+}
+
+impl From<Vec<LocationLink>> for LSDefinitionResponse {
+    fn from(links: Vec<LocationLink>) -> LSDefinitionResponse {
+        LSDefinitionResponse::Links(links)
+    }
+}
+
+/// The capability of the client capabilities for given definition-family method, if it is one.
+pub fn definition_capability(method: &str) -> Option<&'static str> {
+    match method {
+        lsp_methods::TEXT_DOCUMENT_DEFINITION => Some("definition"),
+        lsp_methods::TEXT_DOCUMENT_TYPE_DEFINITION => Some("typeDefinition"),
+        lsp_methods::TEXT_DOCUMENT_IMPLEMENTATION => Some("implementation"),
+        _ => None,
+    }
+}
+
+impl LSDefinitionResponse {
+
+    /// Convert to the form supported for the request `method` by a client with given capabilities
+    /// (`None` if unknown). Links fall back to the locations of their target selection ranges.
+    pub fn into_supported(self, method: &str, client_capabilities: Option<&LSClientCapabilities>)
+        -> LSDefinitionResponse
+    {
+        let supports_links = match (definition_capability(method), client_capabilities) {
+            (Some(capability), Some(caps)) => caps.supports_location_links(capability),
+            _ => false,
+        };
+        match self {
+            LSDefinitionResponse::Links(links) => {
+                if supports_links {
+                    LSDefinitionResponse::Links(links)
+                } else {
+                    LSDefinitionResponse::Locations(links_to_locations(links))
+                }
+            }
+            locations => locations,
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        match *self {
+            LSDefinitionResponse::Locations(ref locations) => serde_json::to_value(locations),
+            LSDefinitionResponse::Links(ref links) => Value::Array(links.iter().map(LocationLink::to_json).collect()),
+        }
+    }
+
+    /// Parse the response, which can also be a single `Location`, or null.
+    /// The form is determined by the first element: links have a `targetUri`.
+    pub fn from_json(value: Value) -> Result<LSDefinitionResponse, String> {
+        let elements = match value {
+            Value::Array(elements) => elements,
+            Value::Null => vec![],
+            Value::Object(obj) => vec![Value::Object(obj)],
+            _ => return Err("Definition response is not an array.".to_string()),
+        };
+        let is_links = match elements.first() {
+            Some(element) => element.find("targetUri").is_some(),
+            None => false,
+        };
+        if !is_links {
+            let locations = try!(serde_json::from_value(Value::Array(elements)).map_err(|err| err.to_string()));
+            return Ok(LSDefinitionResponse::Locations(locations));
+        }
+        let mut links = vec![];
+        for element in elements {
+            links.push(try!(LocationLink::from_json(element)));
+        }
+        Ok(LSDefinitionResponse::Links(links))
+    }
+
+}
+
+impl serde::Serialize for LSDefinitionResponse {
+    fn serialize<S>(&self, serializer: &mut S) -> Result<(), S::Error>
+    where
+        S : serde::Serializer,
+    {
+        self.to_json().serialize(serializer)
+    }
+}
+
+impl serde::Deserialize for LSDefinitionResponse {
+    fn deserialize<D>(deserializer: &mut D) -> Result<LSDefinitionResponse, D::Error>
+    where
+        D : serde::Deserializer,
+    {
+        let value = try!(Value::deserialize(deserializer));
+        LSDefinitionResponse::from_json(value).map_err(D::Error::custom)
+    }
+}
+
+/// The locations of the target selection ranges of given links.
+pub fn links_to_locations(links: Vec<LocationLink>) -> Vec<Location> {
+    links.into_iter().map(|link| Location { uri : link.target_uri, range : link.target_selection_range }).collect()
+}
+
+
+#[test]
+fn definition_response__test() {
+    use url::Url;
+
+    let range = |line: u64, start: u64, end: u64| Range {
+        start : Position { line : line, character : start },
+        end : Position { line : line, character : end },
+    };
+    let link = LocationLink {
+        origin_selection_range : Some(range(10, 4, 8)),
+        target_uri : Url::parse("file:///lib.rs").unwrap(),
+        target_range : range(2, 0, 20),
+        target_selection_range : range(2, 3, 7),
+    };
+    let response = LSDefinitionResponse::from(vec![link.clone()]);
+    assert_eq!(LSDefinitionResponse::from_json(response.to_json()).unwrap(), response);
+
+    let location = Location { uri : link.target_uri.clone(), range : range(2, 3, 7) };
+    assert_eq!(response.clone().into_supported(lsp_methods::TEXT_DOCUMENT_DEFINITION, None),
+        LSDefinitionResponse::Locations(vec![location.clone()]));
+
+    let json = r#"{ "textDocument": { "definition": { "linkSupport": true } } }"#;
+    let caps = LSClientCapabilities(serde_json::from_str(json).unwrap());
+    assert_eq!(response.clone().into_supported(lsp_methods::TEXT_DOCUMENT_DEFINITION, Some(&caps)), response);
+    assert_eq!(response.clone().into_supported(lsp_methods::TEXT_DOCUMENT_IMPLEMENTATION, Some(&caps)),
+        LSDefinitionResponse::Locations(vec![location.clone()]));
+
+    let single = serde_json::to_value(&location);
+    assert_eq!(LSDefinitionResponse::from_json(single).unwrap(), LSDefinitionResponse::Locations(vec![location]));
+    assert_eq!(LSDefinitionResponse::from_json(Value::Null).unwrap(), LSDefinitionResponse::Locations(vec![]));
+}
@@ -0,0 +1,706 @@
+// Copyright 2016 Bruno Medeiros
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>.
+// This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Diagnostics, including the fields newer than `ls_types::Diagnostic`,
+//! a builder for them, an accumulator producing the `publishDiagnostics` params,
+//! and a publisher debouncing the updates.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::Condvar;
+use std::sync::Mutex;
+use std::sync::MutexGuard;
+use std::thread;
+use std::time::Duration;
+use std::time::Instant;
+
+use serde;
+use serde::de::Error as DeError;
+use serde_json;
+use serde_json::Value;
+
+use jsonrpc::Endpoint;
+use jsonrpc::json_util::JsonObject;
+use ls_types::*;
+use url::Url;
+
+use lsp::LspClientRpc;
+use lsp::client_rpc_handle;
+use lsp_capabilities::LSClientCapabilities;
+
+/* ----------------- LSDiagnostic ----------------- */
+
+pub use lsp_enums::DiagnosticTag;
+
+/// A related message and source code location for a diagnostic, such as the other
+/// declaration of a symbol declared twice.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiagnosticRelatedInformation {
+    pub location: Location,
+    pub message: String,
+}
+
+/// A diagnostic, such as a compiler error or warning.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LSDiagnostic {
+    pub diagnostic: Diagnostic,
+    pub related_information: Vec<DiagnosticRelatedInformation>,
+    pub tags: Vec<DiagnosticTag>,
+    /// A page describing the error code, such as the entry of the compiler error index.
+    pub code_description: Option<Url>,
+    /// Data preserved between this diagnostic, and the `textDocument/codeAction` request
+    /// that includes it in its context, so that a fix can be computed without analyzing again.
+    pub data: Option<Value>,
+}
+
+impl From<Diagnostic> for LSDiagnostic {
+    fn from(diagnostic: Diagnostic) -> LSDiagnostic {
+        LSDiagnostic {
+            diagnostic : diagnostic, related_information : vec![], tags : vec![], code_description : None,
+            data : None,
+        }
+    }
+}
+
+impl LSDiagnostic {
+
+    /// Drop the fields a client with given capabilities (`None` if unknown) doesn't support:
+    /// the related information, the tags not in its value set, the code description and the data.
+    pub fn into_supported(mut self, client_capabilities: Option<&LSClientCapabilities>) -> LSDiagnostic {
+        let caps = match client_capabilities {
+            Some(caps) => caps,
+            None => return LSDiagnostic::from(self.diagnostic),
+        };
+        if !caps.supports_diagnostic_related_information() {
+            self.related_information.clear();
+        }
+        self.tags.retain(|tag| caps.supports_diagnostic_tag(tag.to_i32()));
+        if !caps.supports_diagnostic_code_description() {
+            self.code_description = None;
+        }
+        if !caps.supports_diagnostic_data() {
+            self.data = None;
+        }
+        self
+    }
+
+    pub fn to_json(&self) -> Value {
+        let mut obj = match serde_json::to_value(&self.diagnostic) {
+            Value::Object(obj) => obj,
+            _ => JsonObject::new(),
+        };
+        if !self.related_information.is_empty() {
+            let related_information = self.related_information.iter().map(|info| {
+                let mut info_obj = JsonObject::new();
+                info_obj.insert("location".to_string(), serde_json::to_value(&info.location));
+                info_obj.insert("message".to_string(), Value::String(info.message.clone()));
+                Value::Object(info_obj)
+            }).collect();
+            obj.insert("relatedInformation".to_string(), Value::Array(related_information));
+        }
+        if !self.tags.is_empty() {
+            let tags = self.tags.iter().map(DiagnosticTag::to_json).collect();
+            obj.insert("tags".to_string(), Value::Array(tags));
+        }
+        if let Some(ref href) = self.code_description {
+            let mut description = JsonObject::new();
+            description.insert("href".to_string(), Value::String(href.as_str().to_string()));
+            obj.insert("codeDescription".to_string(), Value::Object(description));
+        }
+        if let Some(ref data) = self.data {
+            obj.insert("data".to_string(), data.clone());
+        }
+        Value::Object(obj)
+    }
+
+    pub fn from_json(value: Value) -> Result<LSDiagnostic, String> {
+        let mut obj = match value {
+            Value::Object(obj) => obj,
+            _ => return Err("Diagnostic is not an object.".to_string()),
+        };
+
+        let related_information = match obj.remove("relatedInformation") {
+            Some(Value::Array(infos)) => {
+                let mut related_information = vec![];
+                for info in infos {
+                    let mut info = match info {
+                        Value::Object(info) => info,
+                        _ => return Err("DiagnosticRelatedInformation is not an object.".to_string()),
+                    };
+                    let location = match info.remove("location") {
+                        Some(location) => try!(serde_json::from_value(location).map_err(|err| err.to_string())),
+                        None => return Err("DiagnosticRelatedInformation: missing `location`.".to_string()),
+                    };
+                    let message = match info.remove("message") {
+                        Some(Value::String(message)) => message,
+                        _ => return Err("DiagnosticRelatedInformation: missing `message`.".to_string()),
+                    };
+                    related_information.push(DiagnosticRelatedInformation { location : location, message : message });
+                }
+                related_information
+            }
+            Some(Value::Null) | None => vec![],
+            Some(_) => return Err("Diagnostic: `relatedInformation` is not an array.".to_string()),
+        };
+        let tags = match obj.remove("tags") {
+            // Tags that are not integers are ignored, unknown ones are kept as `Unknown`
+            Some(Value::Array(tags)) => {
+                tags.into_iter().filter_map(|tag| DiagnosticTag::from_json(tag).ok()).collect()
+            }
+            Some(Value::Null) | None => vec![],
+            Some(_) => return Err("Diagnostic: `tags` is not an array.".to_string()),
+        };
+        let code_description = match obj.remove("codeDescription") {
+            Some(Value::Object(mut description)) => {
+                match description.remove("href") {
+                    Some(Value::String(href)) => Some(try!(Url::parse(&href).map_err(|err| err.to_string()))),
+                    _ => return Err("CodeDescription: missing `href`.".to_string()),
+                }
+            }
+            Some(Value::Null) | None => None,
+            Some(_) => return Err("Diagnostic: `codeDescription` is not an object.".to_string()),
+        };
+        let data = match obj.remove("data") {
+            Some(Value::Null) | None => None,
+            Some(data) => Some(data),
+        };
+        let diagnostic = try!(serde_json::from_value(Value::Object(obj)).map_err(|err| err.to_string()));
+        Ok(LSDiagnostic {
+            diagnostic : diagnostic, related_information : related_information, tags : tags,
+            code_description : code_description, data : data,
+        })
+    }
+
+}
+
+impl serde::Serialize for LSDiagnostic {
+    fn serialize<S>(&self, serializer: &mut S) -> Result<(), S::Error>
+    where
+        S : serde::Serializer,
+    {
+        self.to_json().serialize(serializer)
+    }
+}
+
+impl serde::Deserialize for LSDiagnostic {
+    fn deserialize<D>(deserializer: &mut D) -> Result<LSDiagnostic, D::Error>
+    where
+        D : serde::Deserializer,
+    {
+        let value = try!(Value::deserialize(deserializer));
+        LSDiagnostic::from_json(value).map_err(D::Error::custom)
+    }
+}
+
+/// The params of `textDocument/publishDiagnostics`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LSPublishDiagnosticsParams {
+    pub uri: Url,
+    pub diagnostics: Vec<LSDiagnostic>,
+}
+
+impl From<PublishDiagnosticsParams> for LSPublishDiagnosticsParams {
+    fn from(params: PublishDiagnosticsParams) -> LSPublishDiagnosticsParams {
+        LSPublishDiagnosticsParams {
+            uri : params.uri,
+            diagnostics : params.diagnostics.into_iter().map(LSDiagnostic::from).collect(),
+        }
+    }
+}
+
+impl LSPublishDiagnosticsParams {
+
+    /// Drop the diagnostic fields a client with given capabilities (`None` if unknown) doesn't support.
+    pub fn into_supported(self, client_capabilities: Option<&LSClientCapabilities>) -> LSPublishDiagnosticsParams {
+        let diagnostics = self.diagnostics.into_iter()
+            .map(|diagnostic| diagnostic.into_supported(client_capabilities))
+            .collect();
+        LSPublishDiagnosticsParams { uri : self.uri, diagnostics : diagnostics }
+    }
+
+    pub fn to_json(&self) -> Value {
+        let mut obj = JsonObject::new();
+        obj.insert("uri".to_string(), Value::String(self.uri.as_str().to_string()));
+        let diagnostics = self.diagnostics.iter().map(LSDiagnostic::to_json).collect();
+        obj.insert("diagnostics".to_string(), Value::Array(diagnostics));
+        Value::Object(obj)
+    }
+
+    pub fn from_json(value: Value) -> Result<LSPublishDiagnosticsParams, String> {
+        let mut obj = match value {
+            Value::Object(obj) => obj,
+            _ => return Err("PublishDiagnosticsParams is not an object.".to_string()),
+        };
+
+        let uri = match obj.remove("uri") {
+            Some(Value::String(uri)) => try!(Url::parse(&uri).map_err(|err| err.to_string())),
+            _ => return Err("PublishDiagnosticsParams: missing `uri`.".to_string()),
+        };
+        let diagnostics = match obj.remove("diagnostics") {
+            Some(Value::Array(diagnostics)) => {
+                let mut result = vec![];
+                for diagnostic in diagnostics {
+                    result.push(try!(LSDiagnostic::from_json(diagnostic)));
+                }
+                result
+            }
+            _ => return Err("PublishDiagnosticsParams: missing `diagnostics`.".to_string()),
+        };
+        Ok(LSPublishDiagnosticsParams { uri : uri, diagnostics : diagnostics })
+    }
+
+}
+
+impl serde::Serialize for LSPublishDiagnosticsParams {
+    fn serialize<S>(&self, serializer: &mut S) -> Result<(), S::Error>
+    where
+        S : serde::Serializer,
+    {
+        self.to_json().serialize(serializer)
+    }
+}
+
+impl serde::Deserialize for LSPublishDiagnosticsParams {
+    fn deserialize<D>(deserializer: &mut D) -> Result<LSPublishDiagnosticsParams, D::Error>
+    where
+        D : serde::Deserializer,
+    {
+        let value = try!(Value::deserialize(deserializer));
+        LSPublishDiagnosticsParams::from_json(value).map_err(D::Error::custom)
+    }
+}
+
+/* ----------------- Builder ----------------- */
+
+/// Builder for `LSDiagnostic`.
+#[derive(Debug, Clone)]
+pub struct DiagnosticBuilder {
+    diagnostic: LSDiagnostic,
+}
+
+impl DiagnosticBuilder {
+
+    pub fn new(range: Range, message: &str) -> DiagnosticBuilder {
+        let diagnostic = Diagnostic {
+            range : range, severity : None, code : None, source : None, message : message.to_string(),
+        };
+        DiagnosticBuilder { diagnostic : LSDiagnostic::from(diagnostic) }
+    }
+
+    pub fn error(range: Range, message: &str) -> DiagnosticBuilder {
+        Self::new(range, message).severity(DiagnosticSeverity::Error)
+    }
+
+    pub fn warning(range: Range, message: &str) -> DiagnosticBuilder {
+        Self::new(range, message).severity(DiagnosticSeverity::Warning)
+    }
+
+    pub fn information(range: Range, message: &str) -> DiagnosticBuilder {
+        Self::new(range, message).severity(DiagnosticSeverity::Information)
+    }
+
+    pub fn hint(range: Range, message: &str) -> DiagnosticBuilder {
+        Self::new(range, message).severity(DiagnosticSeverity::Hint)
+    }
+
+    pub fn severity(mut self, severity: DiagnosticSeverity) -> Self {
+        self.diagnostic.diagnostic.severity = Some(severity);
+        self
+    }
+
+    pub fn code(mut self, code: &str) -> Self {
+        self.diagnostic.diagnostic.code = Some(NumberOrString::String(code.to_string()));
+        self
+    }
+
+    pub fn code_number(mut self, code: u64) -> Self {
+        self.diagnostic.diagnostic.code = Some(NumberOrString::Number(code));
+        self
+    }
+
+    /// The tool producing the diagnostic, such as `rustc`.
+    pub fn source(mut self, source: &str) -> Self {
+        self.diagnostic.diagnostic.source = Some(source.to_string());
+        self
+    }
+
+    pub fn related(mut self, location: Location, message: &str) -> Self {
+        let info = DiagnosticRelatedInformation { location : location, message : message.to_string() };
+        self.diagnostic.related_information.push(info);
+        self
+    }
+
+    pub fn tag(mut self, tag: DiagnosticTag) -> Self {
+        if !self.diagnostic.tags.contains(&tag) {
+            self.diagnostic.tags.push(tag);
+        }
+        self
+    }
+
+    /// A page describing the error code.
+    pub fn code_description(mut self, href: Url) -> Self {
+        self.diagnostic.code_description = Some(href);
+        self
+    }
+
+    /// Data preserved between this diagnostic and the code action requests that include it.
+    pub fn data(mut self, data: Value) -> Self {
+        self.diagnostic.data = Some(data);
+        self
+    }
+
+    pub fn build(self) -> LSDiagnostic {
+        self.diagnostic
+    }
+
+}
+
+/* ----------------- Diagnostics ----------------- */
+
+/// Accumulates diagnostics per document, to be published with `textDocument/publishDiagnostics`.
+///
+/// A document that was cleared is still published, with no diagnostics,
+/// so that the client removes the ones it was showing.
+#[derive(Debug, Clone, Default)]
+pub struct Diagnostics {
+    diagnostics: HashMap<Url, Vec<LSDiagnostic>>,
+}
+
+impl Diagnostics {
+
+    pub fn new() -> Diagnostics {
+        Diagnostics::default()
+    }
+
+    pub fn add<DIAGNOSTIC>(&mut self, uri: &Url, diagnostic: DIAGNOSTIC)
+    where
+        DIAGNOSTIC : Into<LSDiagnostic>,
+    {
+        self.diagnostics.entry(uri.clone()).or_insert_with(Vec::new).push(diagnostic.into());
+    }
+
+    /// Remove the diagnostics of given document.
+    pub fn clear(&mut self, uri: &Url) {
+        self.diagnostics.insert(uri.clone(), vec![]);
+    }
+
+    pub fn get(&self, uri: &Url) -> &[LSDiagnostic] {
+        match self.diagnostics.get(uri) {
+            Some(diagnostics) => diagnostics,
+            None => &[],
+        }
+    }
+
+    /// Whether there are no documents to publish.
+    pub fn is_empty(&self) -> bool {
+        self.diagnostics.is_empty()
+    }
+
+    /// Take the accumulated diagnostics as publish params, one per document, sorted by URI.
+    pub fn take_params(&mut self) -> Vec<LSPublishDiagnosticsParams> {
+        let mut params : Vec<_> = self.diagnostics.drain().map(|(uri, diagnostics)| {
+            LSPublishDiagnosticsParams { uri : uri, diagnostics : diagnostics }
+        }).collect();
+        params.sort_by(|a, b| a.uri.as_str().cmp(b.uri.as_str()));
+        params
+    }
+
+}
+
+
+/* ----------------- Publisher ----------------- */
+
+#[derive(Default)]
+struct PublisherState {
+    /// The diagnostics to publish, and when they are due.
+    pending: HashMap<Url, (Vec<LSDiagnostic>, Instant)>,
+    /// The last diagnostics published for each document, if not empty.
+    /// They must be cleared when the document is closed.
+    published: HashMap<Url, Vec<LSDiagnostic>>,
+    stopped: bool,
+}
+
+/// Publishes the diagnostics of documents to the client, from a thread.
+///
+/// The diagnostics of a document are published after a delay, and each update in that delay
+/// replaces the previous one and restarts it, so that analysis results produced while
+/// the user types don't flood the client. An update that is the same as the diagnostics
+/// the client has is not published. Dropping the publisher publishes the pending updates.
+pub struct DiagnosticsPublisher {
+    delay: Duration,
+    state: Arc<(Mutex<PublisherState>, Condvar)>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl DiagnosticsPublisher {
+
+    /// Create a publisher sending `publishDiagnostics` with given endpoint, after given delay.
+    pub fn new(endpoint: Endpoint, delay: Duration) -> DiagnosticsPublisher {
+        let mut endpoint = endpoint;
+        Self::with_sender(delay, move |params| {
+            if let Err(error) = client_rpc_handle(&mut endpoint).publish_diagnostics(params) {
+                warn!("Failed to publish diagnostics: {}", error);
+            }
+        })
+    }
+
+    /// Create a publisher calling `send` to publish the diagnostics, after given delay.
+    pub fn with_sender<SEND>(delay: Duration, send: SEND) -> DiagnosticsPublisher
+    where
+        SEND : FnMut(LSPublishDiagnosticsParams) + Send + 'static,
+    {
+        let state = Arc::new((Mutex::new(PublisherState::default()), Condvar::new()));
+        let thread_state = state.clone();
+        let thread = thread::spawn(move || run_publisher(&thread_state, send));
+        DiagnosticsPublisher { delay : delay, state : state, thread : Some(thread) }
+    }
+
+    fn lock_state(&self) -> MutexGuard<PublisherState> {
+        match self.state.0.lock() {
+            Ok(state) => state,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    /// Set the diagnostics of given document, replacing the previous ones.
+    pub fn publish(&self, uri: Url, diagnostics: Vec<LSDiagnostic>) {
+        self.lock_state().pending.insert(uri, (diagnostics, Instant::now() + self.delay));
+        self.state.1.notify_all();
+    }
+
+    /// Publish the updates of given accumulated diagnostics.
+    pub fn publish_all(&self, diagnostics: &mut Diagnostics) {
+        for params in diagnostics.take_params() {
+            self.publish(params.uri, params.diagnostics);
+        }
+    }
+
+    /// Remove the diagnostics of given document, such as when it is closed or deleted.
+    /// Cancels its pending update, and clears the diagnostics the client is showing right away.
+    pub fn clear(&self, uri: &Url) {
+        let mut state = self.lock_state();
+        let published = state.published.contains_key(uri);
+        if state.pending.remove(uri).is_some() || published {
+            state.pending.insert(uri.clone(), (vec![], Instant::now()));
+            self.state.1.notify_all();
+        }
+    }
+
+    /// Publish the pending updates now, without waiting for their delay.
+    pub fn flush(&self) {
+        let mut state = self.lock_state();
+        let now = Instant::now();
+        for update in state.pending.values_mut() {
+            update.1 = now;
+        }
+        self.state.1.notify_all();
+    }
+
+}
+
+impl Drop for DiagnosticsPublisher {
+    fn drop(&mut self) {
+        self.lock_state().stopped = true;
+        self.state.1.notify_all();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn run_publisher<SEND>(state: &(Mutex<PublisherState>, Condvar), mut send: SEND)
+where
+    SEND : FnMut(LSPublishDiagnosticsParams),
+{
+    let &(ref mutex, ref condvar) = state;
+    let mut guard = match mutex.lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    loop {
+        let now = Instant::now();
+        let stopped = guard.stopped;
+        let due : Vec<Url> = guard.pending.iter()
+            .filter(|&(_, &(_, due))| stopped || due <= now)
+            .map(|(uri, _)| uri.clone())
+            .collect();
+
+        if due.is_empty() {
+            if stopped {
+                return;
+            }
+            let next_due = guard.pending.values().map(|&(_, due)| due).min();
+            guard = match next_due {
+                Some(next_due) => {
+                    match condvar.wait_timeout(guard, next_due - now) {
+                        Ok((guard, _)) => guard,
+                        Err(poisoned) => poisoned.into_inner().0,
+                    }
+                }
+                None => {
+                    match condvar.wait(guard) {
+                        Ok(guard) => guard,
+                        Err(poisoned) => poisoned.into_inner(),
+                    }
+                }
+            };
+            continue;
+        }
+
+        let mut updates = vec![];
+        for uri in due {
+            if let Some((diagnostics, _)) = guard.pending.remove(&uri) {
+                let unchanged = match guard.published.get(&uri) {
+                    Some(published) => *published == diagnostics,
+                    None => diagnostics.is_empty(),
+                };
+                if unchanged {
+                    continue;
+                }
+                if diagnostics.is_empty() {
+                    guard.published.remove(&uri);
+                } else {
+                    guard.published.insert(uri.clone(), diagnostics.clone());
+                }
+                updates.push(LSPublishDiagnosticsParams { uri : uri, diagnostics : diagnostics });
+            }
+        }
+        updates.sort_by(|a, b| a.uri.as_str().cmp(b.uri.as_str()));
+
+        drop(guard);
+        for params in updates {
+            send(params);
+        }
+        guard = match mutex.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+    }
+}
+
+
+
+#[cfg(test)]
+fn test_range(line: u64, start: u64, end: u64) -> Range {
+    Range {
+        start : Position { line : line, character : start },
+        end : Position { line : line, character : end },
+    }
+}
+
+#[test]
+fn diagnostic_builder__test() {
+    let uri = Url::parse("file:///lib.rs").unwrap();
+    let location = Location { uri : uri.clone(), range : test_range(0, 0, 3) };
+    let diagnostic = DiagnosticBuilder::warning(test_range(2, 4, 5), "unused variable: `x`")
+        .code("unused_variables")
+        .source("rustc")
+        .related(location, "first declared here")
+        .tag(DiagnosticTag::Unnecessary)
+        .tag(DiagnosticTag::Unnecessary)
+        .build();
+
+    assert_eq!(diagnostic.diagnostic.severity, Some(DiagnosticSeverity::Warning));
+    assert_eq!(diagnostic.tags, vec![DiagnosticTag::Unnecessary]);
+
+    let json = diagnostic.to_json();
+    assert_eq!(json.lookup("source"), Some(&Value::String("rustc".to_string())));
+    assert_eq!(json.lookup("tags.0"), Some(&Value::U64(1)));
+    assert_eq!(json.lookup("relatedInformation.0.message"),
+        Some(&Value::String("first declared here".to_string())));
+
+    let diagnostic2 : LSDiagnostic = serde_json::from_str(&serde_json::to_string(&diagnostic).unwrap()).unwrap();
+    assert_eq!(diagnostic2, diagnostic);
+
+    let href = Url::parse("https://doc.rust-lang.org/error-index.html#E0425").unwrap();
+    let diagnostic = DiagnosticBuilder::error(test_range(1, 0, 3), "cannot find value `foo`")
+        .code("E0425")
+        .code_description(href.clone())
+        .data(Value::String("fix-id".to_string()))
+        .tag(DiagnosticTag::Deprecated)
+        .build();
+    let json = diagnostic.to_json();
+    assert_eq!(json.lookup("codeDescription.href"), Some(&Value::String(href.as_str().to_string())));
+    assert_eq!(LSDiagnostic::from_json(json).unwrap(), diagnostic);
+
+    let caps_json = r#"{ "textDocument": { "publishDiagnostics": {
+        "tagSupport": { "valueSet": [1] }, "codeDescriptionSupport": true, "dataSupport": true
+    } } }"#;
+    let caps = LSClientCapabilities(serde_json::from_str(caps_json).unwrap());
+    let supported = diagnostic.clone().into_supported(Some(&caps));
+    assert_eq!(supported.tags, vec![]);
+    assert_eq!(supported.code_description, Some(href));
+    assert_eq!(supported.data, Some(Value::String("fix-id".to_string())));
+    assert_eq!(diagnostic.into_supported(None).data, None);
+}
+
+#[test]
+fn diagnostics__test() {
+    let uri_a = Url::parse("file:///a.rs").unwrap();
+    let uri_b = Url::parse("file:///b.rs").unwrap();
+
+    let mut diagnostics = Diagnostics::new();
+    diagnostics.add(&uri_b, DiagnosticBuilder::error(test_range(0, 0, 1), "error").build());
+    diagnostics.add(&uri_b, DiagnosticBuilder::hint(test_range(1, 0, 1), "hint").build());
+    diagnostics.clear(&uri_a);
+    assert_eq!(diagnostics.get(&uri_b).len(), 2);
+
+    let params = diagnostics.take_params();
+    assert!(diagnostics.is_empty());
+    assert_eq!(params.len(), 2);
+    assert_eq!(params[0].uri, uri_a);
+    assert!(params[0].diagnostics.is_empty());
+    assert_eq!(params[1].diagnostics[1].diagnostic.message, "hint");
+
+    let params2 = LSPublishDiagnosticsParams::from_json(params[1].to_json()).unwrap();
+    assert_eq!(params2, params[1]);
+}
+
+#[test]
+fn diagnostics_publisher__test() {
+    use std::sync::mpsc;
+
+    let uri_a = Url::parse("file:///a.rs").unwrap();
+    let uri_b = Url::parse("file:///b.rs").unwrap();
+    let (sender, receiver) = mpsc::channel();
+    let publisher = DiagnosticsPublisher::with_sender(Duration::from_millis(100), move |params| {
+        sender.send(params).unwrap();
+    });
+
+    // Rapid updates are debounced to the last one
+    for ix in 0..5 {
+        let message = format!("error {}", ix);
+        publisher.publish(uri_a.clone(), vec![DiagnosticBuilder::error(test_range(0, 0, 1), &message).build()]);
+    }
+    let params = receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!(params.uri, uri_a);
+    assert_eq!(params.diagnostics.len(), 1);
+    assert_eq!(params.diagnostics[0].diagnostic.message, "error 4");
+
+    // Unchanged diagnostics are not published again
+    publisher.publish(uri_a.clone(), params.diagnostics.clone());
+    publisher.flush();
+    assert!(receiver.recv_timeout(Duration::from_millis(200)).is_err());
+
+    // Clearing a published document publishes no diagnostics, a never published one nothing
+    publisher.clear(&uri_b);
+    publisher.clear(&uri_a);
+    let params = receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!(params.uri, uri_a);
+    assert!(params.diagnostics.is_empty());
+    assert!(receiver.recv_timeout(Duration::from_millis(200)).is_err());
+
+    // The empty diagnostics of a cleared document are sent once
+    publisher.publish(uri_a.clone(), vec![]);
+    publisher.flush();
+    assert!(receiver.recv_timeout(Duration::from_millis(200)).is_err());
+
+    // Pending updates are published on drop
+    publisher.publish(uri_b.clone(), vec![DiagnosticBuilder::error(test_range(0, 0, 1), "error").build()]);
+    drop(publisher);
+    assert_eq!(receiver.try_recv().unwrap().uri, uri_b);
+}
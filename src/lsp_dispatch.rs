@@ -0,0 +1,413 @@
+// Copyright 2016 Bruno Medeiros
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>.
+// This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Alternative ways to dispatch the messages to a server, other than in the thread that reads them,
+//! which is what `LSPEndpoint::run_server` does.
+//!
+//! In the actor mode, the server is created in a thread of its own, and owns its state: the messages
+//! are sent to it through a mailbox, and handled one at a time, in order. The server doesn't need to be
+//! `Send`, so it can keep `Rc`s and `RefCell`s, and a slow handler doesn't block the reading of
+//! `$/cancelRequest`.
+//!
+//! In the shared mode, the server is a handle to state that is shared between threads, such as with
+//! `Arc`s of `Sync` types, and each request is dispatched to a clone of it in a pool of workers,
+//! so that requests are handled in parallel. The notifications, which change the state of the server,
+//! are still handled in order, in the thread that reads them, as are the lifecycle requests:
+//! `shutdown` once the requests in the pool are done.
+//!
+//! In the document mode, the server is shared as in the shared mode, but the messages of each document,
+//! notifications included, are handled in the order they arrive, in the worker of that document.
+//! Messages of different documents are handled in parallel. Messages that are not of a document are
+//! handled in the thread that reads them, once the messages before them are done.
+//!
+//! In all these modes, the requests are tracked for cancellation as they are read, not when
+//! they are handled, so that `$/cancelRequest` or a change of their document cancels them while they wait.
+//!
+//! ```ignore
+//! let exit_code = lsp_dispatch::run_actor_server(&mut input, context, |context| MyServer::new(context));
+//! ```
+
+use std::sync::mpsc;
+use std::thread;
+
+use jsonrpc::*;
+use jsonrpc::jsonrpc_request::RequestParams;
+
+use lsp::*;
+use lsp_scheduler::DocumentScheduler;
+use lsp_scheduler::PriorityPool;
+use lsp_scheduler::document_uri_of_params;
+use lsp_scheduler::lane_of_method;
+
+/* ----------------- Actor mode ----------------- */
+
+/// Run the message read loop on a server created by `create_server`, which is called in the thread
+/// of the server, with its context. Returns the process exit code, as `LSPEndpoint::run_server` does.
+pub fn run_actor_server<SERVER, CREATE, MR>(msg_reader: &mut MR, context: LSServerContext, create_server: CREATE) -> i32
+where
+    SERVER : LanguageServerHandling + 'static,
+    CREATE : FnOnce(LSServerContext) -> SERVER + Send + 'static,
+    MR : MessageReader,
+{
+    let (mailbox, messages) = mpsc::channel::<Mail>();
+    let server_context = context.clone();
+    let server_thread = thread::spawn(move || {
+        let server = create_server(server_context.clone());
+        let mut server_handler = ServerRequestHandler { context : server_context, ls : server };
+        for mail in messages {
+            server_handler.dispatch_begun_request(mail.request, &mail.method_name, mail.params, mail.completable);
+        }
+    });
+    let handler = ActorRequestHandler {
+        mailbox : Some(mailbox), server_thread : Some(server_thread), context : context.clone(),
+    };
+    LSPEndpoint::run_server_with_handler(msg_reader, context, Box::new(handler))
+}
+
+/// A message sent to the server thread, whose handling began in the thread that reads the messages.
+struct Mail {
+    request: RequestInfo,
+    method_name: String,
+    params: RequestParams,
+    completable: ResponseCompletable,
+}
+
+struct ActorRequestHandler {
+    mailbox: Option<mpsc::Sender<Mail>>,
+    server_thread: Option<thread::JoinHandle<()>>,
+    context: LSServerContext,
+}
+
+impl ActorRequestHandler {
+
+    /// Close the mailbox, and wait for the server to handle the messages in it.
+    fn join(&mut self) {
+        drop(self.mailbox.take());
+        if let Some(server_thread) = self.server_thread.take() {
+            if server_thread.join().is_err() {
+                error!("The server thread panicked.");
+            }
+        }
+    }
+
+}
+
+impl LSRequestHandler for ActorRequestHandler {
+
+    fn handle_ls_request(&mut self, request: RequestInfo, method_name: &str, params: RequestParams,
+        completable: ResponseCompletable)
+    {
+        if method_name == methods::CANCEL_REQUEST {
+            // Not in the mailbox, where it would wait for the request it cancels to be handled
+            drop(completable);
+            return self.context.request_cancellation().cancel_request_of_params(&params);
+        }
+        // In this thread, so that the request can be cancelled while it waits in the mailbox
+        let request = self.context.begin_request(request, method_name, &params);
+        let mail = Mail {
+            request : request, method_name : method_name.to_string(), params : params, completable : completable,
+        };
+        let sent = match self.mailbox {
+            Some(ref mailbox) => mailbox.send(mail).is_ok(),
+            None => false,
+        };
+        if !sent {
+            warn!("Dropping `{}`, the server thread has terminated.", method_name);
+        }
+        if method_name == methods::EXIT {
+            // The server ends the read loop once it handled `exit`
+            self.join();
+        }
+    }
+
+}
+
+impl Drop for ActorRequestHandler {
+    fn drop(&mut self) {
+        self.join();
+    }
+}
+
+/* ----------------- Shared mode ----------------- */
+
+/// Run the message read loop on given server, dispatching the requests to clones of it,
+/// in a pool of given number of workers. Returns the process exit code, as `LSPEndpoint::run_server` does.
+pub fn run_shared_server<SERVER, MR>(msg_reader: &mut MR, context: LSServerContext, server: SERVER,
+    worker_count: usize) -> i32
+where
+    SERVER : LanguageServerHandling + Clone + Send + 'static,
+    MR : MessageReader,
+{
+    let handler = SharedRequestHandler {
+        server_handler : ServerRequestHandler { context : context.clone(), ls : server },
+        pool : PriorityPool::new(worker_count),
+    };
+    LSPEndpoint::run_server_with_handler(msg_reader, context, Box::new(handler))
+}
+
+struct SharedRequestHandler<SERVER : LanguageServerHandling> {
+    server_handler: ServerRequestHandler<SERVER>,
+    pool: PriorityPool,
+}
+
+impl<SERVER> LSRequestHandler for SharedRequestHandler<SERVER>
+where
+    SERVER : LanguageServerHandling + Clone + Send + 'static,
+{
+
+    fn handle_ls_request(&mut self, request: RequestInfo, method_name: &str, params: RequestParams,
+        completable: ResponseCompletable)
+    {
+        if method_name == methods::SHUTDOWN {
+            // The requests being handled must be answered before `shutdown` is
+            self.pool.wait_idle();
+        }
+        let in_order = request.is_notification() || method_name == methods::SHUTDOWN
+            || self.server_handler.context.state() != ServerState::Running;
+        if in_order {
+            return self.server_handler.handle_ls_request(request, method_name, params, completable);
+        }
+        // In this thread, so that the request can be cancelled while it waits in the pool
+        let request = self.server_handler.context.begin_request(request, method_name, &params);
+        let mut server_handler = ServerRequestHandler {
+            context : self.server_handler.context.clone(), ls : self.server_handler.ls.clone(),
+        };
+        let method_name = method_name.to_string();
+        self.pool.submit(lane_of_method(&method_name), move || {
+            server_handler.dispatch_begun_request(request, &method_name, params, completable)
+        });
+    }
+
+}
+
+/* ----------------- Document mode ----------------- */
+
+/// Run the message read loop on given server, dispatching the messages of each document to a clone of it,
+/// in the worker of that document, among given number of workers.
+/// Returns the process exit code, as `LSPEndpoint::run_server` does.
+pub fn run_document_server<SERVER, MR>(msg_reader: &mut MR, context: LSServerContext, server: SERVER,
+    worker_count: usize) -> i32
+where
+    SERVER : LanguageServerHandling + Clone + Send + 'static,
+    MR : MessageReader,
+{
+    let handler = DocumentRequestHandler {
+        server_handler : ServerRequestHandler { context : context.clone(), ls : server },
+        scheduler : DocumentScheduler::new(worker_count),
+    };
+    LSPEndpoint::run_server_with_handler(msg_reader, context, Box::new(handler))
+}
+
+struct DocumentRequestHandler<SERVER : LanguageServerHandling> {
+    server_handler: ServerRequestHandler<SERVER>,
+    scheduler: DocumentScheduler,
+}
+
+impl<SERVER> LSRequestHandler for DocumentRequestHandler<SERVER>
+where
+    SERVER : LanguageServerHandling + Clone + Send + 'static,
+{
+
+    fn handle_ls_request(&mut self, request: RequestInfo, method_name: &str, params: RequestParams,
+        completable: ResponseCompletable)
+    {
+        if method_name == methods::CANCEL_REQUEST {
+            // Right away, not after the request it cancels
+            return self.server_handler.handle_ls_request(request, method_name, params, completable);
+        }
+        // In this thread, so that a change of the document cancels the requests scheduled before it,
+        // and the requests can be cancelled while they are scheduled
+        let request = self.server_handler.context.begin_request(request, method_name, &params);
+        let document_uri = match document_uri_of_params(&params) {
+            Some(document_uri) if self.server_handler.context.state() == ServerState::Running => {
+                Some(document_uri.to_string())
+            }
+            _ => None,
+        };
+        let document_uri = match document_uri {
+            Some(document_uri) => document_uri,
+            None => {
+                self.scheduler.wait_idle();
+                return self.server_handler.dispatch_begun_request(request, method_name, params, completable);
+            }
+        };
+        let mut server_handler = ServerRequestHandler {
+            context : self.server_handler.context.clone(), ls : self.server_handler.ls.clone(),
+        };
+        let method_name = method_name.to_string();
+        self.scheduler.schedule(&document_uri, move || {
+            server_handler.dispatch_begun_request(request, &method_name, params, completable)
+        });
+    }
+
+}
+
+
+#[cfg(test)]
+use ls_types::*;
+#[cfg(test)]
+use lsp_testing::ServerTestHarness;
+
+/// Counts the hovers in a `Rc`, which is not `Send`.
+#[cfg(test)]
+struct ActorServer(::std::rc::Rc<::std::cell::Cell<u64>>);
+
+#[cfg(test)]
+impl LanguageServerHandling for ActorServer {
+    fn hover(&mut self, _: TextDocumentPositionParams, completable: LSMethodCompletable<methods::HoverRequest>) {
+        self.0.set(self.0.get() + 1);
+        let hover = Hover { contents : vec![MarkedString::String(self.0.get().to_string())], range : None };
+        completable.complete(Ok(LSHover::from(hover)))
+    }
+}
+
+/// Counts the hovers in a shared counter.
+#[cfg(test)]
+#[derive(Clone)]
+struct SharedServer(::std::sync::Arc<::std::sync::atomic::AtomicUsize>);
+
+#[cfg(test)]
+impl LanguageServerHandling for SharedServer {
+    fn hover(&mut self, _: TextDocumentPositionParams, completable: LSMethodCompletable<methods::HoverRequest>) {
+        let count = self.0.fetch_add(1, ::std::sync::atomic::Ordering::SeqCst) + 1;
+        let hover = Hover { contents : vec![MarkedString::String(count.to_string())], range : None };
+        completable.complete(Ok(LSHover::from(hover)))
+    }
+}
+
+/// Answers each hover with whether it was cancelled, once it is, or after a timeout.
+#[cfg(test)]
+#[derive(Clone)]
+struct CancelledServer {
+    request: RequestInfo,
+}
+
+#[cfg(test)]
+impl LanguageServerHandling for CancelledServer {
+    fn before_dispatch(&mut self, request: &RequestInfo) {
+        self.request = request.clone();
+    }
+    fn hover(&mut self, _: TextDocumentPositionParams, completable: LSMethodCompletable<methods::HoverRequest>) {
+        use std::time::Duration;
+        use std::time::Instant;
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !self.request.cancellation.is_cancelled() && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(1));
+        }
+        let cancelled = self.request.cancellation.is_cancelled().to_string();
+        let hover = Hover { contents : vec![MarkedString::String(cancelled)], range : None };
+        completable.complete(Ok(LSHover::from(hover)))
+    }
+}
+
+/// Send two hovers, which a worker handles one after the other, and a change of their document,
+/// which must cancel both: the one being handled, and the one that waits for it.
+#[cfg(test)]
+fn test_cancelled_hovers(harness: &mut ServerTestHarness) -> Vec<String> {
+    use lsp_markup::LSHoverContents;
+    use serde_json::Value;
+    use jsonrpc::json_util::JsonObject;
+    use url::Url;
+
+    harness.initialize(Value::Object(JsonObject::new())).unwrap();
+    let uri = Url::parse("file:///doc.txt").unwrap();
+    let params = TextDocumentPositionParams {
+        text_document : TextDocumentIdentifier { uri : uri.clone() },
+        position : Position { line : 0, character : 0 },
+    };
+    let futures : Vec<_> = (0..2)
+        .map(|_| harness.send_request::<methods::HoverRequest>(params.clone()).unwrap())
+        .collect();
+    harness.did_change(&uri, 2, "text").unwrap();
+    futures.into_iter().map(|future| match future.wait() {
+        Ok(Ok(hover)) => match hover.contents {
+            LSHoverContents::MarkedStrings(ref strings) => format!("{:?}", strings),
+            ref contents => panic!("Unexpected hover: {:?}", contents),
+        },
+        _ => panic!("Hover failed."),
+    }).collect()
+}
+
+#[cfg(test)]
+fn test_hovers(harness: &mut ServerTestHarness, count: usize) -> Vec<String> {
+    use lsp_markup::LSHoverContents;
+    use serde_json::Value;
+    use jsonrpc::json_util::JsonObject;
+    use url::Url;
+
+    harness.initialize(Value::Object(JsonObject::new())).unwrap();
+    let params = TextDocumentPositionParams {
+        text_document : TextDocumentIdentifier { uri : Url::parse("file:///doc.txt").unwrap() },
+        position : Position { line : 0, character : 0 },
+    };
+    let futures : Vec<_> = (0..count)
+        .map(|_| harness.send_request::<methods::HoverRequest>(params.clone()).unwrap())
+        .collect();
+    let mut hovers : Vec<String> = futures.into_iter().map(|future| match future.wait() {
+        Ok(Ok(hover)) => match hover.contents {
+            LSHoverContents::MarkedStrings(ref strings) => format!("{:?}", strings),
+            ref contents => panic!("Unexpected hover: {:?}", contents),
+        },
+        _ => panic!("Hover failed."),
+    }).collect();
+    hovers.sort();
+    hovers
+}
+
+#[test]
+fn run_actor_server__test() {
+    let mut harness = ServerTestHarness::start_with_runner(|input, context| {
+        run_actor_server(input, context, |_| ActorServer(Default::default()))
+    });
+    let hovers = test_hovers(&mut harness, 3);
+    assert_eq!(hovers, vec![r#"[String("1")]"#, r#"[String("2")]"#, r#"[String("3")]"#]);
+    assert_eq!(harness.finish(), 0);
+}
+
+#[test]
+fn run_document_server__test() {
+    let server = SharedServer(Default::default());
+    let counter = server.0.clone();
+    let mut harness = ServerTestHarness::start_with_runner(move |input, context| {
+        run_document_server(input, context, server, 2)
+    });
+    let hovers = test_hovers(&mut harness, 3);
+    assert_eq!(hovers, vec![r#"[String("1")]"#, r#"[String("2")]"#, r#"[String("3")]"#]);
+    assert_eq!(harness.finish(), 0);
+    assert_eq!(counter.load(::std::sync::atomic::Ordering::SeqCst), 3);
+}
+
+#[test]
+fn run_document_server_cancellation__test() {
+    let mut harness = ServerTestHarness::start_with_runner(move |input, context| {
+        run_document_server(input, context, CancelledServer { request : RequestInfo::default() }, 2)
+    });
+    assert_eq!(test_cancelled_hovers(&mut harness), vec![r#"[String("true")]"#, r#"[String("true")]"#]);
+    assert_eq!(harness.finish(), 0);
+}
+
+#[test]
+fn run_shared_server__test() {
+    let server = SharedServer(Default::default());
+    let counter = server.0.clone();
+    let mut harness = ServerTestHarness::start_with_runner(move |input, context| {
+        run_shared_server(input, context, server, 2)
+    });
+    assert_eq!(test_hovers(&mut harness, 4).len(), 4);
+    assert_eq!(harness.finish(), 0);
+    assert_eq!(counter.load(::std::sync::atomic::Ordering::SeqCst), 4);
+}
+
+#[test]
+fn run_shared_server_cancellation__test() {
+    let mut harness = ServerTestHarness::start_with_runner(move |input, context| {
+        run_shared_server(input, context, CancelledServer { request : RequestInfo::default() }, 1)
+    });
+    assert_eq!(test_cancelled_hovers(&mut harness), vec![r#"[String("true")]"#, r#"[String("true")]"#]);
+    assert_eq!(harness.finish(), 0);
+}
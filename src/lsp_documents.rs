@@ -0,0 +1,588 @@
+// Copyright 2016 Bruno Medeiros
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>.
+// This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! In-memory store of the text documents opened by the client, and the client side of it,
+//! which sends the changes of the buffers of a client to the server:
+//!
+//! ```ignore
+//! let mut documents = DocumentSyncClient::negotiated(endpoint, &server_capabilities);
+//! try!(documents.open(uri.clone(), "rust", "fn main() {}"));
+//! try!(documents.edit(&uri, range, "foo"));
+//! try!(documents.close(&uri));
+//! ```
+
+use std::collections::HashMap;
+use std::mem;
+
+use util::core::*;
+
+use jsonrpc::*;
+use jsonrpc::method_types::MethodError;
+use ls_types::*;
+use serde_json::Value;
+use url::Url;
+
+use lsp::*;
+use lsp_capabilities::LSClientCapabilities;
+use lsp_capabilities::LSServerCapabilities;
+use lsp_errors::method_error_ContentModified;
+
+use lsp_position::LineIndex;
+use lsp_position::PositionEncoding;
+
+/* ----------------- Document ----------------- */
+
+/// The current contents of a text document opened by the client.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Document {
+    pub uri: Url,
+    pub language_id: String,
+    pub version: u64,
+    text: String,
+    line_index: LineIndex,
+}
+
+impl Document {
+
+    pub fn new(item: TextDocumentItem) -> Document {
+        let line_index = LineIndex::new(&item.text);
+        Document { 
+            uri : item.uri, language_id : item.language_id, version : item.version, 
+            text : item.text, line_index : line_index,
+        }
+    }
+    
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+    
+    pub fn line_index(&self) -> &LineIndex {
+        &self.line_index
+    }
+
+    /// Apply given content change. A change without range replaces the whole text.
+    /// The range is checked before modifying the document, so an invalid change leaves it unmodified.
+    pub fn apply_change(&mut self, change: &TextDocumentContentChangeEvent, encoding: PositionEncoding) 
+        -> GResult<()> 
+    {
+        try!(self.apply_undoable_change(change, encoding));
+        Ok(())
+    }
+    
+    /// Apply given content changes in order, in place. 
+    /// If one of them is invalid, the ones before it are undone, leaving the document unmodified.
+    pub fn apply_changes(&mut self, changes: &[TextDocumentContentChangeEvent], encoding: PositionEncoding) 
+        -> GResult<()> 
+    {
+        try!(self.apply_undoable_changes(changes, encoding));
+        Ok(())
+    }
+    
+    fn apply_undoable_changes(&mut self, changes: &[TextDocumentContentChangeEvent], encoding: PositionEncoding) 
+        -> GResult<Vec<AppliedEdit>> 
+    {
+        let mut applied = Vec::with_capacity(changes.len());
+        for change in changes {
+            match self.apply_undoable_change(change, encoding) {
+                Ok(edit) => applied.push(edit),
+                Err(error) => {
+                    self.undo_edits(applied);
+                    return Err(error);
+                }
+            }
+        }
+        Ok(applied)
+    }
+    
+    fn apply_undoable_change(&mut self, change: &TextDocumentContentChangeEvent, encoding: PositionEncoding) 
+        -> GResult<AppliedEdit> 
+    {
+        match change.range {
+            None => {
+                let replaced_text = mem::replace(&mut self.text, change.text.clone());
+                self.line_index = LineIndex::new(&self.text);
+                Ok(AppliedEdit { start : 0, end : self.text.len(), replaced_text : replaced_text })
+            }
+            Some(ref range) => {
+                let (start, end) = try!(self.range_to_offsets(range, encoding));
+                let replaced_text = self.text[start..end].to_string();
+                self.replace_range(start, end, &change.text);
+                Ok(AppliedEdit { start : start, end : start + change.text.len(), replaced_text : replaced_text })
+            }
+        }
+    }
+    
+    fn undo_edits(&mut self, applied: Vec<AppliedEdit>) {
+        for edit in applied.into_iter().rev() {
+            self.replace_range(edit.start, edit.end, &edit.replaced_text);
+        }
+    }
+    
+    fn replace_range(&mut self, start: usize, end: usize, new_text: &str) {
+        self.text.replace_range(start..end, new_text);
+        self.line_index.apply_edit(start, end, new_text);
+    }
+    
+    pub fn position_to_offset(&self, position: &Position, encoding: PositionEncoding) -> GResult<usize> {
+        self.line_index.position_to_offset(&self.text, position, encoding)
+    }
+    
+    pub fn offset_to_position(&self, offset: usize, encoding: PositionEncoding) -> GResult<Position> {
+        self.line_index.offset_to_position(&self.text, offset, encoding)
+    }
+    
+    pub fn range_to_offsets(&self, range: &Range, encoding: PositionEncoding) -> GResult<(usize, usize)> {
+        let start = try!(self.position_to_offset(&range.start, encoding));
+        let end = try!(self.position_to_offset(&range.end, encoding));
+        if end < start {
+            return Err("Invalid range: end is before start.".into());
+        }
+        Ok((start, end))
+    }
+    
+    pub fn offsets_to_range(&self, start: usize, end: usize, encoding: PositionEncoding) -> GResult<Range> {
+        Ok(Range {
+            start : try!(self.offset_to_position(start, encoding)),
+            end : try!(self.offset_to_position(end, encoding)),
+        })
+    }
+
+}
+
+/// An edit applied to a document, with the text it replaced, so that it can be undone.
+#[derive(Debug)]
+struct AppliedEdit {
+    start: usize,
+    end: usize,
+    replaced_text: String,
+}
+
+/* ----------------- TextDocuments ----------------- */
+
+/// The text documents currently open in the client, kept up to date from the
+/// `textDocument/didOpen`, `didChange` and `didClose` notifications.
+///
+/// A server implementation owning a `TextDocuments` should forward these notifications to it.
+#[derive(Debug)]
+pub struct TextDocuments {
+    documents: HashMap<Url, Document>,
+    /// The position encoding of incremental changes
+    pub encoding: PositionEncoding,
+    /// The sync kind negotiated with the client, which change events must conform to
+    pub sync_kind: TextDocumentSyncKind,
+    /// Whether the client sends `textDocument/didSave` notifications
+    pub did_save: bool,
+    /// Whether the client sends `textDocument/willSave` notifications
+    pub will_save: bool,
+}
+
+impl Default for TextDocuments {
+    fn default() -> TextDocuments {
+        TextDocuments::new()
+    }
+}
+
+impl TextDocuments {
+
+    pub fn new() -> TextDocuments {
+        Self::with_encoding(PositionEncoding::UTF16)
+    }
+
+    pub fn with_encoding(encoding: PositionEncoding) -> TextDocuments {
+        TextDocuments { 
+            documents : HashMap::new(), encoding : encoding, sync_kind : TextDocumentSyncKind::Incremental,
+            did_save : true, will_save : true,
+        }
+    }
+
+    /// Create a store configured for the sync kind (Full if not declared) and the position encoding
+    /// (UTF-16 if not declared) of given `initialize` result, which the client conforms to,
+    /// and for the save notifications the client declared it sends.
+    pub fn negotiated(initialize_result: &LSInitializeResult, client_capabilities: &LSClientCapabilities) 
+        -> TextDocuments 
+    {
+        let encoding = initialize_result.position_encoding.unwrap_or(PositionEncoding::UTF16);
+        let mut documents = TextDocuments::with_encoding(encoding);
+        documents.sync_kind = initialize_result.capabilities.text_document_sync.clone()
+            .unwrap_or(TextDocumentSyncKind::Full);
+        documents.did_save = client_capabilities.supports_save_notification("didSave");
+        documents.will_save = client_capabilities.supports_save_notification("willSave");
+        documents
+    }
+
+    /// Check given change events conform to the negotiated sync kind.
+    /// (In Incremental mode, the client may still send full-text changes)
+    pub fn check_sync_kind(&self, changes: &[TextDocumentContentChangeEvent]) -> GResult<()> {
+        match self.sync_kind {
+            TextDocumentSyncKind::None => {
+                Err("Received document change, but the negotiated sync kind is None.".into())
+            }
+            TextDocumentSyncKind::Full => {
+                if changes.iter().any(|change| change.range.is_some()) {
+                    return Err("Received incremental document change, but the negotiated sync kind is Full.".into());
+                }
+                Ok(())
+            }
+            TextDocumentSyncKind::Incremental => Ok(()),
+        }
+    }
+
+    pub fn get(&self, uri: &Url) -> Option<&Document> {
+        self.documents.get(uri)
+    }
+
+    pub fn len(&self) -> usize {
+        self.documents.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.documents.is_empty()
+    }
+
+    pub fn iter(&self) -> ::std::collections::hash_map::Values<Url, Document> {
+        self.documents.values()
+    }
+
+    pub fn did_open(&mut self, params: DidOpenTextDocumentParams) {
+        let document = Document::new(params.text_document);
+        self.documents.insert(document.uri.clone(), document);
+    }
+
+    /// The current version of given document, if open.
+    pub fn version(&self, uri: &Url) -> Option<u64> {
+        self.documents.get(uri).map(|document| document.version)
+    }
+
+    /// Whether given version is not the current version of given document, 
+    /// meaning a result computed against it is outdated.
+    pub fn is_stale(&self, uri: &Url, version: u64) -> bool {
+        self.version(uri) != Some(version)
+    }
+
+    /// Check that a result computed against given document version is not outdated,
+    /// otherwise fail with `ContentModified`, as a request handler should.
+    pub fn check_current_version<DATA : Default>(&self, uri: &Url, version: u64) -> Result<(), MethodError<DATA>> {
+        if self.is_stale(uri, version) {
+            return Err(method_error_ContentModified());
+        }
+        Ok(())
+    }
+
+    /// Apply given changes. Changes with a version not newer than the current one are rejected,
+    /// leaving the document unmodified, as are changes of which one is invalid.
+    pub fn did_change(&mut self, params: DidChangeTextDocumentParams) -> GResult<()> {
+        try!(self.check_sync_kind(&params.content_changes));
+        
+        let uri = params.text_document.uri;
+        let new_version = params.text_document.version;
+        let encoding = self.encoding;
+        let document = match self.documents.get_mut(&uri) {
+            Some(document) => document,
+            None => return Err(format!("Document not open: {}", uri).into()),
+        };
+        
+        if new_version <= document.version {
+            warn!("Rejected out-of-order change to {}: version {} after {}", uri, new_version, document.version);
+            return Err(format!("Stale change to {}: version {} is not newer than {}.", 
+                uri, new_version, document.version).into());
+        }
+
+        try!(document.apply_changes(&params.content_changes, encoding));
+        document.version = new_version;
+        Ok(())
+    }
+
+    /// The document contents don't change on save, so this only checks the document is open,
+    /// and that the client declared it sends `didSave`.
+    pub fn did_save(&mut self, params: DidSaveTextDocumentParams) -> GResult<()> {
+        if !self.did_save {
+            return Err("Received didSave, but the client did not declare it sends it.".into());
+        }
+        if !self.documents.contains_key(&params.text_document.uri) {
+            return Err(format!("Document not open: {}", params.text_document.uri).into());
+        }
+        Ok(())
+    }
+
+    pub fn did_close(&mut self, params: DidCloseTextDocumentParams) -> Option<Document> {
+        self.documents.remove(&params.text_document.uri)
+    }
+
+}
+
+/* ----------------- DocumentSyncClient ----------------- */
+
+/// The buffers of a client, sent to the server with the `textDocument/didOpen`, `didChange` and `didClose`
+/// notifications. Each change is a new version of the buffer, sent as the edits or as the full text,
+/// according to the sync kind of the server. If that is None, the buffers are only tracked locally.
+pub struct DocumentSyncClient {
+    endpoint: Endpoint,
+    documents: HashMap<Url, Document>,
+    /// The position encoding of the edits
+    pub encoding: PositionEncoding,
+    /// The sync kind of the server
+    pub sync_kind: TextDocumentSyncKind,
+}
+
+impl DocumentSyncClient {
+
+    pub fn new(endpoint: Endpoint, sync_kind: TextDocumentSyncKind, encoding: PositionEncoding) 
+        -> DocumentSyncClient 
+    {
+        DocumentSyncClient { 
+            endpoint : endpoint, documents : HashMap::new(), encoding : encoding, sync_kind : sync_kind,
+        }
+    }
+
+    /// Create a client for the sync kind and the position encoding declared in the server capabilities.
+    pub fn negotiated(endpoint: Endpoint, server_capabilities: &LSServerCapabilities) -> DocumentSyncClient {
+        let encoding = server_capabilities.get_path(&["positionEncoding"]).and_then(Value::as_str)
+            .and_then(PositionEncoding::from_str)
+            .unwrap_or(PositionEncoding::UTF16);
+        DocumentSyncClient::new(endpoint, server_capabilities.text_document_sync_kind(), encoding)
+    }
+
+    pub fn get(&self, uri: &Url) -> Option<&Document> {
+        self.documents.get(uri)
+    }
+
+    fn is_synced(&self) -> bool {
+        match self.sync_kind {
+            TextDocumentSyncKind::None => false,
+            _ => true,
+        }
+    }
+
+    /// Open a buffer with given text, as version 1.
+    pub fn open(&mut self, uri: Url, language_id: &str, text: &str) -> GResult<()> {
+        if self.documents.contains_key(&uri) {
+            return Err(format!("Document already open: {}", uri).into());
+        }
+        let item = TextDocumentItem { 
+            uri : uri.clone(), language_id : language_id.to_string(), version : 1, text : text.to_string(),
+        };
+        if self.is_synced() {
+            let params = DidOpenTextDocumentParams { text_document : item.clone() };
+            try!(send_lsp_notification::<methods::DidOpenTextDocument>(&mut self.endpoint, params));
+        }
+        self.documents.insert(uri, Document::new(item));
+        Ok(())
+    }
+
+    /// Replace given range of a buffer with given text.
+    pub fn edit(&mut self, uri: &Url, range: Range, text: &str) -> GResult<()> {
+        let change = TextDocumentContentChangeEvent { range : Some(range), range_length : None, text : text.to_string() };
+        self.change(uri, vec![change])
+    }
+
+    /// Replace the whole text of a buffer.
+    pub fn replace_text(&mut self, uri: &Url, text: &str) -> GResult<()> {
+        let change = TextDocumentContentChangeEvent { range : None, range_length : None, text : text.to_string() };
+        self.change(uri, vec![change])
+    }
+
+    /// Apply given changes to a buffer, as its next version. 
+    /// If a change is invalid, or the notification fails to be sent, the buffer is left unmodified.
+    pub fn change(&mut self, uri: &Url, changes: Vec<TextDocumentContentChangeEvent>) -> GResult<()> {
+        let is_synced = self.is_synced();
+        let document = match self.documents.get_mut(uri) {
+            Some(document) => document,
+            None => return Err(format!("Document not open: {}", uri).into()),
+        };
+        let applied = try!(document.apply_undoable_changes(&changes, self.encoding));
+        let new_version = document.version + 1;
+
+        let content_changes = match self.sync_kind {
+            TextDocumentSyncKind::None => vec![],
+            TextDocumentSyncKind::Full => {
+                let text = document.text.clone();
+                vec![TextDocumentContentChangeEvent { range : None, range_length : None, text : text }]
+            }
+            TextDocumentSyncKind::Incremental => changes,
+        };
+        let params = DidChangeTextDocumentParams {
+            text_document : VersionedTextDocumentIdentifier { uri : uri.clone(), version : new_version },
+            content_changes : content_changes,
+        };
+        // Commit the new version only once the server was sent it, so that both agree on it
+        if is_synced {
+            if let Err(error) = send_lsp_notification::<methods::DidChangeTextDocument>(&mut self.endpoint, params) {
+                document.undo_edits(applied);
+                return Err(error.into());
+            }
+        }
+        document.version = new_version;
+        Ok(())
+    }
+
+    pub fn close(&mut self, uri: &Url) -> GResult<()> {
+        if !self.documents.contains_key(uri) {
+            return Err(format!("Document not open: {}", uri).into());
+        }
+        if self.is_synced() {
+            let params = DidCloseTextDocumentParams { text_document : TextDocumentIdentifier { uri : uri.clone() } };
+            try!(send_lsp_notification::<methods::DidCloseTextDocument>(&mut self.endpoint, params));
+        }
+        self.documents.remove(uri);
+        Ok(())
+    }
+
+}
+
+
+#[cfg(test)]
+pub fn test_change(start: (u64, u64), end: (u64, u64), text: &str) -> TextDocumentContentChangeEvent {
+    let range = Range {
+        start : Position { line : start.0, character : start.1 },
+        end : Position { line : end.0, character : end.1 },
+    };
+    TextDocumentContentChangeEvent { range : Some(range), range_length : None, text : text.to_string() }
+}
+
+#[test]
+fn text_documents__test() {
+    let uri = Url::parse("file:///doc.txt").unwrap();
+    let mut documents = TextDocuments::new();
+
+    documents.did_open(DidOpenTextDocumentParams {
+        text_document : TextDocumentItem {
+            uri : uri.clone(), language_id : "text".to_string(), version : 1, text : "abc\ndef\n".to_string()
+        }
+    });
+    assert_eq!(documents.get(&uri).unwrap().text(), "abc\ndef\n");
+
+    documents.did_change(DidChangeTextDocumentParams {
+        text_document : VersionedTextDocumentIdentifier { uri : uri.clone(), version : 2 },
+        content_changes : vec![test_change((1, 1), (1, 2), "XX"), test_change((0, 0), (0, 0), "_")],
+    }).unwrap();
+    let document = documents.get(&uri).unwrap();
+    assert_eq!(document.text(), "_abc\ndXXf\n");
+    assert_eq!(document.version, 2);
+    assert_eq!(document.line_index(), &LineIndex::new("_abc\ndXXf\n"));
+
+    // UTF-16 columns: the emoji is 2 code units
+    documents.did_change(DidChangeTextDocumentParams {
+        text_document : VersionedTextDocumentIdentifier { uri : uri.clone(), version : 3 },
+        content_changes : vec![
+            TextDocumentContentChangeEvent { range : None, range_length : None, text : "\u{1F600}x".to_string() },
+            test_change((0, 2), (0, 3), "y"),
+        ],
+    }).unwrap();
+    assert_eq!(documents.get(&uri).unwrap().text(), "\u{1F600}y");
+
+    // Stale version is rejected
+    assert!(documents.did_change(DidChangeTextDocumentParams {
+        text_document : VersionedTextDocumentIdentifier { uri : uri.clone(), version : 3 },
+        content_changes : vec![test_change((0, 0), (0, 0), "stale")],
+    }).is_err());
+    assert_eq!(documents.get(&uri).unwrap().text(), "\u{1F600}y");
+    assert_eq!(documents.version(&uri), Some(3));
+
+    // A batch with an invalid change is not applied at all
+    assert!(documents.did_change(DidChangeTextDocumentParams {
+        text_document : VersionedTextDocumentIdentifier { uri : uri.clone(), version : 4 },
+        content_changes : vec![test_change((0, 0), (0, 0), "ok\n"), test_change((9, 0), (9, 1), "")],
+    }).is_err());
+    assert_eq!(documents.get(&uri).unwrap().text(), "\u{1F600}y");
+    assert_eq!(documents.get(&uri).unwrap().line_index(), &LineIndex::new("\u{1F600}y"));
+    assert_eq!(documents.version(&uri), Some(3));
+    assert!(documents.is_stale(&uri, 2));
+    assert!(documents.check_current_version::<()>(&uri, 3).is_ok());
+    assert_eq!(documents.check_current_version::<()>(&uri, 2).unwrap_err().code, -32801);
+
+    assert!(documents.did_close(DidCloseTextDocumentParams {
+        text_document : TextDocumentIdentifier { uri : uri.clone() }
+    }).is_some());
+    assert!(documents.get(&uri).is_none());
+}
+
+#[test]
+fn sync_kind__test() {
+    use lsp_capabilities::ClientCapabilitiesBuilder;
+    use lsp_initialize::InitializeResultBuilder;
+
+    let result = InitializeResultBuilder::new().text_document_sync(TextDocumentSyncKind::Full).build();
+    let mut documents = TextDocuments::negotiated(&result, &LSClientCapabilities(Value::Null));
+    assert_eq!(documents.encoding, PositionEncoding::UTF16);
+    assert_eq!((documents.did_save, documents.will_save), (false, false));
+    let uri = Url::parse("file:///doc.txt").unwrap();
+    assert!(documents.did_save(DidSaveTextDocumentParams { text_document : TextDocumentIdentifier { uri : uri } })
+        .is_err());
+    
+    let full_change = TextDocumentContentChangeEvent { range : None, range_length : None, text : "".to_string() };
+    assert!(documents.check_sync_kind(&[full_change.clone()]).is_ok());
+    assert!(documents.check_sync_kind(&[full_change.clone(), test_change((0, 0), (0, 0), "")]).is_err());
+    
+    let result = InitializeResultBuilder::new()
+        .text_document_sync(TextDocumentSyncKind::Incremental)
+        .position_encoding(PositionEncoding::UTF8)
+        .build();
+    let client_capabilities = ClientCapabilitiesBuilder::new().save_notification("didSave").build();
+    let documents = TextDocuments::negotiated(&result, &LSClientCapabilities(client_capabilities));
+    assert_eq!(documents.encoding, PositionEncoding::UTF8);
+    assert_eq!((documents.did_save, documents.will_save), (true, false));
+    assert!(documents.check_sync_kind(&[full_change.clone(), test_change((0, 0), (0, 0), "")]).is_ok());
+}
+
+#[test]
+fn document_sync_client__test() {
+    use serde_json;
+    use lsp_testing::pipe;
+    use lsp_transport::LSPBufferedMessageReader;
+
+    fn next_params(reader: &mut MessageReader, method: &str) -> Value {
+        let message : Value = serde_json::from_str(&reader.read_next().unwrap()).unwrap();
+        assert_eq!(message.find("method").and_then(Value::as_str), Some(method));
+        message.find("params").unwrap().clone()
+    }
+
+    fn range(start: (u64, u64), end: (u64, u64)) -> Range {
+        test_change(start, end, "").range.unwrap()
+    }
+
+    let uri = Url::parse("file:///doc.txt").unwrap();
+    for sync_kind in vec![TextDocumentSyncKind::Full, TextDocumentSyncKind::Incremental] {
+        let (writer, reader) = pipe();
+        let reader = &mut LSPBufferedMessageReader::new(reader);
+        let endpoint = LSPEndpoint::create_lsp_output_with_output_stream(|| writer);
+        let mut client = DocumentSyncClient::new(endpoint.clone(), sync_kind.clone(), PositionEncoding::UTF16);
+        // The server documents, kept in sync by the notifications
+        let mut documents = TextDocuments::new();
+
+        client.open(uri.clone(), "text", "abc\ndef\n").unwrap();
+        assert!(client.open(uri.clone(), "text", "").is_err());
+        documents.did_open(serde_json::from_value(next_params(reader, methods::TEXT_DOCUMENT_DID_OPEN)).unwrap());
+
+        client.edit(&uri, range((1, 0), (1, 1)), "X").unwrap();
+        let params : DidChangeTextDocumentParams = 
+            serde_json::from_value(next_params(reader, methods::TEXT_DOCUMENT_DID_CHANGE)).unwrap();
+        assert_eq!(params.text_document.version, 2);
+        assert_eq!(params.content_changes[0].range.is_some(), sync_kind == TextDocumentSyncKind::Incremental);
+        documents.did_change(params).unwrap();
+
+        // Invalid changes are not applied
+        assert!(client.edit(&uri, range((9, 0), (9, 1)), "").is_err());
+        client.replace_text(&uri, "new").unwrap();
+        documents.did_change(serde_json::from_value(next_params(reader, methods::TEXT_DOCUMENT_DID_CHANGE)).unwrap())
+            .unwrap();
+        assert_eq!(client.get(&uri).unwrap().version, 3);
+        assert_eq!(documents.get(&uri).unwrap(), client.get(&uri).unwrap());
+
+        client.close(&uri).unwrap();
+        next_params(reader, methods::TEXT_DOCUMENT_DID_CLOSE);
+        assert!(client.get(&uri).is_none());
+
+        // A change that fails to be sent is not applied
+        client.open(uri.clone(), "text", "abc").unwrap();
+        next_params(reader, methods::TEXT_DOCUMENT_DID_OPEN);
+        endpoint.clone().shutdown_and_join();
+        assert!(client.replace_text(&uri, "lost").is_err());
+        assert_eq!(client.get(&uri).unwrap().version, 1);
+        assert_eq!(client.get(&uri).unwrap().text(), "abc");
+    }
+}
+
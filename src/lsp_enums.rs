@@ -0,0 +1,319 @@
+// Copyright 2016 Bruno Medeiros
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>.
+// This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Enums for the numeric constants of the protocol. Each serializes to the numeric value of the spec,
+//! and deserializes a value it doesn't know into its `Unknown` variant, instead of failing,
+//! as newer clients can send values added after this crate was written.
+//!
+//! The enums of `ls_types` for the same constants fail on unknown values, and lack the newer values.
+//! `LSMessageType`, `LSDiagnosticSeverity`, `LSCompletionItemKind`, `LSSymbolKind`, `LSDocumentHighlightKind`
+//! and `LSTextDocumentSyncKind` convert from them, and back with `to_ls_types` for known values.
+
+use serde;
+use serde::de::Error as DeError;
+use serde_json;
+use serde_json::Value;
+
+use ls_types;
+
+use lsp_protocol::ProtocolField;
+
+/// Define a protocol enum, with each variant given as `Name = value`, and an `Unknown(i32)` variant.
+macro_rules! protocol_enum {
+    (
+        $(#[$attr:meta])*
+        pub enum $name:ident {
+            $( $(#[$variant_attr:meta])* $variant:ident = $value:tt ),* $(,)*
+        }
+    ) => {
+        $(#[$attr])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub enum $name {
+            $( $(#[$variant_attr])* $variant, )*
+            /// A value this crate doesn't know.
+            Unknown(i32),
+        }
+
+        impl $name {
+
+            pub fn from_i32(value: i32) -> $name {
+                match value {
+                    $( $value => $name::$variant, )*
+                    _ => $name::Unknown(value),
+                }
+            }
+
+            pub fn to_i32(&self) -> i32 {
+                match *self {
+                    $( $name::$variant => $value, )*
+                    $name::Unknown(value) => value,
+                }
+            }
+
+            pub fn is_known(&self) -> bool {
+                match *self {
+                    $name::Unknown(_) => false,
+                    _ => true,
+                }
+            }
+
+            pub fn to_json(&self) -> Value {
+                // As parsed, so that values round-trip as equal
+                match self.to_i32() {
+                    value if value >= 0 => Value::U64(value as u64),
+                    value => Value::I64(value as i64),
+                }
+            }
+
+            pub fn from_json(value: Value) -> Result<$name, String> {
+                match value.as_i64() {
+                    Some(number) if number >= i32::min_value() as i64 && number <= i32::max_value() as i64 => {
+                        Ok($name::from_i32(number as i32))
+                    }
+                    _ => Err(format!("{}: expected an integer, found {}.", stringify!($name), value)),
+                }
+            }
+
+        }
+
+        impl ProtocolField for $name {
+            fn to_json_field(&self) -> Value {
+                self.to_json()
+            }
+
+            fn from_json_field(value: Value) -> Result<Self, String> {
+                $name::from_json(value)
+            }
+        }
+
+        impl serde::Serialize for $name {
+            fn serialize<S>(&self, serializer: &mut S) -> Result<(), S::Error>
+            where
+                S : serde::Serializer,
+            {
+                serializer.serialize_i32(self.to_i32())
+            }
+        }
+
+        impl serde::Deserialize for $name {
+            fn deserialize<D>(deserializer: &mut D) -> Result<$name, D::Error>
+            where
+                D : serde::Deserializer,
+            {
+                let value = try!(Value::deserialize(deserializer));
+                $name::from_json(value).map_err(D::Error::custom)
+            }
+        }
+    };
+}
+
+/// Conversions between a protocol enum, and the `ls_types` enum for the same constants.
+macro_rules! ls_types_enum {
+    ( $name:ident, $ls_types_name:ident ) => {
+        impl From<ls_types::$ls_types_name> for $name {
+            fn from(value: ls_types::$ls_types_name) -> $name {
+                $name::from_json(serde_json::to_value(&value)).unwrap_or($name::Unknown(0))
+            }
+        }
+
+        impl $name {
+            /// The `ls_types` value, if it has this one.
+            pub fn to_ls_types(&self) -> Option<ls_types::$ls_types_name> {
+                serde_json::from_value(self.to_json()).ok()
+            }
+        }
+    };
+}
+
+/* ----------------- Window ----------------- */
+
+protocol_enum! {
+    pub enum LSMessageType {
+        Error = 1,
+        Warning = 2,
+        Info = 3,
+        Log = 4,
+        Debug = 5,
+    }
+}
+ls_types_enum!(LSMessageType, MessageType);
+
+/* ----------------- Text synchronization ----------------- */
+
+protocol_enum! {
+    pub enum LSTextDocumentSyncKind {
+        None = 0,
+        Full = 1,
+        Incremental = 2,
+    }
+}
+ls_types_enum!(LSTextDocumentSyncKind, TextDocumentSyncKind);
+
+/* ----------------- Diagnostics ----------------- */
+
+protocol_enum! {
+    pub enum LSDiagnosticSeverity {
+        Error = 1,
+        Warning = 2,
+        Information = 3,
+        Hint = 4,
+    }
+}
+ls_types_enum!(LSDiagnosticSeverity, DiagnosticSeverity);
+
+protocol_enum! {
+    /// Additional metadata about the type of a diagnostic.
+    pub enum DiagnosticTag {
+        /// Unused or unnecessary code. Clients may render it faded out.
+        Unnecessary = 1,
+        /// Deprecated or obsolete code. Clients may render it struck through.
+        Deprecated = 2,
+    }
+}
+
+/* ----------------- Completion ----------------- */
+
+protocol_enum! {
+    pub enum LSCompletionItemKind {
+        Text = 1,
+        Method = 2,
+        Function = 3,
+        Constructor = 4,
+        Field = 5,
+        Variable = 6,
+        Class = 7,
+        Interface = 8,
+        Module = 9,
+        Property = 10,
+        Unit = 11,
+        Value = 12,
+        Enum = 13,
+        Keyword = 14,
+        Snippet = 15,
+        Color = 16,
+        File = 17,
+        Reference = 18,
+        Folder = 19,
+        EnumMember = 20,
+        Constant = 21,
+        Struct = 22,
+        Event = 23,
+        Operator = 24,
+        TypeParameter = 25,
+    }
+}
+ls_types_enum!(LSCompletionItemKind, CompletionItemKind);
+
+protocol_enum! {
+    pub enum CompletionItemTag {
+        Deprecated = 1,
+    }
+}
+
+protocol_enum! {
+    pub enum InsertTextFormat {
+        PlainText = 1,
+        Snippet = 2,
+    }
+}
+
+protocol_enum! {
+    /// How whitespace and indentation is handled when a completion item is inserted.
+    pub enum InsertTextMode {
+        /// The text is inserted as is.
+        AsIs = 1,
+        /// The indentation of the line is applied to each line of the inserted text.
+        AdjustIndentation = 2,
+    }
+}
+
+protocol_enum! {
+    pub enum CompletionTriggerKind {
+        Invoked = 1,
+        TriggerCharacter = 2,
+        TriggerForIncompleteCompletions = 3,
+    }
+}
+
+/* ----------------- Symbols ----------------- */
+
+protocol_enum! {
+    pub enum LSSymbolKind {
+        File = 1,
+        Module = 2,
+        Namespace = 3,
+        Package = 4,
+        Class = 5,
+        Method = 6,
+        Property = 7,
+        Field = 8,
+        Constructor = 9,
+        Enum = 10,
+        Interface = 11,
+        Function = 12,
+        Variable = 13,
+        Constant = 14,
+        String = 15,
+        Number = 16,
+        Boolean = 17,
+        Array = 18,
+        Object = 19,
+        Key = 20,
+        Null = 21,
+        EnumMember = 22,
+        Struct = 23,
+        Event = 24,
+        Operator = 25,
+        TypeParameter = 26,
+    }
+}
+ls_types_enum!(LSSymbolKind, SymbolKind);
+
+protocol_enum! {
+    pub enum SymbolTag {
+        Deprecated = 1,
+    }
+}
+
+protocol_enum! {
+    pub enum LSDocumentHighlightKind {
+        Text = 1,
+        Read = 2,
+        Write = 3,
+    }
+}
+ls_types_enum!(LSDocumentHighlightKind, DocumentHighlightKind);
+
+/* ----------------- Inlay hints ----------------- */
+
+protocol_enum! {
+    pub enum InlayHintKind {
+        Type = 1,
+        Parameter = 2,
+    }
+}
+
+
+#[test]
+fn protocol_enums__test() {
+    assert_eq!(LSSymbolKind::from_i32(23), LSSymbolKind::Struct);
+    assert_eq!(LSSymbolKind::Struct.to_i32(), 23);
+    assert_eq!(LSSymbolKind::from_i32(99), LSSymbolKind::Unknown(99));
+    assert!(!LSSymbolKind::Unknown(99).is_known());
+
+    assert_eq!(serde_json::to_string(&LSCompletionItemKind::TypeParameter).unwrap(), "25");
+    assert_eq!(serde_json::from_str::<LSCompletionItemKind>("25").unwrap(), LSCompletionItemKind::TypeParameter);
+    assert_eq!(serde_json::from_str::<LSCompletionItemKind>("40").unwrap(), LSCompletionItemKind::Unknown(40));
+    assert_eq!(serde_json::to_string(&LSCompletionItemKind::Unknown(40)).unwrap(), "40");
+    assert!(serde_json::from_str::<LSCompletionItemKind>(r#""text""#).is_err());
+    assert!(serde_json::from_str::<InlayHintKind>("4294967296").is_err());
+
+    assert_eq!(LSMessageType::from(ls_types::MessageType::Warning), LSMessageType::Warning);
+    assert_eq!(LSMessageType::Info.to_ls_types(), Some(ls_types::MessageType::Info));
+    assert_eq!(LSDiagnosticSeverity::from(ls_types::DiagnosticSeverity::Hint), LSDiagnosticSeverity::Hint);
+    assert_eq!(LSTextDocumentSyncKind::Incremental.to_ls_types(), Some(ls_types::TextDocumentSyncKind::Incremental));
+}
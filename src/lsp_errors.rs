@@ -0,0 +1,471 @@
+// Copyright 2016 Bruno Medeiros
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>.
+// This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! The error type of this crate, and the LSP-specific error codes,
+//! in addition to the JSON-RPC ones of `jsonrpc::jsonrpc_common`.
+//! And the formatting of the messages of the error responses, such as to localize them.
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::sync::Arc;
+
+use util::core::GError;
+use util::core::GResult;
+
+use jsonrpc::jsonrpc_response::RequestError;
+use jsonrpc::method_types::MethodError;
+use jsonrpc::service_util::MessageWriter;
+use serde;
+use serde_json;
+use serde_json::Value;
+
+/* ----------------- LSError ----------------- */
+
+/// The kinds of failure of a LSP connection.
+#[derive(Debug)]
+pub enum LSError {
+    /// I/O failure reading or writing the underlying stream.
+    Transport(io::Error),
+    /// A message could not be parsed or serialized. Has the underlying error, if any.
+    Parse(String, Option<GError>),
+    /// A message is well-formed but violates the protocol.
+    Protocol(String),
+    /// A message handler failed.
+    Handler(GError),
+    /// The stream ended or the endpoint was shut down.
+    Shutdown(String),
+    /// The content of a message stopped arriving before its length was reached, within the read timeout.
+    /// The stream is left in the middle of that message, so it must be closed or resynchronized.
+    Truncated(String),
+}
+
+impl LSError {
+    
+    /// Convert an error from the `jsonrpc` layer, recovering the `LSError` if it is one.
+    pub fn from_gerror(error: GError) -> LSError {
+        match error.downcast::<LSError>() {
+            Ok(error) => *error,
+            Err(error) => {
+                match error.downcast::<io::Error>() {
+                    Ok(error) => LSError::Transport(*error),
+                    Err(error) => LSError::Handler(error),
+                }
+            }
+        }
+    }
+    
+    pub fn is_shutdown(&self) -> bool {
+        match *self {
+            LSError::Shutdown(_) => true,
+            _ => false,
+        }
+    }
+    
+    pub fn is_truncated(&self) -> bool {
+        match *self {
+            LSError::Truncated(_) => true,
+            _ => false,
+        }
+    }
+    
+    /// Whether the error is about a single message read, such as a bad header or an oversize message,
+    /// after which the next messages can still be read. Other errors, such as the end of the stream
+    /// or a broken pipe, end the input.
+    pub fn is_recoverable(&self) -> bool {
+        match *self {
+            LSError::Parse(..) | LSError::Protocol(_) => true,
+            _ => false,
+        }
+    }
+    
+}
+
+impl fmt::Display for LSError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LSError::Transport(ref error) => write!(f, "Transport error: {}", error),
+            LSError::Parse(ref message, None) => write!(f, "{}", message),
+            LSError::Parse(ref message, Some(ref error)) => write!(f, "{} {}", message, error),
+            LSError::Protocol(ref message) => write!(f, "{}", message),
+            LSError::Handler(ref error) => write!(f, "Handler error: {}", error),
+            LSError::Shutdown(ref message) => write!(f, "{}", message),
+            LSError::Truncated(ref message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl Error for LSError {
+    fn description(&self) -> &str {
+        match *self {
+            LSError::Transport(_) => "transport error",
+            LSError::Parse(..) => "parse error",
+            LSError::Protocol(_) => "protocol error",
+            LSError::Handler(_) => "handler error",
+            LSError::Shutdown(_) => "shutdown",
+            LSError::Truncated(_) => "truncated message",
+        }
+    }
+    
+    fn source(&self) -> Option<&(Error + 'static)> {
+        match *self {
+            LSError::Transport(ref error) => Some(error),
+            LSError::Parse(_, Some(ref error)) => Some(&**error),
+            LSError::Handler(ref error) => Some(&**error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for LSError {
+    fn from(error: io::Error) -> LSError {
+        LSError::Transport(error)
+    }
+}
+
+/* ----------------- Error codes ----------------- */
+
+pub const ERROR_CODE__ServerNotInitialized: i64 = -32002;
+pub const ERROR_CODE__UnknownErrorCode: i64 = -32001;
+pub const ERROR_CODE__RequestFailed: i64 = -32803;
+pub const ERROR_CODE__ServerCancelled: i64 = -32802;
+pub const ERROR_CODE__ContentModified: i64 = -32801;
+pub const ERROR_CODE__RequestCancelled: i64 = -32800;
+pub const ERROR_CODE__InvalidParams: i64 = -32602;
+pub const ERROR_CODE__InternalError: i64 = -32603;
+pub const ERROR_CODE__ParseError: i64 = -32700;
+
+/* ----------------- Errors ----------------- */
+
+pub fn error_LSP_ServerNotInitialized() -> RequestError {
+    RequestError::new(ERROR_CODE__ServerNotInitialized, "Server not initialized.".to_string())
+}
+
+pub fn error_LSP_RequestCancelled() -> RequestError {
+    RequestError::new(ERROR_CODE__RequestCancelled, "Request cancelled.".to_string())
+}
+
+pub fn error_LSP_ContentModified() -> RequestError {
+    RequestError::new(ERROR_CODE__ContentModified, "Content modified.".to_string())
+}
+
+pub fn error_LSP_RequestFailed(message: String) -> RequestError {
+    RequestError::new(ERROR_CODE__RequestFailed, message)
+}
+
+/// Method error variant of `error_LSP_ContentModified`, for completing a typed method.
+pub fn method_error_ContentModified<DATA : Default>() -> MethodError<DATA> {
+    MethodError { code : ERROR_CODE__ContentModified, message : "Content modified.".to_string(), data : DATA::default() }
+}
+
+/// Method error variant of `error_LSP_RequestCancelled`, for completing a typed method.
+pub fn method_error_RequestCancelled<DATA : Default>() -> MethodError<DATA> {
+    MethodError { code : ERROR_CODE__RequestCancelled, message : "Request cancelled.".to_string(), data : DATA::default() }
+}
+
+/// Method error variant of `error_LSP_RequestFailed`, for completing a typed method.
+pub fn method_error_RequestFailed<DATA : Default>(message: String) -> MethodError<DATA> {
+    MethodError { code : ERROR_CODE__RequestFailed, message : message, data : DATA::default() }
+}
+
+/// Method error for a failure of the server itself, for completing a typed method.
+pub fn method_error_InternalError<DATA : Default>(message: String) -> MethodError<DATA> {
+    MethodError { code : ERROR_CODE__InternalError, message : message, data : DATA::default() }
+}
+
+/* ----------------- ServiceError ----------------- */
+
+/// The error of a request handler. It converts to the `MethodError` the request is completed with,
+/// and from the common errors of handler code, so that the code can use `try!`:
+///
+/// ```ignore
+/// fn read_hover(&self, path: &Path) -> Result<LSHover, ServiceError> {
+///     let text = try!(read_file(path));
+///     ...
+/// }
+///
+/// completable.complete(self.read_hover(&path).map_err(MethodError::from))
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServiceError {
+    pub code: i64,
+    pub message: String,
+    pub data: Option<Value>,
+}
+
+impl ServiceError {
+
+    pub fn new(code: i64, message: &str) -> ServiceError {
+        ServiceError { code : code, message : message.to_string(), data : None }
+    }
+
+    pub fn with_data(mut self, data: Value) -> ServiceError {
+        self.data = Some(data);
+        self
+    }
+
+    pub fn request_cancelled() -> ServiceError {
+        ServiceError::new(ERROR_CODE__RequestCancelled, "Request cancelled.")
+    }
+
+    pub fn content_modified() -> ServiceError {
+        ServiceError::new(ERROR_CODE__ContentModified, "Content modified.")
+    }
+
+    pub fn request_failed(message: &str) -> ServiceError {
+        ServiceError::new(ERROR_CODE__RequestFailed, message)
+    }
+
+    pub fn invalid_params(message: &str) -> ServiceError {
+        ServiceError::new(ERROR_CODE__InvalidParams, message)
+    }
+
+    pub fn internal(message: &str) -> ServiceError {
+        ServiceError::new(ERROR_CODE__InternalError, message)
+    }
+
+}
+
+impl fmt::Display for ServiceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} ({})", self.message, self.code)
+    }
+}
+
+impl Error for ServiceError {
+    fn description(&self) -> &str {
+        &self.message
+    }
+}
+
+/// An I/O failure is an internal error.
+impl From<io::Error> for ServiceError {
+    fn from(error: io::Error) -> ServiceError {
+        ServiceError::internal(&error.to_string())
+    }
+}
+
+/// A failure to deserialize is an error of the params, which the handler deserializes.
+impl From<serde_json::Error> for ServiceError {
+    fn from(error: serde_json::Error) -> ServiceError {
+        ServiceError::invalid_params(&format!("Invalid params: {}", error))
+    }
+}
+
+impl From<LSError> for ServiceError {
+    fn from(error: LSError) -> ServiceError {
+        ServiceError::request_failed(&error.to_string())
+    }
+}
+
+/// The data is deserialized to the error data type of the method, or is its default if it doesn't fit.
+impl<DATA> From<ServiceError> for MethodError<DATA>
+where
+    DATA : serde::Deserialize + Default,
+{
+    fn from(error: ServiceError) -> MethodError<DATA> {
+        let data = error.data.and_then(|data| serde_json::from_value(data).ok()).unwrap_or_default();
+        MethodError { code : error.code, message : error.message, data : data }
+    }
+}
+
+/* ----------------- Result mapping ----------------- */
+
+/// Conversion of the results of handler code to the result a request is completed with,
+/// the error message being the error as displayed:
+///
+/// ```ignore
+/// let text = try!(read_file(&path).or_request_failed());
+/// let value = try!(u64::from_str(&text).or_method_error(ERROR_CODE__InvalidParams));
+/// ```
+pub trait LSResultExt<T> {
+
+    /// Convert the error to a `MethodError` of given code.
+    fn or_method_error<DATA : Default>(self, code: i64) -> Result<T, MethodError<DATA>>;
+
+    /// Convert the error to a `RequestFailed` `MethodError`.
+    fn or_request_failed<DATA : Default>(self) -> Result<T, MethodError<DATA>>;
+
+}
+
+impl<T, E : fmt::Display> LSResultExt<T> for Result<T, E> {
+
+    fn or_method_error<DATA : Default>(self, code: i64) -> Result<T, MethodError<DATA>> {
+        self.map_err(|error| MethodError { code : code, message : error.to_string(), data : DATA::default() })
+    }
+
+    fn or_request_failed<DATA : Default>(self) -> Result<T, MethodError<DATA>> {
+        self.or_method_error(ERROR_CODE__RequestFailed)
+    }
+
+}
+
+/* ----------------- Message formatting ----------------- */
+
+/// Rewrites the `message` of the error responses written by a server, such as to localize or enrich
+/// them, keeping their codes. This covers the errors of this crate, of handlers, and of the 
+/// `jsonrpc` layer alike, by wrapping the `MessageWriter` of the endpoint:
+///
+/// ```ignore
+/// let formatter = ErrorMessageFormatter::new(|code, message| translate(code, message));
+/// let endpoint = LSPEndpoint::create_lsp_output(move || formatter.writer(LSPMessageWriter(io::stdout())));
+/// ```
+#[derive(Clone)]
+pub struct ErrorMessageFormatter {
+    format: Arc<Fn(i64, &str) -> String + Send + Sync>,
+}
+
+impl ErrorMessageFormatter {
+    
+    /// Create a formatter with given function, called with the code and the original message
+    /// of each error.
+    pub fn new<FORMAT>(format: FORMAT) -> ErrorMessageFormatter
+    where
+        FORMAT : Fn(i64, &str) -> String + Send + Sync + 'static,
+    {
+        ErrorMessageFormatter { format : Arc::new(format) }
+    }
+    
+    pub fn format_message(&self, code: i64, message: &str) -> String {
+        (self.format)(code, message)
+    }
+    
+    pub fn format_error(&self, mut error: RequestError) -> RequestError {
+        error.message = self.format_message(error.code, &error.message);
+        error
+    }
+    
+    /// The given message with its error message formatted, if it is an error response.
+    pub fn format_response(&self, message: &str) -> Option<String> {
+        if !message.contains(r#""error""#) {
+            return None;
+        }
+        let mut response = match serde_json::from_str::<Value>(message) {
+            Ok(response) => response,
+            Err(_) => return None,
+        };
+        if response.find("method").is_some() {
+            return None;
+        }
+        {
+            let error = match response.pointer_mut("/error").and_then(Value::as_object_mut) {
+                Some(error) => error,
+                None => return None,
+            };
+            let code = match error.get("code").and_then(Value::as_i64) {
+                Some(code) => code,
+                None => return None,
+            };
+            let formatted = match error.get("message").and_then(Value::as_str) {
+                Some(message) => self.format_message(code, message),
+                None => return None,
+            };
+            error.insert("message".to_string(), Value::String(formatted));
+        }
+        Some(response.to_string())
+    }
+    
+    /// Wrap given writer, to format the error responses it writes.
+    pub fn writer<MW : MessageWriter>(&self, msg_writer: MW) -> ErrorFormattingMessageWriter<MW> {
+        ErrorFormattingMessageWriter { msg_writer : msg_writer, formatter : self.clone() }
+    }
+    
+}
+
+pub struct ErrorFormattingMessageWriter<MW : MessageWriter> {
+    msg_writer: MW,
+    formatter: ErrorMessageFormatter,
+}
+
+impl<MW : MessageWriter> MessageWriter for ErrorFormattingMessageWriter<MW> {
+    fn write_message(&mut self, msg: &str) -> GResult<()> {
+        match self.formatter.format_response(msg) {
+            Some(formatted) => self.msg_writer.write_message(&formatted),
+            None => self.msg_writer.write_message(msg),
+        }
+    }
+}
+
+
+#[test]
+fn ls_error__test() {
+    let error = LSError::from(io::Error::new(io::ErrorKind::BrokenPipe, "pipe"));
+    assert!(error.source().is_some());
+    assert_eq!(error.to_string(), "Transport error: pipe");
+    
+    // Round-trip through GError
+    let gerror : GError = LSError::Shutdown("End of stream reached.".to_string()).into();
+    assert!(LSError::from_gerror(gerror).is_shutdown());
+    assert!(!error.is_recoverable());
+    assert!(LSError::Protocol("Content-Length: not defined or invalid.".to_string()).is_recoverable());
+    let gerror : GError = io::Error::new(io::ErrorKind::Other, "io").into();
+    match LSError::from_gerror(gerror) {
+        LSError::Transport(_) => {}
+        error => panic!("Unexpected: {:?}", error),
+    }
+}
+
+#[test]
+fn lsp_errors__test() {
+    assert_eq!(error_LSP_ServerNotInitialized().code, -32002);
+    assert_eq!(error_LSP_RequestCancelled().code, -32800);
+    assert_eq!(error_LSP_ContentModified().code, -32801);
+    assert_eq!(error_LSP_RequestFailed("Failed.".to_string()).message, "Failed.");
+    assert_eq!(method_error_RequestFailed::<()>("Failed.".to_string()).code, -32803);
+}
+
+#[test]
+fn service_error__test() {
+    fn read_settings(json: &str) -> Result<Value, ServiceError> {
+        Ok(try!(serde_json::from_str(json)))
+    }
+    let error = read_settings("{").unwrap_err();
+    assert_eq!(error.code, ERROR_CODE__InvalidParams);
+    assert!(error.message.starts_with("Invalid params: "));
+
+    let error : ServiceError = io::Error::new(io::ErrorKind::NotFound, "not found").into();
+    assert_eq!(error, ServiceError::internal("not found"));
+    assert_eq!(error.to_string(), "not found (-32603)");
+    assert_eq!(ServiceError::request_cancelled().code, -32800);
+    assert_eq!(ServiceError::content_modified().code, -32801);
+
+    let error = ServiceError::request_failed("Failed.").with_data(Value::Bool(true));
+    let method_error : MethodError<Option<Value>> = error.clone().into();
+    assert_eq!((method_error.code, &method_error.message[..]), (-32803, "Failed."));
+    assert_eq!(method_error.data, Some(Value::Bool(true)));
+    let method_error : MethodError<u64> = error.into();
+    assert_eq!(method_error.data, 0);
+}
+
+#[test]
+fn ls_result_ext__test() {
+    fn parse_line(text: &str) -> Result<u64, MethodError<()>> {
+        let line = try!(text.parse::<u64>().or_method_error(ERROR_CODE__InvalidParams));
+        try!(if line > 0 { Ok(()) } else { Err("Lines start at 1.") }.or_request_failed());
+        Ok(line - 1)
+    }
+    assert_eq!(parse_line("3").ok(), Some(2));
+    let error = parse_line("x").unwrap_err();
+    assert_eq!((error.code, &error.message[..]), (-32602, "invalid digit found in string"));
+    let error = parse_line("0").unwrap_err();
+    assert_eq!((error.code, &error.message[..]), (-32803, "Lines start at 1."));
+}
+
+#[test]
+fn error_message_formatter__test() {
+    let formatter = ErrorMessageFormatter::new(|code, message| format!("[{}] {}", code, message));
+    
+    let error = formatter.format_error(error_LSP_ServerNotInitialized());
+    assert_eq!((error.code, &error.message[..]), (-32002, "[-32002] Server not initialized."));
+    
+    let response = r#"{"jsonrpc":"2.0","id":1,"error":{"code":-32601,"message":"Method not found."}}"#;
+    let formatted = formatter.format_response(response).unwrap();
+    assert_eq!(formatted, r#"{"error":{"code":-32601,"message":"[-32601] Method not found."},"id":1,"jsonrpc":"2.0"}"#);
+    
+    assert_eq!(formatter.format_response(r#"{"jsonrpc":"2.0","id":1,"result":"error"}"#), None);
+    assert_eq!(formatter.format_response(r#"{"jsonrpc":"2.0","method":"m","params":{"error":{}}}"#), None);
+}
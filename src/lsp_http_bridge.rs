@@ -0,0 +1,341 @@
+// Copyright 2016 Bruno Medeiros
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>.
+// This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! A HTTP bridge for web clients, such as browser-hosted editors: the client sends its messages
+//! with `POST /message`, and receives the messages of the server as server-sent events from
+//! `GET /events`, or by long-polling `GET /poll`. This needs no WebSocket infrastructure.
+//!
+//! The bridge serves a single session, and is a minimal HTTP/1.1 implementation, meant to be
+//! used behind a proper HTTP server in production.
+
+use std::collections::VecDeque;
+use std::io;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Read;
+use std::io::Write;
+use std::net::SocketAddr;
+use std::net::TcpListener;
+use std::net::TcpStream;
+use std::net::ToSocketAddrs;
+use std::sync::Arc;
+use std::sync::Condvar;
+use std::sync::Mutex;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use util::core::*;
+
+use jsonrpc::*;
+
+use lsp::*;
+use lsp_errors::LSError;
+
+/// How long `GET /poll` waits for a message, when there is none.
+pub const POLL_TIMEOUT_SECS: u64 = 20;
+
+/* ----------------- Output queue ----------------- */
+
+#[derive(Debug, Default)]
+struct OutputQueueData {
+    messages: VecDeque<String>,
+    closed: bool,
+}
+
+/// The messages of the server, waiting to be taken by the client.
+#[derive(Debug, Clone, Default)]
+struct OutputQueue {
+    data: Arc<(Mutex<OutputQueueData>, Condvar)>,
+}
+
+impl OutputQueue {
+
+    fn with<RET, F : FnOnce(&mut OutputQueueData) -> RET>(&self, f: F) -> RET {
+        let &(ref mutex, ref condvar) = &*self.data;
+        let result = match mutex.lock() {
+            Ok(mut data) => f(&mut data),
+            Err(poisoned) => f(&mut poisoned.into_inner()),
+        };
+        condvar.notify_all();
+        result
+    }
+
+    fn push(&self, message: String) {
+        self.with(|data| data.messages.push_back(message));
+    }
+
+    fn close(&self) {
+        self.with(|data| data.closed = true);
+    }
+
+    /// Take the pending messages, waiting for at least one up to given timeout.
+    /// Returns `None` if the queue is closed.
+    fn take(&self, timeout: Option<Duration>) -> Option<Vec<String>> {
+        let &(ref mutex, ref condvar) = &*self.data;
+        let mut data = match mutex.lock() {
+            Ok(data) => data,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        while data.messages.is_empty() && !data.closed {
+            data = match timeout {
+                Some(timeout) => {
+                    match condvar.wait_timeout(data, timeout) {
+                        Ok((data, result)) => {
+                            if result.timed_out() {
+                                return Some(vec![]);
+                            }
+                            data
+                        }
+                        Err(poisoned) => poisoned.into_inner().0,
+                    }
+                }
+                None => {
+                    match condvar.wait(data) {
+                        Ok(data) => data,
+                        Err(poisoned) => poisoned.into_inner(),
+                    }
+                }
+            };
+        }
+        if data.messages.is_empty() {
+            return None;
+        }
+        Some(data.messages.drain(..).collect())
+    }
+
+}
+
+struct QueueMessageWriter(OutputQueue);
+
+impl MessageWriter for QueueMessageWriter {
+    fn write_message(&mut self, msg: &str) -> GResult<()> {
+        self.0.push(msg.to_string());
+        Ok(())
+    }
+}
+
+struct ChannelMessageReader(mpsc::Receiver<String>);
+
+impl MessageReader for ChannelMessageReader {
+    fn read_next(&mut self) -> GResult<String> {
+        self.0.recv().map_err(|_| LSError::Shutdown("End of stream reached.".to_string()).into())
+    }
+}
+
+/* ----------------- HTTP ----------------- */
+
+struct HttpRequest {
+    method: String,
+    path: String,
+    body: Vec<u8>,
+}
+
+fn read_http_request(stream: &TcpStream) -> GResult<HttpRequest> {
+    let mut reader = BufReader::new(stream);
+
+    let mut request_line = String::new();
+    try!(reader.read_line(&mut request_line));
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or("").to_string();
+    let path = parts.next().unwrap_or("").to_string();
+
+    let mut content_length = 0;
+    loop {
+        let mut line = String::new();
+        if try!(reader.read_line(&mut line)) == 0 {
+            return Err("End of stream reached in HTTP headers.".into());
+        }
+        let line = line.trim_right();
+        if line.is_empty() {
+            break;
+        }
+        let mut header = line.splitn(2, ':');
+        let name = header.next().unwrap_or("");
+        let value = header.next().unwrap_or("").trim();
+        if name.eq_ignore_ascii_case("Content-Length") {
+            content_length = try!(value.parse::<u32>());
+        }
+    }
+
+    let mut body = vec![0; content_length as usize];
+    try!(reader.read_exact(&mut body));
+    Ok(HttpRequest { method : method, path : path, body : body })
+}
+
+fn write_http_response(stream: &mut TcpStream, status: &str, content_type: &str, body: &[u8]) -> io::Result<()> {
+    try!(write!(stream, "HTTP/1.1 {}\r\n", status));
+    try!(write!(stream, "Access-Control-Allow-Origin: *\r\n"));
+    try!(write!(stream, "Access-Control-Allow-Methods: GET, POST, OPTIONS\r\n"));
+    try!(write!(stream, "Access-Control-Allow-Headers: Content-Type\r\n"));
+    try!(write!(stream, "Content-Type: {}\r\n", content_type));
+    try!(write!(stream, "Content-Length: {}\r\nConnection: close\r\n\r\n", body.len()));
+    try!(stream.write_all(body));
+    stream.flush()
+}
+
+/// Write the messages of the queue as server-sent events, until it is closed.
+fn write_events(stream: &mut TcpStream, output: &OutputQueue) -> io::Result<()> {
+    try!(write!(stream, "HTTP/1.1 200 OK\r\n"));
+    try!(write!(stream, "Access-Control-Allow-Origin: *\r\n"));
+    try!(write!(stream, "Content-Type: text/event-stream\r\nCache-Control: no-cache\r\n\r\n"));
+    try!(stream.flush());
+
+    while let Some(messages) = output.take(None) {
+        for message in messages {
+            for line in message.lines() {
+                try!(write!(stream, "data: {}\n", line));
+            }
+            try!(write!(stream, "\n"));
+        }
+        try!(stream.flush());
+    }
+    Ok(())
+}
+
+fn handle_connection(mut stream: TcpStream, input: mpsc::Sender<String>, output: OutputQueue) -> GResult<()> {
+    let request = try!(read_http_request(&stream));
+
+    match (&request.method[..], &request.path[..]) {
+        ("POST", "/message") => {
+            let message = try!(String::from_utf8(request.body));
+            if input.send(message).is_err() {
+                try!(write_http_response(&mut stream, "410 Gone", "text/plain", b"Server has exited."));
+            } else {
+                try!(write_http_response(&mut stream, "202 Accepted", "text/plain", b""));
+            }
+        }
+        ("GET", "/events") => {
+            try!(write_events(&mut stream, &output));
+        }
+        ("GET", "/poll") => {
+            match output.take(Some(Duration::from_secs(POLL_TIMEOUT_SECS))) {
+                Some(messages) => {
+                    let body = format!("[{}]", messages.join(","));
+                    try!(write_http_response(&mut stream, "200 OK", "application/json", body.as_bytes()));
+                }
+                None => try!(write_http_response(&mut stream, "410 Gone", "text/plain", b"Server has exited.")),
+            }
+        }
+        ("OPTIONS", _) => {
+            try!(write_http_response(&mut stream, "204 No Content", "text/plain", b""));
+        }
+        _ => {
+            try!(write_http_response(&mut stream, "404 Not Found", "text/plain", b""));
+        }
+    }
+    Ok(())
+}
+
+/* ----------------- Bridge ----------------- */
+
+/// A HTTP bridge, serving a single session of a server.
+pub struct HttpBridge {
+    listener: TcpListener,
+}
+
+impl HttpBridge {
+
+    pub fn bind<A : ToSocketAddrs>(address: A) -> io::Result<HttpBridge> {
+        Ok(HttpBridge { listener : try!(TcpListener::bind(address)) })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Run the server created by `create_server` on the current thread, with the messages
+    /// of the HTTP clients, until it exits. Returns the exit code, as `LSPEndpoint::run_server`.
+    pub fn run_server<SERVER, CREATE>(self, create_server: CREATE) -> i32
+    where
+        SERVER : LanguageServerHandling + 'static,
+        CREATE : FnOnce(LSServerContext) -> SERVER,
+    {
+        let local_addr = self.listener.local_addr();
+        let (input_sender, input_receiver) = mpsc::channel();
+        let output = OutputQueue::default();
+        let stopped = Arc::new(AtomicBool::new(false));
+
+        let listener = self.listener;
+        let accept_output = output.clone();
+        let accept_stopped = stopped.clone();
+        let accept_thread = thread::spawn(move || {
+            for stream in listener.incoming() {
+                if accept_stopped.load(Ordering::SeqCst) {
+                    break;
+                }
+                let stream = match stream {
+                    Ok(stream) => stream,
+                    Err(error) => {
+                        warn!("HTTP bridge failed to accept connection: {}", error);
+                        continue;
+                    }
+                };
+                let input = input_sender.clone();
+                let output = accept_output.clone();
+                thread::spawn(move || {
+                    if let Err(error) = handle_connection(stream, input, output) {
+                        warn!("HTTP bridge request failed: {}", error);
+                    }
+                });
+            }
+        });
+
+        let writer_output = output.clone();
+        let endpoint = LSPEndpoint::create_lsp_output(move || QueueMessageWriter(writer_output));
+        let context = LSServerContext::new(endpoint);
+        let server = create_server(context.clone());
+        let exit_code = LSPEndpoint::run_server(&mut ChannelMessageReader(input_receiver), context, server);
+
+        output.close();
+        stopped.store(true, Ordering::SeqCst);
+        // Wake up the accept loop
+        if let Ok(local_addr) = local_addr {
+            let _ = TcpStream::connect(local_addr);
+        }
+        let _ = accept_thread.join();
+        exit_code
+    }
+
+}
+
+
+#[test]
+fn http_bridge__test() {
+    struct DefaultServer;
+    impl LanguageServerHandling for DefaultServer {}
+
+    fn post(address: SocketAddr, message: &str) -> String {
+        let mut stream = TcpStream::connect(address).unwrap();
+        write!(stream, "POST /message HTTP/1.1\r\nContent-Length: {}\r\n\r\n{}", message.len(), message).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    let bridge = HttpBridge::bind("127.0.0.1:0").unwrap();
+    let address = bridge.local_addr().unwrap();
+    let server_thread = thread::spawn(move || bridge.run_server(|_| DefaultServer));
+
+    let mut events = BufReader::new(TcpStream::connect(address).unwrap());
+    write!(events.get_mut(), "GET /events HTTP/1.1\r\n\r\n").unwrap();
+
+    let response = post(address, r#"{ "jsonrpc": "2.0", "id": 1, "method": "shutdown" }"#);
+    assert!(response.starts_with("HTTP/1.1 202"));
+
+    let mut event = String::new();
+    while !event.starts_with("data:") {
+        event.clear();
+        events.read_line(&mut event).unwrap();
+    }
+    assert!(event.contains(r#""id":1"#));
+
+    post(address, r#"{ "jsonrpc": "2.0", "method": "exit" }"#);
+    assert_eq!(server_thread.join().unwrap(), 0);
+}
@@ -0,0 +1,284 @@
+// Copyright 2016 Bruno Medeiros
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>.
+// This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! The result of `initialize`, including the fields newer than `ls_types::InitializeResult`,
+//! and a builder for it.
+
+use serde;
+use serde::de::Error as DeError;
+use serde_json;
+use serde_json::Value;
+
+use jsonrpc::json_util::JsonObject;
+use ls_types::*;
+
+use lsp_capabilities::PositionEncoding;
+
+/* ----------------- InitializeResult ----------------- */
+
+/// Information about the server, sent to the client in the `initialize` result.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServerInfo {
+    pub name: String,
+    pub version: Option<String>,
+}
+
+/// The result of `initialize`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LSInitializeResult {
+    pub capabilities: ServerCapabilities,
+    /// The position encoding of the server, sent as `capabilities.positionEncoding`.
+    /// If not set, the client uses UTF-16.
+    pub position_encoding: Option<PositionEncoding>,
+    pub server_info: Option<ServerInfo>,
+}
+
+impl From<InitializeResult> for LSInitializeResult {
+    fn from(result: InitializeResult) -> LSInitializeResult {
+        LSInitializeResult { capabilities : result.capabilities, position_encoding : None, server_info : None }
+    }
+}
+
+impl LSInitializeResult {
+
+    pub fn to_json(&self) -> Value {
+        let mut obj = JsonObject::new();
+        let mut capabilities = serde_json::to_value(&self.capabilities);
+        if let Some(encoding) = self.position_encoding {
+            if let Value::Object(ref mut capabilities_obj) = capabilities {
+                capabilities_obj.insert("positionEncoding".to_string(), Value::String(encoding.as_str().to_string()));
+            }
+        }
+        obj.insert("capabilities".to_string(), capabilities);
+        if let Some(ref server_info) = self.server_info {
+            let mut server_info_obj = JsonObject::new();
+            server_info_obj.insert("name".to_string(), Value::String(server_info.name.clone()));
+            if let Some(ref version) = server_info.version {
+                server_info_obj.insert("version".to_string(), Value::String(version.clone()));
+            }
+            obj.insert("serverInfo".to_string(), Value::Object(server_info_obj));
+        }
+        Value::Object(obj)
+    }
+
+    pub fn from_json(value: Value) -> Result<LSInitializeResult, String> {
+        let mut obj = match value {
+            Value::Object(obj) => obj,
+            _ => return Err("InitializeResult is not an object.".to_string()),
+        };
+
+        let (capabilities, position_encoding) = match obj.remove("capabilities") {
+            Some(capabilities) => {
+                let position_encoding = match capabilities.find("positionEncoding") {
+                    Some(&Value::String(ref encoding)) => match PositionEncoding::from_str(encoding) {
+                        Some(encoding) => Some(encoding),
+                        None => return Err(format!("Unknown position encoding: {}", encoding)),
+                    },
+                    _ => None,
+                };
+                let capabilities = try!(serde_json::from_value(capabilities).map_err(|err| err.to_string()));
+                (capabilities, position_encoding)
+            }
+            None => return Err("InitializeResult: missing `capabilities`.".to_string()),
+        };
+        let server_info = match obj.remove("serverInfo") {
+            Some(Value::Object(server_info)) => {
+                let name = match server_info.get("name") {
+                    Some(&Value::String(ref name)) => name.clone(),
+                    _ => return Err("ServerInfo: missing `name`.".to_string()),
+                };
+                let version = server_info.get("version").and_then(|version| version.as_str()).map(String::from);
+                Some(ServerInfo { name : name, version : version })
+            }
+            Some(Value::Null) | None => None,
+            Some(_) => return Err("ServerInfo is not an object.".to_string()),
+        };
+        Ok(LSInitializeResult {
+            capabilities : capabilities, position_encoding : position_encoding, server_info : server_info,
+        })
+    }
+
+}
+
+impl serde::Serialize for LSInitializeResult {
+    fn serialize<S>(&self, serializer: &mut S) -> Result<(), S::Error>
+    where
+        S : serde::Serializer,
+    {
+        self.to_json().serialize(serializer)
+    }
+}
+
+impl serde::Deserialize for LSInitializeResult {
+    fn deserialize<D>(deserializer: &mut D) -> Result<LSInitializeResult, D::Error>
+    where
+        D : serde::Deserializer,
+    {
+        let value = try!(Value::deserialize(deserializer));
+        LSInitializeResult::from_json(value).map_err(D::Error::custom)
+    }
+}
+
+/* ----------------- Builder ----------------- */
+
+/// Builder for `LSInitializeResult`.
+#[derive(Debug, Clone, Default)]
+pub struct InitializeResultBuilder {
+    capabilities: ServerCapabilities,
+    position_encoding: Option<PositionEncoding>,
+    server_info: Option<ServerInfo>,
+}
+
+impl InitializeResultBuilder {
+
+    pub fn new() -> InitializeResultBuilder {
+        InitializeResultBuilder::default()
+    }
+
+    pub fn server_info(mut self, name: &str, version: Option<&str>) -> Self {
+        self.server_info = Some(ServerInfo { name : name.to_string(), version : version.map(String::from) });
+        self
+    }
+
+    /// Start from given capabilities, replacing any set so far.
+    pub fn capabilities(mut self, capabilities: ServerCapabilities) -> Self {
+        self.capabilities = capabilities;
+        self
+    }
+
+    /// Modify the capabilities with given function.
+    pub fn with_capabilities<FN>(mut self, modify: FN) -> Self
+    where
+        FN : FnOnce(&mut ServerCapabilities),
+    {
+        modify(&mut self.capabilities);
+        self
+    }
+
+    /// The position encoding of the server, which must be one the client supports, such as the one
+    /// chosen by `LSClientCapabilities::position_encoding`. The same encoding must then be used
+    /// to read positions, such as with `TextDocuments::negotiated`.
+    pub fn position_encoding(mut self, encoding: PositionEncoding) -> Self {
+        self.position_encoding = Some(encoding);
+        self
+    }
+
+    pub fn text_document_sync(mut self, sync_kind: TextDocumentSyncKind) -> Self {
+        self.capabilities.text_document_sync = Some(sync_kind);
+        self
+    }
+
+    pub fn hover(mut self) -> Self {
+        self.capabilities.hover_provider = Some(true);
+        self
+    }
+
+    pub fn completion(mut self, options: CompletionOptions) -> Self {
+        self.capabilities.completion_provider = Some(options);
+        self
+    }
+
+    pub fn signature_help(mut self, options: SignatureHelpOptions) -> Self {
+        self.capabilities.signature_help_provider = Some(options);
+        self
+    }
+
+    pub fn definition(mut self) -> Self {
+        self.capabilities.definition_provider = Some(true);
+        self
+    }
+
+    pub fn references(mut self) -> Self {
+        self.capabilities.references_provider = Some(true);
+        self
+    }
+
+    pub fn document_highlight(mut self) -> Self {
+        self.capabilities.document_highlight_provider = Some(true);
+        self
+    }
+
+    pub fn document_symbol(mut self) -> Self {
+        self.capabilities.document_symbol_provider = Some(true);
+        self
+    }
+
+    pub fn workspace_symbol(mut self) -> Self {
+        self.capabilities.workspace_symbol_provider = Some(true);
+        self
+    }
+
+    pub fn code_action(mut self) -> Self {
+        self.capabilities.code_action_provider = Some(true);
+        self
+    }
+
+    pub fn code_lens(mut self, options: CodeLensOptions) -> Self {
+        self.capabilities.code_lens_provider = Some(options);
+        self
+    }
+
+    pub fn formatting(mut self) -> Self {
+        self.capabilities.document_formatting_provider = Some(true);
+        self
+    }
+
+    pub fn range_formatting(mut self) -> Self {
+        self.capabilities.document_range_formatting_provider = Some(true);
+        self
+    }
+
+    pub fn on_type_formatting(mut self, options: DocumentOnTypeFormattingOptions) -> Self {
+        self.capabilities.document_on_type_formatting_provider = Some(options);
+        self
+    }
+
+    pub fn rename(mut self) -> Self {
+        self.capabilities.rename_provider = Some(true);
+        self
+    }
+
+    pub fn build(self) -> LSInitializeResult {
+        LSInitializeResult {
+            capabilities : self.capabilities,
+            position_encoding : self.position_encoding,
+            server_info : self.server_info,
+        }
+    }
+
+}
+
+
+#[test]
+fn initialize_result__test() {
+    let result = InitializeResultBuilder::new()
+        .server_info("test_server", Some("1.0"))
+        .text_document_sync(TextDocumentSyncKind::Incremental)
+        .hover()
+        .definition()
+        .build();
+
+    assert_eq!(result.capabilities.hover_provider, Some(true));
+    assert_eq!(result.capabilities.references_provider, None);
+
+    let json = result.to_json();
+    assert_eq!(json.lookup("serverInfo.name"), Some(&Value::String("test_server".to_string())));
+    assert_eq!(json.lookup("serverInfo.version"), Some(&Value::String("1.0".to_string())));
+    assert_eq!(json.lookup("capabilities.hoverProvider"), Some(&Value::Bool(true)));
+
+    let result2 : LSInitializeResult = serde_json::from_str(&serde_json::to_string(&result).unwrap()).unwrap();
+    assert_eq!(result2, result);
+
+    let result3 = LSInitializeResult::from_json(serde_json::from_str(r#"{ "capabilities": {} }"#).unwrap()).unwrap();
+    assert_eq!(result3.server_info, None);
+    assert_eq!(result3.position_encoding, None);
+
+    let result = InitializeResultBuilder::new().position_encoding(PositionEncoding::UTF8).build();
+    let json = result.to_json();
+    assert_eq!(json.lookup("capabilities.positionEncoding"), Some(&Value::String("utf-8".to_string())));
+    assert_eq!(LSInitializeResult::from_json(json).unwrap(), result);
+}
@@ -0,0 +1,294 @@
+// Copyright 2016 Bruno Medeiros
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>.
+// This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Inline execution of a server: each incoming message is handled on the caller's thread,
+//! and all the output it causes is written before the next message is read.
+//! This makes the output ordering deterministic, which is useful for tests and transcripts.
+//!
+//! The `OutputAgent` of the `jsonrpc` crate always writes on a thread of its own. Here it is only
+//! used as a queue that is drained after each message, so no two messages are ever being handled
+//! or written concurrently. Handlers that complete their requests on other threads are still
+//! supported, but the ordering of that output is not deterministic.
+//!
+//! The way the output is run can also be selected with an `OutputRunner`, whose type documents
+//! the ordering guarantee of the output:
+//!
+//! ```ignore
+//! let exit_code = InlineOutput.run_server(&mut msg_reader, msg_writer, |context| MyServer::new(context));
+//! ```
+
+use std::io;
+use std::sync::Arc;
+use std::sync::Condvar;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use util::core::*;
+
+use jsonrpc::*;
+use serde_json;
+use serde_json::Value;
+
+use lsp::*;
+use lsp_transport::LSPMessageReader;
+use lsp_transport::LSPMessageWriter;
+
+/// A notification that marks the end of the output of an incoming message. It is never written out.
+const SEQUENCE_MARKER: &'static str = "$/rustlsp/sequenceMarker";
+
+#[derive(Debug, Default)]
+struct OutputState {
+    /// The last sequence marker that was reached by the output.
+    sequence: u64,
+    failed: bool,
+}
+
+type SharedOutputState = Arc<(Mutex<OutputState>, Condvar)>;
+
+fn update_output_state<F : FnOnce(&mut OutputState)>(state: &SharedOutputState, update: F) {
+    let &(ref mutex, ref condvar) = &**state;
+    match mutex.lock() {
+        Ok(mut guard) => update(&mut guard),
+        Err(poisoned) => update(&mut poisoned.into_inner()),
+    }
+    condvar.notify_all();
+}
+
+/// A message writer that records the sequence markers instead of writing them.
+struct InlineMessageWriter<MW> {
+    writer: MW,
+    state: SharedOutputState,
+}
+
+impl<MW : MessageWriter> MessageWriter for InlineMessageWriter<MW> {
+    fn write_message(&mut self, msg: &str) -> GResult<()> {
+        if msg.contains(SEQUENCE_MARKER) {
+            if let Ok(message) = serde_json::from_str::<Value>(msg) {
+                if message.find("method").and_then(Value::as_str) == Some(SEQUENCE_MARKER) {
+                    let sequence = message.find("params").and_then(Value::as_u64).unwrap_or(0);
+                    update_output_state(&self.state, |state| state.sequence = sequence);
+                    return Ok(());
+                }
+            }
+        }
+        let result = self.writer.write_message(msg);
+        if result.is_err() {
+            update_output_state(&self.state, |state| state.failed = true);
+        }
+        result
+    }
+}
+
+/// A message reader that, before reading a message, waits until the output
+/// of the previous message has been written.
+struct InlineMessageReader<MR> {
+    reader: MR,
+    endpoint: Endpoint,
+    state: SharedOutputState,
+    next_sequence: u64,
+}
+
+impl<MR> InlineMessageReader<MR> {
+
+    fn wait_for_output(&mut self) {
+        if self.endpoint.is_shutdown() {
+            return;
+        }
+        self.next_sequence += 1;
+        let sequence = self.next_sequence;
+        if self.endpoint.send_notification(SEQUENCE_MARKER, Value::U64(sequence)).is_err() {
+            return;
+        }
+
+        let &(ref mutex, ref condvar) = &*self.state;
+        let mut guard = match mutex.lock() {
+            Ok(guard) => guard,
+            Err(_) => return,
+        };
+        while guard.sequence < sequence && !guard.failed {
+            // Check the shutdown periodically, in case the output agent was stopped
+            guard = match condvar.wait_timeout(guard, Duration::from_millis(100)) {
+                Ok((guard, _)) => guard,
+                Err(_) => return,
+            };
+            if self.endpoint.is_shutdown() {
+                return;
+            }
+        }
+    }
+
+}
+
+impl<'a, MR : MessageReader> MessageReader for InlineMessageReader<&'a mut MR> {
+    fn read_next(&mut self) -> GResult<String> {
+        self.wait_for_output();
+        self.reader.read_next()
+    }
+}
+
+/// Run the server created by `create_server` inline on the current thread,
+/// reading messages from `input` and writing to `output`.
+/// Returns the exit code, as `LSPEndpoint::run_server`.
+pub fn run_server_inline<SERVER, CREATE, OUT>(input: &mut io::BufRead, output: OUT, create_server: CREATE) -> i32
+where
+    SERVER : LanguageServerHandling + 'static,
+    CREATE : FnOnce(LSServerContext) -> SERVER,
+    OUT : io::Write + Send + 'static,
+{
+    run_server_inline_with(&mut LSPMessageReader(input), LSPMessageWriter(output), create_server)
+}
+
+/// Run the server created by `create_server` inline on the current thread,
+/// with given message reader and writer.
+pub fn run_server_inline_with<SERVER, CREATE, MR, MW>(msg_reader: &mut MR, msg_writer: MW, create_server: CREATE)
+    -> i32
+where
+    SERVER : LanguageServerHandling + 'static,
+    CREATE : FnOnce(LSServerContext) -> SERVER,
+    MR : MessageReader,
+    MW : MessageWriter + Send + 'static,
+{
+    let state : SharedOutputState = Arc::new((Mutex::new(OutputState::default()), Condvar::new()));
+
+    let writer_state = state.clone();
+    let endpoint = LSPEndpoint::create_lsp_output(move || {
+        InlineMessageWriter { writer : msg_writer, state : writer_state }
+    });
+    let context = LSServerContext::new(endpoint.clone());
+    let server = create_server(context.clone());
+
+    let mut reader = InlineMessageReader { reader : msg_reader, endpoint : endpoint, state : state, next_sequence : 0 };
+    LSPEndpoint::run_server(&mut reader, context, server)
+}
+
+/// Runs the output of a server, and so decides the order in which it is written
+/// relative to the handling of the incoming messages.
+pub trait OutputRunner {
+
+    /// Run the server created by `create_server`, with given message reader and writer.
+    /// Returns the exit code, as `LSPEndpoint::run_server`.
+    fn run_server<SERVER, CREATE, MR, MW>(self, msg_reader: &mut MR, msg_writer: MW, create_server: CREATE) -> i32
+    where
+        SERVER : LanguageServerHandling + 'static,
+        CREATE : FnOnce(LSServerContext) -> SERVER,
+        MR : MessageReader,
+        MW : MessageWriter + Send + 'static;
+
+}
+
+/// The output is written by a dedicated thread, as with `LSPEndpoint::create_lsp_output`.
+///
+/// Ordering: messages are written in the order they are submitted, but the read loop doesn't wait
+/// for them, so the output of a message may be written after the following messages are handled.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ThreadOutput;
+
+impl OutputRunner for ThreadOutput {
+    fn run_server<SERVER, CREATE, MR, MW>(self, msg_reader: &mut MR, msg_writer: MW, create_server: CREATE) -> i32
+    where
+        SERVER : LanguageServerHandling + 'static,
+        CREATE : FnOnce(LSServerContext) -> SERVER,
+        MR : MessageReader,
+        MW : MessageWriter + Send + 'static,
+    {
+        let endpoint = LSPEndpoint::create_lsp_output(move || msg_writer);
+        let context = LSServerContext::new(endpoint);
+        let server = create_server(context.clone());
+        LSPEndpoint::run_server(msg_reader, context, server)
+    }
+}
+
+/// The output is written inline with the read loop, as with `run_server_inline_with`.
+///
+/// Ordering: all the output submitted while handling a message, on the thread of the read loop,
+/// is written before the next message is read. Output submitted from other threads is written
+/// in the order it is submitted, but at no determined point of the read loop.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct InlineOutput;
+
+impl OutputRunner for InlineOutput {
+    fn run_server<SERVER, CREATE, MR, MW>(self, msg_reader: &mut MR, msg_writer: MW, create_server: CREATE) -> i32
+    where
+        SERVER : LanguageServerHandling + 'static,
+        CREATE : FnOnce(LSServerContext) -> SERVER,
+        MR : MessageReader,
+        MW : MessageWriter + Send + 'static,
+    {
+        run_server_inline_with(msg_reader, msg_writer, create_server)
+    }
+}
+
+
+#[cfg(test)]
+struct OpenNotifyingServer {
+    context: LSServerContext,
+}
+
+#[cfg(test)]
+impl LanguageServerHandling for OpenNotifyingServer {
+    fn did_open_text_document(&mut self, _: DidOpenTextDocumentParams) {
+        let mut endpoint = self.context.endpoint.clone();
+        for message in &["first", "second"] {
+            let params = LogMessageParams { typ : MessageType::Log, message : message.to_string() };
+            client_rpc_handle(&mut endpoint).log_message(params).unwrap();
+        }
+    }
+}
+
+#[cfg(test)]
+fn run_open_notifying_server<RUNNER : OutputRunner>(runner: RUNNER) -> (i32, Vec<Value>) {
+    use std::io::BufReader;
+    use std::io::Read;
+    use lsp_testing::pipe;
+    use lsp_transport::parse_transport_message;
+    use lsp_transport::write_transport_message;
+
+    let mut input = vec![];
+    for message in &[
+        r#"{ "jsonrpc": "2.0", "id": 1, "method": "initialize", "params": { "processId": null, "rootPath": null, "capabilities": {} } }"#,
+        r#"{ "jsonrpc": "2.0", "method": "textDocument/didOpen", "params": { "textDocument":
+            { "uri": "file:///doc.txt", "languageId": "text", "version": 1, "text": "" } } }"#,
+        r#"{ "jsonrpc": "2.0", "id": 2, "method": "shutdown" }"#,
+        r#"{ "jsonrpc": "2.0", "method": "exit" }"#,
+    ] {
+        write_transport_message(message, &mut input).unwrap();
+    }
+
+    let (writer, mut reader) = pipe();
+    let exit_code = runner.run_server(&mut LSPMessageReader(BufReader::new(&input[..])), LSPMessageWriter(writer),
+        |context| OpenNotifyingServer { context : context });
+
+    let mut output = String::new();
+    reader.read_to_string(&mut output).unwrap();
+    let mut output = output.as_bytes();
+    let mut messages = vec![];
+    while let Ok(message) = parse_transport_message(&mut output) {
+        messages.push(serde_json::from_str::<Value>(&message).unwrap());
+    }
+    (exit_code, messages)
+}
+
+#[test]
+fn run_server_inline__test() {
+    let (exit_code, messages) = run_open_notifying_server(InlineOutput);
+    assert_eq!(exit_code, 0);
+    let summary : Vec<String> = messages.iter().map(|message| {
+        match message.find("method") {
+            Some(method) => method.as_str().unwrap().to_string(),
+            None => message.find("id").unwrap().to_string(),
+        }
+    }).collect();
+    assert_eq!(summary, vec!["1", "window/logMessage", "window/logMessage", "2"]);
+    assert_eq!(messages[2].lookup("params.message"), Some(&Value::String("second".to_string())));
+}
+
+#[test]
+fn thread_output__test() {
+    let (exit_code, messages) = run_open_notifying_server(ThreadOutput);
+    assert_eq!(exit_code, 0);
+    assert_eq!(messages.len(), 4);
+}
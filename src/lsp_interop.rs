@@ -0,0 +1,110 @@
+// Copyright 2016 Bruno Medeiros
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>.
+// This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Interoperability with the `lsp-types` crate, enabled with the `lsp-types-interop` feature.
+//!
+//! `lsp-types` is built on serde 1, while `ls_types` (`languageserver-types` 0.6) is built on
+//! serde 0.8, so the values are converted through their JSON form, which is the same for both.
+//! This lets a server that already uses `lsp-types` for its data use this crate's endpoint,
+//! converting at the boundary:
+//!
+//! ```ignore
+//! fn hover(&mut self, params: TextDocumentPositionParams, completable: LSMethodCompletable<methods::HoverRequest>) {
+//!     let params : lsp_types::TextDocumentPositionParams = params.to_lsp_types().unwrap();
+//!     let hover = self.compute_hover(params);
+//!     completable.complete(Hover::from_lsp_types(&hover).map(LSHover::from).map_err(|error| error_RequestFailed(error)))
+//! }
+//! ```
+
+use serde;
+use serde1;
+use serde1_json;
+use serde_json;
+
+use ls_types;
+use lsp_types;
+
+use lsp_errors::LSError;
+
+/// Convert a `ls_types` value to the `lsp-types` value with the same JSON form.
+pub fn to_lsp_types<FROM, TO>(value: &FROM) -> Result<TO, LSError>
+where
+    FROM : serde::Serialize,
+    TO : serde1::de::DeserializeOwned,
+{
+    let json = try!(serde_json::to_string(value).map_err(|error| {
+        LSError::Parse("Failed to serialize value:".to_string(), Some(error.into()))
+    }));
+    serde1_json::from_str(&json).map_err(|error| {
+        LSError::Parse("Failed to convert value to `lsp-types`:".to_string(), Some(error.into()))
+    })
+}
+
+/// Convert a `lsp-types` value to the `ls_types` value with the same JSON form.
+pub fn from_lsp_types<FROM, TO>(value: &FROM) -> Result<TO, LSError>
+where
+    FROM : serde1::Serialize,
+    TO : serde::Deserialize,
+{
+    let json = try!(serde1_json::to_string(value).map_err(|error| {
+        LSError::Parse("Failed to serialize value:".to_string(), Some(error.into()))
+    }));
+    serde_json::from_str(&json).map_err(|error| {
+        LSError::Parse("Failed to convert value from `lsp-types`:".to_string(), Some(error.into()))
+    })
+}
+
+/// A `ls_types` type, and its counterpart in `lsp-types`.
+pub trait LspTypesCompat : serde::Serialize + serde::Deserialize {
+    type LspTypes : serde1::Serialize + serde1::de::DeserializeOwned;
+
+    fn to_lsp_types(&self) -> Result<Self::LspTypes, LSError> {
+        to_lsp_types(self)
+    }
+
+    fn from_lsp_types(value: &Self::LspTypes) -> Result<Self, LSError> {
+        from_lsp_types(value)
+    }
+}
+
+macro_rules! lsp_types_compat {
+    ( $( $name:ident ),* $(,)* ) => {
+        $(
+            impl LspTypesCompat for ls_types::$name {
+                type LspTypes = lsp_types::$name;
+            }
+        )*
+    };
+}
+
+lsp_types_compat!(
+    Position, Range, Location, Diagnostic, DiagnosticSeverity, Command, TextEdit,
+    TextDocumentIdentifier, VersionedTextDocumentIdentifier, TextDocumentItem, TextDocumentPositionParams,
+    InitializeParams, ClientCapabilities, ServerCapabilities, InitializeResult,
+    DidOpenTextDocumentParams, DidChangeTextDocumentParams, DidCloseTextDocumentParams, DidSaveTextDocumentParams,
+    DidChangeConfigurationParams, DidChangeWatchedFilesParams, PublishDiagnosticsParams,
+    ShowMessageParams, ShowMessageRequestParams, LogMessageParams, MessageType,
+    CompletionItem, CompletionItemKind, CompletionList, Hover, MarkedString, SignatureHelp,
+    ReferenceParams, DocumentHighlight, DocumentSymbolParams, SymbolInformation, SymbolKind,
+    WorkspaceSymbolParams, CodeActionParams, CodeLensParams, CodeLens, DocumentLinkParams, DocumentLink,
+    DocumentFormattingParams, DocumentRangeFormattingParams, DocumentOnTypeFormattingParams,
+    RenameParams, WorkspaceEdit,
+);
+
+
+#[test]
+fn lsp_types_interop__test() {
+    let position = ls_types::Position { line : 1, character : 2 };
+    let range = ls_types::Range { start : position.clone(), end : position.clone() };
+
+    let converted : lsp_types::Range = range.to_lsp_types().unwrap();
+    assert_eq!(converted.start, lsp_types::Position { line : 1, character : 2 });
+    assert_eq!(ls_types::Range::from_lsp_types(&converted).unwrap(), range);
+
+    let severity = ls_types::DiagnosticSeverity::Warning;
+    assert_eq!(severity.to_lsp_types().unwrap(), lsp_types::DiagnosticSeverity::WARNING);
+}
@@ -0,0 +1,163 @@
+// Copyright 2016 Bruno Medeiros
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>.
+// This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! More of `jsonrpc::json_util::JsonDeserializerHelper`, for the manual parsing of protocol objects:
+//! optional fields, unsigned numbers, typed arrays, and lookups of nested fields by a dotted path.
+//! The errors name the whole path of the field, so that they can be acted upon by the user:
+//!
+//! ```ignore
+//! let hover = try!(helper.obtain_path(&json, "capabilities.textDocument.hover"));
+//! // Error: missing field `capabilities.textDocument.hover`
+//! ```
+
+use serde;
+use serde_json;
+use serde_json::Value;
+
+use jsonrpc::json_util::JsonDeserializerHelper;
+use jsonrpc::json_util::JsonObject;
+
+pub trait JsonDeserializerHelperExt<ERR> : JsonDeserializerHelper<ERR> {
+
+    /// Remove the value of given key. A `null` value is the same as a missing one.
+    fn obtain_optional_Value(&mut self, json_map: &mut JsonObject, key: &str) -> Option<Value> {
+        match json_map.remove(key) {
+            None | Some(Value::Null) => None,
+            Some(value) => Some(value),
+        }
+    }
+
+    fn obtain_optional_String(&mut self, json_map: &mut JsonObject, key: &str) -> Result<Option<String>, ERR> {
+        match self.obtain_optional_Value(json_map, key) {
+            None => Ok(None),
+            Some(Value::String(string)) => Ok(Some(string)),
+            Some(_) => Err(self.new_error(&format!("field `{}` is not a string", key))),
+        }
+    }
+
+    fn obtain_u64(&mut self, json_map: &mut JsonObject, key: &str) -> Result<u64, ERR> {
+        let value = try!(self.obtain_Value(json_map, key));
+        match value.as_u64() {
+            Some(number) => Ok(number),
+            None => Err(self.new_error(&format!("field `{}` is not an unsigned integer", key))),
+        }
+    }
+
+    /// Remove the array of given key, and deserialize each of its elements.
+    /// A missing array is empty.
+    fn obtain_array_of<T : serde::Deserialize>(&mut self, json_map: &mut JsonObject, key: &str) -> Result<Vec<T>, ERR> {
+        let elements = match self.obtain_optional_Value(json_map, key) {
+            None => return Ok(vec![]),
+            Some(Value::Array(elements)) => elements,
+            Some(_) => return Err(self.new_error(&format!("field `{}` is not an array", key))),
+        };
+        let mut result = Vec::with_capacity(elements.len());
+        for (index, element) in elements.into_iter().enumerate() {
+            match serde_json::from_value(element) {
+                Ok(element) => result.push(element),
+                Err(error) => return Err(self.new_error(&format!("invalid `{}[{}]`: {}", key, index, error))),
+            }
+        }
+        Ok(result)
+    }
+
+    /// The value at given dotted path, such as `"capabilities.textDocument.hover"`, if any.
+    /// A `null` value is the same as a missing one.
+    fn find_optional_path<'a>(&mut self, json_map: &'a JsonObject, path: &str) -> Result<Option<&'a Value>, ERR> {
+        let mut segments = path.split('.');
+        let mut value = match segments.next().and_then(|key| json_map.get(key)) {
+            None => return Ok(None),
+            Some(value) => value,
+        };
+        let mut parent_len = 0;
+        for key in segments {
+            let parent = &path[..parent_len + value_key_len(path, parent_len)];
+            value = match *value {
+                Value::Null => return Ok(None),
+                Value::Object(ref obj) => match obj.get(key) {
+                    None => return Ok(None),
+                    Some(child) => child,
+                },
+                _ => return Err(self.new_error(&format!("field `{}` is not an object", parent))),
+            };
+            parent_len = parent.len() + 1;
+        }
+        Ok(if value.is_null() { None } else { Some(value) })
+    }
+
+    /// The value at given dotted path, which is an error if missing.
+    fn find_path<'a>(&mut self, json_map: &'a JsonObject, path: &str) -> Result<&'a Value, ERR> {
+        match try!(self.find_optional_path(json_map, path)) {
+            Some(value) => Ok(value),
+            None => Err(self.new_error(&format!("missing field `{}`", path))),
+        }
+    }
+
+    /// Deserialize the value at given dotted path, which is an error if missing.
+    fn obtain_path<T : serde::Deserialize>(&mut self, json_map: &JsonObject, path: &str) -> Result<T, ERR> {
+        let value = try!(self.find_path(json_map, path)).clone();
+        serde_json::from_value(value).map_err(|error| self.new_error(&format!("invalid `{}`: {}", path, error)))
+    }
+
+    /// Deserialize the value at given dotted path, if any.
+    fn obtain_optional_path<T : serde::Deserialize>(&mut self, json_map: &JsonObject, path: &str)
+        -> Result<Option<T>, ERR>
+    {
+        let value = match try!(self.find_optional_path(json_map, path)) {
+            None => return Ok(None),
+            Some(value) => value.clone(),
+        };
+        serde_json::from_value(value).map(Some)
+            .map_err(|error| self.new_error(&format!("invalid `{}`: {}", path, error)))
+    }
+
+}
+
+impl<ERR, HELPER : JsonDeserializerHelper<ERR> + ?Sized> JsonDeserializerHelperExt<ERR> for HELPER {}
+
+/// The length of the key of the path that starts at given index.
+fn value_key_len(path: &str, start: usize) -> usize {
+    path[start..].find('.').unwrap_or(path.len() - start)
+}
+
+
+#[cfg(test)]
+struct TestHelper;
+
+#[cfg(test)]
+impl JsonDeserializerHelper<String> for TestHelper {
+    fn new_error(&self, error_message: &str) -> String {
+        error_message.to_string()
+    }
+}
+
+#[test]
+fn json_deserializer_helper_ext__test() {
+    let helper = &mut TestHelper;
+    let json = r#"{
+        "name": "toy", "version": null, "id": 3, "offsets": [1, 2], "names": ["a", 1],
+        "capabilities": { "textDocument": { "hover": { "dynamicRegistration": true } }, "workspace": 1 }
+    }"#;
+    let mut obj : JsonObject = serde_json::from_str(json).unwrap();
+
+    assert_eq!(helper.obtain_optional_String(&mut obj, "name"), Ok(Some("toy".to_string())));
+    assert_eq!(helper.obtain_optional_String(&mut obj, "version"), Ok(None));
+    assert_eq!(helper.obtain_optional_String(&mut obj, "id"), Err("field `id` is not a string".to_string()));
+    obj.insert("id".to_string(), Value::U64(3));
+    assert_eq!(helper.obtain_u64(&mut obj, "id"), Ok(3));
+    assert_eq!(helper.obtain_array_of::<u64>(&mut obj, "offsets"), Ok(vec![1, 2]));
+    assert_eq!(helper.obtain_array_of::<u64>(&mut obj, "missing"), Ok(vec![]));
+    let error = helper.obtain_array_of::<String>(&mut obj, "names").unwrap_err();
+    assert!(error.starts_with("invalid `names[1]`: "));
+
+    assert_eq!(helper.obtain_path(&obj, "capabilities.textDocument.hover.dynamicRegistration"), Ok(true));
+    assert_eq!(helper.obtain_optional_path::<bool>(&obj, "capabilities.textDocument.completion"), Ok(None));
+    assert_eq!(helper.find_path(&obj, "capabilities.textDocument.completion"),
+        Err("missing field `capabilities.textDocument.completion`".to_string()));
+    assert_eq!(helper.find_path(&obj, "capabilities.workspace.symbol"),
+        Err("field `capabilities.workspace` is not an object".to_string()));
+}
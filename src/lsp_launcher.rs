@@ -0,0 +1,296 @@
+// Copyright 2016 Bruno Medeiros
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>.
+// This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Launching a server with the standard command line options editors pass to it:
+//! `--stdio`, `--socket=PORT`, `--pipe=NAME` and `--clientProcessId=PID`.
+//!
+//! ```ignore
+//! fn main() {
+//!     let launcher = ServerLauncher::from_args().unwrap_or_else(|error| {
+//!         eprintln!("{}", error);
+//!         std::process::exit(2)
+//!     });
+//!     std::process::exit(launcher.run(|context| MyServer::new(context)));
+//! }
+//! ```
+
+use std::env;
+use std::io;
+use std::io::BufReader;
+use std::net::TcpStream;
+use std::process;
+use std::thread;
+use std::time::Duration;
+
+use lsp::*;
+use lsp_errors::LSError;
+use lsp_output::OutputChannel;
+use lsp_transport::LSPBufferedMessageWriter;
+
+/// How the server talks with the client.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LaunchTransport {
+    Stdio,
+    /// Connect to the client on this TCP port of localhost.
+    Socket(u16),
+    /// Connect to the client on the pipe of this name (a Unix domain socket path).
+    Pipe(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LaunchOptions {
+    pub transport: LaunchTransport,
+    /// The process id of the client. The server exits when that process dies.
+    pub client_process_id: Option<u64>,
+}
+
+impl Default for LaunchOptions {
+    fn default() -> LaunchOptions {
+        LaunchOptions { transport : LaunchTransport::Stdio, client_process_id : None }
+    }
+}
+
+impl LaunchOptions {
+
+    /// Parse the options from given arguments, excluding the program name.
+    /// Options are either `--name=value` or `--name value`. Unknown options are ignored,
+    /// so that servers can have their own. Without a transport option, stdio is used.
+    pub fn parse<ARGS>(args: ARGS) -> Result<LaunchOptions, String>
+    where
+        ARGS : IntoIterator<Item = String>,
+    {
+        let mut options = LaunchOptions::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let (name, inline_value) = match arg.find('=') {
+                Some(ix) => (arg[..ix].to_string(), Some(arg[ix + 1..].to_string())),
+                None => (arg.clone(), None),
+            };
+            let value = |args: &mut Iterator<Item = String>| option_value(&name, inline_value.clone(), args);
+            match name.as_str() {
+                "--stdio" => options.transport = LaunchTransport::Stdio,
+                "--socket" | "--port" => {
+                    let port = try!(value(&mut args));
+                    let port = try!(port.parse().map_err(|_| format!("Invalid port `{}`.", port)));
+                    options.transport = LaunchTransport::Socket(port);
+                }
+                "--pipe" => options.transport = LaunchTransport::Pipe(try!(value(&mut args))),
+                "--clientProcessId" => {
+                    let pid = try!(value(&mut args));
+                    let pid = try!(pid.parse().map_err(|_| format!("Invalid process id `{}`.", pid)));
+                    options.client_process_id = Some(pid);
+                }
+                _ => {}
+            }
+        }
+        Ok(options)
+    }
+
+}
+
+fn option_value(name: &str, inline_value: Option<String>, args: &mut Iterator<Item = String>) -> Result<String, String> {
+    inline_value.or_else(|| args.next()).ok_or_else(|| format!("Missing value for option `{}`.", name))
+}
+
+/// The interval to check if the client process is still alive, in milliseconds.
+pub const CLIENT_PROCESS_POLL_MILLIS: u64 = 3000;
+
+/// Runs a server with the transport selected by the launch options.
+pub struct ServerLauncher {
+    pub options: LaunchOptions,
+}
+
+impl ServerLauncher {
+
+    pub fn new(options: LaunchOptions) -> ServerLauncher {
+        ServerLauncher { options : options }
+    }
+
+    /// A launcher with the options of the command line of this process.
+    pub fn from_args() -> Result<ServerLauncher, String> {
+        LaunchOptions::parse(env::args().skip(1)).map(ServerLauncher::new)
+    }
+
+    /// Run the server created by `create_server` until it exits, and return its exit code.
+    /// If the client process dies before, this process exits with code 1.
+    pub fn run<SERVER, CREATE>(self, create_server: CREATE) -> i32
+    where
+        SERVER : LanguageServerHandling + 'static,
+        CREATE : FnOnce(LSServerContext) -> SERVER,
+    {
+        match self.try_run(create_server) {
+            Ok(exit_code) => exit_code,
+            Err(error) => {
+                error!("Failed to launch server: {}", error);
+                1
+            }
+        }
+    }
+
+    pub fn try_run<SERVER, CREATE>(self, create_server: CREATE) -> Result<i32, LSError>
+    where
+        SERVER : LanguageServerHandling + 'static,
+        CREATE : FnOnce(LSServerContext) -> SERVER,
+    {
+        if let Some(pid) = self.options.client_process_id {
+            watch_client_process(pid, Duration::from_millis(CLIENT_PROCESS_POLL_MILLIS));
+        }
+
+        match self.options.transport {
+            LaunchTransport::Stdio => {
+                let stdin = io::stdin();
+                Ok(run_launched(&mut stdin.lock(), start_output(io::stdout()), create_server))
+            }
+            LaunchTransport::Socket(port) => {
+                let stream = try!(TcpStream::connect(("127.0.0.1", port)).map_err(LSError::Transport));
+                let output = try!(stream.try_clone().map_err(LSError::Transport));
+                Ok(run_launched(&mut BufReader::new(stream), start_output(output), create_server))
+            }
+            LaunchTransport::Pipe(ref name) => run_pipe(name, create_server),
+        }
+    }
+
+}
+
+/// The output of a launched server: the messages of the server context are serialized straight
+/// to the output, from their typed params or results, instead of through a `Value` in the endpoint.
+fn start_output<OUT : io::Write + Send + 'static>(output: OUT) -> OutputChannel {
+    OutputChannel::start_streaming(move || LSPBufferedMessageWriter::new(output))
+}
+
+fn run_launched<SERVER, CREATE>(input: &mut io::BufRead, mut output: OutputChannel, create_server: CREATE) -> i32
+where
+    SERVER : LanguageServerHandling + 'static,
+    CREATE : FnOnce(LSServerContext) -> SERVER,
+{
+    let context = LSServerContext::new_with_output(output.create_endpoint(), output.sender());
+    let server = create_server(context.clone());
+    let exit_code = LSPEndpoint::run_server_from_input(input, context, server);
+    output.shutdown_and_join();
+    exit_code
+}
+
+#[cfg(unix)]
+fn run_pipe<SERVER, CREATE>(name: &str, create_server: CREATE) -> Result<i32, LSError>
+where
+    SERVER : LanguageServerHandling + 'static,
+    CREATE : FnOnce(LSServerContext) -> SERVER,
+{
+    use std::os::unix::net::UnixStream;
+
+    let stream = try!(UnixStream::connect(name).map_err(LSError::Transport));
+    let output = try!(stream.try_clone().map_err(LSError::Transport));
+    Ok(run_launched(&mut BufReader::new(stream), start_output(output), create_server))
+}
+
+#[cfg(not(unix))]
+fn run_pipe<SERVER, CREATE>(name: &str, _create_server: CREATE) -> Result<i32, LSError>
+where
+    SERVER : LanguageServerHandling + 'static,
+    CREATE : FnOnce(LSServerContext) -> SERVER,
+{
+    Err(LSError::Transport(io::Error::new(io::ErrorKind::Other,
+        format!("Pipe transport is not supported on this platform: {}", name))))
+}
+
+/// Whether the process of given id is alive. `None` if that is not known on this platform,
+/// which is the case on platforms other than Unix and Windows.
+#[cfg(unix)]
+pub fn is_process_alive(pid: u64) -> Option<bool> {
+    extern "C" {
+        fn kill(pid: i32, signal: i32) -> i32;
+    }
+    const EPERM: i32 = 1;
+    
+    // Other ids signal a process group, or all processes
+    if pid == 0 || pid > i32::max_value() as u64 {
+        return Some(false);
+    }
+    // Signal 0 only checks that the process exists and may be signalled
+    if unsafe { kill(pid as i32, 0) } == 0 {
+        return Some(true);
+    }
+    // The process exists, but belongs to another user
+    Some(io::Error::last_os_error().raw_os_error() == Some(EPERM))
+}
+
+/// Whether the process of given id is alive. `None` if that is not known on this platform,
+/// which is the case on platforms other than Unix and Windows.
+#[cfg(windows)]
+pub fn is_process_alive(pid: u64) -> Option<bool> {
+    use std::os::raw::c_void;
+    
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn OpenProcess(desired_access: u32, inherit_handle: i32, process_id: u32) -> *mut c_void;
+        fn GetExitCodeProcess(process: *mut c_void, exit_code: *mut u32) -> i32;
+        fn CloseHandle(handle: *mut c_void) -> i32;
+    }
+    const PROCESS_QUERY_LIMITED_INFORMATION: u32 = 0x1000;
+    const ERROR_ACCESS_DENIED: i32 = 5;
+    const STILL_ACTIVE: u32 = 259;
+    
+    if pid > u32::max_value() as u64 {
+        return Some(false);
+    }
+    let process = unsafe { OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid as u32) };
+    if process.is_null() {
+        // The process exists, but can't be queried. Otherwise there is no process of that id
+        return Some(io::Error::last_os_error().raw_os_error() == Some(ERROR_ACCESS_DENIED));
+    }
+    let mut exit_code = 0;
+    let queried = unsafe { GetExitCodeProcess(process, &mut exit_code) } != 0;
+    unsafe { CloseHandle(process) };
+    Some(!queried || exit_code == STILL_ACTIVE)
+}
+
+/// Whether the process of given id is alive. `None` if that is not known on this platform,
+/// which is the case on platforms other than Unix and Windows.
+#[cfg(not(any(unix, windows)))]
+pub fn is_process_alive(_pid: u64) -> Option<bool> {
+    None
+}
+
+/// Exit this process with code 1 once the client process of given id dies, checking every `interval`.
+pub fn watch_client_process(pid: u64, interval: Duration) {
+    if is_process_alive(pid).is_none() {
+        warn!("Cannot watch the client process on this platform.");
+        return;
+    }
+    thread::spawn(move || {
+        while is_process_alive(pid) != Some(false) {
+            thread::sleep(interval);
+        }
+        error!("The client process {} died, exiting.", pid);
+        process::exit(1);
+    });
+}
+
+
+#[test]
+fn launch_options__test() {
+    let parse = |args: &[&str]| LaunchOptions::parse(args.iter().map(|arg| arg.to_string()));
+
+    assert_eq!(parse(&[]), Ok(LaunchOptions::default()));
+    assert_eq!(parse(&["--stdio", "--clientProcessId=42"]),
+        Ok(LaunchOptions { transport : LaunchTransport::Stdio, client_process_id : Some(42) }));
+    assert_eq!(parse(&["--socket=5007"]).unwrap().transport, LaunchTransport::Socket(5007));
+    assert_eq!(parse(&["--port", "5007", "--verbose"]).unwrap().transport, LaunchTransport::Socket(5007));
+    assert_eq!(parse(&["--pipe=/tmp/lsp.sock"]).unwrap().transport, LaunchTransport::Pipe("/tmp/lsp.sock".to_string()));
+    assert_eq!(parse(&["--clientProcessId", "7"]).unwrap().client_process_id, Some(7));
+
+    assert_eq!(parse(&["--socket=x"]), Err("Invalid port `x`.".to_string()));
+    assert_eq!(parse(&["--pipe"]), Err("Missing value for option `--pipe`.".to_string()));
+
+    if cfg!(any(unix, windows)) {
+        assert_eq!(is_process_alive(process::id() as u64), Some(true));
+        // Not the process group of this process, nor all processes
+        assert_eq!(is_process_alive(0), Some(false));
+        assert_eq!(is_process_alive(u64::max_value()), Some(false));
+        assert_eq!(is_process_alive(1 << 32), Some(false));
+    }
+}
@@ -0,0 +1,233 @@
+// Copyright 2016 Bruno Medeiros
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>.
+// This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Structured logging of the messages of a connection, by wrapping its `MessageReader` and
+//! `MessageWriter`. A `MessageLogger` gets the direction, method, id and size of each message,
+//! and optionally its payload, after a redaction callback has removed what must not be logged,
+//! such as the contents of documents:
+//!
+//! ```ignore
+//! let logging = MessageLogging::new(DefaultMessageLogger).with_redactor(redact_document_text);
+//! let endpoint = LSPEndpoint::create_lsp_output(move || logging.writer(LSPMessageWriter(io::stdout())));
+//! LSPEndpoint::run_server(&mut logging.reader(LSPMessageReader(stdin.lock())), context, server);
+//! ```
+
+use std::sync::Arc;
+
+use util::core::*;
+
+use jsonrpc::*;
+use serde_json;
+use serde_json::Value;
+
+pub use lsp_transcript::Direction;
+
+/// A message read or written, as given to a `MessageLogger`.
+#[derive(Debug)]
+pub struct MessageLogRecord<'a> {
+    pub direction: Direction,
+    /// The method, for requests and notifications.
+    pub method: Option<&'a str>,
+    /// The id, for requests and responses.
+    pub id: Option<&'a Value>,
+    /// The size of the message content, in bytes.
+    pub size: usize,
+    /// The redacted message, if the logger wants payloads and the message is valid JSON.
+    pub payload: Option<&'a Value>,
+}
+
+pub trait MessageLogger : Send + Sync {
+    fn log(&self, record: &MessageLogRecord);
+
+    /// Whether `log` gets the payload of the messages.
+    fn wants_payload(&self) -> bool {
+        false
+    }
+}
+
+/// Logs each message with the `log` crate: a summary at the `info` level,
+/// and the payload at the `debug` level.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DefaultMessageLogger;
+
+impl MessageLogger for DefaultMessageLogger {
+    fn log(&self, record: &MessageLogRecord) {
+        let kind = match (record.method, record.id) {
+            (Some(_), Some(_)) => "request",
+            (Some(_), None) => "notification",
+            (None, _) => "response",
+        };
+        let id = record.id.map(|id| format!(" #{}", id)).unwrap_or_default();
+        info!("JSON-RPC {} {} {}{} ({} bytes)", record.direction.arrow(), kind, record.method.unwrap_or(""), id,
+            record.size);
+        if let Some(payload) = record.payload {
+            debug!("JSON-RPC payload: {}", payload);
+        }
+    }
+
+    fn wants_payload(&self) -> bool {
+        log_enabled!(::log::LogLevel::Debug)
+    }
+}
+
+/// Called with the method (`None` for responses) and the message, to remove from the message
+/// what must not be logged.
+pub type Redactor = Arc<Fn(Option<&str>, &mut Value) + Send + Sync>;
+
+/// A `MessageLogger` with its redactor. Clones share the same logger.
+#[derive(Clone)]
+pub struct MessageLogging {
+    logger: Arc<MessageLogger>,
+    redactor: Option<Redactor>,
+}
+
+impl MessageLogging {
+
+    pub fn new<LOGGER : MessageLogger + 'static>(logger: LOGGER) -> MessageLogging {
+        MessageLogging { logger : Arc::new(logger), redactor : None }
+    }
+
+    pub fn with_redactor<REDACT>(mut self, redactor: REDACT) -> MessageLogging
+    where
+        REDACT : Fn(Option<&str>, &mut Value) + Send + Sync + 'static,
+    {
+        self.redactor = Some(Arc::new(redactor));
+        self
+    }
+
+    pub fn log_message(&self, direction: Direction, message: &str) {
+        let mut payload = serde_json::from_str::<Value>(message).ok();
+        let method = payload.as_ref().and_then(|payload| payload.find("method")).and_then(Value::as_str)
+            .map(String::from);
+        let id = payload.as_ref().and_then(|payload| payload.find("id")).cloned();
+
+        if !self.logger.wants_payload() {
+            payload = None;
+        }
+        if let (Some(payload), Some(redactor)) = (payload.as_mut(), self.redactor.as_ref()) {
+            redactor(method.as_ref().map(String::as_str), payload);
+        }
+
+        self.logger.log(&MessageLogRecord {
+            direction : direction,
+            method : method.as_ref().map(String::as_str),
+            id : id.as_ref(),
+            size : message.len(),
+            payload : payload.as_ref(),
+        });
+    }
+
+    /// Wrap given reader, to log the messages it reads.
+    pub fn reader<MR : MessageReader>(&self, msg_reader: MR) -> LoggingMessageReader<MR> {
+        LoggingMessageReader { msg_reader : msg_reader, logging : self.clone() }
+    }
+
+    /// Wrap given writer, to log the messages it writes.
+    pub fn writer<MW : MessageWriter>(&self, msg_writer: MW) -> LoggingMessageWriter<MW> {
+        LoggingMessageWriter { msg_writer : msg_writer, logging : self.clone() }
+    }
+
+}
+
+pub struct LoggingMessageReader<MR : MessageReader> {
+    msg_reader: MR,
+    logging: MessageLogging,
+}
+
+impl<MR : MessageReader> MessageReader for LoggingMessageReader<MR> {
+    fn read_next(&mut self) -> GResult<String> {
+        let message = try!(self.msg_reader.read_next());
+        self.logging.log_message(Direction::Received, &message);
+        Ok(message)
+    }
+}
+
+pub struct LoggingMessageWriter<MW : MessageWriter> {
+    msg_writer: MW,
+    logging: MessageLogging,
+}
+
+impl<MW : MessageWriter> MessageWriter for LoggingMessageWriter<MW> {
+    fn write_message(&mut self, msg: &str) -> GResult<()> {
+        self.logging.log_message(Direction::Sent, msg);
+        self.msg_writer.write_message(msg)
+    }
+}
+
+/// A redactor that replaces the text of documents in the document synchronization notifications
+/// (`textDocument/didOpen`, `textDocument/didChange` and `textDocument/didSave`) with its length.
+pub fn redact_document_text(method: Option<&str>, message: &mut Value) {
+    fn redact(text: &mut Value) {
+        let length = match *text {
+            Value::String(ref text) => text.len(),
+            _ => return,
+        };
+        *text = Value::String(format!("<redacted: {} bytes>", length));
+    }
+
+    match method {
+        Some("textDocument/didOpen") => {
+            if let Some(text) = message.pointer_mut("/params/textDocument/text") {
+                redact(text);
+            }
+        }
+        Some("textDocument/didChange") => {
+            if let Some(changes) = message.pointer_mut("/params/contentChanges").and_then(Value::as_array_mut) {
+                for change in changes {
+                    if let Some(text) = change.as_object_mut().and_then(|change| change.get_mut("text")) {
+                        redact(text);
+                    }
+                }
+            }
+        }
+        Some("textDocument/didSave") => {
+            if let Some(text) = message.pointer_mut("/params/text") {
+                redact(text);
+            }
+        }
+        _ => {}
+    }
+}
+
+
+#[cfg(test)]
+#[derive(Clone, Default)]
+struct RecordingLogger(Arc<::std::sync::Mutex<Vec<String>>>);
+
+#[cfg(test)]
+impl MessageLogger for RecordingLogger {
+    fn log(&self, record: &MessageLogRecord) {
+        let payload = record.payload.map(|payload| payload.to_string()).unwrap_or_default();
+        let line = format!("{:?} {:?} {:?} {} {}", record.direction, record.method, record.id, record.size, payload);
+        self.0.lock().unwrap().push(line);
+    }
+
+    fn wants_payload(&self) -> bool {
+        true
+    }
+}
+
+#[test]
+fn message_logging__test() {
+    let recording = RecordingLogger::default();
+    let logging = MessageLogging::new(recording.clone()).with_redactor(redact_document_text);
+
+    let did_open = r#"{"jsonrpc":"2.0","method":"textDocument/didOpen","params":{"textDocument":{"text":"secret"}}}"#;
+    let did_change = r#"{"jsonrpc":"2.0","method":"textDocument/didChange","params":{"contentChanges":[{"text":"x"}]}}"#;
+    logging.log_message(Direction::Received, did_open);
+    logging.log_message(Direction::Received, did_change);
+    logging.log_message(Direction::Sent, r#"{"jsonrpc":"2.0","id":1,"result":null}"#);
+    logging.log_message(Direction::Sent, "not json");
+
+    let lines = recording.0.lock().unwrap();
+    assert_eq!(lines[0], format!(
+        r#"Received Some("textDocument/didOpen") None {} {{"jsonrpc":"2.0","method":"textDocument/didOpen","params":{{"textDocument":{{"text":"<redacted: 6 bytes>"}}}}}}"#,
+        did_open.len()));
+    assert!(lines[1].contains(r#"[{"text":"<redacted: 1 bytes>"}]"#));
+    assert!(lines[2].starts_with("Sent None Some(1) 38 "));
+    assert_eq!(lines[3], "Sent None None 8 ");
+}
@@ -0,0 +1,247 @@
+// Copyright 2016 Bruno Medeiros
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>.
+// This file may not be copied, modified, or distributed
+// except according to those terms.
+
+
+/// Implement `LanguageServerHandling` for a type, forwarding only the listed methods to
+/// inherent methods of the type, and advertising exactly the matching capabilities in
+/// `server_capabilities()`. The other methods keep their default implementation.
+/// 
+/// ```ignore
+/// lsp_server! {
+///     impl LanguageServerHandling for MyServer {
+///         hover => my_hover,
+///         completion => my_completion,
+///     }
+/// }
+/// ```
+/// 
+/// The handler methods take the same parameters as the trait method they implement.
+/// 
+/// With the `derive` feature, the handlers can instead be annotated with the LSP method they handle,
+/// in an impl annotated with `#[lsp_routes]`, which expands to this macro:
+/// 
+/// ```ignore
+/// #[lsp_routes]
+/// impl MyServer {
+///     #[lsp(method = "textDocument/hover")]
+///     fn my_hover(&mut self, params: TextDocumentPositionParams, completable: LSMethodCompletable<HoverRequest>) {
+///         ...
+///     }
+/// }
+/// ```
+#[macro_export]
+macro_rules! lsp_server {
+    (impl LanguageServerHandling for $server:ty { $($method:ident => $handler:ident),* $(,)* }) => {
+        impl $crate::lsp::LanguageServerHandling for $server {
+            $( lsp_server!(@method $method, $handler); )*
+            
+            fn server_capabilities(&self) -> $crate::ls_types::ServerCapabilities {
+                #[allow(unused_mut)]
+                let mut capabilities = $crate::ls_types::ServerCapabilities::default();
+                $( lsp_server!(@capability $method, capabilities); )*
+                capabilities
+            }
+        }
+    };
+    
+    /* ----------------- Methods ----------------- */
+    
+    (@method initialize, $handler:ident) => { 
+        lsp_server!(@request initialize, $handler, Initialize); 
+    };
+    (@method shutdown, $handler:ident) => { 
+        lsp_server!(@request shutdown, $handler, Shutdown); 
+    };
+    (@method exit, $handler:ident) => { 
+        lsp_server!(@notification exit, $handler, Exit); 
+    };
+    (@method workspace_change_configuration, $handler:ident) => { 
+        lsp_server!(@notification workspace_change_configuration, $handler, DidChangeConfiguration); 
+    };
+    (@method did_open_text_document, $handler:ident) => { 
+        lsp_server!(@notification did_open_text_document, $handler, DidOpenTextDocument); 
+    };
+    (@method did_change_text_document, $handler:ident) => { 
+        lsp_server!(@notification did_change_text_document, $handler, DidChangeTextDocument); 
+    };
+    (@method did_close_text_document, $handler:ident) => { 
+        lsp_server!(@notification did_close_text_document, $handler, DidCloseTextDocument); 
+    };
+    (@method did_save_text_document, $handler:ident) => { 
+        lsp_server!(@notification did_save_text_document, $handler, DidSaveTextDocument); 
+    };
+    (@method did_change_watched_files, $handler:ident) => { 
+        lsp_server!(@notification did_change_watched_files, $handler, DidChangeWatchedFiles); 
+    };
+    (@method completion, $handler:ident) => { 
+        lsp_server!(@request completion, $handler, Completion); 
+    };
+    (@method resolve_completion_item, $handler:ident) => { 
+        lsp_server!(@request resolve_completion_item, $handler, ResolveCompletionItem); 
+    };
+    (@method hover, $handler:ident) => { 
+        lsp_server!(@request hover, $handler, HoverRequest); 
+    };
+    (@method signature_help, $handler:ident) => { 
+        lsp_server!(@request signature_help, $handler, SignatureHelpRequest); 
+    };
+    (@method goto_definition, $handler:ident) => { 
+        lsp_server!(@request goto_definition, $handler, GotoDefinition); 
+    };
+    (@method goto_type_definition, $handler:ident) => { 
+        lsp_server!(@request goto_type_definition, $handler, GotoTypeDefinition); 
+    };
+    (@method goto_implementation, $handler:ident) => { 
+        lsp_server!(@request goto_implementation, $handler, GotoImplementation); 
+    };
+    (@method references, $handler:ident) => { 
+        lsp_server!(@request references, $handler, References); 
+    };
+    (@method document_highlight, $handler:ident) => { 
+        lsp_server!(@request document_highlight, $handler, DocumentHighlightRequest); 
+    };
+    (@method document_symbols, $handler:ident) => { 
+        lsp_server!(@request document_symbols, $handler, DocumentSymbols); 
+    };
+    (@method workspace_symbols, $handler:ident) => { 
+        lsp_server!(@request workspace_symbols, $handler, WorkspaceSymbol); 
+    };
+    (@method code_action, $handler:ident) => { 
+        lsp_server!(@request code_action, $handler, CodeActionRequest); 
+    };
+    (@method code_lens, $handler:ident) => { 
+        lsp_server!(@request code_lens, $handler, CodeLensRequest); 
+    };
+    (@method code_lens_resolve, $handler:ident) => { 
+        lsp_server!(@request code_lens_resolve, $handler, CodeLensResolve); 
+    };
+    (@method document_link, $handler:ident) => { 
+        lsp_server!(@request document_link, $handler, DocumentLinkRequest); 
+    };
+    (@method document_link_resolve, $handler:ident) => { 
+        lsp_server!(@request document_link_resolve, $handler, DocumentLinkResolve); 
+    };
+    (@method formatting, $handler:ident) => { 
+        lsp_server!(@request formatting, $handler, Formatting); 
+    };
+    (@method range_formatting, $handler:ident) => { 
+        lsp_server!(@request range_formatting, $handler, RangeFormatting); 
+    };
+    (@method on_type_formatting, $handler:ident) => { 
+        lsp_server!(@request on_type_formatting, $handler, OnTypeFormatting); 
+    };
+    (@method rename, $handler:ident) => { 
+        lsp_server!(@request rename, $handler, Rename); 
+    };
+    
+    (@request $method:ident, $handler:ident, $marker:ident) => {
+        fn $method(&mut self, 
+            params: <$crate::lsp::methods::$marker as $crate::lsp_methods::LSRequest>::Params, 
+            completable: $crate::lsp::LSMethodCompletable<$crate::lsp::methods::$marker>
+        ) {
+            self.$handler(params, completable)
+        }
+    };
+    (@notification $method:ident, $handler:ident, $marker:ident) => {
+        fn $method(&mut self, 
+            params: <$crate::lsp::methods::$marker as $crate::lsp_methods::LSNotification>::Params
+        ) {
+            self.$handler(params)
+        }
+    };
+    
+    /* ----------------- Capabilities ----------------- */
+    
+    (@capability did_change_text_document, $caps:ident) => { 
+        $caps.text_document_sync = Some($crate::ls_types::TextDocumentSyncKind::Incremental); 
+    };
+    (@capability completion, $caps:ident) => { 
+        $caps.completion_provider = Some(Default::default()); 
+    };
+    (@capability hover, $caps:ident) => { $caps.hover_provider = Some(true); };
+    (@capability signature_help, $caps:ident) => { 
+        $caps.signature_help_provider = Some(Default::default()); 
+    };
+    (@capability goto_definition, $caps:ident) => { $caps.definition_provider = Some(true); };
+    (@capability references, $caps:ident) => { $caps.references_provider = Some(true); };
+    (@capability document_highlight, $caps:ident) => { $caps.document_highlight_provider = Some(true); };
+    (@capability document_symbols, $caps:ident) => { $caps.document_symbol_provider = Some(true); };
+    (@capability workspace_symbols, $caps:ident) => { $caps.workspace_symbol_provider = Some(true); };
+    (@capability code_action, $caps:ident) => { $caps.code_action_provider = Some(true); };
+    (@capability code_lens, $caps:ident) => { $caps.code_lens_provider = Some(Default::default()); };
+    (@capability document_link, $caps:ident) => { $caps.document_link_provider = Some(Default::default()); };
+    (@capability formatting, $caps:ident) => { $caps.document_formatting_provider = Some(true); };
+    (@capability range_formatting, $caps:ident) => { $caps.document_range_formatting_provider = Some(true); };
+    (@capability on_type_formatting, $caps:ident) => { 
+        $caps.document_on_type_formatting_provider = Some(Default::default()); 
+    };
+    (@capability rename, $caps:ident) => { $caps.rename_provider = Some(true); };
+    (@capability $method:ident, $caps:ident) => {};
+}
+
+
+#[cfg(test)]
+mod tests {
+    use lsp::*;
+    use ls_types::*;
+    
+    struct MacroTestServer;
+    
+    impl MacroTestServer {
+        fn my_hover(&mut self, _: TextDocumentPositionParams, completable: LSMethodCompletable<methods::HoverRequest>) {
+            completable.complete(Ok(LSHover::from(Hover { contents : vec![], range : None })))
+        }
+        fn my_rename(&mut self, _: RenameParams, completable: LSMethodCompletable<methods::Rename>) {
+            completable.complete(Err(error_not_supported()))
+        }
+        fn on_change(&mut self, _: DidChangeTextDocumentParams) {
+        }
+    }
+    
+    lsp_server! {
+        impl LanguageServerHandling for MacroTestServer {
+            hover => my_hover,
+            rename => my_rename,
+            did_change_text_document => on_change,
+        }
+    }
+    
+    #[test]
+    fn lsp_server_macro__test() {
+        let capabilities = MacroTestServer.server_capabilities();
+        assert_eq!(capabilities.hover_provider, Some(true));
+        assert_eq!(capabilities.rename_provider, Some(true));
+        assert_eq!(capabilities.text_document_sync, Some(TextDocumentSyncKind::Incremental));
+        assert_eq!(capabilities.definition_provider, None);
+        assert_eq!(capabilities.completion_provider, None);
+    }
+    
+    #[cfg(feature = "derive")]
+    struct RoutesTestServer;
+    
+    #[cfg(feature = "derive")]
+    #[::lsp_routes]
+    impl RoutesTestServer {
+        #[lsp(method = "textDocument/hover")]
+        fn my_hover(&mut self, _: TextDocumentPositionParams, completable: LSMethodCompletable<methods::HoverRequest>) {
+            completable.complete(Ok(LSHover::from(Hover { contents : vec![], range : None })))
+        }
+        #[lsp(method = "textDocument/didChange")]
+        fn on_change(&mut self, _: DidChangeTextDocumentParams) {
+        }
+    }
+    
+    #[cfg(feature = "derive")]
+    #[test]
+    fn lsp_routes__test() {
+        let capabilities = RoutesTestServer.server_capabilities();
+        assert_eq!(capabilities.hover_provider, Some(true));
+        assert_eq!(capabilities.text_document_sync, Some(TextDocumentSyncKind::Incremental));
+        assert_eq!(capabilities.rename_provider, None);
+    }
+    
+}
@@ -0,0 +1,520 @@
+// Copyright 2016 Bruno Medeiros
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>.
+// This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! `MarkupContent`: documentation text that is either plain text or markdown.
+//! And `MarkupBuilder`, to write it in markdown when the client supports it, in plain text otherwise.
+//!
+//! Older clients don't support `MarkupContent`, only plain strings for documentation, and the
+//! deprecated `MarkedString`s for hovers. `LSDocumentation` and `LSHoverContents` hold either form,
+//! and convert `MarkupContent` to the legacy form for those clients.
+
+use serde;
+use serde::de::Error as DeError;
+use serde_json;
+use serde_json::Value;
+
+use jsonrpc::json_util::JsonObject;
+use ls_types::MarkedString;
+
+use lsp_capabilities::LSClientCapabilities;
+use lsp_protocol::ProtocolField;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MarkupKind {
+    PlainText,
+    Markdown,
+}
+
+impl MarkupKind {
+
+    pub fn from_str(name: &str) -> Option<MarkupKind> {
+        match name {
+            "plaintext" => Some(MarkupKind::PlainText),
+            "markdown" => Some(MarkupKind::Markdown),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            MarkupKind::PlainText => "plaintext",
+            MarkupKind::Markdown => "markdown",
+        }
+    }
+
+}
+
+/// Text in the given markup kind.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MarkupContent {
+    pub kind: MarkupKind,
+    pub value: String,
+}
+
+impl MarkupContent {
+
+    pub fn plain_text(value: &str) -> MarkupContent {
+        MarkupContent { kind : MarkupKind::PlainText, value : value.to_string() }
+    }
+
+    pub fn markdown(value: &str) -> MarkupContent {
+        MarkupContent { kind : MarkupKind::Markdown, value : value.to_string() }
+    }
+
+    pub fn to_json(&self) -> Value {
+        let mut obj = JsonObject::new();
+        obj.insert("kind".to_string(), Value::String(self.kind.as_str().to_string()));
+        obj.insert("value".to_string(), Value::String(self.value.clone()));
+        Value::Object(obj)
+    }
+
+    /// Parse a `MarkupContent` object, or a plain string as sent by older clients and servers.
+    pub fn from_json(value: Value) -> Result<MarkupContent, String> {
+        let mut obj = match value {
+            Value::String(value) => return Ok(MarkupContent { kind : MarkupKind::PlainText, value : value }),
+            Value::Object(obj) => obj,
+            _ => return Err("MarkupContent is not an object or string.".to_string()),
+        };
+
+        let kind = match obj.remove("kind") {
+            Some(Value::String(kind)) => {
+                match MarkupKind::from_str(&kind) {
+                    Some(kind) => kind,
+                    None => return Err(format!("MarkupContent: unknown kind `{}`.", kind)),
+                }
+            }
+            _ => return Err("MarkupContent: missing `kind`.".to_string()),
+        };
+        let value = match obj.remove("value") {
+            Some(Value::String(value)) => value,
+            _ => return Err("MarkupContent: missing `value`.".to_string()),
+        };
+        Ok(MarkupContent { kind : kind, value : value })
+    }
+
+    /// This content as a `MarkedString`, for the contents of a `Hover`.
+    pub fn to_marked_string(&self) -> MarkedString {
+        MarkedString::String(self.value.clone())
+    }
+
+    /// The value as plain text, with the markdown syntax stripped if it is markdown.
+    pub fn to_plain_text(&self) -> String {
+        match self.kind {
+            MarkupKind::PlainText => self.value.clone(),
+            MarkupKind::Markdown => strip_markdown(&self.value),
+        }
+    }
+
+    /// The value as markdown, escaped if it is plain text.
+    pub fn to_markdown(&self) -> String {
+        match self.kind {
+            MarkupKind::PlainText => escape_markdown(&self.value),
+            MarkupKind::Markdown => self.value.clone(),
+        }
+    }
+
+    /// Convert to a kind the client supports for given target: markdown becomes plain text
+    /// if the client lists only plain text.
+    pub fn into_supported(self, target: MarkupTarget, client_capabilities: Option<&LSClientCapabilities>)
+        -> MarkupContent
+    {
+        let kinds = supported_markup_kinds(target, client_capabilities);
+        if self.kind == MarkupKind::Markdown && !kinds.is_empty() && !kinds.contains(&MarkupKind::Markdown) {
+            return MarkupContent::plain_text(&self.to_plain_text());
+        }
+        self
+    }
+
+}
+
+impl serde::Serialize for MarkupContent {
+    fn serialize<S>(&self, serializer: &mut S) -> Result<(), S::Error>
+    where
+        S : serde::Serializer,
+    {
+        self.to_json().serialize(serializer)
+    }
+}
+
+impl serde::Deserialize for MarkupContent {
+    fn deserialize<D>(deserializer: &mut D) -> Result<MarkupContent, D::Error>
+    where
+        D : serde::Deserializer,
+    {
+        let value = try!(Value::deserialize(deserializer));
+        MarkupContent::from_json(value).map_err(D::Error::custom)
+    }
+}
+
+impl ProtocolField for MarkupContent {
+    fn to_json_field(&self) -> Value {
+        self.to_json()
+    }
+
+    fn from_json_field(value: Value) -> Result<Self, String> {
+        MarkupContent::from_json(value)
+    }
+}
+
+/// The markup kinds the client supports for given target, in its order of preference.
+/// Empty if the client doesn't support `MarkupContent` there (or its capabilities are unknown).
+pub fn supported_markup_kinds(target: MarkupTarget, client_capabilities: Option<&LSClientCapabilities>)
+    -> Vec<MarkupKind>
+{
+    match client_capabilities {
+        Some(capabilities) => {
+            capabilities.get_string_array(target.capability_path()).into_iter()
+                .filter_map(MarkupKind::from_str)
+                .collect()
+        }
+        None => vec![],
+    }
+}
+
+/* ----------------- Documentation ----------------- */
+
+/// The documentation of a completion item, signature or parameter:
+/// a plain string, as older clients expect, or `MarkupContent`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LSDocumentation {
+    String(String),
+    Markup(MarkupContent),
+}
+
+impl From<String> for LSDocumentation {
+    fn from(documentation: String) -> LSDocumentation {
+        LSDocumentation::String(documentation)
+    }
+}
+
+impl From<MarkupContent> for LSDocumentation {
+    fn from(documentation: MarkupContent) -> LSDocumentation {
+        LSDocumentation::Markup(documentation)
+    }
+}
+
+impl LSDocumentation {
+
+    /// Convert to the form supported for given target by a client with given capabilities
+    /// (`None` if unknown). For clients without `MarkupContent` support, markup becomes a plain string.
+    pub fn into_supported(self, target: MarkupTarget, client_capabilities: Option<&LSClientCapabilities>)
+        -> LSDocumentation
+    {
+        match self {
+            LSDocumentation::Markup(markup) => {
+                if supported_markup_kinds(target, client_capabilities).is_empty() {
+                    LSDocumentation::String(markup.to_plain_text())
+                } else {
+                    LSDocumentation::Markup(markup.into_supported(target, client_capabilities))
+                }
+            }
+            documentation => documentation,
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        match *self {
+            LSDocumentation::String(ref documentation) => Value::String(documentation.clone()),
+            LSDocumentation::Markup(ref markup) => markup.to_json(),
+        }
+    }
+
+    pub fn from_json(value: Value) -> Result<LSDocumentation, String> {
+        match value {
+            Value::String(documentation) => Ok(LSDocumentation::String(documentation)),
+            value => MarkupContent::from_json(value).map(LSDocumentation::Markup),
+        }
+    }
+
+}
+
+impl ProtocolField for LSDocumentation {
+    fn to_json_field(&self) -> Value {
+        self.to_json()
+    }
+
+    fn from_json_field(value: Value) -> Result<Self, String> {
+        LSDocumentation::from_json(value)
+    }
+}
+
+impl serde::Serialize for LSDocumentation {
+    fn serialize<S>(&self, serializer: &mut S) -> Result<(), S::Error>
+    where
+        S : serde::Serializer,
+    {
+        self.to_json().serialize(serializer)
+    }
+}
+
+impl serde::Deserialize for LSDocumentation {
+    fn deserialize<D>(deserializer: &mut D) -> Result<LSDocumentation, D::Error>
+    where
+        D : serde::Deserializer,
+    {
+        let value = try!(Value::deserialize(deserializer));
+        LSDocumentation::from_json(value).map_err(D::Error::custom)
+    }
+}
+
+/// The contents of a hover: the deprecated `MarkedString`s, as older clients expect, or `MarkupContent`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LSHoverContents {
+    MarkedStrings(Vec<MarkedString>),
+    Markup(MarkupContent),
+}
+
+impl From<MarkupContent> for LSHoverContents {
+    fn from(contents: MarkupContent) -> LSHoverContents {
+        LSHoverContents::Markup(contents)
+    }
+}
+
+impl LSHoverContents {
+
+    /// Convert to the form supported by a client with given capabilities (`None` if unknown).
+    /// For clients without `MarkupContent` support, markup becomes a markdown `MarkedString`.
+    pub fn into_supported(self, client_capabilities: Option<&LSClientCapabilities>) -> LSHoverContents {
+        match self {
+            LSHoverContents::Markup(markup) => {
+                if supported_markup_kinds(MarkupTarget::Hover, client_capabilities).is_empty() {
+                    LSHoverContents::MarkedStrings(vec![MarkedString::String(markup.to_markdown())])
+                } else {
+                    LSHoverContents::Markup(markup.into_supported(MarkupTarget::Hover, client_capabilities))
+                }
+            }
+            contents => contents,
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        match *self {
+            LSHoverContents::MarkedStrings(ref strings) => serde_json::to_value(strings),
+            LSHoverContents::Markup(ref markup) => markup.to_json(),
+        }
+    }
+
+    /// Parse `MarkupContent`, or the legacy forms: a `MarkedString`, or an array of them.
+    pub fn from_json(value: Value) -> Result<LSHoverContents, String> {
+        if value.find("kind").is_some() {
+            return MarkupContent::from_json(value).map(LSHoverContents::Markup);
+        }
+        let strings = match value {
+            Value::Array(strings) => strings,
+            string => vec![string],
+        };
+        let strings = try!(serde_json::from_value(Value::Array(strings)).map_err(|err| err.to_string()));
+        Ok(LSHoverContents::MarkedStrings(strings))
+    }
+
+}
+
+impl ProtocolField for LSHoverContents {
+    fn to_json_field(&self) -> Value {
+        self.to_json()
+    }
+
+    fn from_json_field(value: Value) -> Result<Self, String> {
+        if value.is_null() {
+            return Err("missing value".to_string());
+        }
+        LSHoverContents::from_json(value)
+    }
+}
+
+/* ----------------- Builder ----------------- */
+
+/// Where markup is shown, each with its own capability for the formats the client supports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MarkupTarget {
+    Hover,
+    CompletionDocumentation,
+    SignatureHelpDocumentation,
+}
+
+impl MarkupTarget {
+
+    /// The path of the client capability that lists the supported markup kinds.
+    pub fn capability_path(&self) -> &'static [&'static str] {
+        match *self {
+            MarkupTarget::Hover => &["textDocument", "hover", "contentFormat"],
+            MarkupTarget::CompletionDocumentation =>
+                &["textDocument", "completion", "completionItem", "documentationFormat"],
+            MarkupTarget::SignatureHelpDocumentation =>
+                &["textDocument", "signatureHelp", "signatureInformation", "documentationFormat"],
+        }
+    }
+
+}
+
+/// Builds a `MarkupContent` from paragraphs written in markdown. When building plain text,
+/// the markdown syntax is stripped from them.
+#[derive(Debug, Clone)]
+pub struct MarkupBuilder {
+    kind: MarkupKind,
+    paragraphs: Vec<String>,
+}
+
+impl MarkupBuilder {
+
+    pub fn new(kind: MarkupKind) -> MarkupBuilder {
+        MarkupBuilder { kind : kind, paragraphs : vec![] }
+    }
+
+    /// A builder of markdown if the client supports it for given target, of plain text otherwise.
+    pub fn for_client(capabilities: &LSClientCapabilities, target: MarkupTarget) -> MarkupBuilder {
+        let supports_markdown = capabilities.get_string_array(target.capability_path()).contains(&"markdown");
+        MarkupBuilder::new(if supports_markdown { MarkupKind::Markdown } else { MarkupKind::PlainText })
+    }
+
+    pub fn kind(&self) -> MarkupKind {
+        self.kind
+    }
+
+    /// Add a paragraph in markdown.
+    pub fn markdown(mut self, text: &str) -> Self {
+        let paragraph = match self.kind {
+            MarkupKind::Markdown => text.to_string(),
+            MarkupKind::PlainText => strip_markdown(text),
+        };
+        self.paragraphs.push(paragraph);
+        self
+    }
+
+    /// Add a paragraph of plain text, escaped when building markdown.
+    pub fn text(mut self, text: &str) -> Self {
+        let paragraph = match self.kind {
+            MarkupKind::Markdown => escape_markdown(text),
+            MarkupKind::PlainText => text.to_string(),
+        };
+        self.paragraphs.push(paragraph);
+        self
+    }
+
+    /// Add a code block, in given language.
+    pub fn code_block(mut self, language: &str, code: &str) -> Self {
+        let paragraph = match self.kind {
+            MarkupKind::Markdown => format!("```{}\n{}\n```", language, code),
+            MarkupKind::PlainText => code.to_string(),
+        };
+        self.paragraphs.push(paragraph);
+        self
+    }
+
+    pub fn build(self) -> MarkupContent {
+        MarkupContent { kind : self.kind, value : self.paragraphs.join("\n\n") }
+    }
+
+}
+
+/// Escape the characters of given text that have a meaning in markdown.
+pub fn escape_markdown(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '\\' | '`' | '*' | '_' | '{' | '}' | '[' | ']' | '(' | ')' | '#' | '+' | '-' | '!' | '<' | '>' => {
+                escaped.push('\\');
+            }
+            _ => {}
+        }
+        escaped.push(ch);
+    }
+    escaped
+}
+
+/// Remove the common markdown syntax from given text: code fences, headings, emphasis,
+/// inline code, links (keeping their text) and escapes.
+pub fn strip_markdown(text: &str) -> String {
+    let mut lines = vec![];
+    for line in text.lines() {
+        if line.trim_left().starts_with("```") {
+            continue;
+        }
+        let heading = line.trim_left_matches('#');
+        let line = if heading.len() < line.len() { heading.trim_left() } else { line };
+        lines.push(strip_inline_markdown(line));
+    }
+    lines.join("\n")
+}
+
+fn strip_inline_markdown(line: &str) -> String {
+    let mut stripped = String::with_capacity(line.len());
+    let mut chars = line.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\\' => {
+                if let Some(escaped) = chars.next() {
+                    stripped.push(escaped);
+                }
+            }
+            '`' | '*' | '_' | '[' => {}
+            ']' => {
+                // Drop the target of a link
+                if chars.peek() == Some(&'(') {
+                    while let Some(ch) = chars.next() {
+                        if ch == ')' {
+                            break;
+                        }
+                    }
+                }
+            }
+            _ => stripped.push(ch),
+        }
+    }
+    stripped
+}
+
+
+#[test]
+fn markup_builder__test() {
+    use serde_json;
+
+    let json = r#"{ "textDocument": { "hover": { "contentFormat": ["markdown", "plaintext"] } } }"#;
+    let capabilities = LSClientCapabilities(serde_json::from_str(json).unwrap());
+    let builder = MarkupBuilder::for_client(&capabilities, MarkupTarget::Hover);
+    assert_eq!(builder.kind(), MarkupKind::Markdown);
+    let markup = builder.code_block("rust", "fn foo()").markdown("Does *foo*.").text("a_b").build();
+    assert_eq!(markup, MarkupContent::markdown("```rust\nfn foo()\n```\n\nDoes *foo*.\n\na\\_b"));
+
+    let builder = MarkupBuilder::for_client(&capabilities, MarkupTarget::CompletionDocumentation);
+    assert_eq!(builder.kind(), MarkupKind::PlainText);
+    let markup = builder.code_block("rust", "fn foo()")
+        .markdown("# Foo\nDoes *foo*, see [`bar`](http://bar) and \\*.").text("a_b").build();
+    assert_eq!(markup, MarkupContent::plain_text("fn foo()\n\nFoo\nDoes foo, see bar and *.\n\na_b"));
+}
+
+#[test]
+fn markup_legacy_forms__test() {
+    use serde_json;
+
+    let json = r#"{ "textDocument": {
+        "hover": { "contentFormat": ["plaintext"] },
+        "completion": { "completionItem": { "documentationFormat": ["markdown"] } }
+    } }"#;
+    let capabilities = LSClientCapabilities(serde_json::from_str(json).unwrap());
+    let legacy_capabilities = LSClientCapabilities(serde_json::from_str(r#"{ "textDocument": {} }"#).unwrap());
+    let markdown = MarkupContent::markdown("Does *foo*.");
+
+    let documentation = LSDocumentation::from(markdown.clone());
+    assert_eq!(documentation.clone().into_supported(MarkupTarget::CompletionDocumentation, Some(&capabilities)),
+        documentation);
+    assert_eq!(documentation.clone().into_supported(MarkupTarget::SignatureHelpDocumentation, Some(&capabilities)),
+        LSDocumentation::String("Does foo.".to_string()));
+    assert_eq!(LSDocumentation::from_json(Value::String("Does foo.".to_string())).unwrap(),
+        LSDocumentation::String("Does foo.".to_string()));
+
+    let contents = LSHoverContents::from(markdown.clone());
+    assert_eq!(contents.clone().into_supported(Some(&capabilities)),
+        LSHoverContents::Markup(MarkupContent::plain_text("Does foo.")));
+    assert_eq!(contents.clone().into_supported(Some(&legacy_capabilities)),
+        LSHoverContents::MarkedStrings(vec![MarkedString::String("Does *foo*.".to_string())]));
+    assert_eq!(LSHoverContents::from_json(contents.to_json()).unwrap(), contents);
+
+    let legacy = LSHoverContents::from_json(Value::String("foo".to_string())).unwrap();
+    assert_eq!(legacy, LSHoverContents::MarkedStrings(vec![MarkedString::String("foo".to_string())]));
+    assert_eq!(LSHoverContents::from_json(legacy.to_json()).unwrap(), legacy);
+}
@@ -0,0 +1,307 @@
+// Copyright 2016 Bruno Medeiros
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>.
+// This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! LSP method names, and marker types associating each method with its params and result types.
+
+use serde;
+use serde_json::Value;
+
+use ls_types::*;
+
+use lsp_completion::LSCompletionItem;
+use lsp_completion::LSCompletionList;
+use lsp_definition::LSDefinitionResponse;
+use lsp_diagnostics::LSPublishDiagnosticsParams;
+use lsp_initialize::LSInitializeResult;
+use lsp_protocol::*;
+use lsp_symbols::LSDocumentSymbolResponse;
+use lsp_workspace_edit::LSWorkspaceEdit;
+
+/* ----------------- Method names ----------------- */
+
+pub const CANCEL_REQUEST: &'static str = "$/cancelRequest";
+pub const PROGRESS: &'static str = "$/progress";
+pub const SET_TRACE: &'static str = "$/setTrace";
+pub const LOG_TRACE: &'static str = "$/logTrace";
+
+pub const INITIALIZE: &'static str = "initialize";
+pub const INITIALIZED: &'static str = "initialized";
+pub const SHUTDOWN: &'static str = "shutdown";
+pub const EXIT: &'static str = "exit";
+
+pub const WINDOW_SHOW_MESSAGE: &'static str = "window/showMessage";
+pub const WINDOW_SHOW_MESSAGE_REQUEST: &'static str = "window/showMessageRequest";
+pub const WINDOW_LOG_MESSAGE: &'static str = "window/logMessage";
+pub const WINDOW_WORK_DONE_PROGRESS_CREATE: &'static str = "window/workDoneProgress/create";
+pub const WINDOW_SHOW_DOCUMENT: &'static str = "window/showDocument";
+pub const TELEMETRY_EVENT: &'static str = "telemetry/event";
+
+pub const CLIENT_REGISTER_CAPABILITY: &'static str = "client/registerCapability";
+pub const CLIENT_UNREGISTER_CAPABILITY: &'static str = "client/unregisterCapability";
+
+pub const WORKSPACE_DID_CHANGE_CONFIGURATION: &'static str = "workspace/didChangeConfiguration";
+pub const WORKSPACE_DID_CHANGE_WATCHED_FILES: &'static str = "workspace/didChangeWatchedFiles";
+pub const WORKSPACE_SYMBOL: &'static str = "workspace/symbol";
+pub const WORKSPACE_EXECUTE_COMMAND: &'static str = "workspace/executeCommand";
+pub const WORKSPACE_APPLY_EDIT: &'static str = "workspace/applyEdit";
+pub const WORKSPACE_CONFIGURATION: &'static str = "workspace/configuration";
+pub const WORKSPACE_CODE_LENS_REFRESH: &'static str = "workspace/codeLens/refresh";
+pub const WORKSPACE_SEMANTIC_TOKENS_REFRESH: &'static str = "workspace/semanticTokens/refresh";
+pub const WORKSPACE_INLAY_HINT_REFRESH: &'static str = "workspace/inlayHint/refresh";
+pub const WORKSPACE_DIAGNOSTIC_REFRESH: &'static str = "workspace/diagnostic/refresh";
+
+pub const TEXT_DOCUMENT_PUBLISH_DIAGNOSTICS: &'static str = "textDocument/publishDiagnostics";
+pub const TEXT_DOCUMENT_DID_OPEN: &'static str = "textDocument/didOpen";
+pub const TEXT_DOCUMENT_DID_CHANGE: &'static str = "textDocument/didChange";
+pub const TEXT_DOCUMENT_WILL_SAVE: &'static str = "textDocument/willSave";
+pub const TEXT_DOCUMENT_WILL_SAVE_WAIT_UNTIL: &'static str = "textDocument/willSaveWaitUntil";
+pub const TEXT_DOCUMENT_DID_SAVE: &'static str = "textDocument/didSave";
+pub const TEXT_DOCUMENT_DID_CLOSE: &'static str = "textDocument/didClose";
+
+pub const TEXT_DOCUMENT_COMPLETION: &'static str = "textDocument/completion";
+pub const COMPLETION_ITEM_RESOLVE: &'static str = "completionItem/resolve";
+pub const TEXT_DOCUMENT_HOVER: &'static str = "textDocument/hover";
+pub const TEXT_DOCUMENT_SIGNATURE_HELP: &'static str = "textDocument/signatureHelp";
+pub const TEXT_DOCUMENT_DEFINITION: &'static str = "textDocument/definition";
+pub const TEXT_DOCUMENT_TYPE_DEFINITION: &'static str = "textDocument/typeDefinition";
+pub const TEXT_DOCUMENT_IMPLEMENTATION: &'static str = "textDocument/implementation";
+pub const TEXT_DOCUMENT_REFERENCES: &'static str = "textDocument/references";
+pub const TEXT_DOCUMENT_DOCUMENT_HIGHLIGHT: &'static str = "textDocument/documentHighlight";
+pub const TEXT_DOCUMENT_DOCUMENT_SYMBOL: &'static str = "textDocument/documentSymbol";
+pub const TEXT_DOCUMENT_CODE_ACTION: &'static str = "textDocument/codeAction";
+pub const TEXT_DOCUMENT_CODE_LENS: &'static str = "textDocument/codeLens";
+pub const CODE_LENS_RESOLVE: &'static str = "codeLens/resolve";
+pub const TEXT_DOCUMENT_DOCUMENT_LINK: &'static str = "textDocument/documentLink";
+pub const DOCUMENT_LINK_RESOLVE: &'static str = "documentLink/resolve";
+pub const TEXT_DOCUMENT_FORMATTING: &'static str = "textDocument/formatting";
+pub const TEXT_DOCUMENT_RANGE_FORMATTING: &'static str = "textDocument/rangeFormatting";
+pub const TEXT_DOCUMENT_ON_TYPE_FORMATTING: &'static str = "textDocument/onTypeFormatting";
+pub const TEXT_DOCUMENT_RENAME: &'static str = "textDocument/rename";
+pub const TEXT_DOCUMENT_FOLDING_RANGE: &'static str = "textDocument/foldingRange";
+pub const TEXT_DOCUMENT_SELECTION_RANGE: &'static str = "textDocument/selectionRange";
+pub const TEXT_DOCUMENT_PREPARE_CALL_HIERARCHY: &'static str = "textDocument/prepareCallHierarchy";
+pub const CALL_HIERARCHY_INCOMING_CALLS: &'static str = "callHierarchy/incomingCalls";
+pub const CALL_HIERARCHY_OUTGOING_CALLS: &'static str = "callHierarchy/outgoingCalls";
+pub const TEXT_DOCUMENT_SEMANTIC_TOKENS_FULL: &'static str = "textDocument/semanticTokens/full";
+pub const TEXT_DOCUMENT_SEMANTIC_TOKENS_RANGE: &'static str = "textDocument/semanticTokens/range";
+pub const TEXT_DOCUMENT_LINKED_EDITING_RANGE: &'static str = "textDocument/linkedEditingRange";
+pub const TEXT_DOCUMENT_INLAY_HINT: &'static str = "textDocument/inlayHint";
+pub const TEXT_DOCUMENT_DIAGNOSTIC: &'static str = "textDocument/diagnostic";
+
+/// Whether given method is a known notification, which must never be answered, not even with an error.
+pub fn is_notification_method(method: &str) -> bool {
+    match method {
+        CANCEL_REQUEST |
+        PROGRESS |
+        SET_TRACE |
+        LOG_TRACE |
+        INITIALIZED |
+        EXIT |
+        WINDOW_SHOW_MESSAGE |
+        WINDOW_LOG_MESSAGE |
+        TELEMETRY_EVENT |
+        WORKSPACE_DID_CHANGE_CONFIGURATION |
+        WORKSPACE_DID_CHANGE_WATCHED_FILES |
+        TEXT_DOCUMENT_PUBLISH_DIAGNOSTICS |
+        TEXT_DOCUMENT_DID_OPEN |
+        TEXT_DOCUMENT_DID_CHANGE |
+        TEXT_DOCUMENT_WILL_SAVE |
+        TEXT_DOCUMENT_DID_SAVE |
+        TEXT_DOCUMENT_DID_CLOSE 
+            => true,
+        _ => false,
+    }
+}
+
+/* ----------------- Protocol versions ----------------- */
+
+/// A LSP protocol version, as (major, minor).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ProtocolVersion(pub u32, pub u32);
+
+pub const LSP_2_0: ProtocolVersion = ProtocolVersion(2, 0);
+pub const LSP_3_0: ProtocolVersion = ProtocolVersion(3, 0);
+pub const LSP_3_6: ProtocolVersion = ProtocolVersion(3, 6);
+pub const LSP_3_10: ProtocolVersion = ProtocolVersion(3, 10);
+pub const LSP_3_15: ProtocolVersion = ProtocolVersion(3, 15);
+pub const LSP_3_16: ProtocolVersion = ProtocolVersion(3, 16);
+pub const LSP_3_17: ProtocolVersion = ProtocolVersion(3, 17);
+
+/// The protocol version that introduced given method. Unknown methods are assumed to be from 2.0.
+pub fn method_since_version(method: &str) -> ProtocolVersion {
+    match method {
+        INITIALIZED |
+        CLIENT_REGISTER_CAPABILITY |
+        CLIENT_UNREGISTER_CAPABILITY |
+        WORKSPACE_EXECUTE_COMMAND |
+        WORKSPACE_APPLY_EDIT |
+        TEXT_DOCUMENT_WILL_SAVE |
+        TEXT_DOCUMENT_WILL_SAVE_WAIT_UNTIL |
+        TEXT_DOCUMENT_DOCUMENT_LINK |
+        DOCUMENT_LINK_RESOLVE 
+            => LSP_3_0,
+        WORKSPACE_CONFIGURATION |
+        TEXT_DOCUMENT_TYPE_DEFINITION |
+        TEXT_DOCUMENT_IMPLEMENTATION 
+            => LSP_3_6,
+        TEXT_DOCUMENT_FOLDING_RANGE 
+            => LSP_3_10,
+        PROGRESS |
+        WINDOW_WORK_DONE_PROGRESS_CREATE |
+        TEXT_DOCUMENT_SELECTION_RANGE 
+            => LSP_3_15,
+        WINDOW_SHOW_DOCUMENT |
+        WORKSPACE_CODE_LENS_REFRESH |
+        WORKSPACE_SEMANTIC_TOKENS_REFRESH |
+        TEXT_DOCUMENT_PREPARE_CALL_HIERARCHY |
+        CALL_HIERARCHY_INCOMING_CALLS |
+        CALL_HIERARCHY_OUTGOING_CALLS |
+        TEXT_DOCUMENT_SEMANTIC_TOKENS_FULL |
+        TEXT_DOCUMENT_SEMANTIC_TOKENS_RANGE |
+        TEXT_DOCUMENT_LINKED_EDITING_RANGE 
+            => LSP_3_16,
+        WORKSPACE_INLAY_HINT_REFRESH |
+        WORKSPACE_DIAGNOSTIC_REFRESH |
+        TEXT_DOCUMENT_INLAY_HINT |
+        TEXT_DOCUMENT_DIAGNOSTIC 
+            => LSP_3_17,
+        _ => LSP_2_0,
+    }
+}
+
+/* ----------------- Marker types ----------------- */
+
+/// The default error data type of LSP methods: an optional, free-form JSON payload.
+pub type LSErrorData = Option<Value>;
+
+/// A LSP request method, with its params, result and error data types.
+pub trait LSRequest {
+    type Params : serde::Serialize + serde::Deserialize;
+    type Result : serde::Serialize + serde::Deserialize + Send + 'static;
+    type ErrorData : serde::Serialize + serde::Deserialize + Send + 'static;
+
+    const METHOD: &'static str;
+}
+
+/// A LSP notification method, with its params type.
+pub trait LSNotification {
+    type Params : serde::Serialize + serde::Deserialize;
+
+    const METHOD: &'static str;
+}
+
+macro_rules! lsp_request {
+    ($name:ident, $method:expr, $params:ty, $result:ty) => {
+        lsp_request!($name, $method, $params, $result, LSErrorData);
+    };
+    ($name:ident, $method:expr, $params:ty, $result:ty, $error_data:ty) => {
+        #[derive(Debug, Clone, Copy)]
+        pub struct $name;
+
+        impl LSRequest for $name {
+            type Params = $params;
+            type Result = $result;
+            type ErrorData = $error_data;
+
+            const METHOD: &'static str = $method;
+        }
+    };
+}
+
+macro_rules! lsp_notification {
+    ($name:ident, $method:expr, $params:ty) => {
+        #[derive(Debug, Clone, Copy)]
+        pub struct $name;
+
+        impl LSNotification for $name {
+            type Params = $params;
+
+            const METHOD: &'static str = $method;
+        }
+    };
+}
+
+lsp_request!(Initialize, INITIALIZE, InitializeParams, LSInitializeResult, InitializeError);
+lsp_notification!(Initialized, INITIALIZED, Value);
+lsp_request!(Shutdown, SHUTDOWN, (), ());
+lsp_notification!(Exit, EXIT, ());
+
+lsp_notification!(ShowMessage, WINDOW_SHOW_MESSAGE, ShowMessageParams);
+lsp_request!(ShowMessageRequest, WINDOW_SHOW_MESSAGE_REQUEST, ShowMessageRequestParams, MessageActionItem);
+lsp_notification!(LogMessage, WINDOW_LOG_MESSAGE, LogMessageParams);
+lsp_request!(ShowDocument, WINDOW_SHOW_DOCUMENT, ShowDocumentParams, ShowDocumentResult);
+lsp_notification!(TelemetryEvent, TELEMETRY_EVENT, Value);
+
+lsp_notification!(DidChangeConfiguration, WORKSPACE_DID_CHANGE_CONFIGURATION, DidChangeConfigurationParams);
+lsp_notification!(DidChangeWatchedFiles, WORKSPACE_DID_CHANGE_WATCHED_FILES, DidChangeWatchedFilesParams);
+lsp_request!(WorkspaceSymbol, WORKSPACE_SYMBOL, WorkspaceSymbolParams, Vec<SymbolInformation>);
+lsp_request!(WorkspaceConfiguration, WORKSPACE_CONFIGURATION, ConfigurationParams, Vec<Value>);
+lsp_request!(CodeLensRefresh, WORKSPACE_CODE_LENS_REFRESH, (), ());
+lsp_request!(SemanticTokensRefresh, WORKSPACE_SEMANTIC_TOKENS_REFRESH, (), ());
+lsp_request!(InlayHintRefresh, WORKSPACE_INLAY_HINT_REFRESH, (), ());
+lsp_request!(DiagnosticRefresh, WORKSPACE_DIAGNOSTIC_REFRESH, (), ());
+
+lsp_notification!(PublishDiagnostics, TEXT_DOCUMENT_PUBLISH_DIAGNOSTICS, LSPublishDiagnosticsParams);
+lsp_notification!(DidOpenTextDocument, TEXT_DOCUMENT_DID_OPEN, DidOpenTextDocumentParams);
+lsp_notification!(DidChangeTextDocument, TEXT_DOCUMENT_DID_CHANGE, DidChangeTextDocumentParams);
+lsp_notification!(DidSaveTextDocument, TEXT_DOCUMENT_DID_SAVE, DidSaveTextDocumentParams);
+lsp_notification!(DidCloseTextDocument, TEXT_DOCUMENT_DID_CLOSE, DidCloseTextDocumentParams);
+
+lsp_request!(Completion, TEXT_DOCUMENT_COMPLETION, TextDocumentPositionParams, LSCompletionList);
+lsp_request!(ResolveCompletionItem, COMPLETION_ITEM_RESOLVE, LSCompletionItem, LSCompletionItem);
+lsp_request!(HoverRequest, TEXT_DOCUMENT_HOVER, TextDocumentPositionParams, LSHover);
+lsp_request!(SignatureHelpRequest, TEXT_DOCUMENT_SIGNATURE_HELP, TextDocumentPositionParams, LSSignatureHelp);
+lsp_request!(GotoDefinition, TEXT_DOCUMENT_DEFINITION, TextDocumentPositionParams, LSDefinitionResponse);
+lsp_request!(GotoTypeDefinition, TEXT_DOCUMENT_TYPE_DEFINITION, TextDocumentPositionParams, LSDefinitionResponse);
+lsp_request!(GotoImplementation, TEXT_DOCUMENT_IMPLEMENTATION, TextDocumentPositionParams, LSDefinitionResponse);
+lsp_request!(References, TEXT_DOCUMENT_REFERENCES, ReferenceParams, Vec<Location>);
+lsp_request!(DocumentHighlightRequest, TEXT_DOCUMENT_DOCUMENT_HIGHLIGHT, TextDocumentPositionParams,
+    Vec<DocumentHighlight>);
+lsp_request!(DocumentSymbols, TEXT_DOCUMENT_DOCUMENT_SYMBOL, DocumentSymbolParams, LSDocumentSymbolResponse);
+lsp_request!(CodeActionRequest, TEXT_DOCUMENT_CODE_ACTION, CodeActionParams, Vec<Command>);
+lsp_request!(CodeLensRequest, TEXT_DOCUMENT_CODE_LENS, CodeLensParams, Vec<CodeLens>);
+lsp_request!(CodeLensResolve, CODE_LENS_RESOLVE, CodeLens, CodeLens);
+lsp_request!(DocumentLinkRequest, TEXT_DOCUMENT_DOCUMENT_LINK, DocumentLinkParams, Vec<DocumentLink>);
+lsp_request!(DocumentLinkResolve, DOCUMENT_LINK_RESOLVE, DocumentLink, DocumentLink);
+lsp_request!(Formatting, TEXT_DOCUMENT_FORMATTING, DocumentFormattingParams, Vec<TextEdit>);
+lsp_request!(RangeFormatting, TEXT_DOCUMENT_RANGE_FORMATTING, DocumentRangeFormattingParams, Vec<TextEdit>);
+lsp_request!(OnTypeFormatting, TEXT_DOCUMENT_ON_TYPE_FORMATTING, DocumentOnTypeFormattingParams, Vec<TextEdit>);
+lsp_request!(Rename, TEXT_DOCUMENT_RENAME, RenameParams, LSWorkspaceEdit);
+lsp_request!(FoldingRangeRequest, TEXT_DOCUMENT_FOLDING_RANGE, FoldingRangeParams, Vec<FoldingRange>);
+lsp_request!(SelectionRangeRequest, TEXT_DOCUMENT_SELECTION_RANGE, SelectionRangeParams, Vec<SelectionRange>);
+lsp_request!(PrepareCallHierarchy, TEXT_DOCUMENT_PREPARE_CALL_HIERARCHY, TextDocumentPositionParams,
+    Option<Vec<CallHierarchyItem>>);
+lsp_request!(CallHierarchyIncomingCalls, CALL_HIERARCHY_INCOMING_CALLS, CallHierarchyIncomingCallsParams,
+    Option<Vec<CallHierarchyIncomingCall>>);
+lsp_request!(CallHierarchyOutgoingCalls, CALL_HIERARCHY_OUTGOING_CALLS, CallHierarchyOutgoingCallsParams,
+    Option<Vec<CallHierarchyOutgoingCall>>);
+lsp_request!(SemanticTokensFull, TEXT_DOCUMENT_SEMANTIC_TOKENS_FULL, SemanticTokensParams, Option<SemanticTokens>);
+lsp_request!(SemanticTokensRange, TEXT_DOCUMENT_SEMANTIC_TOKENS_RANGE, SemanticTokensRangeParams,
+    Option<SemanticTokens>);
+lsp_request!(LinkedEditingRange, TEXT_DOCUMENT_LINKED_EDITING_RANGE, TextDocumentPositionParams,
+    Option<LinkedEditingRanges>);
+lsp_request!(InlayHintRequest, TEXT_DOCUMENT_INLAY_HINT, InlayHintParams, Option<Vec<InlayHint>>);
+// The result is a full or unchanged document diagnostic report
+lsp_request!(DocumentDiagnostic, TEXT_DOCUMENT_DIAGNOSTIC, DocumentDiagnosticParams, Value);
+
+
+#[test]
+fn method_names__test() {
+    assert_eq!(<Initialize as LSRequest>::METHOD, "initialize");
+    assert_eq!(<DidOpenTextDocument as LSNotification>::METHOD, "textDocument/didOpen");
+    assert_eq!(<Rename as LSRequest>::METHOD, "textDocument/rename");
+    
+    assert_eq!(method_since_version(<Initialize as LSRequest>::METHOD), LSP_2_0);
+    assert_eq!(method_since_version(<DocumentLinkRequest as LSRequest>::METHOD), LSP_3_0);
+    assert_eq!(method_since_version("$/unknown"), LSP_2_0);
+    assert_eq!(method_since_version(<InlayHintRequest as LSRequest>::METHOD), LSP_3_17);
+    assert!(LSP_3_0 < LSP_3_15);
+    
+    assert!(is_notification_method(<DidOpenTextDocument as LSNotification>::METHOD));
+    assert!(is_notification_method(CANCEL_REQUEST));
+    assert!(!is_notification_method(<Shutdown as LSRequest>::METHOD));
+}
@@ -0,0 +1,270 @@
+// Copyright 2016 Bruno Medeiros
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>.
+// This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Per-method latency and error metrics of a server, reported to a `MetricsSink`.
+//!
+//! A `MetricsRecorder` wraps the `MessageReader` and `MessageWriter` of the server, to know when
+//! each message was read and when each response was written, and is given to the dispatch layer
+//! with `LSServerContext::with_metrics`:
+//!
+//! ```ignore
+//! let recorder = MetricsRecorder::new(MySink::new());
+//! let endpoint = LSPEndpoint::create_lsp_output(move || recorder.writer(LSPMessageWriter(io::stdout())));
+//! let context = LSServerContext::new(endpoint).with_metrics(recorder.clone());
+//! LSPEndpoint::run_server(&mut recorder.reader(LSPMessageReader(stdin.lock())), context, server);
+//! ```
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::MutexGuard;
+use std::time::Duration;
+use std::time::Instant;
+
+use util::core::*;
+
+use jsonrpc::*;
+use serde_json;
+use serde_json::Value;
+
+/// How the handling of a message ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RequestOutcome {
+    /// A notification, which has no response.
+    Notification,
+    /// A request answered with a result.
+    Success,
+    /// A request answered with an error, of given code.
+    Error(i64),
+}
+
+/// The metrics of the handling of a request or notification.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RequestMetrics {
+    pub method: String,
+    /// The time between reading the message and dispatching it to its handler.
+    pub queue_wait: Duration,
+    /// The time between dispatching the message, and writing its response
+    /// (or the handler returning, for notifications).
+    pub handling: Duration,
+    pub outcome: RequestOutcome,
+}
+
+/// Receives the metrics of each request and notification handled by a server.
+/// Called from the threads of the endpoint, so it should not block.
+pub trait MetricsSink : Send + Sync {
+    fn record(&self, metrics: &RequestMetrics);
+}
+
+struct ReadMessage {
+    method: String,
+    /// The id as JSON, for requests.
+    id: Option<String>,
+    read_at: Instant,
+}
+
+struct PendingRequest {
+    method: String,
+    queue_wait: Duration,
+    dispatched_at: Instant,
+}
+
+#[derive(Default)]
+struct RecorderState {
+    /// The last message read, not dispatched yet. The read loop dispatches each message
+    /// right after reading it, on the same thread.
+    last_read: Option<ReadMessage>,
+    /// The dispatched requests without a response yet, by id.
+    pending: HashMap<String, PendingRequest>,
+}
+
+/// Records the metrics of a server, for a `MetricsSink`. Clones share the same state.
+#[derive(Clone)]
+pub struct MetricsRecorder {
+    sink: Arc<MetricsSink>,
+    state: Arc<Mutex<RecorderState>>,
+}
+
+impl MetricsRecorder {
+
+    pub fn new<SINK : MetricsSink + 'static>(sink: SINK) -> MetricsRecorder {
+        MetricsRecorder { sink : Arc::new(sink), state : Arc::new(Mutex::new(RecorderState::default())) }
+    }
+
+    fn lock_state(&self) -> MutexGuard<RecorderState> {
+        match self.state.lock() {
+            Ok(state) => state,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    /// Wrap given reader, to know when each message was read.
+    pub fn reader<MR : MessageReader>(&self, msg_reader: MR) -> MetricsMessageReader<MR> {
+        MetricsMessageReader { msg_reader : msg_reader, recorder : self.clone() }
+    }
+
+    /// Wrap given writer, to know when each response is written.
+    pub fn writer<MW : MessageWriter>(&self, msg_writer: MW) -> MetricsMessageWriter<MW> {
+        MetricsMessageWriter { msg_writer : msg_writer, recorder : self.clone() }
+    }
+
+    fn message_read(&self, message: &str) {
+        let read_at = Instant::now();
+        let read = match serde_json::from_str::<Value>(message) {
+            Ok(message) => {
+                message.find("method").and_then(Value::as_str).map(|method| ReadMessage {
+                    method : method.to_string(),
+                    id : message.find("id").map(Value::to_string),
+                    read_at : read_at,
+                })
+            }
+            Err(_) => None,
+        };
+        self.lock_state().last_read = read;
+    }
+
+    /// Called by the dispatch layer when it starts handling a message.
+    /// Messages not read through `reader` are not recorded.
+    pub fn begin_dispatch(&self, method: &str) -> Dispatch {
+        let dispatched_at = Instant::now();
+        let read = match self.lock_state().last_read.take() {
+            Some(read) => read,
+            None => return Dispatch { notification : None },
+        };
+        if read.method != method {
+            return Dispatch { notification : None };
+        }
+        let queue_wait = dispatched_at.duration_since(read.read_at);
+
+        if let Some(id) = read.id {
+            let pending = PendingRequest { method : read.method, queue_wait : queue_wait, dispatched_at : dispatched_at };
+            self.lock_state().pending.insert(id, pending);
+            return Dispatch { notification : None };
+        }
+        let metrics = RequestMetrics {
+            method : read.method,
+            queue_wait : queue_wait,
+            handling : Duration::from_secs(0),
+            outcome : RequestOutcome::Notification,
+        };
+        Dispatch { notification : Some((self.clone(), metrics, dispatched_at)) }
+    }
+
+    fn message_written(&self, message: &str) {
+        let message = match serde_json::from_str::<Value>(message) {
+            Ok(message) => message,
+            Err(_) => return,
+        };
+        if message.find("method").is_some() {
+            return;
+        }
+        let id = match message.find("id") {
+            Some(id) => id.to_string(),
+            None => return,
+        };
+        let pending = match self.lock_state().pending.remove(&id) {
+            Some(pending) => pending,
+            None => return,
+        };
+        let outcome = match message.find("error") {
+            Some(error) => RequestOutcome::Error(error.find("code").and_then(Value::as_i64).unwrap_or(0)),
+            None => RequestOutcome::Success,
+        };
+        self.sink.record(&RequestMetrics {
+            method : pending.method,
+            queue_wait : pending.queue_wait,
+            handling : pending.dispatched_at.elapsed(),
+            outcome : outcome,
+        });
+    }
+
+}
+
+/// The dispatch of a message, for `MetricsRecorder::begin_dispatch`.
+/// The metrics of a request are recorded when its response is written,
+/// those of a notification when this is ended.
+pub struct Dispatch {
+    notification: Option<(MetricsRecorder, RequestMetrics, Instant)>,
+}
+
+impl Dispatch {
+    /// Called when the handler of the message returns.
+    pub fn end(self) {
+        if let Some((recorder, mut metrics, dispatched_at)) = self.notification {
+            metrics.handling = dispatched_at.elapsed();
+            recorder.sink.record(&metrics);
+        }
+    }
+}
+
+pub struct MetricsMessageReader<MR : MessageReader> {
+    msg_reader: MR,
+    recorder: MetricsRecorder,
+}
+
+impl<MR : MessageReader> MessageReader for MetricsMessageReader<MR> {
+    fn read_next(&mut self) -> GResult<String> {
+        let message = try!(self.msg_reader.read_next());
+        self.recorder.message_read(&message);
+        Ok(message)
+    }
+}
+
+pub struct MetricsMessageWriter<MW : MessageWriter> {
+    msg_writer: MW,
+    recorder: MetricsRecorder,
+}
+
+impl<MW : MessageWriter> MessageWriter for MetricsMessageWriter<MW> {
+    fn write_message(&mut self, msg: &str) -> GResult<()> {
+        try!(self.msg_writer.write_message(msg));
+        self.recorder.message_written(msg);
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+#[derive(Clone, Default)]
+struct RecordingSink(Arc<Mutex<Vec<RequestMetrics>>>);
+
+#[cfg(test)]
+impl MetricsSink for RecordingSink {
+    fn record(&self, metrics: &RequestMetrics) {
+        self.0.lock().unwrap().push(metrics.clone());
+    }
+}
+
+#[test]
+fn metrics_recorder__test() {
+    let sink = RecordingSink::default();
+    let recorder = MetricsRecorder::new(sink.clone());
+
+    recorder.message_read(r#"{"jsonrpc":"2.0","id":1,"method":"textDocument/hover","params":{}}"#);
+    recorder.begin_dispatch("textDocument/hover").end();
+    recorder.message_read(r#"{"jsonrpc":"2.0","id":"2","method":"shutdown"}"#);
+    recorder.begin_dispatch("shutdown").end();
+    recorder.message_read(r#"{"jsonrpc":"2.0","method":"exit"}"#);
+    recorder.begin_dispatch("exit").end();
+    assert_eq!(sink.0.lock().unwrap().len(), 1);
+
+    recorder.message_written(r#"{"jsonrpc":"2.0","id":"2","result":null}"#);
+    recorder.message_written(r#"{"jsonrpc":"2.0","id":1,"error":{"code":-32601,"message":""}}"#);
+    recorder.message_written(r#"{"jsonrpc":"2.0","id":1,"result":null}"#);
+
+    // Not read through the recorder
+    recorder.begin_dispatch("textDocument/didOpen").end();
+
+    let metrics = sink.0.lock().unwrap();
+    let outcomes : Vec<(&str, RequestOutcome)> = metrics.iter()
+        .map(|metrics| (&metrics.method[..], metrics.outcome)).collect();
+    assert_eq!(outcomes, vec![
+        ("exit", RequestOutcome::Notification),
+        ("shutdown", RequestOutcome::Success),
+        ("textDocument/hover", RequestOutcome::Error(-32601)),
+    ]);
+}
@@ -0,0 +1,640 @@
+// Copyright 2016 Bruno Medeiros
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>.
+// This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Several language servers behind one endpoint, for a polyglot workspace. A `MultiplexServer`
+//! routes each document to the first backend whose `DocumentSelector` matches it, by language id
+//! or URI pattern, and merges the capabilities of the backends at `initialize`.
+//!
+//! Each backend runs in-process, in a thread of its own, with its own `LSServerContext`.
+//! The messages a backend sends to the client are forwarded to the client.
+//!
+//! ```ignore
+//! let server = MultiplexServer::new(context.clone())
+//!     .backend(DocumentSelector::language("rust"), |context| RustServer::new(context))
+//!     .backend(try!(DocumentSelector::pattern("**/*.toml")), |context| TomlServer::new(context));
+//! LSPEndpoint::run_server_from_input(&mut input, context, server)
+//! ```
+
+use std::collections::HashMap;
+use std::thread;
+
+use util::core::*;
+
+use jsonrpc::*;
+use jsonrpc::json_util::JsonObject;
+use jsonrpc::jsonrpc_request::RequestParams;
+use jsonrpc::method_types::MethodError;
+use ls_types::*;
+use serde_json;
+use serde_json::Value;
+use url::Url;
+
+use lsp::*;
+use lsp_errors::method_error_RequestFailed;
+use lsp_methods::LSRequest;
+use lsp_protocol::ConfigurationParams;
+use lsp_protocol::ShowDocumentParams;
+use lsp_scheduler::document_uri_of_params;
+use lsp_testing::pipe;
+use lsp_workspace::GlobPattern;
+
+/* ----------------- Document selector ----------------- */
+
+/// Selects the documents of a backend, by language id, and by a glob pattern on the path of the URI.
+/// A selector without either matches all documents.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DocumentSelector {
+    pub language_id: Option<String>,
+    pub pattern: Option<GlobPattern>,
+}
+
+impl DocumentSelector {
+
+    pub fn all() -> DocumentSelector {
+        DocumentSelector { language_id : None, pattern : None }
+    }
+
+    pub fn language(language_id: &str) -> DocumentSelector {
+        DocumentSelector { language_id : Some(language_id.to_string()), pattern : None }
+    }
+
+    pub fn pattern(pattern: &str) -> GResult<DocumentSelector> {
+        Ok(DocumentSelector { language_id : None, pattern : Some(try!(GlobPattern::new(pattern))) })
+    }
+
+    /// Whether the document of given URI, and language id if known, is selected.
+    pub fn matches(&self, uri: &Url, language_id: Option<&str>) -> bool {
+        if let Some(ref selector_language_id) = self.language_id {
+            if language_id != Some(selector_language_id.as_str()) {
+                return false;
+            }
+        }
+        self.pattern.as_ref().map_or(true, |pattern| pattern.matches(uri.path()))
+    }
+
+}
+
+/* ----------------- Backends ----------------- */
+
+/// A backend server, running in a thread of its own, and the client endpoint connected to it.
+struct Backend {
+    selector: DocumentSelector,
+    endpoint: Endpoint,
+    server_thread: Option<thread::JoinHandle<i32>>,
+    client_thread: Option<thread::JoinHandle<()>>,
+}
+
+impl Backend {
+
+    fn start<SERVER, CREATE>(selector: DocumentSelector, create_server: CREATE, context: LSServerContext) -> Backend
+    where
+        SERVER : LanguageServerHandling + 'static,
+        CREATE : FnOnce(LSServerContext) -> SERVER + Send + 'static,
+    {
+        let (client_writer, mut server_reader) = pipe();
+        let (server_writer, mut client_reader) = pipe();
+
+        let server_thread = thread::spawn(move || {
+            let endpoint = LSPEndpoint::create_lsp_output_with_output_stream(|| server_writer);
+            let context = LSServerContext::new(endpoint);
+            let server = create_server(context.clone());
+            LSPEndpoint::run_server_from_input(&mut server_reader, context, server)
+        });
+
+        let endpoint = LSPEndpoint::create_lsp_output_with_output_stream(|| client_writer);
+        let client_endpoint = endpoint.clone();
+        let client_thread = thread::spawn(move || {
+            let client = BackendClient { context : context };
+            let _ = LSPEndpoint::run_client_from_input(&mut client_reader, client_endpoint, client);
+        });
+
+        Backend {
+            selector : selector, endpoint : endpoint,
+            server_thread : Some(server_thread), client_thread : Some(client_thread),
+        }
+    }
+
+    /// Wait for the backend to terminate, after `exit` was sent to it.
+    fn join(&mut self) {
+        if let Some(server_thread) = self.server_thread.take() {
+            let _ = server_thread.join();
+        }
+        self.endpoint.shutdown_and_join();
+        if let Some(client_thread) = self.client_thread.take() {
+            let _ = client_thread.join();
+        }
+    }
+
+}
+
+/// Forwards the messages of a backend to the client of the multiplexer.
+struct BackendClient {
+    context: LSServerContext,
+}
+
+impl LanguageClientHandling for BackendClient {
+
+    fn show_message(&mut self, params: ShowMessageParams) {
+        let _ = self.context.send_notification::<methods::ShowMessage>(params);
+    }
+
+    fn show_message_request(&mut self, params: ShowMessageRequestParams,
+        completable: LSMethodCompletable<methods::ShowMessageRequest>)
+    {
+        forward_request::<methods::ShowMessageRequest>(&mut self.context.endpoint.clone(), params, completable)
+    }
+
+    fn log_message(&mut self, params: LogMessageParams) {
+        let _ = self.context.send_notification::<methods::LogMessage>(params);
+    }
+
+    fn telemetry_event(&mut self, params: Value) {
+        let _ = self.context.send_notification::<methods::TelemetryEvent>(params);
+    }
+
+    fn publish_diagnostics(&mut self, params: LSPublishDiagnosticsParams) {
+        let _ = self.context.send_notification::<methods::PublishDiagnostics>(params);
+    }
+
+    fn show_document(&mut self, params: ShowDocumentParams, completable: LSMethodCompletable<methods::ShowDocument>) {
+        forward_request::<methods::ShowDocument>(&mut self.context.endpoint.clone(), params, completable)
+    }
+
+    fn workspace_configuration(&mut self, params: ConfigurationParams,
+        completable: LSMethodCompletable<methods::WorkspaceConfiguration>)
+    {
+        forward_request::<methods::WorkspaceConfiguration>(&mut self.context.endpoint.clone(), params, completable)
+    }
+
+    fn code_lens_refresh(&mut self, completable: LSMethodCompletable<methods::CodeLensRefresh>) {
+        forward_request::<methods::CodeLensRefresh>(&mut self.context.endpoint.clone(), (), completable)
+    }
+
+    fn semantic_tokens_refresh(&mut self, completable: LSMethodCompletable<methods::SemanticTokensRefresh>) {
+        forward_request::<methods::SemanticTokensRefresh>(&mut self.context.endpoint.clone(), (), completable)
+    }
+
+    fn inlay_hint_refresh(&mut self, completable: LSMethodCompletable<methods::InlayHintRefresh>) {
+        forward_request::<methods::InlayHintRefresh>(&mut self.context.endpoint.clone(), (), completable)
+    }
+
+    fn diagnostic_refresh(&mut self, completable: LSMethodCompletable<methods::DiagnosticRefresh>) {
+        forward_request::<methods::DiagnosticRefresh>(&mut self.context.endpoint.clone(), (), completable)
+    }
+
+    fn handle_other_method(&mut self, request: &RequestInfo, method_name: &str, params: RequestParams,
+        completable: ResponseCompletable)
+    {
+        forward_other_method(&mut self.context.endpoint.clone(), request, method_name, params, completable)
+    }
+
+}
+
+/// Forward a request to given endpoint, and complete it with the response, once that arrives.
+fn forward_request<REQ>(endpoint: &mut Endpoint, params: REQ::Params, completable: LSMethodCompletable<REQ>)
+where
+    REQ : LSRequest,
+    REQ::ErrorData : Default,
+{
+    match send_lsp_request::<REQ>(endpoint, params) {
+        Ok(future) => {
+            thread::spawn(move || complete_lsp_request(completable, wait_response::<REQ>(future)));
+        }
+        Err(error) => completable.complete(Err(method_error_RequestFailed(error.to_string()))),
+    }
+}
+
+/// Wait for the response of a request forwarded to a backend, or to the client.
+fn wait_response<REQ>(future: LSRequestFuture<REQ>) -> Result<REQ::Result, MethodError<REQ::ErrorData>>
+where
+    REQ : LSRequest,
+    REQ::ErrorData : Default,
+{
+    match future.wait() {
+        Ok(result) => result,
+        Err(error) => Err(method_error_RequestFailed(format!("Request `{}` got no response: {:?}", REQ::METHOD, error))),
+    }
+}
+
+/// Forward a method not known to this crate to given endpoint.
+fn forward_other_method(endpoint: &mut Endpoint, request: &RequestInfo, method_name: &str, params: RequestParams,
+    completable: ResponseCompletable)
+{
+    if request.is_notification() {
+        let endpoint = endpoint.clone();
+        let method_name = method_name.to_string();
+        return completable.handle_notification_with(params, move |params: Value| {
+            let _ = endpoint.clone().send_notification(&method_name, params);
+        });
+    }
+    let mut endpoint = endpoint.clone();
+    let method_name = method_name.to_string();
+    completable.handle_request_with(params, move |params: Value, completable: MethodCompletable<Value, Value>| {
+        let request_failed = |message: String| MethodError { code : -32803, message : message, data : Value::Null };
+        let future : RequestFuture<Value, Value> = match endpoint.send_request(&method_name, params) {
+            Ok(future) => future,
+            Err(error) => return completable.complete(Err(request_failed(error.to_string()))),
+        };
+        thread::spawn(move || {
+            let result = match future.wait() {
+                Ok(result) => result,
+                Err(error) => Err(request_failed(format!("Request `{}` got no response: {:?}", method_name, error))),
+            };
+            completable.complete(result)
+        });
+    })
+}
+
+/* ----------------- Capabilities ----------------- */
+
+/// Merge the capabilities of the backends: each capability is the one of the first backend that has it,
+/// and the trigger characters are the union of those of all backends.
+/// The text document sync is `Full` if some backend needs it, as incremental changes can't be converted.
+pub fn merge_server_capabilities(capabilities: &[ServerCapabilities]) -> ServerCapabilities {
+    let mut merged = JsonObject::new();
+    let mut sync_kinds = vec![];
+    for backend_capabilities in capabilities {
+        let backend_capabilities = match serde_json::to_value(backend_capabilities) {
+            Value::Object(obj) => obj,
+            _ => continue,
+        };
+        for (name, value) in backend_capabilities {
+            if value.is_null() {
+                continue;
+            }
+            if name == "textDocumentSync" {
+                sync_kinds.extend(value.as_u64());
+                continue;
+            }
+            if let Some(merged_value) = merged.get_mut(&name) {
+                merge_trigger_characters(merged_value, &value);
+                continue;
+            }
+            merged.insert(name, value);
+        }
+    }
+    let sync_kind = sync_kinds.iter().filter(|kind| **kind > 0).min().cloned()
+        .or_else(|| sync_kinds.first().cloned());
+    if let Some(sync_kind) = sync_kind {
+        merged.insert("textDocumentSync".to_string(), Value::U64(sync_kind));
+    }
+    serde_json::from_value(Value::Object(merged)).unwrap_or_default()
+}
+
+fn merge_trigger_characters(merged: &mut Value, value: &Value) {
+    let characters = match value.find("triggerCharacters") {
+        Some(&Value::Array(ref characters)) => characters,
+        _ => return,
+    };
+    if let Value::Object(ref mut merged) = *merged {
+        let merged_characters = merged.entry("triggerCharacters".to_string()).or_insert_with(|| Value::Array(vec![]));
+        if let Value::Array(ref mut merged_characters) = *merged_characters {
+            for character in characters {
+                if !merged_characters.contains(character) {
+                    merged_characters.push(character.clone());
+                }
+            }
+        }
+    }
+}
+
+/* ----------------- Multiplexer ----------------- */
+
+/// A server that dispatches to backend servers. Requests about a document go to the backend
+/// selected for it when it was opened, or else to the first backend whose selector matches its URI.
+/// Workspace notifications go to all backends, and `workspace/symbol` results are concatenated.
+/// The resolve requests, which have no document, go to the backend of the last request.
+pub struct MultiplexServer {
+    context: LSServerContext,
+    backends: Vec<Backend>,
+    /// The backend of each open document.
+    documents: HashMap<Url, usize>,
+    last_backend: Option<usize>,
+}
+
+impl MultiplexServer {
+
+    pub fn new(context: LSServerContext) -> MultiplexServer {
+        MultiplexServer { context : context, backends : vec![], documents : HashMap::new(), last_backend : None }
+    }
+
+    /// Add a backend, for the documents of given selector, and start the server created by `create_server`.
+    /// Backends are tried in the order they are added.
+    pub fn backend<SERVER, CREATE>(mut self, selector: DocumentSelector, create_server: CREATE) -> Self
+    where
+        SERVER : LanguageServerHandling + 'static,
+        CREATE : FnOnce(LSServerContext) -> SERVER + Send + 'static,
+    {
+        self.backends.push(Backend::start(selector, create_server, self.context.clone()));
+        self
+    }
+
+    /// The index of the backend for given document, if any.
+    pub fn backend_for(&self, uri: &Url, language_id: Option<&str>) -> Option<usize> {
+        if let Some(&ix) = self.documents.get(uri) {
+            return Some(ix);
+        }
+        self.backends.iter().position(|backend| backend.selector.matches(uri, language_id))
+    }
+
+    fn forward<REQ>(&mut self, backend_ix: Option<usize>, params: REQ::Params, completable: LSMethodCompletable<REQ>)
+    where
+        REQ : LSRequest,
+        REQ::ErrorData : Default,
+    {
+        let backend_ix = match backend_ix {
+            Some(backend_ix) => backend_ix,
+            None => return completable.complete(Err(error_not_supported())),
+        };
+        self.last_backend = Some(backend_ix);
+        forward_request::<REQ>(&mut self.backends[backend_ix].endpoint, params, completable)
+    }
+
+    fn forward_document<REQ>(&mut self, uri: &Url, params: REQ::Params, completable: LSMethodCompletable<REQ>)
+    where
+        REQ : LSRequest,
+        REQ::ErrorData : Default,
+    {
+        let backend_ix = self.backend_for(uri, None);
+        self.forward::<REQ>(backend_ix, params, completable)
+    }
+
+    fn notify_document<NOTIF>(&mut self, uri: &Url, params: NOTIF::Params)
+    where
+        NOTIF : methods::LSNotification,
+    {
+        if let Some(backend_ix) = self.backend_for(uri, None) {
+            let _ = send_lsp_notification::<NOTIF>(&mut self.backends[backend_ix].endpoint, params);
+        }
+    }
+
+    fn notify_all<NOTIF>(&mut self, params: NOTIF::Params)
+    where
+        NOTIF : methods::LSNotification,
+        NOTIF::Params : Clone,
+    {
+        for backend in &mut self.backends {
+            let _ = send_lsp_notification::<NOTIF>(&mut backend.endpoint, params.clone());
+        }
+    }
+
+}
+
+impl LanguageServerHandling for MultiplexServer {
+
+    fn initialize(&mut self, params: InitializeParams, completable: LSMethodCompletable<methods::Initialize>) {
+        let mut futures = vec![];
+        for backend in &mut self.backends {
+            match send_lsp_request::<methods::Initialize>(&mut backend.endpoint, params.clone()) {
+                Ok(future) => futures.push(future),
+                Err(error) => {
+                    let message = format!("Failed to initialize backend: {}", error);
+                    return completable.complete(Err(MethodError {
+                        code : -32803, message : message, data : InitializeError { retry : false },
+                    }));
+                }
+            }
+        }
+        let mut capabilities = vec![];
+        for future in futures {
+            match future.wait() {
+                Ok(Ok(result)) => capabilities.push(result.capabilities),
+                Ok(Err(error)) => return completable.complete(Err(error)),
+                Err(error) => {
+                    let message = format!("Backend got no response to initialize: {:?}", error);
+                    return completable.complete(Err(MethodError {
+                        code : -32803, message : message, data : InitializeError { retry : false },
+                    }));
+                }
+            }
+        }
+        let mut result = InitializeResultBuilder::new().capabilities(merge_server_capabilities(&capabilities)).build();
+        self.context.restrict_server_capabilities(&mut result.capabilities);
+        completable.complete(Ok(result))
+    }
+
+    fn shutdown(&mut self, _: (), completable: LSMethodCompletable<methods::Shutdown>) {
+        let futures : Vec<_> = self.backends.iter_mut()
+            .filter_map(|backend| send_lsp_request::<methods::Shutdown>(&mut backend.endpoint, ()).ok())
+            .collect();
+        for future in futures {
+            let _ = future.wait();
+        }
+        completable.complete(Ok(()))
+    }
+
+    fn exit(&mut self, _: ()) {
+        for backend in &mut self.backends {
+            let _ = send_lsp_notification::<methods::Exit>(&mut backend.endpoint, ());
+            backend.join();
+        }
+    }
+
+    fn workspace_change_configuration(&mut self, params: DidChangeConfigurationParams) {
+        self.notify_all::<methods::DidChangeConfiguration>(params)
+    }
+
+    fn did_change_watched_files(&mut self, params: DidChangeWatchedFilesParams) {
+        self.notify_all::<methods::DidChangeWatchedFiles>(params)
+    }
+
+    fn did_open_text_document(&mut self, params: DidOpenTextDocumentParams) {
+        let uri = params.text_document.uri.clone();
+        let backend_ix = self.backends.iter().position(|backend| {
+            backend.selector.matches(&uri, Some(&params.text_document.language_id))
+        });
+        if let Some(backend_ix) = backend_ix {
+            self.documents.insert(uri, backend_ix);
+            let _ = send_lsp_notification::<methods::DidOpenTextDocument>(&mut self.backends[backend_ix].endpoint, params);
+        }
+    }
+
+    fn did_change_text_document(&mut self, params: DidChangeTextDocumentParams) {
+        let uri = params.text_document.uri.clone();
+        self.notify_document::<methods::DidChangeTextDocument>(&uri, params)
+    }
+
+    fn did_close_text_document(&mut self, params: DidCloseTextDocumentParams) {
+        let uri = params.text_document.uri.clone();
+        self.notify_document::<methods::DidCloseTextDocument>(&uri, params);
+        self.documents.remove(&uri);
+    }
+
+    fn did_save_text_document(&mut self, params: DidSaveTextDocumentParams) {
+        let uri = params.text_document.uri.clone();
+        self.notify_document::<methods::DidSaveTextDocument>(&uri, params)
+    }
+
+    fn completion(&mut self, params: TextDocumentPositionParams, completable: LSMethodCompletable<methods::Completion>) {
+        let uri = params.text_document.uri.clone();
+        self.forward_document::<methods::Completion>(&uri, params, completable)
+    }
+    fn resolve_completion_item(&mut self, params: LSCompletionItem, completable: LSMethodCompletable<methods::ResolveCompletionItem>) {
+        let backend_ix = self.last_backend;
+        self.forward::<methods::ResolveCompletionItem>(backend_ix, params, completable)
+    }
+    fn hover(&mut self, params: TextDocumentPositionParams, completable: LSMethodCompletable<methods::HoverRequest>) {
+        let uri = params.text_document.uri.clone();
+        self.forward_document::<methods::HoverRequest>(&uri, params, completable)
+    }
+    fn signature_help(&mut self, params: TextDocumentPositionParams, completable: LSMethodCompletable<methods::SignatureHelpRequest>) {
+        let uri = params.text_document.uri.clone();
+        self.forward_document::<methods::SignatureHelpRequest>(&uri, params, completable)
+    }
+    fn goto_definition(&mut self, params: TextDocumentPositionParams, completable: LSMethodCompletable<methods::GotoDefinition>) {
+        let uri = params.text_document.uri.clone();
+        self.forward_document::<methods::GotoDefinition>(&uri, params, completable)
+    }
+    fn goto_type_definition(&mut self, params: TextDocumentPositionParams, completable: LSMethodCompletable<methods::GotoTypeDefinition>) {
+        let uri = params.text_document.uri.clone();
+        self.forward_document::<methods::GotoTypeDefinition>(&uri, params, completable)
+    }
+    fn goto_implementation(&mut self, params: TextDocumentPositionParams, completable: LSMethodCompletable<methods::GotoImplementation>) {
+        let uri = params.text_document.uri.clone();
+        self.forward_document::<methods::GotoImplementation>(&uri, params, completable)
+    }
+    fn references(&mut self, params: ReferenceParams, completable: LSMethodCompletable<methods::References>) {
+        let uri = params.text_document.uri.clone();
+        self.forward_document::<methods::References>(&uri, params, completable)
+    }
+    fn document_highlight(&mut self, params: TextDocumentPositionParams, completable: LSMethodCompletable<methods::DocumentHighlightRequest>) {
+        let uri = params.text_document.uri.clone();
+        self.forward_document::<methods::DocumentHighlightRequest>(&uri, params, completable)
+    }
+    fn document_symbols(&mut self, params: DocumentSymbolParams, completable: LSMethodCompletable<methods::DocumentSymbols>) {
+        let uri = params.text_document.uri.clone();
+        self.forward_document::<methods::DocumentSymbols>(&uri, params, completable)
+    }
+    fn workspace_symbols(&mut self, params: WorkspaceSymbolParams, completable: LSMethodCompletable<methods::WorkspaceSymbol>) {
+        let futures : Vec<_> = self.backends.iter_mut()
+            .filter_map(|backend| send_lsp_request::<methods::WorkspaceSymbol>(&mut backend.endpoint, params.clone()).ok())
+            .collect();
+        thread::spawn(move || {
+            let mut symbols = vec![];
+            for future in futures {
+                // A backend that doesn't support it contributes no symbols
+                if let Ok(Ok(backend_symbols)) = future.wait() {
+                    symbols.extend(backend_symbols);
+                }
+            }
+            completable.complete(Ok(symbols))
+        });
+    }
+    fn code_action(&mut self, params: CodeActionParams, completable: LSMethodCompletable<methods::CodeActionRequest>) {
+        let uri = params.text_document.uri.clone();
+        self.forward_document::<methods::CodeActionRequest>(&uri, params, completable)
+    }
+    fn code_lens(&mut self, params: CodeLensParams, completable: LSMethodCompletable<methods::CodeLensRequest>) {
+        let uri = params.text_document.uri.clone();
+        self.forward_document::<methods::CodeLensRequest>(&uri, params, completable)
+    }
+    fn code_lens_resolve(&mut self, params: CodeLens, completable: LSMethodCompletable<methods::CodeLensResolve>) {
+        let backend_ix = self.last_backend;
+        self.forward::<methods::CodeLensResolve>(backend_ix, params, completable)
+    }
+    fn document_link(&mut self, params: DocumentLinkParams, completable: LSMethodCompletable<methods::DocumentLinkRequest>) {
+        let uri = params.text_document.uri.clone();
+        self.forward_document::<methods::DocumentLinkRequest>(&uri, params, completable)
+    }
+    fn document_link_resolve(&mut self, params: DocumentLink, completable: LSMethodCompletable<methods::DocumentLinkResolve>) {
+        let backend_ix = self.last_backend;
+        self.forward::<methods::DocumentLinkResolve>(backend_ix, params, completable)
+    }
+    fn formatting(&mut self, params: DocumentFormattingParams, completable: LSMethodCompletable<methods::Formatting>) {
+        let uri = params.text_document.uri.clone();
+        self.forward_document::<methods::Formatting>(&uri, params, completable)
+    }
+    fn range_formatting(&mut self, params: DocumentRangeFormattingParams, completable: LSMethodCompletable<methods::RangeFormatting>) {
+        let uri = params.text_document.uri.clone();
+        self.forward_document::<methods::RangeFormatting>(&uri, params, completable)
+    }
+    fn on_type_formatting(&mut self, params: DocumentOnTypeFormattingParams, completable: LSMethodCompletable<methods::OnTypeFormatting>) {
+        let uri = params.text_document.uri.clone();
+        self.forward_document::<methods::OnTypeFormatting>(&uri, params, completable)
+    }
+    fn rename(&mut self, params: RenameParams, completable: LSMethodCompletable<methods::Rename>) {
+        let uri = params.text_document.uri.clone();
+        self.forward_document::<methods::Rename>(&uri, params, completable)
+    }
+
+    fn handle_other_method(&mut self, request: &RequestInfo, method_name: &str, params: RequestParams,
+        completable: ResponseCompletable)
+    {
+        let backend_ix = match document_uri_of_params(&params).and_then(|uri| Url::parse(uri).ok()) {
+            Some(uri) => self.backend_for(&uri, None),
+            None => self.last_backend.or(if self.backends.is_empty() { None } else { Some(0) }),
+        };
+        match backend_ix {
+            Some(backend_ix) => {
+                forward_other_method(&mut self.backends[backend_ix].endpoint, request, method_name, params, completable)
+            }
+            None => handle_unknown_method(request, method_name, completable),
+        }
+    }
+
+}
+
+impl Drop for MultiplexServer {
+    fn drop(&mut self) {
+        for backend in &mut self.backends {
+            if backend.server_thread.is_some() {
+                let _ = send_lsp_notification::<methods::Exit>(&mut backend.endpoint, ());
+                backend.join();
+            }
+        }
+    }
+}
+
+
+#[cfg(test)]
+struct LanguageServer(&'static str);
+
+#[cfg(test)]
+impl LanguageServerHandling for LanguageServer {
+
+    fn server_capabilities(&self) -> ServerCapabilities {
+        let mut completion = CompletionOptions::default();
+        completion.trigger_characters = Some(vec![self.0.to_string()]);
+        InitializeResultBuilder::new().hover().completion(completion).build().capabilities
+    }
+
+    fn hover(&mut self, _: TextDocumentPositionParams, completable: LSMethodCompletable<methods::HoverRequest>) {
+        let hover = Hover { contents : vec![MarkedString::String(self.0.to_string())], range : None };
+        completable.complete(Ok(LSHover::from(hover)))
+    }
+
+}
+
+#[test]
+fn multiplex_server__test() {
+    use lsp_testing::ServerTestHarness;
+
+    let mut harness = ServerTestHarness::start(|context| {
+        MultiplexServer::new(context)
+            .backend(DocumentSelector::language("rust"), |_| LanguageServer("."))
+            .backend(DocumentSelector::pattern("**/*.toml").unwrap(), |_| LanguageServer("["))
+    });
+    let result = harness.initialize(Value::Object(JsonObject::new())).unwrap();
+    let trigger_characters = result.capabilities.completion_provider.unwrap().trigger_characters;
+    assert_eq!(trigger_characters, Some(vec![".".to_string(), "[".to_string()]));
+
+    let rust_uri = Url::parse("file:///src/main.rs").unwrap();
+    let toml_uri = Url::parse("file:///Cargo.toml").unwrap();
+    harness.did_open(&rust_uri, "rust", 1, "fn main() {}").unwrap();
+    let hover_text = |harness: &mut ServerTestHarness, uri: &Url| {
+        let params = TextDocumentPositionParams {
+            text_document : TextDocumentIdentifier { uri : uri.clone() },
+            position : Position { line : 0, character : 0 },
+        };
+        harness.request::<methods::HoverRequest>(params).map(|hover| format!("{:?}", hover.contents))
+    };
+    assert!(hover_text(&mut harness, &rust_uri).unwrap().contains("\".\""));
+    assert!(hover_text(&mut harness, &toml_uri).unwrap().contains("\"[\""));
+    assert!(hover_text(&mut harness, &Url::parse("file:///README.md").unwrap()).is_err());
+
+    assert_eq!(harness.finish(), 0);
+}
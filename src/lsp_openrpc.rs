@@ -0,0 +1,199 @@
+// Copyright 2016 Bruno Medeiros
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>.
+// This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! OpenRPC documents describing the methods of a server, with JSON Schemas of their params
+//! and results, for client tooling. A document can be dumped to disk, or served with the
+//! `rpc.discover` method from `LanguageServerHandling::handle_other_method`:
+//!
+//! ```ignore
+//! fn handle_other_method(&mut self, request: &RequestInfo, method_name: &str, params: RequestParams,
+//!     completable: ResponseCompletable)
+//! {
+//!     if let Some(completable) = self.openrpc.handle_discover(method_name, params, completable) {
+//!         completable.complete_with_error(jsonrpc_common::error_JSON_RPC_MethodNotFound());
+//!     }
+//! }
+//! ```
+
+use std::fs::File;
+use std::io;
+use std::io::Write;
+use std::path::Path;
+
+use jsonrpc::*;
+use jsonrpc::json_util::JsonObject;
+use jsonrpc::jsonrpc_request::RequestParams;
+use jsonrpc::method_types::MethodCompletable;
+use serde_json;
+use serde_json::Value;
+
+use lsp_methods::LSNotification;
+use lsp_methods::LSRequest;
+
+/// The OpenRPC service discovery method.
+pub const RPC_DISCOVER: &'static str = "rpc.discover";
+
+pub const OPENRPC_VERSION: &'static str = "1.2.6";
+
+/// A method of a server, with the JSON Schemas of its params and result.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MethodDescription {
+    pub name: String,
+    pub summary: Option<String>,
+    pub params_schema: Value,
+    /// `None` for notifications.
+    pub result_schema: Option<Value>,
+}
+
+impl MethodDescription {
+
+    pub fn to_json(&self) -> Value {
+        let mut obj = JsonObject::new();
+        obj.insert("name".to_string(), Value::String(self.name.clone()));
+        if let Some(ref summary) = self.summary {
+            obj.insert("summary".to_string(), Value::String(summary.clone()));
+        }
+        // LSP params are a single object, so each of its properties is a param by name
+        obj.insert("paramStructure".to_string(), Value::String("by-name".to_string()));
+        obj.insert("params".to_string(), Value::Array(params_of_schema(&self.params_schema)));
+        if let Some(ref result_schema) = self.result_schema {
+            obj.insert("result".to_string(), content_descriptor("result", result_schema, None));
+        }
+        Value::Object(obj)
+    }
+
+}
+
+fn params_of_schema(schema: &Value) -> Vec<Value> {
+    let properties = match schema.find("properties").and_then(Value::as_object) {
+        Some(properties) => properties,
+        None => return vec![],
+    };
+    let required : Vec<&str> = schema.find("required").and_then(Value::as_array)
+        .map(|required| required.iter().filter_map(Value::as_str).collect())
+        .unwrap_or_default();
+    properties.iter().map(|(name, schema)| {
+        content_descriptor(name, schema, Some(required.contains(&&name[..])))
+    }).collect()
+}
+
+fn content_descriptor(name: &str, schema: &Value, required: Option<bool>) -> Value {
+    let mut obj = JsonObject::new();
+    obj.insert("name".to_string(), Value::String(name.to_string()));
+    obj.insert("schema".to_string(), schema.clone());
+    if let Some(required) = required {
+        obj.insert("required".to_string(), Value::Bool(required));
+    }
+    Value::Object(obj)
+}
+
+/// An OpenRPC document of the methods of a server, in the order they were added.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OpenRpcDocument {
+    pub title: String,
+    pub version: String,
+    pub methods: Vec<MethodDescription>,
+}
+
+impl OpenRpcDocument {
+
+    /// Create a document for the server of given title and version, with no methods.
+    pub fn new(title: &str, version: &str) -> OpenRpcDocument {
+        OpenRpcDocument { title : title.to_string(), version : version.to_string(), methods : vec![] }
+    }
+
+    pub fn method(mut self, description: MethodDescription) -> Self {
+        self.methods.push(description);
+        self
+    }
+
+    /// Add request `REQ`, with the schemas of its params and result.
+    pub fn request<REQ : LSRequest>(self, params_schema: Value, result_schema: Value) -> Self {
+        self.method(MethodDescription {
+            name : REQ::METHOD.to_string(),
+            summary : None,
+            params_schema : params_schema,
+            result_schema : Some(result_schema),
+        })
+    }
+
+    /// Add notification `NOTIF`, with the schema of its params.
+    pub fn notification<NOTIF : LSNotification>(self, params_schema: Value) -> Self {
+        self.method(MethodDescription {
+            name : NOTIF::METHOD.to_string(),
+            summary : None,
+            params_schema : params_schema,
+            result_schema : None,
+        })
+    }
+
+    pub fn to_json(&self) -> Value {
+        let mut info = JsonObject::new();
+        info.insert("title".to_string(), Value::String(self.title.clone()));
+        info.insert("version".to_string(), Value::String(self.version.clone()));
+
+        let mut obj = JsonObject::new();
+        obj.insert("openrpc".to_string(), Value::String(OPENRPC_VERSION.to_string()));
+        obj.insert("info".to_string(), Value::Object(info));
+        obj.insert("methods".to_string(), Value::Array(self.methods.iter().map(MethodDescription::to_json).collect()));
+        Value::Object(obj)
+    }
+
+    /// Write this document as JSON to the file at given path.
+    pub fn write_to(&self, path: &Path) -> io::Result<()> {
+        let json = serde_json::to_string_pretty(&self.to_json()).unwrap_or_default();
+        let mut file = try!(File::create(path));
+        file.write_all(json.as_bytes())
+    }
+
+    /// Answer given request with this document if it is `rpc.discover`.
+    /// Otherwise gives back the completable, for the request to be handled otherwise.
+    pub fn handle_discover(&self, method_name: &str, params: RequestParams, completable: ResponseCompletable)
+        -> Option<ResponseCompletable>
+    {
+        if method_name != RPC_DISCOVER {
+            return Some(completable);
+        }
+        let document = self.to_json();
+        completable.handle_request_with(params,
+            move |_: Value, completable: MethodCompletable<Value, ()>| completable.complete(Ok(document)));
+        None
+    }
+
+}
+
+
+#[test]
+fn openrpc_document__test() {
+    use lsp_methods as methods;
+
+    let position_params = serde_json::from_str::<Value>(r#"{
+        "type": "object",
+        "properties": { "textDocument": { "type": "object" }, "position": { "type": "object" } },
+        "required": ["position"]
+    }"#).unwrap();
+    let document = OpenRpcDocument::new("Test server", "1.0")
+        .request::<methods::HoverRequest>(position_params, serde_json::from_str(r#"{ "type": "object" }"#).unwrap())
+        .notification::<methods::Exit>(Value::Null);
+
+    let json = document.to_json();
+    assert_eq!(json.lookup("openrpc"), Some(&Value::String(OPENRPC_VERSION.to_string())));
+    assert_eq!(json.lookup("info.title"), Some(&Value::String("Test server".to_string())));
+
+    let hover = &json.find("methods").and_then(Value::as_array).unwrap()[0];
+    assert_eq!(hover.find("name"), Some(&Value::String("textDocument/hover".to_string())));
+    assert_eq!(hover.lookup("result.schema.type"), Some(&Value::String("object".to_string())));
+    let params = hover.find("params").and_then(Value::as_array).unwrap();
+    let params : Vec<(&str, bool)> = params.iter().map(|param| {
+        (param.find("name").and_then(Value::as_str).unwrap(), param.find("required").and_then(Value::as_bool).unwrap())
+    }).collect();
+    assert_eq!(params, vec![("position", true), ("textDocument", false)]);
+
+    let exit = &json.find("methods").and_then(Value::as_array).unwrap()[1];
+    assert_eq!(exit.find("result"), None);
+    assert_eq!(exit.find("params"), Some(&Value::Array(vec![])));
+}
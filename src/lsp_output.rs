@@ -0,0 +1,1046 @@
+// Copyright 2016 Bruno Medeiros
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>.
+// This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! The output of an endpoint through a channel to a writer thread, with cheap, cloneable
+//! `OutputSender` handles. Handlers can send notifications with a sender concurrently,
+//! without locking the `Endpoint`, and the messages are serialized on the sending thread.
+//! The endpoint itself writes straight to the writer of the channel, in its own output thread,
+//! after the messages queued before. Responses completed with `OutputSender::complete_request`
+//! are serialized in the writer thread instead, straight to the output with a streaming writer.
+
+use std::collections::VecDeque;
+use std::mem;
+use std::sync::Arc;
+use std::sync::Condvar;
+use std::sync::Mutex;
+use std::sync::MutexGuard;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use util::core::*;
+
+use jsonrpc::*;
+use serde;
+use jsonrpc::json_util::JsonObject;
+use serde_json;
+use serde_json::Value;
+
+use lsp::ClientRequestHandler;
+use lsp::LSPEndpoint;
+use lsp::LSRequestFuture;
+use lsp::LSResult;
+use lsp::LSSendResult;
+use lsp::LSServerContext;
+use lsp::LanguageClientHandling;
+use lsp::LanguageServerHandling;
+use lsp::RequestInfo;
+use lsp::send_lsp_request;
+use lsp_diagnostics::LSPublishDiagnosticsParams;
+use lsp_errors::ERROR_CODE__InternalError;
+use lsp_errors::LSError;
+use lsp_errors::error_LSP_RequestFailed;
+use lsp_methods as methods;
+use lsp_methods::LSNotification;
+use lsp_methods::LSRequest;
+use lsp_transport::JsonRpcMessage;
+use lsp_transport::JsonRpcResponse;
+use lsp_transport::SerializeMessage;
+use lsp_transport::StreamingMessageWriter;
+
+enum OutputMessage {
+    /// A serialized JSON-RPC message.
+    Text(String),
+    /// A response serialized in the writer thread, straight to the output if the writer can stream it.
+    Response(Value, Box<SerializeMessage + Send>),
+}
+
+/// A response that owns its result, to serialize in the writer thread.
+struct OwnedResponse<RET, ERR_DATA> {
+    id: Value,
+    result: LSResult<RET, ERR_DATA>,
+}
+
+impl<RET : serde::Serialize, ERR_DATA : serde::Serialize> serde::Serialize for OwnedResponse<RET, ERR_DATA> {
+    fn serialize<S>(&self, serializer: &mut S) -> Result<(), S::Error>
+    where
+        S : serde::Serializer,
+    {
+        JsonRpcResponse { id : &self.id, result : &self.result }.serialize(serializer)
+    }
+}
+
+impl OutputMessage {
+
+    fn response<RET, ERR_DATA>(id: &Value, result: LSResult<RET, ERR_DATA>) -> OutputMessage
+    where
+        RET : serde::Serialize + Send + 'static,
+        ERR_DATA : serde::Serialize + Send + 'static,
+    {
+        OutputMessage::Response(id.clone(), Box::new(OwnedResponse { id : id.clone(), result : result }))
+    }
+
+}
+
+/// The response for a request whose result failed to serialize.
+fn internal_error_response(id: &Value) -> String {
+    let mut error_obj = JsonObject::new();
+    error_obj.insert("code".to_string(), Value::I64(ERROR_CODE__InternalError));
+    error_obj.insert("message".to_string(), Value::String("Failed to serialize result.".to_string()));
+    let mut response = JsonObject::new();
+    response.insert("jsonrpc".to_string(), Value::String("2.0".to_string()));
+    response.insert("id".to_string(), id.clone());
+    response.insert("error".to_string(), Value::Object(error_obj));
+    Value::Object(response).to_string()
+}
+
+enum OutputCommand {
+    Message(OutputMessage),
+    /// Messages written one after the other, without other messages between them.
+    Group(Vec<OutputMessage>),
+    /// Write the following messages, and the ones not yet written, with another writer.
+    ReplaceWriter(Box<StreamingMessageWriter + Send>),
+}
+
+#[derive(Default)]
+struct OutputQueue {
+    commands: VecDeque<OutputCommand>,
+    shutdown: bool,
+}
+
+impl OutputQueue {
+
+    /// Whether there is a command that can be run: any, unless the writer failed,
+    /// in which case only a replacement of the writer.
+    fn has_runnable(&self, failed: bool) -> bool {
+        if !failed {
+            return !self.commands.is_empty();
+        }
+        self.commands.iter().any(|command| match *command {
+            OutputCommand::ReplaceWriter(_) => true,
+            _ => false,
+        })
+    }
+
+    fn has_messages(&self) -> bool {
+        self.commands.iter().any(|command| match *command {
+            OutputCommand::ReplaceWriter(_) => false,
+            _ => true,
+        })
+    }
+
+}
+
+/// The state of an `OutputChannel`, shared by its senders, its writer thread, and the endpoint writer.
+struct OutputShared {
+    queue: Mutex<OutputQueue>,
+    /// Notified when a command is queued, and on shutdown.
+    queued: Condvar,
+    /// Locked while writing, before the queue, so that the messages are written one at a time, in order.
+    writer: Mutex<Box<StreamingMessageWriter + Send>>,
+    failed: AtomicBool,
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<T> {
+    // A writer panicking doesn't leave the queue inconsistent
+    match mutex.lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+    }
+}
+
+impl OutputShared {
+
+    fn send(&self, command: OutputCommand) -> LSSendResult<()> {
+        let mut queue = lock(&self.queue);
+        if queue.shutdown {
+            return Err(output_closed_error());
+        }
+        queue.commands.push_back(command);
+        self.queued.notify_all();
+        Ok(())
+    }
+
+    /// Run the queued commands with given writer, which must be the locked `self.writer`.
+    /// After a failed write, the messages are kept until the writer is replaced.
+    fn write_queued(&self, msg_writer: &mut Box<StreamingMessageWriter + Send>) {
+        loop {
+            let command = {
+                let mut queue = lock(&self.queue);
+                if !self.failed.load(Ordering::SeqCst) {
+                    queue.commands.pop_front()
+                } else {
+                    let replace_ix = queue.commands.iter().position(|command| match *command {
+                        OutputCommand::ReplaceWriter(_) => true,
+                        _ => false,
+                    });
+                    replace_ix.and_then(|ix| queue.commands.remove(ix))
+                }
+            };
+            let unwritten = match command {
+                None => return,
+                Some(OutputCommand::Message(message)) => {
+                    write_messages(msg_writer, vec![message]).map(OutputCommand::Group)
+                }
+                Some(OutputCommand::Group(messages)) => {
+                    write_messages(msg_writer, messages).map(OutputCommand::Group)
+                }
+                Some(OutputCommand::ReplaceWriter(new_writer)) => {
+                    *msg_writer = new_writer;
+                    self.failed.store(false, Ordering::SeqCst);
+                    None
+                }
+            };
+            if let Some(unwritten) = unwritten {
+                lock(&self.queue).commands.push_front(unwritten);
+                self.failed.store(true, Ordering::SeqCst);
+            }
+        }
+    }
+
+}
+
+/// Write given messages in order. Returns the ones not written if a write failed,
+/// starting with the one that failed.
+fn write_messages(msg_writer: &mut Box<StreamingMessageWriter + Send>, messages: Vec<OutputMessage>)
+    -> Option<Vec<OutputMessage>>
+{
+    let mut messages = messages.into_iter();
+    while let Some(message) = messages.next() {
+        let written = match message {
+            OutputMessage::Text(ref text) => msg_writer.write_message(text).map_err(LSError::from_gerror),
+            OutputMessage::Response(ref id, ref response) => match msg_writer.write_streaming(&**response) {
+                Err(LSError::Parse(..)) => {
+                    // Nothing was written, as the message is serialized before it is written out
+                    error!("Failed to serialize the response to request {}.", id);
+                    msg_writer.write_message(&internal_error_response(id)).map_err(LSError::from_gerror)
+                }
+                written => written,
+            },
+        };
+        if let Err(error) = written {
+            error!("Failed to write message, output paused until the writer is replaced: {}", error);
+            return Some(Some(message).into_iter().chain(messages).collect());
+        }
+    }
+    None
+}
+
+/// A handle to send messages to an `OutputChannel`. Messages sent through the same sender
+/// (or clones of it) from the same thread are written in the order they were sent.
+#[derive(Clone)]
+pub struct OutputSender {
+    shared: Arc<OutputShared>,
+}
+
+fn output_closed_error() -> LSError {
+    LSError::Shutdown("Cannot send message, output is shut down.".to_string())
+}
+
+fn serialize_notification<PARAMS>(method: &str, params: &PARAMS) -> LSSendResult<String>
+where
+    PARAMS : serde::Serialize,
+{
+    let message = JsonRpcMessage { id : None, method : method, params : params };
+    serde_json::to_string(&message).map_err(|error| {
+        LSError::Parse("Failed to serialize message:".to_string(), Some(error.into()))
+    })
+}
+
+impl OutputSender {
+
+    /// Send given message content, which must be a serialized JSON-RPC message.
+    pub fn send_message(&self, message: String) -> LSSendResult<()> {
+        self.shared.send(OutputCommand::Message(OutputMessage::Text(message)))
+    }
+
+    /// Complete given request with given result, writing the response from this channel,
+    /// instead of from the endpoint: it is serialized in the writer thread, straight to the output
+    /// if the writer can stream it (see `OutputChannel::start_streaming`), such as for a large result.
+    /// The completable is completed without a response for the endpoint to write.
+    pub fn complete_request<RET, ERR_DATA>(&self, request: &RequestInfo, completable: ResponseCompletable,
+        result: LSResult<RET, ERR_DATA>) -> LSSendResult<()>
+    where
+        RET : serde::Serialize + Send + 'static,
+        ERR_DATA : serde::Serialize + Send + 'static,
+    {
+        let request_id = match request.id {
+            Some(ref request_id) => request_id,
+            // A notification has no response
+            None => return Ok(completable.complete(None)),
+        };
+        let sent = self.shared.send(OutputCommand::Message(OutputMessage::response(request_id, result)));
+        complete_written_response(completable, sent.is_ok());
+        sent
+    }
+
+    pub fn send_notification<PARAMS>(&self, method: &str, params: &PARAMS) -> LSSendResult<()>
+    where
+        PARAMS : serde::Serialize,
+    {
+        let message = try!(serialize_notification(method, params));
+        self.send_message(message)
+    }
+
+    /// Send a notification for LSP method `NOTIF`, with its typed params.
+    pub fn send_lsp_notification<NOTIF>(&self, params: &NOTIF::Params) -> LSSendResult<()>
+    where
+        NOTIF : LSNotification,
+    {
+        self.send_notification(NOTIF::METHOD, params)
+    }
+
+    /// Start a group of messages, which are written together when the group is sent:
+    /// messages from other threads are written either before or after all of them.
+    pub fn group(&self) -> MessageGroup {
+        MessageGroup { sender : self.clone(), messages : vec![], completables : vec![] }
+    }
+
+}
+
+/// Complete the completable of a response written from the channel: without a response for the endpoint
+/// to write if the response was sent to the channel, with an error response otherwise.
+fn complete_written_response(completable: ResponseCompletable, sent: bool) {
+    if sent {
+        completable.complete(None)
+    } else {
+        completable.complete_with_error(error_LSP_RequestFailed("The response was not sent.".to_string()))
+    }
+}
+
+/// Messages to write together, without messages from other threads between them.
+/// Such as the end notification of a `$/progress`, and a response that clients expect after it.
+/// Nothing is written until the group is sent.
+pub struct MessageGroup {
+    sender: OutputSender,
+    messages: Vec<OutputMessage>,
+    /// The completables of the responses of the group, completed once the group is sent, or dropped.
+    completables: Vec<ResponseCompletable>,
+}
+
+impl MessageGroup {
+
+    /// Add given message content, which must be a serialized JSON-RPC message.
+    pub fn message(mut self, message: String) -> Self {
+        self.messages.push(OutputMessage::Text(message));
+        self
+    }
+
+    pub fn notification<PARAMS>(mut self, method: &str, params: &PARAMS) -> LSSendResult<Self>
+    where
+        PARAMS : serde::Serialize,
+    {
+        self.messages.push(OutputMessage::Text(try!(serialize_notification(method, params))));
+        Ok(self)
+    }
+
+    /// Add a notification for LSP method `NOTIF`, with its typed params.
+    pub fn lsp_notification<NOTIF>(self, params: &NOTIF::Params) -> LSSendResult<Self>
+    where
+        NOTIF : LSNotification,
+    {
+        self.notification(NOTIF::METHOD, params)
+    }
+
+    /// Add the response to given request, written from the channel as with `OutputSender::complete_request`,
+    /// such as after the end notification of its `$/progress`. The completable is completed when the group
+    /// is sent, or with an error response if the group fails to be sent, or is dropped.
+    pub fn response<RET, ERR_DATA>(mut self, request: &RequestInfo, completable: ResponseCompletable,
+        result: LSResult<RET, ERR_DATA>) -> Self
+    where
+        RET : serde::Serialize + Send + 'static,
+        ERR_DATA : serde::Serialize + Send + 'static,
+    {
+        match request.id {
+            Some(ref request_id) => {
+                self.messages.push(OutputMessage::response(request_id, result));
+                self.completables.push(completable);
+            }
+            // A notification has no response
+            None => completable.complete(None),
+        }
+        self
+    }
+
+    fn complete_responses(&mut self, sent: bool) {
+        for completable in self.completables.drain(..) {
+            complete_written_response(completable, sent);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.messages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+
+    /// Send all the messages of this group at once.
+    pub fn send(mut self) -> LSSendResult<()> {
+        let messages = mem::replace(&mut self.messages, vec![]);
+        if messages.is_empty() {
+            return Ok(());
+        }
+        let sent = self.sender.shared.send(OutputCommand::Group(messages));
+        self.complete_responses(sent.is_ok());
+        sent
+    }
+
+}
+
+impl Drop for MessageGroup {
+    fn drop(&mut self) {
+        self.complete_responses(false);
+    }
+}
+
+/// A handle for worker threads to send notifications to the client, such as progress or diagnostics,
+/// without locking the `Endpoint`. Clones are cheap and share the same output channel.
+///
+/// Ordering: the notifications sent from one thread are written in the order they were sent.
+/// A notification sent while handling a request, before its response is completed, is written
+/// before that response (when the endpoint writes to the same channel, as created by
+/// `create_endpoint_with_channel`). There is no ordering between notifications and responses
+/// completed before them, nor between the notifications of different threads.
+#[derive(Clone)]
+pub struct NotificationSender {
+    output: OutputSender,
+}
+
+impl NotificationSender {
+
+    pub fn new(output: OutputSender) -> NotificationSender {
+        NotificationSender { output : output }
+    }
+
+    /// Send a notification for LSP method `NOTIF`, with its typed params.
+    pub fn send<NOTIF>(&self, params: &NOTIF::Params) -> LSSendResult<()>
+    where
+        NOTIF : LSNotification,
+    {
+        self.output.send_lsp_notification::<NOTIF>(params)
+    }
+
+    pub fn publish_diagnostics(&self, params: &LSPublishDiagnosticsParams) -> LSSendResult<()> {
+        self.send::<methods::PublishDiagnostics>(params)
+    }
+
+    /// Send a `$/progress` notification, with given progress token and value.
+    pub fn progress<VALUE>(&self, token: &Value, value: &VALUE) -> LSSendResult<()>
+    where
+        VALUE : serde::Serialize,
+    {
+        // Unlike `serde_json::to_value`, this reports errors
+        let mut serializer = serde_json::value::Serializer::new();
+        try!(value.serialize(&mut serializer).map_err(|error| {
+            LSError::Parse("Failed to serialize progress value:".to_string(), Some(error.into()))
+        }));
+        let mut params = JsonObject::new();
+        params.insert("token".to_string(), token.clone());
+        params.insert("value".to_string(), serializer.unwrap());
+        self.output.send_notification(methods::PROGRESS, &params)
+    }
+
+    /// Start a group of messages to write together. See `OutputSender::group`.
+    pub fn group(&self) -> MessageGroup {
+        self.output.group()
+    }
+
+}
+
+/// Lets an `Endpoint` write its messages to the channel.
+impl MessageWriter for OutputSender {
+    fn write_message(&mut self, msg: &str) -> GResult<()> {
+        self.send_message(msg.to_string()).map_err(GError::from)
+    }
+}
+
+/// A writer thread, that writes the messages sent to it with its `OutputSender`s.
+pub struct OutputChannel {
+    sender: OutputSender,
+    thread: Option<thread::JoinHandle<()>>,
+    /// Receives whether the writer thread wrote all the messages, when it ends.
+    done: mpsc::Receiver<bool>,
+}
+
+impl OutputChannel {
+
+    /// Start the writer thread, which writes with the writer given by `msg_writer_provider`.
+    pub fn start<MW, MW_PROV>(msg_writer_provider: MW_PROV) -> OutputChannel
+    where
+        MW : MessageWriter + Send + 'static,
+        MW_PROV : FnOnce() -> MW + Send + 'static,
+    {
+        Self::start_with(Box::new(SerializingWriter(msg_writer_provider())))
+    }
+
+    /// Start the writer thread as `start` does, with a writer that writes the responses of `complete_request`
+    /// straight from their result, such as `LSPBufferedMessageWriter`.
+    pub fn start_streaming<MW, MW_PROV>(msg_writer_provider: MW_PROV) -> OutputChannel
+    where
+        MW : StreamingMessageWriter + Send + 'static,
+        MW_PROV : FnOnce() -> MW + Send + 'static,
+    {
+        Self::start_with(Box::new(msg_writer_provider()))
+    }
+
+    fn start_with(msg_writer: Box<StreamingMessageWriter + Send>) -> OutputChannel {
+        let shared = Arc::new(OutputShared {
+            queue : Mutex::new(OutputQueue::default()), queued : Condvar::new(),
+            writer : Mutex::new(msg_writer), failed : AtomicBool::new(false),
+        });
+        let (done_sender, done) = mpsc::channel();
+        let thread_shared = shared.clone();
+        let thread = thread::spawn(move || {
+            let _ = done_sender.send(run_writer(&thread_shared));
+        });
+        OutputChannel { sender : OutputSender { shared : shared }, thread : Some(thread), done : done }
+    }
+
+    pub fn sender(&self) -> OutputSender {
+        self.sender.clone()
+    }
+
+    /// Whether a write failed, such as when the client disconnected. The output is then paused,
+    /// keeping the messages sent, until the writer is replaced.
+    pub fn has_failed(&self) -> bool {
+        self.sender.shared.failed.load(Ordering::SeqCst)
+    }
+
+    /// Write the messages with given writer from now on, such as after reconnecting a socket.
+    /// The messages sent before are written with the previous writer, if it has not failed,
+    /// or with the new one otherwise, starting with the message whose write failed.
+    pub fn replace_writer<MW>(&self, msg_writer: MW) -> LSSendResult<()>
+    where
+        MW : MessageWriter + Send + 'static,
+    {
+        self.sender.shared.send(OutputCommand::ReplaceWriter(Box::new(SerializingWriter(msg_writer))))
+    }
+
+    /// Create an Endpoint that writes its messages straight to the writer of this channel.
+    /// See `create_endpoint_with_channel`.
+    pub fn create_endpoint(&self) -> Endpoint {
+        let endpoint_writer = EndpointWriter { shared : self.sender.shared.clone() };
+        LSPEndpoint::create_lsp_output(move || endpoint_writer)
+    }
+
+    fn close(&self) {
+        lock(&self.sender.shared.queue).shutdown = true;
+        self.sender.shared.queued.notify_all();
+    }
+
+    /// Stop the writer thread, after the messages sent so far are written, and wait for it.
+    /// Messages sent afterwards fail with `LSError::Shutdown`.
+    pub fn shutdown_and_join(&mut self) {
+        self.close();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+
+    /// Stop the writer thread as `shutdown_and_join` does, but wait for it only up to given timeout,
+    /// such as when the writer may be blocked on a pipe that nobody reads. After the timeout,
+    /// the thread is abandoned.
+    ///
+    /// Returns whether all the messages sent so far were written: false if the thread was abandoned,
+    /// or if its writer failed and was not replaced, in which case the messages not yet written are lost.
+    pub fn shutdown_with_timeout(&mut self, timeout: Duration) -> bool {
+        self.close();
+        let thread = match self.thread.take() {
+            Some(thread) => thread,
+            None => return true,
+        };
+        match self.done.recv_timeout(timeout) {
+            Ok(written_all) => {
+                let _ = thread.join();
+                written_all
+            }
+            Err(mpsc::RecvTimeoutError::Disconnected) => {
+                // The writer panicked
+                let _ = thread.join();
+                false
+            }
+            Err(mpsc::RecvTimeoutError::Timeout) => {
+                warn!("Output writer did not finish after {:?}, pending messages are lost.", timeout);
+                false
+            }
+        }
+    }
+
+}
+
+/// Write the messages as they are queued, until a shutdown. Returns whether all the messages were written.
+fn run_writer(shared: &OutputShared) -> bool {
+    loop {
+        shared.write_queued(&mut lock(&shared.writer));
+        let mut queue = lock(&shared.queue);
+        while !queue.shutdown && !queue.has_runnable(shared.failed.load(Ordering::SeqCst)) {
+            queue = match shared.queued.wait(queue) {
+                Ok(queue) => queue,
+                Err(poisoned) => poisoned.into_inner(),
+            };
+        }
+        if queue.shutdown && !queue.has_runnable(shared.failed.load(Ordering::SeqCst)) {
+            return !queue.has_messages();
+        }
+    }
+}
+
+impl Drop for OutputChannel {
+    fn drop(&mut self) {
+        self.shutdown_and_join();
+    }
+}
+
+/// Writes the responses of `complete_request` with a writer that can't stream them, by serializing them first.
+struct SerializingWriter<MW>(MW);
+
+impl<MW : MessageWriter> MessageWriter for SerializingWriter<MW> {
+    fn write_message(&mut self, msg: &str) -> GResult<()> {
+        self.0.write_message(msg)
+    }
+}
+
+impl<MW : MessageWriter> StreamingMessageWriter for SerializingWriter<MW> {
+    fn write_streaming(&mut self, message: &SerializeMessage) -> Result<(), LSError> {
+        let mut content = vec![];
+        try!(message.serialize_to(&mut content).map_err(|error| {
+            LSError::Parse("Failed to serialize message:".to_string(), Some(error.into()))
+        }));
+        let content = try!(String::from_utf8(content).map_err(|error| {
+            LSError::Parse("Serialized message is not UTF-8:".to_string(), Some(error.into()))
+        }));
+        self.0.write_message(&content).map_err(LSError::from_gerror)
+    }
+}
+
+/// Writes the messages of an endpoint straight to the writer of an `OutputChannel`, in the output thread
+/// of the endpoint, after the messages queued before them. If the writer failed, they are queued instead.
+struct EndpointWriter {
+    shared: Arc<OutputShared>,
+}
+
+impl MessageWriter for EndpointWriter {
+    fn write_message(&mut self, msg: &str) -> GResult<()> {
+        if lock(&self.shared.queue).shutdown {
+            return Err(output_closed_error().into());
+        }
+        let mut msg_writer = lock(&self.shared.writer);
+        self.shared.write_queued(&mut msg_writer);
+        if !self.shared.failed.load(Ordering::SeqCst) {
+            match msg_writer.write_message(msg) {
+                Ok(()) => return Ok(()),
+                Err(error) => {
+                    error!("Failed to write message, output paused until the writer is replaced: {}", error);
+                    self.shared.failed.store(true, Ordering::SeqCst);
+                }
+            }
+        }
+        lock(&self.shared.queue).commands.push_back(OutputCommand::Message(OutputMessage::Text(msg.to_string())));
+        Ok(())
+    }
+}
+
+/// Create an Endpoint whose output goes through a new `OutputChannel`, so that the senders
+/// of that channel can send notifications without going through the Endpoint.
+///
+/// The Endpoint writes its messages straight to the writer of the channel, once they are completed,
+/// after the messages queued in the channel before. So a message sent directly with an `OutputSender`
+/// is written before the responses completed after it was sent from the same thread.
+/// See `NotificationSender` for the ordering guarantees.
+pub fn create_endpoint_with_channel<MW, MW_PROV>(msg_writer_provider: MW_PROV) -> (Endpoint, OutputChannel)
+where
+    MW : MessageWriter + Send + 'static,
+    MW_PROV : FnOnce() -> MW + Send + 'static,
+{
+    let channel = OutputChannel::start(msg_writer_provider);
+    let endpoint = channel.create_endpoint();
+    (endpoint, channel)
+}
+
+/* -----------------  Split endpoint  ----------------- */
+
+/// Create an Endpoint whose output goes through a new `OutputChannel`, and split it into
+/// a `RequestSender`, to send messages from anywhere (including from inside the `LanguageServer`),
+/// and a `Dispatcher`, which owns the read loop and the output.
+///
+/// The sender never needs the Endpoint to be locked by the caller, so it can be stored in the
+/// handler without an `Arc<Mutex<Endpoint>>`, which deadlocks when used during dispatch.
+pub fn split_endpoint<MW, MW_PROV>(msg_writer_provider: MW_PROV) -> (RequestSender, Dispatcher)
+where
+    MW : MessageWriter + Send + 'static,
+    MW_PROV : FnOnce() -> MW + Send + 'static,
+{
+    let (endpoint, channel) = create_endpoint_with_channel(msg_writer_provider);
+    let sender = RequestSender { endpoint : endpoint.clone(), output : channel.sender() };
+    (sender, Dispatcher { endpoint : endpoint, channel : channel })
+}
+
+/// The sending half of a split endpoint. Clones can be sent to, and used from, any thread.
+#[derive(Clone)]
+pub struct RequestSender {
+    endpoint: Endpoint,
+    output: OutputSender,
+}
+
+impl RequestSender {
+
+    pub fn output_sender(&self) -> &OutputSender {
+        &self.output
+    }
+
+    pub fn notification_sender(&self) -> NotificationSender {
+        NotificationSender::new(self.output.clone())
+    }
+
+    /// Send a notification for LSP method `NOTIF`, with its typed params.
+    pub fn send_notification<NOTIF>(&self, params: &NOTIF::Params) -> LSSendResult<()>
+    where
+        NOTIF : LSNotification,
+    {
+        self.output.send_lsp_notification::<NOTIF>(params)
+    }
+
+    /// Send a request for LSP method `REQ`, with its typed params.
+    /// The result arrives once the `Dispatcher` reads the response.
+    pub fn send_request<REQ>(&self, params: REQ::Params) -> LSSendResult<LSRequestFuture<REQ>>
+    where
+        REQ : LSRequest,
+    {
+        send_lsp_request::<REQ>(&mut self.endpoint.clone(), params)
+    }
+
+}
+
+/// The dispatching half of a split endpoint: runs the read loop with a handler,
+/// then shuts down the output.
+pub struct Dispatcher {
+    endpoint: Endpoint,
+    channel: OutputChannel,
+}
+
+impl Dispatcher {
+
+    /// A server context for `run_server`, which sends its notifications through the output channel.
+    pub fn server_context(&self) -> LSServerContext {
+        LSServerContext::new_with_output(self.endpoint.clone(), self.channel.sender())
+    }
+
+    /// Run given server until the input ends. See `LSPEndpoint::run_server`.
+    pub fn run_server<SERVER, MR>(mut self, msg_reader: &mut MR, context: LSServerContext, server: SERVER) -> i32
+    where
+        SERVER : LanguageServerHandling + 'static,
+        MR : MessageReader,
+    {
+        let exit_code = LSPEndpoint::run_server(msg_reader, context, server);
+        self.channel.shutdown_and_join();
+        exit_code
+    }
+
+    /// Run given client until the input ends.
+    pub fn run_client<CLIENT, MR>(mut self, msg_reader: &mut MR, client: CLIENT) -> Result<(), LSError>
+    where
+        CLIENT : LanguageClientHandling + 'static,
+        MR : MessageReader,
+    {
+        let handler = new(ClientRequestHandler(client));
+        let result = LSPEndpoint::run_endpoint_loop(msg_reader, self.endpoint.clone(), handler);
+        self.endpoint.shutdown_and_join();
+        self.channel.shutdown_and_join();
+        result
+    }
+
+}
+
+
+#[cfg(test)]
+struct RecordingWriter(::std::sync::Arc<::std::sync::Mutex<Vec<String>>>);
+
+#[cfg(test)]
+impl MessageWriter for RecordingWriter {
+    fn write_message(&mut self, msg: &str) -> GResult<()> {
+        self.0.lock().unwrap().push(msg.to_string());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+struct FailingWriter;
+
+#[cfg(test)]
+impl MessageWriter for FailingWriter {
+    fn write_message(&mut self, _: &str) -> GResult<()> {
+        Err(LSError::Shutdown("Broken pipe.".to_string()).into())
+    }
+}
+
+#[test]
+fn output_channel__test() {
+    use std::sync::Arc;
+    use std::sync::Mutex;
+
+    let written = Arc::new(Mutex::new(vec![]));
+    let writer = RecordingWriter(written.clone());
+    let mut channel = OutputChannel::start(move || writer);
+
+    let threads : Vec<_> = (0..4).map(|thread_ix| {
+        let sender = channel.sender();
+        thread::spawn(move || {
+            for ix in 0..100 {
+                sender.send_notification("test", &vec![thread_ix, ix]).unwrap();
+            }
+        })
+    }).collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    channel.shutdown_and_join();
+    assert!(channel.sender().send_message("{}".to_string()).unwrap_err().is_shutdown());
+
+    let written = written.lock().unwrap();
+    assert_eq!(written.len(), 400);
+    let thread_messages : Vec<&String> = written.iter().filter(|message| message.contains("[2,")).collect();
+    assert_eq!(thread_messages[0], r#"{"jsonrpc":"2.0","method":"test","params":[2,0]}"#);
+    assert_eq!(thread_messages[99], r#"{"jsonrpc":"2.0","method":"test","params":[2,99]}"#);
+}
+
+#[test]
+fn output_channel_shutdown_with_timeout__test() {
+    use std::sync::Arc;
+    use std::sync::Mutex;
+
+    let written = Arc::new(Mutex::new(vec![]));
+    let writer = RecordingWriter(written.clone());
+    let mut channel = OutputChannel::start(move || writer);
+    channel.sender().send_message("{}".to_string()).unwrap();
+    assert_eq!(channel.shutdown_with_timeout(Duration::from_secs(5)), true);
+    assert_eq!(written.lock().unwrap().len(), 1);
+
+    /// A writer blocked until its unblocking sender is dropped.
+    struct BlockedWriter(mpsc::Receiver<()>);
+
+    impl MessageWriter for BlockedWriter {
+        fn write_message(&mut self, _: &str) -> GResult<()> {
+            let _ = self.0.recv();
+            Ok(())
+        }
+    }
+
+    let (unblock, blocked) = mpsc::channel();
+    let mut channel = OutputChannel::start(move || BlockedWriter(blocked));
+    channel.sender().send_message("{}".to_string()).unwrap();
+    assert_eq!(channel.shutdown_with_timeout(Duration::from_millis(50)), false);
+    drop(unblock);
+
+    let mut channel = OutputChannel::start(|| FailingWriter);
+    channel.sender().send_message("{}".to_string()).unwrap();
+    assert_eq!(channel.shutdown_with_timeout(Duration::from_secs(5)), false);
+}
+
+#[test]
+fn output_channel_replace_writer__test() {
+    use std::sync::Mutex;
+
+    let written = Arc::new(Mutex::new(vec![]));
+    let first_writer = RecordingWriter(written.clone());
+    let mut channel = OutputChannel::start(move || first_writer);
+    let sender = channel.sender();
+    sender.send_message("1".to_string()).unwrap();
+    channel.replace_writer(FailingWriter).unwrap();
+    sender.send_message("2".to_string()).unwrap();
+    sender.group().message("3".to_string()).message("4".to_string()).send().unwrap();
+    while !channel.has_failed() {
+        thread::yield_now();
+    }
+
+    // The failed messages are written once the writer is replaced
+    channel.replace_writer(RecordingWriter(written.clone())).unwrap();
+    sender.send_message("5".to_string()).unwrap();
+    assert_eq!(channel.shutdown_with_timeout(Duration::from_secs(5)), true);
+    assert!(!channel.has_failed());
+    assert_eq!(*written.lock().unwrap(), vec!["1", "2", "3", "4", "5"]);
+}
+
+#[test]
+fn server_context_output__test() {
+    use std::sync::Arc;
+    use std::sync::Mutex;
+    use ls_types::*;
+    use lsp::LSServerContext;
+
+    let written = Arc::new(Mutex::new(vec![]));
+    let writer = RecordingWriter(written.clone());
+    let (mut endpoint, mut channel) = create_endpoint_with_channel(move || writer);
+    let context = LSServerContext::new_with_output(endpoint.clone(), channel.sender());
+
+    let params = LogMessageParams { typ : MessageType::Info, message : "direct".to_string() };
+    context.send_notification::<methods::LogMessage>(params).unwrap();
+    context.show_warning("warning").unwrap();
+    endpoint.send_notification("through_endpoint", ::serde_json::Value::Null).unwrap();
+    endpoint.shutdown_and_join();
+    channel.shutdown_and_join();
+
+    // The endpoint writes its messages after the ones queued before
+    let written = written.lock().unwrap();
+    assert_eq!(written.len(), 3);
+    assert!(written[0].contains("direct"));
+    assert!(written[1].contains("window/showMessage"));
+    assert!(written[2].contains("through_endpoint"));
+}
+
+#[test]
+fn split_endpoint__test() {
+    use std::sync::Arc;
+    use std::sync::Mutex;
+    use ls_types::*;
+
+    let written = Arc::new(Mutex::new(vec![]));
+    let writer = RecordingWriter(written.clone());
+    let (sender, dispatcher) = split_endpoint(move || writer);
+
+    let thread_sender = sender.clone();
+    thread::spawn(move || {
+        let params = LogMessageParams { typ : MessageType::Info, message : "from thread".to_string() };
+        thread_sender.send_notification::<methods::LogMessage>(&params).unwrap();
+    }).join().unwrap();
+    drop(dispatcher);
+
+    let params = LogMessageParams { typ : MessageType::Info, message : "after shutdown".to_string() };
+    assert!(sender.send_notification::<methods::LogMessage>(&params).unwrap_err().is_shutdown());
+
+    let written = written.lock().unwrap();
+    assert_eq!(written.len(), 1);
+    assert!(written[0].contains("from thread"));
+}
+
+#[test]
+fn notification_sender__test() {
+    use std::sync::Arc;
+    use std::sync::Mutex;
+
+    let written = Arc::new(Mutex::new(vec![]));
+    let writer = RecordingWriter(written.clone());
+    let mut channel = OutputChannel::start(move || writer);
+    let notifications = NotificationSender::new(channel.sender());
+
+    let workers : Vec<_> = (0..2).map(|worker_ix| {
+        let notifications = notifications.clone();
+        thread::spawn(move || {
+            let token = Value::String(format!("worker{}", worker_ix));
+            notifications.progress(&token, &vec!["begin"]).unwrap();
+            notifications.progress(&token, &vec!["end"]).unwrap();
+        })
+    }).collect();
+    for worker in workers {
+        worker.join().unwrap();
+    }
+    channel.shutdown_and_join();
+
+    let written = written.lock().unwrap();
+    assert_eq!(written.len(), 4);
+    let worker1 : Vec<&String> = written.iter().filter(|message| message.contains("worker1")).collect();
+    assert_eq!(worker1[0], r#"{"jsonrpc":"2.0","method":"$/progress","params":{"token":"worker1","value":["begin"]}}"#);
+    assert_eq!(worker1[1], r#"{"jsonrpc":"2.0","method":"$/progress","params":{"token":"worker1","value":["end"]}}"#);
+}
+
+#[test]
+fn message_group__test() {
+    use std::sync::Arc;
+    use std::sync::Mutex;
+
+    let written = Arc::new(Mutex::new(vec![]));
+    let writer = RecordingWriter(written.clone());
+    let mut channel = OutputChannel::start(move || writer);
+
+    let threads : Vec<_> = (0..4).map(|thread_ix| {
+        let sender = channel.sender();
+        thread::spawn(move || {
+            for ix in 0..50 {
+                sender.group()
+                    .notification("first", &vec![thread_ix, ix]).unwrap()
+                    .message(format!(r#"{{"jsonrpc":"2.0","id":{},"result":null}}"#, thread_ix * 100 + ix))
+                    .send().unwrap();
+            }
+        })
+    }).collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    channel.shutdown_and_join();
+
+    let written = written.lock().unwrap();
+    assert_eq!(written.len(), 400);
+    for pair in written.chunks(2) {
+        let first = ::serde_json::from_str::<Value>(&pair[0]).unwrap();
+        let params = first.find("params").and_then(Value::as_array).unwrap();
+        let id = params[0].as_u64().unwrap() * 100 + params[1].as_u64().unwrap();
+        assert_eq!(pair[1], format!(r#"{{"jsonrpc":"2.0","id":{},"result":null}}"#, id));
+    }
+    assert!(channel.sender().group().message("{}".to_string()).send().unwrap_err().is_shutdown());
+}
+
+#[cfg(test)]
+use jsonrpc::jsonrpc_request::RequestParams;
+
+/// Answers custom requests with responses written from the output channel.
+#[cfg(test)]
+struct OutputServer {
+    context: LSServerContext,
+}
+
+#[cfg(test)]
+impl LanguageServerHandling for OutputServer {
+    fn handle_other_method(&mut self, request: &RequestInfo, method_name: &str, _: RequestParams,
+        completable: ResponseCompletable)
+    {
+        let output = self.context.output_sender().unwrap();
+        let progress : Value = serde_json::from_str(r#"{ "token": 1, "value": { "kind": "end" } }"#).unwrap();
+        let result : LSResult<Vec<u32>, ()> = Ok(vec![1, 2, 3]);
+        match method_name {
+            "custom/complete" => output.complete_request(request, completable, result).unwrap(),
+            "custom/group" => {
+                output.group().notification(methods::PROGRESS, &progress).unwrap()
+                    .response(request, completable, result)
+                    .send().unwrap()
+            }
+            "custom/dropped" => drop(output.group().response(request, completable, result)),
+            _ => ::lsp::handle_unknown_method(request, method_name, completable),
+        }
+    }
+}
+
+#[test]
+fn complete_request__test() {
+    use lsp_testing::ServerTestHarness;
+
+    let mut harness = ServerTestHarness::start(|context| OutputServer { context : context });
+    harness.initialize(Value::Object(JsonObject::new())).unwrap();
+    let result = harness.request_other("custom/complete", Value::Object(JsonObject::new())).unwrap();
+    assert_eq!(result, serde_json::from_str::<Value>("[1, 2, 3]").unwrap());
+    assert_eq!(harness.finish(), 0);
+}
+
+#[test]
+fn message_group_response__test() {
+    use lsp_testing::ServerTestHarness;
+
+    let mut harness = ServerTestHarness::start(|context| OutputServer { context : context });
+    harness.initialize(Value::Object(JsonObject::new())).unwrap();
+    let result = harness.request_other("custom/group", Value::Object(JsonObject::new())).unwrap();
+    assert_eq!(result, serde_json::from_str::<Value>("[1, 2, 3]").unwrap());
+    // The client handled the notification before the response
+    let progress = harness.client_messages.with(|data| data.progress.clone());
+    assert_eq!(progress.len(), 1);
+    assert_eq!(progress[0].lookup("value.kind"), Some(&Value::String("end".to_string())));
+
+    // The response of a group that is not sent is an error
+    assert!(harness.request_other("custom/dropped", Value::Object(JsonObject::new())).is_err());
+    assert_eq!(harness.finish(), 0);
+}
@@ -0,0 +1,268 @@
+// Copyright 2016 Bruno Medeiros
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>.
+// This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Conversions between LSP `Position`s and byte offsets in a text.
+//! The `character` of a position is counted in units of the negotiated `PositionEncoding`,
+//! UTF-16 code units by default.
+
+use util::core::*;
+
+use ls_types::Position;
+use ls_types::Range;
+
+pub use lsp_capabilities::PositionEncoding;
+
+/// The length of given char in units of given encoding.
+pub fn char_len(ch: char, encoding: PositionEncoding) -> u64 {
+    match encoding {
+        PositionEncoding::UTF8 => ch.len_utf8() as u64,
+        PositionEncoding::UTF16 => ch.len_utf16() as u64,
+        PositionEncoding::UTF32 => 1,
+    }
+}
+
+/// The length of given string in units of given encoding.
+pub fn str_len(string: &str, encoding: PositionEncoding) -> u64 {
+    match encoding {
+        PositionEncoding::UTF8 => string.len() as u64,
+        _ => string.chars().map(|ch| char_len(ch, encoding)).sum(),
+    }
+}
+
+/// Get the byte offset of the start of given line.
+fn line_start_offset(text: &str, line: u64) -> GResult<usize> {
+    let mut line_start = 0;
+    for _ in 0..line {
+        match text[line_start..].find('\n') {
+            Some(newline_ix) => line_start += newline_ix + 1,
+            None => return Err(format!("Invalid position: line {} out of bounds.", line).into()),
+        }
+    }
+    Ok(line_start)
+}
+
+/// Get the byte offset in the line starting at `line_start` of given character column.
+/// A column beyond the end of the line means the end of the line, as the spec requires.
+pub fn column_to_offset(text: &str, line_start: usize, character: u64, encoding: PositionEncoding)
+    -> GResult<usize>
+{
+    let mut column = 0;
+    for (ix, ch) in text[line_start..].char_indices() {
+        if column == character || ch == '\n' || ch == '\r' {
+            return Ok(line_start + ix);
+        }
+        if column > character {
+            return Err(format!("Invalid position: character {} is inside a character.", character).into());
+        }
+        column += char_len(ch, encoding);
+    }
+    if column > character {
+        return Err(format!("Invalid position: character {} is inside a character.", character).into());
+    }
+    Ok(text.len())
+}
+
+/// Get the byte offset in `text` of given position.
+pub fn position_to_offset(text: &str, position: &Position, encoding: PositionEncoding) -> GResult<usize> {
+    let line_start = try!(line_start_offset(text, position.line));
+    column_to_offset(text, line_start, position.character, encoding)
+}
+
+/// Get the position of given byte offset in `text`.
+pub fn offset_to_position(text: &str, offset: usize, encoding: PositionEncoding) -> GResult<Position> {
+    if offset > text.len() {
+        return Err(format!("Invalid offset: {} is beyond the end of the text.", offset).into());
+    }
+    if !text.is_char_boundary(offset) {
+        return Err(format!("Invalid offset: {} is inside a character.", offset).into());
+    }
+
+    let before = &text[..offset];
+    let line = before.matches('\n').count() as u64;
+    let line_start = match before.rfind('\n') {
+        Some(newline_ix) => newline_ix + 1,
+        None => 0,
+    };
+    Ok(Position { line : line, character : str_len(&before[line_start..], encoding) })
+}
+
+/// Get the byte offsets (start, end) in `text` of given range.
+pub fn range_to_offsets(text: &str, range: &Range, encoding: PositionEncoding) -> GResult<(usize, usize)> {
+    let start = try!(position_to_offset(text, &range.start, encoding));
+    let end = try!(position_to_offset(text, &range.end, encoding));
+    if end < start {
+        return Err("Invalid range: end is before start.".into());
+    }
+    Ok((start, end))
+}
+
+/// Get the range of given byte offsets in `text`.
+pub fn offsets_to_range(text: &str, start: usize, end: usize, encoding: PositionEncoding) -> GResult<Range> {
+    Ok(Range {
+        start : try!(offset_to_position(text, start, encoding)),
+        end : try!(offset_to_position(text, end, encoding)),
+    })
+}
+
+/* ----------------- LineIndex ----------------- */
+
+/// A table of the line start offsets of a text, for O(log n) position <-> offset conversions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineIndex {
+    /// The byte offset of the start of each line, sorted. The first is always 0.
+    line_starts: Vec<usize>,
+}
+
+impl LineIndex {
+    
+    pub fn new(text: &str) -> LineIndex {
+        let mut line_starts = vec![0];
+        line_starts.extend(newline_starts(text, 0));
+        LineIndex { line_starts : line_starts }
+    }
+    
+    pub fn line_count(&self) -> usize {
+        self.line_starts.len()
+    }
+    
+    pub fn line_start(&self, line: u64) -> Option<usize> {
+        self.line_starts.get(line as usize).cloned()
+    }
+    
+    /// Get the line containing given offset.
+    pub fn line_of_offset(&self, offset: usize) -> usize {
+        match self.line_starts.binary_search(&offset) {
+            Ok(line) => line,
+            Err(next_line) => next_line - 1,
+        }
+    }
+    
+    /// Update the index for the replacement of the byte range `start..end` with `new_text`.
+    pub fn apply_edit(&mut self, start: usize, end: usize, new_text: &str) {
+        // Line starts in (start, end] come from newlines in the replaced range
+        let first_removed = match self.line_starts.binary_search(&start) {
+            Ok(ix) => ix + 1,
+            Err(ix) => ix,
+        };
+        let after_removed = match self.line_starts.binary_search(&end) {
+            Ok(ix) => ix + 1,
+            Err(ix) => ix,
+        };
+        
+        let new_starts : Vec<usize> = newline_starts(new_text, start).collect();
+        let inserted_count = new_starts.len();
+        self.line_starts.splice(first_removed..after_removed, new_starts);
+        
+        let shifted_from = first_removed + inserted_count;
+        for line_start in &mut self.line_starts[shifted_from..] {
+            *line_start = *line_start + new_text.len() - (end - start);
+        }
+    }
+    
+    /// Get the byte offset in `text` of given position. `text` must be the text this index is for.
+    pub fn position_to_offset(&self, text: &str, position: &Position, encoding: PositionEncoding) 
+        -> GResult<usize> 
+    {
+        match self.line_start(position.line) {
+            Some(line_start) => column_to_offset(text, line_start, position.character, encoding),
+            None => Err(format!("Invalid position: line {} out of bounds.", position.line).into()),
+        }
+    }
+    
+    /// Get the position of given byte offset in `text`. `text` must be the text this index is for.
+    pub fn offset_to_position(&self, text: &str, offset: usize, encoding: PositionEncoding) -> GResult<Position> {
+        if offset > text.len() {
+            return Err(format!("Invalid offset: {} is beyond the end of the text.", offset).into());
+        }
+        if !text.is_char_boundary(offset) {
+            return Err(format!("Invalid offset: {} is inside a character.", offset).into());
+        }
+        let line = self.line_of_offset(offset);
+        let character = str_len(&text[self.line_starts[line]..offset], encoding);
+        Ok(Position { line : line as u64, character : character })
+    }
+    
+}
+
+/// The offsets of the line starts following each newline of given text, displaced by `base_offset`.
+fn newline_starts<'a>(text: &'a str, base_offset: usize) -> Box<Iterator<Item = usize> + 'a> {
+    Box::new(text.match_indices('\n').map(move |(ix, _)| base_offset + ix + 1))
+}
+
+
+#[cfg(test)]
+fn pos(line: u64, character: u64) -> Position {
+    Position { line : line, character : character }
+}
+
+#[test]
+fn position_to_offset__test() {
+    use self::PositionEncoding::*;
+
+    let text = "ab\n\u{1F600}c\u{4E2D}d\r\nlast";
+    assert_eq!(position_to_offset(text, &pos(0, 0), UTF16).unwrap(), 0);
+    assert_eq!(position_to_offset(text, &pos(0, 2), UTF16).unwrap(), 2);
+    assert_eq!(position_to_offset(text, &pos(0, 10), UTF16).unwrap(), 2);
+
+    // Emoji: 4 bytes, 2 UTF-16 units, 1 UTF-32 unit. CJK: 3 bytes, 1 UTF-16 unit
+    assert_eq!(position_to_offset(text, &pos(1, 2), UTF16).unwrap(), 7);
+    assert_eq!(position_to_offset(text, &pos(1, 1), UTF32).unwrap(), 7);
+    assert_eq!(position_to_offset(text, &pos(1, 4), UTF8).unwrap(), 7);
+    assert_eq!(position_to_offset(text, &pos(1, 4), UTF16).unwrap(), 11);
+    assert_eq!(position_to_offset(text, &pos(1, 9), UTF16).unwrap(), 12);
+    assert!(position_to_offset(text, &pos(1, 1), UTF16).is_err());
+
+    assert_eq!(position_to_offset(text, &pos(2, 4), UTF16).unwrap(), text.len());
+    assert!(position_to_offset(text, &pos(3, 0), UTF16).is_err());
+}
+
+#[test]
+fn offset_to_position__test() {
+    use self::PositionEncoding::*;
+
+    let text = "ab\n\u{1F600}c\u{4E2D}d\r\nlast";
+    assert_eq!(offset_to_position(text, 0, UTF16).unwrap(), pos(0, 0));
+    assert_eq!(offset_to_position(text, 3, UTF16).unwrap(), pos(1, 0));
+    assert_eq!(offset_to_position(text, 7, UTF16).unwrap(), pos(1, 2));
+    assert_eq!(offset_to_position(text, 7, UTF32).unwrap(), pos(1, 1));
+    assert_eq!(offset_to_position(text, 11, UTF16).unwrap(), pos(1, 4));
+    assert_eq!(offset_to_position(text, text.len(), UTF16).unwrap(), pos(2, 4));
+    assert!(offset_to_position(text, 4, UTF16).is_err());
+    assert!(offset_to_position(text, text.len() + 1, UTF16).is_err());
+
+    let range = offsets_to_range(text, 3, 11, UTF16).unwrap();
+    assert_eq!(range_to_offsets(text, &range, UTF16).unwrap(), (3, 11));
+}
+
+#[test]
+fn line_index__test() {
+    use self::PositionEncoding::*;
+    
+    let text = "ab\n\u{1F600}c\u{4E2D}d\r\nlast";
+    let index = LineIndex::new(text);
+    assert_eq!(index.line_count(), 3);
+    assert_eq!(index.line_of_offset(0), 0);
+    assert_eq!(index.line_of_offset(2), 0);
+    assert_eq!(index.line_of_offset(3), 1);
+    assert_eq!(index.line_of_offset(text.len()), 2);
+    
+    for offset in vec![0, 2, 3, 7, 11, 14, text.len()] {
+        let position = offset_to_position(text, offset, UTF16).unwrap();
+        assert_eq!(index.offset_to_position(text, offset, UTF16).unwrap(), position);
+        assert_eq!(index.position_to_offset(text, &position, UTF16).unwrap(), offset);
+    }
+    assert!(index.position_to_offset(text, &pos(3, 0), UTF16).is_err());
+    
+    let mut index = LineIndex::new("a\nbc\nd\ne");
+    // "a\n[bc\nd]\ne" -> "a\nX\nY\nZ\ne"
+    index.apply_edit(2, 6, "X\nY\nZ");
+    assert_eq!(index, LineIndex::new("a\nX\nY\nZ\ne"));
+    index.apply_edit(0, 0, "\n");
+    assert_eq!(index, LineIndex::new("\na\nX\nY\nZ\ne"));
+    index.apply_edit(1, 4, "");
+    assert_eq!(index, LineIndex::new("\n\nY\nZ\ne"));
+}
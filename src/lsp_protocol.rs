@@ -0,0 +1,873 @@
+// Copyright 2016 Bruno Medeiros
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>.
+// This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Structures of LSP 3.17 that `ls_types` (`languageserver-types` 0.6) lacks: work done progress,
+//! hover and signature help with `MarkupContent`, completion item details and defaults, location links,
+//! folding and selection ranges, call hierarchy, semantic tokens, inlay hints, linked editing
+//! and pull diagnostics. Each converts to and from its JSON form, and implements serde with it.
+//!
+//! Optional fields are `Option`s, omitted from the JSON when `None`.
+
+use serde;
+use serde::de::Error as DeError;
+use serde_json;
+use serde_json::Value;
+use url::Url;
+
+use jsonrpc::json_util::JsonObject;
+use ls_types::*;
+
+use lsp_capabilities::LSClientCapabilities;
+use lsp_enums::InlayHintKind;
+use lsp_enums::InsertTextFormat;
+use lsp_enums::InsertTextMode;
+use lsp_enums::LSSymbolKind;
+use lsp_enums::SymbolTag;
+use lsp_markup::LSDocumentation;
+use lsp_markup::LSHoverContents;
+use lsp_markup::MarkupContent;
+use lsp_markup::MarkupTarget;
+
+/* ----------------- Fields ----------------- */
+
+/// A type of a field of a protocol structure, converted to and from its JSON form.
+pub trait ProtocolField : Sized {
+    fn to_json_field(&self) -> Value;
+
+    /// Convert given JSON value, which is `Null` if the field is missing.
+    fn from_json_field(value: Value) -> Result<Self, String>;
+}
+
+macro_rules! serde_protocol_field {
+    ( $( $typ:ty ),* $(,)* ) => {
+        $(
+            impl ProtocolField for $typ {
+                fn to_json_field(&self) -> Value {
+                    serde_json::to_value(self)
+                }
+
+                fn from_json_field(value: Value) -> Result<Self, String> {
+                    if value.is_null() {
+                        return Err("missing value".to_string());
+                    }
+                    serde_json::from_value(value).map_err(|err| err.to_string())
+                }
+            }
+        )*
+    };
+}
+
+serde_protocol_field!(u64, i64, bool, String, Position, Range, Location, TextEdit, TextDocumentIdentifier);
+
+impl ProtocolField for Value {
+    fn to_json_field(&self) -> Value {
+        self.clone()
+    }
+
+    fn from_json_field(value: Value) -> Result<Self, String> {
+        Ok(value)
+    }
+}
+
+impl ProtocolField for Url {
+    fn to_json_field(&self) -> Value {
+        Value::String(self.as_str().to_string())
+    }
+
+    fn from_json_field(value: Value) -> Result<Self, String> {
+        match value {
+            Value::String(url) => Url::parse(&url).map_err(|err| err.to_string()),
+            _ => Err("expected a URI string".to_string()),
+        }
+    }
+}
+
+impl<T : ProtocolField> ProtocolField for Option<T> {
+    fn to_json_field(&self) -> Value {
+        self.as_ref().map_or(Value::Null, T::to_json_field)
+    }
+
+    fn from_json_field(value: Value) -> Result<Self, String> {
+        match value {
+            Value::Null => Ok(None),
+            value => T::from_json_field(value).map(Some),
+        }
+    }
+}
+
+impl<T : ProtocolField> ProtocolField for Vec<T> {
+    fn to_json_field(&self) -> Value {
+        Value::Array(self.iter().map(T::to_json_field).collect())
+    }
+
+    fn from_json_field(value: Value) -> Result<Self, String> {
+        match value {
+            Value::Array(values) => {
+                let mut result = vec![];
+                for value in values {
+                    result.push(try!(T::from_json_field(value)));
+                }
+                Ok(result)
+            }
+            _ => Err("expected an array".to_string()),
+        }
+    }
+}
+
+impl<T : ProtocolField> ProtocolField for Box<T> {
+    fn to_json_field(&self) -> Value {
+        (**self).to_json_field()
+    }
+
+    fn from_json_field(value: Value) -> Result<Self, String> {
+        T::from_json_field(value).map(Box::new)
+    }
+}
+
+/// Insert given field in given object, unless its JSON form is null.
+pub fn insert_field<T : ProtocolField>(obj: &mut JsonObject, name: &str, field: &T) {
+    let value = field.to_json_field();
+    if !value.is_null() {
+        obj.insert(name.to_string(), value);
+    }
+}
+
+/// Remove the field of given name from given object, and convert it. `struct_name` is for the error message.
+pub fn take_field<T : ProtocolField>(obj: &mut JsonObject, name: &str, struct_name: &str) -> Result<T, String> {
+    let value = obj.remove(name).unwrap_or(Value::Null);
+    let missing = value.is_null();
+    T::from_json_field(value).map_err(|error| {
+        if missing {
+            format!("{}: missing `{}`.", struct_name, name)
+        } else {
+            format!("{}: invalid `{}`: {}.", struct_name, name, error)
+        }
+    })
+}
+
+fn take_object(value: Value, struct_name: &str) -> Result<JsonObject, String> {
+    match value {
+        Value::Object(obj) => Ok(obj),
+        _ => Err(format!("{} is not an object.", struct_name)),
+    }
+}
+
+/// Define a protocol structure, with each field given as `name : Type => "jsonName"`.
+macro_rules! protocol_struct {
+    (
+        $(#[$attr:meta])*
+        pub struct $name:ident {
+            $( $(#[$field_attr:meta])* pub $field:ident : $typ:ty => $json:expr ),* $(,)*
+        }
+    ) => {
+        $(#[$attr])*
+        #[derive(Debug, Clone, PartialEq)]
+        pub struct $name {
+            $( $(#[$field_attr])* pub $field : $typ ),*
+        }
+
+        impl $name {
+
+            pub fn to_json(&self) -> Value {
+                let mut obj = JsonObject::new();
+                $( insert_field(&mut obj, $json, &self.$field); )*
+                Value::Object(obj)
+            }
+
+            #[allow(unused_mut)]
+            pub fn from_json(value: Value) -> Result<$name, String> {
+                let mut obj = try!(take_object(value, stringify!($name)));
+                Ok($name {
+                    $( $field : try!(take_field(&mut obj, $json, stringify!($name))) ),*
+                })
+            }
+
+        }
+
+        impl ProtocolField for $name {
+            fn to_json_field(&self) -> Value {
+                self.to_json()
+            }
+
+            fn from_json_field(value: Value) -> Result<Self, String> {
+                $name::from_json(value)
+            }
+        }
+
+        impl serde::Serialize for $name {
+            fn serialize<S>(&self, serializer: &mut S) -> Result<(), S::Error>
+            where
+                S : serde::Serializer,
+            {
+                self.to_json().serialize(serializer)
+            }
+        }
+
+        impl serde::Deserialize for $name {
+            fn deserialize<D>(deserializer: &mut D) -> Result<$name, D::Error>
+            where
+                D : serde::Deserializer,
+            {
+                let value = try!(Value::deserialize(deserializer));
+                $name::from_json(value).map_err(D::Error::custom)
+            }
+        }
+    };
+}
+
+/* ----------------- Progress ----------------- */
+
+protocol_struct! {
+    /// The params of `$/progress`. The value of a work done progress is a `WorkDoneProgress`.
+    pub struct ProgressParams {
+        pub token : Value => "token",
+        pub value : Value => "value",
+    }
+}
+
+protocol_struct! {
+    /// A settings section the server asks for with `workspace/configuration`.
+    pub struct ConfigurationItem {
+        pub scope_uri : Option<Url> => "scopeUri",
+        pub section : Option<String> => "section",
+    }
+}
+
+protocol_struct! {
+    pub struct ConfigurationParams {
+        pub items : Vec<ConfigurationItem> => "items",
+    }
+}
+
+protocol_struct! {
+    pub struct WorkDoneProgressCreateParams {
+        pub token : Value => "token",
+    }
+}
+
+/* ----------------- Show document ----------------- */
+
+protocol_struct! {
+    /// The params of `window/showDocument`, to show a document or a URL to the user.
+    pub struct ShowDocumentParams {
+        pub uri : Url => "uri",
+        /// Show the resource in an external program, such as a browser.
+        pub external : Option<bool> => "external",
+        pub take_focus : Option<bool> => "takeFocus",
+        /// The range to select, if the resource is a text document.
+        pub selection : Option<Range> => "selection",
+    }
+}
+
+protocol_struct! {
+    pub struct ShowDocumentResult {
+        pub success : bool => "success",
+    }
+}
+
+/// The value of a work done `$/progress` notification.
+#[derive(Debug, Clone, PartialEq)]
+pub enum WorkDoneProgress {
+    Begin { title: String, cancellable: Option<bool>, message: Option<String>, percentage: Option<u64> },
+    Report { cancellable: Option<bool>, message: Option<String>, percentage: Option<u64> },
+    End { message: Option<String> },
+}
+
+impl WorkDoneProgress {
+
+    pub fn to_json(&self) -> Value {
+        let mut obj = JsonObject::new();
+        match *self {
+            WorkDoneProgress::Begin { ref title, ref cancellable, ref message, ref percentage } => {
+                obj.insert("kind".to_string(), Value::String("begin".to_string()));
+                insert_field(&mut obj, "title", title);
+                insert_field(&mut obj, "cancellable", cancellable);
+                insert_field(&mut obj, "message", message);
+                insert_field(&mut obj, "percentage", percentage);
+            }
+            WorkDoneProgress::Report { ref cancellable, ref message, ref percentage } => {
+                obj.insert("kind".to_string(), Value::String("report".to_string()));
+                insert_field(&mut obj, "cancellable", cancellable);
+                insert_field(&mut obj, "message", message);
+                insert_field(&mut obj, "percentage", percentage);
+            }
+            WorkDoneProgress::End { ref message } => {
+                obj.insert("kind".to_string(), Value::String("end".to_string()));
+                insert_field(&mut obj, "message", message);
+            }
+        }
+        Value::Object(obj)
+    }
+
+    pub fn from_json(value: Value) -> Result<WorkDoneProgress, String> {
+        let mut obj = try!(take_object(value, "WorkDoneProgress"));
+        let kind : String = try!(take_field(&mut obj, "kind", "WorkDoneProgress"));
+        match kind.as_str() {
+            "begin" => Ok(WorkDoneProgress::Begin {
+                title : try!(take_field(&mut obj, "title", "WorkDoneProgressBegin")),
+                cancellable : try!(take_field(&mut obj, "cancellable", "WorkDoneProgressBegin")),
+                message : try!(take_field(&mut obj, "message", "WorkDoneProgressBegin")),
+                percentage : try!(take_field(&mut obj, "percentage", "WorkDoneProgressBegin")),
+            }),
+            "report" => Ok(WorkDoneProgress::Report {
+                cancellable : try!(take_field(&mut obj, "cancellable", "WorkDoneProgressReport")),
+                message : try!(take_field(&mut obj, "message", "WorkDoneProgressReport")),
+                percentage : try!(take_field(&mut obj, "percentage", "WorkDoneProgressReport")),
+            }),
+            "end" => Ok(WorkDoneProgress::End {
+                message : try!(take_field(&mut obj, "message", "WorkDoneProgressEnd")),
+            }),
+            _ => Err(format!("WorkDoneProgress: unknown kind `{}`.", kind)),
+        }
+    }
+
+}
+
+impl ProtocolField for WorkDoneProgress {
+    fn to_json_field(&self) -> Value {
+        self.to_json()
+    }
+
+    fn from_json_field(value: Value) -> Result<Self, String> {
+        WorkDoneProgress::from_json(value)
+    }
+}
+
+impl serde::Serialize for WorkDoneProgress {
+    fn serialize<S>(&self, serializer: &mut S) -> Result<(), S::Error>
+    where
+        S : serde::Serializer,
+    {
+        self.to_json().serialize(serializer)
+    }
+}
+
+impl serde::Deserialize for WorkDoneProgress {
+    fn deserialize<D>(deserializer: &mut D) -> Result<WorkDoneProgress, D::Error>
+    where
+        D : serde::Deserializer,
+    {
+        let value = try!(Value::deserialize(deserializer));
+        WorkDoneProgress::from_json(value).map_err(D::Error::custom)
+    }
+}
+
+/* ----------------- Hover and signature help ----------------- */
+
+protocol_struct! {
+    /// The result of `textDocument/hover`.
+    pub struct LSHover {
+        pub contents : LSHoverContents => "contents",
+        pub range : Option<Range> => "range",
+    }
+}
+
+impl From<Hover> for LSHover {
+    fn from(hover: Hover) -> LSHover {
+        LSHover { contents : LSHoverContents::MarkedStrings(hover.contents), range : hover.range }
+    }
+}
+
+impl From<MarkupContent> for LSHover {
+    fn from(contents: MarkupContent) -> LSHover {
+        LSHover { contents : LSHoverContents::Markup(contents), range : None }
+    }
+}
+
+impl LSHover {
+
+    /// Convert to the form supported by a client with given capabilities (`None` if unknown).
+    pub fn into_supported(self, client_capabilities: Option<&LSClientCapabilities>) -> LSHover {
+        LSHover { contents : self.contents.into_supported(client_capabilities), range : self.range }
+    }
+
+}
+
+protocol_struct! {
+    /// The result of `textDocument/signatureHelp`.
+    pub struct LSSignatureHelp {
+        pub signatures : Vec<LSSignatureInformation> => "signatures",
+        pub active_signature : Option<u64> => "activeSignature",
+        pub active_parameter : Option<u64> => "activeParameter",
+    }
+}
+
+protocol_struct! {
+    pub struct LSSignatureInformation {
+        pub label : String => "label",
+        pub documentation : Option<LSDocumentation> => "documentation",
+        pub parameters : Option<Vec<LSParameterInformation>> => "parameters",
+        /// The active parameter of this signature, overriding the one of the `LSSignatureHelp`.
+        pub active_parameter : Option<u64> => "activeParameter",
+    }
+}
+
+protocol_struct! {
+    pub struct LSParameterInformation {
+        /// A substring of the signature label, or the `[start, end]` offsets of the parameter in it.
+        pub label : Value => "label",
+        pub documentation : Option<LSDocumentation> => "documentation",
+    }
+}
+
+impl From<SignatureHelp> for LSSignatureHelp {
+    fn from(help: SignatureHelp) -> LSSignatureHelp {
+        // Both have the same JSON form, with string documentation
+        LSSignatureHelp::from_json(serde_json::to_value(&help)).unwrap_or(LSSignatureHelp {
+            signatures : vec![], active_signature : None, active_parameter : None,
+        })
+    }
+}
+
+impl LSSignatureHelp {
+
+    /// Convert the documentation to the form supported by a client with given capabilities
+    /// (`None` if unknown).
+    pub fn into_supported(self, client_capabilities: Option<&LSClientCapabilities>) -> LSSignatureHelp {
+        let supported = |documentation: Option<LSDocumentation>| documentation.map(|documentation| {
+            documentation.into_supported(MarkupTarget::SignatureHelpDocumentation, client_capabilities)
+        });
+        let signatures = self.signatures.into_iter().map(|signature| {
+            let parameters = signature.parameters.map(|parameters| {
+                parameters.into_iter().map(|parameter| LSParameterInformation {
+                    label : parameter.label,
+                    documentation : supported(parameter.documentation),
+                }).collect()
+            });
+            LSSignatureInformation {
+                label : signature.label,
+                documentation : supported(signature.documentation),
+                parameters : parameters,
+                active_parameter : signature.active_parameter,
+            }
+        }).collect();
+        LSSignatureHelp {
+            signatures : signatures, active_signature : self.active_signature, active_parameter : self.active_parameter,
+        }
+    }
+
+}
+
+/* ----------------- Completion ----------------- */
+
+protocol_struct! {
+    /// Details shown next to the label of a completion item.
+    pub struct CompletionItemLabelDetails {
+        /// Shown right after the label, without spacing, such as a function signature.
+        pub detail : Option<String> => "detail",
+        /// Shown after the detail, less prominently, such as a module path.
+        pub description : Option<String> => "description",
+    }
+}
+
+protocol_struct! {
+    /// A completion item edit with different ranges for inserting the completion, and for replacing
+    /// the word at the cursor with it. The client chooses which to use, usually per a user setting.
+    /// Only clients declaring `textDocument.completion.completionItem.insertReplaceSupport` support it.
+    pub struct InsertReplaceEdit {
+        pub new_text : String => "newText",
+        /// The range to edit when inserting. Must be a prefix of the replace range, on a single line.
+        pub insert : Range => "insert",
+        pub replace : Range => "replace",
+    }
+}
+
+impl InsertReplaceEdit {
+
+    /// The edit to insert the completion, for clients that don't support `InsertReplaceEdit`.
+    pub fn to_insert_edit(&self) -> TextEdit {
+        TextEdit { range : self.insert.clone(), new_text : self.new_text.clone() }
+    }
+
+}
+
+/// The range a completion item edits, when it has no text edit of its own.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CompletionEditRange {
+    Range(Range),
+    /// The range to edit when inserting, and when replacing.
+    InsertReplace { insert: Range, replace: Range },
+}
+
+impl ProtocolField for CompletionEditRange {
+    fn to_json_field(&self) -> Value {
+        match *self {
+            CompletionEditRange::Range(ref range) => range.to_json_field(),
+            CompletionEditRange::InsertReplace { ref insert, ref replace } => {
+                let mut obj = JsonObject::new();
+                insert_field(&mut obj, "insert", insert);
+                insert_field(&mut obj, "replace", replace);
+                Value::Object(obj)
+            }
+        }
+    }
+
+    fn from_json_field(value: Value) -> Result<Self, String> {
+        if value.find("insert").is_none() {
+            return Range::from_json_field(value).map(CompletionEditRange::Range);
+        }
+        let mut obj = try!(take_object(value, "CompletionEditRange"));
+        Ok(CompletionEditRange::InsertReplace {
+            insert : try!(take_field(&mut obj, "insert", "CompletionEditRange")),
+            replace : try!(take_field(&mut obj, "replace", "CompletionEditRange")),
+        })
+    }
+}
+
+protocol_struct! {
+    /// The values of the `CompletionList` items that don't have their own.
+    pub struct CompletionItemDefaults {
+        pub commit_characters : Option<Vec<String>> => "commitCharacters",
+        pub edit_range : Option<CompletionEditRange> => "editRange",
+        pub insert_text_format : Option<InsertTextFormat> => "insertTextFormat",
+        pub insert_text_mode : Option<InsertTextMode> => "insertTextMode",
+        pub data : Option<Value> => "data",
+    }
+}
+
+impl Default for CompletionItemDefaults {
+    fn default() -> CompletionItemDefaults {
+        CompletionItemDefaults {
+            commit_characters : None, edit_range : None, insert_text_format : None, insert_text_mode : None,
+            data : None,
+        }
+    }
+}
+
+/* ----------------- Navigation ----------------- */
+
+protocol_struct! {
+    /// A link to a target location, with the range of the origin that is the link.
+    pub struct LocationLink {
+        /// The span of the origin, such as the word under the cursor. Defaults to that word.
+        pub origin_selection_range : Option<Range> => "originSelectionRange",
+        pub target_uri : Url => "targetUri",
+        /// The whole extent of the target, such as a function including its body.
+        pub target_range : Range => "targetRange",
+        /// The extent to select at the target, such as the function name. Must be contained in `target_range`.
+        pub target_selection_range : Range => "targetSelectionRange",
+    }
+}
+
+/* ----------------- Folding and selection ranges ----------------- */
+
+protocol_struct! {
+    pub struct FoldingRangeParams {
+        pub text_document : TextDocumentIdentifier => "textDocument",
+    }
+}
+
+protocol_struct! {
+    pub struct FoldingRange {
+        pub start_line : u64 => "startLine",
+        pub start_character : Option<u64> => "startCharacter",
+        pub end_line : u64 => "endLine",
+        pub end_character : Option<u64> => "endCharacter",
+        /// `comment`, `imports` or `region`.
+        pub kind : Option<String> => "kind",
+        pub collapsed_text : Option<String> => "collapsedText",
+    }
+}
+
+protocol_struct! {
+    pub struct SelectionRangeParams {
+        pub text_document : TextDocumentIdentifier => "textDocument",
+        pub positions : Vec<Position> => "positions",
+    }
+}
+
+protocol_struct! {
+    pub struct SelectionRange {
+        pub range : Range => "range",
+        /// The range containing this one.
+        pub parent : Option<Box<SelectionRange>> => "parent",
+    }
+}
+
+/* ----------------- Call hierarchy ----------------- */
+
+protocol_struct! {
+    pub struct CallHierarchyItem {
+        pub name : String => "name",
+        pub kind : LSSymbolKind => "kind",
+        pub tags : Option<Vec<SymbolTag>> => "tags",
+        pub detail : Option<String> => "detail",
+        pub uri : Url => "uri",
+        pub range : Range => "range",
+        pub selection_range : Range => "selectionRange",
+        /// Kept between the prepare request and the calls requests.
+        pub data : Option<Value> => "data",
+    }
+}
+
+protocol_struct! {
+    pub struct CallHierarchyIncomingCallsParams {
+        pub item : CallHierarchyItem => "item",
+    }
+}
+
+protocol_struct! {
+    pub struct CallHierarchyIncomingCall {
+        pub from : CallHierarchyItem => "from",
+        /// The ranges of the calls, in the caller.
+        pub from_ranges : Vec<Range> => "fromRanges",
+    }
+}
+
+protocol_struct! {
+    pub struct CallHierarchyOutgoingCallsParams {
+        pub item : CallHierarchyItem => "item",
+    }
+}
+
+protocol_struct! {
+    pub struct CallHierarchyOutgoingCall {
+        pub to : CallHierarchyItem => "to",
+        /// The ranges of the calls, in the item of the params.
+        pub from_ranges : Vec<Range> => "fromRanges",
+    }
+}
+
+/* ----------------- Semantic tokens ----------------- */
+
+protocol_struct! {
+    pub struct SemanticTokensLegend {
+        pub token_types : Vec<String> => "tokenTypes",
+        pub token_modifiers : Vec<String> => "tokenModifiers",
+    }
+}
+
+protocol_struct! {
+    /// The `semanticTokensProvider` server capability.
+    pub struct SemanticTokensOptions {
+        pub legend : SemanticTokensLegend => "legend",
+        pub range : Option<bool> => "range",
+        /// Either a boolean, or `{ "delta": boolean }`.
+        pub full : Option<Value> => "full",
+    }
+}
+
+protocol_struct! {
+    pub struct SemanticTokensParams {
+        pub text_document : TextDocumentIdentifier => "textDocument",
+    }
+}
+
+protocol_struct! {
+    pub struct SemanticTokensRangeParams {
+        pub text_document : TextDocumentIdentifier => "textDocument",
+        pub range : Range => "range",
+    }
+}
+
+protocol_struct! {
+    pub struct SemanticTokens {
+        pub result_id : Option<String> => "resultId",
+        /// Each token as 5 integers, relative to the previous token.
+        pub data : Vec<u64> => "data",
+    }
+}
+
+/* ----------------- Inlay hints ----------------- */
+
+protocol_struct! {
+    /// The `inlayHintProvider` server capability.
+    pub struct InlayHintOptions {
+        pub resolve_provider : Option<bool> => "resolveProvider",
+    }
+}
+
+protocol_struct! {
+    pub struct InlayHintParams {
+        pub text_document : TextDocumentIdentifier => "textDocument",
+        pub range : Range => "range",
+    }
+}
+
+protocol_struct! {
+    pub struct InlayHint {
+        pub position : Position => "position",
+        /// Either a string, or an array of label parts.
+        pub label : Value => "label",
+        pub kind : Option<InlayHintKind> => "kind",
+        pub text_edits : Option<Vec<TextEdit>> => "textEdits",
+        pub tooltip : Option<Value> => "tooltip",
+        pub padding_left : Option<bool> => "paddingLeft",
+        pub padding_right : Option<bool> => "paddingRight",
+        pub data : Option<Value> => "data",
+    }
+}
+
+/* ----------------- Linked editing ----------------- */
+
+protocol_struct! {
+    /// The result of `textDocument/linkedEditingRange`.
+    pub struct LinkedEditingRanges {
+        pub ranges : Vec<Range> => "ranges",
+        pub word_pattern : Option<String> => "wordPattern",
+    }
+}
+
+/* ----------------- Pull diagnostics ----------------- */
+
+protocol_struct! {
+    /// The `diagnosticProvider` server capability.
+    pub struct DiagnosticOptions {
+        pub identifier : Option<String> => "identifier",
+        pub inter_file_dependencies : bool => "interFileDependencies",
+        pub workspace_diagnostics : bool => "workspaceDiagnostics",
+    }
+}
+
+protocol_struct! {
+    pub struct DocumentDiagnosticParams {
+        pub text_document : TextDocumentIdentifier => "textDocument",
+        pub identifier : Option<String> => "identifier",
+        pub previous_result_id : Option<String> => "previousResultId",
+    }
+}
+
+
+#[cfg(test)]
+fn round_trip<T, TO_JSON, FROM_JSON>(json: &str, to_json: TO_JSON, from_json: FROM_JSON) -> T
+where
+    TO_JSON : Fn(&T) -> Value,
+    FROM_JSON : Fn(Value) -> Result<T, String>,
+{
+    let json = serde_json::from_str::<Value>(json).unwrap();
+    let value = from_json(json.clone()).unwrap();
+    assert_eq!(to_json(&value), json);
+    value
+}
+
+#[test]
+fn protocol_structs__test() {
+    // Payloads as sent by VS Code
+    let params = round_trip(r#"{ "textDocument": { "uri": "file:///ws/main.rs" } }"#,
+        FoldingRangeParams::to_json, FoldingRangeParams::from_json);
+    assert_eq!(params.text_document.uri.as_str(), "file:///ws/main.rs");
+
+    round_trip(r#"{ "textDocument": { "uri": "file:///ws/main.rs" },
+        "positions": [ { "line": 3, "character": 7 } ] }"#,
+        SelectionRangeParams::to_json, SelectionRangeParams::from_json);
+
+    let selection = round_trip(r#"{ "range": { "start": { "line": 3, "character": 4 }, "end": { "line": 3, "character": 9 } },
+        "parent": { "range": { "start": { "line": 3, "character": 0 }, "end": { "line": 4, "character": 0 } } } }"#,
+        SelectionRange::to_json, SelectionRange::from_json);
+    assert_eq!(selection.parent.unwrap().parent, None);
+
+    let folding = round_trip(r#"{ "startLine": 1, "endLine": 5, "kind": "imports" }"#,
+        FoldingRange::to_json, FoldingRange::from_json);
+    assert_eq!(folding.start_character, None);
+
+    round_trip(r#"{ "item": { "name": "main", "kind": 12, "uri": "file:///ws/main.rs",
+        "range": { "start": { "line": 0, "character": 0 }, "end": { "line": 9, "character": 1 } },
+        "selectionRange": { "start": { "line": 0, "character": 3 }, "end": { "line": 0, "character": 7 } },
+        "data": { "id": 42 } } }"#,
+        CallHierarchyIncomingCallsParams::to_json, CallHierarchyIncomingCallsParams::from_json);
+
+    round_trip(r#"{ "legend": { "tokenTypes": ["function", "variable"], "tokenModifiers": ["declaration"] },
+        "range": true, "full": { "delta": true } }"#,
+        SemanticTokensOptions::to_json, SemanticTokensOptions::from_json);
+    round_trip(r#"{ "resultId": "1", "data": [0, 3, 4, 0, 1] }"#, SemanticTokens::to_json, SemanticTokens::from_json);
+
+    round_trip(r#"{ "position": { "line": 2, "character": 10 }, "label": ": i32", "kind": 1, "paddingLeft": true }"#,
+        InlayHint::to_json, InlayHint::from_json);
+
+    let show_document = round_trip(r#"{ "uri": "file:///ws/main.rs", "takeFocus": true,
+        "selection": { "start": { "line": 3, "character": 4 }, "end": { "line": 3, "character": 9 } } }"#,
+        ShowDocumentParams::to_json, ShowDocumentParams::from_json);
+    assert_eq!((show_document.external, show_document.take_focus), (None, Some(true)));
+
+    let help = round_trip(r#"{ "signatures": [ { "label": "foo(a: i32)",
+        "documentation": { "kind": "markdown", "value": "Does *foo*." },
+        "parameters": [ { "label": [4, 10], "documentation": "The a." } ] } ], "activeSignature": 0 }"#,
+        LSSignatureHelp::to_json, LSSignatureHelp::from_json);
+    let help = help.into_supported(None);
+    assert_eq!(help.signatures[0].documentation, Some(LSDocumentation::String("Does foo.".to_string())));
+
+    let hover = round_trip(r#"{ "contents": [ "foo", { "language": "rust", "value": "fn foo()" } ] }"#,
+        LSHover::to_json, LSHover::from_json);
+    match hover.contents {
+        LSHoverContents::MarkedStrings(ref strings) => assert_eq!(strings.len(), 2),
+        ref contents => panic!("Unexpected: {:?}", contents),
+    }
+
+    round_trip(r#"{ "textDocument": { "uri": "file:///ws/main.rs" }, "previousResultId": "7" }"#,
+        DocumentDiagnosticParams::to_json, DocumentDiagnosticParams::from_json);
+
+    let progress = round_trip(r#"{ "kind": "begin", "title": "Indexing", "cancellable": false, "percentage": 0 }"#,
+        WorkDoneProgress::to_json, WorkDoneProgress::from_json);
+    assert_eq!(progress, WorkDoneProgress::Begin {
+        title : "Indexing".to_string(), cancellable : Some(false), message : None, percentage : Some(0),
+    });
+    round_trip(r#"{ "kind": "end", "message": "Done" }"#, WorkDoneProgress::to_json, WorkDoneProgress::from_json);
+
+    // Errors, and serde
+    assert_eq!(FoldingRange::from_json(serde_json::from_str(r#"{ "startLine": 1 }"#).unwrap()),
+        Err("FoldingRange: missing `endLine`.".to_string()));
+    assert_eq!(SemanticTokens::from_json(serde_json::from_str(r#"{ "data": "x" }"#).unwrap()),
+        Err("SemanticTokens: invalid `data`: expected an array.".to_string()));
+    assert!(WorkDoneProgress::from_json(serde_json::from_str(r#"{ "kind": "x" }"#).unwrap()).is_err());
+
+    let tokens : SemanticTokens = serde_json::from_str(r#"{ "data": [1, 2, 3, 4, 5] }"#).unwrap();
+    assert_eq!(serde_json::to_string(&tokens).unwrap(), r#"{"data":[1,2,3,4,5]}"#);
+}
+
+#[test]
+fn language_feature_structs__test() {
+    let item = r#"{ "name": "main", "kind": 12, "tags": [1], "detail": "fn main()", "uri": "file:///ws/main.rs",
+        "range": { "start": { "line": 0, "character": 0 }, "end": { "line": 9, "character": 1 } },
+        "selectionRange": { "start": { "line": 0, "character": 3 }, "end": { "line": 0, "character": 7 } } }"#;
+    let call_ranges = r#"[ { "start": { "line": 2, "character": 4 }, "end": { "line": 2, "character": 7 } } ]"#;
+
+    let item = round_trip(item, CallHierarchyItem::to_json, CallHierarchyItem::from_json);
+    assert_eq!(item.kind, LSSymbolKind::Function);
+    assert_eq!(item.data, None);
+    round_trip(&format!(r#"{{ "item": {} }}"#, item.to_json()),
+        CallHierarchyOutgoingCallsParams::to_json, CallHierarchyOutgoingCallsParams::from_json);
+    let call = round_trip(&format!(r#"{{ "from": {}, "fromRanges": {} }}"#, item.to_json(), call_ranges),
+        CallHierarchyIncomingCall::to_json, CallHierarchyIncomingCall::from_json);
+    assert_eq!(call.from_ranges[0].start.line, 2);
+    round_trip(&format!(r#"{{ "to": {}, "fromRanges": {} }}"#, item.to_json(), call_ranges),
+        CallHierarchyOutgoingCall::to_json, CallHierarchyOutgoingCall::from_json);
+
+    round_trip(r#"{ "tokenTypes": [], "tokenModifiers": [] }"#,
+        SemanticTokensLegend::to_json, SemanticTokensLegend::from_json);
+    round_trip(r#"{ "textDocument": { "uri": "file:///ws/main.rs" } }"#,
+        SemanticTokensParams::to_json, SemanticTokensParams::from_json);
+    round_trip(r#"{ "textDocument": { "uri": "file:///ws/main.rs" },
+        "range": { "start": { "line": 0, "character": 0 }, "end": { "line": 20, "character": 0 } } }"#,
+        SemanticTokensRangeParams::to_json, SemanticTokensRangeParams::from_json);
+
+    round_trip(r#"{ "resolveProvider": true }"#, InlayHintOptions::to_json, InlayHintOptions::from_json);
+    let params = round_trip(r#"{ "textDocument": { "uri": "file:///ws/main.rs" },
+        "range": { "start": { "line": 0, "character": 0 }, "end": { "line": 20, "character": 0 } } }"#,
+        InlayHintParams::to_json, InlayHintParams::from_json);
+    assert_eq!(params.range.end.line, 20);
+    round_trip(r#"{ "position": { "line": 2, "character": 10 },
+        "label": [ { "value": "i32", "location": { "uri": "file:///std.rs",
+            "range": { "start": { "line": 0, "character": 0 }, "end": { "line": 0, "character": 3 } } } } ],
+        "textEdits": [ { "newText": ": i32",
+            "range": { "start": { "line": 2, "character": 10 }, "end": { "line": 2, "character": 10 } } } ],
+        "tooltip": { "kind": "markdown", "value": "`i32`" }, "paddingRight": false, "data": 7 }"#,
+        InlayHint::to_json, InlayHint::from_json);
+
+    let ranges = round_trip(r#"{ "ranges": [
+        { "start": { "line": 1, "character": 1 }, "end": { "line": 1, "character": 4 } },
+        { "start": { "line": 1, "character": 7 }, "end": { "line": 1, "character": 10 } } ],
+        "wordPattern": "[a-z]+" }"#,
+        LinkedEditingRanges::to_json, LinkedEditingRanges::from_json);
+    assert_eq!(ranges.ranges.len(), 2);
+
+    let options = round_trip(
+        r#"{ "identifier": "rustc", "interFileDependencies": true, "workspaceDiagnostics": false }"#,
+        DiagnosticOptions::to_json, DiagnosticOptions::from_json);
+    assert_eq!(options.identifier, Some("rustc".to_string()));
+    round_trip(r#"{ "textDocument": { "uri": "file:///ws/main.rs" }, "identifier": "rustc" }"#,
+        DocumentDiagnosticParams::to_json, DocumentDiagnosticParams::from_json);
+}
@@ -0,0 +1,283 @@
+// Copyright 2016 Bruno Medeiros
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>.
+// This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! A proxy server, between an editor and a backend language server, that forwards the messages
+//! in both directions. A `ProxyInterceptor` can rewrite or drop each message, answer requests itself,
+//! and send messages of its own to either side, such as the diagnostics of a linter
+//! merged with the diagnostics of the backend:
+//!
+//! ```ignore
+//! impl ProxyInterceptor for LintMerger {
+//!     fn server_message(&mut self, message: Value, proxy: &ProxyContext) -> Option<Value> {
+//!         if message.find("method") == Some(&Value::String(methods::TEXT_DOCUMENT_PUBLISH_DIAGNOSTICS.into())) {
+//!             return Some(self.merge_lints(message));
+//!         }
+//!         Some(message)
+//!     }
+//! }
+//!
+//! let stdin = io::stdin();
+//! let exit_code = ProxyServer::new(LintMerger::new()).run_with_command(
+//!     &mut LSPBufferedMessageReader::new(stdin.lock()),
+//!     || LSPBufferedMessageWriter::new(io::stdout()),
+//!     Command::new("backend-ls"));
+//! ```
+
+use std::io::BufReader;
+use std::process::Command;
+use std::process::Stdio;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::thread;
+
+use jsonrpc::*;
+use serde_json;
+use serde_json::Value;
+
+use lsp_errors::LSError;
+use lsp_methods as methods;
+use lsp::LSSendResult;
+use lsp_output::OutputChannel;
+use lsp_output::OutputSender;
+use lsp_transport::LSPBufferedMessageReader;
+use lsp_transport::LSPBufferedMessageWriter;
+
+/// Handles the messages going through a `ProxyServer`. By default, they are forwarded unchanged.
+pub trait ProxyInterceptor : Send + 'static {
+
+    /// Handle a message from the editor to the backend server.
+    /// Returns the message to forward to the backend, if any.
+    #[allow(unused_variables)]
+    fn client_message(&mut self, message: Value, proxy: &ProxyContext) -> Option<Value> {
+        Some(message)
+    }
+
+    /// Handle a message from the backend server to the editor.
+    /// Returns the message to forward to the editor, if any.
+    #[allow(unused_variables)]
+    fn server_message(&mut self, message: Value, proxy: &ProxyContext) -> Option<Value> {
+        Some(message)
+    }
+
+}
+
+/// Forwards all messages unchanged.
+pub struct ForwardAll;
+
+impl ProxyInterceptor for ForwardAll {}
+
+/// The outputs of a proxy, to send messages of its own to either side.
+#[derive(Clone)]
+pub struct ProxyContext {
+    /// The output to the editor.
+    pub to_client: OutputSender,
+    /// The output to the backend server.
+    pub to_server: OutputSender,
+}
+
+impl ProxyContext {
+
+    pub fn send_to_client(&self, message: &Value) -> LSSendResult<()> {
+        self.to_client.send_message(message.to_string())
+    }
+
+    pub fn send_to_server(&self, message: &Value) -> LSSendResult<()> {
+        self.to_server.send_message(message.to_string())
+    }
+
+}
+
+/// Sits between an editor and a backend language server, forwarding messages in both directions
+/// through a `ProxyInterceptor`.
+pub struct ProxyServer<INTERCEPTOR : ProxyInterceptor> {
+    interceptor: Arc<Mutex<INTERCEPTOR>>,
+}
+
+impl<INTERCEPTOR : ProxyInterceptor> ProxyServer<INTERCEPTOR> {
+
+    pub fn new(interceptor: INTERCEPTOR) -> ProxyServer<INTERCEPTOR> {
+        ProxyServer { interceptor : Arc::new(Mutex::new(interceptor)) }
+    }
+
+    /// Forward the messages between the editor and the backend server, until the editor input ends
+    /// or the editor sends `exit`. The output to the backend is then closed, and the messages of the backend
+    /// are forwarded until its output ends.
+    pub fn run<CLIENT_MR, CLIENT_MW, CLIENT_MW_PROV, SERVER_MR, SERVER_MW, SERVER_MW_PROV>(
+        self,
+        client_reader: &mut CLIENT_MR, client_writer_provider: CLIENT_MW_PROV,
+        mut server_reader: SERVER_MR, server_writer_provider: SERVER_MW_PROV,
+    )
+    where
+        CLIENT_MR : MessageReader,
+        CLIENT_MW : MessageWriter + Send + 'static,
+        CLIENT_MW_PROV : FnOnce() -> CLIENT_MW + Send + 'static,
+        SERVER_MR : MessageReader + Send + 'static,
+        SERVER_MW : MessageWriter + Send + 'static,
+        SERVER_MW_PROV : FnOnce() -> SERVER_MW + Send + 'static,
+    {
+        let mut client_channel = OutputChannel::start(client_writer_provider);
+        let mut server_channel = OutputChannel::start(server_writer_provider);
+        let context = ProxyContext { to_client : client_channel.sender(), to_server : server_channel.sender() };
+
+        let server_interceptor = self.interceptor.clone();
+        let server_context = context.clone();
+        let server_thread = thread::spawn(move || {
+            forward_messages(&mut server_reader, &server_interceptor, &server_context, false)
+        });
+
+        forward_messages(client_reader, &self.interceptor, &context, true);
+
+        server_channel.shutdown_and_join();
+        let _ = server_thread.join();
+        client_channel.shutdown_and_join();
+    }
+
+    /// Launch the backend server with given command, and forward the messages between the editor
+    /// and its stdin and stdout, as `run` does. Returns the exit code of the backend.
+    pub fn run_with_command<CLIENT_MR, CLIENT_MW, CLIENT_MW_PROV>(
+        self,
+        client_reader: &mut CLIENT_MR, client_writer_provider: CLIENT_MW_PROV, mut command: Command,
+    ) -> Result<i32, LSError>
+    where
+        CLIENT_MR : MessageReader,
+        CLIENT_MW : MessageWriter + Send + 'static,
+        CLIENT_MW_PROV : FnOnce() -> CLIENT_MW + Send + 'static,
+    {
+        let mut child = try!(command.stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().map_err(LSError::Transport));
+        let (stdin, stdout) = match (child.stdin.take(), child.stdout.take()) {
+            (Some(stdin), Some(stdout)) => (stdin, stdout),
+            _ => {
+                let _ = child.kill();
+                return Err(LSError::Shutdown("The backend server process has no stdio.".to_string()));
+            }
+        };
+
+        self.run(client_reader, client_writer_provider,
+            LSPBufferedMessageReader::new(BufReader::new(stdout)), move || LSPBufferedMessageWriter::new(stdin));
+
+        let status = try!(child.wait().map_err(LSError::Transport));
+        Ok(status.code().unwrap_or(1))
+    }
+
+}
+
+/// Forward the messages of given reader, until its input ends.
+fn forward_messages<MR : MessageReader, INTERCEPTOR : ProxyInterceptor>(
+    msg_reader: &mut MR, interceptor: &Mutex<INTERCEPTOR>, context: &ProxyContext, from_client: bool
+) {
+    let output = if from_client { &context.to_server } else { &context.to_client };
+    loop {
+        let message = match msg_reader.read_next() {
+            Ok(message) => message,
+            Err(error) => {
+                info!("Proxy input from the {} ended: {}", if from_client { "client" } else { "server" }, error);
+                return;
+            }
+        };
+        let message = match serde_json::from_str::<Value>(&message) {
+            Ok(message) => message,
+            Err(_) => {
+                // Let the receiver report the invalid message
+                let _ = output.send_message(message);
+                continue;
+            }
+        };
+        let is_exit = from_client && message.find("method") == Some(&Value::String(methods::EXIT.to_string()));
+
+        let forwarded = {
+            let mut interceptor = match interceptor.lock() {
+                Ok(interceptor) => interceptor,
+                Err(poisoned) => poisoned.into_inner(),
+            };
+            if from_client {
+                interceptor.client_message(message, context)
+            } else {
+                interceptor.server_message(message, context)
+            }
+        };
+        if let Some(message) = forwarded {
+            if output.send_message(message.to_string()).is_err() {
+                return;
+            }
+        }
+        if is_exit {
+            return;
+        }
+    }
+}
+
+
+#[cfg(test)]
+struct ProxiedServer;
+
+#[cfg(test)]
+impl ::lsp::LanguageServerHandling for ProxiedServer {
+}
+
+#[cfg(test)]
+struct PingInterceptor;
+
+#[cfg(test)]
+impl ProxyInterceptor for PingInterceptor {
+    fn client_message(&mut self, message: Value, proxy: &ProxyContext) -> Option<Value> {
+        if message.find("method") != Some(&Value::String("proxy/ping".to_string())) {
+            return Some(message);
+        }
+        let response = format!(r#"{{"jsonrpc":"2.0","id":{},"result":"pong"}}"#, message.find("id").unwrap());
+        proxy.send_to_client(&serde_json::from_str(&response).unwrap()).unwrap();
+        None
+    }
+}
+
+#[test]
+fn proxy_server__test() {
+    use lsp::*;
+    use lsp_testing::pipe;
+    use lsp_transport::JsonRpcMessage;
+    use lsp_transport::parse_jsonrpc_message_bytes;
+    use lsp_transport::parse_transport_message_content;
+    use lsp_transport::write_jsonrpc_message;
+
+    let (proxy_writer, mut server_reader) = pipe();
+    let (server_writer, proxy_reader) = pipe();
+    let server_thread = thread::spawn(move || {
+        let endpoint = LSPEndpoint::create_lsp_output_with_output_stream(|| server_writer);
+        let context = LSServerContext::new(endpoint);
+        LSPEndpoint::run_server_from_input(&mut server_reader, context, ProxiedServer)
+    });
+
+    let (mut client_writer, client_input) = pipe();
+    let (client_output, mut client_reader) = pipe();
+    let proxy_thread = thread::spawn(move || {
+        ProxyServer::new(PingInterceptor).run(
+            &mut LSPBufferedMessageReader::new(client_input), move || LSPBufferedMessageWriter::new(client_output),
+            LSPBufferedMessageReader::new(proxy_reader), move || LSPBufferedMessageWriter::new(proxy_writer))
+    });
+
+    let mut send = |id: Option<u64>, method: &str, params: &str| {
+        let params : Value = serde_json::from_str(params).unwrap();
+        write_jsonrpc_message(&JsonRpcMessage { id : id, method : method, params : &params }, &mut client_writer)
+            .unwrap();
+    };
+    let mut receive = || parse_jsonrpc_message_bytes(&parse_transport_message_content(&mut client_reader).unwrap())
+        .unwrap();
+
+    send(Some(1), "proxy/ping", "null");
+    assert_eq!(receive().find("result"), Some(&Value::String("pong".to_string())));
+
+    send(Some(2), methods::INITIALIZE, r#"{ "processId": null, "rootPath": null, "capabilities": {} }"#);
+    let response = receive();
+    assert_eq!(response.find("id"), Some(&Value::U64(2)));
+    assert!(response.lookup("result.capabilities").is_some());
+
+    send(Some(3), methods::SHUTDOWN, "null");
+    assert_eq!(receive().find("id"), Some(&Value::U64(3)));
+    send(None, methods::EXIT, "null");
+
+    proxy_thread.join().unwrap();
+    assert_eq!(server_thread.join().unwrap(), 0);
+}
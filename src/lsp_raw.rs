@@ -0,0 +1,347 @@
+// Copyright 2016 Bruno Medeiros
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>.
+// This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! JSON-RPC requests whose params are kept as their original JSON text, and only deserialized
+//! when a typed handler needs them. A proxy or router can forward such a request as is,
+//! without the parse to `Value` and serialize again of the params, for each message.
+//!
+//! Only the envelope of the message is scanned: the `params` member is located, not parsed.
+
+use serde;
+use serde_json;
+use serde_json::Value;
+
+use lsp_errors::LSError;
+
+/// The params of a request, as their original JSON text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RawParams {
+    json: String,
+}
+
+impl RawParams {
+
+    /// Create the params for given JSON text. The text is not validated.
+    pub fn from_json(json: String) -> RawParams {
+        RawParams { json : json }
+    }
+
+    pub fn from_value(value: &Value) -> RawParams {
+        RawParams { json : value.to_string() }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.json
+    }
+
+    pub fn into_string(self) -> String {
+        self.json
+    }
+
+    /// Deserialize the params to their type for the method.
+    pub fn deserialize<PARAMS : serde::Deserialize>(&self) -> Result<PARAMS, LSError> {
+        serde_json::from_str(&self.json).map_err(|error| {
+            LSError::Parse("Failed to deserialize params:".to_string(), Some(error.into()))
+        })
+    }
+
+    pub fn to_value(&self) -> Result<Value, LSError> {
+        self.deserialize()
+    }
+
+}
+
+/// A JSON-RPC request, or a notification if it has no `id`, with raw params.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RawRequest {
+    pub id: Option<Value>,
+    pub method: String,
+    pub params: Option<RawParams>,
+}
+
+impl RawRequest {
+
+    /// The JSON-RPC message for this request, with the params copied as they are.
+    pub fn to_message(&self) -> String {
+        let method = Value::String(self.method.clone()).to_string();
+        let mut message = String::with_capacity(
+            48 + method.len() + self.params.as_ref().map_or(0, |params| params.json.len()));
+        message.push_str(r#"{"jsonrpc":"2.0""#);
+        if let Some(ref id) = self.id {
+            message.push_str(r#","id":"#);
+            message.push_str(&id.to_string());
+        }
+        message.push_str(r#","method":"#);
+        message.push_str(&method);
+        if let Some(ref params) = self.params {
+            message.push_str(r#","params":"#);
+            message.push_str(&params.json);
+        }
+        message.push('}');
+        message
+    }
+
+}
+
+/// Parse given message as a JSON-RPC 2.0 request or notification, keeping its params as JSON text.
+/// The envelope is validated as in `lsp_transport::parse_jsonrpc_message_bytes`,
+/// but the params are only checked to be a balanced JSON object or array.
+/// Never panics, whatever the input.
+pub fn parse_raw_request(message: &str) -> Result<RawRequest, LSError> {
+    let mut jsonrpc = None;
+    let mut id = None;
+    let mut method = None;
+    let mut params = None;
+
+    try!(scan_members(message, |key, value| {
+        match key {
+            "jsonrpc" => jsonrpc = Some(try!(parse_json::<Value>(value))),
+            "id" => id = Some(try!(parse_json::<Value>(value))),
+            "method" => method = Some(try!(parse_json::<Value>(value))),
+            "params" => params = Some(value),
+            _ => {}
+        }
+        Ok(())
+    }));
+
+    if jsonrpc.as_ref().and_then(Value::as_str) != Some("2.0") {
+        return Err(LSError::Protocol("Message `jsonrpc` is not \"2.0\".".to_string()));
+    }
+    match id {
+        None | Some(Value::Null) | Some(Value::String(_)) | Some(Value::I64(_)) | Some(Value::U64(_)) => {}
+        Some(_) => return Err(LSError::Protocol("Message `id` is not a string or integer.".to_string())),
+    }
+    let method = match method {
+        Some(Value::String(method)) => method,
+        Some(_) => return Err(LSError::Protocol("Message `method` is not a string.".to_string())),
+        None => return Err(LSError::Protocol("Message is not a request or notification.".to_string())),
+    };
+    let params = match params {
+        None => None,
+        Some(params) if params.starts_with('{') || params.starts_with('[') => {
+            Some(RawParams::from_json(params.to_string()))
+        }
+        Some(_) => return Err(LSError::Protocol("Message `params` is not structured.".to_string())),
+    };
+
+    Ok(RawRequest { id : id, method : method, params : params })
+}
+
+/// The `id` and `method` of a JSON-RPC message, whether a request, a notification or a response.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MessageEnvelope {
+    pub id: Option<Value>,
+    pub method: Option<String>,
+}
+
+/// Scan given message for its `id` and `method`, without parsing the other members, such as the params
+/// of a request, or the result of a response. The message is not validated further.
+pub fn scan_message_envelope(message: &str) -> Result<MessageEnvelope, LSError> {
+    let mut envelope = MessageEnvelope { id : None, method : None };
+    try!(scan_members(message, |key, value| {
+        match key {
+            "id" => envelope.id = Some(try!(parse_json(value))),
+            "method" => envelope.method = Some(try!(parse_json(value))),
+            _ => {}
+        }
+        Ok(())
+    }));
+    Ok(envelope)
+}
+
+/// Call `visit` with the key and the JSON text of the value of each member of given JSON object.
+fn scan_members<'a, VISIT>(message: &'a str, mut visit: VISIT) -> Result<(), LSError>
+where
+    VISIT : FnMut(&str, &'a str) -> Result<(), LSError>,
+{
+    let bytes = message.as_bytes();
+    let mut scanner = Scanner { bytes : bytes, pos : 0 };
+
+    try!(scanner.expect(b'{'));
+    if !try!(scanner.next_is(b'}')) {
+        loop {
+            let key_start = scanner.pos;
+            try!(scanner.skip_string());
+            let key : String = try!(parse_json(&message[key_start..scanner.pos]));
+            try!(scanner.expect(b':'));
+
+            scanner.skip_whitespace();
+            let value_start = scanner.pos;
+            try!(scanner.skip_value());
+            try!(visit(&key, &message[value_start..scanner.pos]));
+
+            if try!(scanner.next_is(b'}')) {
+                break;
+            }
+            try!(scanner.expect(b','));
+        }
+    }
+    scanner.skip_whitespace();
+    if scanner.pos != bytes.len() {
+        return Err(invalid_json("trailing characters"));
+    }
+    Ok(())
+}
+
+fn parse_json<T : serde::Deserialize>(json: &str) -> Result<T, LSError> {
+    serde_json::from_str(json).map_err(|error| {
+        LSError::Parse("Message is not valid JSON:".to_string(), Some(error.into()))
+    })
+}
+
+fn invalid_json(reason: &str) -> LSError {
+    LSError::Parse(format!("Message is not valid JSON: {}.", reason), None)
+}
+
+/// Finds the extent of JSON values, without parsing them.
+struct Scanner<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Scanner<'a> {
+
+    fn skip_whitespace(&mut self) {
+        while self.pos < self.bytes.len() {
+            match self.bytes[self.pos] {
+                b' ' | b'\t' | b'\n' | b'\r' => self.pos += 1,
+                _ => break,
+            }
+        }
+    }
+
+    /// Skip whitespace, and consume the next byte if it is `byte`.
+    fn next_is(&mut self, byte: u8) -> Result<bool, LSError> {
+        self.skip_whitespace();
+        match self.bytes.get(self.pos) {
+            Some(&next) if next == byte => {
+                self.pos += 1;
+                Ok(true)
+            }
+            Some(_) => Ok(false),
+            None => Err(invalid_json("unexpected end")),
+        }
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<(), LSError> {
+        if try!(self.next_is(byte)) {
+            Ok(())
+        } else {
+            Err(invalid_json(&format!("expected `{}`", byte as char)))
+        }
+    }
+
+    fn skip_string(&mut self) -> Result<(), LSError> {
+        try!(self.expect(b'"'));
+        while self.pos < self.bytes.len() {
+            match self.bytes[self.pos] {
+                b'"' => {
+                    self.pos += 1;
+                    return Ok(());
+                }
+                b'\\' => self.pos += 2,
+                _ => self.pos += 1,
+            }
+        }
+        Err(invalid_json("unterminated string"))
+    }
+
+    /// Skip a value, checking only that the brackets of a structure are balanced.
+    /// Scalars are checked when parsed, if they are.
+    fn skip_value(&mut self) -> Result<(), LSError> {
+        self.skip_whitespace();
+        match self.bytes.get(self.pos) {
+            None => Err(invalid_json("unexpected end")),
+            Some(&b'"') => self.skip_string(),
+            Some(&b'{') | Some(&b'[') => self.skip_structure(),
+            Some(_) => {
+                while self.pos < self.bytes.len() {
+                    match self.bytes[self.pos] {
+                        b',' | b'}' | b']' | b' ' | b'\t' | b'\n' | b'\r' => break,
+                        _ => self.pos += 1,
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn skip_structure(&mut self) -> Result<(), LSError> {
+        let mut closing = vec![];
+        while self.pos < self.bytes.len() {
+            match self.bytes[self.pos] {
+                b'"' => {
+                    try!(self.skip_string());
+                    continue;
+                }
+                b'{' => closing.push(b'}'),
+                b'[' => closing.push(b']'),
+                byte @ b'}' | byte @ b']' => {
+                    if closing.pop() != Some(byte) {
+                        return Err(invalid_json("unbalanced brackets"));
+                    }
+                    if closing.is_empty() {
+                        self.pos += 1;
+                        return Ok(());
+                    }
+                }
+                _ => {}
+            }
+            self.pos += 1;
+        }
+        Err(invalid_json("unexpected end"))
+    }
+
+}
+
+
+#[test]
+fn parse_raw_request__test() {
+    let message = r#"{ "jsonrpc": "2.0", "id": 1, "method": "textDocument/hover",
+        "params": { "position": { "line": 1, "character": 2 }, "text": "a \"}\" b" } }"#;
+    let request = parse_raw_request(message).unwrap();
+    assert_eq!(request.id, Some(Value::U64(1)));
+    assert_eq!(request.method, "textDocument/hover");
+    let params = request.params.clone().unwrap();
+    assert_eq!(params.as_str(), r#"{ "position": { "line": 1, "character": 2 }, "text": "a \"}\" b" }"#);
+    assert_eq!(params.to_value().unwrap().lookup("position.line"), Some(&Value::U64(1)));
+
+    assert_eq!(request.to_message(),
+        r#"{"jsonrpc":"2.0","id":1,"method":"textDocument/hover","params":"#.to_string() + params.as_str() + "}");
+    assert_eq!(parse_raw_request(&request.to_message()).unwrap(), request);
+
+    let request = parse_raw_request(r#"{"params":[true,null],"method":"exit","jsonrpc":"2.0"}"#).unwrap();
+    assert_eq!(request.id, None);
+    assert_eq!(request.params.unwrap().deserialize::<(bool, Option<u32>)>().unwrap(), (true, None));
+
+    let request = parse_raw_request(r#"{"jsonrpc":"2.0","method":"exit"}"#).unwrap();
+    assert_eq!(request.params, None);
+
+    assert!(parse_raw_request(r#"{"jsonrpc":"2.0","method":"exit","params":1}"#).is_err());
+    assert!(parse_raw_request(r#"{"jsonrpc":"2.0","method":"exit","params":[}"#).is_err());
+    assert!(parse_raw_request(r#"{"jsonrpc":"1.0","method":"exit"}"#).is_err());
+    assert!(parse_raw_request(r#"{"jsonrpc":"2.0","id":1,"result":null}"#).is_err());
+    assert!(parse_raw_request(r#"{"jsonrpc":"2.0","method":"exit"} x"#).is_err());
+    assert!(parse_raw_request(r#"{"jsonrpc":"2.0","method":"exit""#).is_err());
+
+    for length in 0..message.len() {
+        if message.is_char_boundary(length) {
+            let _ = parse_raw_request(&message[..length]);
+        }
+    }
+}
+
+#[test]
+fn scan_message_envelope__test() {
+    let envelope = scan_message_envelope(r#"{"jsonrpc":"2.0","id":"a","result":{"items":[1, "}"]}}"#).unwrap();
+    assert_eq!(envelope, MessageEnvelope { id : Some(Value::String("a".to_string())), method : None });
+    let envelope = scan_message_envelope(r#"{"jsonrpc":"2.0","method":"exit"}"#).unwrap();
+    assert_eq!(envelope, MessageEnvelope { id : None, method : Some("exit".to_string()) });
+
+    assert!(scan_message_envelope(r#"{"jsonrpc":"2.0","id":1,"result":[}"#).is_err());
+    assert!(scan_message_envelope(r#"{"jsonrpc":"2.0","method":1}"#).is_err());
+}
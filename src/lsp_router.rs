@@ -0,0 +1,225 @@
+// Copyright 2016 Bruno Medeiros
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>.
+// This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Typed message handling and sending for either role, client or server, based on the method
+//! marker types of `lsp_methods`, which implement `LSRequest` and `LSNotification`.
+//! The marker type gives both the method name and its params and result types, so the handler
+//! of a method and the sender of it, on the other side, agree on them at compile time.
+//!
+//! ```ignore
+//! let mut router = MethodRouter::new();
+//! router.on::<methods::HoverRequest, _>(|params, completable| completable.complete(Ok(hover(params))));
+//! router.on_notification::<methods::DidOpenTextDocument, _>(|params| open(params));
+//! LSPEndpoint::run_endpoint_loop(&mut input, endpoint, Box::new(router));
+//!
+//! // On the other side:
+//! let future = LSPeer::new(endpoint).send::<methods::HoverRequest>(params);
+//! ```
+
+use std::collections::HashMap;
+
+use jsonrpc::*;
+use jsonrpc::jsonrpc_request::RequestParams;
+use jsonrpc::method_types::MethodError;
+
+use lsp::*;
+use lsp_methods::LSNotification;
+use lsp_methods::LSRequest;
+
+type MethodHandler = Box<FnMut(RequestParams, ResponseCompletable)>;
+
+/// A request handler that dispatches each method to the handler registered for it.
+/// Methods without a handler are answered with MethodNotFound, if they are requests.
+#[derive(Default)]
+pub struct MethodRouter {
+    handlers: HashMap<&'static str, MethodHandler>,
+}
+
+impl MethodRouter {
+
+    pub fn new() -> MethodRouter {
+        MethodRouter::default()
+    }
+
+    /// Handle request `REQ` with given handler. Replaces the previous handler of the method, if any.
+    pub fn on<REQ, HANDLER>(&mut self, mut handler: HANDLER) -> &mut Self
+    where
+        REQ : LSRequest,
+        HANDLER : FnMut(REQ::Params, LSMethodCompletable<REQ>) + 'static,
+    {
+        self.handlers.insert(REQ::METHOD, Box::new(move |params, completable: ResponseCompletable| {
+            completable.handle_request_with(params,
+                |params: REQ::Params, completable: LSMethodCompletable<REQ>| handler(params, completable))
+        }));
+        self
+    }
+
+    /// Handle request `REQ` with given function, which returns the result of the request.
+    /// As with `on`, the function must take the params type of `REQ` and return its result type,
+    /// so a handler registered for the wrong method doesn't compile, instead of failing each request
+    /// with InvalidParams:
+    ///
+    /// ```compile_fail
+    /// # extern crate rust_lsp;
+    /// # use rust_lsp::ls_types::RenameParams;
+    /// # use rust_lsp::lsp::methods;
+    /// # use rust_lsp::lsp_router::MethodRouter;
+    /// # fn main() {
+    /// MethodRouter::new().on_request::<methods::HoverRequest, _>(|_: RenameParams| unimplemented!());
+    /// # }
+    /// ```
+    pub fn on_request<REQ, HANDLER>(&mut self, mut handler: HANDLER) -> &mut Self
+    where
+        REQ : LSRequest,
+        HANDLER : FnMut(REQ::Params) -> Result<REQ::Result, MethodError<REQ::ErrorData>> + 'static,
+    {
+        self.on::<REQ, _>(move |params, completable| completable.complete(handler(params)))
+    }
+
+    /// Handle notification `NOTIF` with given handler. Replaces the previous handler of the method, if any.
+    pub fn on_notification<NOTIF, HANDLER>(&mut self, mut handler: HANDLER) -> &mut Self
+    where
+        NOTIF : LSNotification,
+        HANDLER : FnMut(NOTIF::Params) + 'static,
+    {
+        self.handlers.insert(NOTIF::METHOD, Box::new(move |params, completable| {
+            handle_lsp_notification::<NOTIF, _>(params, completable, |params| handler(params))
+        }));
+        self
+    }
+
+    pub fn handles(&self, method_name: &str) -> bool {
+        self.handlers.contains_key(method_name)
+    }
+
+}
+
+impl LSRequestHandler for MethodRouter {
+
+    fn handle_ls_request(&mut self, request: RequestInfo, method_name: &str, params: RequestParams,
+        completable: ResponseCompletable)
+    {
+        match self.handlers.get_mut(method_name) {
+            Some(handler) => handler(params, completable),
+            None => handle_unknown_method(&request, method_name, completable),
+        }
+    }
+
+}
+
+/// Sends typed requests and notifications to the other side of an endpoint, whichever role it has.
+#[derive(Clone)]
+pub struct LSPeer {
+    pub endpoint: Endpoint,
+}
+
+impl LSPeer {
+
+    pub fn new(endpoint: Endpoint) -> LSPeer {
+        LSPeer { endpoint : endpoint }
+    }
+
+    /// Send request `REQ`, with its typed params. The future has the typed result.
+    pub fn send<REQ>(&self, params: REQ::Params) -> LSSendResult<LSRequestFuture<REQ>>
+    where
+        REQ : LSRequest,
+    {
+        send_lsp_request::<REQ>(&mut self.endpoint.clone(), params)
+    }
+
+    /// Send notification `NOTIF`, with its typed params.
+    pub fn notify<NOTIF>(&self, params: NOTIF::Params) -> LSSendResult<()>
+    where
+        NOTIF : LSNotification,
+    {
+        send_lsp_notification::<NOTIF>(&mut self.endpoint.clone(), params)
+    }
+
+}
+
+
+#[test]
+fn method_router__test() {
+    use std::sync::mpsc;
+    use std::thread;
+    use ls_types::*;
+    use url::Url;
+    use lsp_markup::LSHoverContents;
+    use lsp_testing::pipe;
+    use lsp_transport::LSPBufferedMessageReader;
+
+    fn rename_params() -> RenameParams {
+        RenameParams {
+            text_document : TextDocumentIdentifier { uri : Url::parse("file:///doc.txt").unwrap() },
+            position : Position { line : 1, character : 2 },
+            new_name : "renamed".to_string(),
+        }
+    }
+
+    let (client_writer, server_reader) = pipe();
+    let (server_writer, client_reader) = pipe();
+
+    // The server answers hovers, and logs each to the client
+    let server_endpoint = LSPEndpoint::create_lsp_output_with_output_stream(|| server_writer);
+    let server_peer = LSPeer::new(server_endpoint.clone());
+    let server_thread = thread::spawn(move || {
+        let mut router = MethodRouter::new();
+        router.on::<methods::HoverRequest, _>(move |params, completable| {
+            let message = format!("hover at {}", params.position.line);
+            let log = LogMessageParams { typ : MessageType::Log, message : message.clone() };
+            server_peer.notify::<methods::LogMessage>(log).unwrap();
+            let hover = Hover { contents : vec![MarkedString::String(message)], range : None };
+            completable.complete(Ok(LSHover::from(hover)))
+        });
+        router.on_request::<methods::Rename, _>(|params| {
+            assert_eq!(params, rename_params());
+            Err(MethodError { code : 1, message : "Not renamed.".to_string(), data : None })
+        });
+        let input = &mut LSPBufferedMessageReader::new(server_reader);
+        let _ = LSPEndpoint::run_endpoint_loop(input, server_endpoint.clone(), Box::new(router));
+        // Close the output, which ends the input of the client
+        server_endpoint.clone().shutdown_and_join();
+    });
+
+    let mut client_endpoint = LSPEndpoint::create_lsp_output_with_output_stream(|| client_writer);
+    let (log_sender, logs) = mpsc::channel();
+    let client_loop_endpoint = client_endpoint.clone();
+    let client_thread = thread::spawn(move || {
+        let mut router = MethodRouter::new();
+        router.on_notification::<methods::LogMessage, _>(move |params| log_sender.send(params.message).unwrap());
+        assert!(router.handles(methods::WINDOW_LOG_MESSAGE));
+        let input = &mut LSPBufferedMessageReader::new(client_reader);
+        let _ = LSPEndpoint::run_endpoint_loop(input, client_loop_endpoint, Box::new(router));
+    });
+
+    let client_peer = LSPeer::new(client_endpoint.clone());
+    let params = TextDocumentPositionParams {
+        text_document : TextDocumentIdentifier { uri : Url::parse("file:///doc.txt").unwrap() },
+        position : Position { line : 3, character : 0 },
+    };
+    match client_peer.send::<methods::HoverRequest>(params).unwrap().wait() {
+        Ok(Ok(hover)) => match hover.contents {
+            LSHoverContents::MarkedStrings(ref strings) => assert_eq!(strings.len(), 1),
+            ref contents => panic!("Unexpected hover: {:?}", contents),
+        },
+        _ => panic!("Hover failed."),
+    }
+    assert_eq!(logs.recv().unwrap(), "hover at 3");
+    match client_peer.send::<methods::Rename>(rename_params()).unwrap().wait() {
+        Ok(Err(error)) => assert_eq!(error.message, "Not renamed."),
+        _ => panic!("Expected a rename error."),
+    }
+    // Unhandled requests are answered with MethodNotFound
+    match client_peer.send::<methods::Shutdown>(()).unwrap().wait() {
+        Ok(Err(error)) => assert_eq!(error.code, -32601),
+        _ => panic!("Expected a MethodNotFound error."),
+    }
+
+    client_endpoint.shutdown_and_join();
+    server_thread.join().unwrap();
+    client_thread.join().unwrap();
+}
@@ -0,0 +1,708 @@
+// Copyright 2016 Bruno Medeiros
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>.
+// This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Scheduling of work per document: jobs for the same document run in submission order,
+//! jobs for different documents can run concurrently.
+//! And scheduling of work by priority, so that background work doesn't delay user-facing requests.
+//! And superseding of in-flight requests by newer ones of the same kind, for the same document.
+//! And background tasks of a server, which can be cancelled, and are drained on shutdown.
+
+use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::Hash;
+use std::hash::Hasher;
+use std::sync::Arc;
+use std::sync::Condvar;
+use std::sync::Mutex;
+use std::sync::MutexGuard;
+use std::sync::Weak;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use jsonrpc::jsonrpc_request::RequestParams;
+use serde_json::Value;
+
+use lsp::LSMethodCompletable;
+use lsp_cancellation::CancellationToken;
+use lsp::LSResult;
+use lsp::complete_lsp_request;
+use lsp_errors::method_error_ContentModified;
+use lsp_methods as methods;
+use lsp_methods::LSRequest;
+
+pub type Job = Box<FnOnce() + Send + 'static>;
+
+/// A pool of worker threads, where each document is assigned to a single worker.
+pub struct DocumentScheduler {
+    workers: Vec<Worker>,
+}
+
+struct Worker {
+    sender: Option<mpsc::Sender<Job>>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl DocumentScheduler {
+
+    pub fn new(worker_count: usize) -> DocumentScheduler {
+        assert!(worker_count > 0);
+
+        let workers = (0..worker_count).map(|_| {
+            let (sender, receiver) = mpsc::channel::<Job>();
+            let thread = thread::spawn(move || {
+                for job in receiver {
+                    job();
+                }
+            });
+            Worker { sender : Some(sender), thread : Some(thread) }
+        }).collect();
+
+        DocumentScheduler { workers : workers }
+    }
+
+    pub fn worker_count(&self) -> usize {
+        self.workers.len()
+    }
+
+    fn worker_index(&self, document_uri: &str) -> usize {
+        let mut hasher = DefaultHasher::new();
+        document_uri.hash(&mut hasher);
+        (hasher.finish() % self.workers.len() as u64) as usize
+    }
+
+    /// Schedule given job to run after all jobs previously scheduled for the same document.
+    pub fn schedule<JOB>(&self, document_uri: &str, job: JOB)
+    where
+        JOB : FnOnce() + Send + 'static
+    {
+        let worker = &self.workers[self.worker_index(document_uri)];
+        if let Some(ref sender) = worker.sender {
+            // Only fails if the worker thread panicked, in which case the job is dropped.
+            if sender.send(Box::new(job)).is_err() {
+                error!("Document scheduler worker has terminated, job for {} dropped.", document_uri);
+            }
+        }
+    }
+
+    /// Wait for the jobs scheduled so far, for all documents, to finish.
+    pub fn wait_idle(&self) {
+        let (done_sender, done) = mpsc::channel::<()>();
+        for worker in &self.workers {
+            if let Some(ref sender) = worker.sender {
+                let done_sender = done_sender.clone();
+                let _ = sender.send(Box::new(move || { let _ = done_sender.send(()); }));
+            }
+        }
+        drop(done_sender);
+        // Ends once every worker ran its marker job, or terminated without running it
+        for _ in done {}
+    }
+
+}
+
+impl Drop for DocumentScheduler {
+
+    /// Wait for all scheduled jobs to finish.
+    fn drop(&mut self) {
+        for worker in &mut self.workers {
+            drop(worker.sender.take());
+        }
+        for worker in &mut self.workers {
+            if let Some(thread) = worker.thread.take() {
+                let _ = thread.join();
+            }
+        }
+    }
+
+}
+
+/* ----------------- Priority pool ----------------- */
+
+/// The priority lanes of a `PriorityPool`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lane {
+    /// User-facing requests, such as completion or hover.
+    Interactive,
+    /// Work the user is not waiting for, such as processing `didChangeWatchedFiles`.
+    Background,
+}
+
+/// The default lane of given method: notifications are `Background`, requests are `Interactive`.
+pub fn lane_of_method(method: &str) -> Lane {
+    match method {
+        methods::WORKSPACE_DID_CHANGE_WATCHED_FILES |
+        methods::WORKSPACE_DID_CHANGE_CONFIGURATION |
+        methods::TEXT_DOCUMENT_DID_SAVE |
+        methods::TEXT_DOCUMENT_DID_CLOSE => Lane::Background,
+        _ => Lane::Interactive,
+    }
+}
+
+#[derive(Default)]
+struct LaneQueues {
+    interactive: VecDeque<Job>,
+    background: VecDeque<Job>,
+}
+
+impl LaneQueues {
+    fn lane(&mut self, lane: Lane) -> &mut VecDeque<Job> {
+        match lane {
+            Lane::Interactive => &mut self.interactive,
+            Lane::Background => &mut self.background,
+        }
+    }
+}
+
+#[derive(Default)]
+struct PoolState {
+    pending: usize,
+    /// The jobs submitted and not finished yet, whether pending or running.
+    unfinished: usize,
+    running_background: usize,
+    shutdown: bool,
+}
+
+struct PoolShared {
+    /// The queues of each worker. A worker takes from the front of its own queues,
+    /// and steals from the back of the others.
+    queues: Vec<Mutex<LaneQueues>>,
+    state: Mutex<PoolState>,
+    condvar: Condvar,
+    next_worker: AtomicUsize,
+    /// The maximum number of workers running background jobs at the same time.
+    max_background: usize,
+}
+
+/// A pool of worker threads with two priority lanes, where idle workers steal jobs from busy ones.
+/// Interactive jobs are always taken before background ones, and one worker (if there are more than
+/// one) never runs background jobs, so a burst of background work cannot starve interactive jobs.
+pub struct PriorityPool {
+    shared: Arc<PoolShared>,
+    threads: Vec<thread::JoinHandle<()>>,
+}
+
+impl PriorityPool {
+
+    pub fn new(worker_count: usize) -> PriorityPool {
+        assert!(worker_count > 0);
+
+        let shared = Arc::new(PoolShared {
+            queues : (0..worker_count).map(|_| Mutex::new(LaneQueues::default())).collect(),
+            state : Mutex::new(PoolState::default()),
+            condvar : Condvar::new(),
+            next_worker : AtomicUsize::new(0),
+            max_background : if worker_count > 1 { worker_count - 1 } else { 1 },
+        });
+        let threads = (0..worker_count).map(|index| {
+            let shared = shared.clone();
+            thread::spawn(move || shared.run_worker(index))
+        }).collect();
+
+        PriorityPool { shared : shared, threads : threads }
+    }
+
+    pub fn worker_count(&self) -> usize {
+        self.threads.len()
+    }
+
+    /// Submit given job to given lane.
+    pub fn submit<JOB>(&self, lane: Lane, job: JOB)
+    where
+        JOB : FnOnce() + Send + 'static
+    {
+        let shared = &self.shared;
+        let index = shared.next_worker.fetch_add(1, Ordering::Relaxed) % shared.queues.len();
+        // Count the job before it can be taken, which decrements the count
+        {
+            let mut state = lock(&shared.state);
+            state.pending += 1;
+            state.unfinished += 1;
+        }
+        lock(&shared.queues[index]).lane(lane).push_back(Box::new(job));
+        shared.condvar.notify_all();
+    }
+
+    /// Wait for all the jobs submitted so far to finish. Must not be called from a job.
+    pub fn wait_idle(&self) {
+        let mut state = lock(&self.shared.state);
+        while state.unfinished > 0 {
+            state = match self.shared.condvar.wait_timeout(state, Duration::from_millis(50)) {
+                Ok((state, _)) => state,
+                Err(poisoned) => poisoned.into_inner().0,
+            };
+        }
+    }
+
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<T> {
+    // A job panicking doesn't leave the queues inconsistent
+    match mutex.lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+    }
+}
+
+impl PoolShared {
+
+    fn take_job(&self, index: usize, lane: Lane) -> Option<Job> {
+        if let Some(job) = lock(&self.queues[index]).lane(lane).pop_front() {
+            return Some(job);
+        }
+        let count = self.queues.len();
+        for offset in 1..count {
+            if let Some(job) = lock(&self.queues[(index + offset) % count]).lane(lane).pop_back() {
+                return Some(job);
+            }
+        }
+        None
+    }
+
+    fn find_job(&self, index: usize) -> Option<(Lane, Job)> {
+        if let Some(job) = self.take_job(index, Lane::Interactive) {
+            lock(&self.state).pending -= 1;
+            return Some((Lane::Interactive, job));
+        }
+
+        {
+            let mut state = lock(&self.state);
+            if state.running_background >= self.max_background {
+                return None;
+            }
+            state.running_background += 1;
+        }
+        if let Some(job) = self.take_job(index, Lane::Background) {
+            lock(&self.state).pending -= 1;
+            return Some((Lane::Background, job));
+        }
+        lock(&self.state).running_background -= 1;
+        None
+    }
+
+    fn run_worker(&self, index: usize) {
+        loop {
+            if let Some((lane, job)) = self.find_job(index) {
+                let _finished = FinishedJob(self);
+                job();
+                if lane == Lane::Background {
+                    lock(&self.state).running_background -= 1;
+                }
+                continue;
+            }
+
+            let state = lock(&self.state);
+            if state.pending == 0 && state.shutdown {
+                return;
+            }
+            // Wait for a job, or re-check if there are pending jobs that this worker couldn't take.
+            // The timeout guards against missing a notification between checking the queues and waiting.
+            let _ = self.condvar.wait_timeout(state, Duration::from_millis(50));
+        }
+    }
+
+}
+
+/// Counts a job as finished once dropped, even if the job panicked.
+struct FinishedJob<'a>(&'a PoolShared);
+
+impl<'a> Drop for FinishedJob<'a> {
+    fn drop(&mut self) {
+        lock(&self.0.state).unfinished -= 1;
+        self.0.condvar.notify_all();
+    }
+}
+
+impl Drop for PriorityPool {
+
+    /// Wait for all submitted jobs to finish.
+    fn drop(&mut self) {
+        lock(&self.shared.state).shutdown = true;
+        self.shared.condvar.notify_all();
+        for thread in self.threads.drain(..) {
+            let _ = thread.join();
+        }
+    }
+
+}
+
+/* ----------------- Superseded requests ----------------- */
+
+/// A request registered with `SupersedingRequests::begin`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestTicket {
+    key: String,
+    generation: usize,
+}
+
+/// The in-flight requests of method `REQ`, at most one per key (usually the document URI).
+/// Beginning a request supersedes the in-flight one with the same key, which is answered right away
+/// with `ContentModified`, such as a completion request after another keystroke.
+///
+/// This is opt-in, per method: a handler begins its request here, computes the result
+/// (possibly on another thread, checking `is_superseded` to give up early) and completes it here.
+pub struct SupersedingRequests<REQ : LSRequest> {
+    inflight: Arc<Mutex<HashMap<String, (usize, LSMethodCompletable<REQ>)>>>,
+    next_generation: Arc<AtomicUsize>,
+}
+
+impl<REQ : LSRequest> Clone for SupersedingRequests<REQ> {
+    fn clone(&self) -> Self {
+        SupersedingRequests { inflight : self.inflight.clone(), next_generation : self.next_generation.clone() }
+    }
+}
+
+impl<REQ : LSRequest> SupersedingRequests<REQ>
+where
+    REQ::ErrorData : Default,
+{
+
+    pub fn new() -> SupersedingRequests<REQ> {
+        SupersedingRequests { inflight : Arc::new(Mutex::new(HashMap::new())), next_generation : Arc::new(AtomicUsize::new(0)) }
+    }
+
+    /// Register given request as the in-flight one for `key`, superseding the previous one.
+    pub fn begin(&self, key: &str, completable: LSMethodCompletable<REQ>) -> RequestTicket {
+        let generation = self.next_generation.fetch_add(1, Ordering::Relaxed);
+        let superseded = lock(&self.inflight).insert(key.to_string(), (generation, completable));
+        if let Some((_, superseded)) = superseded {
+            superseded.complete(Err(method_error_ContentModified()));
+        }
+        RequestTicket { key : key.to_string(), generation : generation }
+    }
+
+    /// Whether the request of given ticket was superseded (and already answered).
+    pub fn is_superseded(&self, ticket: &RequestTicket) -> bool {
+        match lock(&self.inflight).get(&ticket.key) {
+            Some(&(generation, _)) => generation != ticket.generation,
+            None => true,
+        }
+    }
+
+    /// Complete the request of given ticket with given result, unless it was superseded,
+    /// in which case the result is discarded. Returns whether the request was completed.
+    pub fn complete(&self, ticket: &RequestTicket, result: LSResult<REQ::Result, REQ::ErrorData>) -> bool {
+        let completable = {
+            let mut inflight = lock(&self.inflight);
+            match inflight.get(&ticket.key) {
+                Some(&(generation, _)) if generation == ticket.generation => {}
+                _ => return false,
+            }
+            inflight.remove(&ticket.key)
+        };
+        match completable {
+            Some((_, completable)) => {
+                complete_lsp_request(completable, result);
+                true
+            }
+            None => false,
+        }
+    }
+
+}
+
+/* ----------------- Background tasks ----------------- */
+
+pub const DEFAULT_BACKGROUND_WORKERS : usize = 2;
+
+struct BackgroundTask {
+    document_uri: Option<String>,
+    token: CancellationToken,
+}
+
+#[derive(Default)]
+struct TasksState {
+    /// Started on the first spawn.
+    pool: Option<PriorityPool>,
+    /// The tasks not finished yet, by id.
+    tasks: HashMap<usize, BackgroundTask>,
+    next_id: usize,
+    shutdown: bool,
+}
+
+/// The background work of a server, such as indexing or analysis launched from notification handlers.
+/// Each task gets a token that is cancelled when its document changes, or the server shuts down,
+/// and the server waits for the running tasks on shutdown. Clones share the same tasks.
+///
+/// The server context owns one, see `LSServerContext::background_tasks`.
+#[derive(Clone)]
+pub struct BackgroundTasks {
+    worker_count: usize,
+    state: Arc<Mutex<TasksState>>,
+}
+
+impl BackgroundTasks {
+
+    pub fn new(worker_count: usize) -> BackgroundTasks {
+        assert!(worker_count > 0);
+        BackgroundTasks { worker_count : worker_count, state : Arc::new(Mutex::new(TasksState::default())) }
+    }
+
+    /// Run given task in given lane, for given document if any. Returns the token of the task.
+    /// A task should check its token regularly, and return early once cancelled.
+    /// After `shutdown`, the task is not run, and the token returned is cancelled.
+    pub fn spawn<TASK>(&self, lane: Lane, document_uri: Option<&str>, task: TASK) -> CancellationToken
+    where
+        TASK : FnOnce(&CancellationToken) + Send + 'static
+    {
+        let token = CancellationToken::new();
+        let mut state = lock(&self.state);
+        if state.shutdown {
+            token.cancel();
+            return token;
+        }
+
+        let id = state.next_id;
+        state.next_id += 1;
+        let task_entry = BackgroundTask { document_uri : document_uri.map(String::from), token : token.clone() };
+        state.tasks.insert(id, task_entry);
+
+        let worker_count = self.worker_count;
+        let tasks_state = Arc::downgrade(&self.state);
+        let task_token = token.clone();
+        let pool = state.pool.get_or_insert_with(|| PriorityPool::new(worker_count));
+        pool.submit(lane, move || {
+            if !task_token.is_cancelled() {
+                task(&task_token);
+            }
+            task_finished(&tasks_state, id);
+        });
+        token
+    }
+
+    /// Cancel the tasks of given document, such as when it changes.
+    pub fn cancel_document(&self, document_uri: &str) {
+        for task in lock(&self.state).tasks.values() {
+            if task.document_uri.as_ref().map(String::as_str) == Some(document_uri) {
+                task.token.cancel();
+            }
+        }
+    }
+
+    pub fn cancel_all(&self) {
+        for task in lock(&self.state).tasks.values() {
+            task.token.cancel();
+        }
+    }
+
+    /// The number of tasks not finished yet, cancelled or not.
+    pub fn task_count(&self) -> usize {
+        lock(&self.state).tasks.len()
+    }
+
+    /// Cancel all tasks, and wait for them to finish. Tasks spawned afterwards are not run.
+    /// Must not be called from a task.
+    pub fn shutdown(&self) {
+        let pool = {
+            let mut state = lock(&self.state);
+            state.shutdown = true;
+            for task in state.tasks.values() {
+                task.token.cancel();
+            }
+            state.pool.take()
+        };
+        // Joins the workers, which need the state to finish their tasks
+        drop(pool);
+    }
+
+}
+
+fn task_finished(state: &Weak<Mutex<TasksState>>, id: usize) {
+    if let Some(state) = state.upgrade() {
+        lock(&state).tasks.remove(&id);
+    }
+}
+
+/* ----------------- ----------------- */
+
+/// Get the `textDocument.uri` of given request params, if present.
+/// This is the key to use for scheduling document-specific methods.
+pub fn document_uri_of_params(params: &RequestParams) -> Option<&str> {
+    match *params {
+        RequestParams::Object(ref obj) => {
+            match obj.get("textDocument") {
+                Some(&Value::Object(ref text_document)) => {
+                    text_document.get("uri").and_then(|uri| uri.as_str())
+                }
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+
+#[test]
+fn document_scheduler__test() {
+    let log = Arc::new(Mutex::new(Vec::<(String, u32)>::new()));
+    {
+        let scheduler = DocumentScheduler::new(4);
+        for ix in 0..50 {
+            for uri in &["file:///a", "file:///b", "file:///c"] {
+                let log = log.clone();
+                let uri = uri.to_string();
+                scheduler.schedule(&uri.clone(), move || log.lock().unwrap().push((uri, ix)));
+            }
+        }
+        scheduler.wait_idle();
+        assert_eq!(log.lock().unwrap().len(), 150);
+    }
+
+    let log = log.lock().unwrap();
+    assert_eq!(log.len(), 150);
+    for uri in &["file:///a", "file:///b", "file:///c"] {
+        let order : Vec<u32> = log.iter().filter(|entry| &entry.0 == uri).map(|entry| entry.1).collect();
+        assert_eq!(order, (0..50).collect::<Vec<u32>>());
+    }
+}
+
+/// Submit a job that blocks its worker, once it has started, until the returned sender is sent to.
+#[cfg(test)]
+fn submit_blocking_job(pool: &PriorityPool, lane: Lane) -> mpsc::Sender<()> {
+    let (started_sender, started) = mpsc::channel();
+    let (release, released) = mpsc::channel::<()>();
+    pool.submit(lane, move || {
+        started_sender.send(()).unwrap();
+        released.recv().unwrap();
+    });
+    started.recv_timeout(Duration::from_secs(5)).unwrap();
+    release
+}
+
+#[test]
+fn priority_pool__test() {
+    let log = Arc::new(Mutex::new(Vec::<&'static str>::new()));
+    {
+        let pool = PriorityPool::new(1);
+        // Queue both lanes while the only worker is blocked
+        let release = submit_blocking_job(&pool, Lane::Background);
+        for _ in 0..4 {
+            let log = log.clone();
+            pool.submit(Lane::Background, move || lock(&log).push("background"));
+        }
+        let interactive_log = log.clone();
+        pool.submit(Lane::Interactive, move || lock(&interactive_log).push("interactive"));
+        release.send(()).unwrap();
+    }
+    assert_eq!(*lock(&log), vec!["interactive", "background", "background", "background", "background"]);
+    assert_eq!(lane_of_method(methods::WORKSPACE_DID_CHANGE_WATCHED_FILES), Lane::Background);
+    assert_eq!(lane_of_method(methods::TEXT_DOCUMENT_COMPLETION), Lane::Interactive);
+    
+    // One worker is reserved for interactive jobs, so one runs while the other worker is blocked
+    // on a background job, with more background jobs queued
+    let pool = PriorityPool::new(2);
+    let release = submit_blocking_job(&pool, Lane::Background);
+    for _ in 0..4 {
+        pool.submit(Lane::Background, || ());
+    }
+    let (interactive_sender, interactive_finished) = mpsc::channel();
+    pool.submit(Lane::Interactive, move || interactive_sender.send(()).unwrap());
+    interactive_finished.recv_timeout(Duration::from_secs(5)).unwrap();
+    release.send(()).unwrap();
+    
+    let finished = Arc::new(AtomicUsize::new(0));
+    for _ in 0..4 {
+        let finished = finished.clone();
+        pool.submit(Lane::Interactive, move || { finished.fetch_add(1, Ordering::SeqCst); });
+    }
+    pool.wait_idle();
+    assert_eq!(finished.load(Ordering::SeqCst), 4);
+}
+
+#[test]
+fn document_uri_of_params__test() {
+    use serde_json;
+
+    let params = RequestParams::Object(serde_json::from_str(r#"{ "textDocument": { "uri": "file:///a" } }"#).unwrap());
+    assert_eq!(document_uri_of_params(&params), Some("file:///a"));
+    assert_eq!(document_uri_of_params(&RequestParams::None), None);
+}
+
+#[test]
+fn background_tasks__test() {
+    let tasks = BackgroundTasks::new(2);
+    let (started_sender, started) = mpsc::channel();
+    let log = Arc::new(Mutex::new(Vec::<&'static str>::new()));
+
+    for &(uri, name) in &[("file:///a", "a"), ("file:///b", "b")] {
+        let started_sender = started_sender.clone();
+        let log = log.clone();
+        tasks.spawn(Lane::Interactive, Some(uri), move |token| {
+            started_sender.send(()).unwrap();
+            while !token.is_cancelled() {
+                thread::sleep(Duration::from_millis(5));
+            }
+            lock(&log).push(name);
+        });
+    }
+    started.recv_timeout(Duration::from_secs(5)).unwrap();
+    started.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!(tasks.task_count(), 2);
+
+    tasks.cancel_document("file:///a");
+    while tasks.task_count() > 1 {
+        thread::sleep(Duration::from_millis(5));
+    }
+    assert_eq!(*lock(&log), vec!["a"]);
+
+    tasks.shutdown();
+    assert_eq!(tasks.task_count(), 0);
+    assert_eq!(*lock(&log), vec!["a", "b"]);
+
+    let token = tasks.spawn(Lane::Interactive, None, |_| panic!("Task run after shutdown"));
+    assert!(token.is_cancelled());
+}
+
+#[test]
+fn superseding_requests__test() {
+    use ls_types::*;
+    use jsonrpc::*;
+    use jsonrpc::json_util::JsonObject;
+    use lsp::*;
+    use lsp_testing::ServerTestHarness;
+
+    struct CompletionServer {
+        requests: SupersedingRequests<methods::Completion>,
+        tickets: mpsc::Sender<RequestTicket>,
+    }
+    impl LanguageServerHandling for CompletionServer {
+        fn completion(&mut self, params: TextDocumentPositionParams, completable: LSMethodCompletable<methods::Completion>) {
+            let ticket = self.requests.begin(params.text_document.uri.as_str(), completable);
+            self.tickets.send(ticket).unwrap();
+        }
+    }
+
+    let requests = SupersedingRequests::<methods::Completion>::new();
+    let (sender, tickets) = mpsc::channel();
+    let server_requests = requests.clone();
+    let mut harness = ServerTestHarness::start(move |_| CompletionServer { requests : server_requests, tickets : sender });
+    harness.initialize(Value::Object(JsonObject::new())).unwrap();
+
+    let params = TextDocumentPositionParams {
+        text_document : TextDocumentIdentifier { uri : ::url::Url::parse("file:///a.rs").unwrap() },
+        position : Position { line : 0, character : 0 },
+    };
+    let first = harness.send_request::<methods::Completion>(params.clone()).unwrap();
+    let first_ticket = tickets.recv().unwrap();
+    let second = harness.send_request::<methods::Completion>(params).unwrap();
+    let second_ticket = tickets.recv().unwrap();
+
+    assert!(requests.is_superseded(&first_ticket));
+    assert!(!requests.is_superseded(&second_ticket));
+    assert_eq!(requests.complete(&first_ticket, Ok(LSCompletionList::new(vec![]))), false);
+    assert_eq!(requests.complete(&second_ticket, Ok(LSCompletionList::new(vec![]))), true);
+
+    match first.wait() {
+        Ok(Err(error)) => assert_eq!(error.code, ::lsp_errors::ERROR_CODE__ContentModified),
+        _ => panic!("Expected the first request to fail"),
+    }
+    assert!(second.wait().unwrap().is_ok());
+    assert_eq!(harness.finish(), 0);
+}
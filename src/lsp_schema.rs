@@ -0,0 +1,227 @@
+// Copyright 2016 Bruno Medeiros
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>.
+// This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Optional validation of the params of incoming messages against JSON Schemas, before dispatch.
+//! Invalid params are rejected with an `InvalidParams` error that has the path of the offending
+//! value and what was expected, unlike the terse errors of deserialization.
+//!
+//! Supports the subset of JSON Schema that describes LSP payloads: `type`, `enum`, `properties`,
+//! `required`, `additionalProperties`, `items` and `anyOf`.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+use jsonrpc::jsonrpc_request::RequestParams;
+use jsonrpc::jsonrpc_response::RequestError;
+use serde_json::Value;
+
+pub use lsp_errors::ERROR_CODE__InvalidParams;
+use lsp_openrpc::OpenRpcDocument;
+
+/// A value that doesn't match its schema.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SchemaError {
+    /// The JSON Pointer of the value in the params, such as `/textDocument/uri`.
+    pub path: String,
+    pub message: String,
+}
+
+impl fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let path = if self.path.is_empty() { "/" } else { &self.path };
+        write!(f, "at `{}`: {}", path, self.message)
+    }
+}
+
+pub fn error_InvalidParams(error: &SchemaError) -> RequestError {
+    RequestError::new(ERROR_CODE__InvalidParams, format!("Invalid params {}.", error))
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match *value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::I64(_) | Value::U64(_) => "integer",
+        Value::F64(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+fn has_type(value: &Value, name: &str) -> bool {
+    let value_type = type_name(value);
+    value_type == name || (name == "number" && value_type == "integer")
+}
+
+/// Validate given value against given schema. Returns the first mismatch found.
+pub fn validate(schema: &Value, value: &Value) -> Result<(), SchemaError> {
+    validate_at(schema, value, &mut String::new())
+}
+
+fn validate_at(schema: &Value, value: &Value, path: &mut String) -> Result<(), SchemaError> {
+    let error = |path: &String, message: String| Err(SchemaError { path : path.clone(), message : message });
+
+    match schema.find("type") {
+        Some(&Value::String(ref name)) => {
+            if !has_type(value, name) {
+                return error(path, format!("expected {}, found {}", name, type_name(value)));
+            }
+        }
+        Some(&Value::Array(ref names)) => {
+            if !names.iter().filter_map(Value::as_str).any(|name| has_type(value, name)) {
+                let names : Vec<&str> = names.iter().filter_map(Value::as_str).collect();
+                return error(path, format!("expected one of {}, found {}", names.join(", "), type_name(value)));
+            }
+        }
+        _ => {}
+    }
+
+    if let Some(values) = schema.find("enum").and_then(Value::as_array) {
+        if !values.contains(value) {
+            let values : Vec<String> = values.iter().map(Value::to_string).collect();
+            return error(path, format!("expected one of {}, found {}", values.join(", "), value));
+        }
+    }
+
+    if let Some(schemas) = schema.find("anyOf").and_then(Value::as_array) {
+        if !schemas.iter().any(|schema| validate_at(schema, value, &mut path.clone()).is_ok()) {
+            return error(path, "matches none of the expected schemas".to_string());
+        }
+    }
+
+    match *value {
+        Value::Object(ref obj) => {
+            if let Some(required) = schema.find("required").and_then(Value::as_array) {
+                for name in required.iter().filter_map(Value::as_str) {
+                    if !obj.contains_key(name) {
+                        return error(path, format!("missing property `{}`", name));
+                    }
+                }
+            }
+            let properties = schema.find("properties").and_then(Value::as_object);
+            for (name, property) in obj {
+                let property_schema = match properties.and_then(|properties| properties.get(name)) {
+                    Some(property_schema) => property_schema,
+                    None => match schema.find("additionalProperties") {
+                        Some(&Value::Bool(false)) => {
+                            return error(path, format!("unexpected property `{}`", name));
+                        }
+                        Some(additional) if additional.is_object() => additional,
+                        _ => continue,
+                    },
+                };
+                let len = path.len();
+                path.push('/');
+                path.push_str(&name.replace("~", "~0").replace("/", "~1"));
+                try!(validate_at(property_schema, property, path));
+                path.truncate(len);
+            }
+        }
+        Value::Array(ref array) => {
+            if let Some(items) = schema.find("items") {
+                for (index, item) in array.iter().enumerate() {
+                    let len = path.len();
+                    path.push_str(&format!("/{}", index));
+                    try!(validate_at(items, item, path));
+                    path.truncate(len);
+                }
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// The params schemas of methods, to validate their params before dispatch.
+/// Given to the dispatch layer with `LSServerContext::with_params_validator`.
+#[derive(Debug, Clone, Default)]
+pub struct ParamsValidator {
+    schemas: Arc<HashMap<String, Value>>,
+}
+
+impl ParamsValidator {
+
+    pub fn new() -> ParamsValidator {
+        ParamsValidator::default()
+    }
+
+    /// A validator for the params schemas of the methods of given document.
+    pub fn from_openrpc(document: &OpenRpcDocument) -> ParamsValidator {
+        document.methods.iter().fold(ParamsValidator::new(), |validator, method| {
+            validator.schema(&method.name, method.params_schema.clone())
+        })
+    }
+
+    /// Validate the params of given method with given schema.
+    pub fn schema(mut self, method: &str, schema: Value) -> Self {
+        Arc::make_mut(&mut self.schemas).insert(method.to_string(), schema);
+        self
+    }
+
+    /// Validate given params of given method. The params of methods without a schema are valid.
+    pub fn validate_params(&self, method: &str, params: &RequestParams) -> Result<(), SchemaError> {
+        let schema = match self.schemas.get(method) {
+            Some(schema) => schema,
+            None => return Ok(()),
+        };
+        let params = match *params {
+            RequestParams::Object(ref obj) => Value::Object(obj.clone()),
+            RequestParams::Array(ref array) => Value::Array(array.clone()),
+            RequestParams::None => Value::Null,
+        };
+        validate(schema, &params)
+    }
+
+}
+
+
+#[test]
+fn validate__test() {
+    use serde_json;
+
+    let schema = serde_json::from_str::<Value>(r#"{
+        "type": "object",
+        "properties": {
+            "textDocument": {
+                "type": "object",
+                "properties": { "uri": { "type": "string" } },
+                "required": ["uri"]
+            },
+            "position": {
+                "type": "object",
+                "properties": { "line": { "type": "integer" }, "character": { "type": "integer" } },
+                "required": ["line", "character"],
+                "additionalProperties": false
+            },
+            "triggerKind": { "enum": [1, 2, 3] },
+            "tags": { "type": "array", "items": { "type": ["string", "null"] } }
+        },
+        "required": ["textDocument", "position"]
+    }"#).unwrap();
+    let check = |params: &str| validate(&schema, &serde_json::from_str(params).unwrap()).map_err(|error| error.to_string());
+
+    assert_eq!(check(r#"{ "textDocument": { "uri": "file:///a" }, "position": { "line": 1, "character": 2 },
+        "triggerKind": 2, "tags": ["a", null] }"#), Ok(()));
+    assert_eq!(check(r#"[]"#), Err("at `/`: expected object, found array".to_string()));
+    assert_eq!(check(r#"{ "textDocument": { "uri": "file:///a" } }"#),
+        Err("at `/`: missing property `position`".to_string()));
+    assert_eq!(check(r#"{ "textDocument": { "uri": 1 }, "position": { "line": 1, "character": 2 } }"#),
+        Err("at `/textDocument/uri`: expected string, found integer".to_string()));
+    assert_eq!(check(r#"{ "textDocument": { "uri": "" }, "position": { "line": 1, "character": 2, "x": 0 } }"#),
+        Err("at `/position`: unexpected property `x`".to_string()));
+    assert_eq!(check(r#"{ "textDocument": { "uri": "" }, "position": { "line": 1, "character": 2 }, "triggerKind": 4 }"#),
+        Err("at `/triggerKind`: expected one of 1, 2, 3, found 4".to_string()));
+    assert_eq!(check(r#"{ "textDocument": { "uri": "" }, "position": { "line": 1, "character": 2 }, "tags": [1] }"#),
+        Err("at `/tags/0`: expected one of string, null, found integer".to_string()));
+
+    let validator = ParamsValidator::new().schema("textDocument/hover", schema.clone());
+    let error = validator.validate_params("textDocument/hover", &RequestParams::None).unwrap_err();
+    assert_eq!(error_InvalidParams(&error).message, "Invalid params at `/`: expected object, found null.");
+    assert!(validator.validate_params("textDocument/completion", &RequestParams::None).is_ok());
+}
@@ -0,0 +1,324 @@
+// Copyright 2016 Bruno Medeiros
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>.
+// This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! The result of `textDocument/documentSymbol`, either flat `SymbolInformation`s or
+//! hierarchical `DocumentSymbol`s, and conversions between the two.
+
+use serde;
+use serde::de::Error as DeError;
+use serde_json;
+use serde_json::Value;
+
+use jsonrpc::json_util::JsonObject;
+use ls_types::*;
+use url::Url;
+
+use lsp_capabilities::LSClientCapabilities;
+
+/* ----------------- DocumentSymbol ----------------- */
+
+/// A symbol of a document, with its nested symbols.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DocumentSymbol {
+    pub name: String,
+    pub detail: Option<String>,
+    pub kind: SymbolKind,
+    /// The whole extent of the symbol, such as a function including its body.
+    pub range: Range,
+    /// The extent to select when navigating to the symbol, such as the function name.
+    /// Must be contained in `range`.
+    pub selection_range: Range,
+    pub children: Vec<DocumentSymbol>,
+}
+
+fn range_to_json(range: &Range) -> Value {
+    serde_json::to_value(range)
+}
+
+fn range_from_json(value: Option<Value>, name: &str) -> Result<Range, String> {
+    match value {
+        Some(range) => serde_json::from_value(range).map_err(|err| err.to_string()),
+        None => Err(format!("DocumentSymbol: missing `{}`.", name)),
+    }
+}
+
+impl DocumentSymbol {
+
+    pub fn to_json(&self) -> Value {
+        let mut obj = JsonObject::new();
+        obj.insert("name".to_string(), Value::String(self.name.clone()));
+        if let Some(ref detail) = self.detail {
+            obj.insert("detail".to_string(), Value::String(detail.clone()));
+        }
+        obj.insert("kind".to_string(), serde_json::to_value(&self.kind));
+        obj.insert("range".to_string(), range_to_json(&self.range));
+        obj.insert("selectionRange".to_string(), range_to_json(&self.selection_range));
+        if !self.children.is_empty() {
+            obj.insert("children".to_string(), Value::Array(self.children.iter().map(Self::to_json).collect()));
+        }
+        Value::Object(obj)
+    }
+
+    pub fn from_json(value: Value) -> Result<DocumentSymbol, String> {
+        let mut obj = match value {
+            Value::Object(obj) => obj,
+            _ => return Err("DocumentSymbol is not an object.".to_string()),
+        };
+
+        let name = match obj.remove("name") {
+            Some(Value::String(name)) => name,
+            _ => return Err("DocumentSymbol: missing `name`.".to_string()),
+        };
+        let detail = obj.get("detail").and_then(Value::as_str).map(String::from);
+        let kind = match obj.remove("kind") {
+            Some(kind) => try!(serde_json::from_value(kind).map_err(|err| err.to_string())),
+            None => return Err("DocumentSymbol: missing `kind`.".to_string()),
+        };
+        let range = try!(range_from_json(obj.remove("range"), "range"));
+        let selection_range = try!(range_from_json(obj.remove("selectionRange"), "selectionRange"));
+        let children = match obj.remove("children") {
+            Some(Value::Array(children)) => {
+                let mut result = vec![];
+                for child in children {
+                    result.push(try!(DocumentSymbol::from_json(child)));
+                }
+                result
+            }
+            Some(Value::Null) | None => vec![],
+            Some(_) => return Err("DocumentSymbol: `children` is not an array.".to_string()),
+        };
+        Ok(DocumentSymbol {
+            name : name, detail : detail, kind : kind, range : range, selection_range : selection_range,
+            children : children,
+        })
+    }
+
+}
+
+/* ----------------- Response ----------------- */
+
+/// The result of `textDocument/documentSymbol`. Only clients declaring
+/// `textDocument.documentSymbol.hierarchicalDocumentSymbolSupport` support the `Nested` form.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LSDocumentSymbolResponse {
+    Flat(Vec<SymbolInformation>),
+    Nested(Vec<DocumentSymbol>),
+}
+
+impl From<Vec<SymbolInformation>> for LSDocumentSymbolResponse {
+    fn from(symbols: Vec<SymbolInformation>) -> LSDocumentSymbolResponse {
+        LSDocumentSymbolResponse::Flat(symbols)
+    }
+}
+
+impl From<Vec<DocumentSymbol>> for LSDocumentSymbolResponse {
+    fn from(symbols: Vec<DocumentSymbol>) -> LSDocumentSymbolResponse {
+        LSDocumentSymbolResponse::Nested(symbols)
+    }
+}
+
+impl LSDocumentSymbolResponse {
+
+    /// Convert to the form supported by a client with given capabilities (`None` if unknown).
+    /// `uri` is the document the symbols are from.
+    pub fn into_supported(self, uri: &Url, client_capabilities: Option<&LSClientCapabilities>)
+        -> LSDocumentSymbolResponse
+    {
+        let supports_nested = client_capabilities.map_or(false, |caps| caps.supports_hierarchical_document_symbols());
+        match self {
+            LSDocumentSymbolResponse::Nested(symbols) => {
+                if supports_nested {
+                    LSDocumentSymbolResponse::Nested(symbols)
+                } else {
+                    LSDocumentSymbolResponse::Flat(nested_to_flat(uri, symbols))
+                }
+            }
+            LSDocumentSymbolResponse::Flat(symbols) => {
+                if supports_nested {
+                    LSDocumentSymbolResponse::Nested(flat_to_nested(symbols))
+                } else {
+                    LSDocumentSymbolResponse::Flat(symbols)
+                }
+            }
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        match *self {
+            LSDocumentSymbolResponse::Flat(ref symbols) => serde_json::to_value(symbols),
+            LSDocumentSymbolResponse::Nested(ref symbols) => {
+                Value::Array(symbols.iter().map(DocumentSymbol::to_json).collect())
+            }
+        }
+    }
+
+    /// Parse the response. The form is determined by the first symbol:
+    /// flat symbols have a `location`.
+    pub fn from_json(value: Value) -> Result<LSDocumentSymbolResponse, String> {
+        let symbols = match value {
+            Value::Array(symbols) => symbols,
+            Value::Null => vec![],
+            _ => return Err("DocumentSymbol response is not an array.".to_string()),
+        };
+        let is_flat = match symbols.first() {
+            Some(symbol) => symbol.find("location").is_some(),
+            None => true,
+        };
+        if is_flat {
+            let symbols = try!(serde_json::from_value(Value::Array(symbols)).map_err(|err| err.to_string()));
+            return Ok(LSDocumentSymbolResponse::Flat(symbols));
+        }
+        let mut result = vec![];
+        for symbol in symbols {
+            result.push(try!(DocumentSymbol::from_json(symbol)));
+        }
+        Ok(LSDocumentSymbolResponse::Nested(result))
+    }
+
+}
+
+impl serde::Serialize for LSDocumentSymbolResponse {
+    fn serialize<S>(&self, serializer: &mut S) -> Result<(), S::Error>
+    where
+        S : serde::Serializer,
+    {
+        self.to_json().serialize(serializer)
+    }
+}
+
+impl serde::Deserialize for LSDocumentSymbolResponse {
+    fn deserialize<D>(deserializer: &mut D) -> Result<LSDocumentSymbolResponse, D::Error>
+    where
+        D : serde::Deserializer,
+    {
+        let value = try!(Value::deserialize(deserializer));
+        LSDocumentSymbolResponse::from_json(value).map_err(D::Error::custom)
+    }
+}
+
+/* ----------------- Conversions ----------------- */
+
+/// Flatten given symbols of document `uri`, in pre-order,
+/// with the name of the parent symbol as the container name.
+pub fn nested_to_flat(uri: &Url, symbols: Vec<DocumentSymbol>) -> Vec<SymbolInformation> {
+    fn add_symbols(uri: &Url, symbols: Vec<DocumentSymbol>, container_name: Option<&str>,
+        result: &mut Vec<SymbolInformation>)
+    {
+        for symbol in symbols {
+            result.push(SymbolInformation {
+                name : symbol.name.clone(),
+                kind : symbol.kind.clone(),
+                location : Location { uri : uri.clone(), range : symbol.range.clone() },
+                container_name : container_name.map(String::from),
+            });
+            add_symbols(uri, symbol.children, Some(&symbol.name), result);
+        }
+    }
+
+    let mut result = vec![];
+    add_symbols(uri, symbols, None, &mut result);
+    result
+}
+
+fn position_key(position: &Position) -> (u64, u64) {
+    (position.line, position.character)
+}
+
+fn range_contains(outer: &Range, inner: &Range) -> bool {
+    position_key(&outer.start) <= position_key(&inner.start) && position_key(&inner.end) <= position_key(&outer.end)
+}
+
+/// Nest given flat symbols by the containment of their ranges.
+/// (The container names are not reliable enough to determine the nesting)
+pub fn flat_to_nested(mut symbols: Vec<SymbolInformation>) -> Vec<DocumentSymbol> {
+    // Sort by start, then outermost first
+    symbols.sort_by(|a, b| {
+        let a_range = &a.location.range;
+        let b_range = &b.location.range;
+        position_key(&a_range.start).cmp(&position_key(&b_range.start))
+            .then(position_key(&b_range.end).cmp(&position_key(&a_range.end)))
+    });
+
+    fn close_symbol(symbol: DocumentSymbol, open: &mut Vec<DocumentSymbol>, roots: &mut Vec<DocumentSymbol>) {
+        match open.last_mut() {
+            Some(parent) => parent.children.push(symbol),
+            None => roots.push(symbol),
+        }
+    }
+
+    let mut roots = vec![];
+    let mut open : Vec<DocumentSymbol> = vec![];
+    for symbol in symbols {
+        let symbol = DocumentSymbol {
+            name : symbol.name,
+            detail : None,
+            kind : symbol.kind,
+            range : symbol.location.range.clone(),
+            selection_range : symbol.location.range,
+            children : vec![],
+        };
+        while let Some(last) = open.pop() {
+            if range_contains(&last.range, &symbol.range) {
+                open.push(last);
+                break;
+            }
+            close_symbol(last, &mut open, &mut roots);
+        }
+        open.push(symbol);
+    }
+    while let Some(last) = open.pop() {
+        close_symbol(last, &mut open, &mut roots);
+    }
+    roots
+}
+
+
+#[cfg(test)]
+fn test_symbol(name: &str, kind: SymbolKind, start_line: u64, end_line: u64) -> SymbolInformation {
+    let range = Range {
+        start : Position { line : start_line, character : 0 },
+        end : Position { line : end_line, character : 1 },
+    };
+    SymbolInformation {
+        name : name.to_string(), kind : kind, container_name : None,
+        location : Location { uri : Url::parse("file:///lib.rs").unwrap(), range : range },
+    }
+}
+
+#[test]
+fn document_symbols__test() {
+    let uri = Url::parse("file:///lib.rs").unwrap();
+    let flat = vec![
+        test_symbol("method", SymbolKind::Method, 2, 3),
+        test_symbol("Struct", SymbolKind::Class, 1, 5),
+        test_symbol("function", SymbolKind::Function, 7, 8),
+        test_symbol("field", SymbolKind::Field, 4, 4),
+    ];
+
+    let nested = flat_to_nested(flat);
+    assert_eq!(nested.len(), 2);
+    assert_eq!(nested[0].name, "Struct");
+    assert_eq!(nested[0].children.iter().map(|child| &child.name[..]).collect::<Vec<_>>(), vec!["method", "field"]);
+    assert_eq!(nested[1].name, "function");
+
+    let flat = nested_to_flat(&uri, nested.clone());
+    assert_eq!(flat.iter().map(|symbol| &symbol.name[..]).collect::<Vec<_>>(),
+        vec!["Struct", "method", "field", "function"]);
+    assert_eq!(flat[1].container_name, Some("Struct".to_string()));
+    assert_eq!(flat[3].container_name, None);
+
+    let response = LSDocumentSymbolResponse::Nested(nested.clone());
+    assert_eq!(LSDocumentSymbolResponse::from_json(response.to_json()).unwrap(), response);
+    match response.clone().into_supported(&uri, None) {
+        LSDocumentSymbolResponse::Flat(symbols) => assert_eq!(symbols, flat),
+        response => panic!("Unexpected: {:?}", response),
+    }
+
+    let json = r#"{ "textDocument": { "documentSymbol": { "hierarchicalDocumentSymbolSupport": true } } }"#;
+    let caps = LSClientCapabilities(serde_json::from_str(json).unwrap());
+    assert_eq!(response.clone().into_supported(&uri, Some(&caps)), response);
+}
@@ -0,0 +1,773 @@
+// Copyright 2016 Bruno Medeiros
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>.
+// This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Utilities for testing a `LanguageServerHandling` implementation in-process:
+//! in-memory transports, and a harness that runs the server and drives it as a client would.
+//!
+//! ```ignore
+//! let mut harness = ServerTestHarness::start(|context| MyServer::new(context));
+//! harness.initialize(Value::Object(JsonObject::new())).unwrap();
+//! harness.did_open(&uri, "rust", 1, "fn main() {}").unwrap();
+//! let hover = harness.request::<methods::HoverRequest>(params).unwrap();
+//! assert_eq!(harness.finish(), 0);
+//! ```
+
+use std::cmp;
+use std::io;
+use std::sync::Arc;
+use std::sync::Condvar;
+use std::sync::Mutex;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+use std::time::Instant;
+
+use util::core::*;
+
+use jsonrpc::*;
+use jsonrpc::json_util::JsonObject;
+use jsonrpc::jsonrpc_request::RequestParams;
+use ls_types::*;
+use serde_json::Value;
+use url::Url;
+
+use lsp::*;
+use lsp_methods::LSNotification;
+use lsp_methods::LSRequest;
+use lsp_output::OutputChannel;
+#[cfg(test)] use lsp_protocol::FoldingRange;
+#[cfg(test)] use lsp_protocol::FoldingRangeParams;
+#[cfg(test)] use lsp_protocol::InlayHintParams;
+#[cfg(test)] use lsp_protocol::SemanticTokens;
+#[cfg(test)] use lsp_protocol::SemanticTokensParams;
+use lsp_protocol::ShowDocumentParams;
+use lsp_protocol::ShowDocumentResult;
+use lsp_transport::LSPBufferedMessageWriter;
+use lsp_workspace_edit::LSWorkspaceEdit;
+
+/* ----------------- In-memory transport ----------------- */
+
+/// The writing end of an in-memory pipe. Dropping it ends the input of the reading end.
+pub struct PipeWriter(mpsc::Sender<Vec<u8>>);
+
+/// The reading end of an in-memory pipe.
+pub struct PipeReader {
+    receiver: mpsc::Receiver<Vec<u8>>,
+    buffer: Vec<u8>,
+    position: usize,
+}
+
+/// Create an in-memory pipe, for connecting two endpoints in the same process.
+pub fn pipe() -> (PipeWriter, PipeReader) {
+    let (sender, receiver) = mpsc::channel();
+    (PipeWriter(sender), PipeReader { receiver : receiver, buffer : vec![], position : 0 })
+}
+
+impl io::Write for PipeWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self.0.send(buf.to_vec()) {
+            Ok(()) => Ok(buf.len()),
+            Err(_) => Err(io::Error::new(io::ErrorKind::BrokenPipe, "Pipe reader was dropped.")),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl io::BufRead for PipeReader {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        while self.position == self.buffer.len() {
+            match self.receiver.recv() {
+                Ok(chunk) => {
+                    self.buffer = chunk;
+                    self.position = 0;
+                }
+                // Writer dropped: end of input
+                Err(_) => return Ok(&[]),
+            }
+        }
+        Ok(&self.buffer[self.position..])
+    }
+
+    fn consume(&mut self, amount: usize) {
+        self.position = cmp::min(self.position + amount, self.buffer.len());
+    }
+}
+
+impl io::Read for PipeReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let count = {
+            let available = try!(io::BufRead::fill_buf(self));
+            let count = cmp::min(available.len(), buf.len());
+            buf[..count].copy_from_slice(&available[..count]);
+            count
+        };
+        io::BufRead::consume(self, count);
+        Ok(count)
+    }
+}
+
+/* ----------------- Mock client ----------------- */
+
+/// The server-initiated messages received by a `MockLanguageClient`, in order of arrival.
+#[derive(Debug, Default)]
+pub struct ClientMessagesData {
+    pub diagnostics: Vec<LSPublishDiagnosticsParams>,
+    pub show_messages: Vec<ShowMessageParams>,
+    pub log_messages: Vec<LogMessageParams>,
+    pub telemetry_events: Vec<Value>,
+    pub applied_edits: Vec<LSWorkspaceEdit>,
+    /// The params of `$/progress` notifications
+    pub progress: Vec<Value>,
+    /// The methods of the refresh requests, such as `workspace/codeLens/refresh`
+    pub refresh_requests: Vec<String>,
+    pub shown_documents: Vec<ShowDocumentParams>,
+}
+
+/// A shared handle to the messages received by a `MockLanguageClient`.
+/// The `wait_for_*` methods remove and return the first matching message, 
+/// waiting for it to arrive if necessary, up to given timeout.
+#[derive(Debug, Clone, Default)]
+pub struct ClientMessages {
+    data: Arc<(Mutex<ClientMessagesData>, Condvar)>,
+}
+
+impl ClientMessages {
+
+    /// Run given function with the received messages.
+    pub fn with<RET, FN>(&self, function: FN) -> RET
+    where
+        FN : FnOnce(&mut ClientMessagesData) -> RET,
+    {
+        function(&mut self.data.0.lock().unwrap())
+    }
+
+    fn add<FN>(&self, add: FN)
+    where
+        FN : FnOnce(&mut ClientMessagesData),
+    {
+        add(&mut self.data.0.lock().unwrap());
+        self.data.1.notify_all();
+    }
+
+    /// Wait until `take` returns a value, or the timeout elapses.
+    pub fn wait_for<RET, FN>(&self, timeout: Duration, mut take: FN) -> Option<RET>
+    where
+        FN : FnMut(&mut ClientMessagesData) -> Option<RET>,
+    {
+        let deadline = Instant::now() + timeout;
+        let mut data = self.data.0.lock().unwrap();
+        loop {
+            if let Some(result) = take(&mut data) {
+                return Some(result);
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return None;
+            }
+            data = self.data.1.wait_timeout(data, deadline - now).unwrap().0;
+        }
+    }
+
+    pub fn wait_for_diagnostics(&self, uri: &Url, timeout: Duration) -> Option<LSPublishDiagnosticsParams> {
+        self.wait_for(timeout, |data| {
+            data.diagnostics.iter().position(|params| &params.uri == uri)
+                .map(|ix| data.diagnostics.remove(ix))
+        })
+    }
+
+    pub fn wait_for_show_message(&self, timeout: Duration) -> Option<ShowMessageParams> {
+        self.wait_for(timeout, |data| take_first(&mut data.show_messages))
+    }
+
+    pub fn wait_for_log_message(&self, timeout: Duration) -> Option<LogMessageParams> {
+        self.wait_for(timeout, |data| take_first(&mut data.log_messages))
+    }
+
+    pub fn wait_for_applied_edit(&self, timeout: Duration) -> Option<LSWorkspaceEdit> {
+        self.wait_for(timeout, |data| take_first(&mut data.applied_edits))
+    }
+
+    pub fn wait_for_progress(&self, timeout: Duration) -> Option<Value> {
+        self.wait_for(timeout, |data| take_first(&mut data.progress))
+    }
+
+}
+
+fn take_first<T>(messages: &mut Vec<T>) -> Option<T> {
+    if messages.is_empty() { None } else { Some(messages.remove(0)) }
+}
+
+/// A client that records the server-initiated messages, for tests to inspect.
+/// `workspace/applyEdit` requests are answered as applied, `showMessageRequest`s
+/// with no action selected, and `showDocument` and refresh requests with success.
+#[derive(Debug, Clone, Default)]
+pub struct MockLanguageClient {
+    pub messages: ClientMessages,
+}
+
+impl MockLanguageClient {
+    pub fn new() -> MockLanguageClient {
+        MockLanguageClient::default()
+    }
+
+    fn add_refresh_request(&self, method_name: &str) {
+        self.messages.add(|data| data.refresh_requests.push(method_name.to_string()))
+    }
+}
+
+impl LanguageClientHandling for MockLanguageClient {
+    
+    fn show_message(&mut self, params: ShowMessageParams) {
+        self.messages.add(|data| data.show_messages.push(params))
+    }
+    
+    fn show_message_request(&mut self, params: ShowMessageRequestParams,
+        completable: LSMethodCompletable<methods::ShowMessageRequest>)
+    {
+        let show_message = ShowMessageParams { typ : params.typ, message : params.message };
+        self.messages.add(|data| data.show_messages.push(show_message));
+        completable.complete(Err(error_not_supported()))
+    }
+    
+    fn log_message(&mut self, params: LogMessageParams) {
+        self.messages.add(|data| data.log_messages.push(params))
+    }
+    
+    fn telemetry_event(&mut self, params: Value) {
+        self.messages.add(|data| data.telemetry_events.push(params))
+    }
+    
+    fn publish_diagnostics(&mut self, params: LSPublishDiagnosticsParams) {
+        self.messages.add(|data| data.diagnostics.push(params))
+    }
+    
+    fn show_document(&mut self, params: ShowDocumentParams, completable: LSMethodCompletable<methods::ShowDocument>) {
+        self.messages.add(|data| data.shown_documents.push(params));
+        completable.complete(Ok(ShowDocumentResult { success : true }))
+    }
+    
+    fn code_lens_refresh(&mut self, completable: LSMethodCompletable<methods::CodeLensRefresh>) {
+        self.add_refresh_request(methods::WORKSPACE_CODE_LENS_REFRESH);
+        completable.complete(Ok(()))
+    }
+    
+    fn semantic_tokens_refresh(&mut self, completable: LSMethodCompletable<methods::SemanticTokensRefresh>) {
+        self.add_refresh_request(methods::WORKSPACE_SEMANTIC_TOKENS_REFRESH);
+        completable.complete(Ok(()))
+    }
+    
+    fn inlay_hint_refresh(&mut self, completable: LSMethodCompletable<methods::InlayHintRefresh>) {
+        self.add_refresh_request(methods::WORKSPACE_INLAY_HINT_REFRESH);
+        completable.complete(Ok(()))
+    }
+    
+    fn diagnostic_refresh(&mut self, completable: LSMethodCompletable<methods::DiagnosticRefresh>) {
+        self.add_refresh_request(methods::WORKSPACE_DIAGNOSTIC_REFRESH);
+        completable.complete(Ok(()))
+    }
+    
+    fn handle_other_method(&mut self, request: &RequestInfo, method_name: &str, params: RequestParams,
+        completable: ResponseCompletable)
+    {
+        let messages = self.messages.clone();
+        match method_name {
+            methods::WORKSPACE_APPLY_EDIT => {
+                completable.handle_request_with(params, 
+                    move |params: Value, completable: MethodCompletable<Value, ()>| {
+                        let edit = params.find("edit").cloned().unwrap_or(Value::Null);
+                        match LSWorkspaceEdit::from_json(edit) {
+                            Ok(edit) => messages.add(|data| data.applied_edits.push(edit)),
+                            Err(error) => warn!("Invalid workspace/applyEdit params: {}", error),
+                        }
+                        let mut result = JsonObject::new();
+                        result.insert("applied".to_string(), Value::Bool(true));
+                        completable.complete(Ok(Value::Object(result)))
+                    }
+                )
+            }
+            methods::PROGRESS => {
+                completable.handle_notification_with(params, 
+                    move |params: Value| messages.add(|data| data.progress.push(params)))
+            }
+            methods::WINDOW_WORK_DONE_PROGRESS_CREATE => {
+                completable.handle_request_with(params, 
+                    |_: Value, completable: MethodCompletable<Value, ()>| completable.complete(Ok(Value::Null)))
+            }
+            _ => {
+                handle_unknown_method(request, method_name, completable);
+            }
+        }
+    }
+    
+}
+
+/* ----------------- Server harness ----------------- */
+
+/// Runs a language server in a background thread, connected through in-memory pipes
+/// to a client endpoint, with methods to send it requests and notifications and wait for the results.
+pub struct ServerTestHarness {
+    /// The client endpoint, connected to the server.
+    pub endpoint: Endpoint,
+    /// The messages received from the server, if the client is the default `MockLanguageClient`.
+    pub client_messages: ClientMessages,
+    server_thread: Option<thread::JoinHandle<i32>>,
+    client_thread: Option<thread::JoinHandle<()>>,
+}
+
+impl ServerTestHarness {
+
+    /// Start the server created by `create_server`, which is called in the server thread.
+    /// Messages from the server are recorded in `client_messages`.
+    pub fn start<SERVER, CREATE>(create_server: CREATE) -> ServerTestHarness
+    where
+        SERVER : LanguageServerHandling + 'static,
+        CREATE : FnOnce(LSServerContext) -> SERVER + Send + 'static,
+    {
+        let client = MockLanguageClient::new();
+        let mut harness = Self::start_with_client(create_server, client.clone());
+        harness.client_messages = client.messages;
+        harness
+    }
+
+    /// Start the server created by `create_server`, with the messages from the server
+    /// to the client handled by `client`.
+    pub fn start_with_client<SERVER, CREATE, CLIENT>(create_server: CREATE, client: CLIENT) -> ServerTestHarness
+    where
+        SERVER : LanguageServerHandling + 'static,
+        CREATE : FnOnce(LSServerContext) -> SERVER + Send + 'static,
+        CLIENT : LanguageClientHandling + Send + 'static,
+    {
+        Self::start_with_runner_and_client(move |input, context| {
+            let server = create_server(context.clone());
+            LSPEndpoint::run_server_from_input(input, context, server)
+        }, client)
+    }
+
+    /// Start a server with `run_server`, which is called in the server thread with the input
+    /// and context of the server, and runs its read loop, such as with `lsp_dispatch::run_actor_server`.
+    /// Messages from the server are recorded in `client_messages`.
+    pub fn start_with_runner<RUN>(run_server: RUN) -> ServerTestHarness
+    where
+        RUN : FnOnce(&mut PipeReader, LSServerContext) -> i32 + Send + 'static,
+    {
+        let client = MockLanguageClient::new();
+        let mut harness = Self::start_with_runner_and_client(run_server, client.clone());
+        harness.client_messages = client.messages;
+        harness
+    }
+
+    fn start_with_runner_and_client<RUN, CLIENT>(run_server: RUN, client: CLIENT) -> ServerTestHarness
+    where
+        RUN : FnOnce(&mut PipeReader, LSServerContext) -> i32 + Send + 'static,
+        CLIENT : LanguageClientHandling + Send + 'static,
+    {
+        let (client_writer, mut server_reader) = pipe();
+        let (server_writer, mut client_reader) = pipe();
+
+        let server_thread = thread::spawn(move || {
+            // With an output channel, as `lsp_launcher` runs a server
+            let mut output = OutputChannel::start_streaming(move || LSPBufferedMessageWriter::new(server_writer));
+            let context = LSServerContext::new_with_output(output.create_endpoint(), output.sender());
+            let exit_code = run_server(&mut server_reader, context);
+            output.shutdown_and_join();
+            exit_code
+        });
+
+        let endpoint = LSPEndpoint::create_lsp_output_with_output_stream(|| client_writer);
+        let client_endpoint = endpoint.clone();
+        let client_thread = thread::spawn(move || {
+            let _ = LSPEndpoint::run_client_from_input(&mut client_reader, client_endpoint, client);
+        });
+
+        ServerTestHarness {
+            endpoint : endpoint, client_messages : ClientMessages::default(),
+            server_thread : Some(server_thread), client_thread : Some(client_thread),
+        }
+    }
+
+    /// Send a request, without waiting for the result.
+    pub fn send_request<REQ>(&mut self, params: REQ::Params) -> LSSendResult<LSRequestFuture<REQ>>
+    where
+        REQ : LSRequest,
+    {
+        send_lsp_request::<REQ>(&mut self.endpoint, params)
+    }
+
+    /// Send a request and wait for its result. A response error is returned as an error.
+    pub fn request<REQ>(&mut self, params: REQ::Params) -> GResult<REQ::Result>
+    where
+        REQ : LSRequest,
+    {
+        let future = try!(self.send_request::<REQ>(params));
+        match future.wait() {
+            Ok(Ok(result)) => Ok(result),
+            Ok(Err(error)) => Err(format!("Request `{}` failed: {:?}", REQ::METHOD, error).into()),
+            Err(error) => Err(format!("Request `{}` got no response: {:?}", REQ::METHOD, error).into()),
+        }
+    }
+
+    pub fn notify<NOTIF>(&mut self, params: NOTIF::Params) -> GResult<()>
+    where
+        NOTIF : LSNotification,
+    {
+        try!(send_lsp_notification::<NOTIF>(&mut self.endpoint, params));
+        Ok(())
+    }
+
+    /// Send a request of a method not known to this crate, and wait for its result.
+    pub fn request_other(&mut self, method: &str, params: Value) -> GResult<Value> {
+        let future : RequestFuture<Value, Value> = try!(self.endpoint.send_request(method, params));
+        match future.wait() {
+            Ok(Ok(result)) => Ok(result),
+            Ok(Err(error)) => Err(format!("Request `{}` failed: {:?}", method, error).into()),
+            Err(error) => Err(format!("Request `{}` got no response: {:?}", method, error).into()),
+        }
+    }
+
+    /// Send the `initialize` request with given client capabilities, and wait for the result.
+    pub fn initialize(&mut self, capabilities: Value) -> GResult<LSInitializeResult> {
+        let params = InitializeParams {
+            process_id : None,
+            root_path : None,
+            initialization_options : None,
+            capabilities : capabilities,
+        };
+        self.request::<methods::Initialize>(params)
+    }
+
+    pub fn did_open(&mut self, uri: &Url, language_id: &str, version: u64, text: &str) -> GResult<()> {
+        let text_document = TextDocumentItem {
+            uri : uri.clone(), language_id : language_id.to_string(), version : version, text : text.to_string(),
+        };
+        self.notify::<methods::DidOpenTextDocument>(DidOpenTextDocumentParams { text_document : text_document })
+    }
+
+    /// Replace the whole text of given document.
+    pub fn did_change(&mut self, uri: &Url, version: u64, text: &str) -> GResult<()> {
+        let change = TextDocumentContentChangeEvent { range : None, range_length : None, text : text.to_string() };
+        self.notify::<methods::DidChangeTextDocument>(DidChangeTextDocumentParams {
+            text_document : VersionedTextDocumentIdentifier { uri : uri.clone(), version : version },
+            content_changes : vec![change],
+        })
+    }
+
+    pub fn did_close(&mut self, uri: &Url) -> GResult<()> {
+        self.notify::<methods::DidCloseTextDocument>(DidCloseTextDocumentParams {
+            text_document : TextDocumentIdentifier { uri : uri.clone() }
+        })
+    }
+
+    /// Send `shutdown` and `exit`, and wait for the server to terminate. Returns the server exit code.
+    pub fn finish(mut self) -> i32 {
+        let _ = self.request::<methods::Shutdown>(());
+        let _ = self.notify::<methods::Exit>(());
+        self.join()
+    }
+
+    /// Wait for the server to terminate, and shut down the client. Returns the server exit code.
+    fn join(&mut self) -> i32 {
+        let exit_code = match self.server_thread.take() {
+            Some(server_thread) => server_thread.join().unwrap_or(1),
+            None => return 1,
+        };
+        self.endpoint.shutdown_and_join();
+        if let Some(client_thread) = self.client_thread.take() {
+            let _ = client_thread.join();
+        }
+        exit_code
+    }
+
+}
+
+impl Drop for ServerTestHarness {
+    fn drop(&mut self) {
+        if self.server_thread.is_some() && !thread::panicking() {
+            // Terminate the server with `exit`, so that the threads don't outlive the test
+            let _ = self.notify::<methods::Exit>(());
+            self.join();
+        }
+    }
+}
+
+
+#[cfg(test)]
+struct TestServer {
+    context: LSServerContext,
+}
+
+#[cfg(test)]
+impl LanguageServerHandling for TestServer {
+    fn hover(&mut self, _: TextDocumentPositionParams, completable: LSMethodCompletable<methods::HoverRequest>) {
+        let hover = Hover { contents : vec![MarkedString::String("hover_text".to_string())], range : None };
+        completable.complete(Ok(LSHover::from(hover)))
+    }
+    fn did_open_text_document(&mut self, params: DidOpenTextDocumentParams) {
+        let mut endpoint = self.context.endpoint.clone();
+        let show_message = ShowMessageParams { typ : MessageType::Info, message : "Opened.".to_string() };
+        client_rpc_handle(&mut endpoint).show_message(show_message).unwrap();
+        let diagnostics = LSPublishDiagnosticsParams { uri : params.text_document.uri, diagnostics : vec![] };
+        client_rpc_handle(&mut endpoint).publish_diagnostics(diagnostics).unwrap();
+    }
+    fn folding_range(&mut self, _: FoldingRangeParams, completable: LSMethodCompletable<methods::FoldingRangeRequest>) {
+        let folding_range = FoldingRange { 
+            start_line : 0, start_character : None, end_line : 2, end_character : None, 
+            kind : Some("region".to_string()), collapsed_text : None,
+        };
+        completable.complete(Ok(vec![folding_range]))
+    }
+    fn semantic_tokens_full(&mut self, _: SemanticTokensParams, 
+        completable: LSMethodCompletable<methods::SemanticTokensFull>) 
+    {
+        completable.complete(Ok(Some(SemanticTokens { result_id : None, data : vec![0, 3, 4, 0, 1] })))
+    }
+    fn server_capabilities(&self) -> ServerCapabilities {
+        InitializeResultBuilder::new().hover().build().capabilities
+    }
+}
+
+#[test]
+fn pipe__test() {
+    use std::io::BufRead;
+    use std::io::Read;
+    use std::io::Write;
+    
+    let (mut writer, mut reader) = pipe();
+    writer.write_all(b"ab").unwrap();
+    writer.write_all(b"c\nd").unwrap();
+    drop(writer);
+
+    let mut line = String::new();
+    reader.read_line(&mut line).unwrap();
+    assert_eq!(line, "abc\n");
+    let mut rest = String::new();
+    reader.read_to_string(&mut rest).unwrap();
+    assert_eq!(rest, "d");
+}
+
+#[test]
+fn server_test_harness__test() {
+    use lsp_markup::LSHoverContents;
+
+    let mut harness = ServerTestHarness::start(|context| TestServer { context : context });
+
+    let result = harness.initialize(Value::Object(JsonObject::new())).unwrap();
+    assert_eq!(result.capabilities.hover_provider, Some(true));
+
+    let uri = Url::parse("file:///doc.txt").unwrap();
+    harness.did_open(&uri, "text", 1, "text").unwrap();
+    
+    let timeout = Duration::from_secs(5);
+    let message = harness.client_messages.wait_for_show_message(timeout).unwrap();
+    assert_eq!(message.message, "Opened.");
+    let diagnostics = harness.client_messages.wait_for_diagnostics(&uri, timeout).unwrap();
+    assert!(diagnostics.diagnostics.is_empty());
+    assert!(harness.client_messages.wait_for_diagnostics(&uri, Duration::from_millis(10)).is_none());
+
+    let params = TextDocumentPositionParams {
+        text_document : TextDocumentIdentifier { uri : uri.clone() },
+        position : Position { line : 0, character : 0 },
+    };
+    let hover = harness.request::<methods::HoverRequest>(params.clone()).unwrap();
+    assert_eq!(hover.contents, LSHoverContents::MarkedStrings(vec![MarkedString::String("hover_text".to_string())]));
+    assert!(harness.request::<methods::GotoDefinition>(params).is_err());
+
+    assert_eq!(harness.finish(), 0);
+}
+
+#[test]
+fn protocol_requests__test() {
+    let mut harness = ServerTestHarness::start(|context| TestServer { context : context });
+    harness.initialize(Value::Object(JsonObject::new())).unwrap();
+
+    let text_document = TextDocumentIdentifier { uri : Url::parse("file:///doc.txt").unwrap() };
+    let folding_ranges = harness.request::<methods::FoldingRangeRequest>(
+        FoldingRangeParams { text_document : text_document.clone() }).unwrap();
+    assert_eq!(folding_ranges.len(), 1);
+    assert_eq!((folding_ranges[0].start_line, folding_ranges[0].end_line), (0, 2));
+
+    let tokens = harness.request::<methods::SemanticTokensFull>(
+        SemanticTokensParams { text_document : text_document.clone() }).unwrap();
+    assert_eq!(tokens.unwrap().data, vec![0, 3, 4, 0, 1]);
+
+    // Not provided by the server
+    let range = Range { start : Position { line : 0, character : 0 }, end : Position { line : 1, character : 0 } };
+    match harness.send_request::<methods::InlayHintRequest>(
+        InlayHintParams { text_document : text_document, range : range }).unwrap().wait() 
+    {
+        Ok(Err(error)) => assert_eq!(error.code, -32601),
+        result => panic!("Expected not supported, got: {:?}", result),
+    }
+
+    assert_eq!(harness.finish(), 0);
+}
+
+#[test]
+fn server_test_harness_after_shutdown__test() {
+    let mut harness = ServerTestHarness::start(|context| TestServer { context : context });
+    harness.initialize(Value::Object(JsonObject::new())).unwrap();
+    harness.request::<methods::Shutdown>(()).unwrap();
+
+    let params = TextDocumentPositionParams {
+        text_document : TextDocumentIdentifier { uri : Url::parse("file:///doc.txt").unwrap() },
+        position : Position { line : 0, character : 0 },
+    };
+    match harness.send_request::<methods::HoverRequest>(params).unwrap().wait() {
+        Ok(Err(error)) => assert_eq!(error.code, -32600),
+        result => panic!("Expected InvalidRequest, got: {:?}", result),
+    }
+    match harness.send_request::<methods::Shutdown>(()).unwrap().wait() {
+        Ok(Err(error)) => assert_eq!(error.code, -32600),
+        result => panic!("Expected InvalidRequest, got: {:?}", result),
+    }
+
+    assert_eq!(harness.finish(), 0);
+}
+
+#[test]
+fn unknown_methods__test() {
+    use serde_json;
+    use lsp_transport::JsonRpcMessage;
+    use lsp_transport::parse_jsonrpc_message_bytes;
+    use lsp_transport::parse_transport_message_content;
+    use lsp_transport::write_jsonrpc_message;
+
+    let (mut client_writer, mut server_reader) = pipe();
+    let (server_writer, mut client_reader) = pipe();
+    let server_thread = thread::spawn(move || {
+        let endpoint = LSPEndpoint::create_lsp_output_with_output_stream(|| server_writer);
+        let context = LSServerContext::new(endpoint);
+        LSPEndpoint::run_server_from_input(&mut server_reader, context.clone(), TestServer { context : context })
+    });
+
+    let mut send = |id: Option<u64>, method: &str| {
+        let params = match method {
+            methods::INITIALIZE => serde_json::from_str(r#"{ "processId": null, "rootPath": null, "capabilities": {} }"#)
+                .unwrap(),
+            _ => Value::Object(JsonObject::new()),
+        };
+        write_jsonrpc_message(&JsonRpcMessage { id : id, method : method, params : &params }, &mut client_writer)
+            .unwrap();
+    };
+    send(Some(1), methods::INITIALIZE);
+    send(None, "custom/notification");
+    send(None, "$/custom");
+    send(Some(2), "custom/request");
+    send(Some(3), "$/custom");
+    send(Some(4), methods::SHUTDOWN);
+    send(None, methods::EXIT);
+
+    // The notifications are not answered
+    let mut receive = || parse_jsonrpc_message_bytes(&parse_transport_message_content(&mut client_reader).unwrap())
+        .unwrap();
+    assert_eq!(receive().find("id"), Some(&Value::U64(1)));
+    for id in 2..4 {
+        let response = receive();
+        assert_eq!(response.find("id"), Some(&Value::U64(id)));
+        assert_eq!(response.lookup("error.code"), Some(&Value::I64(-32601)));
+    }
+    assert_eq!(receive().find("id"), Some(&Value::U64(4)));
+
+    assert_eq!(server_thread.join().unwrap(), 0);
+}
+
+/// Answers `custom/requestId` with the id of the request, from another thread.
+#[cfg(test)]
+struct RequestIdServer {
+    request: RequestInfo,
+}
+
+#[cfg(test)]
+impl LanguageServerHandling for RequestIdServer {
+    fn before_dispatch(&mut self, request: &RequestInfo) {
+        self.request = request.clone();
+    }
+    fn handle_other_method(&mut self, request: &RequestInfo, method_name: &str, params: RequestParams,
+        completable: ResponseCompletable)
+    {
+        if method_name != "custom/requestId" {
+            return handle_unknown_method(request, method_name, completable);
+        }
+        let request_id = self.request.id.clone().unwrap_or(Value::Null);
+        completable.handle_request_with(params, move |_: Value, completable: MethodCompletable<Value, ()>| {
+            thread::spawn(move || completable.complete(Ok(request_id)));
+        })
+    }
+}
+
+#[test]
+fn request_info__test() {
+    let mut harness = ServerTestHarness::start(|_| RequestIdServer { request : RequestInfo::default() });
+    harness.initialize(Value::Object(JsonObject::new())).unwrap();
+    let first = harness.request_other("custom/requestId", Value::Object(JsonObject::new())).unwrap();
+    let second = harness.request_other("custom/requestId", Value::Object(JsonObject::new())).unwrap();
+    assert!(first.as_u64().is_some());
+    assert_eq!(second.as_u64(), first.as_u64().map(|id| id + 1));
+    assert_eq!(harness.finish(), 0);
+}
+
+#[test]
+fn server_state__test() {
+    let transitions = newArcMutex(vec![]);
+    let recorded = transitions.clone();
+    let mut harness = ServerTestHarness::start(move |context: LSServerContext| {
+        assert_eq!(context.state(), ServerState::Uninitialized);
+        context.on_state_change(move |old_state, new_state| recorded.lock().unwrap().push((old_state, new_state)));
+        TestServer { context : context }
+    });
+    harness.initialize(Value::Object(JsonObject::new())).unwrap();
+    assert_eq!(harness.finish(), 0);
+
+    assert_eq!(*transitions.lock().unwrap(), vec![
+        (ServerState::Uninitialized, ServerState::Initializing),
+        (ServerState::Initializing, ServerState::Running),
+        (ServerState::Running, ServerState::ShuttingDown),
+        (ServerState::ShuttingDown, ServerState::Exited),
+    ]);
+}
+
+/// Start a `TestServer`, and get its context, to send requests to the client from the test thread.
+#[cfg(test)]
+fn start_test_server_with_context() -> (ServerTestHarness, LSServerContext) {
+    let (context_sender, context_receiver) = mpsc::channel();
+    let mut harness = ServerTestHarness::start(move |context: LSServerContext| {
+        context_sender.send(context.clone()).unwrap();
+        TestServer { context : context }
+    });
+    harness.initialize(Value::Object(JsonObject::new())).unwrap();
+    (harness, context_receiver.recv().unwrap())
+}
+
+#[test]
+fn refresh_requests__test() {
+    let (mut harness, context) = start_test_server_with_context();
+    let mut endpoint = context.endpoint.clone();
+    client_rpc_handle(&mut endpoint).refresh_and_wait::<methods::CodeLensRefresh>().unwrap();
+    client_rpc_handle(&mut endpoint).refresh_and_wait::<methods::DiagnosticRefresh>().unwrap();
+    assert!(client_rpc_handle(&mut endpoint).inlay_hint_refresh().unwrap().wait().unwrap().is_ok());
+    assert_eq!(harness.client_messages.with(|data| data.refresh_requests.clone()), vec![
+        methods::WORKSPACE_CODE_LENS_REFRESH, methods::WORKSPACE_DIAGNOSTIC_REFRESH, methods::WORKSPACE_INLAY_HINT_REFRESH,
+    ]);
+    assert_eq!(harness.finish(), 0);
+}
+
+#[test]
+fn show_document__test() {
+    let (mut harness, context) = start_test_server_with_context();
+    let mut endpoint = context.endpoint.clone();
+    let uri = Url::parse("file:///doc.txt").unwrap();
+    let options = ShowDocumentOptions { take_focus : Some(true), ..Default::default() };
+    match client_rpc_handle(&mut endpoint).show_document(uri.clone(), options).unwrap().wait() {
+        Ok(Ok(result)) => assert_eq!(result, ShowDocumentResult { success : true }),
+        _ => panic!("showDocument failed."),
+    }
+    let shown = harness.client_messages.with(|data| data.shown_documents.clone());
+    assert_eq!(shown, vec![ShowDocumentParams { uri : uri, external : None, take_focus : Some(true), selection : None }]);
+    assert_eq!(harness.finish(), 0);
+}
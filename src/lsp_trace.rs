@@ -0,0 +1,320 @@
+// Copyright 2016 Bruno Medeiros
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>.
+// This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Tracing of the messages of a server to the client, with `$/logTrace` notifications, in the
+//! style that VS Code shows in its trace output channel ("Received request 'x - (1)'.",
+//! "Sending response 'x - (1)'. Processing request took 5ms", and so on).
+//!
+//! The trace level is the `trace` of the `initialize` params, then the one of each `$/setTrace`
+//! notification. It is off by default. A `Tracer` wraps the `MessageReader` and `MessageWriter`
+//! of the server, and is given to its context, which it uses to send the traces of received messages:
+//!
+//! ```ignore
+//! let tracer = Tracer::new();
+//! let endpoint = LSPEndpoint::create_lsp_output(move || tracer.writer(LSPMessageWriter(io::stdout())));
+//! let context = LSServerContext::new(endpoint).with_tracer(tracer.clone());
+//! LSPEndpoint::run_server(&mut tracer.reader(LSPMessageReader(stdin.lock())), context, server);
+//! ```
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::MutexGuard;
+use std::time::Instant;
+
+use util::core::*;
+
+use jsonrpc::*;
+use serde_json;
+use serde_json::Map;
+use serde_json::Value;
+
+use lsp_methods as methods;
+use lsp_transport::JsonRpcMessage;
+
+/// The trace level, as in the `trace` of the `initialize` params.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceValue {
+    Off,
+    /// Trace the messages.
+    Messages,
+    /// Trace the messages, with their params and results.
+    Verbose,
+}
+
+impl TraceValue {
+    pub fn from_str(value: &str) -> Option<TraceValue> {
+        match value {
+            "off" => Some(TraceValue::Off),
+            "messages" => Some(TraceValue::Messages),
+            "verbose" => Some(TraceValue::Verbose),
+            _ => None,
+        }
+    }
+}
+
+impl Default for TraceValue {
+    fn default() -> TraceValue {
+        TraceValue::Off
+    }
+}
+
+struct TracerState {
+    trace: TraceValue,
+    endpoint: Option<Endpoint>,
+    /// The method and time of the requests received, by id.
+    received_requests: HashMap<String, (String, Instant)>,
+    /// The method and time of the requests sent, by id.
+    sent_requests: HashMap<String, (String, Instant)>,
+}
+
+/// Traces the messages of a server. Clones share the same state.
+#[derive(Clone)]
+pub struct Tracer {
+    state: Arc<Mutex<TracerState>>,
+}
+
+impl Tracer {
+
+    pub fn new() -> Tracer {
+        let state = TracerState {
+            trace : TraceValue::Off,
+            endpoint : None,
+            received_requests : HashMap::new(),
+            sent_requests : HashMap::new(),
+        };
+        Tracer { state : Arc::new(Mutex::new(state)) }
+    }
+
+    fn lock_state(&self) -> MutexGuard<TracerState> {
+        match self.state.lock() {
+            Ok(state) => state,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    pub fn trace_value(&self) -> TraceValue {
+        self.lock_state().trace
+    }
+
+    pub fn set_trace_value(&self, trace: TraceValue) {
+        self.lock_state().trace = trace;
+    }
+
+    /// Set the endpoint to send the traces of received messages with.
+    /// Done by `LSServerContext::with_tracer`.
+    pub fn attach(&self, endpoint: Endpoint) {
+        self.lock_state().endpoint = Some(endpoint);
+    }
+
+    /// Wrap given reader, to trace the messages it reads.
+    pub fn reader<MR : MessageReader>(&self, msg_reader: MR) -> TracingMessageReader<MR> {
+        TracingMessageReader { msg_reader : msg_reader, tracer : self.clone() }
+    }
+
+    /// Wrap given writer, to trace the messages it writes.
+    pub fn writer<MW : MessageWriter>(&self, msg_writer: MW) -> TracingMessageWriter<MW> {
+        TracingMessageWriter { msg_writer : msg_writer, tracer : self.clone() }
+    }
+
+    /// Update the trace level from given received message, and return the trace for it, if any.
+    fn message_received(&self, message: &str) -> Option<Value> {
+        let message = match serde_json::from_str::<Value>(message) {
+            Ok(message) => message,
+            Err(_) => return None,
+        };
+        let method = message.find("method").and_then(Value::as_str);
+        let trace_param = match method {
+            Some(methods::INITIALIZE) => message.lookup("params.trace"),
+            Some(methods::SET_TRACE) => message.lookup("params.value"),
+            _ => None,
+        };
+
+        let mut state = self.lock_state();
+        if let Some(trace) = trace_param.and_then(Value::as_str).and_then(TraceValue::from_str) {
+            state.trace = trace;
+        }
+        if method == Some(methods::SET_TRACE) || state.trace == TraceValue::Off {
+            return None;
+        }
+
+        let id = message.find("id").map(id_text);
+        let (text, verbose) = match (method, id) {
+            (Some(method), Some(id)) => {
+                let text = format!("Received request '{} - ({})'.", method, id);
+                state.received_requests.insert(id, (method.to_string(), Instant::now()));
+                (text, message.find("params").map(|params| format!("Params: {}", params)))
+            }
+            (Some(method), None) => {
+                let text = format!("Received notification '{}'.", method);
+                (text, message.find("params").map(|params| format!("Params: {}", params)))
+            }
+            (None, Some(id)) => {
+                let (method, sent_at) = match state.sent_requests.remove(&id) {
+                    Some(request) => request,
+                    None => ("<unknown>".to_string(), Instant::now()),
+                };
+                let text = format!("Received response '{} - ({})' in {}ms.", method, id, millis_since(sent_at));
+                (text, response_details(&message))
+            }
+            (None, None) => return None,
+        };
+        Some(log_trace_params(text, if state.trace == TraceValue::Verbose { verbose } else { None }))
+    }
+
+    /// Return the trace for given message about to be written, if any.
+    fn message_sent(&self, message: &str) -> Option<Value> {
+        let message = match serde_json::from_str::<Value>(message) {
+            Ok(message) => message,
+            Err(_) => return None,
+        };
+        let method = message.find("method").and_then(Value::as_str);
+        if method == Some(methods::LOG_TRACE) {
+            return None;
+        }
+
+        let mut state = self.lock_state();
+        if state.trace == TraceValue::Off {
+            return None;
+        }
+        let id = message.find("id").map(id_text);
+        let (text, verbose) = match (method, id) {
+            (Some(method), Some(id)) => {
+                let text = format!("Sending request '{} - ({})'.", method, id);
+                state.sent_requests.insert(id, (method.to_string(), Instant::now()));
+                (text, message.find("params").map(|params| format!("Params: {}", params)))
+            }
+            (Some(method), None) => {
+                let text = format!("Sending notification '{}'.", method);
+                (text, message.find("params").map(|params| format!("Params: {}", params)))
+            }
+            (None, Some(id)) => {
+                let request = state.received_requests.remove(&id);
+                let (method, received_at) = match request {
+                    Some(request) => request,
+                    None => return None,
+                };
+                let mut text = format!("Sending response '{} - ({})'. Processing request took {}ms",
+                    method, id, millis_since(received_at));
+                if let Some(error) = message.find("error") {
+                    let error_message = error.find("message").and_then(Value::as_str).unwrap_or("");
+                    let code = error.find("code").map(Value::to_string).unwrap_or_default();
+                    text = format!("{}. Request failed: {} ({}).", text, error_message, code);
+                }
+                (text, response_details(&message))
+            }
+            (None, None) => return None,
+        };
+        Some(log_trace_params(text, if state.trace == TraceValue::Verbose { verbose } else { None }))
+    }
+
+}
+
+/// The id of a message, as shown in traces.
+fn id_text(id: &Value) -> String {
+    match *id {
+        Value::String(ref id) => id.clone(),
+        ref id => id.to_string(),
+    }
+}
+
+fn millis_since(instant: Instant) -> u64 {
+    let elapsed = instant.elapsed();
+    elapsed.as_secs() * 1000 + (elapsed.subsec_nanos() / 1_000_000) as u64
+}
+
+fn response_details(message: &Value) -> Option<String> {
+    match (message.find("result"), message.find("error")) {
+        (Some(result), _) => Some(format!("Result: {}", result)),
+        (None, Some(error)) => Some(format!("Error: {}", error)),
+        (None, None) => None,
+    }
+}
+
+/// The params of a `$/logTrace` notification.
+fn log_trace_params(message: String, verbose: Option<String>) -> Value {
+    let mut params = Map::new();
+    params.insert("message".to_string(), Value::String(message));
+    if let Some(verbose) = verbose {
+        params.insert("verbose".to_string(), Value::String(verbose));
+    }
+    Value::Object(params)
+}
+
+pub struct TracingMessageReader<MR : MessageReader> {
+    msg_reader: MR,
+    tracer: Tracer,
+}
+
+impl<MR : MessageReader> MessageReader for TracingMessageReader<MR> {
+    fn read_next(&mut self) -> GResult<String> {
+        let message = try!(self.msg_reader.read_next());
+        if let Some(params) = self.tracer.message_received(&message) {
+            let endpoint = self.tracer.lock_state().endpoint.clone();
+            if let Some(mut endpoint) = endpoint {
+                // The trace is lost if the endpoint is shut down, which is fine
+                let _ = endpoint.send_notification(methods::LOG_TRACE, params);
+            }
+        }
+        Ok(message)
+    }
+}
+
+pub struct TracingMessageWriter<MW : MessageWriter> {
+    msg_writer: MW,
+    tracer: Tracer,
+}
+
+impl<MW : MessageWriter> MessageWriter for TracingMessageWriter<MW> {
+    fn write_message(&mut self, msg: &str) -> GResult<()> {
+        if let Some(params) = self.tracer.message_sent(msg) {
+            let trace = JsonRpcMessage { id : None, method : methods::LOG_TRACE, params : &params };
+            try!(self.msg_writer.write_message(&try!(serde_json::to_string(&trace))));
+        }
+        self.msg_writer.write_message(msg)
+    }
+}
+
+
+#[test]
+fn tracer__test() {
+    let tracer = Tracer::new();
+    let initialize = r#"{"jsonrpc":"2.0","id":0,"method":"initialize","params":{"capabilities":{}}}"#;
+    assert_eq!(tracer.message_received(initialize), None);
+    assert_eq!(tracer.message_sent(r#"{"jsonrpc":"2.0","id":0,"result":{}}"#), None);
+    assert_eq!(tracer.trace_value(), TraceValue::Off);
+
+    let initialize = r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{"trace":"verbose"}}"#;
+    let trace = tracer.message_received(initialize).unwrap();
+    assert_eq!(tracer.trace_value(), TraceValue::Verbose);
+    assert_eq!(trace.find("message").and_then(Value::as_str), Some("Received request 'initialize - (1)'."));
+    assert_eq!(trace.find("verbose").and_then(Value::as_str), Some(r#"Params: {"trace":"verbose"}"#));
+
+    let trace = tracer.message_sent(r#"{"jsonrpc":"2.0","id":1,"error":{"code":-32603,"message":"Failed"}}"#).unwrap();
+    let text = trace.find("message").and_then(Value::as_str).unwrap();
+    assert!(text.starts_with("Sending response 'initialize - (1)'. Processing request took "));
+    assert!(text.ends_with("ms. Request failed: Failed (-32603)."));
+
+    assert_eq!(tracer.message_received(r#"{"jsonrpc":"2.0","method":"$/setTrace","params":{"value":"messages"}}"#),
+        None);
+    assert_eq!(tracer.trace_value(), TraceValue::Messages);
+
+    let trace = tracer.message_sent(r#"{"jsonrpc":"2.0","id":"a","method":"workspace/configuration"}"#).unwrap();
+    assert_eq!(trace.find("message").and_then(Value::as_str), Some("Sending request 'workspace/configuration - (a)'."));
+    assert_eq!(trace.find("verbose"), None);
+    let trace = tracer.message_received(r#"{"jsonrpc":"2.0","id":"a","result":[]}"#).unwrap();
+    let text = trace.find("message").and_then(Value::as_str).unwrap();
+    assert!(text.starts_with("Received response 'workspace/configuration - (a)' in "));
+
+    assert_eq!(tracer.message_sent(r#"{"jsonrpc":"2.0","method":"$/logTrace","params":{}}"#), None);
+
+    let mut writer = tracer.writer(::lsp_transport::LSPMessageWriter(vec![]));
+    writer.write_message(r#"{"jsonrpc":"2.0","method":"window/logMessage","params":{}}"#).unwrap();
+    let written = String::from_utf8(writer.msg_writer.0).unwrap();
+    assert!(written.contains(r#""method":"$/logTrace","params":{"message":"Sending notification 'window/logMessage'."}"#));
+    assert!(written.ends_with(r#""method":"window/logMessage","params":{}}"#));
+}
@@ -0,0 +1,372 @@
+// Copyright 2016 Bruno Medeiros
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>.
+// This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Capture of the transcript of a session, for regression tests of the protocol behavior of a server.
+//!
+//! The messages of a session are recorded by wrapping its `MessageReader` and `MessageWriter`.
+//! The transcript is then normalized (request ids renumbered, volatile values masked) and compared
+//! with an expected transcript stored in a golden file. Set the `RUSTLSP_UPDATE_GOLDEN` environment
+//! variable to write the actual transcripts to the golden files instead.
+//!
+//! Sessions should be run with `lsp_inline::run_server_inline_with`, so that the order of the
+//! transcript is deterministic.
+
+use std::collections::HashMap;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io::Read;
+use std::io::Write;
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use util::core::*;
+
+use jsonrpc::*;
+use serde_json;
+use serde_json::Value;
+
+/// The environment variable that, when set, makes `Transcript::assert_golden` update the golden files.
+pub const UPDATE_GOLDEN_VAR: &'static str = "RUSTLSP_UPDATE_GOLDEN";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Direction {
+    /// A message read by the recorded endpoint.
+    Received,
+    /// A message written by the recorded endpoint.
+    Sent,
+}
+
+impl Direction {
+    pub fn opposite(self) -> Direction {
+        match self {
+            Direction::Received => Direction::Sent,
+            Direction::Sent => Direction::Received,
+        }
+    }
+
+    pub fn arrow(self) -> &'static str {
+        match self {
+            Direction::Received => "-->",
+            Direction::Sent => "<--",
+        }
+    }
+}
+
+/// A message of a transcript.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TranscriptEntry {
+    pub direction: Direction,
+    /// The method, for requests and notifications.
+    pub method: Option<String>,
+    /// The id, for requests and responses.
+    pub id: Option<Value>,
+    /// The whole message. `Value::String` of the raw message if it is not valid JSON.
+    pub payload: Value,
+}
+
+impl TranscriptEntry {
+
+    pub fn new(direction: Direction, message: &str) -> TranscriptEntry {
+        let payload = serde_json::from_str::<Value>(message).unwrap_or_else(|_| Value::String(message.to_string()));
+        let method = payload.find("method").and_then(Value::as_str).map(String::from);
+        let id = payload.find("id").cloned();
+        TranscriptEntry { direction : direction, method : method, id : id, payload : payload }
+    }
+
+    /// The direction of the request this entry belongs to: its own for requests,
+    /// the opposite for responses.
+    fn request_direction(&self) -> Direction {
+        if self.method.is_some() { self.direction } else { self.direction.opposite() }
+    }
+
+}
+
+impl fmt::Display for TranscriptEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(write!(f, "{}", self.direction.arrow()));
+        match self.method {
+            Some(ref method) if self.id.is_some() => try!(write!(f, " request {}", method)),
+            Some(ref method) => try!(write!(f, " notification {}", method)),
+            None => try!(write!(f, " response")),
+        }
+        if let Some(ref id) = self.id {
+            try!(write!(f, " #{}", id));
+        }
+        let payload = serde_json::to_string_pretty(&self.payload).unwrap_or_default();
+        write!(f, "\n{}\n", payload)
+    }
+}
+
+/* ----------------- Transcript ----------------- */
+
+/// The messages of a session, in the order they were read or written.
+/// Clones share the same messages.
+#[derive(Debug, Clone, Default)]
+pub struct Transcript {
+    entries: Arc<Mutex<Vec<TranscriptEntry>>>,
+}
+
+impl Transcript {
+
+    pub fn new() -> Transcript {
+        Transcript::default()
+    }
+
+    pub fn record(&self, direction: Direction, message: &str) {
+        let entry = TranscriptEntry::new(direction, message);
+        match self.entries.lock() {
+            Ok(mut entries) => entries.push(entry),
+            Err(poisoned) => poisoned.into_inner().push(entry),
+        }
+    }
+
+    pub fn entries(&self) -> Vec<TranscriptEntry> {
+        match self.entries.lock() {
+            Ok(entries) => entries.clone(),
+            Err(poisoned) => poisoned.into_inner().clone(),
+        }
+    }
+
+    /// Wrap given reader, to record the messages it reads.
+    pub fn reader<MR : MessageReader>(&self, msg_reader: MR) -> TranscriptReader<MR> {
+        TranscriptReader { msg_reader : msg_reader, transcript : self.clone() }
+    }
+
+    /// Wrap given writer, to record the messages it writes.
+    pub fn writer<MW : MessageWriter>(&self, msg_writer: MW) -> TranscriptWriter<MW> {
+        TranscriptWriter { msg_writer : msg_writer, transcript : self.clone() }
+    }
+
+    /// The text of the normalized transcript, with given payload keys masked.
+    pub fn to_text(&self, masked_keys: &[&str]) -> String {
+        transcript_text(&normalize(self.entries(), masked_keys))
+    }
+
+    /// Compare the normalized transcript with the one stored in given golden file,
+    /// panicking with a diff if they differ.
+    /// The golden file is written instead if it does not exist, or if `UPDATE_GOLDEN_VAR` is set.
+    pub fn assert_golden(&self, golden_file: &Path, masked_keys: &[&str]) {
+        let actual = self.to_text(masked_keys);
+
+        if env::var_os(UPDATE_GOLDEN_VAR).is_some() || !golden_file.exists() {
+            if let Err(error) = write_file(golden_file, &actual) {
+                panic!("Failed to write golden file {}: {}", golden_file.display(), error);
+            }
+            return;
+        }
+
+        let expected = match read_file(golden_file) {
+            Ok(expected) => expected,
+            Err(error) => panic!("Failed to read golden file {}: {}", golden_file.display(), error),
+        };
+        if let Some(diff) = diff_lines(&expected, &actual) {
+            panic!("Transcript differs from golden file {} (set {} to update it):\n{}",
+                golden_file.display(), UPDATE_GOLDEN_VAR, diff);
+        }
+    }
+
+}
+
+pub struct TranscriptReader<MR> {
+    msg_reader: MR,
+    transcript: Transcript,
+}
+
+impl<MR : MessageReader> MessageReader for TranscriptReader<MR> {
+    fn read_next(&mut self) -> GResult<String> {
+        let message = try!(self.msg_reader.read_next());
+        self.transcript.record(Direction::Received, &message);
+        Ok(message)
+    }
+}
+
+pub struct TranscriptWriter<MW> {
+    msg_writer: MW,
+    transcript: Transcript,
+}
+
+impl<MW : MessageWriter> MessageWriter for TranscriptWriter<MW> {
+    fn write_message(&mut self, msg: &str) -> GResult<()> {
+        self.transcript.record(Direction::Sent, msg);
+        self.msg_writer.write_message(msg)
+    }
+}
+
+fn read_file(path: &Path) -> GResult<String> {
+    let mut contents = String::new();
+    try!(try!(fs::File::open(path)).read_to_string(&mut contents));
+    Ok(contents)
+}
+
+fn write_file(path: &Path, contents: &str) -> GResult<()> {
+    if let Some(parent) = path.parent() {
+        try!(fs::create_dir_all(parent));
+    }
+    try!(try!(fs::File::create(path)).write_all(contents.as_bytes()));
+    Ok(())
+}
+
+/* ----------------- Normalization ----------------- */
+
+const MASK: &'static str = "<masked>";
+
+fn mask_keys(value: &mut Value, masked_keys: &[&str]) {
+    match *value {
+        Value::Object(ref mut obj) => {
+            for (key, value) in obj.iter_mut() {
+                if masked_keys.contains(&&key[..]) {
+                    *value = Value::String(MASK.to_string());
+                } else {
+                    mask_keys(value, masked_keys);
+                }
+            }
+        }
+        Value::Array(ref mut values) => {
+            for value in values {
+                mask_keys(value, masked_keys);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Normalize given entries so they can be compared across sessions:
+/// the request ids are renumbered in order of appearance, separately for each direction,
+/// and the values of given keys (such as timestamps) are masked at any depth of the payloads.
+pub fn normalize(entries: Vec<TranscriptEntry>, masked_keys: &[&str]) -> Vec<TranscriptEntry> {
+    let mut ids : HashMap<(Direction, String), u64> = HashMap::new();
+
+    entries.into_iter().map(|mut entry| {
+        if let Some(id) = entry.id.take() {
+            let id = if id == Value::Null {
+                id
+            } else {
+                let direction = entry.request_direction();
+                let next_id = ids.keys().filter(|key| key.0 == direction).count() as u64 + 1;
+                Value::U64(*ids.entry((direction, id.to_string())).or_insert(next_id))
+            };
+            if let Value::Object(ref mut obj) = entry.payload {
+                obj.insert("id".to_string(), id.clone());
+            }
+            entry.id = Some(id);
+        }
+        mask_keys(&mut entry.payload, masked_keys);
+        entry
+    }).collect()
+}
+
+/// The text form of given entries, as stored in golden files.
+pub fn transcript_text(entries: &[TranscriptEntry]) -> String {
+    entries.iter().map(|entry| entry.to_string()).collect::<Vec<_>>().join("\n")
+}
+
+/// A line diff between `expected` and `actual`, or `None` if they are equal.
+pub fn diff_lines(expected: &str, actual: &str) -> Option<String> {
+    if expected == actual {
+        return None;
+    }
+    let expected : Vec<&str> = expected.lines().collect();
+    let actual : Vec<&str> = actual.lines().collect();
+
+    // Longest common subsequence table, from the end
+    let mut lcs = vec![vec![0usize; actual.len() + 1]; expected.len() + 1];
+    for i in (0..expected.len()).rev() {
+        for j in (0..actual.len()).rev() {
+            lcs[i][j] = if expected[i] == actual[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut diff = String::new();
+    let (mut i, mut j) = (0, 0);
+    while i < expected.len() || j < actual.len() {
+        if i < expected.len() && j < actual.len() && expected[i] == actual[j] {
+            diff.push_str(&format!("  {}\n", expected[i]));
+            i += 1;
+            j += 1;
+        } else if j < actual.len() && (i == expected.len() || lcs[i][j + 1] >= lcs[i + 1][j]) {
+            diff.push_str(&format!("+ {}\n", actual[j]));
+            j += 1;
+        } else {
+            diff.push_str(&format!("- {}\n", expected[i]));
+            i += 1;
+        }
+    }
+    Some(diff)
+}
+
+
+#[test]
+fn transcript__test() {
+    let transcript = Transcript::new();
+    transcript.record(Direction::Received, r#"{ "jsonrpc": "2.0", "id": 7, "method": "initialize", "params": {} }"#);
+    transcript.record(Direction::Sent, r#"{ "jsonrpc": "2.0", "id": "x1", "method": "client/registerCapability" }"#);
+    transcript.record(Direction::Sent, r#"{ "jsonrpc": "2.0", "id": 7, "result": { "time": 1234 } }"#);
+    transcript.record(Direction::Received, r#"{ "jsonrpc": "2.0", "id": "x1", "result": null }"#);
+    transcript.record(Direction::Received, r#"{ "#);
+
+    let entries = normalize(transcript.entries(), &["time"]);
+    assert_eq!(entries[0].id, Some(Value::U64(1)));
+    assert_eq!(entries[1].id, Some(Value::U64(1)));
+    assert_eq!(entries[2].id, Some(Value::U64(1)));
+    assert_eq!(entries[3].id, Some(Value::U64(1)));
+    assert_eq!(entries[3].request_direction(), Direction::Sent);
+    assert_eq!(entries[2].payload.lookup("result.time"), Some(&Value::String(MASK.to_string())));
+    assert_eq!(entries[4].payload, Value::String("{ ".to_string()));
+
+    let text = transcript_text(&entries);
+    assert!(text.starts_with("--> request initialize #1\n"));
+    assert!(text.contains("<-- response #1\n"));
+
+    assert_eq!(diff_lines(&text, &text), None);
+    assert_eq!(diff_lines("a\nb\nc", "a\nc\nd").unwrap(), "  a\n- b\n  c\n+ d\n");
+}
+
+#[test]
+fn transcript_session__test() {
+    use std::io::BufReader;
+    use lsp::*;
+    use lsp_inline::run_server_inline_with;
+    use lsp_transport::LSPMessageReader;
+    use lsp_transport::LSPMessageWriter;
+    use lsp_transport::write_transport_message;
+
+    struct DefaultServer;
+    impl LanguageServerHandling for DefaultServer {}
+
+    let mut input = vec![];
+    for message in &[
+        r#"{ "jsonrpc": "2.0", "id": 1, "method": "shutdown" }"#,
+        r#"{ "jsonrpc": "2.0", "method": "exit" }"#,
+    ] {
+        write_transport_message(message, &mut input).unwrap();
+    }
+
+    let transcript = Transcript::new();
+    let mut input = BufReader::new(&input[..]);
+    let mut reader = transcript.reader(LSPMessageReader(&mut input));
+    run_server_inline_with(&mut reader, transcript.writer(LSPMessageWriter(vec![])), |_| DefaultServer);
+
+    let entries = transcript.entries();
+    let summary : Vec<_> = entries.iter().map(|entry| (entry.direction, entry.method.clone())).collect();
+    assert_eq!(summary, vec![
+        (Direction::Received, Some("shutdown".to_string())),
+        (Direction::Sent, None),
+        (Direction::Received, Some("exit".to_string())),
+    ]);
+
+    let golden_file = env::temp_dir().join("rustlsp_transcript_session__test.txt");
+    let _ = fs::remove_file(&golden_file);
+    transcript.assert_golden(&golden_file, &[]);
+    transcript.assert_golden(&golden_file, &[]);
+    let _ = fs::remove_file(&golden_file);
+}
@@ -6,20 +6,219 @@
 // except according to those terms.
 
 
-use std::io::{self, Read};
+#[cfg(test)] use std::cell::Cell;
+use std::cmp;
+use std::io::{self, Read, Write};
+use std::str;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+use std::time::Instant;
 
 use util::core::*;
 
+use serde;
+use serde::de::Error as DeError;
+use serde_json;
+use serde_json::Value;
+
+use jsonrpc::json_util::JsonObject;
+use jsonrpc::method_types::MethodError;
 use jsonrpc::service_util::MessageReader;
 use jsonrpc::service_util::MessageWriter;
 
+use lsp_cancellation::CancellationToken;
+use lsp_errors::ERROR_CODE__ParseError;
+use lsp_errors::LSError;
+
 /* -----------------  ----------------- */
 
 pub struct LSPMessageReader<T : io::BufRead>(pub T);
 
 impl<T : io::BufRead> MessageReader for LSPMessageReader<T> {
     fn read_next(&mut self) -> GResult<String> {
-        parse_transport_message(&mut self.0)
+        parse_transport_message(&mut self.0).map_err(GError::from)
+    }
+}
+
+/// A message reader that reuses its header buffer across messages.
+pub struct LSPBufferedMessageReader<T : io::BufRead> {
+    input: T,
+    line: Vec<u8>,
+    max_content_length: Option<u32>,
+    utf8_decoding: Utf8Decoding,
+}
+
+impl<T : io::BufRead> LSPBufferedMessageReader<T> {
+    pub fn new(input: T) -> LSPBufferedMessageReader<T> {
+        LSPBufferedMessageReader {
+            input : input,
+            line : Vec::with_capacity(64),
+            max_content_length : None,
+            utf8_decoding : Utf8Decoding::Reject,
+        }
+    }
+    
+    /// Reject the messages with a content longer than given length, skipping their content,
+    /// so that the next message can still be read.
+    pub fn with_max_content_length(mut self, max_content_length: u32) -> Self {
+        self.max_content_length = Some(max_content_length);
+        self
+    }
+    
+    /// Decode the content of the messages with given mode, instead of rejecting invalid UTF-8.
+    pub fn with_utf8_decoding(mut self, utf8_decoding: Utf8Decoding) -> Self {
+        self.utf8_decoding = utf8_decoding;
+        self
+    }
+}
+
+impl<T : io::BufRead> LSPBufferedMessageReader<T> {
+    
+    /// Read the headers of the next message, returning its content length. 
+    /// The content of a message that is too long is skipped.
+    fn read_headers(&mut self) -> Result<u32, LSError> {
+        let content_length = try!(parse_transport_headers(&mut self.input, &mut self.line));
+        if let Some(max_content_length) = self.max_content_length {
+            if content_length > max_content_length {
+                try!(io::copy(&mut (&mut self.input).take(content_length as u64), &mut io::sink()));
+                return Err(LSError::Protocol(format!("Message of {} bytes is longer than the maximum of {}.", 
+                    content_length, max_content_length)));
+            }
+        }
+        Ok(content_length)
+    }
+    
+    fn read_content(&mut self, content_length: u32) -> Result<String, LSError> {
+        let content = try!(read_transport_content(&mut self.input, content_length));
+        decode_transport_content(content, self.utf8_decoding)
+    }
+    
+}
+
+impl<T : io::BufRead> MessageReader for LSPBufferedMessageReader<T> {
+    fn read_next(&mut self) -> GResult<String> {
+        let content_length = try!(self.read_headers());
+        self.read_content(content_length).map_err(GError::from)
+    }
+}
+
+/// A message reader whose whole content must arrive within a timeout, once its headers are read.
+/// Otherwise, the message is failed with `LSError::Truncated`, instead of blocking forever
+/// on a peer that sent less content than its `Content-Length`, and then stalled.
+/// There is no timeout between messages.
+pub struct LSPTimeoutMessageReader {
+    reader: LSPBufferedMessageReader<DeadlineInput>,
+    content_timeout: Duration,
+}
+
+impl LSPTimeoutMessageReader {
+    
+    pub fn new<R : io::Read + Send + 'static>(input: R, content_timeout: Duration) -> LSPTimeoutMessageReader {
+        LSPTimeoutMessageReader {
+            reader : LSPBufferedMessageReader::new(DeadlineInput::new(input)),
+            content_timeout : content_timeout,
+        }
+    }
+    
+    /// As `LSPBufferedMessageReader::with_max_content_length`.
+    pub fn with_max_content_length(mut self, max_content_length: u32) -> Self {
+        self.reader.max_content_length = Some(max_content_length);
+        self
+    }
+    
+    /// As `LSPBufferedMessageReader::with_utf8_decoding`.
+    pub fn with_utf8_decoding(mut self, utf8_decoding: Utf8Decoding) -> Self {
+        self.reader.utf8_decoding = utf8_decoding;
+        self
+    }
+    
+}
+
+impl MessageReader for LSPTimeoutMessageReader {
+    fn read_next(&mut self) -> GResult<String> {
+        let content_length = try!(self.reader.read_headers());
+        self.reader.input.deadline = Some(Instant::now() + self.content_timeout);
+        let content = self.reader.read_content(content_length);
+        self.reader.input.deadline = None;
+        content.map_err(GError::from)
+    }
+}
+
+/// An input read in a background thread, so that reading it can have a deadline, 
+/// after which reads fail with an `io::ErrorKind::TimedOut` error.
+/// The thread ends at the end of the input, or once the input is read after this is dropped.
+pub struct DeadlineInput {
+    receiver: mpsc::Receiver<io::Result<Vec<u8>>>,
+    buffer: Vec<u8>,
+    position: usize,
+    /// The deadline of the reads, if any.
+    pub deadline: Option<Instant>,
+}
+
+const TIMEOUT_INPUT_CHUNK_SIZE: usize = 8 * 1024;
+
+impl DeadlineInput {
+    
+    pub fn new<R : io::Read + Send + 'static>(mut input: R) -> DeadlineInput {
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let mut chunk = vec![0; TIMEOUT_INPUT_CHUNK_SIZE];
+            loop {
+                let read = match input.read(&mut chunk) {
+                    Ok(0) => return,
+                    Ok(count) => Ok(chunk[..count].to_vec()),
+                    Err(ref error) if error.kind() == io::ErrorKind::Interrupted => continue,
+                    Err(error) => Err(error),
+                };
+                let is_error = read.is_err();
+                if sender.send(read).is_err() || is_error {
+                    return;
+                }
+            }
+        });
+        DeadlineInput { receiver : receiver, buffer : vec![], position : 0, deadline : None }
+    }
+    
+}
+
+impl io::BufRead for DeadlineInput {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        while self.position == self.buffer.len() {
+            let read = match self.deadline {
+                Some(deadline) => match self.receiver.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                    Ok(read) => read,
+                    Err(mpsc::RecvTimeoutError::Timeout) => {
+                        return Err(io::Error::new(io::ErrorKind::TimedOut, "Read timed out."));
+                    }
+                    Err(mpsc::RecvTimeoutError::Disconnected) => return Ok(&[]),
+                },
+                None => match self.receiver.recv() {
+                    Ok(read) => read,
+                    Err(_) => return Ok(&[]),
+                },
+            };
+            self.buffer = try!(read);
+            self.position = 0;
+        }
+        Ok(&self.buffer[self.position..])
+    }
+
+    fn consume(&mut self, amount: usize) {
+        self.position = cmp::min(self.position + amount, self.buffer.len());
+    }
+}
+
+impl io::Read for DeadlineInput {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let count = {
+            let available = try!(io::BufRead::fill_buf(self));
+            let count = cmp::min(available.len(), buf.len());
+            buf[..count].copy_from_slice(&available[..count]);
+            count
+        };
+        io::BufRead::consume(self, count);
+        Ok(count)
     }
 }
 
@@ -27,7 +226,122 @@ pub struct LSPMessageWriter<T: io::Write>(pub T);
 
 impl<T: io::Write> MessageWriter for LSPMessageWriter<T> {
     fn write_message(&mut self, msg: &str) -> Result<(), GError> {
-        write_transport_message(msg, &mut self.0)
+        write_transport_message(msg, &mut self.0).map_err(GError::from)
+    }
+}
+
+impl<T: io::Write> StreamingMessageWriter for LSPMessageWriter<T> {
+    fn write_streaming(&mut self, message: &SerializeMessage) -> Result<(), LSError> {
+        write_jsonrpc_message_streaming(message, &mut self.0)
+    }
+}
+
+/// A message that can be serialized to any writer, so that it can be written without knowing its type.
+pub trait SerializeMessage {
+    fn serialize_to(&self, out: &mut io::Write) -> Result<(), serde_json::Error>;
+}
+
+impl<MESSAGE : serde::Serialize> SerializeMessage for MESSAGE {
+    fn serialize_to(&self, mut out: &mut io::Write) -> Result<(), serde_json::Error> {
+        serde_json::to_writer(&mut out, self)
+    }
+}
+
+/// A message writer that can also write a message serialized straight to its output,
+/// so that the content of a large message doesn't have to be held in memory.
+pub trait StreamingMessageWriter : MessageWriter {
+    fn write_streaming(&mut self, message: &SerializeMessage) -> Result<(), LSError>;
+}
+
+/// A message writer that reuses its buffers across messages, instead of allocating them per message.
+pub struct LSPBufferedMessageWriter<T : io::Write> {
+    out: T,
+    header: Vec<u8>,
+    content: Vec<u8>,
+}
+
+/// Buffers that grow larger than this, for a large message, are released after it is written.
+const MAX_RETAINED_BUFFER: usize = 1024 * 1024;
+
+impl<T : io::Write> LSPBufferedMessageWriter<T> {
+    
+    pub fn new(out: T) -> LSPBufferedMessageWriter<T> {
+        LSPBufferedMessageWriter { out : out, header : Vec::with_capacity(32), content : vec![] }
+    }
+    
+    pub fn get_ref(&self) -> &T {
+        &self.out
+    }
+    
+    fn write_framed(out: &mut T, header: &mut Vec<u8>, content: &[u8]) -> Result<(), LSError> {
+        header.clear();
+        try!(write!(header, "{} {}\r\n\r\n", CONTENT_LENGTH, content.len()));
+        try!(out.write_all(header));
+        try!(out.write_all(content));
+        try!(out.flush());
+        Ok(())
+    }
+    
+    /// Write given request or notification, serializing it into the reused content buffer.
+    pub fn write_jsonrpc_message<PARAMS>(&mut self, message: &JsonRpcMessage<PARAMS>) -> Result<(), LSError>
+    where
+        PARAMS : serde::Serialize,
+    {
+        self.content.clear();
+        try!(serde_json::to_writer(&mut self.content, message).map_err(|error| {
+            LSError::Parse("Failed to serialize message:".to_string(), Some(error.into()))
+        }));
+        let result = Self::write_framed(&mut self.out, &mut self.header, &self.content);
+        if self.content.capacity() > MAX_RETAINED_BUFFER {
+            self.content = vec![];
+        }
+        result
+    }
+    
+}
+
+impl<T : io::Write> MessageWriter for LSPBufferedMessageWriter<T> {
+    fn write_message(&mut self, msg: &str) -> Result<(), GError> {
+        Self::write_framed(&mut self.out, &mut self.header, msg.as_bytes()).map_err(GError::from)
+    }
+}
+
+/// Serializes the message into the reused content buffer, unless it is larger than `MAX_RETAINED_BUFFER`,
+/// in which case that serialization only counts its length, and the message is then streamed with it.
+impl<T : io::Write> StreamingMessageWriter for LSPBufferedMessageWriter<T> {
+    fn write_streaming(&mut self, message: &SerializeMessage) -> Result<(), LSError> {
+        self.content.clear();
+        let content_length = {
+            let mut content = BoundedWriter { out : &mut self.content, limit : MAX_RETAINED_BUFFER, count : 0 };
+            try!(message.serialize_to(&mut content).map_err(serialize_error));
+            content.count
+        };
+        if content_length <= MAX_RETAINED_BUFFER {
+            return Self::write_framed(&mut self.out, &mut self.header, &self.content);
+        }
+        self.content = vec![];
+        write_streamed_content(message, content_length, &mut self.out)
+    }
+}
+
+/// Writes to a buffer until it would grow past its limit. From then on, only counts the bytes written.
+struct BoundedWriter<'a> {
+    out: &'a mut Vec<u8>,
+    limit: usize,
+    count: usize,
+}
+
+impl<'a> io::Write for BoundedWriter<'a> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.count += buf.len();
+        if self.count <= self.limit {
+            self.out.extend_from_slice(buf);
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
     }
 }
 
@@ -35,38 +349,398 @@ impl<T: io::Write> MessageWriter for LSPMessageWriter<T> {
 
 const CONTENT_LENGTH: &'static str = "Content-Length:";
 
-pub fn parse_transport_message<R : io::BufRead + ?Sized>(reader: &mut R) -> GResult<String>
+pub fn parse_transport_message<R : io::BufRead + ?Sized>(reader: &mut R) -> Result<String, LSError>
 {
-    
-    let mut content_length : u32 = 0; 
+    let content = try!(parse_transport_message_content(reader));
+    decode_transport_content(content, Utf8Decoding::Reject)
+}
+
+/// How to decode message content that is not valid UTF-8.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Utf8Decoding {
+    /// Fail the message with a parse error.
+    Reject,
+    /// Replace the invalid sequences with U+FFFD, the replacement character.
+    Replace,
+}
+
+const UTF8_BOM: &'static [u8] = b"\xEF\xBB\xBF";
+
+/// Decode the content of a message as UTF-8. A byte order mark at the start of the content, 
+/// which some clients on Windows write, is skipped, instead of failing to parse as JSON.
+pub fn decode_transport_content(mut content: Vec<u8>, utf8_decoding: Utf8Decoding) -> Result<String, LSError> {
+    if content.starts_with(UTF8_BOM) {
+        content.drain(..UTF8_BOM.len());
+    }
+    match String::from_utf8(content) {
+        Ok(content) => Ok(content),
+        Err(error) => match utf8_decoding {
+            Utf8Decoding::Reject => {
+                Err(LSError::Parse("Message content is not valid UTF-8:".to_string(), Some(error.into())))
+            }
+            Utf8Decoding::Replace => {
+                warn!("Replaced invalid UTF-8 in message content: {}", error.utf8_error());
+                Ok(String::from_utf8_lossy(error.as_bytes()).into_owned())
+            }
+        },
+    }
+}
+
+/// Parse a transport message, returning its content as bytes, whatever their encoding.
+pub fn parse_transport_message_content<R : io::BufRead + ?Sized>(reader: &mut R) -> Result<Vec<u8>, LSError>
+{
+    parse_transport_message_content_with(reader, &mut Vec::new())
+}
+
+/// Parse a transport message, as `parse_transport_message_content`, reading the header lines 
+/// into given buffer. If the buffer is reused across messages, parsing the headers allocates nothing.
+pub fn parse_transport_message_content_with<R : io::BufRead + ?Sized>(reader: &mut R, line: &mut Vec<u8>) 
+    -> Result<Vec<u8>, LSError>
+{
+    let content_length = try!(parse_transport_headers(reader, line));
+    read_transport_content(reader, content_length)
+}
+
+/// Parse the headers of a transport message, returning its content length.
+/// 
+/// The headers are read up to their end even if one is invalid, so that the next message can be read.
+/// For the same reason, a header that doesn't start its line is found too, 
+/// which is the case after the content of a message whose length was invalid.
+fn parse_transport_headers<R : io::BufRead + ?Sized>(reader: &mut R, line: &mut Vec<u8>) -> Result<u32, LSError> {
+    let mut content_length = Ok(0);
     
     loop {
-        let mut line = String::new();
+        line.clear();
+        try!(reader.read_until(b'\n', line));
         
-        try!(reader.read_line(&mut line));
-        
-        if line.starts_with(CONTENT_LENGTH) {
-            let len_str : &str = &line[CONTENT_LENGTH.len()..]; 
-            let int_result = len_str.trim().parse::<u32>();
-            
-            content_length = try!(int_result);
-            
-        } else if line.eq("\r\n") {
+        if let Some(start) = find_content_length(line) {
+            if start > 0 {
+                warn!("Skipped {} bytes before the {} header.", start, CONTENT_LENGTH);
+            }
+            content_length = parse_content_length(&line[start + CONTENT_LENGTH.len()..]);
+        } else if &line[..] == b"\r\n" {
             break;
         } else if line.is_empty() {
-            return Err("End of stream reached.".into());
+            return Err(LSError::Shutdown("End of stream reached.".to_string()));
         }
     }
-    if content_length == 0 {
-        return Err((String::from(CONTENT_LENGTH) + " not defined or invalid.").into());
+    match try!(content_length) {
+        0 => Err(LSError::Protocol(String::from(CONTENT_LENGTH) + " not defined or invalid.")),
+        content_length => Ok(content_length),
+    }
+}
+
+fn find_content_length(line: &[u8]) -> Option<usize> {
+    if line.starts_with(CONTENT_LENGTH.as_bytes()) {
+        return Some(0);
+    }
+    line.windows(CONTENT_LENGTH.len()).position(|bytes| bytes == CONTENT_LENGTH.as_bytes())
+}
+
+fn read_transport_content<R : io::BufRead + ?Sized>(reader: &mut R, content_length: u32) -> Result<Vec<u8>, LSError> {
+    // Don't trust the length for the initial capacity, it may be garbage
+    let mut message = Vec::with_capacity(cmp::min(content_length as usize, MAX_INITIAL_CONTENT_CAPACITY));
+    match reader.take(content_length as u64).read_to_end(&mut message) {
+        Ok(_) => {}
+        // A timeout of the input, such as that of `DeadlineInput`, or the read timeout of a socket
+        Err(ref error) if error.kind() == io::ErrorKind::TimedOut || error.kind() == io::ErrorKind::WouldBlock => {
+            return Err(LSError::Truncated(format!("Message truncated: received {} of {} bytes before the timeout.", 
+                message.len(), content_length)));
+        }
+        Err(error) => return Err(error.into()),
+    }
+    if message.len() < content_length as usize {
+        return Err(LSError::Shutdown("End of stream reached before the end of the message.".to_string()));
     }
-    
-    let mut message_reader = reader.take(content_length as u64);
-    let mut message = String::new();
-    try!(message_reader.read_to_string(&mut message));
     return Ok(message);
 }
 
+const MAX_INITIAL_CONTENT_CAPACITY: usize = 1024 * 1024;
+
+fn parse_content_length(value: &[u8]) -> Result<u32, LSError> {
+    let invalid_length = |error: GError| LSError::Parse(format!("Invalid {}", CONTENT_LENGTH), Some(error));
+    let value = try!(str::from_utf8(value).map_err(|error| invalid_length(error.into())));
+    value.trim().parse::<u32>().map_err(|error| invalid_length(error.into()))
+}
+
+/// Parse a transport message from the start of given bytes, without any IO.
+/// Returns the message content, and the number of bytes it took.
+/// Never panics, whatever the input, so it can be used as a fuzzing entry point.
+pub fn parse_transport_message_bytes(bytes: &[u8]) -> Result<(String, usize), LSError> {
+    let mut reader = bytes;
+    let message = try!(parse_transport_message(&mut reader));
+    Ok((message, bytes.len() - reader.len()))
+}
+
+/// Parse given bytes as a JSON-RPC 2.0 message (request, notification or response), without any IO.
+/// Only the envelope is validated, not the params or result.
+/// Never panics, whatever the input, so it can be used as a fuzzing entry point.
+pub fn parse_jsonrpc_message_bytes(bytes: &[u8]) -> Result<Value, LSError> {
+    let message : Value = try!(serde_json::from_slice(bytes).map_err(|error| {
+        LSError::Parse("Message is not valid JSON:".to_string(), Some(error.into()))
+    }));
+    
+    {
+        let obj = match message.as_object() {
+            Some(obj) => obj,
+            None => return Err(LSError::Protocol("Message is not a JSON object.".to_string())),
+        };
+        if obj.get("jsonrpc").and_then(Value::as_str) != Some("2.0") {
+            return Err(LSError::Protocol("Message `jsonrpc` is not \"2.0\".".to_string()));
+        }
+        match obj.get("id") {
+            None | Some(&Value::Null) | Some(&Value::String(_)) | Some(&Value::I64(_)) | Some(&Value::U64(_)) => {}
+            Some(_) => return Err(LSError::Protocol("Message `id` is not a string or integer.".to_string())),
+        }
+        match obj.get("method") {
+            Some(&Value::String(_)) => {
+                match obj.get("params") {
+                    None | Some(&Value::Object(_)) | Some(&Value::Array(_)) => {}
+                    Some(_) => return Err(LSError::Protocol("Message `params` is not structured.".to_string())),
+                }
+            }
+            Some(_) => return Err(LSError::Protocol("Message `method` is not a string.".to_string())),
+            None => {
+                let has_result = obj.contains_key("result");
+                let has_error = obj.contains_key("error");
+                if has_result == has_error {
+                    return Err(LSError::Protocol(
+                        "Response must have exactly one of `result` or `error`.".to_string()));
+                }
+                if !obj.contains_key("id") {
+                    return Err(LSError::Protocol("Response has no `id`.".to_string()));
+                }
+            }
+        }
+    }
+    Ok(message)
+}
+
+/// A JSON-RPC request, or a notification if it has no `id`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JsonRpcRequest {
+    pub id: Option<Value>,
+    pub method: String,
+    /// `Value::Null` if there are no params.
+    pub params: Value,
+}
+
+/// Parse given bytes as a JSON-RPC 2.0 request or notification, straight from the transport buffer,
+/// without making a `String` of them first.
+pub fn parse_jsonrpc_request_bytes(bytes: &[u8]) -> Result<JsonRpcRequest, LSError> {
+    serde_json::from_slice(bytes).map_err(request_parse_error)
+}
+
+/// The error of a request that failed to deserialize. The envelope errors of `JsonRpcRequest`
+/// are custom errors, with the message of the protocol error.
+fn request_parse_error(error: serde_json::Error) -> LSError {
+    match error {
+        serde_json::Error::Syntax(serde_json::error::ErrorCode::Custom(message), _, _) => LSError::Protocol(message),
+        error => LSError::Parse("Message is not valid JSON:".to_string(), Some(error.into())),
+    }
+}
+
+impl serde::Deserialize for JsonRpcRequest {
+    fn deserialize<D>(deserializer: &mut D) -> Result<JsonRpcRequest, D::Error>
+    where
+        D : serde::Deserializer,
+    {
+        deserializer.deserialize(JsonRpcRequestVisitor)
+    }
+}
+
+const ERROR_NOT_AN_OBJECT: &'static str = "Message is not a JSON object.";
+
+struct JsonRpcRequestVisitor;
+
+impl serde::de::Visitor for JsonRpcRequestVisitor {
+    type Value = JsonRpcRequest;
+
+    fn visit_map<V>(&mut self, mut visitor: V) -> Result<JsonRpcRequest, V::Error>
+    where
+        V : serde::de::MapVisitor,
+    {
+        let mut version = None;
+        let mut id = None;
+        let mut method = None;
+        let mut params = Value::Null;
+        while let Some(key) = try!(visitor.visit_key::<String>()) {
+            match key.as_str() {
+                "jsonrpc" => version = Some(try!(visitor.visit_value::<Value>())),
+                "id" => id = Some(try!(visitor.visit_value::<RequestIdField>()).0),
+                "method" => method = Some(try!(visitor.visit_value::<MethodField>()).0),
+                "params" => params = try!(visitor.visit_value::<ParamsField>()).0,
+                _ => { try!(visitor.visit_value::<serde::de::impls::IgnoredAny>()); }
+            }
+        }
+        try!(visitor.end());
+        
+        if version.as_ref().and_then(Value::as_str) != Some("2.0") {
+            return Err(V::Error::custom("Message `jsonrpc` is not \"2.0\"."));
+        }
+        let method = match method {
+            Some(method) => method,
+            None => return Err(V::Error::custom("Message is not a request or notification.")),
+        };
+        Ok(JsonRpcRequest { id : id, method : method, params : params })
+    }
+
+    fn visit_seq<V>(&mut self, _: V) -> Result<JsonRpcRequest, V::Error>
+    where
+        V : serde::de::SeqVisitor,
+    {
+        Err(V::Error::custom(ERROR_NOT_AN_OBJECT))
+    }
+
+    fn visit_unit<E : serde::de::Error>(&mut self) -> Result<JsonRpcRequest, E> {
+        Err(E::custom(ERROR_NOT_AN_OBJECT))
+    }
+
+    fn visit_bool<E : serde::de::Error>(&mut self, _: bool) -> Result<JsonRpcRequest, E> {
+        Err(E::custom(ERROR_NOT_AN_OBJECT))
+    }
+
+    fn visit_i64<E : serde::de::Error>(&mut self, _: i64) -> Result<JsonRpcRequest, E> {
+        Err(E::custom(ERROR_NOT_AN_OBJECT))
+    }
+
+    fn visit_u64<E : serde::de::Error>(&mut self, _: u64) -> Result<JsonRpcRequest, E> {
+        Err(E::custom(ERROR_NOT_AN_OBJECT))
+    }
+
+    fn visit_f64<E : serde::de::Error>(&mut self, _: f64) -> Result<JsonRpcRequest, E> {
+        Err(E::custom(ERROR_NOT_AN_OBJECT))
+    }
+
+    fn visit_str<E : serde::de::Error>(&mut self, _: &str) -> Result<JsonRpcRequest, E> {
+        Err(E::custom(ERROR_NOT_AN_OBJECT))
+    }
+}
+
+/// The `id` of a request: a string, an integer, or `null`.
+struct RequestIdField(Value);
+
+impl serde::Deserialize for RequestIdField {
+    fn deserialize<D>(deserializer: &mut D) -> Result<RequestIdField, D::Error>
+    where
+        D : serde::Deserializer,
+    {
+        match try!(Value::deserialize(deserializer)) {
+            id @ Value::Null | id @ Value::String(_) | id @ Value::I64(_) | id @ Value::U64(_) => Ok(RequestIdField(id)),
+            _ => Err(D::Error::custom("Message `id` is not a string or integer.")),
+        }
+    }
+}
+
+struct MethodField(String);
+
+impl serde::Deserialize for MethodField {
+    fn deserialize<D>(deserializer: &mut D) -> Result<MethodField, D::Error>
+    where
+        D : serde::Deserializer,
+    {
+        match try!(Value::deserialize(deserializer)) {
+            Value::String(method) => Ok(MethodField(method)),
+            _ => Err(D::Error::custom("Message `method` is not a string.")),
+        }
+    }
+}
+
+/// The `params` of a request, which must be structured: an object or an array.
+struct ParamsField(Value);
+
+impl serde::Deserialize for ParamsField {
+    fn deserialize<D>(deserializer: &mut D) -> Result<ParamsField, D::Error>
+    where
+        D : serde::Deserializer,
+    {
+        match try!(Value::deserialize(deserializer)) {
+            params @ Value::Object(_) | params @ Value::Array(_) => Ok(ParamsField(params)),
+            _ => Err(D::Error::custom("Message `params` is not structured.")),
+        }
+    }
+}
+
+/// A reader that skips the messages of the underlying reader that fail to be read with a recoverable error 
+/// (see `LSError::is_recoverable`), which would otherwise end the read loop.
+/// Each skipped message is logged, and answered with a ParseError response, if given a writer for that.
+pub struct RecoveringMessageReader<'a, MR : MessageReader + 'a> {
+    reader: &'a mut MR,
+    error_writer: Option<Box<MessageWriter>>,
+}
+
+impl<'a, MR : MessageReader + 'a> RecoveringMessageReader<'a, MR> {
+    
+    pub fn new(reader: &'a mut MR) -> RecoveringMessageReader<'a, MR> {
+        RecoveringMessageReader { reader : reader, error_writer : None }
+    }
+    
+    /// Answer each skipped message with a ParseError response, written to given writer.
+    pub fn with_error_responses(mut self, error_writer: Box<MessageWriter>) -> Self {
+        self.error_writer = Some(error_writer);
+        self
+    }
+    
+}
+
+impl<'a, MR : MessageReader + 'a> MessageReader for RecoveringMessageReader<'a, MR> {
+    fn read_next(&mut self) -> GResult<String> {
+        loop {
+            let error = match self.reader.read_next() {
+                Ok(message) => return Ok(message),
+                Err(error) => LSError::from_gerror(error),
+            };
+            if !error.is_recoverable() {
+                return Err(error.into());
+            }
+            warn!("Skipped a message that could not be read: {}", error);
+            if let Some(ref mut error_writer) = self.error_writer {
+                if let Err(error) = error_writer.write_message(&parse_error_response(&error)) {
+                    error!("Failed to write the ParseError response: {}", error);
+                }
+            }
+        }
+    }
+}
+
+/// The response to a message that could not be read, which has no `id` then.
+pub fn parse_error_response(error: &LSError) -> String {
+    let mut error_obj = JsonObject::new();
+    error_obj.insert("code".to_string(), Value::I64(ERROR_CODE__ParseError));
+    error_obj.insert("message".to_string(), Value::String(error.to_string()));
+    let mut response = JsonObject::new();
+    response.insert("jsonrpc".to_string(), Value::String("2.0".to_string()));
+    response.insert("id".to_string(), Value::Null);
+    response.insert("error".to_string(), Value::Object(error_obj));
+    Value::Object(response).to_string()
+}
+
+/// What the read loop knows of a request or notification, which it passes to its request handler,
+/// along with the params and the completable.
+#[derive(Debug, Clone, Default)]
+pub struct RequestInfo {
+    /// The `id` of a request, `None` for a notification, which must not be answered.
+    pub id: Option<Value>,
+    /// The token that cancels the request, which the dispatch layer gives it.
+    /// Never cancelled until then.
+    pub cancellation: CancellationToken,
+}
+
+impl RequestInfo {
+    
+    pub fn notification() -> RequestInfo {
+        RequestInfo { id : None, cancellation : CancellationToken::new() }
+    }
+    
+    pub fn request(id: Value) -> RequestInfo {
+        RequestInfo { id : Some(id), cancellation : CancellationToken::new() }
+    }
+    
+    pub fn is_notification(&self) -> bool {
+        self.id.is_none()
+    }
+    
+}
 
 #[test]
 fn parse_transport_message__test() {
@@ -81,25 +755,314 @@ fn parse_transport_message__test() {
     
     // Test no-content
     let string = "\r\n\r\n1234567890abcdef";
-    let err : GError = parse_transport_message(&mut BufReader::new(string.as_bytes())).unwrap_err();
+    let err = parse_transport_message(&mut BufReader::new(string.as_bytes())).unwrap_err();
     assert_eq!(&err.to_string(), "Content-Length: not defined or invalid.");
+    match err { LSError::Protocol(_) => {} _ => panic!() }
     
     // Test EOS
     let string = "";
-    let err : GError = parse_transport_message(&mut BufReader::new(string.as_bytes())).unwrap_err();
+    let err = parse_transport_message(&mut BufReader::new(string.as_bytes())).unwrap_err();
     assert_eq!(&err.to_string(), "End of stream reached.");
+    assert!(err.is_shutdown());
+    
+    // Test invalid length
+    let string = "Content-Length: 1x\r\n\r\n1234567890abcdef";
+    let err = parse_transport_message(&mut BufReader::new(string.as_bytes())).unwrap_err();
+    match err { LSError::Parse(_, Some(_)) => {} _ => panic!() }
+    
+    // Test truncated content
+    let string = "Content-Length: 10\r\n\r\n12345";
+    let err = parse_transport_message(&mut BufReader::new(string.as_bytes())).unwrap_err();
+    assert!(err.is_shutdown());
+    
+    // Test invalid UTF-8 header
+    let bytes = b"Content-Length: \xFF\r\n\r\n1";
+    match parse_transport_message(&mut BufReader::new(&bytes[..])).unwrap_err() { 
+        LSError::Parse(..) => {} 
+        err => panic!("Unexpected: {:?}", err),
+    }
+}
+
+#[test]
+fn recovering_message_reader__test() {
+    use lsp_testing::pipe;
+    
+    let input : &[u8] = b"Content-Length: 1x\r\nContent-Type: a\r\n\r\n{}Content-Length: 1\r\n\r\n1\
+        Content-Length: 5\r\n\r\n12345Content-Length: 1\r\n\r\n2Content-Length: 1\r\n\r\n\xFF\
+        Content-Length: 1\r\n\r\n3";
+    let mut reader = LSPBufferedMessageReader::new(input).with_max_content_length(4);
+    let (writer, responses) = pipe();
+    {
+        let mut reader = RecoveringMessageReader::new(&mut reader)
+            .with_error_responses(Box::new(LSPBufferedMessageWriter::new(writer)));
+        assert_eq!(reader.read_next().unwrap(), "1");
+        assert_eq!(reader.read_next().unwrap(), "2");
+        assert_eq!(reader.read_next().unwrap(), "3");
+        assert!(LSError::from_gerror(reader.read_next().unwrap_err()).is_shutdown());
+    }
+    
+    // The invalid length, the oversize message, and the invalid UTF-8 are answered
+    let mut responses = LSPBufferedMessageReader::new(responses);
+    for _ in 0..3 {
+        let response : Value = serde_json::from_str(&responses.read_next().unwrap()).unwrap();
+        assert_eq!(response.find("id"), Some(&Value::Null));
+        assert_eq!(response.lookup("error.code").and_then(Value::as_i64), Some(-32700));
+    }
+    assert!(responses.read_next().is_err());
+}
+
+#[test]
+fn timeout_message_reader__test() {
+    use lsp_testing::pipe;
+    
+    let (mut writer, input) = pipe();
+    let mut reader = LSPTimeoutMessageReader::new(input, Duration::from_millis(50));
+    writer.write_all(b"Content-Length: 2\r\n\r\n{}").unwrap();
+    assert_eq!(reader.read_next().unwrap(), "{}");
+    
+    // The writer is still open, but stalls in the middle of the content
+    writer.write_all(b"Content-Length: 10\r\n\r\n12345").unwrap();
+    let error = LSError::from_gerror(reader.read_next().unwrap_err());
+    assert!(error.is_truncated());
+    assert!(!error.is_recoverable());
+    assert_eq!(error.to_string(), "Message truncated: received 5 of 10 bytes before the timeout.");
+    
+    drop(writer);
+    assert!(LSError::from_gerror(reader.read_next().unwrap_err()).is_shutdown());
+}
+
+#[test]
+fn decode_transport_content__test() {
+    let bytes = b"Content-Length: 5\r\n\r\n\xEF\xBB\xBF{}Content-Length: 4\r\n\r\n[\xFF1]";
+    let mut reader = LSPBufferedMessageReader::new(&bytes[..]);
+    assert_eq!(reader.read_next().unwrap(), "{}");
+    match LSError::from_gerror(reader.read_next().unwrap_err()) {
+        LSError::Parse(..) => {}
+        err => panic!("Unexpected: {:?}", err),
+    }
+    
+    let mut reader = LSPBufferedMessageReader::new(&bytes[..]).with_utf8_decoding(Utf8Decoding::Replace);
+    assert_eq!(reader.read_next().unwrap(), "{}");
+    assert_eq!(reader.read_next().unwrap(), "[\u{FFFD}1]");
+    
+    // Only a leading BOM is skipped
+    assert_eq!(decode_transport_content(b"1\xEF\xBB\xBF".to_vec(), Utf8Decoding::Reject).unwrap(), "1\u{FEFF}");
+}
+
+#[test]
+fn parse_message_bytes__test() {
+    let bytes = b"Content-Length: 2\r\n\r\n{}Content-Length: 1\r\n\r\n1";
+    assert_eq!(parse_transport_message_bytes(bytes).unwrap(), ("{}".to_string(), 23));
+    assert_eq!(parse_transport_message_bytes(&bytes[23..]).unwrap(), ("1".to_string(), 22));
+    
+    assert!(parse_jsonrpc_message_bytes(br#"{"jsonrpc": "2.0", "id": 1, "method": "m", "params": {}}"#).is_ok());
+    assert!(parse_jsonrpc_message_bytes(br#"{"jsonrpc": "2.0", "method": "m"}"#).is_ok());
+    assert!(parse_jsonrpc_message_bytes(br#"{"jsonrpc": "2.0", "id": null, "error": {}}"#).is_ok());
+    assert!(parse_jsonrpc_message_bytes(br#"{"jsonrpc": "2.0", "id": 1, "result": null, "error": {}}"#).is_err());
+    assert!(parse_jsonrpc_message_bytes(br#"{"jsonrpc": "2.0", "id": 1.5, "method": "m"}"#).is_err());
+    assert!(parse_jsonrpc_message_bytes(br#"{"jsonrpc": "1.0", "method": "m"}"#).is_err());
+    assert!(parse_jsonrpc_message_bytes(br#"[1, 2]"#).is_err());
+    assert!(parse_jsonrpc_message_bytes(b"\xFF{").is_err());
     
+    let request = parse_jsonrpc_request_bytes(br#"{"jsonrpc": "2.0", "id": "a", "method": "m", "params": [1]}"#).unwrap();
+    assert_eq!(request.id, Some(Value::String("a".to_string())));
+    assert_eq!(request.method, "m");
+    assert_eq!(request.params, Value::Array(vec![Value::U64(1)]));
+    let notification = parse_jsonrpc_request_bytes(br#"{"jsonrpc": "2.0", "method": "m"}"#).unwrap();
+    assert_eq!((notification.id, notification.params), (None, Value::Null));
+    assert!(parse_jsonrpc_request_bytes(br#"{"jsonrpc": "2.0", "id": 1, "result": null}"#).is_err());
+    let error_message = |bytes: &[u8]| match parse_jsonrpc_request_bytes(bytes) {
+        Err(LSError::Protocol(message)) => message,
+        result => panic!("Unexpected result: {:?}", result),
+    };
+    assert_eq!(error_message(br#"[1, 2]"#), "Message is not a JSON object.");
+    assert_eq!(error_message(br#"{"method": "m"}"#), "Message `jsonrpc` is not \"2.0\".");
+    assert_eq!(error_message(br#"{"jsonrpc": "2.0", "id": 1.5, "method": "m"}"#),
+        "Message `id` is not a string or integer.");
+    assert_eq!(error_message(br#"{"jsonrpc": "2.0", "method": 1}"#), "Message `method` is not a string.");
+    assert_eq!(error_message(br#"{"jsonrpc": "2.0", "method": "m", "params": 1}"#), "Message `params` is not structured.");
+    match parse_jsonrpc_request_bytes(b"{") {
+        Err(LSError::Parse(..)) => {}
+        result => panic!("Unexpected result: {:?}", result),
+    }
+    
+    // Missing params are null params
+    let request = parse_jsonrpc_request_bytes(br#"{"jsonrpc": "2.0", "id": 1, "method": "shutdown"}"#).unwrap();
+    assert_eq!(request.id, Some(Value::U64(1)));
+    assert_eq!(request.params, Value::Null);
+    let request = parse_jsonrpc_request_bytes(br#"{"jsonrpc": "2.0", "method": "exit"}"#).unwrap();
+    assert_eq!(request.id, None);
+    assert!(parse_jsonrpc_request_bytes(br#"{"jsonrpc": "2.0", "id": 1, "result": null}"#).is_err());
+    
+    // Arbitrary input must not panic
+    let mut seed : u32 = 12345;
+    for _ in 0..2000 {
+        let mut bytes = b"Content-Length: 12\r\n\r\n{\"jsonrpc\":1}".to_vec();
+        for _ in 0..4 {
+            seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+            let ix = (seed >> 8) as usize % bytes.len();
+            bytes[ix] = (seed >> 16) as u8;
+        }
+        if let Ok((message, _)) = parse_transport_message_bytes(&bytes) {
+            let _ = parse_jsonrpc_message_bytes(message.as_bytes());
+            let _ = parse_jsonrpc_request_bytes(message.as_bytes());
+        }
+        let _ = parse_jsonrpc_message_bytes(&bytes);
+    }
+}
+
+/* ----------------- Typed messages ----------------- */
+
+/// A JSON-RPC request (if it has an `id`) or notification, serialized straight from its typed params,
+/// without building a `Value` first.
+#[derive(Debug)]
+pub struct JsonRpcMessage<'a, PARAMS : 'a> {
+    pub id: Option<u64>,
+    pub method: &'a str,
+    pub params: &'a PARAMS,
+}
+
+impl<'a, PARAMS : serde::Serialize> serde::Serialize for JsonRpcMessage<'a, PARAMS> {
+    fn serialize<S>(&self, serializer: &mut S) -> Result<(), S::Error>
+    where
+        S : serde::Serializer,
+    {
+        let len = if self.id.is_some() { 4 } else { 3 };
+        let mut state = try!(serializer.serialize_struct("JsonRpcMessage", len));
+        try!(serializer.serialize_struct_elt(&mut state, "jsonrpc", "2.0"));
+        if let Some(id) = self.id {
+            try!(serializer.serialize_struct_elt(&mut state, "id", id));
+        }
+        try!(serializer.serialize_struct_elt(&mut state, "method", self.method));
+        try!(serializer.serialize_struct_elt(&mut state, "params", self.params));
+        serializer.serialize_struct_end(state)
+    }
+}
+
+/// A JSON-RPC response, serialized straight from its typed result or error, without building a `Value` first.
+pub struct JsonRpcResponse<'a, RET : 'a, ERR_DATA : 'a> {
+    pub id: &'a Value,
+    pub result: &'a Result<RET, MethodError<ERR_DATA>>,
+}
+
+impl<'a, RET : serde::Serialize, ERR_DATA : serde::Serialize> serde::Serialize for JsonRpcResponse<'a, RET, ERR_DATA> {
+    fn serialize<S>(&self, serializer: &mut S) -> Result<(), S::Error>
+    where
+        S : serde::Serializer,
+    {
+        let mut state = try!(serializer.serialize_struct("JsonRpcResponse", 3));
+        try!(serializer.serialize_struct_elt(&mut state, "jsonrpc", "2.0"));
+        try!(serializer.serialize_struct_elt(&mut state, "id", self.id));
+        match *self.result {
+            Ok(ref result) => try!(serializer.serialize_struct_elt(&mut state, "result", result)),
+            Err(ref error) => try!(serializer.serialize_struct_elt(&mut state, "error", ResponseError(error))),
+        }
+        serializer.serialize_struct_end(state)
+    }
+}
+
+/// The error object of a `JsonRpcResponse`.
+struct ResponseError<'a, ERR_DATA : 'a>(&'a MethodError<ERR_DATA>);
+
+impl<'a, ERR_DATA : serde::Serialize> serde::Serialize for ResponseError<'a, ERR_DATA> {
+    fn serialize<S>(&self, serializer: &mut S) -> Result<(), S::Error>
+    where
+        S : serde::Serializer,
+    {
+        let mut state = try!(serializer.serialize_struct("ResponseError", 3));
+        try!(serializer.serialize_struct_elt(&mut state, "code", self.0.code));
+        try!(serializer.serialize_struct_elt(&mut state, "message", &self.0.message));
+        try!(serializer.serialize_struct_elt(&mut state, "data", &self.0.data));
+        serializer.serialize_struct_end(state)
+    }
+}
+
+/// Write given request or notification as a transport message.
+pub fn write_jsonrpc_message<WRITE, PARAMS>(message: &JsonRpcMessage<PARAMS>, out: &mut WRITE) -> Result<(), LSError>
+where
+    WRITE : io::Write,
+    PARAMS : serde::Serialize,
+{
+    let content = try!(serde_json::to_vec(message).map_err(|error| {
+        LSError::Parse("Failed to serialize message:".to_string(), Some(error.into()))
+    }));
+    write_transport_message_content(&content, out)
+}
+
+/// Counts the bytes written, and passes them on to the underlying writer, if any.
+struct CountingWriter<W> {
+    out: Option<W>,
+    count: usize,
+}
+
+impl<W : io::Write> io::Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = match self.out {
+            Some(ref mut out) => try!(out.write(buf)),
+            None => buf.len(),
+        };
+        self.count += written;
+        Ok(written)
+    }
+    
+    fn flush(&mut self) -> io::Result<()> {
+        match self.out {
+            Some(ref mut out) => out.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Write given message, such as a `JsonRpcMessage` or `JsonRpcResponse`, serializing it straight to `out`,
+/// so that the content is never held in memory. The message is serialized twice: first only to compute
+/// its length, for the header. This suits large messages; smaller ones are faster with `LSPBufferedMessageWriter`.
+pub fn write_jsonrpc_message_streaming<WRITE>(message: &SerializeMessage, out: &mut WRITE) -> Result<(), LSError>
+where
+    WRITE : io::Write,
+{
+    let mut counter : CountingWriter<io::Sink> = CountingWriter { out : None, count : 0 };
+    try!(message.serialize_to(&mut counter).map_err(serialize_error));
+    write_streamed_content(message, counter.count, out)
 }
 
-pub fn write_transport_message<WRITE : io::Write>(message: & str, out: &mut WRITE) -> GResult<()>
+/// Write given message, of which the content length was already counted, serializing it straight to `out`.
+fn write_streamed_content<WRITE>(message: &SerializeMessage, content_length: usize, out: &mut WRITE) 
+    -> Result<(), LSError>
+where
+    WRITE : io::Write,
+{
+    let mut out = io::BufWriter::new(out);
+    try!(write!(out, "{} {}\r\n\r\n", CONTENT_LENGTH, content_length));
+    let mut content_out = CountingWriter { out : Some(&mut out), count : 0 };
+    try!(message.serialize_to(&mut content_out).map_err(serialize_error));
+    if content_out.count != content_length {
+        // The stream is now corrupt, since the header had the wrong length
+        return Err(LSError::Protocol(format!("Message serialization is not deterministic: {} bytes, then {}.",
+            content_length, content_out.count)));
+    }
+    try!(out.flush());
+    Ok(())
+}
+
+fn serialize_error(error: serde_json::Error) -> LSError {
+    LSError::Parse("Failed to serialize message:".to_string(), Some(error.into()))
+}
+
+/* ----------------- Write ----------------- */
+
+pub fn write_transport_message<WRITE : io::Write>(message: & str, out: &mut WRITE) -> Result<(), LSError>
+{
+    write_transport_message_content(message.as_bytes(), out)
+}
+
+/// Write a transport message with given content, whatever its encoding.
+pub fn write_transport_message_content<WRITE : io::Write>(contents: &[u8], out: &mut WRITE) -> Result<(), LSError>
 {
-//    let out : &mut io::Write = out;
     try!(out.write_all(CONTENT_LENGTH.as_bytes()));
     try!(out.write(&[' ' as u8]));
-    let contents = message.as_bytes();
     try!(out.write_all(contents.len().to_string().as_bytes()));
     try!(out.write_all("\r\n\r\n".as_bytes()));
-    try!(out.write_all(message.as_bytes()));
+    try!(out.write_all(contents));
     try!(out.flush());
     Ok(())
 }
@@ -113,4 +1076,110 @@ fn write_transport_message__test() {
     write_transport_message(&"1234\n67", &mut out).unwrap();
     
     assert_equal(String::from_utf8(out).unwrap(), "xContent-Length: 7\r\n\r\n1234\n67".to_string());
-}
\ No newline at end of file
+}
+
+#[test]
+fn write_jsonrpc_message__test() {
+    let mut out : Vec<u8> = vec![];
+    let params = vec![1, 2];
+    write_jsonrpc_message(&JsonRpcMessage { id : Some(3), method : "m", params : &params }, &mut out).unwrap();
+    let content = r#"{"jsonrpc":"2.0","id":3,"method":"m","params":[1,2]}"#;
+    assert_eq!(String::from_utf8(out).unwrap(), format!("Content-Length: {}\r\n\r\n{}", content.len(), content));
+    
+    let mut out : Vec<u8> = vec![];
+    write_jsonrpc_message(&JsonRpcMessage { id : None, method : "m", params : &Value::Null }, &mut out).unwrap();
+    let (message, _) = parse_transport_message_bytes(&out).unwrap();
+    assert_eq!(message, r#"{"jsonrpc":"2.0","method":"m","params":null}"#);
+}
+
+#[test]
+fn buffered_message_writer__test() {
+    let mut writer = LSPBufferedMessageWriter::new(vec![]);
+    writer.write_message("{}").unwrap();
+    writer.write_jsonrpc_message(&JsonRpcMessage { id : Some(1), method : "m", params : &Value::Null }).unwrap();
+    let content_capacity = writer.content.capacity();
+    writer.write_jsonrpc_message(&JsonRpcMessage { id : Some(2), method : "m", params : &Value::Null }).unwrap();
+    assert_eq!(writer.content.capacity(), content_capacity);
+    
+    let mut output = &writer.get_ref()[..];
+    assert_eq!(parse_transport_message(&mut output).unwrap(), "{}");
+    assert_eq!(parse_transport_message(&mut output).unwrap(), r#"{"jsonrpc":"2.0","id":1,"method":"m","params":null}"#);
+    assert_eq!(parse_transport_message(&mut output).unwrap(), r#"{"jsonrpc":"2.0","id":2,"method":"m","params":null}"#);
+    
+    let large = Value::String("x".repeat(MAX_RETAINED_BUFFER));
+    writer.write_jsonrpc_message(&JsonRpcMessage { id : None, method : "m", params : &large }).unwrap();
+    assert_eq!(writer.content.capacity(), 0);
+}
+
+#[test]
+fn write_jsonrpc_message_streaming__test() {
+    let params = vec!["a".to_string(), "\u{4E2D}\"".to_string()];
+    let message = JsonRpcMessage { id : Some(1), method : "m", params : &params };
+    
+    let mut streamed = vec![];
+    write_jsonrpc_message_streaming(&message, &mut streamed).unwrap();
+    let mut buffered = vec![];
+    write_jsonrpc_message(&message, &mut buffered).unwrap();
+    assert_eq!(String::from_utf8(streamed).unwrap(), String::from_utf8(buffered).unwrap());
+}
+
+#[cfg(test)]
+struct SerializationCounter(Cell<usize>, String);
+
+#[cfg(test)]
+impl serde::Serialize for SerializationCounter {
+    fn serialize<S : serde::Serializer>(&self, serializer: &mut S) -> Result<(), S::Error> {
+        self.0.set(self.0.get() + 1);
+        self.1.serialize(serializer)
+    }
+}
+
+#[test]
+fn write_jsonrpc_response__test() {
+    let id = Value::U64(7);
+    let result : Result<Vec<u32>, MethodError<()>> = Ok(vec![1, 2]);
+    let error : Result<Vec<u32>, MethodError<()>> = 
+        Err(MethodError { code : -32603, message : "Failed".to_string(), data : () });
+    let large : Result<SerializationCounter, MethodError<()>> = 
+        Ok(SerializationCounter(Cell::new(0), "x".repeat(MAX_RETAINED_BUFFER)));
+    
+    let mut writer = LSPBufferedMessageWriter::new(vec![]);
+    writer.write_streaming(&JsonRpcResponse { id : &id, result : &result }).unwrap();
+    writer.write_streaming(&JsonRpcResponse { id : &id, result : &error }).unwrap();
+    // Larger than the buffer, so streamed
+    writer.write_streaming(&JsonRpcResponse { id : &id, result : &large }).unwrap();
+    assert_eq!(writer.content.capacity(), 0);
+    // Once to count its length, and once to write it
+    assert_eq!(large.as_ref().unwrap().0.get(), 2);
+    
+    let mut output = &writer.get_ref()[..];
+    assert_eq!(parse_transport_message(&mut output).unwrap(), r#"{"jsonrpc":"2.0","id":7,"result":[1,2]}"#);
+    assert_eq!(parse_transport_message(&mut output).unwrap(), 
+        r#"{"jsonrpc":"2.0","id":7,"error":{"code":-32603,"message":"Failed","data":null}}"#);
+    assert_eq!(parse_transport_message(&mut output).unwrap(), 
+        format!(r#"{{"jsonrpc":"2.0","id":7,"result":"{}"}}"#, "x".repeat(MAX_RETAINED_BUFFER)));
+}
+
+/// Run with `cargo test --release -- --ignored --nocapture parse_transport_message__bench`
+#[test]
+#[ignore]
+fn parse_transport_message__bench() {
+    use std::time::Instant;
+    
+    let change = r#"{"jsonrpc":"2.0","method":"textDocument/didChange","params":{"textDocument":
+        {"uri":"file:///lib.rs","version":1},"contentChanges":[{"text":"x"}]}}"#;
+    let mut input = vec![];
+    let count = 200000;
+    for _ in 0..count {
+        write_transport_message(change, &mut input).unwrap();
+    }
+    
+    let start = Instant::now();
+    let mut reader = LSPBufferedMessageReader::new(&input[..]);
+    for _ in 0..count {
+        reader.read_next().unwrap();
+    }
+    let elapsed = start.elapsed();
+    let secs = elapsed.as_secs() as f64 + elapsed.subsec_nanos() as f64 / 1e9;
+    println!("Parsed {} messages in {:.3}s ({:.0} messages/s)", count, secs, count as f64 / secs);
+}
@@ -0,0 +1,176 @@
+// Copyright 2016 Bruno Medeiros
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>.
+// This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Conversions between `file` URIs, such as a `textDocument.uri`, and filesystem paths.
+//!
+//! Unlike `Url::to_file_path`, the conversions of Windows paths (drive letters, UNC paths)
+//! are available on any platform, which is needed to handle URIs of a client on another OS,
+//! and to test them.
+
+use std::path::Path;
+use std::path::PathBuf;
+
+use util::core::*;
+
+use url::Url;
+use url::percent_encoding::percent_decode;
+use url::percent_encoding::utf8_percent_encode;
+use url::percent_encoding::PATH_SEGMENT_ENCODE_SET;
+
+/// The path style of the host OS.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PathStyle {
+    /// `/` separated paths.
+    Unix,
+    /// `\` separated paths, with drive letters (`C:\dir`) or UNC hosts (`\\server\share\dir`).
+    Windows,
+}
+
+impl PathStyle {
+    pub fn native() -> PathStyle {
+        if cfg!(windows) { PathStyle::Windows } else { PathStyle::Unix }
+    }
+}
+
+fn is_drive_letter(segment: &str) -> bool {
+    let bytes = segment.as_bytes();
+    bytes.len() == 2 && (bytes[0] as char).is_ascii_alphabetic() && bytes[1] == b':'
+}
+
+/// Convert given `file` URI to a path in given style.
+pub fn uri_to_path_string(uri: &Url, style: PathStyle) -> GResult<String> {
+    if uri.scheme() != "file" {
+        return Err(format!("Not a file URI: {}", uri).into());
+    }
+
+    let mut segments = vec![];
+    for segment in uri.path().split('/').skip(1) {
+        let segment = try!(percent_decode(segment.as_bytes()).decode_utf8()
+            .map_err(|_| format!("Invalid UTF-8 in URI path: {}", uri)));
+        if segment.contains('/') || (style == PathStyle::Windows && segment.contains('\\')) {
+            return Err(format!("URI path segment contains a separator: {}", uri).into());
+        }
+        segments.push(segment.into_owned());
+    }
+
+    let host = match uri.host_str() {
+        Some("localhost") | Some("") | None => None,
+        Some(host) => Some(host),
+    };
+
+    match style {
+        PathStyle::Unix => {
+            if let Some(host) = host {
+                return Err(format!("File URI with a host ({}) is not supported: {}", host, uri).into());
+            }
+            Ok(format!("/{}", segments.join("/")))
+        }
+        PathStyle::Windows => {
+            if let Some(host) = host {
+                return Ok(format!(r"\\{}\{}", host, segments.join(r"\")));
+            }
+            match segments.first() {
+                Some(drive) if is_drive_letter(drive) => {}
+                _ => return Err(format!("File URI has no drive letter: {}", uri).into()),
+            }
+            if segments.len() == 1 {
+                // The root of the drive
+                segments.push("".to_string());
+            }
+            Ok(segments.join(r"\"))
+        }
+    }
+}
+
+/// Convert given absolute path, in given style, to a `file` URI.
+pub fn path_string_to_uri(path: &str, style: PathStyle) -> GResult<Url> {
+    let (host, segments) : (&str, Vec<&str>) = match style {
+        PathStyle::Unix => {
+            if !path.starts_with('/') {
+                return Err(format!("Path is not absolute: {}", path).into());
+            }
+            ("", path[1..].split('/').collect())
+        }
+        PathStyle::Windows => {
+            let is_separator = |ch: char| ch == '\\' || ch == '/';
+            if path.starts_with(r"\\") || path.starts_with("//") {
+                let mut parts = path[2..].splitn(2, is_separator);
+                let host = parts.next().unwrap_or("");
+                if host.is_empty() {
+                    return Err(format!("UNC path has no host: {}", path).into());
+                }
+                (host, parts.next().unwrap_or("").split(is_separator).collect())
+            } else {
+                let segments : Vec<&str> = path.split(is_separator).collect();
+                if !is_drive_letter(segments[0]) {
+                    return Err(format!("Path is not absolute: {}", path).into());
+                }
+                ("", segments)
+            }
+        }
+    };
+
+    let mut uri = format!("file://{}", host);
+    for segment in segments {
+        uri.push('/');
+        uri.extend(utf8_percent_encode(segment, PATH_SEGMENT_ENCODE_SET));
+    }
+    Url::parse(&uri).map_err(|error| format!("Invalid file URI {}: {}", uri, error).into())
+}
+
+/// Convert given `file` URI to a path of the host OS.
+pub fn uri_to_path(uri: &Url) -> GResult<PathBuf> {
+    uri_to_path_string(uri, PathStyle::native()).map(PathBuf::from)
+}
+
+/// Convert given absolute path of the host OS to a `file` URI.
+pub fn path_to_uri(path: &Path) -> GResult<Url> {
+    match path.to_str() {
+        Some(path) => path_string_to_uri(path, PathStyle::native()),
+        None => Err(format!("Path is not valid UTF-8: {:?}", path).into()),
+    }
+}
+
+
+#[test]
+fn uri_to_path__test() {
+    use self::PathStyle::*;
+    let to_path = |uri: &str, style| uri_to_path_string(&Url::parse(uri).unwrap(), style);
+
+    assert_eq!(to_path("file:///home/user/lib.rs", Unix).unwrap(), "/home/user/lib.rs");
+    assert_eq!(to_path("file://localhost/home/user/a%20b.rs", Unix).unwrap(), "/home/user/a b.rs");
+    assert_eq!(to_path("file:///home/%E4%B8%AD/lib.rs", Unix).unwrap(), "/home/\u{4E2D}/lib.rs");
+    assert!(to_path("file:///home/a%2Fb", Unix).is_err());
+    assert!(to_path("file://server/share/lib.rs", Unix).is_err());
+    assert!(to_path("untitled:Untitled-1", Unix).is_err());
+
+    // VS Code encodes the drive letter colon
+    assert_eq!(to_path("file:///c%3A/Users/lib.rs", Windows).unwrap(), r"c:\Users\lib.rs");
+    assert_eq!(to_path("file:///C:/Users/lib.rs", Windows).unwrap(), r"C:\Users\lib.rs");
+    assert_eq!(to_path("file:///C:", Windows).unwrap(), r"C:\");
+    assert_eq!(to_path("file://server/share/lib.rs", Windows).unwrap(), r"\\server\share\lib.rs");
+    assert!(to_path("file:///Users/lib.rs", Windows).is_err());
+}
+
+#[test]
+fn path_to_uri__test() {
+    use self::PathStyle::*;
+    let to_uri = |path: &str, style| path_string_to_uri(path, style).map(|uri| uri.as_str().to_string());
+
+    assert_eq!(to_uri("/home/user/a b#.rs", Unix).unwrap(), "file:///home/user/a%20b%23.rs");
+    assert!(to_uri("home/user", Unix).is_err());
+
+    assert_eq!(to_uri(r"C:\Users\a b.rs", Windows).unwrap(), "file:///C:/Users/a%20b.rs");
+    assert_eq!(to_uri(r"\\server\share\lib.rs", Windows).unwrap(), "file://server/share/lib.rs");
+    assert!(to_uri(r"Users\lib.rs", Windows).is_err());
+
+    // Round trip
+    for path in &[r"C:\Users\%\lib.rs", r"\\server\share\x y"] {
+        let uri = path_string_to_uri(path, Windows).unwrap();
+        assert_eq!(&uri_to_path_string(&uri, Windows).unwrap(), path);
+    }
+}
@@ -0,0 +1,515 @@
+// Copyright 2016 Bruno Medeiros
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>.
+// This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Detection of requests that take too long, to help users diagnose a server that hangs,
+//! such as with a misbehaving formatter, without attaching a debugger.
+//!
+//! A `SlowRequestDetector` wraps the `MessageReader` and `MessageWriter` of the server, and calls
+//! a callback once for each request still running after a threshold. By default, the callback
+//! sends the client a `window/logMessage` warning, with the endpoint given to the context:
+//!
+//! ```ignore
+//! let detector = SlowRequestDetector::new(Duration::from_secs(5));
+//! let endpoint = LSPEndpoint::create_lsp_output(move || detector.writer(LSPMessageWriter(io::stdout())));
+//! let context = LSServerContext::new(endpoint).with_slow_request_detector(detector.clone());
+//! LSPEndpoint::run_server(&mut detector.reader(LSPMessageReader(stdin.lock())), context, server);
+//! ```
+//!
+//! And timeouts per method: `RequestTimeouts` answers a request still running after the timeout
+//! of its method with an error or a fallback result, and drops the late response of the handler.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::MutexGuard;
+use std::sync::Weak;
+use std::thread;
+use std::time::Duration;
+use std::time::Instant;
+
+use util::core::*;
+
+use jsonrpc::*;
+use jsonrpc::json_util::JsonObject;
+use serde_json::Value;
+
+use ls_types::LogMessageParams;
+use ls_types::MessageType;
+
+use lsp::send_lsp_notification;
+use lsp_errors::ERROR_CODE__RequestFailed;
+use lsp_methods as methods;
+use lsp_raw::scan_message_envelope;
+
+/// A request still running after the threshold.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SlowRequest {
+    pub method: String,
+    /// The id of the request, as JSON.
+    pub id: String,
+    pub elapsed: Duration,
+}
+
+impl SlowRequest {
+    /// The text of the default warning.
+    pub fn message(&self) -> String {
+        let elapsed = self.elapsed.as_secs() as f64 + self.elapsed.subsec_nanos() as f64 / 1e9;
+        format!("Request `{}` ({}) has been running for {:.1}s.", self.method, self.id, elapsed)
+    }
+}
+
+pub type SlowRequestCallback = Box<Fn(&SlowRequest) + Send>;
+
+struct RunningRequest {
+    method: String,
+    started: Instant,
+    reported: bool,
+}
+
+struct DetectorState {
+    running: HashMap<String, RunningRequest>,
+    callback: Option<SlowRequestCallback>,
+    endpoint: Option<Endpoint>,
+}
+
+/// Calls a callback for each request still running after a threshold. Clones share the same state.
+///
+/// The requests are checked by a thread, which runs while there are clones of the detector,
+/// and the endpoint given with `attach` (if any) is not shut down.
+#[derive(Clone)]
+pub struct SlowRequestDetector {
+    threshold: Duration,
+    state: Arc<Mutex<DetectorState>>,
+}
+
+fn lock_state(state: &Mutex<DetectorState>) -> MutexGuard<DetectorState> {
+    match state.lock() {
+        Ok(state) => state,
+        Err(poisoned) => poisoned.into_inner(),
+    }
+}
+
+impl SlowRequestDetector {
+
+    pub fn new(threshold: Duration) -> SlowRequestDetector {
+        let state = DetectorState { running : HashMap::new(), callback : None, endpoint : None };
+        let detector = SlowRequestDetector { threshold : threshold, state : Arc::new(Mutex::new(state)) };
+
+        let state = Arc::downgrade(&detector.state);
+        let period = threshold / 4;
+        let period = if period < Duration::from_millis(10) { Duration::from_millis(10) } else { period };
+        thread::spawn(move || {
+            loop {
+                thread::sleep(period);
+                if !check_running(&state, threshold) {
+                    break;
+                }
+            }
+        });
+        detector
+    }
+
+    /// Call given callback for the slow requests, instead of sending a warning to the client.
+    pub fn with_callback<CALLBACK>(self, callback: CALLBACK) -> SlowRequestDetector
+    where
+        CALLBACK : Fn(&SlowRequest) + Send + 'static,
+    {
+        lock_state(&self.state).callback = Some(Box::new(callback));
+        self
+    }
+
+    pub fn threshold(&self) -> Duration {
+        self.threshold
+    }
+
+    /// Set the endpoint to send the default warnings with.
+    /// Done by `LSServerContext::with_slow_request_detector`.
+    pub fn attach(&self, endpoint: Endpoint) {
+        lock_state(&self.state).endpoint = Some(endpoint);
+    }
+
+    /// Wrap given reader, to know when each request starts.
+    pub fn reader<MR : MessageReader>(&self, msg_reader: MR) -> DetectorMessageReader<MR> {
+        DetectorMessageReader { msg_reader : msg_reader, detector : self.clone() }
+    }
+
+    /// Wrap given writer, to know when each request ends.
+    pub fn writer<MW : MessageWriter>(&self, msg_writer: MW) -> DetectorMessageWriter<MW> {
+        DetectorMessageWriter { msg_writer : msg_writer, detector : self.clone() }
+    }
+
+    fn message_read(&self, message: &str) {
+        if let Ok(envelope) = scan_message_envelope(message) {
+            if let (Some(method), Some(id)) = (envelope.method, envelope.id) {
+                let request = RunningRequest { method : method, started : Instant::now(), reported : false };
+                lock_state(&self.state).running.insert(id.to_string(), request);
+            }
+        }
+    }
+
+    fn message_written(&self, message: &str) {
+        if let Ok(envelope) = scan_message_envelope(message) {
+            if let (None, Some(id)) = (envelope.method, envelope.id) {
+                lock_state(&self.state).running.remove(&id.to_string());
+            }
+        }
+    }
+
+}
+
+/// Report the requests that became slow. Returns whether the checks should go on.
+fn check_running(state: &Weak<Mutex<DetectorState>>, threshold: Duration) -> bool {
+    let state = match state.upgrade() {
+        Some(state) => state,
+        None => return false,
+    };
+    let mut state = lock_state(&state);
+    if state.endpoint.as_ref().map_or(false, Endpoint::is_shutdown) {
+        return false;
+    }
+
+    let mut slow_requests = vec![];
+    for (id, request) in &mut state.running {
+        let elapsed = request.started.elapsed();
+        if !request.reported && elapsed >= threshold {
+            request.reported = true;
+            slow_requests.push(SlowRequest { method : request.method.clone(), id : id.clone(), elapsed : elapsed });
+        }
+    }
+
+    for slow_request in &slow_requests {
+        if let Some(ref callback) = state.callback {
+            callback(slow_request);
+        } else if let Some(ref endpoint) = state.endpoint {
+            let params = LogMessageParams { typ : MessageType::Warning, message : slow_request.message() };
+            if let Err(error) = send_lsp_notification::<methods::LogMessage>(&mut endpoint.clone(), params) {
+                warn!("Failed to report slow request: {}", error);
+            }
+        } else {
+            warn!("{}", slow_request.message());
+        }
+    }
+    true
+}
+
+pub struct DetectorMessageReader<MR : MessageReader> {
+    msg_reader: MR,
+    detector: SlowRequestDetector,
+}
+
+impl<MR : MessageReader> MessageReader for DetectorMessageReader<MR> {
+    fn read_next(&mut self) -> GResult<String> {
+        let message = try!(self.msg_reader.read_next());
+        self.detector.message_read(&message);
+        Ok(message)
+    }
+}
+
+pub struct DetectorMessageWriter<MW : MessageWriter> {
+    msg_writer: MW,
+    detector: SlowRequestDetector,
+}
+
+impl<MW : MessageWriter> MessageWriter for DetectorMessageWriter<MW> {
+    fn write_message(&mut self, msg: &str) -> GResult<()> {
+        self.detector.message_written(msg);
+        self.msg_writer.write_message(msg)
+    }
+}
+
+/* ----------------- Timeouts ----------------- */
+
+struct MethodTimeout {
+    timeout: Duration,
+    /// The result to answer with, instead of an error.
+    fallback: Option<Value>,
+}
+
+struct TimedRequest {
+    method: String,
+    id: Value,
+    started: Instant,
+    deadline: Instant,
+}
+
+/// The most requests answered on timeout that are remembered, to drop their late response.
+/// The oldest are forgotten first, such as those whose handler never responds.
+const MAX_TIMED_OUT_REQUESTS: usize = 1000;
+
+struct TimeoutsState {
+    timeouts: HashMap<String, MethodTimeout>,
+    /// The running requests with a timeout, by id as JSON.
+    running: HashMap<String, TimedRequest>,
+    /// The requests answered on timeout, whose response must be dropped, by id as JSON.
+    timed_out: HashMap<String, TimedRequest>,
+    writer: Option<Box<MessageWriter + Send>>,
+    late_callback: Option<SlowRequestCallback>,
+}
+
+/// Answers the requests still running after the timeout of their method, keeping editors responsive
+/// when a single provider hangs. Clones share the same state.
+///
+/// Wraps the `MessageReader` and `MessageWriter` of the server. The timeout responses are written
+/// with the wrapped writer, and the late responses of the handlers are dropped, after calling the
+/// late response callback (by default, a warning is logged).
+#[derive(Clone)]
+pub struct RequestTimeouts {
+    state: Arc<Mutex<TimeoutsState>>,
+}
+
+fn lock_timeouts(state: &Mutex<TimeoutsState>) -> MutexGuard<TimeoutsState> {
+    match state.lock() {
+        Ok(state) => state,
+        Err(poisoned) => poisoned.into_inner(),
+    }
+}
+
+impl RequestTimeouts {
+
+    pub fn new() -> RequestTimeouts {
+        let state = TimeoutsState {
+            timeouts : HashMap::new(),
+            running : HashMap::new(),
+            timed_out : HashMap::new(),
+            writer : None,
+            late_callback : None,
+        };
+        let timeouts = RequestTimeouts { state : Arc::new(Mutex::new(state)) };
+        let state = Arc::downgrade(&timeouts.state);
+        thread::spawn(move || {
+            while let Some(wait) = check_timeouts(&state) {
+                thread::sleep(wait);
+            }
+        });
+        timeouts
+    }
+
+    /// Answer the requests of given method that take longer than given timeout with an error.
+    pub fn timeout(self, method: &str, timeout: Duration) -> Self {
+        let method_timeout = MethodTimeout { timeout : timeout, fallback : None };
+        lock_timeouts(&self.state).timeouts.insert(method.to_string(), method_timeout);
+        self
+    }
+
+    /// Answer the requests of given method that take longer than given timeout with given result.
+    pub fn timeout_with_fallback(self, method: &str, timeout: Duration, fallback: Value) -> Self {
+        let method_timeout = MethodTimeout { timeout : timeout, fallback : Some(fallback) };
+        lock_timeouts(&self.state).timeouts.insert(method.to_string(), method_timeout);
+        self
+    }
+
+    /// Call given callback for each late response of a handler, which is dropped.
+    pub fn on_late_response<CALLBACK>(self, callback: CALLBACK) -> Self
+    where
+        CALLBACK : Fn(&SlowRequest) + Send + 'static,
+    {
+        lock_timeouts(&self.state).late_callback = Some(Box::new(callback));
+        self
+    }
+
+    /// Wrap given reader, to know when each request starts.
+    pub fn reader<MR : MessageReader>(&self, msg_reader: MR) -> TimeoutsMessageReader<MR> {
+        TimeoutsMessageReader { msg_reader : msg_reader, timeouts : self.clone() }
+    }
+
+    /// Wrap given writer, which the timeout responses are also written with.
+    pub fn writer<MW : MessageWriter + Send + 'static>(&self, msg_writer: MW) -> TimeoutsMessageWriter {
+        lock_timeouts(&self.state).writer = Some(Box::new(msg_writer));
+        TimeoutsMessageWriter { timeouts : self.clone() }
+    }
+
+    fn message_read(&self, message: &str) {
+        let envelope = match scan_message_envelope(message) {
+            Ok(envelope) => envelope,
+            Err(_) => return,
+        };
+        if let (Some(method), Some(id)) = (envelope.method, envelope.id) {
+            let mut state = lock_timeouts(&self.state);
+            let timeout = match state.timeouts.get(&method) {
+                Some(method_timeout) => method_timeout.timeout,
+                None => return,
+            };
+            let started = Instant::now();
+            let key = id.to_string();
+            let request = TimedRequest { method : method, id : id, started : started, deadline : started + timeout };
+            state.running.insert(key, request);
+        }
+    }
+
+    fn write_message(&self, msg: &str) -> GResult<()> {
+        let mut state = lock_timeouts(&self.state);
+        if let Ok(envelope) = scan_message_envelope(msg) {
+            if let (None, Some(id)) = (envelope.method, envelope.id) {
+                let id = id.to_string();
+                state.running.remove(&id);
+                if let Some(request) = state.timed_out.remove(&id) {
+                    let late = SlowRequest { method : request.method, id : id, elapsed : request.started.elapsed() };
+                    match state.late_callback {
+                        Some(ref callback) => callback(&late),
+                        None => warn!("Dropped late response of request `{}` ({}), which timed out.", late.method, late.id),
+                    }
+                    return Ok(());
+                }
+            }
+        }
+        match state.writer {
+            Some(ref mut writer) => writer.write_message(msg),
+            None => Ok(()),
+        }
+    }
+
+}
+
+/// Answer the requests that timed out. Returns how long to wait for the next check,
+/// or `None` if the checks should stop.
+fn check_timeouts(state: &Weak<Mutex<TimeoutsState>>) -> Option<Duration> {
+    let state = match state.upgrade() {
+        Some(state) => state,
+        None => return None,
+    };
+    let mut state = lock_timeouts(&state);
+    let now = Instant::now();
+
+    let expired : Vec<String> = state.running.iter()
+        .filter(|&(_, request)| request.deadline <= now)
+        .map(|(id, _)| id.clone())
+        .collect();
+    for id in expired {
+        let request = match state.running.remove(&id) {
+            Some(request) => request,
+            None => continue,
+        };
+        let response = {
+            let method_timeout = state.timeouts.get(&request.method);
+            timeout_response(&request, method_timeout.and_then(|method_timeout| method_timeout.fallback.clone()))
+        };
+        if let Some(ref mut writer) = state.writer {
+            if let Err(error) = writer.write_message(&response) {
+                warn!("Failed to write timeout response: {}", error);
+            }
+        }
+        state.timed_out.insert(id, request);
+    }
+    while state.timed_out.len() > MAX_TIMED_OUT_REQUESTS {
+        let oldest = state.timed_out.iter().min_by_key(|&(_, request)| request.started).map(|(id, _)| id.clone());
+        if let Some(oldest) = oldest {
+            state.timed_out.remove(&oldest);
+        }
+    }
+
+    let max_wait = Duration::from_millis(50);
+    let wait = state.running.values().map(|request| request.deadline).min()
+        .map_or(max_wait, |deadline| if deadline > now { deadline - now } else { Duration::from_millis(0) });
+    Some(if wait < max_wait { wait } else { max_wait })
+}
+
+fn timeout_response(request: &TimedRequest, fallback: Option<Value>) -> String {
+    let mut response = JsonObject::new();
+    response.insert("jsonrpc".to_string(), Value::String("2.0".to_string()));
+    response.insert("id".to_string(), request.id.clone());
+    match fallback {
+        Some(result) => {
+            response.insert("result".to_string(), result);
+        }
+        None => {
+            let elapsed = request.started.elapsed();
+            let mut error = JsonObject::new();
+            error.insert("code".to_string(), Value::I64(ERROR_CODE__RequestFailed));
+            error.insert("message".to_string(), Value::String(format!("Request `{}` timed out after {}ms.",
+                request.method, elapsed.as_secs() * 1000 + elapsed.subsec_nanos() as u64 / 1_000_000)));
+            response.insert("error".to_string(), Value::Object(error));
+        }
+    }
+    Value::Object(response).to_string()
+}
+
+pub struct TimeoutsMessageReader<MR : MessageReader> {
+    msg_reader: MR,
+    timeouts: RequestTimeouts,
+}
+
+impl<MR : MessageReader> MessageReader for TimeoutsMessageReader<MR> {
+    fn read_next(&mut self) -> GResult<String> {
+        let message = try!(self.msg_reader.read_next());
+        self.timeouts.message_read(&message);
+        Ok(message)
+    }
+}
+
+pub struct TimeoutsMessageWriter {
+    timeouts: RequestTimeouts,
+}
+
+impl MessageWriter for TimeoutsMessageWriter {
+    fn write_message(&mut self, msg: &str) -> GResult<()> {
+        self.timeouts.write_message(msg)
+    }
+}
+
+
+#[test]
+fn slow_request_detector__test() {
+    use std::sync::mpsc;
+
+    let (sender, receiver) = mpsc::channel();
+    let detector = SlowRequestDetector::new(Duration::from_millis(50)).with_callback(move |request| {
+        let _ = sender.send(request.clone());
+    });
+
+    detector.message_read(r#"{"jsonrpc":"2.0","id":1,"method":"textDocument/formatting","params":{}}"#);
+    detector.message_read(r#"{"jsonrpc":"2.0","id":2,"method":"textDocument/hover","params":{}}"#);
+    detector.message_read(r#"{"jsonrpc":"2.0","method":"textDocument/didOpen","params":{}}"#);
+    detector.message_written(r#"{"jsonrpc":"2.0","id":2,"result":null}"#);
+
+    let slow_request = receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!(slow_request.method, "textDocument/formatting");
+    assert_eq!(slow_request.id, "1");
+    assert!(slow_request.elapsed >= Duration::from_millis(50));
+    assert!(slow_request.message().starts_with("Request `textDocument/formatting` (1) has been running for "));
+
+    // Reported only once
+    assert!(receiver.recv_timeout(Duration::from_millis(200)).is_err());
+}
+
+#[test]
+fn request_timeouts__test() {
+    use std::sync::mpsc;
+
+    #[derive(Clone, Default)]
+    struct RecordingWriter(Arc<Mutex<Vec<String>>>);
+    impl MessageWriter for RecordingWriter {
+        fn write_message(&mut self, msg: &str) -> GResult<()> {
+            self.0.lock().unwrap().push(msg.to_string());
+            Ok(())
+        }
+    }
+
+    let (sender, receiver) = mpsc::channel();
+    let timeouts = RequestTimeouts::new()
+        .timeout("textDocument/formatting", Duration::from_millis(20))
+        .timeout_with_fallback("textDocument/hover", Duration::from_millis(20), Value::Null)
+        .on_late_response(move |request| { let _ = sender.send(request.clone()); });
+    let written = RecordingWriter::default();
+    let mut writer = timeouts.writer(written.clone());
+
+    timeouts.message_read(r#"{"jsonrpc":"2.0","id":1,"method":"textDocument/formatting","params":{}}"#);
+    timeouts.message_read(r#"{"jsonrpc":"2.0","id":2,"method":"textDocument/hover","params":{}}"#);
+    timeouts.message_read(r#"{"jsonrpc":"2.0","id":3,"method":"textDocument/completion","params":{}}"#);
+    thread::sleep(Duration::from_millis(200));
+
+    writer.write_message(r#"{"jsonrpc":"2.0","id":1,"result":[]}"#).unwrap();
+    writer.write_message(r#"{"jsonrpc":"2.0","id":3,"result":null}"#).unwrap();
+    let late = receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!((&late.method[..], &late.id[..]), ("textDocument/formatting", "1"));
+
+    let mut written = written.0.lock().unwrap().clone();
+    written.sort();
+    assert_eq!(written.len(), 3);
+    assert!(written[0].starts_with(r#"{"error":{"code":-32803,"message":"Request `textDocument/formatting` timed out after "#));
+    assert_eq!(written[1], r#"{"id":2,"jsonrpc":"2.0","result":null}"#);
+    assert_eq!(written[2], r#"{"jsonrpc":"2.0","id":3,"result":null}"#);
+}
@@ -0,0 +1,340 @@
+// Copyright 2016 Bruno Medeiros
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>.
+// This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Scanning of the workspace files, filtered with LSP glob patterns.
+
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+
+use util::core::*;
+
+use url::Url;
+
+/* ----------------- Glob patterns ----------------- */
+
+#[derive(Debug, Clone, PartialEq)]
+enum GlobToken {
+    Literal(char),
+    /// `?`: any char except a path separator
+    AnyChar,
+    /// `*`: any sequence of chars in a path segment
+    Star,
+    /// `**`: any sequence of path segments, including none
+    DoubleStar,
+    /// `[...]`: one char of the given ranges, or not of them if negated
+    Class(bool, Vec<(char, char)>),
+}
+
+/// A glob pattern as used in LSP `DocumentFilter`s and file operation filters:
+/// `*`, `?`, `**`, `{a,b}` alternatives and `[a-z]`/`[!a-z]` character classes.
+/// Paths are matched with `/` separators.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GlobPattern {
+    pattern: String,
+    alternatives: Vec<Vec<GlobToken>>,
+}
+
+impl GlobPattern {
+
+    pub fn new(pattern: &str) -> GResult<GlobPattern> {
+        let mut alternatives = vec![];
+        for expanded in try!(expand_braces(pattern)) {
+            alternatives.push(try!(tokenize_glob(&expanded)));
+        }
+        Ok(GlobPattern { pattern : pattern.to_string(), alternatives : alternatives })
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.pattern
+    }
+
+    pub fn matches(&self, path: &str) -> bool {
+        let path : Vec<char> = path.chars().collect();
+        self.alternatives.iter().any(|tokens| glob_matches(tokens, &path))
+    }
+
+}
+
+/// Expand the `{a,b}` alternatives of given pattern. Alternatives can be nested.
+fn expand_braces(pattern: &str) -> GResult<Vec<String>> {
+    let open_ix = match pattern.find('{') {
+        Some(open_ix) => open_ix,
+        None => return Ok(vec![pattern.to_string()]),
+    };
+
+    let mut depth = 0;
+    let mut choice_start = open_ix + 1;
+    let mut choices = vec![];
+    for (ix, ch) in pattern[open_ix..].char_indices().map(|(ix, ch)| (open_ix + ix, ch)) {
+        match ch {
+            '{' => depth += 1,
+            '}' if depth == 1 => {
+                choices.push(&pattern[choice_start..ix]);
+                let prefix = &pattern[..open_ix];
+                let mut expanded = vec![];
+                for choice in choices {
+                    let rest = format!("{}{}{}", prefix, choice, &pattern[ix + 1..]);
+                    expanded.extend(try!(expand_braces(&rest)));
+                }
+                return Ok(expanded);
+            }
+            '}' => depth -= 1,
+            ',' if depth == 1 => {
+                choices.push(&pattern[choice_start..ix]);
+                choice_start = ix + 1;
+            }
+            _ => {}
+        }
+    }
+    Err(format!("Invalid glob pattern, unclosed `{{`: {}", pattern).into())
+}
+
+fn tokenize_glob(pattern: &str) -> GResult<Vec<GlobToken>> {
+    let mut tokens = vec![];
+    let mut chars = pattern.chars().peekable();
+    while let Some(ch) = chars.next() {
+        let token = match ch {
+            '?' => GlobToken::AnyChar,
+            '*' => {
+                if chars.peek() == Some(&'*') {
+                    chars.next();
+                    GlobToken::DoubleStar
+                } else {
+                    GlobToken::Star
+                }
+            }
+            '[' => {
+                let negated = chars.peek() == Some(&'!') || chars.peek() == Some(&'^');
+                if negated {
+                    chars.next();
+                }
+                let mut ranges = vec![];
+                loop {
+                    match chars.next() {
+                        Some(']') if !ranges.is_empty() => break,
+                        Some(start) => {
+                            if chars.peek() == Some(&'-') {
+                                chars.next();
+                                match chars.next() {
+                                    Some(']') => {
+                                        ranges.push((start, start));
+                                        ranges.push(('-', '-'));
+                                        break;
+                                    }
+                                    Some(end) => ranges.push((start, end)),
+                                    None => break,
+                                }
+                            } else {
+                                ranges.push((start, start));
+                            }
+                        }
+                        None => return Err(format!("Invalid glob pattern, unclosed `[`: {}", pattern).into()),
+                    }
+                }
+                GlobToken::Class(negated, ranges)
+            }
+            _ => GlobToken::Literal(ch),
+        };
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+fn glob_matches(tokens: &[GlobToken], path: &[char]) -> bool {
+    let (token, rest) = match tokens.split_first() {
+        Some(split) => split,
+        None => return path.is_empty(),
+    };
+
+    match *token {
+        GlobToken::Literal(ch) => {
+            !path.is_empty() && path[0] == ch && glob_matches(rest, &path[1..])
+        }
+        GlobToken::AnyChar => {
+            !path.is_empty() && path[0] != '/' && glob_matches(rest, &path[1..])
+        }
+        GlobToken::Class(negated, ref ranges) => {
+            if path.is_empty() || path[0] == '/' {
+                return false;
+            }
+            let in_class = ranges.iter().any(|&(start, end)| start <= path[0] && path[0] <= end);
+            in_class != negated && glob_matches(rest, &path[1..])
+        }
+        GlobToken::Star => {
+            for ix in 0..path.len() + 1 {
+                if glob_matches(rest, &path[ix..]) {
+                    return true;
+                }
+                if ix < path.len() && path[ix] == '/' {
+                    break;
+                }
+            }
+            false
+        }
+        GlobToken::DoubleStar => {
+            // `**/` can also match no segments at all
+            if rest.first() == Some(&GlobToken::Literal('/')) && glob_matches(&rest[1..], path) {
+                return true;
+            }
+            (0..path.len() + 1).any(|ix| glob_matches(rest, &path[ix..]))
+        }
+    }
+}
+
+/* ----------------- Workspace scanner ----------------- */
+
+/// Walks the workspace roots, reporting the files matching the include patterns
+/// and none of the exclude patterns. Patterns are matched against the path relative to the root.
+/// If there are no include patterns, all files are included.
+#[derive(Debug, Clone, Default)]
+pub struct WorkspaceScanner {
+    pub roots: Vec<PathBuf>,
+    pub includes: Vec<GlobPattern>,
+    pub excludes: Vec<GlobPattern>,
+}
+
+impl WorkspaceScanner {
+
+    pub fn new() -> WorkspaceScanner {
+        WorkspaceScanner::default()
+    }
+
+    pub fn add_root(&mut self, root: PathBuf) -> &mut Self {
+        self.roots.push(root);
+        self
+    }
+
+    /// Add given root URI (such as the `rootUri` or a workspace folder). Must be a `file` URI.
+    pub fn add_root_uri(&mut self, root_uri: &Url) -> GResult<&mut Self> {
+        match root_uri.to_file_path() {
+            Ok(root) => Ok(self.add_root(root)),
+            Err(_) => Err(format!("Workspace root is not a file URI: {}", root_uri).into()),
+        }
+    }
+
+    pub fn include(&mut self, pattern: &str) -> GResult<&mut Self> {
+        self.includes.push(try!(GlobPattern::new(pattern)));
+        Ok(self)
+    }
+
+    pub fn exclude(&mut self, pattern: &str) -> GResult<&mut Self> {
+        self.excludes.push(try!(GlobPattern::new(pattern)));
+        Ok(self)
+    }
+
+    /// Whether given path, relative to a root, passes the include/exclude filters.
+    pub fn is_included(&self, relative_path: &str) -> bool {
+        (self.includes.is_empty() || self.includes.iter().any(|pattern| pattern.matches(relative_path)))
+            && !self.excludes.iter().any(|pattern| pattern.matches(relative_path))
+    }
+
+    /// Whether given directory, relative to a root, is excluded, and so does not need to be walked.
+    fn is_dir_excluded(&self, relative_path: &str) -> bool {
+        self.excludes.iter().any(|pattern| pattern.matches(relative_path))
+    }
+
+    /// Walk the roots, calling `on_file` with the path of each file that passes the filters.
+    pub fn scan<FN>(&self, mut on_file: FN) -> GResult<()>
+    where
+        FN : FnMut(&Path),
+    {
+        for root in &self.roots {
+            try!(self.scan_dir(root, "", &mut on_file));
+        }
+        Ok(())
+    }
+
+    /// Walk the roots, collecting the paths of the files that pass the filters.
+    pub fn scan_to_vec(&self) -> GResult<Vec<PathBuf>> {
+        let mut files = vec![];
+        try!(self.scan(|path| files.push(path.to_path_buf())));
+        Ok(files)
+    }
+
+    fn scan_dir<FN>(&self, dir: &Path, relative_dir: &str, on_file: &mut FN) -> GResult<()>
+    where
+        FN : FnMut(&Path),
+    {
+        let mut entries = vec![];
+        for entry in try!(fs::read_dir(dir)) {
+            entries.push(try!(entry));
+        }
+        entries.sort_by_key(|entry| entry.file_name());
+
+        for entry in entries {
+            let file_name = entry.file_name();
+            let file_name = match file_name.to_str() {
+                Some(file_name) => file_name,
+                None => {
+                    warn!("Skipping non-UTF-8 path: {:?}", entry.path());
+                    continue;
+                }
+            };
+            let relative_path = if relative_dir.is_empty() {
+                file_name.to_string()
+            } else {
+                format!("{}/{}", relative_dir, file_name)
+            };
+
+            let file_type = try!(entry.file_type());
+            if file_type.is_dir() {
+                if !self.is_dir_excluded(&relative_path) {
+                    try!(self.scan_dir(&entry.path(), &relative_path, on_file));
+                }
+            } else if self.is_included(&relative_path) {
+                on_file(&entry.path());
+            }
+        }
+        Ok(())
+    }
+
+}
+
+
+#[test]
+fn glob_pattern__test() {
+    let matches = |pattern: &str, path: &str| GlobPattern::new(pattern).unwrap().matches(path);
+
+    assert!(matches("*.rs", "lib.rs"));
+    assert!(!matches("*.rs", "src/lib.rs"));
+    assert!(matches("**/*.rs", "lib.rs"));
+    assert!(matches("**/*.rs", "src/a/lib.rs"));
+    assert!(matches("src/**", "src/a/lib.rs"));
+    assert!(!matches("src/**", "tests/lib.rs"));
+    assert!(matches("**/*.{rs,toml}", "Cargo.toml"));
+    assert!(!matches("**/*.{rs,toml}", "README.md"));
+    assert!(matches("file?.txt", "file1.txt"));
+    assert!(!matches("file?.txt", "file/.txt"));
+    assert!(matches("[a-c]x", "bx"));
+    assert!(!matches("[!a-c]x", "bx"));
+    assert!(matches("{target,**/node_modules}", "a/node_modules"));
+    assert!(GlobPattern::new("{a,b").is_err());
+    assert!(GlobPattern::new("[ab").is_err());
+}
+
+#[test]
+fn workspace_scanner__test() {
+    use std::env;
+    use std::fs::File;
+
+    let root = env::temp_dir().join(format!("rust_lsp_workspace_scanner__test_{}", ::std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(root.join("src/nested")).unwrap();
+    fs::create_dir_all(root.join("target")).unwrap();
+    for file in &["Cargo.toml", "src/lib.rs", "src/nested/mod.rs", "src/notes.txt", "target/gen.rs"] {
+        File::create(root.join(file)).unwrap();
+    }
+
+    let mut scanner = WorkspaceScanner::new();
+    scanner.add_root(root.clone());
+    scanner.include("**/*.rs").unwrap().exclude("target").unwrap();
+    let files = scanner.scan_to_vec().unwrap();
+    assert_eq!(files, vec![root.join("src/lib.rs"), root.join("src/nested/mod.rs")]);
+
+    fs::remove_dir_all(&root).unwrap();
+}
@@ -0,0 +1,581 @@
+// Copyright 2016 Bruno Medeiros
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>.
+// This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Workspace edits, including the `documentChanges` form not in `ls_types::WorkspaceEdit`,
+//! with file operations and change annotations, and a builder that picks the form the client supports.
+
+use std::collections::HashMap;
+
+use serde;
+use serde::de::Error as DeError;
+use serde_json;
+use serde_json::Value;
+
+use util::core::*;
+
+use jsonrpc::json_util::JsonObject;
+use ls_types::*;
+use url::Url;
+
+use lsp_capabilities::LSClientCapabilities;
+
+/* ----------------- Document changes ----------------- */
+
+/// A file operation of a workspace edit.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ResourceOperation {
+    Create { uri: Url, overwrite: bool, ignore_if_exists: bool },
+    Rename { old_uri: Url, new_uri: Url, overwrite: bool, ignore_if_exists: bool },
+    Delete { uri: Url, recursive: bool, ignore_if_not_exists: bool },
+}
+
+impl ResourceOperation {
+
+    /// The `kind` of the operation, as named in the `resourceOperations` client capability.
+    pub fn kind(&self) -> &'static str {
+        match *self {
+            ResourceOperation::Create { .. } => "create",
+            ResourceOperation::Rename { .. } => "rename",
+            ResourceOperation::Delete { .. } => "delete",
+        }
+    }
+
+}
+
+/// An element of `documentChanges`: the edits to a document, or a file operation.
+/// Each can have the id of a change annotation of the workspace edit.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DocumentChange {
+    /// Edits to the document with given URI. If the version is given,
+    /// the client must not apply them to any other version.
+    Edit { uri: Url, version: Option<u64>, edits: Vec<TextEdit>, annotation_id: Option<String> },
+    Operation { operation: ResourceOperation, annotation_id: Option<String> },
+}
+
+impl DocumentChange {
+
+    pub fn annotation_id(&self) -> Option<&str> {
+        match *self {
+            DocumentChange::Edit { ref annotation_id, .. } => annotation_id.as_ref().map(String::as_str),
+            DocumentChange::Operation { ref annotation_id, .. } => annotation_id.as_ref().map(String::as_str),
+        }
+    }
+
+    fn without_annotation(self) -> DocumentChange {
+        match self {
+            DocumentChange::Edit { uri, version, edits, .. } => {
+                DocumentChange::Edit { uri : uri, version : version, edits : edits, annotation_id : None }
+            }
+            DocumentChange::Operation { operation, .. } => {
+                DocumentChange::Operation { operation : operation, annotation_id : None }
+            }
+        }
+    }
+
+}
+
+/// A description of changes of a workspace edit, which clients can show and group them by,
+/// or ask the user to confirm.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChangeAnnotation {
+    pub label: String,
+    /// Whether the user must confirm the changes before they are applied.
+    pub needs_confirmation: bool,
+    /// Shown less prominently than the label.
+    pub description: Option<String>,
+}
+
+impl ChangeAnnotation {
+
+    pub fn new(label: &str) -> ChangeAnnotation {
+        ChangeAnnotation { label : label.to_string(), needs_confirmation : false, description : None }
+    }
+
+    pub fn to_json(&self) -> Value {
+        let mut obj = JsonObject::new();
+        obj.insert("label".to_string(), Value::String(self.label.clone()));
+        if self.needs_confirmation {
+            obj.insert("needsConfirmation".to_string(), Value::Bool(true));
+        }
+        if let Some(ref description) = self.description {
+            obj.insert("description".to_string(), Value::String(description.clone()));
+        }
+        Value::Object(obj)
+    }
+
+    pub fn from_json(value: Value) -> Result<ChangeAnnotation, String> {
+        let mut obj = match value {
+            Value::Object(obj) => obj,
+            _ => return Err("ChangeAnnotation is not an object.".to_string()),
+        };
+        let label = match obj.remove("label") {
+            Some(Value::String(label)) => label,
+            _ => return Err("ChangeAnnotation: missing `label`.".to_string()),
+        };
+        let needs_confirmation = obj.get("needsConfirmation").and_then(Value::as_bool).unwrap_or(false);
+        let description = obj.get("description").and_then(Value::as_str).map(String::from);
+        Ok(ChangeAnnotation { label : label, needs_confirmation : needs_confirmation, description : description })
+    }
+
+}
+
+/* ----------------- LSWorkspaceEdit ----------------- */
+
+/// A workspace edit, in one of the two forms of the protocol.
+/// Only clients declaring `workspace.workspaceEdit.documentChanges` support the `DocumentChanges` form,
+/// and only those declaring `workspace.workspaceEdit.changeAnnotationSupport` support its annotations.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LSWorkspaceEdit {
+    Changes(HashMap<Url, Vec<TextEdit>>),
+    DocumentChanges {
+        document_changes: Vec<DocumentChange>,
+        /// The annotations of the document changes, by id.
+        change_annotations: HashMap<String, ChangeAnnotation>,
+    },
+}
+
+fn uri_from_json(value: Option<Value>, what: &str) -> Result<Url, String> {
+    match value {
+        Some(Value::String(uri)) => Url::parse(&uri).map_err(|err| err.to_string()),
+        _ => Err(format!("{}: missing or invalid uri.", what)),
+    }
+}
+
+fn flag_from_json(options: Option<&Value>, name: &str) -> bool {
+    options.and_then(|options| options.find(name)).and_then(Value::as_bool).unwrap_or(false)
+}
+
+fn edits_from_json(value: Option<Value>) -> Result<Vec<TextEdit>, String> {
+    match value {
+        Some(edits) => serde_json::from_value(edits).map_err(|err| err.to_string()),
+        None => Err("Missing `edits`.".to_string()),
+    }
+}
+
+fn annotation_id_from_json(obj: &JsonObject) -> Option<String> {
+    obj.get("annotationId").and_then(Value::as_str).map(String::from)
+}
+
+/// Parse the edits of a `TextDocumentEdit`, grouped by consecutive annotation id.
+/// (An `AnnotatedTextEdit` is a `TextEdit` with an `annotationId`)
+fn annotated_edits_from_json(value: Option<Value>) -> Result<Vec<(Option<String>, Vec<TextEdit>)>, String> {
+    let edits = match value {
+        Some(Value::Array(edits)) => edits,
+        Some(_) => return Err("TextDocumentEdit: `edits` is not an array.".to_string()),
+        None => return Err("Missing `edits`.".to_string()),
+    };
+    let mut groups : Vec<(Option<String>, Vec<TextEdit>)> = vec![];
+    for edit in edits {
+        let annotation_id = match edit {
+            Value::Object(ref edit) => annotation_id_from_json(edit),
+            _ => None,
+        };
+        let edit = try!(serde_json::from_value(edit).map_err(|err| err.to_string()));
+        if let Some(&mut (ref last_annotation_id, ref mut last_edits)) = groups.last_mut() {
+            if *last_annotation_id == annotation_id {
+                last_edits.push(edit);
+                continue;
+            }
+        }
+        groups.push((annotation_id, vec![edit]));
+    }
+    if groups.is_empty() {
+        groups.push((None, vec![]));
+    }
+    Ok(groups)
+}
+
+impl DocumentChange {
+
+    pub fn to_json(&self) -> Value {
+        let mut obj = JsonObject::new();
+        let mut options = JsonObject::new();
+        let operation = match *self {
+            DocumentChange::Edit { ref uri, version, ref edits, ref annotation_id } => {
+                let mut text_document = JsonObject::new();
+                text_document.insert("uri".to_string(), Value::String(uri.as_str().to_string()));
+                text_document.insert("version".to_string(), match version {
+                    Some(version) => Value::U64(version),
+                    None => Value::Null,
+                });
+                obj.insert("textDocument".to_string(), Value::Object(text_document));
+                let edits = edits.iter().map(|edit| {
+                    let mut edit = serde_json::to_value(edit);
+                    if let (&Some(ref annotation_id), &mut Value::Object(ref mut obj)) = (annotation_id, &mut edit) {
+                        obj.insert("annotationId".to_string(), Value::String(annotation_id.clone()));
+                    }
+                    edit
+                }).collect();
+                obj.insert("edits".to_string(), Value::Array(edits));
+                return Value::Object(obj);
+            }
+            DocumentChange::Operation { ref operation, .. } => operation,
+        };
+        match *operation {
+            ResourceOperation::Create { ref uri, overwrite, ignore_if_exists } => {
+                obj.insert("uri".to_string(), Value::String(uri.as_str().to_string()));
+                options.insert("overwrite".to_string(), Value::Bool(overwrite));
+                options.insert("ignoreIfExists".to_string(), Value::Bool(ignore_if_exists));
+            }
+            ResourceOperation::Rename { ref old_uri, ref new_uri, overwrite, ignore_if_exists } => {
+                obj.insert("oldUri".to_string(), Value::String(old_uri.as_str().to_string()));
+                obj.insert("newUri".to_string(), Value::String(new_uri.as_str().to_string()));
+                options.insert("overwrite".to_string(), Value::Bool(overwrite));
+                options.insert("ignoreIfExists".to_string(), Value::Bool(ignore_if_exists));
+            }
+            ResourceOperation::Delete { ref uri, recursive, ignore_if_not_exists } => {
+                obj.insert("uri".to_string(), Value::String(uri.as_str().to_string()));
+                options.insert("recursive".to_string(), Value::Bool(recursive));
+                options.insert("ignoreIfNotExists".to_string(), Value::Bool(ignore_if_not_exists));
+            }
+        }
+        obj.insert("kind".to_string(), Value::String(operation.kind().to_string()));
+        obj.insert("options".to_string(), Value::Object(options));
+        if let Some(annotation_id) = self.annotation_id() {
+            obj.insert("annotationId".to_string(), Value::String(annotation_id.to_string()));
+        }
+        Value::Object(obj)
+    }
+
+    /// Parse an element of `documentChanges`. A `TextDocumentEdit` whose edits have
+    /// different annotations results in an `Edit` for each run of edits with the same annotation.
+    pub fn from_json(value: Value) -> Result<Vec<DocumentChange>, String> {
+        let mut obj = match value {
+            Value::Object(obj) => obj,
+            _ => return Err("Document change is not an object.".to_string()),
+        };
+        let annotation_id = annotation_id_from_json(&obj);
+
+        let kind = match obj.remove("kind") {
+            Some(Value::String(kind)) => kind,
+            Some(_) => return Err("Document change: `kind` is not a string.".to_string()),
+            None => {
+                let mut text_document = match obj.remove("textDocument") {
+                    Some(Value::Object(text_document)) => text_document,
+                    _ => return Err("TextDocumentEdit: missing `textDocument`.".to_string()),
+                };
+                let uri = try!(uri_from_json(text_document.remove("uri"), "TextDocumentEdit"));
+                let version = text_document.get("version").and_then(Value::as_u64);
+                let groups = try!(annotated_edits_from_json(obj.remove("edits")));
+                return Ok(groups.into_iter().map(|(annotation_id, edits)| {
+                    DocumentChange::Edit { uri : uri.clone(), version : version, edits : edits, annotation_id : annotation_id }
+                }).collect());
+            }
+        };
+        let options = obj.remove("options");
+        let options = options.as_ref();
+        let operation = match kind.as_ref() {
+            "create" => ResourceOperation::Create {
+                uri : try!(uri_from_json(obj.remove("uri"), "CreateFile")),
+                overwrite : flag_from_json(options, "overwrite"),
+                ignore_if_exists : flag_from_json(options, "ignoreIfExists"),
+            },
+            "rename" => ResourceOperation::Rename {
+                old_uri : try!(uri_from_json(obj.remove("oldUri"), "RenameFile")),
+                new_uri : try!(uri_from_json(obj.remove("newUri"), "RenameFile")),
+                overwrite : flag_from_json(options, "overwrite"),
+                ignore_if_exists : flag_from_json(options, "ignoreIfExists"),
+            },
+            "delete" => ResourceOperation::Delete {
+                uri : try!(uri_from_json(obj.remove("uri"), "DeleteFile")),
+                recursive : flag_from_json(options, "recursive"),
+                ignore_if_not_exists : flag_from_json(options, "ignoreIfNotExists"),
+            },
+            _ => return Err(format!("Unknown resource operation kind: {}", kind)),
+        };
+        Ok(vec![DocumentChange::Operation { operation : operation, annotation_id : annotation_id }])
+    }
+
+}
+
+impl LSWorkspaceEdit {
+
+    pub fn to_json(&self) -> Value {
+        let mut obj = JsonObject::new();
+        match *self {
+            LSWorkspaceEdit::Changes(ref changes) => {
+                let mut changes_obj = JsonObject::new();
+                for (uri, edits) in changes {
+                    changes_obj.insert(uri.as_str().to_string(), serde_json::to_value(edits));
+                }
+                obj.insert("changes".to_string(), Value::Object(changes_obj));
+            }
+            LSWorkspaceEdit::DocumentChanges { ref document_changes, ref change_annotations } => {
+                let document_changes = document_changes.iter().map(DocumentChange::to_json).collect();
+                obj.insert("documentChanges".to_string(), Value::Array(document_changes));
+                if !change_annotations.is_empty() {
+                    let mut annotations_obj = JsonObject::new();
+                    for (id, annotation) in change_annotations {
+                        annotations_obj.insert(id.clone(), annotation.to_json());
+                    }
+                    obj.insert("changeAnnotations".to_string(), Value::Object(annotations_obj));
+                }
+            }
+        }
+        Value::Object(obj)
+    }
+
+    pub fn from_json(value: Value) -> Result<LSWorkspaceEdit, String> {
+        let mut obj = match value {
+            Value::Object(obj) => obj,
+            _ => return Err("WorkspaceEdit is not an object.".to_string()),
+        };
+
+        // As the spec says, `documentChanges` is preferred over `changes` if both are present
+        match obj.remove("documentChanges") {
+            Some(Value::Array(document_changes)) => {
+                let mut result = vec![];
+                for document_change in document_changes {
+                    result.extend(try!(DocumentChange::from_json(document_change)));
+                }
+                let mut change_annotations = HashMap::new();
+                match obj.remove("changeAnnotations") {
+                    Some(Value::Object(annotations_obj)) => {
+                        for (id, annotation) in annotations_obj {
+                            change_annotations.insert(id, try!(ChangeAnnotation::from_json(annotation)));
+                        }
+                    }
+                    Some(Value::Null) | None => {}
+                    Some(_) => return Err("WorkspaceEdit: `changeAnnotations` is not an object.".to_string()),
+                }
+                return Ok(LSWorkspaceEdit::DocumentChanges {
+                    document_changes : result, change_annotations : change_annotations
+                });
+            }
+            Some(Value::Null) | None => {}
+            Some(_) => return Err("WorkspaceEdit: `documentChanges` is not an array.".to_string()),
+        }
+        match obj.remove("changes") {
+            Some(Value::Object(changes_obj)) => {
+                let mut changes = HashMap::new();
+                for (uri, edits) in changes_obj {
+                    let uri = try!(Url::parse(&uri).map_err(|err| err.to_string()));
+                    changes.insert(uri, try!(edits_from_json(Some(edits))));
+                }
+                Ok(LSWorkspaceEdit::Changes(changes))
+            }
+            Some(Value::Null) | None => Ok(LSWorkspaceEdit::Changes(HashMap::new())),
+            Some(_) => Err("WorkspaceEdit: `changes` is not an object.".to_string()),
+        }
+    }
+
+}
+
+impl serde::Serialize for LSWorkspaceEdit {
+    fn serialize<S>(&self, serializer: &mut S) -> Result<(), S::Error>
+    where
+        S : serde::Serializer,
+    {
+        self.to_json().serialize(serializer)
+    }
+}
+
+impl serde::Deserialize for LSWorkspaceEdit {
+    fn deserialize<D>(deserializer: &mut D) -> Result<LSWorkspaceEdit, D::Error>
+    where
+        D : serde::Deserializer,
+    {
+        let value = try!(Value::deserialize(deserializer));
+        LSWorkspaceEdit::from_json(value).map_err(D::Error::custom)
+    }
+}
+
+/* ----------------- Builder ----------------- */
+
+/// Builder for `LSWorkspaceEdit`. Collects the changes in order, then produces
+/// the form of workspace edit that the client supports.
+#[derive(Debug, Clone, Default)]
+pub struct WorkspaceEditBuilder {
+    document_changes: Vec<DocumentChange>,
+    change_annotations: HashMap<String, ChangeAnnotation>,
+    /// The annotation of the changes being added.
+    annotation_id: Option<String>,
+}
+
+impl WorkspaceEditBuilder {
+
+    pub fn new() -> WorkspaceEditBuilder {
+        WorkspaceEditBuilder::default()
+    }
+
+    /// Define a change annotation, with given id.
+    pub fn change_annotation(mut self, id: &str, annotation: ChangeAnnotation) -> Self {
+        self.change_annotations.insert(id.to_string(), annotation);
+        self
+    }
+
+    /// Annotate the changes added after this with the change annotation of given id
+    /// (or no annotation, if `None`).
+    pub fn annotate(mut self, annotation_id: Option<&str>) -> Self {
+        self.annotation_id = annotation_id.map(String::from);
+        self
+    }
+
+    /// Add an edit to given document version. Consecutive edits to the same document version,
+    /// with the same annotation, are grouped in the same `TextDocumentEdit`.
+    pub fn edit(mut self, uri: &Url, version: Option<u64>, edit: TextEdit) -> Self {
+        if let Some(&mut DocumentChange::Edit {
+            uri: ref last_uri, version: last_version, ref mut edits, ref annotation_id
+        }) = self.document_changes.last_mut()
+        {
+            if last_uri == uri && last_version == version && *annotation_id == self.annotation_id {
+                edits.push(edit);
+                return self;
+            }
+        }
+        let annotation_id = self.annotation_id.clone();
+        self.document_changes.push(DocumentChange::Edit {
+            uri : uri.clone(), version : version, edits : vec![edit], annotation_id : annotation_id
+        });
+        self
+    }
+
+    pub fn edits(self, uri: &Url, version: Option<u64>, edits: Vec<TextEdit>) -> Self {
+        edits.into_iter().fold(self, |builder, edit| builder.edit(uri, version, edit))
+    }
+
+    fn operation(mut self, operation: ResourceOperation) -> Self {
+        let annotation_id = self.annotation_id.clone();
+        self.document_changes.push(DocumentChange::Operation { operation : operation, annotation_id : annotation_id });
+        self
+    }
+
+    pub fn create_file(self, uri: &Url, overwrite: bool, ignore_if_exists: bool) -> Self {
+        self.operation(ResourceOperation::Create {
+            uri : uri.clone(), overwrite : overwrite, ignore_if_exists : ignore_if_exists
+        })
+    }
+
+    pub fn rename_file(self, old_uri: &Url, new_uri: &Url, overwrite: bool, ignore_if_exists: bool) -> Self {
+        self.operation(ResourceOperation::Rename {
+            old_uri : old_uri.clone(), new_uri : new_uri.clone(),
+            overwrite : overwrite, ignore_if_exists : ignore_if_exists
+        })
+    }
+
+    pub fn delete_file(self, uri: &Url, recursive: bool, ignore_if_not_exists: bool) -> Self {
+        self.operation(ResourceOperation::Delete {
+            uri : uri.clone(), recursive : recursive, ignore_if_not_exists : ignore_if_not_exists
+        })
+    }
+
+    /// Build the workspace edit for a client with given capabilities (`None` if unknown).
+    /// Uses `documentChanges` if the client supports it, otherwise the `changes` map,
+    /// dropping the document versions. The change annotations are dropped if the client
+    /// does not support them.
+    /// Fails if there is a file operation that the client does not support,
+    /// or a change with an annotation id that was not defined.
+    pub fn build(self, client_capabilities: Option<&LSClientCapabilities>) -> GResult<LSWorkspaceEdit> {
+        let supports_document_changes = client_capabilities.map_or(false, |caps| caps.supports_document_changes());
+        let supports_annotations = client_capabilities.map_or(false, |caps| caps.supports_change_annotations());
+
+        for document_change in &self.document_changes {
+            if let DocumentChange::Operation { ref operation, .. } = *document_change {
+                let supported = client_capabilities.map_or(false, |caps| {
+                    caps.supports_resource_operation(operation.kind())
+                });
+                if !supports_document_changes || !supported {
+                    return Err(format!("Client does not support resource operation: {}", operation.kind()).into());
+                }
+            }
+            if let Some(annotation_id) = document_change.annotation_id() {
+                if !self.change_annotations.contains_key(annotation_id) {
+                    return Err(format!("Undefined change annotation: {}", annotation_id).into());
+                }
+            }
+        }
+
+        if supports_document_changes {
+            if supports_annotations {
+                return Ok(LSWorkspaceEdit::DocumentChanges {
+                    document_changes : self.document_changes, change_annotations : self.change_annotations
+                });
+            }
+            let document_changes = self.document_changes.into_iter().map(DocumentChange::without_annotation);
+            return Ok(LSWorkspaceEdit::DocumentChanges {
+                document_changes : document_changes.collect(), change_annotations : HashMap::new()
+            });
+        }
+
+        let mut changes = HashMap::new();
+        for document_change in self.document_changes {
+            if let DocumentChange::Edit { uri, edits, .. } = document_change {
+                changes.entry(uri).or_insert_with(Vec::new).extend(edits);
+            }
+        }
+        Ok(LSWorkspaceEdit::Changes(changes))
+    }
+
+}
+
+#[cfg(test)]
+fn test_edit(line: u64, new_text: &str) -> TextEdit {
+    let position = Position { line : line, character : 0 };
+    TextEdit { range : Range { start : position, end : position }, new_text : new_text.to_string() }
+}
+
+#[test]
+fn workspace_edit_builder__test() {
+    let uri_a = Url::parse("file:///a.rs").unwrap();
+    let uri_b = Url::parse("file:///b.rs").unwrap();
+    let builder = WorkspaceEditBuilder::new()
+        .edit(&uri_a, Some(1), test_edit(0, "x"))
+        .edit(&uri_a, Some(1), test_edit(1, "y"))
+        .edit(&uri_b, None, test_edit(0, "z"))
+        .edit(&uri_a, Some(1), test_edit(2, "w"));
+
+    // Client without documentChanges support
+    match builder.clone().build(None).unwrap() {
+        LSWorkspaceEdit::Changes(changes) => {
+            assert_eq!(changes[&uri_a].len(), 3);
+            assert_eq!(changes[&uri_b].len(), 1);
+        }
+        edit => panic!("Unexpected: {:?}", edit),
+    }
+    assert!(builder.clone().rename_file(&uri_a, &uri_b, false, false).build(None).is_err());
+
+    let json = r#"{ "workspace": { "workspaceEdit": {
+        "documentChanges": true, "resourceOperations": ["create", "rename"]
+    } } }"#;
+    let caps = LSClientCapabilities(serde_json::from_str(json).unwrap());
+    let edit = builder.clone().rename_file(&uri_a, &uri_b, false, true).build(Some(&caps)).unwrap();
+    match edit {
+        LSWorkspaceEdit::DocumentChanges { ref document_changes, .. } => assert_eq!(document_changes.len(), 4),
+        ref edit => panic!("Unexpected: {:?}", edit),
+    }
+    let json = edit.to_json();
+    assert_eq!(json.lookup("documentChanges.0.textDocument.version"), Some(&Value::U64(1)));
+    assert_eq!(json.lookup("documentChanges.1.textDocument.version"), Some(&Value::Null));
+    assert_eq!(json.lookup("documentChanges.3.kind"), Some(&Value::String("rename".to_string())));
+    assert_eq!(LSWorkspaceEdit::from_json(json).unwrap(), edit);
+
+    assert!(builder.clone().delete_file(&uri_b, false, false).build(Some(&caps)).is_err());
+
+    // Change annotations
+    let builder = builder
+        .change_annotation("move", ChangeAnnotation::new("Move a.rs"))
+        .annotate(Some("move"))
+        .edit(&uri_a, Some(1), test_edit(3, "v"))
+        .rename_file(&uri_a, &uri_b, false, false);
+    let edit = builder.clone().build(Some(&caps)).unwrap();
+    assert_eq!(edit.to_json().lookup("documentChanges.5.annotationId"), None);
+    assert_eq!(edit.to_json().lookup("documentChanges.4.edits.0.annotationId"), None);
+    assert_eq!(edit.to_json().find("changeAnnotations"), None);
+
+    let json = r#"{ "workspace": { "workspaceEdit": {
+        "documentChanges": true, "resourceOperations": ["rename"], "changeAnnotationSupport": {}
+    } } }"#;
+    let caps = LSClientCapabilities(serde_json::from_str(json).unwrap());
+    let edit = builder.clone().build(Some(&caps)).unwrap();
+    let json = edit.to_json();
+    assert_eq!(json.lookup("documentChanges.4.edits.0.annotationId"), Some(&Value::String("move".to_string())));
+    assert_eq!(json.lookup("documentChanges.5.annotationId"), Some(&Value::String("move".to_string())));
+    assert_eq!(json.lookup("changeAnnotations.move.label"), Some(&Value::String("Move a.rs".to_string())));
+    assert_eq!(LSWorkspaceEdit::from_json(json).unwrap(), edit);
+
+    assert!(builder.annotate(Some("other")).delete_file(&uri_b, false, false).build(Some(&caps)).is_err());
+}
@@ -2,7 +2,6 @@
 
 
 use lsp::*;
-use jsonrpc::method_types::MethodError;
 use jsonrpc::*;
 use ls_types::*;
 
@@ -34,7 +33,7 @@ pub fn test_run_lsp_server() {
     let client_handler = thread::spawn(|| {
         let mut input = io::BufReader::new(stream);
         let endpoint = ls_client.endpoint.clone();
-        LSPEndpoint::run_client_from_input(&mut input, endpoint, ls_client);
+        let _ = LSPEndpoint::run_client_from_input(&mut input, endpoint, ls_client);
     });
     
     let init_params = InitializeParams { 
@@ -76,57 +75,44 @@ fn tcp_server(listener: TcpListener) {
 fn handle_connection(stream: TcpStream) {
     let out_stream = stream.try_clone().expect("Failed to clone stream");
     let endpoint = LSPEndpoint::create_lsp_output_with_output_stream(|| { out_stream });
+    let context = LSServerContext::new(endpoint);
     
-    let ls = TestsLanguageServer { counter : 0, endpoint : endpoint.clone() };
+    let ls = TestsLanguageServer { counter : 0, context : context.clone() };
     
     let mut input = io::BufReader::new(stream);
-    LSPEndpoint::run_server_from_input(&mut input, endpoint, ls);
+    let exit_code = LSPEndpoint::run_server_from_input(&mut input, context, ls);
+    assert_eq!(exit_code, 0);
 }
 
 pub struct TestsLanguageServer {
     counter: u32,
-    endpoint: Endpoint,
-}
-
-impl TestsLanguageServer {
-    
-    pub fn error_not_available<DATA>(data : DATA) -> MethodError<DATA> {
-        let msg = "Functionality not implemented.".to_string();
-        MethodError::<DATA> { code : 1, message : msg, data : data }
-    }
-    
+    context: LSServerContext,
 }
 
 impl LanguageServerHandling for TestsLanguageServer {
     
-    fn initialize(&mut self, _: InitializeParams, completable: MethodCompletable<InitializeResult, InitializeError>) {
-        let capabilities = ServerCapabilities::default();
+    fn initialize(&mut self, _: InitializeParams, completable: LSMethodCompletable<methods::Initialize>) {
+        let encoding = self.context.client_capabilities().unwrap().position_encoding();
+        let mut result = InitializeResultBuilder::new()
+            .server_info("TestsLanguageServer", None)
+            .position_encoding(encoding)
+            .hover()
+            .build();
+        self.context.restrict_server_capabilities(&mut result.capabilities);
         assert_eq!(self.counter, 0);
+        assert!(self.context.client_capabilities().is_some());
         self.counter = 1;
-        completable.complete(Ok(InitializeResult { capabilities : capabilities }))
+        completable.complete(Ok(result))
     }
     fn shutdown(&mut self, _: (), completable: LSCompletable<()>) {
         completable.complete(Ok(()));
     }
     fn exit(&mut self, _: ()) {
-        self.endpoint.request_shutdown();
+        assert!(self.context.is_shutdown_received());
     }
     
-    fn workspace_change_configuration(&mut self, _: DidChangeConfigurationParams) {}
-    fn did_open_text_document(&mut self, _: DidOpenTextDocumentParams) {}
-    fn did_change_text_document(&mut self, _: DidChangeTextDocumentParams) {}
-    fn did_close_text_document(&mut self, _: DidCloseTextDocumentParams) {}
-    fn did_save_text_document(&mut self, _: DidSaveTextDocumentParams) {}
-    fn did_change_watched_files(&mut self, _: DidChangeWatchedFilesParams) {}
-    
-    fn completion(&mut self, _: TextDocumentPositionParams, completable: LSCompletable<CompletionList>) {
-        completable.complete(Err(Self::error_not_available(())));
-    }
-    fn resolve_completion_item(&mut self, _: CompletionItem, completable: LSCompletable<CompletionItem>) {
-        completable.complete(Err(Self::error_not_available(())));
-    }
-    fn hover(&mut self, _: TextDocumentPositionParams, completable: LSCompletable<Hover>) {
-        let mut endpoint = self.endpoint.clone();
+    fn hover(&mut self, _: TextDocumentPositionParams, completable: LSCompletable<LSHover>) {
+        let mut endpoint = self.context.endpoint.clone();
         thread::spawn(move || {
             client_rpc_handle(&mut endpoint).telemetry_event(Value::Null)
                 .unwrap();
@@ -134,54 +120,9 @@ impl LanguageServerHandling for TestsLanguageServer {
             let hover_str = "hover_text".to_string();
             let hover = Hover { contents: vec![MarkedString::String(hover_str)], range: None };
             
-            completable.complete(Ok(hover));
+            completable.complete(Ok(LSHover::from(hover)));
         });
     }
-    fn signature_help(&mut self, _: TextDocumentPositionParams, completable: LSCompletable<SignatureHelp>) {
-        completable.complete(Err(Self::error_not_available(())));
-    }
-    fn goto_definition(&mut self, _: TextDocumentPositionParams, completable: LSCompletable<Vec<Location>>) {
-        completable.complete(Err(Self::error_not_available(())));
-    }
-    fn references(&mut self, _: ReferenceParams, completable: LSCompletable<Vec<Location>>) {
-        completable.complete(Err(Self::error_not_available(())));
-    }
-    fn document_highlight(&mut self, _: TextDocumentPositionParams, completable: LSCompletable<Vec<DocumentHighlight>>) {
-        completable.complete(Err(Self::error_not_available(())));
-    }
-    fn document_symbols(&mut self, _: DocumentSymbolParams, completable: LSCompletable<Vec<SymbolInformation>>) {
-        completable.complete(Err(Self::error_not_available(())));
-    }
-    fn workspace_symbols(&mut self, _: WorkspaceSymbolParams, completable: LSCompletable<Vec<SymbolInformation>>) {
-        completable.complete(Err(Self::error_not_available(())));
-    }
-    fn code_action(&mut self, _: CodeActionParams, completable: LSCompletable<Vec<Command>>) {
-        completable.complete(Err(Self::error_not_available(())));
-    }
-    fn code_lens(&mut self, _: CodeLensParams, completable: LSCompletable<Vec<CodeLens>>) {
-        completable.complete(Err(Self::error_not_available(())));
-    }
-    fn code_lens_resolve(&mut self, _: CodeLens, completable: LSCompletable<CodeLens>) {
-        completable.complete(Err(Self::error_not_available(())));
-    }
-    fn document_link(&mut self, _params: DocumentLinkParams, completable: LSCompletable<Vec<DocumentLink>>) {
-        completable.complete(Err(Self::error_not_available(())));
-    }
-    fn document_link_resolve(&mut self, _params: DocumentLink, completable: LSCompletable<DocumentLink>) {
-        completable.complete(Err(Self::error_not_available(())));
-    }
-    fn formatting(&mut self, _: DocumentFormattingParams, completable: LSCompletable<Vec<TextEdit>>) {
-        completable.complete(Err(Self::error_not_available(())));
-    }
-    fn range_formatting(&mut self, _: DocumentRangeFormattingParams, completable: LSCompletable<Vec<TextEdit>>) {
-        completable.complete(Err(Self::error_not_available(())));
-    }
-    fn on_type_formatting(&mut self, _: DocumentOnTypeFormattingParams, completable: LSCompletable<Vec<TextEdit>>) {
-        completable.complete(Err(Self::error_not_available(())));
-    }
-    fn rename(&mut self, _: RenameParams, completable: LSCompletable<WorkspaceEdit>) {
-        completable.complete(Err(Self::error_not_available(())));
-    }
 }
 
 /* -----------------  ----------------- */
@@ -212,7 +153,7 @@ impl LanguageClientHandling for TestsLanguageClient {
         self.counter += 1;
     }
     
-    fn publish_diagnostics(&mut self, params: PublishDiagnosticsParams) {
+    fn publish_diagnostics(&mut self, params: LSPublishDiagnosticsParams) {
         
     }
     